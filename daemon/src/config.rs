@@ -12,6 +12,7 @@ use std::{
 
 use async_stream::stream;
 use futures::stream::BoxStream;
+use nonempty::NonEmpty;
 use tokio::sync::watch;
 
 use librad::{crypto::BoxedSigner, git::replication, net, net::discovery, paths, PeerId, Signer};
@@ -59,13 +60,19 @@ where
         signer,
         protocol: net::protocol::Config {
             paths,
-            listen_addr,
+            listen_addrs: NonEmpty::new(listen_addr),
             advertised_addrs: None,
+            dialer: Default::default(),
             membership: net::protocol::membership::Params::default(),
             network: net::Network::default(),
             replication: replication::Config::default(),
             fetch: net::protocol::config::Fetch::default(),
+            graft: net::protocol::config::Graft::default(),
+            serve: net::protocol::config::Serve::default(),
             rate_limits: net::protocol::Quota::default(),
+            pinned: Default::default(),
+            transport: Default::default(),
+            tofu: None,
         },
         storage: net::peer::config::Storage::default(),
     }
@@ -420,6 +420,7 @@ mod test {
                 membership_active: 1,
                 membership_passive: 1,
                 caches: downstream::CacheStats::default(),
+                rtt: HashMap::new(),
             })))
         };
         assert!(cmds.is_empty());
@@ -184,24 +184,29 @@ where
 {
     let config = config
         .into()
-        .unwrap_or_else(|| peer.protocol_config().replication);
+        .unwrap_or_else(|| peer.protocol_config().replication.clone());
     let owner = default_owner(peer).await?.ok_or(Error::MissingOwner)?;
-    Ok(peer
-        .using_storage(move |store| {
-            // FIXME(finto): we could configure retry logic
-            let fetcher =
-                fetcher::PeerToPeer::new(urn, remote_peer, addr_hints).build_fetcher(store)?;
-            match fetcher {
-                Ok(fetcher) => {
-                    replication::replicate(store, fetcher, config, Some(owner)).map_err(Error::from)
-                },
-                Err(info) => Err(Error::FetchLocked {
-                    urn: info.urn,
-                    remote_peer: info.remote_peer,
-                }),
-            }
-        })
-        .await??)
+    let addr_hints = addr_hints.into_iter().collect::<Vec<_>>();
+    replication::retry::retrying(config.retry, || {
+        let (urn, addr_hints, owner) = (urn.clone(), addr_hints.clone(), owner.clone());
+        let config = config.clone();
+        async move {
+            peer.using_storage(move |store| {
+                let fetcher =
+                    fetcher::PeerToPeer::new(urn, remote_peer, addr_hints).build_fetcher(store)?;
+                match fetcher {
+                    Ok(fetcher) => replication::replicate(store, fetcher, config, Some(owner))
+                        .map_err(Error::from),
+                    Err(info) => Err(Error::FetchLocked {
+                        urn: info.urn,
+                        remote_peer: info.remote_peer,
+                    }),
+                }
+            })
+            .await?
+        }
+    })
+    .await
 }
 
 /// Get the project found at `urn`.
@@ -337,23 +342,28 @@ where
 {
     let config = config
         .into()
-        .unwrap_or_else(|| peer.protocol_config().replication);
-    peer.using_storage(move |store| {
-        // FIXME(finto): we could configure retry logic
-        let fetcher =
-            fetcher::PeerToPeer::new(urn, remote_peer, addr_hints).build_fetcher(store)?;
-        match fetcher {
-            Ok(fetcher) => {
-                replication::replicate(store, fetcher, config, None).map_err(Error::from)
-            },
-            Err(info) => Err(Error::FetchLocked {
-                urn: info.urn,
-                remote_peer: info.remote_peer,
-            }),
+        .unwrap_or_else(|| peer.protocol_config().replication.clone());
+    let addr_hints = addr_hints.into_iter().collect::<Vec<_>>();
+    replication::retry::retrying(config.retry, || {
+        let (urn, addr_hints, config) = (urn.clone(), addr_hints.clone(), config.clone());
+        async move {
+            peer.using_storage(move |store| {
+                let fetcher =
+                    fetcher::PeerToPeer::new(urn, remote_peer, addr_hints).build_fetcher(store)?;
+                match fetcher {
+                    Ok(fetcher) => {
+                        replication::replicate(store, fetcher, config, None).map_err(Error::from)
+                    },
+                    Err(info) => Err(Error::FetchLocked {
+                        urn: info.urn,
+                        remote_peer: info.remote_peer,
+                    }),
+                }
+            })
+            .await?
         }
     })
-    .await?
-    .map_err(Error::from)
+    .await
 }
 
 /// Get the user found at `urn`.
@@ -410,24 +420,28 @@ where
 {
     let config = config
         .into()
-        .unwrap_or_else(|| peer.protocol_config().replication);
-
-    Ok(peer
-        .using_storage(move |store| -> Result<_, Error> {
-            // FIXME(finto): we could configure retry logic
-            let fetcher =
-                fetcher::PeerToPeer::new(urn, remote_peer, addr_hints).build_fetcher(store)?;
-            match fetcher {
-                Ok(fetcher) => {
-                    replication::replicate(store, fetcher, config, None).map_err(Error::from)
-                },
-                Err(info) => Err(Error::FetchLocked {
-                    urn: info.urn,
-                    remote_peer: info.remote_peer,
-                }),
-            }
-        })
-        .await??)
+        .unwrap_or_else(|| peer.protocol_config().replication.clone());
+    let addr_hints = addr_hints.into_iter().collect::<Vec<_>>();
+    replication::retry::retrying(config.retry, || {
+        let (urn, addr_hints, config) = (urn.clone(), addr_hints.clone(), config.clone());
+        async move {
+            peer.using_storage(move |store| -> Result<_, Error> {
+                let fetcher =
+                    fetcher::PeerToPeer::new(urn, remote_peer, addr_hints).build_fetcher(store)?;
+                match fetcher {
+                    Ok(fetcher) => {
+                        replication::replicate(store, fetcher, config, None).map_err(Error::from)
+                    },
+                    Err(info) => Err(Error::FetchLocked {
+                        urn: info.urn,
+                        remote_peer: info.remote_peer,
+                    }),
+                }
+            })
+            .await?
+        }
+    })
+    .await
 }
 
 /// Initialize a [`Project`] that is owned by the `owner`.
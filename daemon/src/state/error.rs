@@ -7,6 +7,7 @@
 
 use librad::{
     git::{
+        replication::retry,
         types::{One, Reference},
         Urn,
     },
@@ -137,6 +138,18 @@ impl From<Infallible> for Error {
     }
 }
 
+impl retry::Classify for Error {
+    fn category(&self) -> retry::Category {
+        match self {
+            Self::Replication(e) => e.category(),
+            // Another fetch of the same urn is in progress, which will
+            // eventually release the lock.
+            Self::FetchLocked { .. } => retry::Category::Transient,
+            _ => retry::Category::Permanent,
+        }
+    }
+}
+
 impl From<librad::git::identities::Error> for Error {
     fn from(err: librad::git::identities::Error) -> Self {
         Self::Identities(Box::new(err))
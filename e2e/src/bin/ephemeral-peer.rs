@@ -32,6 +32,7 @@ use librad::{
     PeerId,
     SecretKey,
 };
+use nonempty::NonEmpty;
 use radicle_link_e2e::logging;
 use tempfile::tempdir;
 use tokio::task::JoinError;
@@ -106,13 +107,21 @@ async fn main() {
             signer: key,
             protocol: protocol::Config {
                 paths,
-                listen_addr: opts.listen.unwrap_or_else(|| "0.0.0.0:0".parse().unwrap()),
+                listen_addrs: NonEmpty::new(
+                    opts.listen.unwrap_or_else(|| "0.0.0.0:0".parse().unwrap()),
+                ),
                 advertised_addrs: None,
+                dialer: Default::default(),
                 membership: Default::default(),
                 network: opts.network,
                 replication: Default::default(),
                 fetch: Default::default(),
+                graft: Default::default(),
+                serve: Default::default(),
                 rate_limits: Default::default(),
+                pinned: Default::default(),
+                transport: Default::default(),
+                tofu: None,
             },
             storage: Default::default(),
         })
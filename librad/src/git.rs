@@ -3,13 +3,21 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+pub mod bundle;
+pub mod checkout;
+pub mod cobs;
 pub mod fetch;
+pub mod grep;
 pub mod identities;
+pub mod import;
+pub mod inbox;
 pub mod include;
+pub mod large;
 pub mod local;
 pub mod p2p;
 pub mod refs;
 pub mod replication;
+pub mod restore;
 
 pub mod storage;
 pub use storage::Storage;
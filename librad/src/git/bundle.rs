@@ -0,0 +1,397 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Exchanging the refs of a namespace offline, as git bundles.
+//!
+//! [`create`] writes the signed refs of the local peer for a [`Urn`] to a
+//! bundle, which can be carried to a peer without network access. The refs
+//! are named as the receiving peer stores them, ie.
+//! `refs/namespaces/<urn>/refs/remotes/<local peer>/...`, so the bundle
+//! describes which namespace and peer it belongs to. Given a previous
+//! `rad/signed_refs` commit, the bundle only contains the objects which were
+//! added since, and lists the tips signed at that point as prerequisites.
+//!
+//! [`apply`] verifies the signature of the signed refs contained in a
+//! bundle, and that the bundled refs are the ones which were signed, before
+//! updating the remote-tracking refs of the peer like [`restore::apply`].
+//! Only bundles of tracked peers are applied, and the `rad/signed_refs` of
+//! the peer must move forward.
+//!
+//! The identity of the namespace is not verified, replicating from the peer
+//! does that once the network is available again.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::{self, BufRead, Write},
+};
+
+use git_ext as ext;
+use thiserror::Error;
+
+use super::{
+    refs::{self, Refs},
+    restore,
+    storage::{ReadOnlyStorage as _, Storage},
+    tracking,
+    types::Namespace,
+    Urn,
+};
+use crate::PeerId;
+
+const SIGNATURE: &str = "# v2 git bundle";
+const SIGNED_REFS: &str = "rad/signed_refs";
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("no signed refs of the local peer found for {0}")]
+    NoSignedRefs(Urn),
+
+    #[error("{0} is not a rad/signed_refs commit of the local peer")]
+    UnknownSince(ext::Oid),
+
+    #[error("nothing changed since {0}")]
+    UpToDate(ext::Oid),
+
+    #[error("malformed bundle: {0}")]
+    Malformed(String),
+
+    #[error("the bundle was created by the local peer")]
+    LocalPeer,
+
+    #[error("{0} is not present in the storage")]
+    UnknownUrn(Urn),
+
+    #[error("{peer} is not tracked for {urn}")]
+    NotTracked { urn: Urn, peer: PeerId },
+
+    #[error("the bundle requires the objects {0:?}, which are missing from the storage")]
+    MissingPrerequisites(Vec<ext::Oid>),
+
+    #[error("the bundle contains {0}, which was not signed")]
+    Unsigned(String),
+
+    #[error("refusing to rewind the signed refs of {peer} from {old} to {new}")]
+    NonFastForward {
+        peer: PeerId,
+        old: ext::Oid,
+        new: ext::Oid,
+    },
+
+    #[error(transparent)]
+    Restore(#[from] restore::Error),
+
+    #[error(transparent)]
+    Refs(#[from] refs::stored::Error),
+
+    #[error(transparent)]
+    Signed(#[from] refs::signed::Error),
+
+    #[error(transparent)]
+    Track(#[from] tracking::Error),
+
+    #[error(transparent)]
+    Store(#[from] super::storage::Error),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+}
+
+/// The contents of a bundle written by [`create`].
+#[derive(Clone, Debug)]
+pub struct Created {
+    /// The `rad/signed_refs` commit of the local peer the bundle brings the
+    /// receiver to.
+    pub at: ext::Oid,
+    /// The refs contained in the bundle, and their targets.
+    pub refs: BTreeMap<String, ext::Oid>,
+    /// The commits the receiver must have in order to apply the bundle.
+    pub prerequisites: BTreeSet<ext::Oid>,
+    /// The number of objects in the pack of the bundle.
+    pub objects: usize,
+}
+
+fn remote_prefix(urn: &Urn, peer: PeerId) -> String {
+    format!(
+        "refs/namespaces/{}/refs/remotes/{}/",
+        Namespace::from(urn),
+        peer
+    )
+}
+
+/// The refs of `signed` at `at`, as stored by a peer tracking `peer`.
+fn signed_refs(urn: &Urn, peer: PeerId, signed: &Refs, at: ext::Oid) -> BTreeMap<String, ext::Oid> {
+    let prefix = remote_prefix(urn, peer);
+    let mut refs = signed
+        .iter_categorised()
+        .map(|((name, oid), category)| (format!("{}{}/{}", prefix, category, name), *oid))
+        .collect::<BTreeMap<_, _>>();
+    refs.insert(format!("{}{}", prefix, SIGNED_REFS), at);
+    refs
+}
+
+/// Write the signed refs of the local peer for `urn` as a bundle to `out`.
+///
+/// If `since` is given, it must be a previous `rad/signed_refs` commit of the
+/// local peer, and only objects not reachable from the tips signed at
+/// `since` are included.
+#[tracing::instrument(skip(storage, out), fields(urn = %urn))]
+pub fn create<W>(
+    storage: &Storage,
+    urn: &Urn,
+    since: Option<ext::Oid>,
+    mut out: W,
+) -> Result<Created, Error>
+where
+    W: Write,
+{
+    let urn = urn.clone().with_path(None);
+    let local = *storage.peer_id();
+    let at = refs::tips(storage, &urn)?
+        .remove(&local)
+        .ok_or_else(|| Error::NoSignedRefs(urn.clone()))?;
+    let signed =
+        Refs::load_at(storage, &urn, None, at)?.ok_or_else(|| Error::NoSignedRefs(urn.clone()))?;
+    let refs = signed_refs(&urn, local, &signed, at);
+
+    let repo = storage.as_raw();
+    let mut prerequisites = BTreeSet::new();
+    if let Some(since) = since {
+        if since == at {
+            return Err(Error::UpToDate(since));
+        }
+        let previous = match Refs::load_at(storage, &urn, None, since) {
+            Ok(Some(previous)) => previous,
+            Ok(None) | Err(refs::stored::Error::NotInHistory { .. }) => {
+                return Err(Error::UnknownSince(since))
+            },
+            Err(e) => return Err(e.into()),
+        };
+        prerequisites.insert(since);
+        for ((_, oid), _) in previous.iter_categorised() {
+            // Only commits can be prerequisites of a bundle
+            if let Ok(commit) = repo
+                .find_object((*oid).into(), None)
+                .and_then(|obj| obj.peel_to_commit())
+            {
+                prerequisites.insert(commit.id().into());
+            }
+        }
+    }
+
+    let mut walk = repo.revwalk()?;
+    let mut pack = repo.packbuilder()?;
+    for oid in refs.values() {
+        let obj = repo.find_object((*oid).into(), None)?;
+        match obj.kind() {
+            Some(git2::ObjectType::Commit) => walk.push(obj.id())?,
+            Some(git2::ObjectType::Tag) => {
+                pack.insert_object(obj.id(), None)?;
+                walk.push(obj.peel_to_commit()?.id())?;
+            },
+            _ => pack.insert_recursive(obj.id(), None)?,
+        }
+    }
+    for oid in &prerequisites {
+        walk.hide((*oid).into())?;
+    }
+    pack.insert_walk(&mut walk)?;
+
+    writeln!(out, "{}", SIGNATURE)?;
+    for oid in &prerequisites {
+        writeln!(out, "-{}", oid)?;
+    }
+    for (name, oid) in &refs {
+        writeln!(out, "{} {}", oid, name)?;
+    }
+    writeln!(out)?;
+    let mut written = Ok(());
+    let packed = pack.foreach(|buf| {
+        written = out.write_all(buf);
+        written.is_ok()
+    });
+    // Aborting the iteration is reported as an error, prefer the reason
+    written?;
+    packed?;
+    out.flush()?;
+
+    Ok(Created {
+        at,
+        refs,
+        prerequisites,
+        objects: pack.object_count(),
+    })
+}
+
+/// The header of a bundle.
+struct Header {
+    prerequisites: Vec<ext::Oid>,
+    refs: BTreeMap<String, ext::Oid>,
+}
+
+fn read_header<R>(bundle: &mut R) -> Result<Header, Error>
+where
+    R: BufRead,
+{
+    let malformed = |msg: &str| Error::Malformed(msg.to_owned());
+    let parse_oid = |hex: &str| {
+        hex.parse::<ext::Oid>()
+            .map_err(|_| Error::Malformed(format!("invalid object id `{}`", hex)))
+    };
+
+    let mut line = String::new();
+    bundle.read_line(&mut line)?;
+    if line.trim_end() != SIGNATURE {
+        return Err(malformed("not a v2 git bundle"));
+    }
+
+    let mut prerequisites = Vec::new();
+    let mut refs = BTreeMap::new();
+    loop {
+        line.clear();
+        if bundle.read_line(&mut line)? == 0 {
+            return Err(malformed("unexpected end of header"));
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        match line.strip_prefix('-') {
+            Some(prerequisite) => {
+                let hex = prerequisite.split(' ').next().unwrap_or_default();
+                prerequisites.push(parse_oid(hex)?);
+            },
+            None => {
+                let (hex, name) = line
+                    .split_once(' ')
+                    .ok_or_else(|| malformed("ref without a name"))?;
+                refs.insert(name.to_owned(), parse_oid(hex)?);
+            },
+        }
+    }
+
+    Ok(Header {
+        prerequisites,
+        refs,
+    })
+}
+
+/// The namespace and peer of the refs of a bundle.
+fn owner(refs: &BTreeMap<String, ext::Oid>) -> Result<(Urn, PeerId), Error> {
+    let parse = |name: &str| -> Option<(String, String)> {
+        let rest = name.strip_prefix("refs/namespaces/")?;
+        let (id, rest) = rest.split_once("/refs/remotes/")?;
+        let (peer, _) = rest.split_once('/')?;
+        Some((id.to_owned(), peer.to_owned()))
+    };
+
+    let owners = refs
+        .keys()
+        .map(|name| parse(name).ok_or_else(|| Error::Unsigned(name.clone())))
+        .collect::<Result<BTreeSet<_>, _>>()?;
+    let mut owners = owners.into_iter();
+    match (owners.next(), owners.next()) {
+        (Some((id, peer)), None) => {
+            let urn = Urn::try_from_id(&id)
+                .map_err(|_| Error::Malformed(format!("invalid namespace `{}`", id)))?;
+            let peer = peer
+                .parse()
+                .map_err(|_| Error::Malformed(format!("invalid peer id `{}`", peer)))?;
+            Ok((urn, peer))
+        },
+        (None, _) => Err(Error::Malformed("no refs".to_owned())),
+        (Some(_), Some(_)) => Err(Error::Malformed(
+            "refs of more than one namespace or peer".to_owned(),
+        )),
+    }
+}
+
+/// Verify the bundle read from `bundle`, and update the remote-tracking refs
+/// of the peer which created it to the signed refs it contains.
+///
+/// The objects of the bundle are added to the storage before the signed refs
+/// can be verified. If verification fails, they are left to be garbage
+/// collected.
+#[tracing::instrument(skip(storage, bundle))]
+pub fn apply<R>(storage: &Storage, mut bundle: R) -> Result<restore::Plan, Error>
+where
+    R: BufRead,
+{
+    let Header {
+        prerequisites,
+        refs,
+    } = read_header(&mut bundle)?;
+    let (urn, peer) = owner(&refs)?;
+    if &peer == storage.peer_id() {
+        return Err(Error::LocalPeer);
+    }
+    if !storage.has_urn(&urn)? {
+        return Err(Error::UnknownUrn(urn));
+    }
+    if !tracking::is_tracked(storage, &urn, peer)? {
+        return Err(Error::NotTracked { urn, peer });
+    }
+
+    let repo = storage.as_raw();
+    let odb = repo.odb()?;
+    let missing = prerequisites
+        .into_iter()
+        .filter(|oid| !odb.exists((*oid).into()))
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        return Err(Error::MissingPrerequisites(missing));
+    }
+
+    let mut writer = odb.packwriter()?;
+    io::copy(&mut bundle, &mut writer)?;
+    writer.commit()?;
+
+    let prefix = remote_prefix(&urn, peer);
+    let sigrefs = format!("{}{}", prefix, SIGNED_REFS);
+    let at = *refs
+        .get(&sigrefs)
+        .ok_or_else(|| Error::Malformed(format!("no {} in the bundle", SIGNED_REFS)))?;
+    let blob = repo
+        .find_commit(at.into())?
+        .tree()?
+        .get_path(std::path::Path::new(refs::stored::BLOB_PATH))?
+        .to_object(repo)?
+        .peel_to_blob()?;
+    let signed = Refs::from(refs::Signed::from_json(blob.content(), &peer)?);
+    let expected = signed_refs(&urn, peer, &signed, at);
+    if let Some((name, _)) = refs
+        .iter()
+        .find(|(name, oid)| expected.get(name.as_str()) != Some(oid))
+    {
+        return Err(Error::Unsigned(name.clone()));
+    }
+
+    match repo.refname_to_id(&sigrefs) {
+        Ok(old) if old != at.into() && !repo.graph_descendant_of(at.into(), old)? => {
+            return Err(Error::NonFastForward {
+                peer,
+                old: old.into(),
+                new: at,
+            })
+        },
+        Err(e) if e.code() != git2::ErrorCode::NotFound => return Err(e.into()),
+        _ => {},
+    }
+    // With the signed refs of the peer at `at`, updating the remaining refs
+    // is exactly restoring them to what was signed there.
+    repo.reference(
+        &sigrefs,
+        at.into(),
+        true,
+        &format!("bundle: signed refs at {}", at),
+    )?;
+    let plan = restore::plan(storage, &urn, peer, at)?;
+    restore::apply(storage, &plan, true)?;
+
+    Ok(plan)
+}
@@ -0,0 +1,365 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Management of working copies linked to a namespace of the monorepo.
+//!
+//! A working copy created by [`checkout`] is set up as follows:
+//!
+//! ```text
+//! [remote "rad"]
+//!     url = rad://<urn id>.git
+//!     fetch = +refs/heads/*:refs/remotes/rad/*
+//! [branch "<branch>"]
+//!     remote = rad
+//!     merge = refs/heads/<branch>
+//! [include]
+//!     path = <git includes dir>/<urn id>.inc
+//! ```
+//!
+//! The `rad` remote is the local peer's own view of the namespace: pushing to
+//! it updates (and signs) `refs/namespaces/<urn>/refs/heads/*`. The include
+//! file defines a remote `<handle>@<peer>` for every tracked peer which has
+//! been replicated, as described in [`include`].
+//!
+//! When checking out the view of another peer, its branch is published to the
+//! local peer's view, such that the working copy starts out as a fork.
+//!
+//! As replication brings in new peers and updates, [`refresh`] regenerates
+//! the include file and fetches all remotes of the namespace.
+
+use std::{convert::TryFrom, path::PathBuf};
+
+use git_ext::{self as ext, is_not_found_err};
+use std_ext::result::ResultExt as _;
+use thiserror::Error;
+
+use super::{
+    identities::{self, relations},
+    include::{self, Include},
+    local::{
+        transport::{self, CanOpenStorage},
+        url::LocalUrl,
+    },
+    storage::Storage,
+    types::{
+        remote::{self, LocalFetchspec, LocalPushspec, Remote},
+        Force,
+        Refspec,
+    },
+    Urn,
+};
+use crate::{paths::Paths, PeerId};
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("the project at `{0}` does not have a default branch set")]
+    MissingDefaultBranch(Urn),
+
+    #[error("branch `{branch}` not found on remote `{remote}`")]
+    MissingBranch {
+        remote: ext::RefLike,
+        branch: ext::OneLevel,
+    },
+
+    #[error("peer {0} is not tracked, or has not been replicated yet")]
+    NotReplicated(PeerId),
+
+    #[error("the repository at `{0}` has no `rad` remote")]
+    NotAWorkingCopy(PathBuf),
+
+    #[error("failed to open storage")]
+    OpenStorage(#[source] transport::OpenStorageError),
+
+    #[error(transparent)]
+    Identities(#[from] Box<identities::Error>),
+
+    #[error(transparent)]
+    Relations(#[from] Box<relations::Error>),
+
+    #[error(transparent)]
+    Include(#[from] include::Error),
+
+    #[error(transparent)]
+    Remote(#[from] remote::FindError),
+
+    #[error(transparent)]
+    Transport(#[from] transport::Error),
+
+    #[error(transparent)]
+    Ref(#[from] ext::name::Error),
+
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+}
+
+impl From<identities::Error> for Error {
+    fn from(e: identities::Error) -> Self {
+        Self::Identities(Box::new(e))
+    }
+}
+
+impl From<relations::Error> for Error {
+    fn from(e: relations::Error) -> Self {
+        Self::Relations(Box::new(e))
+    }
+}
+
+/// Whose view of the namespace to check out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum View {
+    /// The local peer's own view.
+    Local,
+    /// The view of a tracked peer.
+    Peer(PeerId),
+}
+
+impl Default for View {
+    fn default() -> Self {
+        Self::Local
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// The directory to create the working copy in. It must not be a git
+    /// repository already.
+    pub path: PathBuf,
+    /// Whose view to check out.
+    pub view: View,
+    /// The branch to check out. If `None`, the default branch of the project
+    /// is used.
+    pub branch: Option<ext::OneLevel>,
+}
+
+/// Create a working copy of the project `urn`, according to [`Options`].
+#[tracing::instrument(skip(paths, open_storage, options), fields(urn = %urn, view = ?options.view))]
+pub fn checkout<F>(
+    paths: &Paths,
+    open_storage: F,
+    urn: &Urn,
+    options: Options,
+) -> Result<git2::Repository, Error>
+where
+    F: CanOpenStorage + Clone + 'static,
+{
+    let urn = urn.with_path(None);
+    let Options { path, view, branch } = options;
+
+    let storage = open_storage.open_storage().map_err(Error::OpenStorage)?;
+    let storage: &Storage = (*storage).as_ref();
+
+    let branch = match branch {
+        Some(branch) => branch,
+        None => {
+            let project = identities::project::verify(storage, &urn)?
+                .ok_or_else(|| identities::Error::NotFound(urn.clone()))?;
+            let default_branch = project
+                .subject()
+                .default_branch
+                .as_ref()
+                .ok_or_else(|| Error::MissingDefaultBranch(urn.clone()))?;
+            ext::OneLevel::from(ext::RefLike::try_from(default_branch.as_str())?)
+        },
+    };
+    let view = match view {
+        View::Peer(peer) if peer == *storage.peer_id() => View::Local,
+        view => view,
+    };
+
+    let repo = {
+        let mut options = git2::RepositoryInitOptions::new();
+        options.no_reinit(true);
+        options.mkpath(true);
+        options.initial_head(branch.as_str());
+        git2::Repository::init_opts(&path, &options)?
+    };
+
+    let mut rad = Remote::rad_remote(
+        LocalUrl::from(urn.clone()),
+        Refspec {
+            src: refspec_pattern!("refs/heads/*"),
+            dst: refspec_pattern!("refs/remotes/rad/*"),
+            force: Force::True,
+        },
+    );
+    rad.save(&repo)?;
+
+    let tracked = update_include(paths, storage, &urn)?;
+    include::set_include_path(&repo, include_path(paths, &urn))?;
+
+    let mut upstream = match view {
+        View::Local => rad,
+        View::Peer(peer) => {
+            let handle = tracked
+                .into_iter()
+                .find_map(|(handle, tracked)| (tracked == peer).then(|| handle))
+                .ok_or(Error::NotReplicated(peer))?;
+            Remote::<LocalUrl>::find(&repo, remote_name(&handle, &peer))?
+                .ok_or(Error::NotReplicated(peer))?
+        },
+    };
+    upstream
+        .fetch(open_storage.clone(), &repo, LocalFetchspec::Configured)?
+        .for_each(drop);
+
+    let tip = {
+        let name = reflike!("refs/remotes")
+            .join(upstream.name.clone())
+            .join(branch.clone());
+        repo.refname_to_id(name.as_str())
+            .or_matches(is_not_found_err, || {
+                Err(Error::MissingBranch {
+                    remote: upstream.name.clone(),
+                    branch: branch.clone(),
+                })
+            })?
+    };
+    let local_branch = ext::Qualified::from(branch.clone());
+    repo.reference(
+        local_branch.as_str(),
+        tip,
+        false,
+        &format!("checkout from {}", upstream.name),
+    )?;
+
+    let rad = match view {
+        View::Local => upstream,
+        View::Peer(_) => {
+            let mut rad = Remote::<LocalUrl>::find(&repo, reflike!("rad"))?
+                .ok_or_else(|| Error::NotAWorkingCopy(path.clone()))?;
+            for pushed in rad.push(
+                open_storage.clone(),
+                &repo,
+                LocalPushspec::Matching {
+                    pattern: local_branch.clone().into(),
+                    force: Force::False,
+                },
+            )? {
+                tracing::debug!("published `{}` to the local view", pushed);
+            }
+            rad.fetch(open_storage, &repo, LocalFetchspec::Configured)?
+                .for_each(drop);
+            rad
+        },
+    };
+
+    set_upstream(&repo, &rad, &branch)?;
+    repo.set_head(local_branch.as_str())?;
+    repo.checkout_head(Some(git2::build::CheckoutBuilder::new().force()))?;
+
+    Ok(repo)
+}
+
+/// Regenerate the include file of the working copy `repo`, and fetch from all
+/// of its remotes pointing to the namespace.
+///
+/// This is intended to be called after replication, such that the working
+/// copy sees the most recent branches of all tracked peers.
+///
+/// The updated remote tracking branches are returned.
+#[tracing::instrument(skip(paths, open_storage, repo), fields(path = %repo.path().display()))]
+pub fn refresh<F>(
+    paths: &Paths,
+    open_storage: F,
+    repo: &git2::Repository,
+) -> Result<Vec<(ext::RefLike, git2::Oid)>, Error>
+where
+    F: CanOpenStorage + Clone + 'static,
+{
+    let urn = Remote::<LocalUrl>::find(repo, reflike!("rad"))?
+        .ok_or_else(|| Error::NotAWorkingCopy(repo.path().to_path_buf()))?
+        .url
+        .urn;
+
+    {
+        let storage = open_storage.open_storage().map_err(Error::OpenStorage)?;
+        update_include(paths, (*storage).as_ref(), &urn)?;
+    }
+    include::set_include_path(repo, include_path(paths, &urn))?;
+
+    let mut updated = Vec::new();
+    for name in repo.remotes()?.iter().flatten() {
+        let remote = match repo.find_remote(name) {
+            Ok(remote) => remote,
+            Err(e) if is_not_found_err(&e) => {
+                tracing::warn!(remote = %name, "remote was removed concurrently, skipping");
+                continue;
+            },
+            Err(e) => return Err(e.into()),
+        };
+        let url = remote.url().map(|url| url.parse::<LocalUrl>());
+        match url {
+            Some(Ok(url)) if url.urn == urn => {},
+            _ => {
+                tracing::trace!(remote = %name, "skipping foreign remote");
+                continue;
+            },
+        }
+
+        let mut remote = match Remote::<LocalUrl>::find(repo, ext::RefLike::try_from(name)?)? {
+            Some(remote) => remote,
+            None => {
+                tracing::warn!(remote = %name, "remote was removed concurrently, skipping");
+                continue;
+            },
+        };
+        updated.extend(remote.fetch(open_storage.clone(), repo, LocalFetchspec::Configured)?);
+    }
+
+    Ok(updated)
+}
+
+/// Write the include file for `urn`, returning the `(handle, peer)` pairs
+/// included.
+fn update_include(
+    paths: &Paths,
+    storage: &Storage,
+    urn: &Urn,
+) -> Result<Vec<(ext::RefLike, PeerId)>, Error> {
+    let tracked = relations::tracked(storage, urn)?
+        .into_iter()
+        .filter_map(|peer| {
+            peer.replicated_remote().map(|(peer, person)| {
+                ext::RefLike::try_from(person.subject().name.as_str()).map(|handle| (handle, peer))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let include = Include::from_tracked_persons(
+        paths.git_includes_dir().to_path_buf(),
+        LocalUrl::from(urn.clone()),
+        tracked.clone(),
+    );
+    include.save()?;
+
+    Ok(tracked)
+}
+
+fn include_path(paths: &Paths, urn: &Urn) -> PathBuf {
+    Include::new(
+        paths.git_includes_dir().to_path_buf(),
+        LocalUrl::from(urn.clone()),
+    )
+    .file_path()
+}
+
+/// The name of the remote of `peer` in the include file.
+fn remote_name(handle: &ext::RefLike, peer: &PeerId) -> ext::RefLike {
+    ext::RefLike::try_from(format!("{}@{}", handle, peer)).expect("handle and peer are reflike")
+}
+
+fn set_upstream(
+    repo: &git2::Repository,
+    remote: &Remote<LocalUrl>,
+    branch: &ext::OneLevel,
+) -> Result<(), git2::Error> {
+    let mut config = repo.config()?;
+    config.set_str(&format!("branch.{}.remote", branch), remote.name.as_str())?;
+    config.set_str(
+        &format!("branch.{}.merge", branch),
+        ext::Qualified::from(branch.clone()).as_str(),
+    )
+}
@@ -0,0 +1,39 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Collaborative objects, ie. the refs published under
+//! `refs/cobs/<typename>/<object id>`.
+//!
+//! The storage only knows about the ref layout: interpreting the histories
+//! pointed to by those refs is up to the application which registered the
+//! typename.
+
+use git_ext as ext;
+
+pub mod attachment;
+pub mod ci;
+pub mod gc;
+pub mod graph;
+pub mod handoff;
+pub mod patch;
+pub mod policy;
+
+/// `true` if `typename` is non-empty, and consists only of alphanumeric
+/// characters, `.`, `-` and `_`.
+///
+/// Typenames are conventionally given in reverse domain notation, eg.
+/// `xyz.radicle.issue`.
+pub fn is_valid_typename(typename: &str) -> bool {
+    !typename.is_empty()
+        && typename
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+}
+
+/// The typename of a [`crate::git::refs::Refs::cobs`] entry, ie. the first
+/// path component of `<typename>/<object id>`.
+pub fn typename_of(name: &ext::OneLevel) -> &str {
+    name.as_str().split('/').next().unwrap_or_default()
+}
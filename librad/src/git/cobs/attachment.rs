@@ -0,0 +1,179 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Binary attachments of collaborative objects.
+//!
+//! An attachment is stored as a blob, which is content-addressed by its id.
+//! Every peer which refers to an attachment in a change publishes it at
+//! `refs/cobs/xyz.radicle.attachment/<blob id>`. Since that ref is covered by
+//! the signed refs of the peer, replication fetches the attachment in the same
+//! fetch as the changes referring to it, regardless of which peer added it
+//! first.
+//!
+//! # Lazy fetching
+//!
+//! Fetching the attachments of a peer can be deferred by excluding
+//! [`TYPENAME`] using [`tracking::exclude_cobs`]. [`read`] returns `None` for
+//! attachments which have not been fetched, and [`missing`] tells which of the
+//! attachments of an object those are. After [`tracking::include_cobs`], the
+//! next replication fetches them.
+//!
+//! [`tracking::exclude_cobs`]: crate::git::tracking::exclude_cobs
+//! [`tracking::include_cobs`]: crate::git::tracking::include_cobs
+
+use std::convert::TryFrom;
+
+use git_ext::{self as ext, is_not_found_err};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::git::{
+    refs::{self, Refs},
+    storage::Storage,
+    types::{Namespace, Reference, RefsCategory},
+    Urn,
+};
+
+/// The typename under which attachments are published.
+pub const TYPENAME: &str = "xyz.radicle.attachment";
+
+/// The maximum size of an attachment, in bytes.
+pub const MAX_SIZE: usize = 8 * 1024 * 1024;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("attachment of {size} bytes exceeds the limit of {limit} bytes")]
+    TooLarge { size: usize, limit: usize },
+
+    #[error("attachment {oid} has {actual} bytes, but {expected} bytes were expected")]
+    SizeMismatch {
+        oid: ext::Oid,
+        expected: usize,
+        actual: usize,
+    },
+
+    #[error("attachment {0} not found in storage")]
+    NotFound(ext::Oid),
+
+    #[error(transparent)]
+    Refs(#[from] refs::stored::Error),
+
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+}
+
+/// A reference to a blob, as recorded in a change.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Attachment {
+    pub oid: ext::Oid,
+    /// The file name of the attachment, as given by the peer which added it.
+    pub name: String,
+    /// The size of the blob in bytes.
+    pub size: usize,
+}
+
+/// Store `data` as an attachment of the objects in the namespace `urn`.
+///
+/// The attachment is published right away, but is typically referred to by a
+/// change which is appended after.
+pub fn store(storage: &Storage, urn: &Urn, name: String, data: &[u8]) -> Result<Attachment, Error> {
+    if data.len() > MAX_SIZE {
+        return Err(Error::TooLarge {
+            size: data.len(),
+            limit: MAX_SIZE,
+        });
+    }
+
+    let oid = storage.as_raw().blob(data)?;
+    let attachment = Attachment {
+        oid: oid.into(),
+        name,
+        size: data.len(),
+    };
+    link(storage, urn, &attachment)?;
+    Refs::update(storage, urn)?;
+
+    Ok(attachment)
+}
+
+/// Read the contents of `attachment`.
+///
+/// `None` is returned if the attachment was not fetched (yet).
+pub fn read(storage: &Storage, attachment: &Attachment) -> Result<Option<Vec<u8>>, Error> {
+    match storage.as_raw().find_blob(*attachment.oid) {
+        Ok(blob) => {
+            verify(attachment, blob.size())?;
+            Ok(Some(blob.content().to_vec()))
+        },
+        Err(e) if is_not_found_err(&e) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The `attachments` which have not been fetched (yet).
+pub fn missing<'a, I>(storage: &Storage, attachments: I) -> Result<Vec<&'a Attachment>, Error>
+where
+    I: IntoIterator<Item = &'a Attachment>,
+{
+    let odb = storage.as_raw().odb()?;
+    Ok(attachments
+        .into_iter()
+        .filter(|attachment| !odb.exists(*attachment.oid))
+        .collect())
+}
+
+/// Publish `attachment` in the namespace `urn` of the local peer, without
+/// updating the signed refs.
+///
+/// The blob must be present in the storage, and match the size recorded in
+/// `attachment`.
+pub(crate) fn link(storage: &Storage, urn: &Urn, attachment: &Attachment) -> Result<(), Error> {
+    let repo = storage.as_raw();
+    let blob = repo.find_blob(*attachment.oid).map_err(|e| {
+        if is_not_found_err(&e) {
+            Error::NotFound(attachment.oid)
+        } else {
+            e.into()
+        }
+    })?;
+    verify(attachment, blob.size())?;
+
+    let name = Reference {
+        remote: None,
+        category: RefsCategory::Cobs,
+        name: ext::RefLike::try_from(format!("{}/{}", TYPENAME, attachment.oid))
+            .expect("typename and oid are valid ref components"),
+        namespace: Some(Namespace::from(urn)),
+    }
+    .to_string();
+    repo.reference(
+        &name,
+        *attachment.oid,
+        true,
+        &format!("{} {}", TYPENAME, attachment.name),
+    )?;
+
+    Ok(())
+}
+
+fn verify(attachment: &Attachment, actual: usize) -> Result<(), Error> {
+    if actual > MAX_SIZE {
+        return Err(Error::TooLarge {
+            size: actual,
+            limit: MAX_SIZE,
+        });
+    }
+    if actual != attachment.size {
+        return Err(Error::SizeMismatch {
+            oid: attachment.oid,
+            expected: attachment.size,
+            actual,
+        });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,121 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Results of running checks against commits, eg. by a CI system, as a
+//! built-in collaborative object type.
+//!
+//! The id of the object is the checked commit: every peer which ran checks
+//! against it publishes its [`Report`]s at `refs/cobs/xyz.radicle.ci/<commit>`.
+//! A report is stored as a change commit with a single [`REPORT_PATH`] blob in
+//! its tree, see [`super::graph`]. Its first parent is the previous report of
+//! the same peer, if any, and its last parent the checked commit, which is
+//! thereby anchored.
+//!
+//! Reports are attributed to the peer publishing them, whose signed refs cover
+//! the object, and are not signed individually.
+
+use git_ext as ext;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::graph::{self, Layout};
+use crate::{
+    git::{
+        storage::{self, ReadOnlyStorage as _, Storage},
+        Urn,
+    },
+    PeerId,
+};
+
+/// The typename of check results.
+pub const TYPENAME: &str = "xyz.radicle.ci";
+
+/// The path of the blob holding the report in the tree of a report commit.
+pub const REPORT_PATH: &str = "report";
+
+const LAYOUT: Layout = Layout {
+    typename: TYPENAME,
+    path: REPORT_PATH,
+};
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error(transparent)]
+    Graph(#[from] graph::Error),
+
+    #[error(transparent)]
+    Store(#[from] storage::Error),
+
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Status {
+    Success,
+    Failure,
+}
+
+/// The outcome of running checks against a commit.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Report {
+    pub status: Status,
+    /// The branch the commit was checked as the head of, if any.
+    pub branch: Option<String>,
+    /// The hex-encoded SHA-256 of the output of the checks. The output itself
+    /// is kept by the peer which ran them.
+    pub log_digest: String,
+    /// Time the checks finished, in seconds since the UNIX epoch.
+    pub timestamp: u64,
+}
+
+/// The latest [`Report`] of `peer` about `commit`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Published {
+    pub peer: PeerId,
+    pub commit: ext::Oid,
+    pub report: Report,
+}
+
+/// Publish `report` about `commit` in the namespace `urn`.
+pub fn publish(
+    storage: &Storage,
+    urn: &Urn,
+    commit: ext::Oid,
+    report: &Report,
+) -> Result<(), Error> {
+    let prev = storage
+        .reference(&LAYOUT.reference(urn, None, commit))?
+        .and_then(|r| r.target());
+    let oid = LAYOUT.write(storage, prev, &[commit], report)?;
+    Ok(LAYOUT.publish(storage, urn, commit, prev, *oid)?)
+}
+
+/// The latest reports about `commit` published by the local peer and all
+/// tracked peers of `urn`.
+///
+/// Malformed reports are skipped.
+pub fn reports(storage: &Storage, urn: &Urn, commit: ext::Oid) -> Result<Vec<Published>, Error> {
+    let repo = storage.as_raw();
+    let mut reports = Vec::new();
+    for (peer, tip) in LAYOUT.tips(storage, urn, commit)? {
+        match LAYOUT.read(repo, &repo.find_commit(tip)?) {
+            Ok(report) => reports.push(Published {
+                peer,
+                commit,
+                report,
+            }),
+            Err(e) => {
+                tracing::warn!(peer = %peer, report = %tip, err = %e, "skipping malformed report")
+            },
+        }
+    }
+
+    Ok(reports)
+}
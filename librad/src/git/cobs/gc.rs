@@ -0,0 +1,145 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Garbage collection of collaborative objects.
+//!
+//! Replication only ever adds or updates the `refs/remotes/<peer>/cobs/*` of
+//! tracked peers. When a peer deletes an object, or squashes its history into
+//! a new object, the stale refs remain, and so do the change commits they
+//! point to.
+//!
+//! [`collect`] computes the set of live objects of a namespace from the
+//! signed refs of each tracked peer, and removes the refs not in that set, as
+//! well as those of typenames excluded via [`tracking::exclude_cobs`]. The
+//! change commits which become unreachable as a result are removed by the
+//! next `git gc` of the storage.
+
+use std::{collections::BTreeSet, convert::TryFrom};
+
+use git_ext as ext;
+use thiserror::Error;
+
+use super::typename_of;
+use crate::{
+    git::{
+        identities,
+        refs::{self, Refs},
+        storage::{self, ReadOnlyStorage as _, Storage},
+        tracking,
+        types::{Namespace, Reference},
+        Urn,
+    },
+    PeerId,
+};
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error(transparent)]
+    Refs(#[from] refs::stored::Error),
+
+    #[error(transparent)]
+    Tracking(#[from] tracking::Error),
+
+    #[error(transparent)]
+    Identities(#[from] Box<identities::Error>),
+
+    #[error(transparent)]
+    Store(#[from] storage::Error),
+
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+}
+
+impl From<identities::Error> for Error {
+    fn from(e: identities::Error) -> Self {
+        Self::Identities(Box::new(e))
+    }
+}
+
+/// The outcome of [`collect`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Collected {
+    /// The refs which were removed, per tracked peer.
+    pub pruned: Vec<(PeerId, ext::RefLike)>,
+    /// The tracked peers which were skipped, because their signed refs could
+    /// not be loaded.
+    pub skipped: BTreeSet<PeerId>,
+}
+
+/// Remove the orphaned collaborative object refs of the tracked peers of
+/// `urn`.
+///
+/// If the signed refs of a tracked peer are not present locally, none of its
+/// refs are removed.
+#[tracing::instrument(skip(storage), fields(urn = %urn))]
+pub fn collect(storage: &Storage, urn: &Urn) -> Result<Collected, Error> {
+    let urn = urn.with_path(None);
+    let namespace = Namespace::from(&urn);
+    let mut collected = Collected::default();
+
+    for peer in tracking::tracked(storage, &urn)? {
+        let live = match Refs::load(storage, &urn, peer)? {
+            Some(refs) => refs.cobs,
+            None => {
+                collected.skipped.insert(peer);
+                continue;
+            },
+        };
+        let excluded = tracking::excluded_cobs(storage, &urn, peer)?;
+
+        let prefix = Reference::cobs(namespace.clone(), peer).to_string();
+        let prefix = prefix.trim_end_matches('*');
+        for r in storage.references(&Reference::cobs(namespace.clone(), peer))? {
+            let mut r = r?;
+            let name = match r.name() {
+                Some(name) => name.to_owned(),
+                None => continue,
+            };
+            let object = match name
+                .strip_prefix(prefix)
+                .and_then(|object| ext::RefLike::try_from(object).ok())
+            {
+                Some(object) => ext::OneLevel::from(object),
+                None => continue,
+            };
+
+            if live.contains_key(&object) && !excluded.contains(typename_of(&object)) {
+                continue;
+            }
+
+            tracing::debug!(peer = %peer, name = %name, "pruning orphaned object");
+            r.delete()?;
+            if let Ok(name) = ext::RefLike::try_from(name) {
+                collected.pruned.push((peer, name))
+            }
+        }
+    }
+
+    Ok(collected)
+}
+
+/// Run [`collect`] for all identities in `storage`.
+///
+/// Errors are logged and skipped, such that a single corrupt namespace does
+/// not prevent collection of the others.
+pub fn collect_all(storage: &Storage) -> Result<Vec<(Urn, Collected)>, Error> {
+    let mut all = Vec::new();
+    for urn in identities::any::list_urns(storage)? {
+        let urn = match urn {
+            Ok(urn) => urn,
+            Err(e) => {
+                tracing::warn!(err = %e, "skipping invalid namespace");
+                continue;
+            },
+        };
+        match collect(storage, &urn) {
+            Ok(collected) => all.push((urn, collected)),
+            Err(e) => tracing::warn!(urn = %urn, err = %e, "failed to collect objects"),
+        }
+    }
+
+    Ok(all)
+}
@@ -0,0 +1,281 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! The change graphs the built-in collaborative object types are stored as.
+//!
+//! Every peer participating in an object publishes its own history at
+//! `refs/cobs/<typename>/<id>`. Each change in a history is a commit with a
+//! single blob at [`Layout::path`] in its tree, holding the change as JSON.
+//! The first parent of a change commit is the change it builds upon, if any,
+//! and the commits the change refers to are added as further parents. This
+//! _anchors_ them, so that they are replicated along with the object.
+//!
+//! Which changes are taken into account, and how they are applied, is up to
+//! the object type.
+
+use std::{collections::BTreeSet, convert::TryFrom, path::Path};
+
+use git_ext as ext;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use thiserror::Error;
+
+use super::typename_of;
+use crate::{
+    git::{
+        refs::{self, Refs},
+        storage::{self, ReadOnlyStorage as _, Storage},
+        tracking,
+        types::{Namespace, Reference, RefsCategory},
+        Urn,
+    },
+    PeerId,
+};
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("commit {0} not found in storage")]
+    MissingCommit(ext::Oid),
+
+    #[error("change {0} is malformed")]
+    Malformed(ext::Oid, #[source] serde_json::Error),
+
+    #[error(transparent)]
+    Refs(#[from] refs::stored::Error),
+
+    #[error(transparent)]
+    Tracking(#[from] tracking::Error),
+
+    #[error(transparent)]
+    Store(#[from] storage::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+}
+
+/// A change, along with the peer claiming to have authored it.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Envelope<C> {
+    pub author: PeerId,
+    pub change: C,
+}
+
+/// Where the history of a peer which did not publish any changes to an object
+/// yet starts, see [`Layout::append`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Start {
+    /// The change which created the object.
+    Root,
+    /// The most recent change of any peer, by commit time.
+    Latest,
+}
+
+/// How the objects of a typename are laid out in the storage.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Layout {
+    pub typename: &'static str,
+    /// The path of the blob holding the change in the tree of a change commit.
+    pub path: &'static str,
+}
+
+impl Layout {
+    /// The [`Reference`] of the object `id` in the view of `remote`.
+    pub fn reference(
+        &self,
+        urn: &Urn,
+        remote: Option<PeerId>,
+        id: ext::Oid,
+    ) -> Reference<ext::RefLike> {
+        Reference {
+            remote,
+            category: RefsCategory::Cobs,
+            name: ext::RefLike::try_from(format!("{}/{}", self.typename, id))
+                .expect("typename and oid are valid ref components"),
+            namespace: Some(Namespace::from(urn)),
+        }
+    }
+
+    /// The tips of the object `id` of the local peer, and all tracked peers.
+    pub fn tips(
+        &self,
+        storage: &Storage,
+        urn: &Urn,
+        id: ext::Oid,
+    ) -> Result<Vec<(PeerId, git2::Oid)>, Error> {
+        let mut tips = Vec::new();
+        let local = Some((*storage.peer_id(), None));
+        let remotes = tracking::tracked(storage, urn)?.map(|peer| (peer, Some(peer)));
+        for (peer, remote) in local.into_iter().chain(remotes) {
+            if let Some(oid) = storage
+                .reference(&self.reference(urn, remote, id))?
+                .and_then(|r| r.target())
+            {
+                tips.push((peer, oid))
+            }
+        }
+        Ok(tips)
+    }
+
+    /// The ids of the objects published by the local peer and all tracked
+    /// peers of `urn`.
+    pub fn ids(&self, storage: &Storage, urn: &Urn) -> Result<BTreeSet<ext::Oid>, Error> {
+        let namespace = Namespace::from(urn);
+        let mut ids = BTreeSet::new();
+        let remotes = tracking::tracked(storage, urn)?.map(Some);
+        for remote in Some(None).into_iter().chain(remotes) {
+            let glob = Reference::cobs(namespace.clone(), remote);
+            let prefix = glob.to_string();
+            let prefix = prefix.trim_end_matches('*');
+            for name in storage.reference_names(&glob)? {
+                let name = name?;
+                let object = name
+                    .as_str()
+                    .strip_prefix(prefix)
+                    .and_then(|object| ext::RefLike::try_from(object).ok())
+                    .map(ext::OneLevel::from);
+                if let Some(object) = object {
+                    if typename_of(&object) != self.typename {
+                        continue;
+                    }
+                    if let Some(id) = object
+                        .as_str()
+                        .rsplit('/')
+                        .next()
+                        .and_then(|id| git2::Oid::from_str(id).ok())
+                    {
+                        ids.insert(ext::Oid::from(id));
+                    }
+                }
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Read the change stored in the change `commit`.
+    pub fn read<T>(&self, repo: &git2::Repository, commit: &git2::Commit) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+    {
+        let blob = commit
+            .tree()?
+            .get_path(Path::new(self.path))?
+            .to_object(repo)?
+            .peel_to_blob()?;
+        serde_json::from_slice(blob.content()).map_err(|e| Error::Malformed(commit.id().into(), e))
+    }
+
+    /// Write a change commit storing `change`, whose first parent is `prev`,
+    /// followed by the `anchors`.
+    ///
+    /// The commit is not published, see [`Layout::publish`].
+    pub fn write<T>(
+        &self,
+        storage: &Storage,
+        prev: Option<git2::Oid>,
+        anchors: &[ext::Oid],
+        change: &T,
+    ) -> Result<ext::Oid, Error>
+    where
+        T: Serialize,
+    {
+        let repo = storage.as_raw();
+
+        let mut parents = Vec::new();
+        if let Some(prev) = prev {
+            parents.push(repo.find_commit(prev)?);
+        }
+        for anchor in anchors {
+            let commit = repo
+                .find_commit(**anchor)
+                .map_err(|_| Error::MissingCommit(*anchor))?;
+            parents.push(commit);
+        }
+
+        let tree = {
+            let blob = repo.blob(&serde_json::to_vec(change)?)?;
+            let mut builder = repo.treebuilder(None)?;
+            builder.insert(self.path, blob, 0o100_644)?;
+            repo.find_tree(builder.write()?)?
+        };
+        let author = repo.signature()?;
+        let oid = repo.commit(
+            None,
+            &author,
+            &author,
+            &format!("{} {}", self.typename, self.path),
+            &tree,
+            &parents.iter().collect::<Vec<_>>(),
+        )?;
+
+        Ok(oid.into())
+    }
+
+    /// Append `change` to the local history of the object `id`, anchoring
+    /// `anchors`.
+    ///
+    /// If the local peer has not published any changes to the object yet, its
+    /// history starts as given by `start`.
+    pub fn append<T>(
+        &self,
+        storage: &Storage,
+        urn: &Urn,
+        id: ext::Oid,
+        start: Start,
+        anchors: &[ext::Oid],
+        change: &T,
+    ) -> Result<(), Error>
+    where
+        T: Serialize,
+    {
+        let local = storage.peer_id();
+        let tips = self.tips(storage, urn, id)?;
+        let current = tips
+            .iter()
+            .find_map(|(peer, tip)| (peer == local).then(|| *tip));
+        let prev = match (current, start) {
+            (Some(tip), _) => tip,
+            (None, Start::Root) => *id,
+            (None, Start::Latest) => {
+                let repo = storage.as_raw();
+                let mut latest = (i64::MIN, *id);
+                for (_, tip) in &tips {
+                    let time = repo.find_commit(*tip)?.time().seconds();
+                    if time > latest.0 {
+                        latest = (time, *tip);
+                    }
+                }
+                latest.1
+            },
+        };
+        let oid = self.write(storage, Some(prev), anchors, change)?;
+        self.publish(storage, urn, id, current, *oid)
+    }
+
+    /// Point the local ref of the object `id` to `new`, provided it still
+    /// points to `old`, and update the signed refs.
+    pub fn publish(
+        &self,
+        storage: &Storage,
+        urn: &Urn,
+        id: ext::Oid,
+        old: Option<git2::Oid>,
+        new: git2::Oid,
+    ) -> Result<(), Error> {
+        let name = self.reference(urn, None, id).to_string();
+        let repo = storage.as_raw();
+        let msg = format!("{} {}", self.typename, id);
+        match old {
+            Some(old) => repo.reference_matching(&name, new, true, old, &msg)?,
+            None => repo.reference(&name, new, false, &msg)?,
+        };
+        Refs::update(storage, urn)?;
+
+        Ok(())
+    }
+}
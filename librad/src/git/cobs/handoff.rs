@@ -0,0 +1,451 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Delegate handoffs, ie. adding or removing a delegate of a project, as a
+//! built-in collaborative object type.
+//!
+//! Changing the delegations of a project requires a new revision of the
+//! project identity which is signed by a quorum of the current delegates.
+//! Since delegates are usually not online at the same time, the signatures
+//! are exchanged asynchronously:
+//!
+//! 1. A delegate [`propose`]s the change, which creates the new revision
+//!    signed by the proposer, and publishes a handoff pointing to it.
+//! 2. Once the handoff was replicated, the other delegates [`sign`] it. This
+//!    merges the proposed revision into their own identity history, adding
+//!    their signature, and records the signed identity commit in the
+//!    handoff.
+//! 3. Anyone can then [`collect`] the signatures, merging the identity
+//!    commits of all signers into their own history, which will verify once a
+//!    quorum has signed.
+//!
+//! # Storage
+//!
+//! Every peer participating in a handoff publishes its own history at
+//! `refs/cobs/xyz.radicle.handoff/<id>`, where each change is stored as a
+//! commit with a single [`CHANGE_PATH`] blob in its tree, whose first parent
+//! is the previous change of the same peer. The id of a handoff is the commit
+//! id of the change proposing it. The identity commit a change refers to is
+//! added as a further parent, so that it is replicated along with the
+//! handoff, see [`super::graph`].
+//!
+//! A change is only considered if it is reachable from the history published
+//! by the peer it claims to be authored by, see also [`super::patch`].
+
+use std::collections::BTreeMap;
+
+use either::Either;
+use git_ext as ext;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::graph::{self, Layout, Start};
+use crate::{
+    git::{
+        identities::{self, person, project},
+        storage::Storage,
+        Urn,
+    },
+    identities::{
+        delegation,
+        git::{error::Merge, IndirectDelegation, Person, Project},
+    },
+    PeerId,
+    PublicKey,
+};
+
+/// The typename of handoffs.
+pub const TYPENAME: &str = "xyz.radicle.handoff";
+
+/// The path of the blob holding the change in the tree of a change commit.
+pub const CHANGE_PATH: &str = "change";
+
+const LAYOUT: Layout = Layout {
+    typename: TYPENAME,
+    path: CHANGE_PATH,
+};
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("handoff {0} not found")]
+    NotFound(ext::Oid),
+
+    #[error("handoff {0} was withdrawn")]
+    Withdrawn(ext::Oid),
+
+    #[error("only the proposer of handoff {0} can withdraw it")]
+    NotProposer(ext::Oid),
+
+    #[error("{0} is already a delegate")]
+    AlreadyDelegate(Delegate),
+
+    #[error("{0} is not a delegate")]
+    NotDelegate(Delegate),
+
+    #[error("removing {0} would leave the project without delegates")]
+    LastDelegate(Delegate),
+
+    #[error("person {0} not found")]
+    PersonNotFound(Urn),
+
+    #[error("the proposed revision {proposed} was superseded by {actual}")]
+    Superseded {
+        proposed: ext::Oid,
+        actual: ext::Oid,
+    },
+
+    #[error(transparent)]
+    Delegations(#[from] delegation::indirect::error::FromIter<ext::Oid>),
+
+    #[error(transparent)]
+    Identities(#[from] identities::Error),
+
+    #[error(transparent)]
+    Load(#[from] crate::identities::git::error::Load),
+
+    #[error(transparent)]
+    Graph(#[from] graph::Error),
+
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+}
+
+/// A delegate of a project.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "type", content = "id")]
+pub enum Delegate {
+    /// A key, delegated to directly.
+    Peer(PeerId),
+    /// A person identity, all of whose keys are delegated to.
+    Person(Urn),
+}
+
+impl std::fmt::Display for Delegate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Peer(peer) => write!(f, "peer {}", peer),
+            Self::Person(urn) => write!(f, "person {}", urn),
+        }
+    }
+}
+
+/// The change to the delegations proposed by a handoff.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "action", content = "delegate")]
+pub enum Action {
+    Add(Delegate),
+    Remove(Delegate),
+}
+
+/// The state of a handoff, as computed from the changes of all peers.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Handoff {
+    pub id: ext::Oid,
+    pub proposer: PeerId,
+    pub action: Action,
+    /// The proposed revision of the project identity.
+    pub revision: ext::Oid,
+    /// The identity commits carrying the signature of each peer which signed
+    /// the proposed revision, including the proposer.
+    pub signatures: BTreeMap<PeerId, ext::Oid>,
+    pub withdrawn: bool,
+}
+
+type Envelope = graph::Envelope<Change>;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum Change {
+    Propose {
+        action: Action,
+        revision: ext::Oid,
+        commit: ext::Oid,
+    },
+    Sign {
+        commit: ext::Oid,
+    },
+    Withdraw,
+}
+
+impl Change {
+    fn anchors(&self) -> Vec<ext::Oid> {
+        match self {
+            Self::Propose { commit, .. } | Self::Sign { commit } => vec![*commit],
+            Self::Withdraw => vec![],
+        }
+    }
+}
+
+/// The delegations of `project` after applying `action`.
+pub fn delegations(
+    storage: &Storage,
+    project: &Project,
+    action: &Action,
+) -> Result<IndirectDelegation, Error> {
+    let current = project.delegations();
+    let nested = current
+        .nested()
+        .map(|nested| nested.root)
+        .collect::<Vec<_>>();
+    let mut delegations = current.clone().into_iter().collect::<Vec<_>>();
+
+    let is = |delegate: &Delegate, d: &Either<PublicKey, Person>| match (delegate, d) {
+        (Delegate::Peer(peer), Either::Left(key)) => peer.as_public_key() == key,
+        (Delegate::Person(urn), Either::Right(person)) => person.root == urn.id,
+        _ => false,
+    };
+    match action {
+        Action::Add(delegate) => {
+            if delegations.iter().any(|d| is(delegate, d)) {
+                return Err(Error::AlreadyDelegate(delegate.clone()));
+            }
+            delegations.push(match delegate {
+                Delegate::Peer(peer) => Either::Left(*peer.as_public_key()),
+                Delegate::Person(urn) => Either::Right(
+                    person::get(storage, urn)?.ok_or_else(|| Error::PersonNotFound(urn.clone()))?,
+                ),
+            })
+        },
+        Action::Remove(delegate) => {
+            let before = delegations.len();
+            delegations.retain(|d| !is(delegate, d));
+            if delegations.len() == before {
+                return Err(Error::NotDelegate(delegate.clone()));
+            }
+            if delegations.is_empty() && nested.is_empty() {
+                return Err(Error::LastDelegate(delegate.clone()));
+            }
+        },
+    }
+
+    Ok(IndirectDelegation::try_from_iter(delegations)?.with_nested(nested)?)
+}
+
+/// Propose `action` for the project `urn`, returning the id of the handoff.
+///
+/// This updates the local project identity to a new revision with the changed
+/// delegations, signed by the local peer.
+pub fn propose(storage: &Storage, urn: &Urn, action: Action) -> Result<ext::Oid, Error> {
+    let current =
+        project::get(storage, urn)?.ok_or_else(|| identities::Error::NotFound(urn.clone()))?;
+    let delegations = delegations(storage, &current, &action)?;
+    let next = project::update(storage, urn, None, None, delegations)?;
+
+    let change = Change::Propose {
+        action,
+        revision: next.revision,
+        commit: next.content_id,
+    };
+    let envelope = Envelope {
+        author: *storage.peer_id(),
+        change,
+    };
+    let id = LAYOUT.write(storage, None, &envelope.change.anchors(), &envelope)?;
+    LAYOUT.publish(storage, urn, id, None, *id)?;
+
+    Ok(id)
+}
+
+/// Sign the revision proposed by the handoff `id`.
+///
+/// The proposed revision is merged into the local identity history from the
+/// proposer's view of the project. If the local peer has not signed the
+/// project before, eg. because it is being added as a delegate, its history
+/// is first reset to the proposed delegations.
+pub fn sign(storage: &Storage, urn: &Urn, id: ext::Oid) -> Result<Project, Error> {
+    let handoff = get(storage, urn, id)?.ok_or(Error::NotFound(id))?;
+    if handoff.withdrawn {
+        return Err(Error::Withdrawn(id));
+    }
+
+    let signed = match project::merge(storage, urn, handoff.proposer) {
+        Err(identities::Error::Merge(Merge::ForeignBase)) => {
+            let proposed = storage
+                .as_ref()
+                .identities::<Project>()
+                .get(*handoff.signatures[&handoff.proposer])?;
+            project::update(
+                storage,
+                urn,
+                None,
+                proposed.payload().clone(),
+                proposed.delegations().clone(),
+            )?;
+            project::merge(storage, urn, handoff.proposer)?
+        },
+        res => res?,
+    };
+    if signed.revision != handoff.revision {
+        return Err(Error::Superseded {
+            proposed: handoff.revision,
+            actual: signed.revision,
+        });
+    }
+
+    append(
+        storage,
+        urn,
+        id,
+        Change::Sign {
+            commit: signed.content_id,
+        },
+    )?;
+
+    Ok(signed)
+}
+
+/// Merge the signatures of all peers which signed the handoff `id` into the
+/// local identity history.
+///
+/// Whether the result verifies depends on whether a quorum of delegates has
+/// signed, see [`project::verify`].
+pub fn collect(storage: &Storage, urn: &Urn, id: ext::Oid) -> Result<Project, Error> {
+    let handoff = get(storage, urn, id)?.ok_or(Error::NotFound(id))?;
+    if handoff.withdrawn {
+        return Err(Error::Withdrawn(id));
+    }
+
+    let local = storage.peer_id();
+    for peer in handoff.signatures.keys().filter(|peer| *peer != local) {
+        project::merge(storage, urn, *peer)?;
+    }
+    let project =
+        project::get(storage, urn)?.ok_or_else(|| identities::Error::NotFound(urn.clone()))?;
+    if project.revision != handoff.revision {
+        return Err(Error::Superseded {
+            proposed: handoff.revision,
+            actual: project.revision,
+        });
+    }
+
+    Ok(project)
+}
+
+/// Withdraw the handoff `id`. Only the proposer can withdraw a handoff.
+///
+/// Note that this does not revert the local identity history.
+pub fn withdraw(storage: &Storage, urn: &Urn, id: ext::Oid) -> Result<(), Error> {
+    let handoff = get(storage, urn, id)?.ok_or(Error::NotFound(id))?;
+    if handoff.proposer != *storage.peer_id() {
+        return Err(Error::NotProposer(id));
+    }
+    if handoff.withdrawn {
+        return Ok(());
+    }
+
+    append(storage, urn, id, Change::Withdraw)
+}
+
+/// Compute the state of the handoff `id` from the changes published by the
+/// local peer and all tracked peers of `urn`.
+///
+/// `None` is returned if the proposer did not publish the handoff. Signatures
+/// are only taken into account if the identity commit they refer to is
+/// signed by the peer which published it, and has the proposed revision.
+pub fn get(storage: &Storage, urn: &Urn, id: ext::Oid) -> Result<Option<Handoff>, Error> {
+    let repo = storage.as_raw();
+
+    let mut changes: BTreeMap<PeerId, Vec<(git2::Oid, Change)>> = BTreeMap::new();
+    for (peer, tip) in LAYOUT.tips(storage, urn, id)? {
+        let history = changes.entry(peer).or_default();
+        let mut next = Some(tip);
+        while let Some(oid) = next {
+            let commit = repo.find_commit(oid)?;
+            let envelope: Envelope = match LAYOUT.read(repo, &commit) {
+                Ok(envelope) => envelope,
+                Err(e) => {
+                    tracing::warn!(peer = %peer, change = %oid, err = %e, "skipping malformed history");
+                    break;
+                },
+            };
+            if envelope.author == peer {
+                history.push((oid, envelope.change.clone()));
+            }
+            next = if oid != *id && commit.parent_count() > envelope.change.anchors().len() {
+                Some(commit.parent_id(0)?)
+            } else {
+                None
+            };
+        }
+        history.reverse();
+    }
+
+    let root = changes.iter().find_map(|(peer, history)| {
+        history.iter().find_map(|(oid, change)| match change {
+            Change::Propose {
+                action,
+                revision,
+                commit,
+            } if *oid == *id => Some((*peer, action.clone(), *revision, *commit)),
+            _ => None,
+        })
+    });
+    let (proposer, action, revision, commit) = match root {
+        Some(root) => root,
+        None => return Ok(None),
+    };
+
+    let mut handoff = Handoff {
+        id,
+        proposer,
+        action,
+        revision,
+        signatures: vec![(proposer, commit)].into_iter().collect(),
+        withdrawn: false,
+    };
+    let identities = storage.as_ref().identities::<Project>();
+    for (peer, history) in changes {
+        for (oid, change) in history {
+            match change {
+                Change::Sign { commit } => {
+                    let signed = identities.get(*commit).map(|project| {
+                        project.revision == revision
+                            && project.signatures.contains_key(peer.as_public_key())
+                    });
+                    match signed {
+                        Ok(true) => {
+                            handoff.signatures.insert(peer, commit);
+                        },
+                        _ => {
+                            tracing::warn!(peer = %peer, change = %oid, "ignoring invalid signature")
+                        },
+                    }
+                },
+                Change::Withdraw if peer == proposer => handoff.withdrawn = true,
+                _ => {},
+            }
+        }
+    }
+
+    Ok(Some(handoff))
+}
+
+/// List the handoffs published by the local peer and all tracked peers of
+/// `urn`.
+pub fn list(storage: &Storage, urn: &Urn) -> Result<Vec<Handoff>, Error> {
+    let ids = LAYOUT.ids(storage, urn)?;
+    let mut handoffs = Vec::with_capacity(ids.len());
+    for id in ids {
+        if let Some(handoff) = get(storage, urn, id)? {
+            handoffs.push(handoff)
+        }
+    }
+    Ok(handoffs)
+}
+
+/// Append `change` to the local history of the handoff `id`.
+///
+/// If the local peer has not published any changes to the handoff yet, its
+/// history starts from the proposing change.
+fn append(storage: &Storage, urn: &Urn, id: ext::Oid, change: Change) -> Result<(), Error> {
+    let anchors = change.anchors();
+    let envelope = Envelope {
+        author: *storage.peer_id(),
+        change,
+    };
+    Ok(LAYOUT.append(storage, urn, id, Start::Root, &anchors, &envelope)?)
+}
@@ -0,0 +1,671 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Patches, aka merge requests, as a built-in collaborative object type.
+//!
+//! A patch is a history of changes, each stored as a commit with a single
+//! [`CHANGE_PATH`] blob in its tree. The first change opens the patch, and its
+//! commit id is the id of the patch. Every peer participating in a patch
+//! publishes its own history at `refs/cobs/xyz.radicle.patch/<id>`, where the
+//! first parent of a change commit is the previous change of that peer (or,
+//! for its first change, the latest change it has seen). See [`super::graph`]
+//! for the layout shared by the built-in collaborative object types.
+//!
+//! # Anchoring
+//!
+//! The commits a change refers to (the base and head of a revision, the merge
+//! commit) are added as further parents of the change commit. This way, they
+//! are fetched along with the object during replication, even if they are not
+//! reachable from any signed branch of the peer which proposed them.
+//!
+//! Reviews can carry binary [`attachment`]s, eg. screenshots. These are
+//! published along with the change referring to them, see the module
+//! documentation for how they are replicated.
+//!
+//! # Concurrency
+//!
+//! Changes are applied in topological order, with concurrent changes ordered
+//! by commit time. Where concurrent changes disagree, eg. two peers merging
+//! different revisions, the first one wins. [`concurrency`] exposes these
+//! decisions, so they can be shown to the user.
+//!
+//! # Authorship
+//!
+//! A change is only considered if it is reachable from the history published
+//! by the peer it claims to be authored by. Since the published histories are
+//! covered by the signed refs of each peer, this attributes changes to peers
+//! without signing every change individually.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use git_ext as ext;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{
+    attachment::{self, Attachment},
+    graph::{self, Layout, Start},
+};
+use crate::{
+    git::{storage::Storage, Urn},
+    PeerId,
+};
+
+/// The typename of patches.
+pub const TYPENAME: &str = "xyz.radicle.patch";
+
+/// The path of the blob holding the change in the tree of a change commit.
+pub const CHANGE_PATH: &str = "change";
+
+const LAYOUT: Layout = Layout {
+    typename: TYPENAME,
+    path: CHANGE_PATH,
+};
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("patch {0} not found")]
+    NotFound(ext::Oid),
+
+    #[error("revision {revision} of patch {patch} does not exist")]
+    NoSuchRevision { patch: ext::Oid, revision: usize },
+
+    #[error("only the author of patch {0} can revise it")]
+    NotAuthor(ext::Oid),
+
+    #[error("patch {0} is already merged")]
+    AlreadyMerged(ext::Oid),
+
+    #[error(transparent)]
+    Attachment(#[from] attachment::Error),
+
+    #[error(transparent)]
+    Graph(#[from] graph::Error),
+
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+}
+
+/// The state of a patch, as computed from the changes of all peers.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Patch {
+    pub id: ext::Oid,
+    pub author: PeerId,
+    pub title: String,
+    pub description: String,
+    /// The revisions of the patch, oldest first. There is always at least one.
+    pub revisions: Vec<Revision>,
+    pub merge: Option<Merge>,
+}
+
+impl Patch {
+    /// The most recent [`Revision`].
+    pub fn latest(&self) -> &Revision {
+        self.revisions
+            .last()
+            .expect("a patch has at least one revision")
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Revision {
+    pub base: ext::Oid,
+    pub head: ext::Oid,
+    /// The peer in whose view `head` can be found.
+    pub peer: PeerId,
+    pub note: Option<String>,
+    pub reviews: Vec<Review>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Review {
+    pub reviewer: PeerId,
+    pub verdict: Verdict,
+    pub comment: Option<String>,
+    pub attachments: Vec<Attachment>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Verdict {
+    Accept,
+    Reject,
+    Comment,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Merge {
+    pub merger: PeerId,
+    /// The index of the merged revision in [`Patch::revisions`].
+    pub revision: usize,
+    pub commit: ext::Oid,
+}
+
+/// The concurrency structure of the changes to a patch, see [`concurrency`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Concurrency {
+    /// The changes no other change builds upon. More than one means that
+    /// peers made changes without having seen each other's.
+    pub frontier: Vec<ConcurrentChange>,
+    /// The fields whose state was decided between concurrent changes.
+    pub conflicts: Vec<Conflict>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConcurrentChange {
+    pub id: ext::Oid,
+    pub author: PeerId,
+    /// The vector clock of the change, ie. the number of changes of each peer
+    /// it builds upon, including itself.
+    pub clock: BTreeMap<PeerId, usize>,
+}
+
+/// Concurrent changes to the same [`Field`], of which only `applied` took
+/// effect.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Conflict {
+    pub field: Field,
+    pub applied: ConcurrentChange,
+    pub discarded: Vec<ConcurrentChange>,
+}
+
+/// The parts of a [`Patch`] which concurrent changes can disagree on.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Field {
+    /// Whether, and which revision the patch was merged as. Merges, and
+    /// revisions made concurrently to the first merge are discarded.
+    Merge,
+}
+
+/// The arguments to [`open`] and [`revise`].
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Proposal {
+    pub base: ext::Oid,
+    pub head: ext::Oid,
+    /// The peer in whose view `head` can be found.
+    pub peer: PeerId,
+    pub note: Option<String>,
+}
+
+type Envelope = graph::Envelope<Change>;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+enum Change {
+    Open {
+        title: String,
+        description: String,
+        proposal: Proposal,
+    },
+    Revise {
+        proposal: Proposal,
+    },
+    Review {
+        revision: usize,
+        verdict: Verdict,
+        comment: Option<String>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        attachments: Vec<Attachment>,
+    },
+    Merge {
+        revision: usize,
+        commit: ext::Oid,
+    },
+}
+
+impl Change {
+    fn anchors(&self) -> Vec<ext::Oid> {
+        match self {
+            Self::Open { proposal, .. } | Self::Revise { proposal } => {
+                if proposal.base == proposal.head {
+                    vec![proposal.head]
+                } else {
+                    vec![proposal.base, proposal.head]
+                }
+            },
+            Self::Review { .. } => vec![],
+            Self::Merge { commit, .. } => vec![*commit],
+        }
+    }
+}
+
+/// Open a new patch in the namespace `urn`, returning its id.
+pub fn open(
+    storage: &Storage,
+    urn: &Urn,
+    title: String,
+    description: String,
+    proposal: Proposal,
+) -> Result<ext::Oid, Error> {
+    let change = Change::Open {
+        title,
+        description,
+        proposal,
+    };
+    let envelope = Envelope {
+        author: *storage.peer_id(),
+        change,
+    };
+    let id = LAYOUT.write(storage, None, &envelope.change.anchors(), &envelope)?;
+    LAYOUT.publish(storage, urn, id, None, *id)?;
+
+    Ok(id)
+}
+
+/// Add a new revision to the patch `id`. Only the author of the patch can
+/// revise it.
+pub fn revise(storage: &Storage, urn: &Urn, id: ext::Oid, proposal: Proposal) -> Result<(), Error> {
+    let patch = get(storage, urn, id)?.ok_or(Error::NotFound(id))?;
+    if patch.author != *storage.peer_id() {
+        return Err(Error::NotAuthor(id));
+    }
+    if patch.merge.is_some() {
+        return Err(Error::AlreadyMerged(id));
+    }
+
+    append(storage, urn, id, Change::Revise { proposal })
+}
+
+/// Review the `revision` of the patch `id`.
+///
+/// The `attachments` must have been stored using [`attachment::store`], or
+/// fetched from another peer.
+pub fn review(
+    storage: &Storage,
+    urn: &Urn,
+    id: ext::Oid,
+    revision: usize,
+    verdict: Verdict,
+    comment: Option<String>,
+    attachments: Vec<Attachment>,
+) -> Result<(), Error> {
+    let patch = get(storage, urn, id)?.ok_or(Error::NotFound(id))?;
+    if revision >= patch.revisions.len() {
+        return Err(Error::NoSuchRevision {
+            patch: id,
+            revision,
+        });
+    }
+    for attachment in &attachments {
+        attachment::link(storage, urn, attachment)?;
+    }
+
+    append(
+        storage,
+        urn,
+        id,
+        Change::Review {
+            revision,
+            verdict,
+            comment,
+            attachments,
+        },
+    )
+}
+
+/// Record that the `revision` of the patch `id` was merged as `commit`.
+pub fn merge(
+    storage: &Storage,
+    urn: &Urn,
+    id: ext::Oid,
+    revision: usize,
+    commit: ext::Oid,
+) -> Result<(), Error> {
+    let patch = get(storage, urn, id)?.ok_or(Error::NotFound(id))?;
+    if revision >= patch.revisions.len() {
+        return Err(Error::NoSuchRevision {
+            patch: id,
+            revision,
+        });
+    }
+    if patch.merge.is_some() {
+        return Err(Error::AlreadyMerged(id));
+    }
+
+    append(storage, urn, id, Change::Merge { revision, commit })
+}
+
+/// Compute the state of the patch `id` from the changes published by the
+/// local peer and all tracked peers of `urn`.
+///
+/// `None` is returned if no peer published the patch.
+pub fn get(storage: &Storage, urn: &Urn, id: ext::Oid) -> Result<Option<Patch>, Error> {
+    Ok(history(storage, urn, id)?.map(|history| fold(id, history).0))
+}
+
+/// Compute the [`Concurrency`] of the changes to the patch `id`, as published
+/// by the local peer and all tracked peers of `urn`.
+///
+/// `None` is returned if no peer published the patch.
+pub fn concurrency(
+    storage: &Storage,
+    urn: &Urn,
+    id: ext::Oid,
+) -> Result<Option<Concurrency>, Error> {
+    let history = match history(storage, urn, id)? {
+        Some(history) => history,
+        None => return Ok(None),
+    };
+
+    let prevs = history
+        .changes
+        .iter()
+        .map(|(oid, node)| (*oid, node.prev))
+        .collect::<BTreeMap<_, _>>();
+    let (_, fold) = fold(id, history);
+
+    let mut clocks: BTreeMap<git2::Oid, Clock> = BTreeMap::new();
+    clocks.insert(*id, Clock::default().tick(fold.root));
+    for (oid, author) in &fold.order {
+        let clock = prevs[oid]
+            .and_then(|prev| clocks.get(&prev))
+            .cloned()
+            .unwrap_or_default()
+            .tick(*author);
+        clocks.insert(*oid, clock);
+    }
+    let change = |oid: git2::Oid, author: PeerId| ConcurrentChange {
+        id: oid.into(),
+        author,
+        clock: clocks[&oid].0.clone(),
+    };
+
+    let built_upon = prevs.values().flatten().copied().collect::<BTreeSet<_>>();
+    let frontier = Some((*id, fold.root))
+        .into_iter()
+        .chain(fold.order.iter().copied())
+        .filter(|(oid, _)| !built_upon.contains(oid))
+        .map(|(oid, author)| change(oid, author))
+        .collect();
+
+    let mut conflicts: Vec<Conflict> = Vec::new();
+    for (discarded, author, field) in fold.discarded {
+        let applied = fold.applied.iter().find(|(oid, _, applied)| {
+            *applied == field && clocks[oid].is_concurrent(&clocks[&discarded])
+        });
+        if let Some((oid, by, _)) = applied {
+            let discarded = change(discarded, author);
+            match conflicts
+                .iter_mut()
+                .find(|c| c.field == field && *c.applied.id == *oid)
+            {
+                Some(conflict) => conflict.discarded.push(discarded),
+                None => conflicts.push(Conflict {
+                    field,
+                    applied: change(*oid, *by),
+                    discarded: vec![discarded],
+                }),
+            }
+        }
+    }
+
+    Ok(Some(Concurrency {
+        frontier,
+        conflicts,
+    }))
+}
+
+/// List the patches published by the local peer and all tracked peers of
+/// `urn`.
+pub fn list(storage: &Storage, urn: &Urn) -> Result<Vec<Patch>, Error> {
+    let ids = LAYOUT.ids(storage, urn)?;
+    let mut patches = Vec::with_capacity(ids.len());
+    for id in ids {
+        if let Some(patch) = get(storage, urn, id)? {
+            patches.push(patch)
+        }
+    }
+    Ok(patches)
+}
+
+impl From<Proposal> for Revision {
+    fn from(
+        Proposal {
+            base,
+            head,
+            peer,
+            note,
+        }: Proposal,
+    ) -> Self {
+        Self {
+            base,
+            head,
+            peer,
+            note,
+            reviews: vec![],
+        }
+    }
+}
+
+struct Node {
+    time: i64,
+    prev: Option<git2::Oid>,
+    envelope: Envelope,
+}
+
+/// The changes to a patch, as published by the peers which authored them.
+struct History {
+    root: Envelope,
+    changes: BTreeMap<git2::Oid, Node>,
+}
+
+/// The outcome of applying the changes of a [`History`] in order.
+struct Fold {
+    root: PeerId,
+    /// The changes in the order they were applied.
+    order: Vec<(git2::Oid, PeerId)>,
+    /// The changes which determined the state of a [`Field`].
+    applied: Vec<(git2::Oid, PeerId, Field)>,
+    /// The changes which were ignored because the state of a [`Field`] was
+    /// already determined.
+    discarded: Vec<(git2::Oid, PeerId, Field)>,
+}
+
+/// The changes of all peers which are reachable from the history published
+/// by their author, or `None` if the patch was not published.
+fn history(storage: &Storage, urn: &Urn, id: ext::Oid) -> Result<Option<History>, Error> {
+    let repo = storage.as_raw();
+
+    // The change commits published by each peer
+    let mut published: BTreeMap<PeerId, BTreeSet<git2::Oid>> = BTreeMap::new();
+    let mut changes: BTreeMap<git2::Oid, Node> = BTreeMap::new();
+    for (peer, tip) in LAYOUT.tips(storage, urn, id)? {
+        let chain = published.entry(peer).or_default();
+        let mut next = Some(tip);
+        while let Some(oid) = next {
+            if !chain.insert(oid) {
+                break;
+            }
+            if let Some(node) = changes.get(&oid) {
+                next = node.prev;
+                continue;
+            }
+
+            let commit = repo.find_commit(oid)?;
+            let envelope: Envelope = match LAYOUT.read(repo, &commit) {
+                Ok(envelope) => envelope,
+                Err(e) => {
+                    tracing::warn!(peer = %peer, change = %oid, err = %e, "skipping malformed history");
+                    chain.remove(&oid);
+                    break;
+                },
+            };
+            let prev = if oid != *id && commit.parent_count() > envelope.change.anchors().len() {
+                Some(commit.parent_id(0)?)
+            } else {
+                None
+            };
+            changes.insert(
+                oid,
+                Node {
+                    time: commit.time().seconds(),
+                    prev,
+                    envelope,
+                },
+            );
+            next = prev;
+        }
+    }
+    changes.retain(|oid, node| is_published_by(&published, &node.envelope.author, *oid));
+
+    Ok(changes
+        .remove(&*id)
+        .map(|root| History {
+            root: root.envelope,
+            changes,
+        })
+        .filter(|history| matches!(history.root.change, Change::Open { .. })))
+}
+
+/// Apply the changes of `history` in [`topological`] order.
+fn fold(id: ext::Oid, History { root, changes }: History) -> (Patch, Fold) {
+    let mut patch = match root.change {
+        Change::Open {
+            title,
+            description,
+            proposal,
+        } => Patch {
+            id,
+            author: root.author,
+            title,
+            description,
+            revisions: vec![Revision::from(proposal)],
+            merge: None,
+        },
+        _ => unreachable!("history starts with an open change"),
+    };
+    let mut fold = Fold {
+        root: root.author,
+        order: Vec::with_capacity(changes.len()),
+        applied: vec![],
+        discarded: vec![],
+    };
+
+    for (oid, Envelope { author, change }) in topological(changes) {
+        fold.order.push((oid, author));
+        match change {
+            Change::Revise { proposal } if author == patch.author => {
+                if patch.merge.is_none() {
+                    patch.revisions.push(Revision::from(proposal))
+                } else {
+                    fold.discarded.push((oid, author, Field::Merge))
+                }
+            },
+            Change::Review {
+                revision,
+                verdict,
+                comment,
+                attachments,
+            } if revision < patch.revisions.len() => {
+                patch.revisions[revision].reviews.push(Review {
+                    reviewer: author,
+                    verdict,
+                    comment,
+                    attachments,
+                })
+            },
+            Change::Merge { revision, commit } if revision < patch.revisions.len() => {
+                if patch.merge.is_none() {
+                    patch.merge = Some(Merge {
+                        merger: author,
+                        revision,
+                        commit,
+                    });
+                    fold.applied.push((oid, author, Field::Merge))
+                } else {
+                    fold.discarded.push((oid, author, Field::Merge))
+                }
+            },
+            _ => tracing::debug!(change = %oid, patch = %id, "ignoring inapplicable change"),
+        }
+    }
+
+    (patch, fold)
+}
+
+/// A vector clock, counting the changes of each peer a change builds upon.
+#[derive(Clone, Debug, Default)]
+struct Clock(BTreeMap<PeerId, usize>);
+
+impl Clock {
+    fn tick(mut self, peer: PeerId) -> Self {
+        *self.0.entry(peer).or_default() += 1;
+        self
+    }
+
+    fn happened_before(&self, other: &Self) -> bool {
+        self.0
+            .iter()
+            .all(|(peer, n)| other.0.get(peer).map(|m| n <= m).unwrap_or(false))
+    }
+
+    fn is_concurrent(&self, other: &Self) -> bool {
+        !self.happened_before(other) && !other.happened_before(self)
+    }
+}
+
+/// Order `changes` such that every change comes after its predecessor, and
+/// concurrent changes are ordered by commit time, and then by id.
+fn topological(mut changes: BTreeMap<git2::Oid, Node>) -> Vec<(git2::Oid, Envelope)> {
+    let mut successors: BTreeMap<git2::Oid, Vec<git2::Oid>> = BTreeMap::new();
+    let mut ready = BTreeSet::new();
+    for (oid, node) in &changes {
+        match node.prev {
+            Some(prev) if changes.contains_key(&prev) => {
+                successors.entry(prev).or_default().push(*oid)
+            },
+            _ => {
+                ready.insert((node.time, *oid));
+            },
+        }
+    }
+
+    let mut ordered = Vec::with_capacity(changes.len());
+    while let Some(next) = ready.iter().next().copied() {
+        ready.remove(&next);
+        let (_, oid) = next;
+        for succ in successors.remove(&oid).unwrap_or_default() {
+            ready.insert((changes[&succ].time, succ));
+        }
+        if let Some(node) = changes.remove(&oid) {
+            ordered.push((oid, node.envelope));
+        }
+    }
+    ordered
+}
+
+fn is_published_by(
+    published: &BTreeMap<PeerId, BTreeSet<git2::Oid>>,
+    author: &PeerId,
+    oid: git2::Oid,
+) -> bool {
+    published
+        .get(author)
+        .map(|chain| chain.contains(&oid))
+        .unwrap_or(false)
+}
+
+/// Append `change` to the local history of the patch `id`.
+///
+/// If the local peer has not published any changes to the patch yet, its
+/// history starts from the most recent change of any tracked peer.
+fn append(storage: &Storage, urn: &Urn, id: ext::Oid, change: Change) -> Result<(), Error> {
+    let anchors = change.anchors();
+    let envelope = Envelope {
+        author: *storage.peer_id(),
+        change,
+    };
+    Ok(LAYOUT.append(storage, urn, id, Start::Latest, &anchors, &envelope)?)
+}
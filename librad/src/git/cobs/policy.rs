@@ -0,0 +1,226 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Per-typename authorization of collaborative objects.
+//!
+//! An application can restrict which peers may publish objects of the
+//! typenames it owns by registering an [`Authorization`] using [`set`]. The
+//! rule is stored in the storage config as `cobs.<typename>.authorization`.
+//!
+//! During replication, the `refs/cobs/<typename>/*` of peers which are not
+//! authorized are not fetched. Instead, they are _quarantined_: recorded per
+//! [`Urn`], such that they can be inspected using [`quarantined`], and
+//! obtained by a later replication once the rule permits it.
+//!
+//! There is no rule admitting only tracked peers: the `cobs` of a peer are
+//! only replicated if the peer is tracked in the first place.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryFrom,
+    fmt,
+    fs,
+    io,
+    path::PathBuf,
+    str::FromStr,
+};
+
+use git_ext::{self as ext, is_not_found_err};
+use std_ext::result::ResultExt as _;
+use thiserror::Error;
+
+use super::{is_valid_typename, typename_of};
+use crate::{
+    git::{
+        refs::Refs,
+        storage::{self, Storage},
+        Urn,
+    },
+    PeerId,
+};
+
+const QUARANTINE_DIR: &str = "cobs/quarantine";
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("invalid collaborative object typename `{0}`")]
+    InvalidTypename(String),
+
+    #[error("invalid authorization `{0}` for typename `{1}`")]
+    InvalidAuthorization(String, String),
+
+    #[error(transparent)]
+    Config(#[from] storage::config::Error),
+
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+/// Who may publish collaborative objects of a given typename.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Authorization {
+    /// Only the delegates of the project.
+    Delegates,
+    /// Any peer. This is the default if no rule is set.
+    Anyone,
+}
+
+impl Default for Authorization {
+    fn default() -> Self {
+        Self::Anyone
+    }
+}
+
+impl fmt::Display for Authorization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Delegates => f.write_str("delegates"),
+            Self::Anyone => f.write_str("anyone"),
+        }
+    }
+}
+
+impl FromStr for Authorization {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "delegates" => Ok(Self::Delegates),
+            "anyone" => Ok(Self::Anyone),
+            _ => Err(s.to_owned()),
+        }
+    }
+}
+
+/// The refs withheld from replication, per peer.
+pub type Quarantined = BTreeMap<PeerId, BTreeMap<ext::OneLevel, ext::Oid>>;
+
+/// Register the [`Authorization`] rule for `typename`.
+pub fn set(storage: &Storage, typename: &str, auth: Authorization) -> Result<(), Error> {
+    let key = config_key(typename)?;
+    let mut config = storage::Config::try_from(storage)?;
+    config.as_raw_mut().set_str(&key, &auth.to_string())?;
+
+    Ok(())
+}
+
+/// Remove the [`Authorization`] rule for `typename`, reverting to the default.
+///
+/// `true` is returned if a rule was set before.
+pub fn unset(storage: &Storage, typename: &str) -> Result<bool, Error> {
+    let key = config_key(typename)?;
+    let mut config = storage::Config::try_from(storage)?;
+    config
+        .as_raw_mut()
+        .remove(&key)
+        .map(|()| true)
+        .or_matches::<Error, _, _>(is_not_found_err, || Ok(false))
+}
+
+/// The [`Authorization`] rule for `typename`.
+pub fn get(storage: &Storage, typename: &str) -> Result<Authorization, Error> {
+    let key = config_key(typename)?;
+    let config = storage.as_raw().config()?.snapshot()?;
+    match config.get_string(&key) {
+        Ok(val) => val
+            .parse()
+            .map_err(|val| Error::InvalidAuthorization(val, typename.to_owned())),
+        Err(e) if is_not_found_err(&e) => Ok(Authorization::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The refs of `urn` quarantined by the most recent replication.
+pub fn quarantined(storage: &Storage, urn: &Urn) -> Result<Quarantined, Error> {
+    match fs::read(quarantine_path(storage, urn)) {
+        Ok(data) => Ok(serde_json::from_slice(&data)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Quarantined::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Remove the unauthorized `cobs` from `tracked_sigrefs`, and record them as
+/// [`quarantined`].
+///
+/// `delegates` are the delegates of the project `urn`.
+pub(crate) fn authorize(
+    storage: &Storage,
+    urn: &Urn,
+    delegates: &BTreeSet<PeerId>,
+    tracked_sigrefs: &mut BTreeMap<PeerId, Refs>,
+) -> Result<Quarantined, Error> {
+    let mut rules = BTreeMap::new();
+    let mut quarantine = Quarantined::new();
+    for (peer, refs) in tracked_sigrefs.iter_mut() {
+        let mut rejected = BTreeMap::new();
+        for (name, oid) in &refs.cobs {
+            let typename = typename_of(name);
+            let auth = match rules.get(typename) {
+                Some(auth) => *auth,
+                None => {
+                    let auth = get(storage, typename)?;
+                    rules.insert(typename.to_owned(), auth);
+                    auth
+                },
+            };
+            let authorized = match auth {
+                Authorization::Anyone => true,
+                Authorization::Delegates => delegates.contains(peer),
+            };
+            if !authorized {
+                rejected.insert(name.clone(), *oid);
+            }
+        }
+
+        if !rejected.is_empty() {
+            tracing::info!(
+                peer = %peer,
+                refs = ?rejected.keys().collect::<Vec<_>>(),
+                "quarantining unauthorized collaborative objects"
+            );
+            refs.cobs.retain(|name, _| !rejected.contains_key(name));
+            quarantine.insert(*peer, rejected);
+        }
+    }
+
+    let path = quarantine_path(storage, urn);
+    if quarantine.is_empty() {
+        if let Err(e) = fs::remove_file(&path) {
+            if e.kind() != io::ErrorKind::NotFound {
+                return Err(e.into());
+            }
+        }
+    } else {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, serde_json::to_vec(&quarantine)?)?;
+    }
+
+    Ok(quarantine)
+}
+
+fn config_key(typename: &str) -> Result<String, Error> {
+    if is_valid_typename(typename) {
+        Ok(format!("cobs.{}.authorization", typename))
+    } else {
+        Err(Error::InvalidTypename(typename.to_owned()))
+    }
+}
+
+fn quarantine_path(storage: &Storage, urn: &Urn) -> PathBuf {
+    storage
+        .as_raw()
+        .path()
+        .join(QUARANTINE_DIR)
+        .join(urn.encode_id())
+}
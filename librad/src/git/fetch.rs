@@ -3,11 +3,20 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::{collections::BTreeMap, iter::FromIterator, ops::Deref};
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom as _,
+    iter::FromIterator,
+    ops::Deref,
+    time::Duration,
+};
 
 use git_ext as ext;
+use serde::{Deserialize, Serialize};
 
-use crate::identities::Urn;
+use crate::{identities::Urn, net::protocol::Features};
+
+pub mod haves;
 
 mod specs;
 pub use specs::Fetchspecs;
@@ -44,20 +53,80 @@ impl Default for Limit {
     }
 }
 
+/// Suffix of the refs advertised in addition to annotated tags, denoting the
+/// object the tag peels to.
+const PEELED_SUFFIX: &str = "^{}";
+
+/// The refs advertised by the remote end, and their targets.
+///
+/// Annotated tags may additionally be advertised as `<name>^{}`, pointing to
+/// the object the tag peels to. Those targets are available via
+/// [`RemoteHeads::peeled`].
 #[derive(Clone, Debug, Default)]
-pub struct RemoteHeads(BTreeMap<ext::RefLike, ext::Oid>);
+pub struct RemoteHeads {
+    heads: BTreeMap<ext::RefLike, ext::Oid>,
+    peeled: BTreeMap<ext::RefLike, ext::Oid>,
+}
+
+impl RemoteHeads {
+    /// Collect the refs of a ref advertisement, including the peeled
+    /// `<name>^{}` entries.
+    ///
+    /// Entries whose name is not a valid ref name are skipped.
+    pub fn from_advertised<'a, I>(advertised: I) -> Self
+    where
+        I: IntoIterator<Item = (&'a str, ext::Oid)>,
+    {
+        let mut remote_heads = Self::default();
+        for (name, oid) in advertised {
+            let (target, name) = match name.strip_suffix(PEELED_SUFFIX) {
+                Some(name) => (&mut remote_heads.peeled, name),
+                None => (&mut remote_heads.heads, name),
+            };
+            match ext::RefLike::try_from(name) {
+                Ok(name) => {
+                    target.insert(name, oid);
+                },
+                Err(e) => tracing::warn!("invalid refname `{}`: {}", name, e),
+            }
+        }
+        remote_heads
+    }
+
+    /// The object `name` ultimately points to, ie. the peeled target if `name`
+    /// is an annotated tag, and its direct target otherwise.
+    pub fn peeled(&self, name: &ext::RefLike) -> Option<&ext::Oid> {
+        self.peeled.get(name).or_else(|| self.heads.get(name))
+    }
+
+    /// The refs in the form they were advertised, ie. including the peeled
+    /// `<name>^{}` entries. The inverse of [`RemoteHeads::from_advertised`].
+    pub fn advertised(&self) -> impl Iterator<Item = (String, ext::Oid)> + '_ {
+        self.heads
+            .iter()
+            .map(|(name, oid)| (name.to_string(), *oid))
+            .chain(
+                self.peeled
+                    .iter()
+                    .map(|(name, oid)| (format!("{}{}", name, PEELED_SUFFIX), *oid)),
+            )
+    }
+}
 
 impl Deref for RemoteHeads {
     type Target = BTreeMap<ext::RefLike, ext::Oid>;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.heads
     }
 }
 
 impl From<BTreeMap<ext::RefLike, ext::Oid>> for RemoteHeads {
-    fn from(map: BTreeMap<ext::RefLike, ext::Oid>) -> Self {
-        Self(map)
+    fn from(heads: BTreeMap<ext::RefLike, ext::Oid>) -> Self {
+        Self {
+            heads,
+            peeled: BTreeMap::new(),
+        }
     }
 }
 
@@ -66,12 +135,164 @@ impl FromIterator<(ext::RefLike, ext::Oid)> for RemoteHeads {
     where
         T: IntoIterator<Item = (ext::RefLike, ext::Oid)>,
     {
-        Self(iter.into_iter().collect())
+        Self::from(iter.into_iter().collect::<BTreeMap<_, _>>())
     }
 }
 
 pub struct FetchResult {
     pub updated_tips: BTreeMap<ext::RefLike, ext::Oid>,
+    /// The same refs as [`FetchResult::updated_tips`], along with their
+    /// previous targets.
+    pub updates: BTreeMap<ext::RefLike, TipUpdate>,
+    /// The metadata of the pack received, or `None` if no pack was
+    /// transferred.
+    pub pack: Option<PackStats>,
+    /// Set if the fetch was skipped without negotiating with the remote end.
+    pub skipped: Option<Skipped>,
+}
+
+/// The kind of [`Fetchspecs`] a fetch was made with.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Step {
+    PeekAll,
+    Peek,
+    Replicate,
+}
+
+impl<P, R> From<&Fetchspecs<P, R>> for Step {
+    fn from(specs: &Fetchspecs<P, R>) -> Self {
+        match specs {
+            Fetchspecs::PeekAll { .. } => Self::PeekAll,
+            Fetchspecs::Peek { .. } => Self::Peek,
+            Fetchspecs::Replicate { .. } => Self::Replicate,
+        }
+    }
+}
+
+/// Why a fetch was skipped.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Skipped {
+    /// The remote end advertises none of the refs asked for.
+    NoMatchingRefs,
+    /// The refs asked for were already fetched at the tips the remote end
+    /// advertises.
+    WantNothing,
+}
+
+/// Why a [`Fetcher`] retried talking to the remote end.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Retry {
+    /// The pack received lacked tips the remote end advertised, as it updated
+    /// its refs in the meantime. The refs were listed again, and the fetch
+    /// retried against the fresh advertisement.
+    Renegotiation,
+    /// The remote end reset the stream listing the refs or fetching was
+    /// carried over. The operation was retried on a new stream.
+    StreamReset,
+}
+
+/// How a ref was moved by a fetch.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum UpdateKind {
+    /// The ref did not exist before.
+    Created,
+    /// The new target is a descendant of the previous one.
+    FastForward,
+    /// The new target is not a descendant of the previous one, eg. because
+    /// the remote peer rewrote its history.
+    Forced,
+    /// The new target is yet to be fetched, so whether it descends from the
+    /// previous one is not known. Only reported for updates which are about to
+    /// be made, see [`crate::git::replication::hooks`].
+    Unresolved,
+}
+
+/// The update of a single ref by a fetch.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TipUpdate {
+    /// The previous target, or `None` if the ref was created.
+    pub old: Option<ext::Oid>,
+    pub new: ext::Oid,
+    pub kind: UpdateKind,
+}
+
+impl TipUpdate {
+    /// Classify the update of a ref from `old` to `new`, where a zero `old`
+    /// denotes that the ref was created.
+    ///
+    /// Targets which are not commits, such as annotated tags, are only
+    /// considered fast-forwards if they didn't change. If `new` is not in
+    /// `repo`, the update is [`UpdateKind::Unresolved`].
+    pub fn classify(repo: &git2::Repository, old: git2::Oid, new: git2::Oid) -> Self {
+        let kind = if old.is_zero() {
+            UpdateKind::Created
+        } else if old == new {
+            UpdateKind::FastForward
+        } else if !repo.odb().map(|odb| odb.exists(new)).unwrap_or(false) {
+            UpdateKind::Unresolved
+        } else if repo.graph_descendant_of(new, old).unwrap_or(false) {
+            UpdateKind::FastForward
+        } else {
+            UpdateKind::Forced
+        };
+
+        Self {
+            old: (!old.is_zero()).then(|| old.into()),
+            new: new.into(),
+            kind,
+        }
+    }
+
+    /// Combine `self` with a subsequent update of the same ref, yielding the
+    /// update from the target before `self` to the target after `next`.
+    ///
+    /// The result is [`UpdateKind::Forced`] if either update was, and
+    /// otherwise [`UpdateKind::Unresolved`] if either update was.
+    pub fn then(self, next: Self) -> Self {
+        let kind = match (self.kind, next.kind) {
+            (UpdateKind::Created, _) => UpdateKind::Created,
+            (UpdateKind::Forced, _) | (_, UpdateKind::Forced) => UpdateKind::Forced,
+            (UpdateKind::Unresolved, _) | (_, UpdateKind::Unresolved) => UpdateKind::Unresolved,
+            _ => UpdateKind::FastForward,
+        };
+
+        Self {
+            old: self.old,
+            new: next.new,
+            kind,
+        }
+    }
+}
+
+/// The metadata of a received pack, as reported by the final transfer
+/// progress.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackStats {
+    /// The number of objects announced in the pack header.
+    pub total_objects: usize,
+    pub received_objects: usize,
+    pub indexed_objects: usize,
+    pub total_deltas: usize,
+    pub indexed_deltas: usize,
+    pub received_bytes: usize,
+}
+
+impl From<&git2::Progress<'_>> for PackStats {
+    fn from(prog: &git2::Progress<'_>) -> Self {
+        Self {
+            total_objects: prog.total_objects(),
+            received_objects: prog.received_objects(),
+            indexed_objects: prog.indexed_objects(),
+            total_deltas: prog.total_deltas(),
+            indexed_deltas: prog.indexed_deltas(),
+            received_bytes: prog.received_bytes(),
+        }
+    }
 }
 
 /// Types which can process [`Fetchspecs`], and update the local storage
@@ -95,4 +316,21 @@ pub trait Fetcher {
         &mut self,
         fetchspecs: Fetchspecs<Self::PeerId, Self::UrnId>,
     ) -> Result<FetchResult, Self::Error>;
+
+    /// The optional [`Features`] negotiated with the remote peer, or `None`
+    /// if they are not known.
+    fn remote_features(&self) -> Option<Features> {
+        None
+    }
+
+    /// How long the remote end took to advertise its refs, or `None` if not
+    /// known.
+    fn ls_refs_duration(&self) -> Option<Duration> {
+        None
+    }
+
+    /// The [`Retry`]s made since the last call.
+    fn take_retries(&mut self) -> Vec<Retry> {
+        Vec::new()
+    }
 }
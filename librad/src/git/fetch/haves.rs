@@ -0,0 +1,134 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! The objects to advertise as `have`s when fetching a namespace.
+//!
+//! With [`Scope::Namespace`], only the tips of the namespace being fetched are
+//! advertised. With [`Scope::Related`], the tips of the local namespaces which
+//! likely share objects with it are advertised as well, namely the upstream
+//! of a forked project (see [`Fork`]). When cloning a fork of a project which
+//! is already stored locally, this lets the remote end omit the history both
+//! have in common from the pack.
+//!
+//! The result is meant to be passed as `link_git_protocol::fetch::Options`'s
+//! `haves`. Fetches driven by libgit2, as done by
+//! [`super::super::replication`], negotiate using every ref of the monorepo,
+//! which subsumes [`Scope::Related`].
+
+use std::collections::BTreeSet;
+
+use git_ext as ext;
+use thiserror::Error;
+
+use crate::{
+    git::{
+        identities,
+        storage::{self, watch, ReadOnly, ReadOnlyStorage as _},
+        Urn,
+    },
+    identities::payload::project::Fork,
+};
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error(transparent)]
+    Identities(#[from] identities::Error),
+
+    #[error(transparent)]
+    Watch(#[from] watch::Error),
+
+    #[error(transparent)]
+    Store(#[from] storage::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+/// Which local namespaces to take `have`s from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Scope {
+    /// Only the namespace being fetched.
+    Namespace,
+    /// The namespace being fetched, and the namespaces sharing objects with
+    /// it.
+    Related,
+}
+
+impl Default for Scope {
+    fn default() -> Self {
+        Self::Namespace
+    }
+}
+
+/// The tips of the refs of `urn`, and of the namespaces related to it as per
+/// `scope`, without duplicates.
+pub fn haves<S>(storage: &S, urn: &Urn, scope: Scope) -> Result<Vec<ext::Oid>, Error>
+where
+    S: AsRef<ReadOnly>,
+{
+    let storage = storage.as_ref();
+    let urns = match scope {
+        Scope::Namespace => vec![urn.clone().with_path(None)],
+        Scope::Related => related(storage, urn)?,
+    };
+
+    let mut tips = BTreeSet::new();
+    for urn in urns {
+        tips.extend(watch::snapshot(storage, &urn)?.values().copied());
+    }
+
+    Ok(tips.into_iter().collect())
+}
+
+/// The namespaces sharing objects with `urn`, including `urn` itself.
+///
+/// If `urn` is a fork, its upstream is related to it, provided it is present
+/// in `storage`. Whether the project is a fork is determined from the local
+/// `rad/id` if there is one, otherwise from the `rad/id` of any remote, as is
+/// the case after peeking a project which is being cloned.
+pub fn related<S>(storage: &S, urn: &Urn) -> Result<Vec<Urn>, Error>
+where
+    S: AsRef<ReadOnly>,
+{
+    let storage = storage.as_ref();
+    let urn = urn.clone().with_path(None);
+
+    let mut related = vec![urn.clone()];
+    if let Some(Fork { upstream }) = fork(storage, &urn)? {
+        let upstream = upstream.with_path(None);
+        if upstream != urn && storage.has_urn(&upstream)? {
+            related.push(upstream);
+        }
+    }
+
+    Ok(related)
+}
+
+fn fork(storage: &ReadOnly, urn: &Urn) -> Result<Option<Fork>, Error> {
+    if let Some(project) = identities::project::get(storage, urn)? {
+        return Ok(project.payload().fork()?);
+    }
+
+    // `refs/remotes/<peer>/rad/id`, but not the refs of remotes of remotes
+    let ids = watch::snapshot(storage, urn)?
+        .into_iter()
+        .map(|(name, _)| name)
+        .filter(|name| {
+            let name = name.as_str();
+            name.starts_with("refs/remotes/")
+                && name.ends_with("/rad/id")
+                && name.split('/').count() == 5
+        });
+    for name in ids {
+        if let Some(project) = identities::project::get(storage, &urn.clone().with_path(name))? {
+            if let Some(fork) = project.payload().fork()? {
+                return Ok(Some(fork));
+            }
+        }
+    }
+
+    Ok(None)
+}
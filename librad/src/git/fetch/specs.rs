@@ -27,7 +27,7 @@ use crate::{
 /// Seed value to compute the fetchspecs for the desired fetch phase from.
 ///
 /// See also: [`crate::git::replication::replicate`]
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum Fetchspecs<P, R> {
     /// Request all identity documents
     PeekAll { limit: Limit },
@@ -322,6 +322,10 @@ pub mod refspecs {
                 );
                 // Only include the advertised ref if its target OID
                 // is the same as the signed one.
+                //
+                // Annotated tags may have been signed as the object they peel
+                // to, in which case the advertised tag is included if it peels
+                // to the signed object.
                 let targets_match = {
                     let found = remote_heads.get(&namespaced_name);
                     match found {
@@ -333,6 +337,15 @@ pub mod refspecs {
                         Some(remote_target) => {
                             if remote_target == &*target {
                                 true
+                            } else if category == RefsCategory::Tags
+                                && remote_heads.peeled(&namespaced_name) == Some(&*target)
+                            {
+                                tracing::debug!(
+                                    "{} peels to the signed target {}",
+                                    namespaced_name,
+                                    target
+                                );
+                                true
                             } else {
                                 tracing::warn!(
                                     "{} target mismatch: expected {}, got {}",
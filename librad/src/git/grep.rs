@@ -0,0 +1,343 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! A trigram index over the contents of selected namespaces, allowing to
+//! search for text without checking out working copies.
+//!
+//! The index of a namespace covers the files of a set of branches, typically
+//! the canonical heads of a project, which are passed to [`update`] whenever
+//! they change. Since blobs are content-addressed, an update only reads the
+//! blobs which aren't indexed yet, and forgets those which are no longer part
+//! of any branch.
+//!
+//! For every indexed blob, the index records the sequences of three bytes
+//! (trigrams) occurring in it. [`search`] only reads the blobs which contain
+//! all trigrams of the pattern. Binary blobs, and blobs larger than
+//! [`MAX_BLOB_SIZE`], are not indexed.
+//!
+//! Indexes are stored alongside the monorepo, one file per namespace.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+use git_ext as ext;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{
+    storage::{self, ReadOnly, ReadOnlyStorage as _},
+    Urn,
+};
+
+const INDEX_DIR: &str = "grep";
+
+/// Blobs larger than this are not indexed.
+pub const MAX_BLOB_SIZE: usize = 1024 * 1024;
+
+/// Blobs containing a NUL byte within this many leading bytes are considered
+/// binary, like git does.
+const BINARY_PROBE: usize = 8000;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("the search pattern is empty")]
+    EmptyPattern,
+
+    #[error("commit {0} not found in storage")]
+    MissingCommit(ext::Oid),
+
+    #[error(transparent)]
+    Store(#[from] storage::Error),
+
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+/// A line matching the pattern passed to [`search`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Match {
+    pub urn: Urn,
+    /// The fully qualified name of the branch the file is on.
+    pub branch: String,
+    pub path: String,
+    /// The line number, starting at 1.
+    pub line: usize,
+    pub text: String,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Index {
+    urn: Urn,
+    /// The indexed commit of every branch.
+    heads: BTreeMap<String, ext::Oid>,
+    /// The files of every branch, by path.
+    files: BTreeMap<String, BTreeMap<String, ext::Oid>>,
+    /// The blobs which are indexed.
+    blobs: BTreeSet<ext::Oid>,
+    /// The blobs which are binary or too large to be indexed.
+    skipped: BTreeSet<ext::Oid>,
+    /// The blobs containing each trigram.
+    trigrams: BTreeMap<u32, BTreeSet<ext::Oid>>,
+}
+
+impl Index {
+    fn new(urn: Urn) -> Self {
+        Self {
+            urn,
+            heads: BTreeMap::new(),
+            files: BTreeMap::new(),
+            blobs: BTreeSet::new(),
+            skipped: BTreeSet::new(),
+            trigrams: BTreeMap::new(),
+        }
+    }
+
+    fn path(storage: &ReadOnly, urn: &Urn) -> PathBuf {
+        storage
+            .path()
+            .join(INDEX_DIR)
+            .join(format!("{}.json", urn.encode_id()))
+    }
+
+    fn load(path: &Path) -> Result<Option<Self>, Error> {
+        match fs::read(path) {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self, path: &Path) -> Result<(), Error> {
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        fs::create_dir_all(dir)?;
+        let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+        serde_json::to_writer(&mut tmp, self)?;
+        tmp.persist(path).map_err(|e| e.error)?;
+        Ok(())
+    }
+
+    /// The indexed blobs which may contain `pattern`.
+    fn candidates(&self, pattern: &[u8]) -> BTreeSet<ext::Oid> {
+        let mut candidates = self.blobs.clone();
+        for trigram in trigrams(pattern) {
+            match self.trigrams.get(&trigram) {
+                Some(blobs) => candidates.retain(|blob| blobs.contains(blob)),
+                None => return BTreeSet::new(),
+            }
+        }
+        candidates
+    }
+}
+
+/// Update the index of `urn` to cover exactly the branches `heads`, given as
+/// fully qualified names and the commits they point to.
+///
+/// The index is created if it doesn't exist yet.
+pub fn update<S>(storage: &S, urn: &Urn, heads: &BTreeMap<String, ext::Oid>) -> Result<(), Error>
+where
+    S: AsRef<ReadOnly>,
+{
+    let storage = storage.as_ref();
+    let urn = urn.clone().with_path(None);
+    let path = Index::path(storage, &urn);
+    let mut index = Index::load(&path)?.unwrap_or_else(|| Index::new(urn));
+
+    index.heads.retain(|name, _| heads.contains_key(name));
+    index.files.retain(|name, _| heads.contains_key(name));
+    for (name, commit) in heads {
+        if index.heads.get(name) == Some(commit) {
+            continue;
+        }
+        index.files.insert(name.clone(), files(storage, *commit)?);
+        index.heads.insert(name.clone(), *commit);
+    }
+
+    let referenced = index
+        .files
+        .values()
+        .flat_map(|files| files.values().copied())
+        .collect::<BTreeSet<_>>();
+
+    let gone = index
+        .blobs
+        .difference(&referenced)
+        .copied()
+        .collect::<BTreeSet<_>>();
+    if !gone.is_empty() {
+        for blobs in index.trigrams.values_mut() {
+            blobs.retain(|blob| !gone.contains(blob));
+        }
+        index.trigrams.retain(|_, blobs| !blobs.is_empty());
+        index.blobs.retain(|blob| !gone.contains(blob));
+    }
+    index.skipped.retain(|blob| referenced.contains(blob));
+
+    for oid in referenced {
+        if index.blobs.contains(&oid) || index.skipped.contains(&oid) {
+            continue;
+        }
+        match read(storage, oid)? {
+            Some(content) => {
+                for trigram in trigrams(&content) {
+                    index.trigrams.entry(trigram).or_default().insert(oid);
+                }
+                index.blobs.insert(oid);
+            },
+            None => {
+                index.skipped.insert(oid);
+            },
+        }
+    }
+
+    index.save(&path)
+}
+
+/// Remove the index of `urn`, returning whether it existed.
+pub fn remove<S>(storage: &S, urn: &Urn) -> Result<bool, Error>
+where
+    S: AsRef<ReadOnly>,
+{
+    match fs::remove_file(Index::path(storage.as_ref(), urn)) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Search the indexed files for lines containing `pattern`.
+///
+/// If `urn` is given, only its index is searched, otherwise all indexes of
+/// `storage`. The pattern is matched literally and case-sensitively.
+pub fn search<S>(storage: &S, pattern: &str, urn: Option<&Urn>) -> Result<Vec<Match>, Error>
+where
+    S: AsRef<ReadOnly>,
+{
+    if pattern.is_empty() {
+        return Err(Error::EmptyPattern);
+    }
+    let storage = storage.as_ref();
+
+    let paths = match urn {
+        Some(urn) => vec![Index::path(storage, &urn.clone().with_path(None))],
+        None => match fs::read_dir(storage.path().join(INDEX_DIR)) {
+            Ok(entries) => entries
+                .map(|entry| entry.map(|entry| entry.path()))
+                .filter(|path| {
+                    path.as_ref()
+                        .map(|path| path.extension().map_or(false, |ext| ext == "json"))
+                        .unwrap_or(true)
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e.into()),
+        },
+    };
+
+    let mut matches = Vec::new();
+    for path in paths {
+        let index = match Index::load(&path)? {
+            Some(index) => index,
+            None => continue,
+        };
+        let candidates = index.candidates(pattern.as_bytes());
+        if candidates.is_empty() {
+            continue;
+        }
+
+        let mut contents = BTreeMap::new();
+        for (branch, files) in &index.files {
+            for (file, blob) in files {
+                if !candidates.contains(blob) {
+                    continue;
+                }
+                if !contents.contains_key(blob) {
+                    contents.insert(*blob, read(storage, *blob)?.unwrap_or_default());
+                }
+                let content = String::from_utf8_lossy(&contents[blob]);
+                for (n, line) in content.lines().enumerate() {
+                    if line.contains(pattern) {
+                        matches.push(Match {
+                            urn: index.urn.clone(),
+                            branch: branch.clone(),
+                            path: file.clone(),
+                            line: n + 1,
+                            text: line.to_owned(),
+                        })
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(matches)
+}
+
+/// The blobs in the tree of `commit`, by path. Submodules are omitted.
+fn files(storage: &ReadOnly, commit: ext::Oid) -> Result<BTreeMap<String, ext::Oid>, Error> {
+    let tree = storage
+        .find_object(commit)?
+        .ok_or(Error::MissingCommit(commit))?
+        .peel_to_tree()?;
+
+    let mut files = BTreeMap::new();
+    let mut trees = vec![(String::new(), tree)];
+    while let Some((prefix, tree)) = trees.pop() {
+        for entry in tree.iter() {
+            let name = match entry.name() {
+                Some(name) => name,
+                None => continue,
+            };
+            let oid = ext::Oid::from(entry.id());
+            match entry.kind() {
+                Some(git2::ObjectType::Tree) => {
+                    if let Some(obj) = storage.find_object(oid)? {
+                        trees.push((format!("{}{}/", prefix, name), obj.peel_to_tree()?));
+                    }
+                },
+                Some(git2::ObjectType::Blob) => {
+                    files.insert(format!("{}{}", prefix, name), oid);
+                },
+                _ => {},
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// The content of the blob `oid`, or `None` if it is not indexable.
+fn read(storage: &ReadOnly, oid: ext::Oid) -> Result<Option<Vec<u8>>, Error> {
+    let obj = match storage.find_object(oid)? {
+        Some(obj) => obj,
+        None => return Ok(None),
+    };
+    let blob = obj.peel_to_blob()?;
+    let content = blob.content();
+    if content.len() > MAX_BLOB_SIZE || content.iter().take(BINARY_PROBE).any(|b| *b == 0) {
+        return Ok(None);
+    }
+
+    Ok(Some(content.to_vec()))
+}
+
+fn trigrams(bytes: &[u8]) -> BTreeSet<u32> {
+    bytes
+        .windows(3)
+        .map(|w| u32::from(w[0]) << 16 | u32::from(w[1]) << 8 | u32::from(w[2]))
+        .collect()
+}
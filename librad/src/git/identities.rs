@@ -4,6 +4,7 @@
 // Linking Exception. For full terms see the included LICENSE file.
 
 pub mod any;
+pub mod checkpoint;
 pub mod error;
 pub mod local;
 pub mod person;
@@ -0,0 +1,99 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Persisted [`Checkpoint`]s of identity verifications.
+//!
+//! Checkpoints are stored in the git directory of the monorepo, keyed by the
+//! [`Urn`] (including its path) of the identity branch they were obtained
+//! from. They are not replicated.
+
+use std::{
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+use super::super::storage::{self, Storage};
+use crate::identities::git::{Checkpoint, Urn};
+
+const CHECKPOINTS_DIR: &str = "identities/checkpoints";
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+}
+
+/// Load the [`Checkpoint`] recorded for `urn`, if any.
+pub fn load<S>(storage: &S, urn: &Urn) -> Result<Option<Checkpoint>, Error>
+where
+    S: AsRef<storage::ReadOnly>,
+{
+    let path = checkpoint_path(storage.as_ref().path(), urn)?;
+    match fs::read(path) {
+        Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Record `checkpoint` for `urn`, replacing any previous one.
+pub fn store(storage: &Storage, urn: &Urn, checkpoint: &Checkpoint) -> Result<(), Error> {
+    let path = checkpoint_path(storage.path(), urn)?;
+    let dir = path.parent().expect("checkpoint path has a parent");
+    fs::create_dir_all(dir)?;
+    // Write to a temporary file first, so concurrent readers never observe a
+    // partially written checkpoint.
+    let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+    serde_json::to_writer(&mut tmp, checkpoint)?;
+    tmp.persist(path).map_err(|e| e.error)?;
+
+    Ok(())
+}
+
+/// Remove all recorded checkpoints.
+pub fn clear(storage: &Storage) -> Result<(), Error> {
+    match fs::remove_dir_all(storage.path().join(CHECKPOINTS_DIR)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Run `verify`, resuming from the checkpoint recorded for `urn`, and record
+/// the resulting checkpoint.
+///
+/// Failure to load or store the checkpoint is not fatal: the history is then
+/// verified from the root, or the checkpoint is not updated, respectively.
+pub(super) fn resuming<T, E, F>(storage: &Storage, urn: &Urn, verify: F) -> Result<T, E>
+where
+    F: FnOnce(Option<Checkpoint>) -> Result<(T, Checkpoint), E>,
+{
+    let resume_from = load(storage, urn).unwrap_or_else(|e| {
+        tracing::warn!(err = %e, "failed to load verification checkpoint");
+        None
+    });
+    let (verified, checkpoint) = verify(resume_from)?;
+    if let Err(e) = store(storage, urn, &checkpoint) {
+        tracing::warn!(err = %e, "failed to store verification checkpoint");
+    }
+
+    Ok(verified)
+}
+
+fn checkpoint_path(git_dir: &Path, urn: &Urn) -> Result<PathBuf, Error> {
+    // The URN path may contain slashes, so use a digest of the whole URN as
+    // the file name.
+    let key = git2::Oid::hash_object(git2::ObjectType::Blob, urn.to_string().as_bytes())?;
+    Ok(git_dir.join(CHECKPOINTS_DIR).join(key.to_string()))
+}
@@ -61,6 +61,9 @@ pub enum Error {
     #[error(transparent)]
     ProjHist(#[from] identities::git::error::History<identities::git::ProjectDoc>),
 
+    #[error(transparent)]
+    PayloadExt(#[from] identities::payload::ExtError),
+
     #[error(transparent)]
     Git(#[from] git2::Error),
 }
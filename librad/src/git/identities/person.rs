@@ -13,6 +13,7 @@ use super::{
         storage::{self, ReadOnlyStorage as _, Storage},
         types::Reference,
     },
+    checkpoint,
     common,
     error::Error,
     local::LocalIdentity,
@@ -21,7 +22,7 @@ use crate::{
     identities::{
         self,
         delegation,
-        git::{Identities, Verifying},
+        git::{Identities, Progress, Verifying},
         urn,
     },
     PeerId,
@@ -86,6 +87,37 @@ where
     }
 }
 
+/// Like [`verify`], but report `progress` for every identity commit visited.
+///
+/// Verification resumes from the [`checkpoint`] recorded by a previous
+/// invocation for `urn`, if it is still part of the history. This makes
+/// repeated verifications of long histories cheap.
+#[tracing::instrument(level = "debug", skip(storage, progress))]
+pub fn verify_with_progress<P>(
+    storage: &Storage,
+    urn: &Urn,
+    progress: P,
+) -> Result<Option<VerifiedPerson>, Error>
+where
+    P: FnMut(Progress),
+{
+    match storage.reference(&Reference::try_from(urn)?) {
+        Ok(Some(reference)) => {
+            let tip = reference.peel_to_commit()?.id();
+            checkpoint::resuming(storage, urn, |resume_from| {
+                identities(storage)
+                    .verify_with(tip, resume_from, progress)
+                    .map_err(|e| Error::Verify(e.into()))
+            })
+            .map(Some)
+        },
+
+        Ok(None) => Ok(None),
+        Err(storage::Error::Git(e)) if is_not_found_err(&e) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
 /// Get the root [`Urn`] for the given `payload` and set of `delegations`.
 #[tracing::instrument(level = "debug", skip(storage))]
 pub fn urn<S, P>(storage: &S, payload: P, delegations: delegation::Direct) -> Result<Urn, Error>
@@ -14,6 +14,7 @@ use super::{
         storage::{self, ReadOnlyStorage as _, Storage},
         types::{namespace, reference, Force, Reference, Single, SymbolicRef},
     },
+    checkpoint,
     common,
     error::Error,
     local::LocalIdentity,
@@ -21,7 +22,16 @@ use super::{
 use crate::{
     identities::{
         self,
-        git::{Identities, IndirectDelegation, Project, Revision, VerifiedProject, Verifying},
+        git::{
+            Identities,
+            IndirectDelegation,
+            Progress,
+            Project,
+            Revision,
+            VerifiedProject,
+            Verifying,
+        },
+        payload::project::Fork,
         urn,
     },
     PeerId,
@@ -116,6 +126,42 @@ where
     }
 }
 
+/// Like [`verify`], but report `progress` for every identity commit visited.
+///
+/// Verification resumes from the [`checkpoint`] recorded by a previous
+/// invocation for `urn`, if it is still part of the history. This makes
+/// repeated verifications of long histories cheap. The indirect delegations
+/// are always resolved anew.
+#[tracing::instrument(level = "debug", skip(storage, progress))]
+pub fn verify_with_progress<P>(
+    storage: &Storage,
+    urn: &Urn,
+    progress: P,
+) -> Result<Option<VerifiedProject>, Error>
+where
+    P: FnMut(Progress),
+{
+    let lookup = |urn| {
+        let refname = Reference::rad_id(Namespace::from(urn));
+        storage.reference_oid(&refname).map(|oid| oid.into())
+    };
+    match storage.reference(&Reference::try_from(urn)?) {
+        Ok(Some(reference)) => {
+            let tip = reference.peel_to_commit()?.id();
+            checkpoint::resuming(storage, urn, |resume_from| {
+                identities(storage)
+                    .verify_with(tip, lookup, resume_from, progress)
+                    .map_err(|e| Error::Verify(e.into()))
+            })
+            .map(Some)
+        },
+
+        Ok(None) => Ok(None),
+        Err(storage::Error::Git(e)) if is_not_found_err(&e) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
 /// Get the root [`Urn`] for the given `payload` and set of `delegations`.
 #[tracing::instrument(level = "debug", skip(storage))]
 pub fn urn<S, P>(storage: &S, payload: P, delegations: IndirectDelegation) -> Result<Urn, Error>
@@ -199,6 +245,48 @@ pub fn merge(storage: &Storage, urn: &Urn, from: PeerId) -> Result<Project, Erro
     Ok(next)
 }
 
+/// Fork the [`Project`] at `urn`, creating a new project owned by `whoami`.
+///
+/// The fork has the same payload as the original, extended by a [`Fork`]
+/// pointing back to `urn`, and `whoami` as its only delegate. The branches,
+/// tags and notes of the local peer are copied into the namespace of the fork.
+/// Since all namespaces share the same object database, no objects are copied,
+/// so forking is cheap regardless of the size of the project.
+///
+/// Note that forking the same project twice with the same `whoami` yields the
+/// same [`Urn`], and so fails on the second attempt.
+#[tracing::instrument(level = "debug", skip(storage, whoami))]
+pub fn fork(storage: &Storage, urn: &Urn, whoami: LocalIdentity) -> Result<Project, Error> {
+    const CATEGORIES: [&str; 3] = ["heads", "tags", "notes"];
+
+    let orig = get(storage, urn)?.ok_or_else(|| Error::NotFound(urn.clone()))?;
+    let delegations = IndirectDelegation::from(whoami.clone().into_inner().into_inner());
+    let payload = orig.payload().clone().with_ext(Fork {
+        upstream: Urn::new(urn.id),
+    })?;
+    let project = identities(storage).create(payload, delegations, storage.signer())?;
+    let fork = project.urn();
+    ProjectRefs::Create(&project).apply(storage)?;
+    whoami.link(storage, &fork)?;
+
+    let repo = storage.as_raw();
+    let src = format!("refs/namespaces/{}/refs/", Namespace::from(urn));
+    let dst = format!("refs/namespaces/{}/refs/", Namespace::from(&fork));
+    let msg = format!("fork of {}", urn);
+    for category in &CATEGORIES {
+        for r in repo.references_glob(&format!("{}{}/*", src, category))? {
+            let r = r?;
+            let name = r.name().and_then(|name| name.strip_prefix(src.as_str()));
+            if let (Some(name), Some(oid)) = (name, r.target()) {
+                repo.reference(&format!("{}{}", dst, name), oid, false, &msg)?;
+            }
+        }
+    }
+    Sigrefs::update(storage, &fork)?;
+
+    Ok(project)
+}
+
 /// Return the newer of `a` and `b`, or an error if their histories are
 /// unrelated.
 pub fn newer<S>(
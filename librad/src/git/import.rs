@@ -0,0 +1,159 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Import of repositories hosted elsewhere as new projects.
+//!
+//! [`import`] creates a project delegating to the local identity, and
+//! populates the local peer's view of it with the `heads`, `tags` and `notes`
+//! of the external repository, as if they had been pushed by the local peer.
+//! The external repository can be [`pull`]ed again later to publish its
+//! updates.
+//!
+//! The external repository is authoritative: refs are force-updated, and refs
+//! which were deleted from it are deleted from the namespace.
+
+use thiserror::Error;
+
+use super::{
+    identities::{self, local::LocalIdentity, project, Project},
+    refs::{self, Refs},
+    storage::Storage,
+    types::Namespace,
+    Urn,
+};
+use crate::identities::{delegation, payload};
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("could not determine the default branch of the repository, please provide one")]
+    UnknownDefaultBranch,
+
+    #[error("branch `{0}` not found in the repository")]
+    MissingBranch(String),
+
+    #[error("could not derive a project name from the URL, please provide one")]
+    UnknownName,
+
+    #[error(transparent)]
+    Identities(#[from] Box<identities::Error>),
+
+    #[error(transparent)]
+    Refs(#[from] refs::stored::Error),
+
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+}
+
+impl From<identities::Error> for Error {
+    fn from(e: identities::Error) -> Self {
+        Self::Identities(Box::new(e))
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// The name of the project. If `None`, it is derived from the last path
+    /// component of the URL.
+    pub name: Option<String>,
+    pub description: Option<String>,
+    /// The default branch of the project. If `None`, the branch `HEAD` of the
+    /// external repository points to is used.
+    pub default_branch: Option<String>,
+}
+
+/// Create a project from the repository at `url`, delegating to `whoami`.
+///
+/// The `heads`, `tags` and `notes` of the repository are fetched into the
+/// namespace of the new project, and the signed refs are updated.
+///
+/// Note that `url` may contain credentials, and is thus neither logged nor
+/// part of any error.
+#[tracing::instrument(skip(storage, whoami, url, options))]
+pub fn import(
+    storage: &Storage,
+    whoami: LocalIdentity,
+    url: &str,
+    options: Options,
+) -> Result<Project, Error> {
+    let default_branch = {
+        let mut remote = storage.as_raw().remote_anonymous(url)?;
+        remote.connect(git2::Direction::Fetch)?;
+        let branch = match options.default_branch {
+            Some(branch) => branch,
+            None => remote
+                .default_branch()
+                .ok()
+                .and_then(|head| {
+                    head.as_str()
+                        .and_then(|head| head.strip_prefix("refs/heads/"))
+                        .map(ToOwned::to_owned)
+                })
+                .ok_or(Error::UnknownDefaultBranch)?,
+        };
+        let head = format!("refs/heads/{}", branch);
+        if !remote.list()?.iter().any(|r| r.name() == head) {
+            return Err(Error::MissingBranch(branch));
+        }
+        remote.disconnect()?;
+        branch
+    };
+    let name = match options.name {
+        Some(name) => name,
+        None => name_from_url(url).ok_or(Error::UnknownName)?,
+    };
+
+    let delegations = delegation::Indirect::from(whoami.clone().into_inner().into_inner());
+    let proj = project::create(
+        storage,
+        whoami,
+        payload::Project {
+            name: name.into(),
+            description: options.description.map(Into::into),
+            default_branch: Some(default_branch.into()),
+        },
+        delegations,
+    )?;
+    pull(storage, &proj.urn(), url)?;
+
+    Ok(proj)
+}
+
+/// Fetch the `heads`, `tags` and `notes` of the repository at `url` into the
+/// local peer's view of `urn`, and update the signed refs.
+#[tracing::instrument(skip(storage, url), fields(urn = %urn))]
+pub fn pull(storage: &Storage, urn: &Urn, url: &str) -> Result<refs::Updated, Error> {
+    let namespace = Namespace::from(urn.clone().with_path(None));
+    let refspecs = ["heads", "notes", "tags"]
+        .iter()
+        .map(|category| {
+            format!(
+                "+refs/{category}/*:refs/namespaces/{ns}/refs/{category}/*",
+                category = category,
+                ns = namespace
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let mut remote = storage.as_raw().remote_anonymous(url)?;
+    let mut opts = git2::FetchOptions::new();
+    opts.prune(git2::FetchPrune::On)
+        .download_tags(git2::AutotagOption::None)
+        .update_fetchhead(false);
+    remote.fetch(&refspecs, Some(&mut opts), None)?;
+
+    Ok(Refs::update(storage, urn)?)
+}
+
+/// Derive a project name from the last path component of `url`, eg.
+/// `radicle-link` for `https://github.com/radicle-dev/radicle-link.git`.
+pub fn name_from_url(url: &str) -> Option<String> {
+    let last = url
+        .trim_end_matches('/')
+        .rsplit(|c| c == '/' || c == ':')
+        .next()?;
+    let name = last.strip_suffix(".git").unwrap_or(last);
+    (!name.is_empty()).then(|| name.to_owned())
+}
@@ -0,0 +1,347 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Notifications derived from replicated data.
+//!
+//! After each successful [`super::replication::replicate`], the refs of the
+//! namespace are compared to their state before the replication, and a
+//! [`Notification`] is recorded for:
+//!
+//! * new commits on the branches of remote peers
+//! * new changes to collaborative objects which mention the local peer, or the
+//!   default identity of the local peer
+//! * identity updates of the delegates, which may need to be confirmed
+//!
+//! Notifications are appended to a log file stored alongside the monorepo,
+//! which can be queried using [`list`].
+
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom as _,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead as _, BufReader, Write as _},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use git_ext as ext;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{
+    identities,
+    replication::{IdStatus, Mode, ReplicateResult},
+    storage::{watch, ReadOnly, Storage},
+    Urn,
+};
+use crate::PeerId;
+
+const INBOX_FILE: &str = "inbox.log";
+
+/// The maximum number of commits inspected per updated ref.
+const MAX_WALK: usize = 256;
+
+/// Blobs larger than this are not searched for mentions.
+const MAX_BLOB_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error(transparent)]
+    Watch(#[from] watch::Error),
+
+    #[error(transparent)]
+    LocalId(#[from] identities::local::Error),
+
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Notification {
+    pub urn: Urn,
+    /// The peer the data was replicated from.
+    pub remote_peer: PeerId,
+    /// Time of the replication, in seconds since the UNIX epoch.
+    pub timestamp: u64,
+    pub kind: Kind,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum Kind {
+    /// A branch of `peer` was created or advanced.
+    #[serde(rename_all = "camelCase")]
+    Commits {
+        peer: PeerId,
+        branch: ext::RefLike,
+        old: Option<ext::Oid>,
+        new: ext::Oid,
+        /// The number of new commits, capped at an internal limit.
+        count: usize,
+    },
+    /// A change to a collaborative object of `peer` mentions the local peer.
+    #[serde(rename_all = "camelCase")]
+    Mention {
+        peer: PeerId,
+        /// The name of the object, ie. `<typename>/<object id>`.
+        object: ext::RefLike,
+        change: ext::Oid,
+    },
+    /// The identity document was updated by the delegates, and the local view
+    /// may need to be confirmed.
+    IdentityUpdate,
+}
+
+fn inbox_path(storage: &ReadOnly) -> PathBuf {
+    storage.path().join(INBOX_FILE)
+}
+
+/// Derive and record the [`Notification`]s for a replication of `urn`, given
+/// the [`watch::snapshot`] of the namespace taken `before` the replication.
+pub(crate) fn update(
+    storage: &Storage,
+    urn: &Urn,
+    remote_peer: PeerId,
+    before: &BTreeMap<ext::RefLike, ext::Oid>,
+    result: &ReplicateResult,
+) -> Result<Vec<Notification>, Error> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let mut kinds = Vec::new();
+
+    // Everything is new after a clone, which isn't worth notifying about.
+    if matches!(result.mode, Mode::Fetch) && !result.updated_tips.is_empty() {
+        let after = watch::snapshot(storage.read_only(), urn)?;
+        let needles = mention_needles(storage)?;
+        let repo = storage.as_raw();
+        for update in watch::diff(before, &after) {
+            let new = match update.new {
+                Some(new) => new,
+                None => continue,
+            };
+            match classify(&update.name) {
+                Some((peer, Category::Heads, branch)) => {
+                    let count = walk(repo, new, update.old, false)?.len();
+                    if count > 0 {
+                        kinds.push(Kind::Commits {
+                            peer,
+                            branch,
+                            old: update.old,
+                            new,
+                            count,
+                        })
+                    }
+                },
+                Some((peer, Category::Cobs, object)) => {
+                    for change in walk(repo, new, update.old, true)? {
+                        if mentions(repo, change, &needles)? {
+                            kinds.push(Kind::Mention {
+                                peer,
+                                object: object.clone(),
+                                change: change.into(),
+                            })
+                        }
+                    }
+                },
+                None => {},
+            }
+        }
+    }
+    if matches!(result.identity, IdStatus::Uneven) {
+        kinds.push(Kind::IdentityUpdate)
+    }
+
+    let notifications = kinds
+        .into_iter()
+        .map(|kind| Notification {
+            urn: urn.clone(),
+            remote_peer,
+            timestamp,
+            kind,
+        })
+        .collect::<Vec<_>>();
+    record(storage, &notifications)?;
+
+    Ok(notifications)
+}
+
+/// Append `notifications` to the inbox of `storage`.
+pub fn record(storage: &Storage, notifications: &[Notification]) -> Result<(), Error> {
+    if notifications.is_empty() {
+        return Ok(());
+    }
+
+    let mut lines = Vec::new();
+    for notification in notifications {
+        serde_json::to_writer(&mut lines, notification)?;
+        lines.push(b'\n');
+    }
+    // See `replication::audit::record`
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(inbox_path(storage.read_only()))?;
+    file.write_all(&lines)?;
+
+    Ok(())
+}
+
+/// Read the notifications from the inbox of `storage`, oldest first.
+///
+/// If `urn` is given, only notifications pertaining to that [`Urn`] are
+/// returned. If `since` is given, only notifications recorded at or after
+/// that time (in seconds since the UNIX epoch) are returned. Malformed lines
+/// are skipped.
+pub fn list<S>(
+    storage: &S,
+    urn: Option<&Urn>,
+    since: Option<u64>,
+) -> Result<Vec<Notification>, Error>
+where
+    S: AsRef<ReadOnly>,
+{
+    let file = match File::open(inbox_path(storage.as_ref())) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut notifications = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        let notification = match serde_json::from_str::<Notification>(&line) {
+            Ok(notification) => notification,
+            Err(e) => {
+                tracing::warn!(err = %e, "skipping malformed inbox entry");
+                continue;
+            },
+        };
+        if urn.map(|urn| urn == &notification.urn).unwrap_or(true)
+            && since
+                .map(|since| notification.timestamp >= since)
+                .unwrap_or(true)
+        {
+            notifications.push(notification)
+        }
+    }
+
+    Ok(notifications)
+}
+
+/// Remove all notifications from the inbox of `storage`.
+pub fn clear<S>(storage: &S) -> Result<(), Error>
+where
+    S: AsRef<ReadOnly>,
+{
+    match fs::remove_file(inbox_path(storage.as_ref())) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+enum Category {
+    Heads,
+    Cobs,
+}
+
+/// Split `refs/remotes/<peer>/<heads|cobs>/<rest>` into its components.
+fn classify(name: &ext::RefLike) -> Option<(PeerId, Category, ext::RefLike)> {
+    let mut components = name.as_str().splitn(5, '/');
+    match (
+        components.next(),
+        components.next(),
+        components.next(),
+        components.next(),
+        components.next(),
+    ) {
+        (Some("refs"), Some("remotes"), Some(peer), Some(category), Some(rest)) => {
+            let peer = peer.parse().ok()?;
+            let category = match category {
+                "heads" => Category::Heads,
+                "cobs" => Category::Cobs,
+                _ => return None,
+            };
+            let rest = ext::RefLike::try_from(rest).ok()?;
+            Some((peer, category, rest))
+        },
+        _ => None,
+    }
+}
+
+/// The commits reachable from `new`, but not from `old`, newest first.
+///
+/// Collaborative objects anchor the commits they refer to as additional
+/// parents, so their histories are walked along the first parent only.
+fn walk(
+    repo: &git2::Repository,
+    new: ext::Oid,
+    old: Option<ext::Oid>,
+    first_parent: bool,
+) -> Result<Vec<git2::Oid>, Error> {
+    let mut revwalk = repo.revwalk()?;
+    if first_parent {
+        revwalk.simplify_first_parent()?;
+    }
+    revwalk.push(*new)?;
+    if let Some(old) = old {
+        // The previous tip may be gone if the history was rewritten
+        if repo.find_commit(*old).is_ok() {
+            revwalk.hide(*old)?;
+        }
+    }
+
+    revwalk
+        .take(MAX_WALK)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Error::from)
+}
+
+/// The strings identifying the local peer.
+fn mention_needles(storage: &Storage) -> Result<Vec<String>, Error> {
+    let mut needles = vec![storage.peer_id().to_string()];
+    if let Some(local) = identities::local::default(storage)? {
+        needles.push(local.urn().encode_id())
+    }
+    Ok(needles)
+}
+
+/// `true` if the message of `commit`, or any blob at the root of its tree
+/// contains one of `needles`.
+fn mentions(repo: &git2::Repository, commit: git2::Oid, needles: &[String]) -> Result<bool, Error> {
+    let commit = repo.find_commit(commit)?;
+    let contains = |haystack: &[u8]| {
+        needles.iter().any(|needle| {
+            haystack
+                .windows(needle.len())
+                .any(|w| w == needle.as_bytes())
+        })
+    };
+    if contains(commit.message_bytes()) {
+        return Ok(true);
+    }
+
+    let tree = commit.tree()?;
+    for entry in tree.iter() {
+        if entry.kind() != Some(git2::ObjectType::Blob) {
+            continue;
+        }
+        let blob = repo.find_blob(entry.id())?;
+        if blob.size() <= MAX_BLOB_SIZE && contains(blob.content()) {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
@@ -0,0 +1,377 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Storage of large objects outside of git.
+//!
+//! Blobs exceeding a size threshold (see [`DEFAULT_THRESHOLD`]) would bloat
+//! the packfiles exchanged during replication, and quickly exceed the fetch
+//! limits. Instead, their content can be [`Store::offload`]ed into a
+//! content-addressed [`Store`] alongside the monorepo, and only a small
+//! [`Pointer`] blob be committed in their place.
+//!
+//! Replicating peers find the pointers in the trees they fetched (see
+//! [`pointers`]), and download the objects they don't have yet in [`Chunk`]s
+//! via a separate request, see
+//! [`crate::net::protocol::interrogation::Request::GetLargeObject`].
+//! Incomplete downloads are kept, such that they can be resumed from where
+//! they stopped. An object only becomes available in the store once its
+//! content matches its [`Oid`].
+
+use std::{
+    collections::BTreeSet,
+    convert::TryFrom,
+    fmt,
+    fs,
+    io::{self, Read as _, Seek as _, SeekFrom, Write as _},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use sha2::{Digest as _, Sha256};
+use thiserror::Error;
+
+use super::storage::Storage;
+use crate::paths::Paths;
+
+/// Blobs larger than this (in bytes) should be offloaded.
+pub const DEFAULT_THRESHOLD: u64 = 10 * 1024 * 1024;
+
+/// The maximum size of a [`Chunk`] served to other peers.
+pub const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Blobs larger than this can't be [`Pointer`]s, and are not inspected by
+/// [`pointers`].
+const MAX_POINTER_SIZE: usize = 256;
+
+const POINTER_VERSION: &str = "version radicle-link/large/v1";
+
+const STORE_DIR: &str = "large";
+const PARTIAL_DIR: &str = "partial";
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("chunk at offset {offset} does not continue download at {expected}")]
+    Discontinuous { offset: u64, expected: u64 },
+
+    #[error("chunk exceeds the size of {0}")]
+    Oversized(Oid),
+
+    #[error("content does not match {0}, discarding download")]
+    Corrupt(Oid),
+
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// The SHA-256 hash of the content of a large object.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Oid([u8; 32]);
+
+impl Oid {
+    pub fn hash(data: &[u8]) -> Self {
+        Self(Sha256::digest(data).into())
+    }
+}
+
+impl fmt::Display for Oid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in &self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("invalid large object id")]
+pub struct ParseOidError;
+
+impl FromStr for Oid {
+    type Err = ParseOidError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 64 || !s.is_ascii() {
+            return Err(ParseOidError);
+        }
+        let mut oid = [0; 32];
+        for (i, byte) in oid.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&s[2 * i..2 * i + 2], 16).map_err(|_| ParseOidError)?;
+        }
+        Ok(Self(oid))
+    }
+}
+
+impl minicbor::Encode for Oid {
+    fn encode<W: minicbor::encode::Write>(
+        &self,
+        e: &mut minicbor::Encoder<W>,
+    ) -> Result<(), minicbor::encode::Error<W::Error>> {
+        e.bytes(&self.0)?;
+        Ok(())
+    }
+}
+
+impl<'b> minicbor::Decode<'b> for Oid {
+    fn decode(d: &mut minicbor::Decoder<'b>) -> Result<Self, minicbor::decode::Error> {
+        <[u8; 32]>::try_from(d.bytes()?)
+            .map(Self)
+            .map_err(|_| minicbor::decode::Error::Message("invalid large object id"))
+    }
+}
+
+/// The blob committed in place of a large object.
+///
+/// Its textual form is:
+///
+/// ```text
+/// version radicle-link/large/v1
+/// oid sha256:<hex>
+/// size <bytes>
+/// ```
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Pointer {
+    pub oid: Oid,
+    pub size: u64,
+}
+
+impl Pointer {
+    /// Parse a pointer from the content of a blob, returning `None` if it
+    /// isn't one.
+    pub fn parse(blob: &[u8]) -> Option<Self> {
+        if blob.len() > MAX_POINTER_SIZE {
+            return None;
+        }
+        let mut lines = std::str::from_utf8(blob).ok()?.lines();
+        if lines.next()? != POINTER_VERSION {
+            return None;
+        }
+        let oid = lines.next()?.strip_prefix("oid sha256:")?.parse().ok()?;
+        let size = lines.next()?.strip_prefix("size ")?.parse().ok()?;
+        lines.next().is_none().then(|| Self { oid, size })
+    }
+}
+
+impl fmt::Display for Pointer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", POINTER_VERSION)?;
+        writeln!(f, "oid sha256:{}", self.oid)?;
+        writeln!(f, "size {}", self.size)
+    }
+}
+
+/// A slice of the content of a large object, starting at `offset`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chunk {
+    /// The total size of the object.
+    pub size: u64,
+    pub offset: u64,
+    pub data: Vec<u8>,
+}
+
+impl minicbor::Encode for Chunk {
+    fn encode<W: minicbor::encode::Write>(
+        &self,
+        e: &mut minicbor::Encoder<W>,
+    ) -> Result<(), minicbor::encode::Error<W::Error>> {
+        e.array(3)?
+            .u64(self.size)?
+            .u64(self.offset)?
+            .bytes(&self.data)?;
+        Ok(())
+    }
+}
+
+impl<'b> minicbor::Decode<'b> for Chunk {
+    fn decode(d: &mut minicbor::Decoder<'b>) -> Result<Self, minicbor::decode::Error> {
+        if d.array()? != Some(3) {
+            return Err(minicbor::decode::Error::Message("expected chunk array"));
+        }
+        Ok(Self {
+            size: d.u64()?,
+            offset: d.u64()?,
+            data: d.bytes()?.to_vec(),
+        })
+    }
+}
+
+/// The state of a download after [`Store::write_chunk`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Progress {
+    /// The number of bytes downloaded so far.
+    Partial(u64),
+    /// The object was downloaded and verified.
+    Complete,
+}
+
+/// A content-addressed store of large objects.
+#[derive(Clone, Debug)]
+pub struct Store {
+    root: PathBuf,
+}
+
+impl Store {
+    /// The store of the profile with the given `paths`.
+    pub fn open(paths: &Paths) -> Self {
+        Self::at(paths.git_dir())
+    }
+
+    /// The store alongside the monorepo at `git_dir`.
+    pub fn at(git_dir: &Path) -> Self {
+        Self {
+            root: git_dir.join(STORE_DIR),
+        }
+    }
+
+    fn path(&self, oid: &Oid) -> PathBuf {
+        let hex = oid.to_string();
+        self.root.join(&hex[..2]).join(&hex[2..])
+    }
+
+    fn partial_path(&self, oid: &Oid) -> PathBuf {
+        self.root.join(PARTIAL_DIR).join(oid.to_string())
+    }
+
+    pub fn has(&self, oid: &Oid) -> bool {
+        self.path(oid).is_file()
+    }
+
+    /// Store `data`, returning the [`Pointer`] to commit in its place.
+    pub fn offload(&self, data: &[u8]) -> Result<Pointer, Error> {
+        let pointer = Pointer {
+            oid: Oid::hash(data),
+            size: data.len() as u64,
+        };
+        if !self.has(&pointer.oid) {
+            let path = self.path(&pointer.oid);
+            let dir = path.parent().expect("object path has a parent");
+            fs::create_dir_all(dir)?;
+            let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+            tmp.write_all(data)?;
+            tmp.persist(path).map_err(|e| e.error)?;
+        }
+        Ok(pointer)
+    }
+
+    /// Read the content of the object `oid`, if present.
+    pub fn read(&self, oid: &Oid) -> Result<Option<Vec<u8>>, Error> {
+        match fs::read(self.path(oid)) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Read at most [`CHUNK_SIZE`] bytes of the object `oid`, starting at
+    /// `offset`.
+    pub fn read_chunk(&self, oid: &Oid, offset: u64) -> Result<Option<Chunk>, Error> {
+        let mut file = match fs::File::open(self.path(oid)) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let size = file.metadata()?.len();
+        file.seek(SeekFrom::Start(offset.min(size)))?;
+        let mut data = Vec::with_capacity(CHUNK_SIZE.min(size.saturating_sub(offset) as usize));
+        file.take(CHUNK_SIZE as u64).read_to_end(&mut data)?;
+        Ok(Some(Chunk { size, offset, data }))
+    }
+
+    /// The offset to resume the download of `oid` from.
+    pub fn resume_offset(&self, oid: &Oid) -> Result<u64, Error> {
+        match fs::metadata(self.partial_path(oid)) {
+            Ok(meta) => Ok(meta.len()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Append a downloaded `chunk` of the object `pointer` refers to.
+    ///
+    /// The chunk must continue at the [`Store::resume_offset`]. Once all of
+    /// the content was written, it is verified against the [`Oid`] and moved
+    /// into the store.
+    pub fn write_chunk(&self, pointer: &Pointer, chunk: &Chunk) -> Result<Progress, Error> {
+        let expected = self.resume_offset(&pointer.oid)?;
+        if chunk.offset != expected {
+            return Err(Error::Discontinuous {
+                offset: chunk.offset,
+                expected,
+            });
+        }
+        let len = expected + chunk.data.len() as u64;
+        if len > pointer.size || chunk.size != pointer.size {
+            return Err(Error::Oversized(pointer.oid));
+        }
+
+        let partial = self.partial_path(&pointer.oid);
+        fs::create_dir_all(partial.parent().expect("partial path has a parent"))?;
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&partial)?
+            .write_all(&chunk.data)?;
+        if len < pointer.size {
+            return Ok(Progress::Partial(len));
+        }
+
+        let mut hasher = Sha256::new();
+        io::copy(&mut fs::File::open(&partial)?, &mut hasher)?;
+        if Oid(hasher.finalize().into()) != pointer.oid {
+            fs::remove_file(&partial)?;
+            return Err(Error::Corrupt(pointer.oid));
+        }
+        let path = self.path(&pointer.oid);
+        fs::create_dir_all(path.parent().expect("object path has a parent"))?;
+        fs::rename(&partial, &path)?;
+
+        Ok(Progress::Complete)
+    }
+}
+
+/// The [`Pointer`]s found in the trees of the commits `tips`.
+///
+/// Only the trees of the tips themselves are inspected, not their history.
+pub fn pointers<I>(storage: &Storage, tips: I) -> Result<BTreeSet<Pointer>, Error>
+where
+    I: IntoIterator<Item = git2::Oid>,
+{
+    let repo = storage.as_raw();
+    let odb = repo.odb()?;
+    let mut pointers = BTreeSet::new();
+    for tip in tips {
+        let tree = repo.find_commit(tip)?.tree()?;
+        let mut err = None;
+        let res = tree.walk(git2::TreeWalkMode::PreOrder, |_, entry| {
+            if entry.kind() != Some(git2::ObjectType::Blob) {
+                return git2::TreeWalkResult::Ok;
+            }
+            match odb.read_header(entry.id()) {
+                Ok((size, _)) if size <= MAX_POINTER_SIZE => match repo.find_blob(entry.id()) {
+                    Ok(blob) => pointers.extend(Pointer::parse(blob.content())),
+                    Err(e) => err = Some(e),
+                },
+                Ok(_) => {},
+                Err(e) => err = Some(e),
+            }
+            if err.is_some() {
+                git2::TreeWalkResult::Abort
+            } else {
+                git2::TreeWalkResult::Ok
+            }
+        });
+        // Aborting the walk yields an error, which is less informative than
+        // the one which caused it.
+        if let Some(e) = err {
+            return Err(e.into());
+        }
+        res?;
+    }
+
+    Ok(pointers)
+}
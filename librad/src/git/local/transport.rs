@@ -200,6 +200,8 @@ impl LocalTransport {
                 "transfer.hiderefs=!refs/heads",
                 "-c",
                 "transfer.hiderefs=!refs/tags",
+                "-c",
+                "transfer.hiderefs=!refs/notes",
             ]);
 
         match service {
@@ -4,6 +4,7 @@
 // Linking Exception. For full terms see the included LICENSE file.
 
 pub mod header;
+pub mod pack_cache;
 pub mod server;
 pub mod transport;
 pub mod url;
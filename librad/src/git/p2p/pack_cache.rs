@@ -0,0 +1,359 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Reuse of the packs generated in response to clones.
+//!
+//! A clone is an upload-pack request which has no `have` lines. Since the
+//! objects reachable from the wanted tips never change, the response of `git
+//! upload-pack` to the same request can be replayed verbatim to subsequent
+//! cloners, saving the server from enumerating and compressing the objects
+//! again. Responses are keyed by the URN being cloned and a hash of the
+//! request, which covers the wanted tips and the negotiated capabilities.
+//!
+//! The cache is stored in a directory alongside the monorepo, and bounded by
+//! its total size: when it is exceeded, the least recently used responses
+//! are evicted.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    fs,
+    io::{self, Write as _},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::SystemTime,
+};
+
+use futures::io::{AsyncRead, AsyncReadExt as _};
+use parking_lot::Mutex;
+use sha2::{Digest as _, Sha256};
+use tempfile::NamedTempFile;
+
+use super::super::Urn;
+
+/// Requests exceeding this size are not considered for caching.
+const MAX_REQUEST_SIZE: usize = 64 * 1024;
+
+const EXTENSION: &str = "pack-response";
+
+/// Identifies a cached response.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Key(String);
+
+impl Key {
+    pub fn new(urn: &Urn, request: &[u8]) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(urn.to_string().as_bytes());
+        hasher.update(&[0]);
+        hasher.update(request);
+        Self(
+            hasher
+                .finalize()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+        )
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// The beginning of an upload-pack request, as read by [`read_request`].
+pub struct Request {
+    /// The raw bytes read so far, to be handed to `git upload-pack`.
+    pub bytes: Vec<u8>,
+    /// Whether the request is a complete clone request, ie. `bytes` is the
+    /// whole request.
+    pub is_clone: bool,
+}
+
+impl Request {
+    /// The cache key of the request, if it is a clone.
+    pub fn key(&self, urn: &Urn) -> Option<Key> {
+        self.is_clone.then(|| Key::new(urn, &self.bytes))
+    }
+}
+
+enum Pkt {
+    Flush,
+    Data(Vec<u8>),
+}
+
+/// Read the upload-pack request from `recv` up to the point where it is known
+/// whether it is a clone, ie. the wants followed by a flush and `done`.
+pub async fn read_request<R>(recv: &mut R) -> io::Result<Request>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut bytes = Vec::new();
+    let mut wants = false;
+    loop {
+        match read_pkt(recv, &mut bytes).await? {
+            Pkt::Flush => break,
+            Pkt::Data(data) => wants |= data.starts_with(b"want "),
+        }
+        if bytes.len() > MAX_REQUEST_SIZE {
+            return Ok(Request {
+                bytes,
+                is_clone: false,
+            });
+        }
+    }
+    let is_clone = wants
+        && match read_pkt(recv, &mut bytes).await? {
+            Pkt::Data(data) => data == b"done\n" || data == b"done",
+            Pkt::Flush => false,
+        };
+
+    Ok(Request { bytes, is_clone })
+}
+
+async fn read_pkt<R>(recv: &mut R, bytes: &mut Vec<u8>) -> io::Result<Pkt>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut hdr = [0; 4];
+    recv.read_exact(&mut hdr).await?;
+    bytes.extend_from_slice(&hdr);
+    let len = std::str::from_utf8(&hdr)
+        .ok()
+        .and_then(|hdr| usize::from_str_radix(hdr, 16).ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid pkt-line length"))?;
+    match len {
+        0 => Ok(Pkt::Flush),
+        // Delimiter and response-end packets are not part of clone requests
+        1..=3 => Ok(Pkt::Data(vec![])),
+        len => {
+            let mut data = vec![0; len - 4];
+            recv.read_exact(&mut data).await?;
+            bytes.extend_from_slice(&data);
+            Ok(Pkt::Data(data))
+        },
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Stats {
+    pub hits: u64,
+    pub misses: u64,
+    /// The number of cached responses.
+    pub entries: usize,
+    /// The total size in bytes of the cached responses.
+    pub size: u64,
+}
+
+#[derive(Clone)]
+pub struct PackCache {
+    dir: PathBuf,
+    max_size: u64,
+    index: Arc<Mutex<Index>>,
+    hits: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
+}
+
+#[derive(Default)]
+struct Index {
+    entries: HashMap<Key, Entry>,
+    size: u64,
+    clock: u64,
+}
+
+struct Entry {
+    size: u64,
+    used: u64,
+}
+
+impl Index {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    /// Remove least recently used entries until the total size is at most
+    /// `max_size`, returning the removed keys.
+    fn evict(&mut self, max_size: u64) -> Vec<Key> {
+        let mut evicted = Vec::new();
+        while self.size > max_size {
+            let lru = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.used)
+                .map(|(key, _)| key.clone());
+            match lru.and_then(|key| self.entries.remove(&key).map(|entry| (key, entry))) {
+                Some((key, entry)) => {
+                    self.size -= entry.size;
+                    evicted.push(key);
+                },
+                None => break,
+            }
+        }
+        evicted
+    }
+}
+
+impl PackCache {
+    /// Open the cache stored in `dir`, creating it if it doesn't exist.
+    ///
+    /// Responses cached previously are picked up, in the order of their
+    /// modification time.
+    pub fn open(dir: impl Into<PathBuf>, max_size: u64) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+
+        let mut found = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            let key = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(stem) if path.extension().map_or(false, |ext| ext == EXTENSION) => {
+                    Key(stem.to_owned())
+                },
+                // Leftovers of interrupted writes
+                _ => {
+                    fs::remove_file(&path).ok();
+                    continue;
+                },
+            };
+            let meta = entry.metadata()?;
+            let mtime = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            found.push((mtime, key, meta.len()));
+        }
+        found.sort_by_key(|(mtime, _, _)| *mtime);
+
+        let mut index = Index::default();
+        for (_, key, size) in found {
+            let used = index.tick();
+            index.size += size;
+            index.entries.insert(key, Entry { size, used });
+        }
+        let this = Self {
+            dir,
+            max_size,
+            index: Arc::new(Mutex::new(index)),
+            hits: Arc::new(AtomicU64::new(0)),
+            misses: Arc::new(AtomicU64::new(0)),
+        };
+        let evicted = this.index.lock().evict(max_size);
+        this.remove(evicted);
+
+        Ok(this)
+    }
+
+    /// Open the cached response for `key`, if any.
+    pub fn get(&self, key: &Key) -> io::Result<Option<fs::File>> {
+        let mut index = self.index.lock();
+        let used = index.tick();
+        let file = match index.entries.get_mut(key) {
+            Some(entry) => {
+                entry.used = used;
+                // Opened while holding the lock, so it can't be evicted in
+                // between.
+                Some(fs::File::open(self.path(key))?)
+            },
+            None => None,
+        };
+        match file {
+            Some(_) => self.hits.fetch_add(1, Ordering::Relaxed),
+            None => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+
+        Ok(file)
+    }
+
+    /// Start recording a response, to be [`PackCache::insert`]ed once it is
+    /// complete.
+    pub fn pending(&self) -> io::Result<Pending> {
+        Ok(Pending {
+            tmp: Some(NamedTempFile::new_in(&self.dir)?),
+            size: 0,
+            max_size: self.max_size,
+        })
+    }
+
+    /// Add the recorded response for `key`, evicting the least recently used
+    /// responses as needed.
+    ///
+    /// Returns `false` if the response was too large to be cached.
+    pub fn insert(&self, key: Key, pending: Pending) -> io::Result<bool> {
+        let mut tmp = match pending.tmp {
+            Some(tmp) => tmp,
+            None => return Ok(false),
+        };
+        tmp.flush()?;
+        tmp.persist(self.path(&key)).map_err(|e| e.error)?;
+
+        let evicted = {
+            let mut index = self.index.lock();
+            let used = index.tick();
+            if let Some(prev) = index.entries.insert(
+                key,
+                Entry {
+                    size: pending.size,
+                    used,
+                },
+            ) {
+                index.size -= prev.size;
+            }
+            index.size += pending.size;
+            index.evict(self.max_size)
+        };
+        self.remove(evicted);
+
+        Ok(true)
+    }
+
+    pub fn stats(&self) -> Stats {
+        let index = self.index.lock();
+        Stats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: index.entries.len(),
+            size: index.size,
+        }
+    }
+
+    fn path(&self, key: &Key) -> PathBuf {
+        self.dir.join(&key.0).with_extension(EXTENSION)
+    }
+
+    fn remove(&self, keys: Vec<Key>) {
+        for key in keys {
+            let path = self.path(&key);
+            if let Err(e) = fs::remove_file(&path) {
+                tracing::warn!(err = ?e, path = %path.display(), "failed to remove cached pack");
+            }
+        }
+    }
+}
+
+/// A response being recorded.
+///
+/// Recording stops silently once the response exceeds the maximum size of the
+/// cache.
+pub struct Pending {
+    tmp: Option<NamedTempFile>,
+    size: u64,
+    max_size: u64,
+}
+
+impl Pending {
+    pub fn write(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.size += buf.len() as u64;
+        if self.size > self.max_size {
+            self.tmp = None;
+        }
+        if let Some(tmp) = &mut self.tmp {
+            tmp.write_all(buf)?;
+        }
+        Ok(())
+    }
+}
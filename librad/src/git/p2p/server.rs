@@ -29,26 +29,55 @@ use git_ext::{into_io_err, RefLike, References, UPLOAD_PACK_HEADER};
 use tokio::process::{self, Command};
 use tokio_util::compat::{TokioAsyncReadCompatExt, TokioAsyncWriteCompatExt};
 
+/// Directory of the [`PackCache`], relative to the monorepo.
+const PACK_CACHE_DIR: &str = "pack-cache";
+
 use super::{
     super::{
+        storage::bitmap,
         types::namespace::{AsNamespace, Namespace},
         Urn,
     },
     header::{self, Header},
+    pack_cache::{self, PackCache},
 };
 use crate::paths::Paths;
 
 #[derive(Clone)]
 pub struct GitServer {
     repo_path: PathBuf,
+    bitmaps: bitmap::Counters,
+    pack_cache: Option<PackCache>,
 }
 
 impl GitServer {
     pub fn new(paths: &Paths) -> Self {
         Self {
             repo_path: paths.git_dir().to_path_buf(),
+            bitmaps: bitmap::Counters::default(),
+            pack_cache: None,
         }
     }
+
+    /// Cache the packs sent in response to clones, up to `max_size` bytes in
+    /// total. See [`pack_cache`].
+    pub fn with_pack_cache(self, max_size: u64) -> io::Result<Self> {
+        let pack_cache = PackCache::open(self.repo_path.join(PACK_CACHE_DIR), max_size)?;
+        Ok(Self {
+            pack_cache: Some(pack_cache),
+            ..self
+        })
+    }
+
+    /// How well the packs served so far were covered by bitmap indices.
+    pub fn bitmap_stats(&self) -> bitmap::Stats {
+        self.bitmaps.stats()
+    }
+
+    /// Usage of the pack cache, if enabled.
+    pub fn pack_cache_stats(&self) -> Option<pack_cache::Stats> {
+        self.pack_cache.as_ref().map(PackCache::stats)
+    }
 }
 
 impl GitServer {
@@ -72,6 +101,8 @@ impl GitServer {
             Ok(header) => Ok(GitService {
                 repo_path: self.repo_path.to_path_buf(),
                 header,
+                bitmaps: self.bitmaps.clone(),
+                pack_cache: self.pack_cache.clone(),
                 recv,
                 send,
             }),
@@ -87,6 +118,8 @@ impl GitServer {
 pub struct GitService<R, W> {
     pub repo_path: PathBuf,
     pub header: Header<Urn>,
+    bitmaps: bitmap::Counters,
+    pack_cache: Option<PackCache>,
     recv: R,
     send: W,
 }
@@ -103,9 +136,21 @@ where
         match *service {
             Service::UploadPack => {
                 tracing::info!("upload pack");
-                UploadPack::upload_pack(&self.repo_path)?
-                    .run(self.recv, self.send)
-                    .await?;
+                match bitmap::Coverage::of(&self.repo_path) {
+                    Ok(coverage) => self.bitmaps.record(&coverage),
+                    Err(e) => tracing::warn!(err = ?e, "unable to inspect packs"),
+                }
+                match self.pack_cache {
+                    Some(cache) => {
+                        upload_pack_cached(&self.repo_path, &cache, &repo, self.recv, self.send)
+                            .await?
+                    },
+                    None => {
+                        UploadPack::upload_pack(&self.repo_path)?
+                            .run(self.recv, self.send)
+                            .await?
+                    },
+                }
             },
             Service::UploadPackLs => {
                 tracing::info!("upload pack ls");
@@ -183,24 +228,7 @@ impl UploadPack {
 
     #[tracing::instrument(level = "debug")]
     fn upload_pack(repo_path: &Path) -> io::Result<Self> {
-        let mut git = Command::new("git");
-        git.arg("-c").arg("uploadpack.allowanysha1inwant=true");
-
-        git_tracing(&mut git);
-        git.args(&[
-            "upload-pack",
-            "--strict",
-            "--timeout=5",
-            "--stateless-rpc",
-            ".",
-        ])
-        .current_dir(repo_path)
-        .stdout(Stdio::piped())
-        .stdin(Stdio::piped())
-        .stderr(Stdio::inherit())
-        .kill_on_drop(true)
-        .spawn()
-        .map(Self::UploadPack)
+        spawn_upload_pack(repo_path).map(Self::UploadPack)
     }
 
     #[allow(clippy::unit_arg)]
@@ -265,6 +293,95 @@ impl UploadPack {
     }
 }
 
+fn spawn_upload_pack(repo_path: &Path) -> io::Result<process::Child> {
+    let mut git = Command::new("git");
+    git.arg("-c").arg("uploadpack.allowanysha1inwant=true");
+
+    git_tracing(&mut git);
+    git.args(&[
+        "upload-pack",
+        "--strict",
+        "--timeout=5",
+        "--stateless-rpc",
+        ".",
+    ])
+    .current_dir(repo_path)
+    .stdout(Stdio::piped())
+    .stdin(Stdio::piped())
+    .stderr(Stdio::inherit())
+    .kill_on_drop(true)
+    .spawn()
+}
+
+/// Like [`UploadPack::upload_pack`], but replay the response from `cache` if
+/// the request is a clone which was served before, and record it otherwise.
+#[tracing::instrument(skip(cache, recv, send))]
+async fn upload_pack_cached<R, W>(
+    repo_path: &Path,
+    cache: &PackCache,
+    urn: &Urn,
+    mut recv: R,
+    mut send: W,
+) -> io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let request = pack_cache::read_request(&mut recv).await?;
+    let key = request.key(urn);
+    if let Some(key) = &key {
+        if let Some(cached) = cache.get(key)? {
+            tracing::debug!(%key, "serving cached pack");
+            let mut cached = tokio::fs::File::from_std(cached).compat();
+            futures::io::copy(&mut cached, &mut send).await?;
+            // See `UploadPack::run`
+            futures::io::copy(&mut recv, &mut futures::io::sink()).await?;
+            return Ok(());
+        }
+    }
+
+    let mut child = spawn_upload_pack(repo_path)?;
+    let mut stdin = child.stdin.take().unwrap().compat_write();
+    let mut stdout = child.stdout.take().unwrap().compat();
+    let mut pending = match key {
+        Some(_) => Some(cache.pending()?),
+        None => None,
+    };
+
+    let forward = async {
+        stdin.write_all(&request.bytes).await?;
+        futures::io::copy(&mut recv, &mut stdin).await
+    };
+    let tee = async {
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            let n = stdout.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            send.write_all(&buf[..n]).await?;
+            if let Some(pending) = &mut pending {
+                pending.write(&buf[..n])?;
+            }
+        }
+        send.flush().await
+    };
+    let (_, _, status) = futures::try_join!(forward, tee, child.wait())?;
+    if !status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("upload-pack exited non-zero: {:?}", status),
+        ));
+    }
+
+    if let (Some(key), Some(pending)) = (key, pending) {
+        tracing::debug!(%key, "caching pack");
+        cache.insert(key, pending)?;
+    }
+
+    Ok(())
+}
+
 fn git_tracing(git: &mut Command) {
     git.envs(::std::env::vars().filter(|(key, _)| key.starts_with("GIT_TRACE")));
 }
@@ -35,11 +35,11 @@
 //! [`GitServer`]: ../server/struct.GitServer.html
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     future::Future,
     io::{self, Read, Write},
     net::SocketAddr,
-    sync::{Arc, Once, RwLock, Weak},
+    sync::{Arc, Mutex, Once, RwLock, Weak},
 };
 
 use futures::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
@@ -47,12 +47,13 @@ use git2::transport::{Service, SmartSubtransport, SmartSubtransportStream, Trans
 use git_ext::into_git_err;
 
 use super::{header::Header, url::GitUrl};
-use crate::{identities::git::Urn, PeerId};
+use crate::{identities::git::Urn, net::quic, PeerId};
 
 type Factories = Arc<RwLock<HashMap<PeerId, Weak<Box<dyn GitStreamFactory>>>>>;
 
 lazy_static! {
     static ref FACTORIES: Factories = Arc::new(RwLock::new(HashMap::with_capacity(1)));
+    static ref RESETS: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
 }
 
 /// The underlying [`AsyncRead`] + [`AsyncWrite`] of a [`RadSubTransport`]
@@ -173,6 +174,7 @@ impl SmartSubtransport for RadTransport {
         let header = Header::new(service, Urn::new(repo), remote_peer, nonce);
 
         Ok(Box::new(RadSubTransport {
+            url: url.to_owned(),
             header: Some(header),
             stream,
         }))
@@ -184,6 +186,7 @@ impl SmartSubtransport for RadTransport {
 }
 
 struct RadSubTransport {
+    url: String,
     header: Option<Header<Urn>>,
     stream: Box<dyn GitStream>,
 }
@@ -204,6 +207,7 @@ impl Read for RadSubTransport {
             self.ensure_header_sent().await?;
             self.stream.read(buf).await
         })
+        .map_err(|e| record_reset(&self.url, e))
     }
 }
 
@@ -213,6 +217,7 @@ impl Write for RadSubTransport {
             self.ensure_header_sent().await?;
             self.stream.write(buf).await
         })
+        .map_err(|e| record_reset(&self.url, e))
     }
 
     fn flush(&mut self) -> io::Result<()> {
@@ -220,9 +225,28 @@ impl Write for RadSubTransport {
             self.ensure_header_sent().await?;
             self.stream.flush().await
         })
+        .map_err(|e| record_reset(&self.url, e))
     }
 }
 
+/// Whether the remote end reset the stream of a [`RadSubTransport`] for `url`
+/// since the last call.
+///
+/// `libgit2` only retains the message of the errors a transport returns, so
+/// stream resets are recorded by the URL of the transport instead. As the
+/// connection remains usable (see [`quic::is_stream_reset`]), the operation
+/// which failed can be retried, which opens a new stream.
+pub fn take_stream_reset(url: &str) -> bool {
+    RESETS.lock().unwrap().remove(url)
+}
+
+fn record_reset(url: &str, e: io::Error) -> io::Error {
+    if quic::is_stream_reset(&e) {
+        RESETS.lock().unwrap().insert(url.to_owned());
+    }
+    e
+}
+
 fn io_error<E>(err: E) -> io::Error
 where
     E: Into<Box<dyn std::error::Error + Send + Sync>>,
@@ -4,10 +4,10 @@
 // Linking Exception. For full terms see the included LICENSE file.
 
 use std::{
-    collections::{btree_map, BTreeMap},
+    collections::{btree_map, BTreeMap, BTreeSet, VecDeque},
     convert::TryFrom,
     fmt::{self, Debug},
-    iter::FromIterator,
+    iter::{self, FromIterator},
     marker::PhantomData,
     ops::{Deref, DerefMut},
     path::Path,
@@ -27,7 +27,7 @@ use thiserror::Error;
 use super::{
     storage::{self, ReadOnlyStorage, Storage},
     tracking,
-    types::{Namespace, Reference, RefsCategory},
+    types::{Namespace, One, Reference, RefsCategory},
 };
 use crate::{PeerId, Signature, Signer};
 
@@ -119,6 +119,32 @@ impl<A: Ord> Remotes<A> {
             inner: None,
         }
     }
+
+    /// The distinct nodes of the tracking graph, in breadth-first order, up to
+    /// `max` of them.
+    ///
+    /// Also returns the number of distinct nodes which were omitted because
+    /// `max` was exceeded.
+    pub fn flatten_bounded(&self, max: usize) -> (BTreeSet<&A>, usize) {
+        let mut found = BTreeSet::new();
+        let mut omitted = BTreeSet::new();
+        let mut queue = VecDeque::from(vec![self]);
+        while let Some(remotes) = queue.pop_front() {
+            for (a, next) in remotes.iter() {
+                if found.contains(a) {
+                    continue;
+                }
+                if found.len() < max {
+                    found.insert(a);
+                } else {
+                    omitted.insert(a);
+                }
+                queue.push_back(&**next);
+            }
+        }
+
+        (found, omitted.len())
+    }
 }
 
 /// Iterator which yields all `A`s in an unspecified order.
@@ -167,7 +193,7 @@ pub mod signing {
 pub mod stored {
     use super::*;
 
-    pub(super) const BLOB_PATH: &str = "refs"; // `Path::new` ain't no const fn :(
+    pub(crate) const BLOB_PATH: &str = "refs"; // `Path::new` ain't no const fn :(
 
     #[derive(Debug, Error)]
     #[non_exhaustive]
@@ -193,6 +219,9 @@ pub mod stored {
         #[error(transparent)]
         Store(#[from] storage::Error),
 
+        #[error("{at} is not in the history of {branch}")]
+        NotInHistory { at: git_ext::Oid, branch: String },
+
         #[error(transparent)]
         Git(#[from] git2::Error),
     }
@@ -227,9 +256,18 @@ pub struct Refs {
     /// `refs/tags/*`
     pub tags: BTreeMap<reference::OneLevel, Oid>,
 
-    /// `refs/notes/*`
+    /// `refs/notes/*`, unless opted out of via
+    /// [`storage::config::Config::set_sign_notes`].
     pub notes: BTreeMap<reference::OneLevel, Oid>,
 
+    /// `refs/cobs/*`, ie. collaborative objects keyed by
+    /// `<typename>/<object id>`.
+    ///
+    /// Omitted from the serialised form if empty, so as to not invalidate
+    /// signatures made before this field was introduced.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub cobs: BTreeMap<reference::OneLevel, Oid>,
+
     /// The [`Remotes`], ie. tracking graph.
     ///
     /// Note that this does does not include the oids, as they can be determined
@@ -280,8 +318,21 @@ impl Refs {
             .filter_map(peeled)
             .map(refined)
             .collect::<Result<_, _>>()?;
-        let notes = storage
-            .references(&Reference::notes(namespace, None))?
+        let sign_notes = storage
+            .config()?
+            .sign_notes()
+            .map_err(storage::Error::from)?;
+        let notes = if sign_notes {
+            storage
+                .references(&Reference::notes(namespace.clone(), None))?
+                .filter_map(peeled)
+                .map(refined)
+                .collect::<Result<_, _>>()?
+        } else {
+            BTreeMap::new()
+        };
+        let cobs = storage
+            .references(&Reference::cobs(namespace, None))?
             .filter_map(peeled)
             .map(refined)
             .collect::<Result<_, _>>()?;
@@ -298,6 +349,7 @@ impl Refs {
             rad,
             tags,
             notes,
+            cobs,
             remotes,
         })
     }
@@ -319,6 +371,26 @@ impl Refs {
         load(storage, urn, peer).map(|may| may.map(|Loaded { refs, .. }| Self::from(refs)))
     }
 
+    /// Like [`Refs::load`], but load the signed refs as of the
+    /// `rad/signed_refs` commit `at`, which must be in the history of the
+    /// current `rad/signed_refs` of `peer`.
+    ///
+    /// This is the state of the namespace `peer` signed off on at that point,
+    /// eg. before an update which turned out to be bad.
+    #[tracing::instrument(level = "debug", skip(storage, urn), fields(urn = %urn))]
+    pub fn load_at<S, P>(
+        storage: &S,
+        urn: &Urn,
+        peer: P,
+        at: Oid,
+    ) -> Result<Option<Self>, stored::Error>
+    where
+        S: AsRef<storage::ReadOnly>,
+        P: Into<Option<PeerId>> + Debug,
+    {
+        load_at(storage, urn, peer, at).map(|may| may.map(|Loaded { refs, .. }| Self::from(refs)))
+    }
+
     /// Compute the current [`Refs`], sign them, and store them at the
     /// `rad/signed_refs` branch of [`Urn`].
     #[tracing::instrument(skip(storage, urn), fields(urn = %urn))]
@@ -411,6 +483,7 @@ impl Refs {
             rad,
             tags,
             notes,
+            cobs,
             remotes: _,
         } = self;
         heads
@@ -419,6 +492,7 @@ impl Refs {
             .chain(rad.iter().map(|x| (x, RefsCategory::Rad)))
             .chain(tags.iter().map(|x| (x, RefsCategory::Tags)))
             .chain(notes.iter().map(|x| (x, RefsCategory::Notes)))
+            .chain(cobs.iter().map(|x| (x, RefsCategory::Cobs)))
     }
 
     fn canonical_form(&self) -> Result<Vec<u8>, CjsonError> {
@@ -600,23 +674,88 @@ where
     )?;
     match at {
         None => Ok(None),
-        Some(at_commit) => {
-            let path = Path::new(stored::BLOB_PATH);
-
-            tracing::debug!(
-                "loading signed_refs from {}:{} {}",
-                &sigrefs,
-                &at_commit,
-                path.display()
-            );
-
-            let maybe_refs = storage
-                .blob_at(at_commit, path)?
-                .map(|blob| Signed::from_json(blob.content(), &signer))
-                .transpose()
-                .map_err(stored::Error::from)?;
-
-            Ok(maybe_refs.map(|refs| Loaded { at_commit, refs }))
-        },
+        Some(at_commit) => load_commit(storage, &sigrefs, at_commit, &signer),
+    }
+}
+
+pub(crate) fn load_at<S, P>(
+    storage: &S,
+    urn: &Urn,
+    peer: P,
+    at: git_ext::Oid,
+) -> Result<Option<Loaded>, stored::Error>
+where
+    S: AsRef<storage::ReadOnly>,
+    P: Into<Option<PeerId>> + Debug,
+{
+    let storage = storage.as_ref();
+    let peer = peer.into();
+    let signer = peer.unwrap_or_else(|| *storage.peer_id());
+
+    let sigrefs = Reference::rad_signed_refs(Namespace::from(urn), peer);
+    let path = match peer {
+        None => reflike!("refs/rad/signed_refs"),
+        Some(peer) => reflike!("refs/remotes")
+            .join(peer)
+            .join(reflike!("rad/signed_refs")),
+    };
+    if !storage.has_commit(&urn.clone().with_path(path), at)? {
+        return Err(stored::Error::NotInHistory {
+            at,
+            branch: sigrefs.to_string(),
+        });
     }
+
+    load_commit(storage, &sigrefs, at, &signer)
+}
+
+fn load_commit(
+    storage: &storage::ReadOnly,
+    sigrefs: &Reference<One>,
+    at_commit: git_ext::Oid,
+    signer: &PeerId,
+) -> Result<Option<Loaded>, stored::Error> {
+    let path = Path::new(stored::BLOB_PATH);
+
+    tracing::debug!(
+        "loading signed_refs from {}:{} {}",
+        sigrefs,
+        &at_commit,
+        path.display()
+    );
+
+    let maybe_refs = storage
+        .blob_at(at_commit, path)?
+        .map(|blob| Signed::from_json(blob.content(), signer))
+        .transpose()
+        .map_err(stored::Error::from)?;
+
+    Ok(maybe_refs.map(|refs| Loaded { at_commit, refs }))
+}
+
+/// The tips of the `rad/signed_refs` of the local peer and all tracked peers
+/// in the context of `urn`.
+///
+/// Peers for which no `rad/signed_refs` are present are omitted, so the
+/// result is empty if `urn` is not present in `storage`.
+pub fn tips<S>(storage: &S, urn: &Urn) -> Result<BTreeMap<PeerId, git_ext::Oid>, stored::Error>
+where
+    S: AsRef<storage::ReadOnly>,
+{
+    let storage = storage.as_ref();
+    let local = *storage.peer_id();
+
+    let mut tips = BTreeMap::new();
+    for peer in iter::once(None).chain(tracking::tracked(storage, urn)?.map(Some)) {
+        let sigrefs = Reference::rad_signed_refs(Namespace::from(urn), peer);
+        let at = storage.reference_oid(&sigrefs).map(Some).or_matches(
+            |e| matches!(e, storage::read::Error::Git(e) if is_not_found_err(e)),
+            || Ok::<_, storage::read::Error>(None),
+        )?;
+        if let Some(at) = at {
+            tips.insert(peer.unwrap_or(local), at);
+        }
+    }
+
+    Ok(tips)
 }
@@ -6,7 +6,9 @@
 use std::{
     collections::{BTreeMap, BTreeSet},
     convert::{TryFrom, TryInto},
-    iter,
+    num::NonZeroUsize,
+    thread,
+    time::{Duration, Instant, SystemTime},
 };
 
 use either::Either;
@@ -15,78 +17,301 @@ use std_ext::result::ResultExt as _;
 use thiserror::Error;
 
 use super::{
+    cobs,
     fetch,
     identities::{self, local::LocalIdentity},
+    inbox,
     refs::{self, Refs},
-    storage::{self, ReadOnlyStorage, Storage},
+    storage::{self, watch, ReadOnlyStorage, Storage},
     tracking,
-    types::{reference, Force, Namespace, One, Reference},
+    types::{reference, Fetchspec, Force, Namespace, One, Reference},
 };
 use crate::{
     identities::git::{Person, Project, Revision, SomeIdentity, VerifiedPerson, VerifiedProject},
+    net::protocol::Features,
     PeerId,
 };
 
 pub use crate::identities::git::Urn;
 
+pub mod audit;
+pub mod head;
+pub mod hooks;
+pub mod hygiene;
+pub mod metrics;
+pub mod pack_sizes;
+pub mod retry;
+pub mod sanity;
+pub mod slots;
+pub mod trace;
+
+/// Errors which can occur during [`replicate`].
+///
+/// The variants correspond to the stage of the replication at which the error
+/// occurred. See [`retry::Classify`] for whether it is sensible to attempt the
+/// replication again.
+///
+/// Errors of the underlying operations are assigned to a stage where they
+/// occur, rather than by their type: a failure to read the tracked peers is a
+/// [`Error::Negotiation`] error while determining what to fetch, but a
+/// [`Error::Tx`] error while setting up the namespace.
 #[derive(Debug, Error)]
 #[non_exhaustive]
 pub enum Error {
     #[error("cannot replicate from self")]
     SelfReplication,
 
-    #[error("identity not found")]
-    MissingIdentity,
+    #[error("failed to peek identity refs")]
+    Peek(#[source] error::Fetch),
 
-    #[error("no identity was found for `{0}`, leading to not being able to adopt a `rad/id`")]
-    MissingIdentities(Urn),
+    #[error("failed to determine the refs to fetch")]
+    Negotiation(#[from] error::Negotiation),
 
-    #[error("missing required ref: {0}")]
-    Missing(ext::RefLike),
+    #[error("failed to fetch refs")]
+    Fetch(#[source] error::Fetch),
 
-    #[error("the identity did not have any delegates or tracked peers")]
-    NoTrustee,
+    #[error("identity verification failed")]
+    Verification(#[from] error::Verification),
 
-    #[error("failed to convert {urn} to reference")]
-    RefFromUrn {
-        urn: Urn,
-        source: reference::FromUrnError,
-    },
+    #[error("signed refs validation failed")]
+    Validation(#[source] refs::stored::Error),
 
-    #[error("fork detected between `{mine}` and `{theirs}`")]
-    Fork { mine: Urn, theirs: Urn },
+    #[error("ref name hygiene check failed")]
+    Hygiene(#[from] hygiene::Error),
 
-    #[error("unknown identity kind")]
-    UnknownIdentityKind(SomeIdentity),
+    #[error("namespace rejected by sanity checks")]
+    Sanity(#[from] sanity::Error),
 
-    #[error(transparent)]
-    Refs(#[from] refs::stored::Error),
+    #[error("ref updates were vetoed: {0}")]
+    Vetoed(hooks::Veto),
 
-    #[error(transparent)]
-    Track(#[from] tracking::Error),
+    #[error("failed to update local storage")]
+    Tx(#[from] error::Tx),
+}
+
+impl Error {
+    /// Whether the replication may succeed if it is attempted again.
+    ///
+    /// This is the case for errors which occurred while talking to the remote
+    /// peer. Any other error is not expected to resolve itself without
+    /// intervention, or without obtaining different data from another peer.
+    pub fn is_retryable(&self) -> bool {
+        use retry::Classify as _;
 
-    #[error("signer error: {0}")]
-    Sign(#[source] Box<dyn std::error::Error + Send + Sync + 'static>),
+        self.category() == retry::Category::Transient
+    }
+}
 
-    #[error("fetcher error: {0}")]
-    Fetch(#[source] Box<dyn std::error::Error + Send + Sync + 'static>),
+pub mod error {
+    use super::*;
 
+    /// An error returned by the [`fetch::Fetcher`].
+    #[derive(Debug, Error)]
     #[error(transparent)]
-    Identities(#[from] Box<identities::error::Error>),
+    pub struct Fetch(Box<dyn std::error::Error + Send + Sync + 'static>);
 
-    #[error(transparent)]
-    Store(#[from] storage::Error),
-}
+    impl Fetch {
+        pub fn new<E>(e: E) -> Self
+        where
+            E: std::error::Error + Send + Sync + 'static,
+        {
+            Self(Box::new(e))
+        }
+    }
+
+    /// An error determining which refs to ask the remote peer for.
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Negotiation {
+        #[error("failed to read tracked peers")]
+        Tracked(#[source] tracking::Error),
+
+        #[error("failed to read excluded collaborative objects")]
+        ExcludedCobs(#[source] tracking::Error),
+
+        #[error(transparent)]
+        Cobs(#[from] cobs::policy::Error),
+    }
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Verification {
+        #[error("identity not found")]
+        MissingIdentity,
+
+        #[error("no identity was found for `{0}`, leading to not being able to adopt a `rad/id`")]
+        MissingIdentities(Urn),
+
+        #[error("missing required ref: {0}")]
+        Missing(ext::RefLike),
+
+        #[error("fork detected between `{mine}` and `{theirs}`")]
+        Fork { mine: Urn, theirs: Urn },
+
+        #[error("unknown identity kind")]
+        UnknownIdentityKind(SomeIdentity),
+
+        #[error(transparent)]
+        Identities(#[from] Box<identities::error::Error>),
+    }
+
+    impl From<identities::error::Error> for Verification {
+        fn from(e: identities::error::Error) -> Self {
+            Self::Identities(Box::new(e))
+        }
+    }
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Tx {
+        #[error("failed to convert {urn} to reference")]
+        RefFromUrn {
+            urn: Urn,
+            source: reference::FromUrnError,
+        },
+
+        #[error(transparent)]
+        Track(#[from] tracking::Error),
+
+        #[error("failed to update signed refs")]
+        Sigrefs(#[source] refs::stored::Error),
+
+        #[error("failed to keep signed refs `{name}`")]
+        StaleSigrefs {
+            name: ext::RefLike,
+            source: git2::Error,
+        },
+
+        #[error("failed to roll back vetoed ref updates")]
+        Rollback(#[source] git2::Error),
 
-impl From<identities::error::Error> for Error {
-    fn from(e: identities::error::Error) -> Self {
-        Self::Identities(Box::new(e))
+        #[error(transparent)]
+        Store(#[from] storage::Error),
     }
 }
 
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct Config {
     pub fetch_limit: fetch::Limit,
+    /// If set, the data fetched for a namespace is bounded relative to the
+    /// largest pack received for it before, with
+    /// [`fetch::Limit::data`] as the ceiling.
+    ///
+    /// See [`pack_sizes`].
+    pub adaptive_fetch_limit: Option<pack_sizes::Adaptive>,
+    /// If set, the default branch of a project is updated to the head its
+    /// delegates converged on after a successful replication.
+    ///
+    /// See [`head::converge`].
+    pub default_branch: Option<head::Convergence>,
+    /// The rules the ref names of tracked peers must obey.
+    ///
+    /// See [`hygiene`].
+    pub hygiene: hygiene::Policy,
+    /// If set, the refs of the storage are packed after a successful
+    /// replication leaves more than this many loose refs in the namespace.
+    /// `Some(0)` keeps the storage (almost) entirely packed.
+    ///
+    /// See [`storage::packed`].
+    pub pack_refs: Option<usize>,
+    /// How replications failing with transient errors are retried by callers
+    /// which can afford to wait, eg. the protocol when acting on gossip.
+    ///
+    /// See [`retry`].
+    pub retry: retry::Policy,
+    /// If set, the project identities of a project's delegates are verified
+    /// on up to this many threads, instead of one after the other.
+    ///
+    /// Verification is CPU-bound and independent per delegate, so this speeds
+    /// up the replication of projects with many delegates. The outcome is the
+    /// same either way.
+    pub parallel_verification: Option<NonZeroUsize>,
+    /// Bounds on what the remote peer may advertise and send while peeking.
+    ///
+    /// See [`sanity`].
+    pub sanity: sanity::Limits,
+    /// Bounds on the peers discovered via the tracking graphs of tracked
+    /// peers.
+    pub remotes: RemotesLimit,
+    /// If set, a [`trace::Trace`] of every replication is written alongside
+    /// the monorepo, for debugging.
+    ///
+    /// See [`trace`].
+    pub trace: bool,
+    /// Where to report measurements of the stages of every replication.
+    ///
+    /// See [`metrics`].
+    pub metrics: metrics::Sink,
+    /// Bounds on the replications in progress, which callers replicating on
+    /// behalf of the network are expected to obtain a [`slots::Permit`] for.
+    ///
+    /// See [`slots`].
+    pub slots: slots::Limits,
+    /// What decides whether the ref updates of a replication are applied,
+    /// and is told about the ones which were.
+    ///
+    /// See [`hooks`].
+    pub hooks: hooks::Sink,
+}
+
+/// Bounds on the peers discovered via the tracking graphs (ie.
+/// [`Refs::remotes`]) in the signed refs of tracked peers.
+///
+/// The discovered peers are tracked, and subsequently fetched from, so
+/// adversarial signed refs could otherwise make us track an arbitrary number
+/// of peers. Excess peers are omitted, and reported as a [`Warning`].
+#[derive(Clone, Copy, Debug)]
+pub struct RemotesLimit {
+    /// The maximum number of peers taken from the tracking graph of a
+    /// single peer.
+    ///
+    /// Default: 256
+    pub per_peer: usize,
+    /// The maximum number of peers taken from the tracking graphs of all
+    /// tracked peers combined, in addition to the tracked peers themselves.
+    ///
+    /// Default: 1024
+    pub total: usize,
+}
+
+impl Default for RemotesLimit {
+    fn default() -> Self {
+        Self {
+            per_peer: 256,
+            total: 1024,
+        }
+    }
+}
+
+/// Irregularities in the data of remote peers, which were dealt with without
+/// failing the replication.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Warning {
+    /// The tracking graph of `peer` exceeded [`RemotesLimit::per_peer`], and
+    /// `omitted` peers were not tracked.
+    RemotesPerPeer { peer: PeerId, omitted: usize },
+    /// The tracking graphs of all tracked peers exceeded
+    /// [`RemotesLimit::total`], and `omitted` peers were not tracked.
+    RemotesTotal { omitted: usize },
+    /// The delegate `peer` has no head for the project's default `branch`.
+    DefaultBranchMissing { peer: PeerId, branch: ext::RefLike },
+    /// The delegates' heads of the project's default `branch` differ.
+    DefaultBranchDisagreement {
+        branch: ext::RefLike,
+        heads: BTreeMap<PeerId, ext::Oid>,
+    },
+    /// The remote peer served `rad/signed_refs` of `peer` at `fetched`, which
+    /// does not descend from the `stored` ones. If `behind`, `fetched` is an
+    /// ancestor of `stored`, otherwise the histories diverged. The `stored`
+    /// signed refs were kept.
+    StaleSigrefs {
+        peer: PeerId,
+        name: ext::RefLike,
+        stored: ext::Oid,
+        fetched: ext::Oid,
+        behind: bool,
+    },
 }
 
 /// The success outcome of [`self::replicate`].
@@ -98,6 +323,10 @@ pub struct ReplicateResult {
     /// If this is empty, no data was fetched from the other side.
     pub updated_tips: BTreeMap<ext::RefLike, ext::Oid>,
 
+    /// The same refs as [`ReplicateResult::updated_tips`], along with the
+    /// targets they pointed to before the sync, and how they were moved.
+    pub updates: BTreeMap<ext::RefLike, fetch::TipUpdate>,
+
     /// An indicator of whether the local view of the identity document might
     /// need approval of updates.
     pub identity: IdStatus,
@@ -105,6 +334,24 @@ pub struct ReplicateResult {
     /// Whether the replicated [`Urn`] was previously present in local storage
     /// or not.
     pub mode: Mode,
+
+    /// Irregularities encountered along the way.
+    pub warnings: Vec<Warning>,
+
+    /// The fetches which were skipped without negotiating with the remote
+    /// peer, in the order they were attempted.
+    ///
+    /// If [`ReplicateResult::updated_tips`] is empty, this tells whether we
+    /// were up to date already ([`fetch::Skipped::WantNothing`]), or the
+    /// remote peer had nothing to offer ([`fetch::Skipped::NoMatchingRefs`]).
+    pub skipped: Vec<SkippedFetch>,
+}
+
+/// A fetch of a [`replicate`] which was skipped.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SkippedFetch {
+    pub step: fetch::Step,
+    pub reason: fetch::Skipped,
 }
 
 /// The "freshness" of the local view of a repo identity wrt the delegates.
@@ -171,10 +418,13 @@ enum ModeInternal {
 /// set, which is enforced by the
 /// [`crate::git::local::transport::LocalTransport`].
 #[allow(clippy::unit_arg)]
-#[tracing::instrument(skip(storage, fetcher, whoami))]
+#[tracing::instrument(
+    skip(storage, fetcher, whoami),
+    fields(urn = %fetcher.urn(), remote_peer = %fetcher.remote_peer())
+)]
 pub fn replicate<'a, F>(
     storage: &'a Storage,
-    mut fetcher: F,
+    fetcher: F,
     config: Config,
     whoami: Option<LocalIdentity>,
 ) -> Result<ReplicateResult, Error>
@@ -182,49 +432,162 @@ where
     F: fetch::Fetcher<PeerId = PeerId, UrnId = Revision>,
     F::Error: std::error::Error + Send + Sync + 'static,
 {
+    let urn = Urn::new(fetcher.urn().id);
+    let remote_peer = *fetcher.remote_peer();
+    let started = SystemTime::now();
+    let start = Instant::now();
+
+    let before = match watch::snapshot(storage.read_only(), &urn) {
+        Ok(before) => Some(before),
+        Err(e) => {
+            tracing::warn!(err = %e, "failed to snapshot namespace, inbox will not be updated");
+            None
+        },
+    };
+
+    let (res, recorder) = if config.trace {
+        let recorder = trace::Recorder::new(&fetcher, started);
+        let res = replicate_(storage, recorder.wrap(fetcher), &config, whoami);
+        (res, Some(recorder))
+    } else {
+        (replicate_(storage, fetcher, &config, whoami), None)
+    };
+    if let Some(recorder) = recorder {
+        if let Err(e) = trace::write(storage, &recorder.finish(&res)) {
+            tracing::warn!(err = %e, "failed to write replication trace");
+        }
+    }
+
+    if let (Ok(res), Some(before)) = (&res, before) {
+        if let Err(e) = inbox::update(storage, &urn, remote_peer, &before, res) {
+            tracing::warn!(err = %e, "failed to update inbox");
+        }
+    }
+    if let (Ok(res), Some(threshold)) = (&res, config.pack_refs) {
+        if !res.updated_tips.is_empty() {
+            if let Err(e) = storage::packed::compact_if(storage, &urn, threshold) {
+                tracing::warn!(err = %e, "failed to pack refs");
+            }
+        }
+    }
+
+    let entry = audit::Entry::new(urn, remote_peer, started, start.elapsed(), &res);
+    if let Err(e) = audit::record(storage, &entry) {
+        tracing::warn!(err = %e, "failed to record replication in audit log");
+    }
+
+    res
+}
+
+fn replicate_<F>(
+    storage: &Storage,
+    fetcher: F,
+    config: &Config,
+    whoami: Option<LocalIdentity>,
+) -> Result<ReplicateResult, Error>
+where
+    F: fetch::Fetcher<PeerId = PeerId, UrnId = Revision>,
+    F::Error: std::error::Error + Send + Sync + 'static,
+{
+    let mut fetcher = Updates::new(storage, fetcher, config);
+    let res = replicate_steps(storage, &mut fetcher, config, whoami);
+    // A veto stops the replication at the fetch it was raised for, so any
+    // error is a consequence of it.
+    if let Some(veto) = fetcher.vetoed.take() {
+        tracing::info!(reason = %veto, "ref updates vetoed, rolling back");
+        hooks::rollback(storage, &fetcher.updates).map_err(error::Tx::Rollback)?;
+        return Err(Error::Vetoed(veto));
+    }
+
+    let result = res?;
+    if !result.updates.is_empty() {
+        config.hooks.post_apply(&hooks::Pending {
+            urn: Urn::new(fetcher.urn().id),
+            remote_peer: *fetcher.remote_peer(),
+            updates: result.updates.clone(),
+        });
+    }
+
+    Ok(result)
+}
+
+fn replicate_steps<F>(
+    storage: &Storage,
+    fetcher: &mut Updates<F>,
+    config: &Config,
+    whoami: Option<LocalIdentity>,
+) -> Result<ReplicateResult, Error>
+where
+    F: fetch::Fetcher<PeerId = PeerId, UrnId = Revision>,
+    F::Error: std::error::Error + Send + Sync + 'static,
+{
+    if let Some(took) = fetcher.ls_refs_duration() {
+        config.metrics.ls_refs(took);
+    }
     let remote_peer = *fetcher.remote_peer();
     let local_peer_id = storage.peer_id();
     if local_peer_id == &remote_peer {
         return Err(Error::SelfReplication);
     }
     let urn = Urn::new(fetcher.urn().id);
-    let (mut updated_tips, next) = determine_mode(
+    let (mut updated_tips, next, peek_warnings) = determine_mode(
         storage,
-        &mut fetcher,
+        fetcher,
         config.fetch_limit,
+        config.sanity,
         urn.clone(),
         remote_peer,
     )?;
-    let (result, mut remove) = match next {
+    let is_project = matches!(
+        next,
+        ModeInternal::Clone {
+            identity: SomeIdentity::Project(_),
+            ..
+        } | ModeInternal::Fetch {
+            identity: SomeIdentity::Project(_),
+            ..
+        }
+    );
+    // Everything from here on is setting up the namespace, save for the time
+    // spent fetching and verifying.
+    let setup = Instant::now();
+    let fetched = fetcher.fetching;
+    let mut verified = Duration::default();
+    let (mut result, mut remove) = match next {
         ModeInternal::Clone {
             urn,
             identity,
             fetched_peers,
         } => {
-            let (allowed, id_status) = match identity {
+            let (allowed, id_status, warnings) = match identity {
                 SomeIdentity::Project(proj) => {
-                    let delegates = project::delegate_views(storage, proj, Some(remote_peer))?;
+                    let verifying = Instant::now();
+                    let delegates = project::delegate_views(
+                        storage,
+                        proj,
+                        Some(remote_peer),
+                        config.parallel_verification,
+                    )?;
                     let mut allowed = delegates.keys().copied().collect::<BTreeSet<_>>();
                     let rad_id = unsafe_into_urn(
                         Reference::rad_id(Namespace::from(&urn)).with_remote(remote_peer),
                     );
                     let proj = project::verify_with_delegate(storage, &rad_id, Some(remote_peer))?;
+                    let took = verifying.elapsed();
+                    verified += took;
+                    config.metrics.verification(took);
                     let project::SetupResult {
                         updated_tips: mut project_tips,
                         identity: id_status,
-                    } = project::ensure_setup(
-                        storage,
-                        &mut fetcher,
-                        config.fetch_limit,
-                        delegates,
-                        &rad_id,
-                        proj,
-                    )?;
+                        warnings,
+                    } = project::ensure_setup(storage, fetcher, config, delegates, &rad_id, proj)?;
                     updated_tips.append(&mut project_tips);
-                    let tracked = tracking::tracked(storage, &urn)?.collect::<BTreeSet<_>>();
+                    let tracked = tracking::tracked(storage, &urn)
+                        .map_err(error::Tx::from)?
+                        .collect::<BTreeSet<_>>();
                     allowed.extend(tracked);
 
-                    (allowed, id_status)
+                    (allowed, id_status, warnings)
                 },
                 SomeIdentity::Person(person) => {
                     let rad_id = unsafe_into_urn(
@@ -237,22 +600,25 @@ where
                         .copied()
                         .map(PeerId::from)
                         .collect();
-                    (allowed, id_status)
+                    (allowed, id_status, vec![])
                 },
 
-                unknown => return Err(Error::UnknownIdentityKind(unknown)),
+                unknown => return Err(error::Verification::UnknownIdentityKind(unknown).into()),
             };
 
             // Symref `rad/self` if a `LocalIdentity` was given
             if let Some(local_id) = whoami {
-                local_id.link(storage, &urn)?;
+                local_id.link(storage, &urn).map_err(error::Tx::from)?;
             }
 
             Ok::<_, Error>((
                 ReplicateResult {
                     updated_tips,
+                    updates: BTreeMap::new(),
+                    skipped: vec![],
                     identity: id_status,
                     mode: Mode::Clone,
+                    warnings,
                 },
                 fetched_peers.difference(&allowed).copied().collect(),
             ))
@@ -265,31 +631,41 @@ where
         } => {
             let (result, updated) = match identity {
                 SomeIdentity::Project(proj) => {
-                    let delegate_views = project::delegate_views(storage, proj, None)?;
+                    let verifying = Instant::now();
+                    let delegate_views =
+                        project::delegate_views(storage, proj, None, config.parallel_verification)?;
                     let proj = project::verify_with_delegate(storage, &urn, None)?;
+                    let took = verifying.elapsed();
+                    verified += took;
+                    config.metrics.verification(took);
                     let mut updated_delegations = project::all_delegates(&proj);
                     let rad_id = unsafe_into_urn(Reference::rad_id(Namespace::from(&urn)));
                     let project::SetupResult {
                         updated_tips: mut project_tips,
                         identity: id_status,
+                        warnings,
                     } = project::ensure_setup(
                         storage,
-                        &mut fetcher,
-                        config.fetch_limit,
+                        fetcher,
+                        config,
                         delegate_views,
                         &rad_id,
                         proj,
                     )?;
                     updated_tips.append(&mut project_tips);
 
-                    let mut updated_tracked =
-                        tracking::tracked(storage, &urn)?.collect::<BTreeSet<_>>();
+                    let mut updated_tracked = tracking::tracked(storage, &urn)
+                        .map_err(error::Tx::from)?
+                        .collect::<BTreeSet<_>>();
                     updated_tracked.append(&mut updated_delegations);
                     (
                         ReplicateResult {
                             updated_tips,
+                            updates: BTreeMap::new(),
+                            skipped: vec![],
                             identity: id_status,
                             mode: Mode::Fetch,
+                            warnings,
                         },
                         updated_tracked,
                     )
@@ -300,14 +676,19 @@ where
                     (
                         ReplicateResult {
                             updated_tips,
+                            updates: BTreeMap::new(),
+                            skipped: vec![],
                             identity: id_status,
                             mode: Mode::Fetch,
+                            warnings: vec![],
                         },
-                        tracking::tracked(storage, &urn)?.collect::<BTreeSet<_>>(),
+                        tracking::tracked(storage, &urn)
+                            .map_err(error::Tx::from)?
+                            .collect::<BTreeSet<_>>(),
                     )
                 },
 
-                unknown => return Err(Error::UnknownIdentityKind(unknown)),
+                unknown => return Err(error::Verification::UnknownIdentityKind(unknown).into()),
             };
 
             let Partition { removed, .. } = partition(&existing, &updated);
@@ -318,8 +699,39 @@ where
     // Ensure we're not tracking ourselves
     remove.insert(*local_peer_id);
 
+    result.warnings.extend(peek_warnings);
+
     // Remove any remote tracking branches we don't need
     prune(storage, &urn, remove.iter())?;
+    config.metrics.transaction(
+        setup
+            .elapsed()
+            .saturating_sub(fetcher.fetching - fetched + verified),
+    );
+
+    if is_project {
+        // The replication itself succeeded, so failing to validate or
+        // converge the default branch is not fatal.
+        match head::delegate_heads(storage, &urn) {
+            Ok(heads) => result
+                .warnings
+                .extend(heads.iter().flat_map(head::DelegateHeads::warnings)),
+            Err(e) => tracing::warn!(err = %e, "failed to validate default branch"),
+        }
+        if let Some(rule) = config.default_branch {
+            if let Err(e) = head::converge(storage, &urn, rule) {
+                tracing::warn!(err = %e, "failed to converge default branch");
+            }
+        }
+    }
+
+    result.updates = fetcher.updates.clone();
+    for warning in &result.warnings {
+        if let Warning::StaleSigrefs { name, .. } = warning {
+            result.updates.remove(name);
+        }
+    }
+    result.skipped = std::mem::take(&mut fetcher.skipped);
 
     // TODO: At this point, the tracking graph may have changed, and/or we
     // created top-level person namespaces. We will eventually converge, but
@@ -328,6 +740,193 @@ where
     Ok(result)
 }
 
+/// A [`fetch::Fetcher`] accumulating the [`fetch::TipUpdate`]s of all fetches
+/// made through it, such that refs updated by several fetches are reported
+/// once, from their first previous to their final target. Skipped fetches
+/// are recorded as [`SkippedFetch`]es.
+///
+/// The size and retries of every fetch are reported to the
+/// [`metrics::Metrics`], and the time spent fetching accumulated in
+/// `fetching`.
+///
+/// Before each fetch, the updates it is about to make are determined from the
+/// refs the remote end advertised, and passed to [`hooks::Hooks::pre_apply`]
+/// along with the ones made by previous fetches. If they are vetoed, the fetch
+/// is not made, and fails with [`UpdatesError::Vetoed`]. The [`hooks::Veto`]
+/// is kept in `vetoed`, so the updates made by previous fetches can be rolled
+/// back.
+struct Updates<'a, F> {
+    storage: &'a Storage,
+    inner: F,
+    metrics: metrics::Sink,
+    hooks: hooks::Sink,
+    updates: BTreeMap<ext::RefLike, fetch::TipUpdate>,
+    skipped: Vec<SkippedFetch>,
+    fetching: Duration,
+    vetoed: Option<hooks::Veto>,
+}
+
+/// The error of a fetch made through [`Updates`].
+#[derive(Debug, Error)]
+enum UpdatesError<E> {
+    #[error(transparent)]
+    Fetch(E),
+
+    #[error("ref updates were vetoed: {0}")]
+    Vetoed(hooks::Veto),
+}
+
+impl<'a, F> Updates<'a, F>
+where
+    F: fetch::Fetcher<PeerId = PeerId, UrnId = Revision>,
+{
+    fn new(storage: &'a Storage, inner: F, config: &Config) -> Self {
+        Self {
+            storage,
+            inner,
+            metrics: config.metrics,
+            hooks: config.hooks.clone(),
+            updates: BTreeMap::new(),
+            skipped: Vec::new(),
+            fetching: Duration::default(),
+            vetoed: None,
+        }
+    }
+
+    /// The updates fetching `specs` would make, given the refs the remote end
+    /// advertised and the current targets of the local refs.
+    fn planned(&self, specs: &[Fetchspec]) -> BTreeMap<ext::RefLike, fetch::TipUpdate> {
+        let repo = self.storage.as_raw();
+        let mut planned = BTreeMap::new();
+        for (name, tip) in self.inner.remote_heads().iter() {
+            for dst in specs.iter().filter_map(|spec| spec.dst_of(name)) {
+                let old = repo
+                    .refname_to_id(dst.as_str())
+                    .unwrap_or_else(|_| git2::Oid::zero());
+                let new = git2::Oid::from(*tip);
+                if old != new {
+                    planned.insert(dst, fetch::TipUpdate::classify(repo, old, new));
+                }
+            }
+        }
+        planned
+    }
+
+    /// Ask the [`hooks::Hooks`] whether `step` may be applied on top of the
+    /// updates made so far.
+    fn pre_apply(
+        &mut self,
+        step: &BTreeMap<ext::RefLike, fetch::TipUpdate>,
+    ) -> Result<(), hooks::Veto> {
+        let mut updates = self.updates.clone();
+        accumulate(&mut updates, step);
+        let pending = hooks::Pending {
+            urn: Urn::new(self.inner.urn().id),
+            remote_peer: *self.inner.remote_peer(),
+            updates,
+        };
+        self.hooks.pre_apply(&pending).map_err(|veto| {
+            self.vetoed = Some(veto.clone());
+            veto
+        })
+    }
+}
+
+/// Record the updates of a subsequent fetch in `updates`, such that refs
+/// updated by several fetches are reported once.
+fn accumulate(
+    updates: &mut BTreeMap<ext::RefLike, fetch::TipUpdate>,
+    next: &BTreeMap<ext::RefLike, fetch::TipUpdate>,
+) {
+    for (name, update) in next {
+        let update = match updates.get(name) {
+            Some(prev) => prev.then(*update),
+            None => *update,
+        };
+        updates.insert(name.clone(), update);
+    }
+}
+
+impl<F> fetch::Fetcher for Updates<'_, F>
+where
+    F: fetch::Fetcher<PeerId = PeerId, UrnId = Revision>,
+{
+    type Error = UpdatesError<F::Error>;
+    type PeerId = PeerId;
+    type UrnId = Revision;
+
+    fn urn(&self) -> &crate::identities::Urn<Self::UrnId> {
+        self.inner.urn()
+    }
+
+    fn remote_peer(&self) -> &Self::PeerId {
+        self.inner.remote_peer()
+    }
+
+    fn remote_heads(&self) -> &fetch::RemoteHeads {
+        self.inner.remote_heads()
+    }
+
+    fn fetch(
+        &mut self,
+        fetchspecs: fetch::Fetchspecs<Self::PeerId, Self::UrnId>,
+    ) -> Result<fetch::FetchResult, Self::Error> {
+        let specs = fetchspecs.refspecs(
+            self.inner.urn(),
+            *self.inner.remote_peer(),
+            self.inner.remote_heads(),
+        );
+        self.metrics.wants(specs.len());
+        let planned = self.planned(&specs);
+        if !planned.is_empty() {
+            self.pre_apply(&planned).map_err(UpdatesError::Vetoed)?;
+        }
+
+        let step = fetch::Step::from(&fetchspecs);
+        let start = Instant::now();
+        let res = self.inner.fetch(fetchspecs);
+        self.fetching += start.elapsed();
+        for retry in self.inner.take_retries() {
+            self.metrics.retry(retry);
+        }
+        let res = res.map_err(UpdatesError::Fetch)?;
+
+        // The remote end may have moved its refs since advertising them, in
+        // which case the updates made were not the ones approved.
+        let unplanned = res
+            .updates
+            .iter()
+            .any(|(name, update)| planned.get(name).map(|p| p.new) != Some(update.new));
+        let vetoed = if unplanned {
+            self.pre_apply(&res.updates).err()
+        } else {
+            None
+        };
+
+        if let Some(pack) = &res.pack {
+            self.metrics.pack(pack.received_bytes);
+        }
+        if let Some(reason) = res.skipped {
+            self.skipped.push(SkippedFetch { step, reason });
+        }
+        accumulate(&mut self.updates, &res.updates);
+        // Rolled back along with the updates of previous fetches
+        if let Some(veto) = vetoed {
+            return Err(UpdatesError::Vetoed(veto));
+        }
+
+        Ok(res)
+    }
+
+    fn remote_features(&self) -> Option<Features> {
+        self.inner.remote_features()
+    }
+
+    fn ls_refs_duration(&self) -> Option<Duration> {
+        self.inner.ls_refs_duration()
+    }
+}
+
 /// Identify the type of replication case we're in -- whether it's a new
 /// identity which we're cloning onto our machine or an existing identity that
 /// we are updating.
@@ -347,29 +946,36 @@ fn determine_mode<F>(
     storage: &Storage,
     fetcher: &mut F,
     limit: fetch::Limit,
+    sanity_limits: sanity::Limits,
     urn: Urn,
     remote_peer: PeerId,
-) -> Result<(BTreeMap<ext::RefLike, ext::Oid>, ModeInternal), Error>
+) -> Result<(BTreeMap<ext::RefLike, ext::Oid>, ModeInternal, Vec<Warning>), Error>
 where
     F: fetch::Fetcher<PeerId = PeerId>,
     F::Error: std::error::Error + Send + Sync + 'static,
 {
-    if !storage.has_urn(&urn)? {
-        let updated = fetcher
+    sanity_limits.check_advertised(fetcher.remote_heads())?;
+    let before = sanity::snapshot(storage, &urn)?;
+
+    if !storage.has_urn(&urn).map_err(error::Tx::from)? {
+        let mut updated = fetcher
             .fetch(fetch::Fetchspecs::PeekAll { limit })
-            .map_err(|e| Error::Fetch(e.into()))?;
+            .map_err(|e| Error::Peek(error::Fetch::new(e)))?;
+        let mut warnings = keep_sigrefs(storage, &mut updated)?;
         let fetched_peers = project::fetched_peers(&updated)?;
 
         let mut tips = updated.updated_tips;
         // We can't fetch `refs/remotes/*/rad/ids/*` since we can't have two globs, so
         // we fetch `refs/remotes/{fetched_peer}/rad/ids/*`.
-        let peeked = fetcher
+        let mut peeked = fetcher
             .fetch(fetch::Fetchspecs::Peek {
                 remotes: fetched_peers.clone(),
                 limit,
             })
-            .map_err(|e| Error::Fetch(e.into()))?;
+            .map_err(|e| Error::Peek(error::Fetch::new(e)))?;
+        warnings.append(&mut keep_sigrefs(storage, &mut peeked)?);
         tips.extend(peeked.updated_tips);
+        check_peeked(storage, &urn, sanity_limits, &before, &tips)?;
 
         let remote_ident =
             unsafe_into_urn(Reference::rad_id(Namespace::from(&urn)).with_remote(remote_peer));
@@ -378,43 +984,126 @@ where
             ModeInternal::Clone {
                 urn,
                 fetched_peers,
-                identity: identities::any::get(storage, &remote_ident)?
-                    .ok_or(Error::MissingIdentity)?,
+                identity: identities::any::get(storage, &remote_ident)
+                    .map_err(error::Verification::from)?
+                    .ok_or(error::Verification::MissingIdentity)?,
             },
+            warnings,
         ))
     } else {
-        let identity = identities::any::get(storage, &urn)?.ok_or(Error::MissingIdentity)?;
+        let identity = identities::any::get(storage, &urn)
+            .map_err(error::Verification::from)?
+            .ok_or(error::Verification::MissingIdentity)?;
+        let tracked = || {
+            tracking::tracked(storage, &urn)
+                .map(|tracked| tracked.collect::<BTreeSet<_>>())
+                .map_err(error::Negotiation::Tracked)
+        };
         let existing = match identity {
             SomeIdentity::Project(ref proj) => {
                 let mut remotes = project::all_delegates(proj);
-                let mut tracked = tracking::tracked(storage, &urn)?.collect::<BTreeSet<_>>();
-                remotes.append(&mut tracked);
+                remotes.append(&mut tracked()?);
 
                 remotes
             },
-            SomeIdentity::Person(_) => tracking::tracked(storage, &urn)?.collect::<BTreeSet<_>>(),
+            SomeIdentity::Person(_) => tracked()?,
 
-            unknown => return Err(Error::UnknownIdentityKind(unknown)),
+            unknown => return Err(error::Verification::UnknownIdentityKind(unknown).into()),
         };
 
-        let fetch::FetchResult { updated_tips } = fetcher
+        let mut peeked = fetcher
             .fetch(fetch::Fetchspecs::Peek {
                 remotes: existing.clone(),
                 limit,
             })
-            .map_err(|e| Error::Fetch(e.into()))?;
+            .map_err(|e| Error::Peek(error::Fetch::new(e)))?;
+        let warnings = keep_sigrefs(storage, &mut peeked)?;
+        check_peeked(storage, &urn, sanity_limits, &before, &peeked.updated_tips)?;
 
         Ok((
-            updated_tips,
+            peeked.updated_tips,
             ModeInternal::Fetch {
                 urn,
                 identity,
                 existing,
             },
+            warnings,
         ))
     }
 }
 
+/// Apply the [`sanity`] checks to the `updated` refs of a peek, rolling
+/// back the peek if they fail.
+fn check_peeked(
+    storage: &Storage,
+    urn: &Urn,
+    limits: sanity::Limits,
+    before: &BTreeMap<ext::RefLike, ext::Oid>,
+    updated: &BTreeMap<ext::RefLike, ext::Oid>,
+) -> Result<(), Error> {
+    if let Err(e) = limits.check_peeked(storage, updated) {
+        tracing::warn!(err = %e, "rejecting namespace");
+        sanity::rollback(storage, urn, before, updated)?;
+        return Err(e.into());
+    }
+
+    Ok(())
+}
+
+/// Refuse the updates in `res` of the `rad/signed_refs` of remote peers which
+/// don't fast-forward the ones we already have.
+///
+/// Signed refs only ever move forward, so a remote peer serving older or
+/// diverging ones is either behind, or misbehaving. The affected refs are
+/// reset to their previous target and removed from `res`, and a
+/// [`Warning::StaleSigrefs`] is returned for each of them.
+fn keep_sigrefs(storage: &Storage, res: &mut fetch::FetchResult) -> Result<Vec<Warning>, Error> {
+    let repo = storage.as_raw();
+    let stale = res
+        .updates
+        .iter()
+        .filter(|(_, update)| update.kind == fetch::UpdateKind::Forced)
+        .filter_map(|(name, update)| {
+            let peer = remote_sigrefs_of(name)?;
+            Some((name.clone(), peer, update.old?, update.new))
+        })
+        .collect::<Vec<_>>();
+
+    let mut warnings = Vec::with_capacity(stale.len());
+    for (name, peer, stored, fetched) in stale {
+        let behind = repo.graph_descendant_of(*stored, *fetched).unwrap_or(false);
+        tracing::warn!(%peer, %stored, %fetched, behind, "refusing stale signed refs");
+        repo.reference(name.as_str(), *stored, true, "refusing stale signed refs")
+            .map_err(|source| error::Tx::StaleSigrefs {
+                name: name.clone(),
+                source,
+            })?;
+        res.updated_tips.remove(&name);
+        res.updates.remove(&name);
+        warnings.push(Warning::StaleSigrefs {
+            peer,
+            name,
+            stored,
+            fetched,
+            behind,
+        });
+    }
+
+    Ok(warnings)
+}
+
+/// The peer whose signed refs `name` is, if it is of the form
+/// `refs/namespaces/<urn>/refs/remotes/<peer>/rad/signed_refs`.
+fn remote_sigrefs_of(name: &ext::RefLike) -> Option<PeerId> {
+    let components = name.as_str().split('/').collect::<Vec<_>>();
+    match components.as_slice() {
+        ["refs", "namespaces", _, "refs", "remotes", peer, "rad", "signed_refs"] => {
+            peer.parse().ok()
+        },
+        _ => None,
+    }
+}
+
 fn unsafe_into_urn(reference: Reference<git_ext::RefLike>) -> Urn {
     reference.try_into().expect("namespace is set")
 }
@@ -428,24 +1117,28 @@ fn ensure_rad_id(storage: &Storage, urn: &Urn, tip: ext::Oid) -> Result<ext::Oid
     let id_ref = identities::common::IdRef::from(urn);
     id_ref
         .create(storage, tip)
-        .map_err(|e| Error::Store(e.into()))?;
+        .map_err(|e| error::Tx::from(storage::Error::from(e)))?;
 
-    id_ref.oid(storage).map(Into::into).map_err(Error::Store)
+    Ok(id_ref
+        .oid(storage)
+        .map(Into::into)
+        .map_err(error::Tx::from)?)
 }
 
 fn adopt_rad_self(storage: &Storage, urn: &Urn, peer: PeerId) -> Result<(), Error> {
     let rad_self = Reference::rad_self(Namespace::from(urn), peer);
 
     // We only need to create the rad/id there's a rad/self
-    if storage.has_ref(&rad_self)? {
+    if storage.has_ref(&rad_self).map_err(error::Tx::from)? {
         if let Some(person) =
-            identities::person::verify(storage, &unsafe_into_urn(rad_self.clone()))?
+            identities::person::verify(storage, &unsafe_into_urn(rad_self.clone()))
+                .map_err(error::Verification::from)?
         {
             let rad_id = unsafe_into_urn(Reference::rad_id(Namespace::from(person.urn())));
-            if !storage.has_urn(&person.urn())? {
+            if !storage.has_urn(&person.urn()).map_err(error::Tx::from)? {
                 ensure_rad_id(storage, &rad_id, person.content_id)?;
                 symref(storage, &rad_id, rad_self)?;
-                tracking::track(storage, &rad_id, peer)?;
+                tracking::track(storage, &rad_id, peer).map_err(error::Tx::from)?;
             }
         }
     }
@@ -456,7 +1149,7 @@ fn adopt_rad_self(storage: &Storage, urn: &Urn, peer: PeerId) -> Result<(), Erro
 fn symref(storage: &Storage, top_level: &Urn, symbolic: Reference<One>) -> Result<(), Error> {
     // Now point our view to the top-level
     Reference::try_from(top_level)
-        .map_err(|e| Error::RefFromUrn {
+        .map_err(|e| error::Tx::RefFromUrn {
             urn: top_level.clone(),
             source: e,
         })?
@@ -464,7 +1157,7 @@ fn symref(storage: &Storage, top_level: &Urn, symbolic: Reference<One>) -> Resul
         .create(storage.as_raw())
         .and(Ok(()))
         .or_matches(is_exists_err, || Ok(()))
-        .map_err(|e: git2::Error| Error::Store(e.into()))
+        .map_err(|e: git2::Error| error::Tx::from(storage::Error::from(e)).into())
 }
 
 /// Untrack the list of `PeerId`s, which also has the side-effect of removing
@@ -496,7 +1189,7 @@ fn prune<'a>(
             },
             Err(err) => {
                 tracing::warn!(peer = %peer, err = %err, "failed to prune");
-                return Err(err.into());
+                return Err(error::Tx::from(err).into());
             },
         }
     }
@@ -557,8 +1250,10 @@ mod person {
         let local_peer = storage.peer_id();
         let urn = person.urn();
 
-        let delegations = match identities::person::verify(storage, rad_id)? {
-            None => Err(Error::MissingIdentity),
+        let verified =
+            identities::person::verify(storage, rad_id).map_err(error::Verification::from)?;
+        let delegations = match verified {
+            None => Err(Error::from(error::Verification::MissingIdentity)),
             Some(person) => {
                 let delegations = person
                     .into_inner()
@@ -569,11 +1264,14 @@ mod person {
                     .collect::<BTreeSet<_>>();
 
                 // Track all delegations
-                for peer_id in delegations.iter() {
-                    if peer_id != local_peer {
-                        tracking::track(storage, &urn, *peer_id)?;
-                        adopt_rad_self(storage, &urn, *peer_id)?;
-                    }
+                let remotes = delegations.iter().filter(|peer_id| *peer_id != local_peer);
+                tracking::track_batch(
+                    storage,
+                    remotes.clone().map(|peer_id| (urn.clone(), *peer_id)),
+                )
+                .map_err(error::Tx::from)?;
+                for peer_id in remotes {
+                    adopt_rad_self(storage, &urn, *peer_id)?;
                 }
 
                 Ok(delegations)
@@ -600,8 +1298,9 @@ mod person {
             .map(|peer| {
                 let remote_urn =
                     unsafe_into_urn(Reference::rad_id(Namespace::from(urn)).with_remote(peer));
-                let verified = identities::person::verify(storage, &remote_urn)?
-                    .ok_or_else(|| Error::MissingIdentities(remote_urn.clone()))?;
+                let verified = identities::person::verify(storage, &remote_urn)
+                    .map_err(error::Verification::from)?
+                    .ok_or_else(|| error::Verification::MissingIdentities(remote_urn.clone()))?;
 
                 Ok((peer, verified))
             })
@@ -612,7 +1311,8 @@ mod person {
                 match prev {
                     None => prev = Some(pers),
                     Some(p) => {
-                        let newer = identities::person::newer(storage, p, pers)?;
+                        let newer = identities::person::newer(storage, p, pers)
+                            .map_err(error::Verification::from)?;
                         prev = Some(newer);
                     },
                 }
@@ -646,6 +1346,7 @@ mod project {
     pub struct SetupResult {
         pub updated_tips: BTreeMap<ext::RefLike, ext::Oid>,
         pub identity: IdStatus,
+        pub warnings: Vec<Warning>,
     }
 
     /// Process the setup of a `Project` by:
@@ -663,7 +1364,7 @@ mod project {
     pub fn ensure_setup<F>(
         storage: &Storage,
         fetcher: &mut F,
-        limit: fetch::Limit,
+        config: &Config,
         delegates: BTreeMap<PeerId, project::DelegateView>,
         rad_id: &Urn,
         proj: VerifiedProject,
@@ -677,31 +1378,41 @@ mod project {
         let id_status = self::adopt_latest(storage, &urn, &delegates)?;
 
         self::track_direct(storage, &proj)?;
-        let (fetch_result, tracked) = replicate_signed_refs(
+        let (fetch_result, tracked, warnings) = replicate_signed_refs(
             storage,
             fetcher,
-            limit,
+            config,
             &urn,
             delegates
                 .values()
                 .map(|delegate| delegate.urn.clone())
                 .collect(),
+            &all_delegates(&proj),
         )?;
+        let tracked = tracked
+            .into_iter()
+            .filter(|peer| peer != local_peer)
+            .collect::<Vec<_>>();
+        tracking::track_batch(storage, tracked.iter().map(|peer| (urn.clone(), *peer)))
+            .map_err(error::Tx::from)?;
         for peer in tracked {
-            if peer != *local_peer {
-                tracking::track(storage, &urn, peer)?;
-                adopt_rad_self(storage, &urn, peer)?;
-            }
+            adopt_rad_self(storage, &urn, peer)?;
         }
 
         Ok(SetupResult {
             updated_tips: fetch_result.updated_tips,
             identity: id_status,
+            warnings,
         })
     }
 
     /// Fetch `rad/signed_refs` and `refs/heads` of the delegates and our
     /// tracked graph, returning the set of tracked peers.
+    ///
+    /// Collaborative objects excluded via [`tracking::exclude_cobs`], or all
+    /// of them if the remote peer is known not to support
+    /// [`Features::COBS`], are skipped, and those not authorized according to
+    /// [`cobs::policy`] are quarantined.
     #[tracing::instrument(
         level = "trace",
         skip(storage, fetcher, urn),
@@ -711,17 +1422,20 @@ mod project {
     pub fn replicate_signed_refs<F>(
         storage: &Storage,
         fetcher: &mut F,
-        limit: fetch::Limit,
+        config: &Config,
         urn: &Urn,
         delegates: BTreeSet<Urn>,
-    ) -> Result<(fetch::FetchResult, BTreeSet<PeerId>), Error>
+        delegate_peers: &BTreeSet<PeerId>,
+    ) -> Result<(fetch::FetchResult, BTreeSet<PeerId>, Vec<Warning>), Error>
     where
         F: fetch::Fetcher<PeerId = PeerId, UrnId = Revision>,
         F::Error: std::error::Error + Send + Sync + 'static,
     {
         // Read `signed_refs` for all tracked
-        let tracked = tracking::tracked(storage, urn)?.collect::<BTreeSet<_>>();
-        let tracked_sigrefs = tracked
+        let tracked = tracking::tracked(storage, urn)
+            .map_err(error::Negotiation::Tracked)?
+            .collect::<BTreeSet<_>>();
+        let mut tracked_sigrefs = tracked
             .into_iter()
             .filter_map(|peer| match Refs::load(storage, urn, peer) {
                 Ok(Some(refs)) => Some(Ok((peer, refs))),
@@ -729,36 +1443,213 @@ mod project {
                 Ok(None) => None,
                 Err(e) => Some(Err(e)),
             })
-            .collect::<Result<BTreeMap<_, _>, _>>()?;
+            .collect::<Result<BTreeMap<_, _>, _>>()
+            .map_err(Error::Validation)?;
+
+        // Peers which don't serve collaborative objects can't have any, so
+        // don't ask for them
+        let remote_cobs = fetcher
+            .remote_features()
+            .map_or(true, |features| features.contains(Features::COBS));
+        if !remote_cobs {
+            tracing::debug!("remote peer does not serve collaborative objects");
+            for refs in tracked_sigrefs.values_mut() {
+                refs.cobs.clear();
+            }
+        }
+        // Skip the collaborative objects we're not interested in
+        for (peer, refs) in tracked_sigrefs.iter_mut() {
+            let excluded = tracking::excluded_cobs(storage, urn, *peer)
+                .map_err(error::Negotiation::ExcludedCobs)?;
+            if excluded.contains(tracking::ALL_COBS) {
+                refs.cobs.clear();
+            } else if !excluded.is_empty() {
+                refs.cobs
+                    .retain(|name, _| !excluded.contains(cobs::typename_of(name)));
+            }
+        }
+        // Don't write any names we consider suspicious
+        for (peer, refs) in tracked_sigrefs.iter_mut() {
+            config.hygiene.apply(*peer, refs)?;
+        }
+        cobs::policy::authorize(storage, urn, delegate_peers, &mut tracked_sigrefs)
+            .map_err(error::Negotiation::from)?;
+
+        let limit = match config.adaptive_fetch_limit {
+            None => config.fetch_limit,
+            Some(adaptive) => {
+                let largest = pack_sizes::largest(storage, urn).unwrap_or_else(|e| {
+                    tracing::warn!(err = %e, "failed to read pack size record");
+                    None
+                });
+                let data = adaptive.limit(largest, config.fetch_limit.data);
+                tracing::debug!(?largest, data, "adapted fetch limit");
+                fetch::Limit {
+                    data,
+                    ..config.fetch_limit
+                }
+            },
+        };
 
         // Fetch all the rest
         tracing::debug!("fetching heads: {:?}, {:?}", tracked_sigrefs, delegates);
-        let res = fetcher
+        let mut res = fetcher
             .fetch(fetch::Fetchspecs::Replicate {
                 tracked_sigrefs: tracked_sigrefs.clone(),
                 delegates,
                 limit,
             })
-            .map_err(|e| Error::Fetch(e.into()))?;
+            .map_err(|e| Error::Fetch(error::Fetch::new(e)))?;
+        if let (Some(_), Some(pack)) = (config.adaptive_fetch_limit, &res.pack) {
+            if let Err(e) = pack_sizes::record(storage, urn, pack.received_bytes) {
+                tracing::warn!(err = %e, "failed to record pack size");
+            }
+        }
+        let mut stale = keep_sigrefs(storage, &mut res)?;
 
-        Refs::update(storage, urn)?;
-        Ok((
-            res,
-            tracked_sigrefs
-                .iter()
-                .flat_map(|(peer, refs)| iter::once(*peer).chain(refs.remotes.flatten().copied()))
-                .collect(),
-        ))
+        Refs::update(storage, urn).map_err(error::Tx::Sigrefs)?;
+        let (tracked, mut warnings) = discovered_peers(&tracked_sigrefs, config.remotes);
+        warnings.append(&mut stale);
+        Ok((res, tracked, warnings))
+    }
+
+    /// The tracked peers, and the peers in their tracking graphs, within the
+    /// bounds of `limit`.
+    fn discovered_peers(
+        tracked_sigrefs: &BTreeMap<PeerId, Refs>,
+        limit: RemotesLimit,
+    ) -> (BTreeSet<PeerId>, Vec<Warning>) {
+        let mut peers = tracked_sigrefs.keys().copied().collect::<BTreeSet<_>>();
+        let mut warnings = Vec::new();
+        let mut budget = limit.total;
+        let mut over_budget = BTreeSet::new();
+        for (peer, refs) in tracked_sigrefs {
+            let (remotes, omitted) = refs.remotes.flatten_bounded(limit.per_peer);
+            if omitted > 0 {
+                tracing::warn!(%peer, omitted, "too many remotes in signed refs");
+                warnings.push(Warning::RemotesPerPeer {
+                    peer: *peer,
+                    omitted,
+                });
+            }
+            for remote in remotes {
+                if peers.contains(remote) {
+                    continue;
+                }
+                if budget > 0 {
+                    peers.insert(*remote);
+                    budget -= 1;
+                } else {
+                    over_budget.insert(*remote);
+                }
+            }
+        }
+        if !over_budget.is_empty() {
+            tracing::warn!(
+                omitted = over_budget.len(),
+                "too many remotes in signed refs of tracked peers"
+            );
+            warnings.push(Warning::RemotesTotal {
+                omitted: over_budget.len(),
+            });
+        }
+
+        (peers, warnings)
     }
 
     /// For each delegate in `remotes/<remote_peer>/rad/ids/*` get the view for
     /// that delegate that _should_ be local the `storage` after a fetch.
+    ///
+    /// If `parallelism` is given, the delegates' views of the project are
+    /// verified concurrently, see [`Config::parallel_verification`].
     #[allow(clippy::unit_arg)]
     #[tracing::instrument(level = "trace", skip(storage))]
     pub fn delegate_views(
         storage: &Storage,
         proj: Project,
         remote_peer: Option<PeerId>,
+        parallelism: Option<NonZeroUsize>,
+    ) -> Result<BTreeMap<PeerId, DelegateView>, Error> {
+        let parallelism = match parallelism {
+            Some(n) if n.get() > 1 => n,
+            _ => return delegate_views_sequential(storage, proj, remote_peer),
+        };
+
+        // Adopting the delegate persons writes to the storage, so happens
+        // upfront. Only the verification of the project is parallelised.
+        let mut pending = Vec::new();
+        let local_peer_id = storage.peer_id();
+        for delegate in proj.delegations().iter().indirect() {
+            let in_rad_ids = unsafe_into_urn(
+                Reference::rad_delegate(Namespace::from(&proj.urn()), &delegate.urn())
+                    .with_remote(remote_peer),
+            );
+            match identities::person::verify(storage, &in_rad_ids)
+                .map_err(error::Verification::from)?
+            {
+                None => return Err(error::Verification::Missing(in_rad_ids.into()).into()),
+                Some(person) => {
+                    for key in person.delegations().iter() {
+                        let peer_id = PeerId::from(*key);
+                        let urn = if &peer_id == local_peer_id {
+                            proj.urn()
+                        } else {
+                            adopt_delegate_person(storage, peer_id, &person, &proj.urn())?;
+                            unsafe_into_urn(
+                                Reference::rad_id(Namespace::from(&proj.urn()))
+                                    .with_remote(peer_id),
+                            )
+                        };
+                        pending.push((peer_id, urn, person.clone()));
+                    }
+                },
+            }
+        }
+
+        let chunk_size = (pending.len() + parallelism.get() - 1) / parallelism.get();
+        let mut workers = Vec::new();
+        let mut pending = pending.into_iter().peekable();
+        while pending.peek().is_some() {
+            let chunk = pending.by_ref().take(chunk_size.max(1)).collect::<Vec<_>>();
+            let storage = storage
+                .read_only()
+                .reopen()
+                .map_err(|e| error::Tx::from(storage::Error::from(e)))?;
+            workers.push(thread::spawn(move || {
+                chunk
+                    .into_iter()
+                    .map(|(peer_id, urn, delegate)| {
+                        let project = verify_with_delegate(&storage, &urn, remote_peer)?;
+                        Ok((
+                            peer_id,
+                            DelegateView {
+                                urn,
+                                delegate,
+                                project,
+                            },
+                        ))
+                    })
+                    .collect::<Vec<Result<_, Error>>>()
+            }));
+        }
+
+        // Join all workers before inspecting the results, and report the first
+        // error in delegation order, such that the outcome is deterministic.
+        let results = workers
+            .into_iter()
+            .map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e))
+            })
+            .collect::<Vec<_>>();
+        results.into_iter().flatten().collect()
+    }
+
+    fn delegate_views_sequential(
+        storage: &Storage,
+        proj: Project,
+        remote_peer: Option<PeerId>,
     ) -> Result<BTreeMap<PeerId, DelegateView>, Error> {
         let mut delegate_views = BTreeMap::new();
         let local_peer_id = storage.peer_id();
@@ -767,8 +1658,10 @@ mod project {
                 Reference::rad_delegate(Namespace::from(&proj.urn()), &delegate.urn())
                     .with_remote(remote_peer),
             );
-            match identities::person::verify(storage, &in_rad_ids)? {
-                None => return Err(Error::Missing(in_rad_ids.into())),
+            match identities::person::verify(storage, &in_rad_ids)
+                .map_err(error::Verification::from)?
+            {
+                None => return Err(error::Verification::Missing(in_rad_ids.into()).into()),
                 Some(delegate_person) => {
                     let person = delegate_person.clone();
                     for key in delegate_person.delegations().iter() {
@@ -816,12 +1709,15 @@ mod project {
         let delegate_urn = person.urn();
 
         // if the identity is known we see if we can fast-forward it
-        if storage.has_urn(&delegate_urn)? {
-            identities::person::fast_forward(storage, person)?;
+        if storage.has_urn(&delegate_urn).map_err(error::Tx::from)? {
+            identities::person::fast_forward(storage, person).map_err(error::Verification::from)?;
         } else {
             ensure_rad_id(storage, &delegate_urn, person.content_id)?;
-            tracking::track(storage, &delegate_urn, peer)?;
-            tracking::track(storage, project_urn, peer)?;
+            tracking::track_batch(
+                storage,
+                vec![(delegate_urn.clone(), peer), (project_urn.clone(), peer)],
+            )
+            .map_err(error::Tx::from)?;
         }
 
         // Now point our view to the top-level
@@ -837,15 +1733,17 @@ mod project {
     #[tracing::instrument(level = "trace", skip(storage))]
     fn track_direct(storage: &Storage, proj: &VerifiedProject) -> Result<(), Error> {
         let local_peer_id = storage.peer_id();
+        let urn = proj.urn();
 
-        for key in proj
-            .delegations()
-            .iter()
-            .direct()
-            .filter(|&key| key != local_peer_id.as_public_key())
-        {
-            tracking::track(storage, &proj.urn(), PeerId::from(*key))?;
-        }
+        tracking::track_batch(
+            storage,
+            proj.delegations()
+                .iter()
+                .direct()
+                .filter(|&key| key != local_peer_id.as_public_key())
+                .map(|key| (urn.clone(), PeerId::from(*key))),
+        )
+        .map_err(error::Tx::from)?;
 
         Ok(())
     }
@@ -868,7 +1766,8 @@ mod project {
                 match prev {
                     None => prev = Some(proj),
                     Some(p) => {
-                        let newer = identities::project::newer(storage, p, proj)?;
+                        let newer = identities::project::newer(storage, p, proj)
+                            .map_err(error::Verification::from)?;
                         prev = Some(newer);
                     },
                 }
@@ -946,7 +1845,8 @@ mod project {
             let refname =
                 Reference::rad_delegate(Namespace::from(urn.clone()), &delegate).with_remote(peer);
             storage.reference_oid(&refname).map(|oid| oid.into())
-        })?
-        .ok_or(Error::MissingIdentity)
+        })
+        .map_err(error::Verification::from)?
+        .ok_or_else(|| error::Verification::MissingIdentity.into())
     }
 }
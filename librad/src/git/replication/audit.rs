@@ -0,0 +1,241 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Append-only log of replication attempts.
+//!
+//! Every invocation of [`super::replicate`] appends an [`Entry`] to a log file
+//! stored alongside the monorepo. This is mainly useful to answer questions
+//! like "why does my seed not have X?".
+//!
+//! Once the log grows beyond [`MAX_LOG_SIZE`], it is rotated, replacing the
+//! previously rotated log. The log thus occupies at most about twice that
+//! size.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read as _, Seek as _, SeekFrom, Write as _},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{Error as ReplicationError, Mode, ReplicateResult, Urn};
+use crate::{git::storage::Storage, PeerId};
+
+const LOG_FILE: &str = "replication.log";
+const ROTATED_LOG_FILE: &str = "replication.log.1";
+
+/// Size in bytes beyond which the log is rotated.
+pub const MAX_LOG_SIZE: u64 = 8 * 1024 * 1024;
+
+/// Size of the chunks in which [`recent`] reads the log backwards.
+const CHUNK_SIZE: u64 = 64 * 1024;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+/// A single replication attempt.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Entry {
+    pub urn: Urn,
+    pub remote_peer: PeerId,
+    /// Start of the attempt, in seconds since the UNIX epoch.
+    pub timestamp: u64,
+    /// Duration of the attempt, in milliseconds.
+    pub duration: u64,
+    pub outcome: Outcome,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum Outcome {
+    /// The replication succeeded.
+    #[serde(rename_all = "camelCase")]
+    Success {
+        /// `true` if the [`Urn`] was not present locally before.
+        clone: bool,
+        /// Number of refs updated.
+        updated: usize,
+    },
+    /// The replication failed.
+    #[serde(rename_all = "camelCase")]
+    Failure {
+        /// The formatted error.
+        error: String,
+        /// See [`super::Error::is_retryable`].
+        retryable: bool,
+    },
+}
+
+impl Entry {
+    pub fn new(
+        urn: Urn,
+        remote_peer: PeerId,
+        started: SystemTime,
+        duration: Duration,
+        result: &Result<ReplicateResult, ReplicationError>,
+    ) -> Self {
+        let outcome = match result {
+            Ok(res) => Outcome::Success {
+                clone: matches!(res.mode, Mode::Clone),
+                updated: res.updated_tips.len(),
+            },
+            Err(e) => Outcome::Failure {
+                error: e.to_string(),
+                retryable: e.is_retryable(),
+            },
+        };
+
+        Self {
+            urn,
+            remote_peer,
+            timestamp: started
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            duration: duration.as_millis() as u64,
+            outcome,
+        }
+    }
+}
+
+fn log_path(storage: &Storage) -> PathBuf {
+    storage.as_raw().path().join(LOG_FILE)
+}
+
+fn rotated_log_path(storage: &Storage) -> PathBuf {
+    storage.as_raw().path().join(ROTATED_LOG_FILE)
+}
+
+/// Append `entry` to the audit log of `storage`, rotating the log if it
+/// exceeds [`MAX_LOG_SIZE`].
+pub fn record(storage: &Storage, entry: &Entry) -> Result<(), Error> {
+    record_with_limit(storage, entry, MAX_LOG_SIZE)
+}
+
+/// Like [`record`], but rotate the log if it exceeds `max_size` bytes.
+pub fn record_with_limit(storage: &Storage, entry: &Entry, max_size: u64) -> Result<(), Error> {
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+
+    let path = log_path(storage);
+    match fs::metadata(&path) {
+        Ok(meta) if meta.len() >= max_size => {
+            // Concurrent writers may race to rotate, in which case the loser
+            // rotates a log containing only the winner's entry. As this
+            // requires both to observe the full log, it is exceedingly rare.
+            fs::rename(&path, rotated_log_path(storage))?;
+        },
+        Ok(_) => {},
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {},
+        Err(e) => return Err(e.into()),
+    }
+
+    // `O_APPEND` ensures concurrent writers don't clobber each other's lines, as
+    // long as each line is written in a single call.
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(&line)?;
+
+    Ok(())
+}
+
+/// Read the `limit` most recent entries from the audit log of `storage`,
+/// oldest first.
+///
+/// If `urn` is given, only entries pertaining to that [`Urn`] are considered.
+/// Malformed lines are skipped.
+///
+/// The log is read backwards from its end, so only as much of it is read as
+/// is needed to find `limit` entries. The rotated log is consulted if the
+/// current one doesn't contain enough.
+pub fn recent(storage: &Storage, urn: Option<&Urn>, limit: usize) -> Result<Vec<Entry>, Error> {
+    let mut entries = Vec::with_capacity(limit);
+    for path in &[log_path(storage), rotated_log_path(storage)] {
+        if entries.len() >= limit {
+            break;
+        }
+        read_backwards(path, urn, limit, &mut entries)?;
+    }
+    entries.reverse();
+
+    Ok(entries)
+}
+
+/// Push the entries of the log at `path` onto `entries`, newest first, until
+/// there are `limit` of them.
+fn read_backwards(
+    path: &Path,
+    urn: Option<&Urn>,
+    limit: usize,
+    entries: &mut Vec<Entry>,
+) -> Result<(), Error> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut pos = file.seek(SeekFrom::End(0))?;
+    // The tail of a line whose beginning wasn't read yet
+    let mut partial = Vec::new();
+    while pos > 0 && entries.len() < limit {
+        let len = CHUNK_SIZE.min(pos);
+        pos -= len;
+        file.seek(SeekFrom::Start(pos))?;
+        let mut chunk = vec![0; len as usize];
+        file.read_exact(&mut chunk)?;
+        chunk.append(&mut partial);
+
+        // Unless we're at the start of the file, the first line may be
+        // incomplete
+        let lines = if pos > 0 {
+            match chunk.iter().position(|b| *b == b'\n') {
+                None => {
+                    partial = chunk;
+                    continue;
+                },
+                Some(i) => {
+                    let lines = chunk.split_off(i + 1);
+                    chunk.truncate(i);
+                    partial = chunk;
+                    lines
+                },
+            }
+        } else {
+            chunk
+        };
+
+        for line in lines.split(|b| *b == b'\n').rev() {
+            if line.is_empty() {
+                continue;
+            }
+            let entry = match serde_json::from_slice::<Entry>(line) {
+                Ok(entry) => entry,
+                Err(e) => {
+                    tracing::warn!(err = %e, "skipping malformed audit log entry");
+                    continue;
+                },
+            };
+            if urn.map(|urn| urn == &entry.urn).unwrap_or(true) {
+                entries.push(entry);
+                if entries.len() >= limit {
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,301 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Converging the local default branch of a project onto the heads of its
+//! delegates.
+//!
+//! After a project has been replicated, its local `refs/heads/<default
+//! branch>` can be updated to the head the delegates agree on, according to a
+//! [`Convergence`] rule. Working copies checking out the project will then
+//! see the canonical state without having to inspect each delegate's remote
+//! branch.
+//!
+//! The local branch is only ever fast-forwarded: if it has diverged from the
+//! converged head (eg. because the local peer committed to it), it is left
+//! untouched. Once the local branch exists, the `HEAD` of the project's
+//! namespace is pointed at it, so clones of the namespace check out the
+//! default branch.
+//!
+//! Independently of converging, the [`DelegateHeads`] can be inspected to
+//! detect delegates which lack the default branch, or disagree on its head.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryFrom,
+};
+
+use git_ext as ext;
+use thiserror::Error;
+
+use super::{Urn, Warning};
+use crate::{
+    git::{
+        identities,
+        storage::Storage,
+        types::{Force, Namespace, One, Reference},
+    },
+    PeerId,
+};
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("invalid default branch `{0}`")]
+    InvalidBranch(String),
+
+    #[error(transparent)]
+    Identities(#[from] Box<identities::Error>),
+
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+}
+
+impl From<identities::Error> for Error {
+    fn from(e: identities::Error) -> Self {
+        Self::Identities(Box::new(e))
+    }
+}
+
+/// Rule to determine the head of the default branch from the delegates'
+/// heads.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Convergence {
+    /// All delegates must point to the same commit.
+    All,
+    /// More than half of the delegates must point to the same commit.
+    Quorum,
+    /// The delegate head with the most recent commit time wins.
+    Newest,
+}
+
+/// The outcome of [`converge`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Converged {
+    /// The project has no default branch, or the delegates did not converge
+    /// according to the [`Convergence`] rule.
+    None,
+    /// The local default branch already points to the converged head.
+    Unchanged(ext::Oid),
+    /// The local default branch was created or fast-forwarded.
+    Updated(ext::Oid),
+    /// The local default branch is not an ancestor of the converged head.
+    Diverged {
+        local: ext::Oid,
+        converged: ext::Oid,
+    },
+}
+
+/// The heads of the default branch of a project's delegates.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DelegateHeads {
+    /// The default branch, as per the project payload.
+    pub branch: ext::RefLike,
+    /// The head of the default branch of each delegate which has it.
+    pub heads: BTreeMap<PeerId, ext::Oid>,
+    /// The delegates which don't have the default branch.
+    pub missing: BTreeSet<PeerId>,
+}
+
+impl DelegateHeads {
+    /// The number of delegates, regardless of whether they have the default
+    /// branch.
+    pub fn delegates(&self) -> usize {
+        self.heads.len() + self.missing.len()
+    }
+
+    /// `true` if the delegates which have the default branch don't all point
+    /// to the same commit.
+    pub fn disagree(&self) -> bool {
+        self.heads.values().collect::<BTreeSet<_>>().len() > 1
+    }
+
+    /// The [`Warning`]s to report for missing and disagreeing heads.
+    pub fn warnings(&self) -> Vec<Warning> {
+        let mut warnings = self
+            .missing
+            .iter()
+            .map(|peer| Warning::DefaultBranchMissing {
+                peer: *peer,
+                branch: self.branch.clone(),
+            })
+            .collect::<Vec<_>>();
+        if self.disagree() {
+            warnings.push(Warning::DefaultBranchDisagreement {
+                branch: self.branch.clone(),
+                heads: self.heads.clone(),
+            })
+        }
+        warnings
+    }
+}
+
+/// Determine the [`DelegateHeads`] of the project `urn`.
+///
+/// Returns `None` if the project is not found, or has no default branch.
+#[tracing::instrument(skip(storage), fields(urn = %urn))]
+pub fn delegate_heads(storage: &Storage, urn: &Urn) -> Result<Option<DelegateHeads>, Error> {
+    let proj = match identities::project::verify(storage, urn)? {
+        None => return Ok(None),
+        Some(proj) => proj,
+    };
+    let branch = match &proj.subject().default_branch {
+        None => return Ok(None),
+        Some(branch) => ext::RefLike::try_from(branch.as_str())
+            .map_err(|_| Error::InvalidBranch(branch.to_string()))?,
+    };
+
+    let repo = storage.as_raw();
+    let local_peer = storage.peer_id();
+    let namespace = Namespace::from(urn);
+
+    let mut heads = BTreeMap::new();
+    let mut missing = BTreeSet::new();
+    for peer in super::project::all_delegates(&proj) {
+        let remote = if &peer == local_peer {
+            None
+        } else {
+            Some(peer)
+        };
+        match oid_of(
+            repo,
+            &Reference::head(namespace.clone(), remote, branch.clone()),
+        )? {
+            Some(head) => {
+                heads.insert(peer, head.into());
+            },
+            None => {
+                missing.insert(peer);
+            },
+        }
+    }
+
+    Ok(Some(DelegateHeads {
+        branch,
+        heads,
+        missing,
+    }))
+}
+
+/// Update the default branch of the project `urn` to the head its delegates
+/// converged on, according to `rule`.
+#[tracing::instrument(skip(storage), fields(urn = %urn))]
+pub fn converge(storage: &Storage, urn: &Urn, rule: Convergence) -> Result<Converged, Error> {
+    let DelegateHeads {
+        branch,
+        heads,
+        missing,
+    } = match delegate_heads(storage, urn)? {
+        None => return Ok(Converged::None),
+        Some(heads) => heads,
+    };
+    let delegates = heads.len() + missing.len();
+
+    let repo = storage.as_raw();
+    let namespace = Namespace::from(urn);
+    let local = Reference::head(namespace.clone(), None, branch.clone());
+    let local_head = oid_of(repo, &local)?;
+
+    let converged = match rule {
+        Convergence::All => {
+            let mut distinct = heads.values().collect::<BTreeSet<_>>().into_iter();
+            match (distinct.next(), distinct.next()) {
+                (Some(head), None) if missing.is_empty() => Some(**head),
+                _ => None,
+            }
+        },
+        Convergence::Quorum => {
+            let mut votes: BTreeMap<git2::Oid, usize> = BTreeMap::new();
+            for head in heads.values() {
+                *votes.entry(**head).or_default() += 1;
+            }
+            votes
+                .into_iter()
+                .find(|(_, n)| *n > delegates / 2)
+                .map(|(head, _)| head)
+        },
+        Convergence::Newest => {
+            let mut newest = None;
+            for head in heads.values() {
+                let head = **head;
+                let time = repo.find_commit(head)?.time().seconds();
+                if newest.map(|(t, _)| time > t).unwrap_or(true) {
+                    newest = Some((time, *head))
+                }
+            }
+            newest.map(|(_, head)| head)
+        },
+    };
+
+    let converged = match converged {
+        None => {
+            tracing::debug!(?heads, "delegates did not converge");
+            return Ok(Converged::None);
+        },
+        Some(head) => head,
+    };
+
+    let outcome = match local_head {
+        Some(head) if head == converged => Converged::Unchanged(head.into()),
+        Some(head) if !repo.graph_descendant_of(converged, head)? => {
+            tracing::warn!(
+                local = %head,
+                converged = %converged,
+                "not updating diverged default branch"
+            );
+            Converged::Diverged {
+                local: head.into(),
+                converged: converged.into(),
+            }
+        },
+        _ => {
+            local.create(
+                repo,
+                converged,
+                Force::True,
+                &format!("converged delegate heads ({:?})", rule),
+            )?;
+            Converged::Updated(converged.into())
+        },
+    };
+    set_head(repo, urn, &branch)?;
+
+    Ok(outcome)
+}
+
+/// Point the `HEAD` of the namespace of `urn` to the local `branch`.
+fn set_head(repo: &git2::Repository, urn: &Urn, branch: &ext::RefLike) -> Result<(), Error> {
+    let head = format!("refs/namespaces/{}/HEAD", urn.encode_id());
+    let target = format!("refs/namespaces/{}/refs/heads/{}", urn.encode_id(), branch);
+
+    let current = match repo.find_reference(&head) {
+        Ok(current) => current.symbolic_target().map(ToOwned::to_owned),
+        Err(e) if ext::is_not_found_err(&e) => None,
+        Err(e) => return Err(e.into()),
+    };
+    if current.as_deref() != Some(target.as_str()) {
+        repo.reference_symbolic(
+            &head,
+            &target,
+            true,
+            &format!("default branch is {}", branch),
+        )?;
+    }
+
+    Ok(())
+}
+
+fn oid_of<N, R>(
+    repo: &git2::Repository,
+    reference: &Reference<N, R, One>,
+) -> Result<Option<git2::Oid>, Error>
+where
+    Reference<N, R, One>: ToString,
+{
+    match reference.oid(repo) {
+        Ok(oid) => Ok(Some(oid)),
+        Err(e) if ext::is_not_found_err(&e) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
@@ -0,0 +1,130 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Policy hooks around the ref updates of [`super::replicate`].
+//!
+//! A replication fetches the refs of a namespace in several steps, each of
+//! which moves the remote-tracking refs like `git fetch` does. Before each
+//! step, the updates it is about to make are determined from the refs the
+//! remote peer advertised, and passed to [`Hooks::pre_apply`] of the
+//! [`super::Config`] along with the updates of the previous steps. As their
+//! new targets are yet to be fetched, those updates may be
+//! [`fetch::UpdateKind::Unresolved`].
+//!
+//! If [`Hooks::pre_apply`] returns a [`Veto`], the step is not fetched, the
+//! refs moved by previous steps are moved back to their previous targets,
+//! and the replication fails with [`super::Error::Vetoed`]. Otherwise, the
+//! updates are passed to [`Hooks::post_apply`] once the replication
+//! completed.
+//!
+//! Should the remote peer move its refs between advertising and serving
+//! them, the updates actually made by a step are passed to
+//! [`Hooks::pre_apply`] once more after they were written, and rolled back
+//! if vetoed. Changes to tracking relationships made while setting up the
+//! namespace are not subject to the hooks.
+
+use std::{collections::BTreeMap, fmt, sync::Arc};
+
+use git_ext as ext;
+use serde::Serialize;
+use thiserror::Error;
+
+use super::{fetch, Urn};
+use crate::{git::storage::Storage, PeerId};
+
+/// The ref updates of a replication of `urn` from `remote_peer`.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Pending {
+    pub urn: Urn,
+    pub remote_peer: PeerId,
+    pub updates: BTreeMap<ext::RefLike, fetch::TipUpdate>,
+}
+
+/// The refusal of [`Hooks::pre_apply`] to apply the [`Pending`] updates.
+#[derive(Clone, Debug, Error)]
+#[error("{reason}")]
+pub struct Veto {
+    pub reason: String,
+}
+
+/// Decides whether the updates of a replication are applied, and is told
+/// about the ones which were.
+///
+/// All methods default to accepting, and doing nothing, respectively.
+/// Implementations are called from within the replication, and so hold it up
+/// until they return.
+pub trait Hooks: fmt::Debug + Send + Sync {
+    /// Called before each step of the replication writes any refs, with the
+    /// updates it is about to make on top of the ones made so far.
+    fn pre_apply(&self, _pending: &Pending) -> Result<(), Veto> {
+        Ok(())
+    }
+
+    /// Called after the replication completed, with the updates it made.
+    fn post_apply(&self, _applied: &Pending) {}
+}
+
+/// [`Hooks`] which accept everything.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Noop;
+
+impl Hooks for Noop {}
+
+/// The [`Hooks`] of a [`super::Config`].
+///
+/// Defaults to [`Noop`].
+#[derive(Clone)]
+pub struct Sink(Arc<dyn Hooks>);
+
+impl Sink {
+    pub fn new(hooks: Arc<dyn Hooks>) -> Self {
+        Self(hooks)
+    }
+}
+
+impl Default for Sink {
+    fn default() -> Self {
+        Self(Arc::new(Noop))
+    }
+}
+
+impl fmt::Debug for Sink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::ops::Deref for Sink {
+    type Target = dyn Hooks;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+/// Move the refs of `updates` back to their previous targets, deleting the
+/// ones which were created.
+pub(super) fn rollback(
+    storage: &Storage,
+    updates: &BTreeMap<ext::RefLike, fetch::TipUpdate>,
+) -> Result<(), git2::Error> {
+    let repo = storage.as_raw();
+    for (name, update) in updates {
+        match update.old {
+            Some(old) => {
+                repo.reference(name.as_str(), old.into(), true, "replication: vetoed")?;
+            },
+            None => match repo.find_reference(name.as_str()) {
+                Ok(mut r) => r.delete()?,
+                Err(e) if e.code() == git2::ErrorCode::NotFound => {},
+                Err(e) => return Err(e),
+            },
+        }
+        tracing::debug!(%name, old = ?update.old, new = %update.new, "rolled back");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,300 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Hygiene rules for the ref names of remote peers.
+//!
+//! The refs replicated from a tracked peer are determined by its signed refs,
+//! which means that the peer is in control of the ref names written to local
+//! storage. Being valid according to `git-check-ref-format` does not make a
+//! name harmless, though: it may be excessively long, contain characters
+//! which render misleadingly in a terminal, or look like a namespaced ref
+//! once the namespace prefix is stripped.
+//!
+//! Before fetching, the signed refs of every tracked peer are checked against
+//! a [`Policy`], and the offending refs are handled according to its
+//! [`Action`]. The number of names checked, and the violations found, are
+//! counted process-wide and can be inspected using [`metrics`].
+//!
+//! On case-insensitive filesystems, refs whose names differ only by case
+//! would clobber each other in the loose ref store. If
+//! [`Policy::case_insensitive`] is set, such names are treated as violations,
+//! too.
+//!
+//! Note that signed refs are JSON-encoded, so ref names are always valid
+//! UTF-8 at this point -- names which are not are rejected when the signed
+//! refs are loaded.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{self, Display},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use thiserror::Error;
+
+use crate::{
+    git::{refs::Refs, types::RefsCategory},
+    PeerId,
+};
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("ref `{category}/{name}` of {peer} rejected: {violation}")]
+    Rejected {
+        peer: PeerId,
+        category: RefsCategory,
+        name: String,
+        violation: Violation,
+    },
+}
+
+/// What to do with a ref whose name violates the [`Policy`].
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Action {
+    /// Replicate the ref anyway. The violation is only logged and counted.
+    Allow,
+    /// Omit the ref from the replication, but replicate all other refs of
+    /// the peer.
+    Drop,
+    /// Abort the replication.
+    Reject,
+}
+
+/// The rules ref names are checked against.
+///
+/// The names checked are relative to the category of the ref, ie. for
+/// `refs/heads/feature/x` the name is `feature/x`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Policy {
+    pub action: Action,
+    /// The maximum length of a single component of the name, in bytes.
+    pub max_component_len: usize,
+    /// The maximum length of the name, in bytes.
+    pub max_len: usize,
+    /// Whether to treat names which differ only by case as colliding.
+    ///
+    /// On case-insensitive filesystems, such refs would be written to the
+    /// same loose ref file. Enabled by default on macOS and Windows.
+    pub case_insensitive: bool,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            action: Action::Drop,
+            max_component_len: 255,
+            max_len: 1024,
+            case_insensitive: cfg!(any(target_os = "macos", target_os = "windows")),
+        }
+    }
+}
+
+/// The ways a ref name can violate the [`Policy`].
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Violation {
+    /// The name exceeds [`Policy::max_len`].
+    TooLong,
+    /// A component of the name exceeds [`Policy::max_component_len`].
+    ComponentTooLong,
+    /// The name contains a `.` or `..` component, or a
+    /// `namespaces/<namespace>/refs` sequence which may be mistaken for a
+    /// different namespace after the namespace prefix is stripped.
+    Traversal,
+    /// The name contains control characters, or invisible or bidirectional
+    /// formatting characters.
+    Unprintable,
+    /// The name differs only by case from another name of the same peer, or
+    /// a directory thereof, and [`Policy::case_insensitive`] is set. The
+    /// first name in lexicographic order is retained.
+    CaseCollision,
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::TooLong => f.write_str("name too long"),
+            Self::ComponentTooLong => f.write_str("name component too long"),
+            Self::Traversal => f.write_str("path traversal"),
+            Self::Unprintable => f.write_str("unprintable characters"),
+            Self::CaseCollision => f.write_str("case-insensitive collision"),
+        }
+    }
+}
+
+impl Policy {
+    /// Check `name` against this policy, returning the first violation
+    /// found.
+    pub fn check(&self, name: &str) -> Option<Violation> {
+        if name.len() > self.max_len {
+            return Some(Violation::TooLong);
+        }
+        if name.chars().any(is_unprintable) {
+            return Some(Violation::Unprintable);
+        }
+
+        let components = name.split('/').collect::<Vec<_>>();
+        if components.iter().any(|c| c.len() > self.max_component_len) {
+            return Some(Violation::ComponentTooLong);
+        }
+        let traversal = components.iter().any(|c| *c == "." || *c == "..")
+            || components
+                .windows(3)
+                .any(|w| w[0] == "namespaces" && w[2] == "refs");
+        if traversal {
+            return Some(Violation::Traversal);
+        }
+
+        None
+    }
+
+    /// Check the names of the signed `refs` of `peer`, removing the refs
+    /// which violate this policy if the [`Action`] is [`Action::Drop`].
+    ///
+    /// The names of the dropped refs are returned.
+    pub fn apply(&self, peer: PeerId, refs: &mut Refs) -> Result<Vec<String>, Error> {
+        let mut violations = Vec::new();
+        let mut folded = Folded::default();
+        for ((name, _), category) in refs.iter_categorised() {
+            CHECKED.fetch_add(1, Ordering::Relaxed);
+            let violation = self.check(name.as_str()).or_else(|| {
+                (self.case_insensitive && !folded.insert(category, name.as_str()))
+                    .then(|| Violation::CaseCollision)
+            });
+            if let Some(violation) = violation {
+                violations.push((name.clone(), category, violation))
+            }
+        }
+
+        let mut dropped = Vec::new();
+        for (name, category, violation) in violations {
+            count(self.action, violation);
+            tracing::warn!(
+                peer = %peer,
+                name = %format!("{}/{}", category, name.as_str().escape_debug()),
+                violation = %violation,
+                action = ?self.action,
+                "ref name violates hygiene policy"
+            );
+            match self.action {
+                Action::Allow => {},
+                Action::Drop => {
+                    match category {
+                        RefsCategory::Heads => refs.heads.remove(&name),
+                        RefsCategory::Rad => refs.rad.remove(&name),
+                        RefsCategory::Tags => refs.tags.remove(&name),
+                        RefsCategory::Notes => refs.notes.remove(&name),
+                        RefsCategory::Cobs => refs.cobs.remove(&name),
+                    };
+                    dropped.push(format!("{}/{}", category, name.as_str()))
+                },
+                Action::Reject => {
+                    return Err(Error::Rejected {
+                        peer,
+                        category,
+                        name: name.as_str().to_owned(),
+                        violation,
+                    })
+                },
+            }
+        }
+
+        Ok(dropped)
+    }
+}
+
+/// The ref names of a peer as seen by a case-insensitive filesystem.
+#[derive(Default)]
+struct Folded {
+    /// The case-folded names of the refs.
+    files: BTreeSet<String>,
+    /// The case-folded names of the directories containing the refs.
+    dirs: BTreeSet<String>,
+}
+
+impl Folded {
+    /// Record `name`, returning `false` if it would occupy the same path as a
+    /// previously recorded name, or a directory thereof.
+    fn insert(&mut self, category: RefsCategory, name: &str) -> bool {
+        let path = format!("{}/{}", category, name).to_lowercase();
+        let dirs = path
+            .match_indices('/')
+            .map(|(i, _)| path[..i].to_owned())
+            .collect::<Vec<_>>();
+        if self.files.contains(&path)
+            || self.dirs.contains(&path)
+            || dirs.iter().any(|dir| self.files.contains(dir))
+        {
+            return false;
+        }
+        self.files.insert(path);
+        self.dirs.extend(dirs);
+        true
+    }
+}
+
+fn is_unprintable(c: char) -> bool {
+    c.is_control()
+        || matches!(
+            c,
+            // zero width space, joiners, and marks
+            '\u{200b}'..='\u{200f}'
+            // bidirectional embeddings and overrides
+            | '\u{202a}'..='\u{202e}'
+            // bidirectional isolates
+            | '\u{2066}'..='\u{2069}'
+            // byte order mark
+            | '\u{feff}'
+        )
+}
+
+/// Counters of the hygiene checks performed by this process.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Metrics {
+    /// The number of ref names checked.
+    pub checked: u64,
+    /// The number of violations found, by the [`Action`] of the policy
+    /// applied and the kind of [`Violation`]. Zero counts are omitted.
+    pub violations: BTreeMap<(Action, Violation), u64>,
+}
+
+const ACTIONS: [Action; 3] = [Action::Allow, Action::Drop, Action::Reject];
+const VIOLATIONS: [Violation; 5] = [
+    Violation::TooLong,
+    Violation::ComponentTooLong,
+    Violation::Traversal,
+    Violation::Unprintable,
+    Violation::CaseCollision,
+];
+
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicU64 = AtomicU64::new(0);
+static CHECKED: AtomicU64 = ZERO;
+static VIOLATED: [AtomicU64; ACTIONS.len() * VIOLATIONS.len()] =
+    [ZERO; ACTIONS.len() * VIOLATIONS.len()];
+
+fn count(action: Action, violation: Violation) {
+    VIOLATED[action as usize * VIOLATIONS.len() + violation as usize]
+        .fetch_add(1, Ordering::Relaxed);
+}
+
+/// Obtain a snapshot of the [`Metrics`] of this process.
+pub fn metrics() -> Metrics {
+    let mut violations = BTreeMap::new();
+    for action in ACTIONS.iter() {
+        for violation in VIOLATIONS.iter() {
+            let n = VIOLATED[*action as usize * VIOLATIONS.len() + *violation as usize]
+                .load(Ordering::Relaxed);
+            if n > 0 {
+                violations.insert((*action, *violation), n);
+            }
+        }
+    }
+
+    Metrics {
+        checked: CHECKED.load(Ordering::Relaxed),
+        violations,
+    }
+}
@@ -0,0 +1,223 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Instrumentation of [`super::replicate`].
+//!
+//! The stages of every replication are reported to the [`Metrics`] of the
+//! [`super::Config`], which does nothing by default. Nodes exporting metrics
+//! install a [`Counters`] (or their own implementation) instead, and read
+//! its [`Stats`] periodically.
+
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use crate::git::fetch::Retry;
+
+/// Receives measurements of the stages of a replication.
+///
+/// All methods default to doing nothing. Implementations are called from
+/// within the replication, and so should return quickly.
+pub trait Metrics: fmt::Debug + Send + Sync {
+    /// The remote end took `took` to advertise its refs.
+    fn ls_refs(&self, _took: Duration) {}
+
+    /// A fetch asked for `refs` refs.
+    fn wants(&self, _refs: usize) {}
+
+    /// A fetch received a pack of `bytes` bytes.
+    fn pack(&self, _bytes: usize) {}
+
+    /// The fetcher had to talk to the remote end once more, see [`Retry`].
+    fn retry(&self, _retry: Retry) {}
+
+    /// Verifying the identities of the delegates of a project took `took`.
+    fn verification(&self, _took: Duration) {}
+
+    /// Updating the refs and tracking relationships of the namespace after
+    /// verification took `took`.
+    fn transaction(&self, _took: Duration) {}
+}
+
+/// [`Metrics`] which discards all measurements.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Noop;
+
+impl Metrics for Noop {}
+
+/// The [`Metrics`] of a [`super::Config`].
+///
+/// Defaults to [`Noop`].
+#[derive(Clone, Copy)]
+pub struct Sink(&'static dyn Metrics);
+
+impl Sink {
+    pub fn new(metrics: &'static dyn Metrics) -> Self {
+        Self(metrics)
+    }
+}
+
+impl Default for Sink {
+    fn default() -> Self {
+        Self(&Noop)
+    }
+}
+
+impl fmt::Debug for Sink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::ops::Deref for Sink {
+    type Target = dyn Metrics;
+
+    fn deref(&self) -> &Self::Target {
+        self.0
+    }
+}
+
+/// The number of measurements of a duration, and their sum.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Timer {
+    pub count: u64,
+    pub total: Duration,
+}
+
+impl Timer {
+    /// The mean duration, or `None` if nothing was measured.
+    pub fn mean(&self) -> Option<Duration> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(self.total / self.count as u32)
+        }
+    }
+}
+
+/// A snapshot of [`Counters`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Stats {
+    pub ls_refs: Timer,
+    /// The number of fetches, and the number of refs they asked for.
+    pub fetches: u64,
+    pub wants: u64,
+    /// The number of packs received, and their size in bytes.
+    pub packs: u64,
+    pub pack_bytes: u64,
+    /// The number of [`Retry::Renegotiation`]s and [`Retry::StreamReset`]s.
+    pub renegotiations: u64,
+    pub stream_resets: u64,
+    pub verification: Timer,
+    pub transaction: Timer,
+}
+
+#[derive(Debug, Default)]
+struct AtomicTimer {
+    count: AtomicU64,
+    micros: AtomicU64,
+}
+
+impl AtomicTimer {
+    const fn new() -> Self {
+        Self {
+            count: AtomicU64::new(0),
+            micros: AtomicU64::new(0),
+        }
+    }
+
+    fn record(&self, took: Duration) {
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.micros
+            .fetch_add(took.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn get(&self) -> Timer {
+        Timer {
+            count: self.count.load(Ordering::Relaxed),
+            total: Duration::from_micros(self.micros.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// [`Metrics`] accumulating all measurements since their creation.
+///
+/// Can be created in a `static`, such that it can be passed to
+/// [`Sink::new`].
+#[derive(Debug, Default)]
+pub struct Counters {
+    ls_refs: AtomicTimer,
+    fetches: AtomicU64,
+    wants: AtomicU64,
+    packs: AtomicU64,
+    pack_bytes: AtomicU64,
+    renegotiations: AtomicU64,
+    stream_resets: AtomicU64,
+    verification: AtomicTimer,
+    transaction: AtomicTimer,
+}
+
+impl Counters {
+    pub const fn new() -> Self {
+        Self {
+            ls_refs: AtomicTimer::new(),
+            fetches: AtomicU64::new(0),
+            wants: AtomicU64::new(0),
+            packs: AtomicU64::new(0),
+            pack_bytes: AtomicU64::new(0),
+            renegotiations: AtomicU64::new(0),
+            stream_resets: AtomicU64::new(0),
+            verification: AtomicTimer::new(),
+            transaction: AtomicTimer::new(),
+        }
+    }
+
+    pub fn stats(&self) -> Stats {
+        Stats {
+            ls_refs: self.ls_refs.get(),
+            fetches: self.fetches.load(Ordering::Relaxed),
+            wants: self.wants.load(Ordering::Relaxed),
+            packs: self.packs.load(Ordering::Relaxed),
+            pack_bytes: self.pack_bytes.load(Ordering::Relaxed),
+            renegotiations: self.renegotiations.load(Ordering::Relaxed),
+            stream_resets: self.stream_resets.load(Ordering::Relaxed),
+            verification: self.verification.get(),
+            transaction: self.transaction.get(),
+        }
+    }
+}
+
+impl Metrics for Counters {
+    fn ls_refs(&self, took: Duration) {
+        self.ls_refs.record(took)
+    }
+
+    fn wants(&self, refs: usize) {
+        self.fetches.fetch_add(1, Ordering::Relaxed);
+        self.wants.fetch_add(refs as u64, Ordering::Relaxed);
+    }
+
+    fn pack(&self, bytes: usize) {
+        self.packs.fetch_add(1, Ordering::Relaxed);
+        self.pack_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    fn retry(&self, retry: Retry) {
+        match retry {
+            Retry::Renegotiation => self.renegotiations.fetch_add(1, Ordering::Relaxed),
+            Retry::StreamReset => self.stream_resets.fetch_add(1, Ordering::Relaxed),
+        };
+    }
+
+    fn verification(&self, took: Duration) {
+        self.verification.record(took)
+    }
+
+    fn transaction(&self, took: Duration) {
+        self.transaction.record(took)
+    }
+}
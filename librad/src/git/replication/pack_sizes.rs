@@ -0,0 +1,126 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Fetch limits adapted to the size of a namespace.
+//!
+//! A static [`crate::git::fetch::Limit`] is either too small for big
+//! projects, or too permissive for tiny ones. Instead, the size of the largest
+//! pack received for a namespace is recorded in a file per namespace in the
+//! `pack-sizes` directory alongside the monorepo, and subsequent fetches of
+//! the namespace are bounded by a multiple of it as per [`Adaptive`].
+//!
+//! The largest pack is considered rather than the last one, as fetches of
+//! updates are usually much smaller than the clone which preceded them.
+
+use std::{
+    fs,
+    io::{self, Write as _},
+    num::NonZeroUsize,
+    path::PathBuf,
+};
+
+use thiserror::Error;
+
+use super::Urn;
+use crate::git::{fetch::ONE_KB, storage::Storage};
+
+const PACK_SIZES_DIR: &str = "pack-sizes";
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("malformed pack size record for {urn}")]
+    Malformed {
+        urn: Urn,
+        source: std::num::ParseIntError,
+    },
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// How to derive the limit for fetching the data of a namespace from the
+/// largest pack received for it before.
+///
+/// The limit is `factor` times the size of that pack, but no less than
+/// `floor`. The configured [`crate::git::fetch::Limit::data`] serves as the
+/// ceiling, and as the limit for namespaces we haven't received a pack for.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Adaptive {
+    /// Default: 4
+    pub factor: NonZeroUsize,
+    /// Default: 100MiB
+    pub floor: usize,
+}
+
+impl Default for Adaptive {
+    fn default() -> Self {
+        Self {
+            factor: NonZeroUsize::new(4).unwrap(),
+            floor: 100 * ONE_KB * ONE_KB,
+        }
+    }
+}
+
+impl Adaptive {
+    /// The limit for fetching a namespace whose largest pack so far had
+    /// `largest` bytes, given the configured `ceiling`.
+    pub fn limit(&self, largest: Option<usize>, ceiling: usize) -> usize {
+        match largest {
+            None => ceiling,
+            Some(largest) => largest
+                .saturating_mul(self.factor.get())
+                .max(self.floor)
+                .min(ceiling),
+        }
+    }
+}
+
+fn record_path(storage: &Storage, urn: &Urn) -> PathBuf {
+    storage
+        .as_raw()
+        .path()
+        .join(PACK_SIZES_DIR)
+        .join(urn.encode_id())
+}
+
+/// The size in bytes of the largest pack received for `urn`, if any.
+pub fn largest(storage: &Storage, urn: &Urn) -> Result<Option<usize>, Error> {
+    match fs::read_to_string(record_path(storage, urn)) {
+        Ok(size) => size
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|source| Error::Malformed {
+                urn: urn.clone(),
+                source,
+            }),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Record that a pack of `size` bytes was received for `urn`, if it is larger
+/// than any received before.
+pub fn record(storage: &Storage, urn: &Urn, size: usize) -> Result<(), Error> {
+    let largest = match largest(storage, urn) {
+        Ok(largest) => largest,
+        Err(Error::Malformed { .. }) => None,
+        Err(e) => return Err(e),
+    };
+    if largest.map(|largest| size <= largest).unwrap_or(false) {
+        return Ok(());
+    }
+
+    let path = record_path(storage, urn);
+    let dir = path.parent().expect("record path has a parent");
+    fs::create_dir_all(dir)?;
+    // Write to a temporary file first, so readers never see a partial record
+    let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+    write!(tmp, "{}", size)?;
+    tmp.persist(&path).map_err(|e| e.error)?;
+
+    Ok(())
+}
@@ -0,0 +1,117 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Retrying replication after transient failures.
+//!
+//! Errors are sorted into [`Category`]s via [`Classify`]. Only
+//! [`Category::Transient`] errors, ie. those which occurred while talking to
+//! the remote peer, are retried by [`retrying`], after a delay which grows
+//! exponentially with the number of attempts made so far. The other
+//! categories are returned immediately: attempting the replication again
+//! would yield the same result.
+
+use std::{fmt::Display, future::Future, time::Duration};
+
+use super::Error;
+
+/// The kind of a replication failure, determining whether it is retried.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Category {
+    /// The connection to the remote peer failed, was reset, or timed out.
+    Transient,
+    /// The data obtained from the remote peer did not pass verification.
+    Verification,
+    /// Any other error, eg. of the local storage.
+    Permanent,
+}
+
+/// Errors which can be sorted into a [`Category`].
+pub trait Classify {
+    fn category(&self) -> Category;
+}
+
+impl Classify for Error {
+    fn category(&self) -> Category {
+        match self {
+            Self::Peek(_) | Self::Fetch(_) => Category::Transient,
+            Self::Verification(_) | Self::Validation(_) | Self::Hygiene(_) => {
+                Category::Verification
+            },
+            Self::Sanity(e) if e.is_rejection() => Category::Verification,
+            Self::Sanity(_) => Category::Permanent,
+            Self::SelfReplication | Self::Negotiation(_) | Self::Vetoed(_) | Self::Tx(_) => {
+                Category::Permanent
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Policy {
+    /// The maximum number of attempts, including the first one. A value of
+    /// `1` disables retries.
+    ///
+    /// Default: 3
+    pub max_attempts: u32,
+    /// The delay before the first retry.
+    ///
+    /// Default: 1s
+    pub initial_backoff: Duration,
+    /// The upper bound of the delay between attempts.
+    ///
+    /// Default: 30s
+    pub max_backoff: Duration,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+impl Policy {
+    /// A policy which never retries.
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// The delay before the next attempt, after `attempts` failed ones.
+    pub fn backoff(&self, attempts: u32) -> Duration {
+        self.initial_backoff
+            .checked_mul(2u32.saturating_pow(attempts.saturating_sub(1)))
+            .map_or(self.max_backoff, |delay| delay.min(self.max_backoff))
+    }
+}
+
+/// Run the replication created by `f`, and create and run it again as long as
+/// it fails with a [`Category::Transient`] error and `policy` permits.
+///
+/// The last error is returned if all attempts failed.
+pub async fn retrying<F, Fut, A, E>(policy: Policy, mut f: F) -> Result<A, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<A, E>>,
+    E: Classify + Display,
+{
+    let mut attempts = 1;
+    loop {
+        match f().await {
+            Err(e) if e.category() == Category::Transient && attempts < policy.max_attempts => {
+                let delay = policy.backoff(attempts);
+                tracing::warn!(err = %e, attempts, ?delay, "replication failed, retrying");
+                tokio::time::sleep(delay).await;
+                attempts += 1;
+            },
+            res => return res,
+        }
+    }
+}
@@ -0,0 +1,168 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Sanity checks of the data a remote peer offers, applied while peeking.
+//!
+//! Verifying an identity means walking its entire history, and the remote
+//! peer decides how many refs it advertises. Before the identity is verified
+//! and the bulk of the data is fetched, a namespace is therefore rejected if:
+//!
+//! * the remote peer advertises more refs than [`Limits::advertised_refs`],
+//!   which is checked before anything is fetched
+//! * the history of an identity ref received while peeking (`rad/id`,
+//!   `rad/self` or `rad/ids/*`) has more revisions than
+//!   [`Limits::id_revisions`]
+//!
+//! When a namespace is rejected after peeking, the refs updated by the peek
+//! are reset to their previous targets, or removed if they didn't exist
+//! before. This protects small nodes from spending resources on namespaces
+//! crafted to exhaust them.
+
+use std::{collections::BTreeMap, convert::TryFrom as _};
+
+use git_ext as ext;
+use thiserror::Error;
+
+use crate::git::{
+    fetch::RemoteHeads,
+    storage::{watch, Storage},
+    Urn,
+};
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("remote advertised {advertised} refs, exceeding the limit of {limit}")]
+    AdvertisedRefs { advertised: usize, limit: usize },
+
+    #[error("history of `{name}` exceeds the limit of {limit} revisions")]
+    IdRevisions { name: ext::RefLike, limit: usize },
+
+    #[error("failed to roll back peeked refs")]
+    Rollback(#[source] git2::Error),
+
+    #[error(transparent)]
+    Snapshot(#[from] watch::Error),
+
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+}
+
+impl Error {
+    /// Whether the namespace was rejected because it exceeded the
+    /// [`Limits`], as opposed to failing to inspect or roll back the storage.
+    pub fn is_rejection(&self) -> bool {
+        matches!(self, Self::AdvertisedRefs { .. } | Self::IdRevisions { .. })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Limits {
+    /// The maximum number of refs the remote peer may advertise for the
+    /// namespace.
+    ///
+    /// Default: 100 000
+    pub advertised_refs: usize,
+    /// The maximum number of revisions in the history of an identity ref.
+    ///
+    /// Default: 1024
+    pub id_revisions: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            advertised_refs: 100_000,
+            id_revisions: 1024,
+        }
+    }
+}
+
+impl Limits {
+    /// Check the refs advertised by the remote peer.
+    pub fn check_advertised(&self, remote_heads: &RemoteHeads) -> Result<(), Error> {
+        let advertised = remote_heads.len();
+        if advertised > self.advertised_refs {
+            return Err(Error::AdvertisedRefs {
+                advertised,
+                limit: self.advertised_refs,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Check the identity refs among the `updated` refs of a peek, given by
+    /// their fully qualified names.
+    pub(super) fn check_peeked(
+        &self,
+        storage: &Storage,
+        updated: &BTreeMap<ext::RefLike, ext::Oid>,
+    ) -> Result<(), Error> {
+        let repo = storage.as_raw();
+        for (name, tip) in updated.iter().filter(|(name, _)| is_identity(name)) {
+            let mut walk = repo.revwalk()?;
+            walk.push(**tip)?;
+            let revisions = walk.take(self.id_revisions + 1).count();
+            if revisions > self.id_revisions {
+                return Err(Error::IdRevisions {
+                    name: name.clone(),
+                    limit: self.id_revisions,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The state of the namespace `urn` before a peek, to [`rollback`] to.
+pub(super) fn snapshot(
+    storage: &Storage,
+    urn: &Urn,
+) -> Result<BTreeMap<ext::RefLike, ext::Oid>, Error> {
+    Ok(watch::snapshot(storage.read_only(), urn)?)
+}
+
+/// Reset the `updated` refs of the namespace `urn` to their state in
+/// `before`, as returned by [`snapshot`].
+pub(super) fn rollback(
+    storage: &Storage,
+    urn: &Urn,
+    before: &BTreeMap<ext::RefLike, ext::Oid>,
+    updated: &BTreeMap<ext::RefLike, ext::Oid>,
+) -> Result<(), Error> {
+    let repo = storage.as_raw();
+    let prefix = format!("refs/namespaces/{}/", urn.encode_id());
+    for name in updated.keys() {
+        let previous = name
+            .as_str()
+            .strip_prefix(&prefix)
+            .and_then(|name| before.get(&ext::RefLike::try_from(name).ok()?));
+        let res = match previous {
+            Some(oid) => repo
+                .reference(
+                    name.as_str(),
+                    **oid,
+                    true,
+                    "rollback: rejected by sanity checks",
+                )
+                .map(|_| ()),
+            None => match repo.find_reference(name.as_str()) {
+                Ok(mut r) => r.delete(),
+                Err(e) if ext::is_not_found_err(&e) => Ok(()),
+                Err(e) => Err(e),
+            },
+        };
+        res.map_err(Error::Rollback)?;
+    }
+
+    Ok(())
+}
+
+fn is_identity(name: &ext::RefLike) -> bool {
+    let name = name.as_str();
+    name.ends_with("/rad/id") || name.ends_with("/rad/self") || name.contains("/rad/ids/")
+}
@@ -0,0 +1,240 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Fair scheduling of concurrent replications.
+//!
+//! Without bounds, a node starts every replication it is asked for as soon
+//! as a [`crate::git::storage::Storage`] of its pool is available. On a seed,
+//! a single project receiving lots of updates, or a single peer pushing lots
+//! of projects, can thus occupy the pool and starve all other replications.
+//!
+//! [`Slots`] bounds the number of replications in progress overall, per
+//! remote peer and per [`Urn`], as per its [`Limits`]. Replications which
+//! would exceed a bound wait in a queue per [`Urn`], and the queues take
+//! turns: a project with many waiting replications is served no more often
+//! than one with a single waiting replication.
+//!
+//! Note that fetches of the same [`Urn`] are serialised by the storage anyway
+//! (see [`crate::git::storage::Fetchers`]). A per-[`Urn`] limit above one
+//! only lets more replications of the [`Urn`] wait for the storage instead of
+//! in the queue.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    num::NonZeroUsize,
+    sync::Arc,
+};
+
+use futures::channel::oneshot;
+use parking_lot::Mutex;
+
+use super::Urn;
+use crate::PeerId;
+
+/// Bounds on the number of replications in progress. `None` means unbounded.
+///
+/// The [`Default`] doesn't bound anything.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Limits {
+    /// Replications in progress overall.
+    pub total: Option<NonZeroUsize>,
+    /// Replications in progress from the same remote peer.
+    pub per_remote: Option<NonZeroUsize>,
+    /// Replications in progress of the same [`Urn`].
+    pub per_urn: Option<NonZeroUsize>,
+}
+
+fn admits(limit: Option<NonZeroUsize>, running: usize) -> bool {
+    limit.map(|limit| running < limit.get()).unwrap_or(true)
+}
+
+/// The number of replications in progress, and waiting for a [`Permit`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Stats {
+    pub running: usize,
+    pub waiting: usize,
+}
+
+/// Hands out [`Permit`]s to replicate acc. to its [`Limits`].
+///
+/// Clones share the same slots.
+#[derive(Clone)]
+pub struct Slots {
+    limits: Limits,
+    state: Arc<Mutex<State>>,
+}
+
+/// The permission to replicate, which frees the slot when dropped.
+#[must_use = "the slot is freed when the permit is dropped"]
+pub struct Permit {
+    slots: Slots,
+    urn: Urn,
+    remote_peer: PeerId,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.slots.state.lock().finish(&self.urn, self.remote_peer);
+        self.slots.schedule();
+    }
+}
+
+struct Waiter {
+    remote_peer: PeerId,
+    tx: oneshot::Sender<Permit>,
+}
+
+#[derive(Default)]
+struct State {
+    running: usize,
+    per_remote: HashMap<PeerId, usize>,
+    per_urn: HashMap<Urn, usize>,
+    queues: HashMap<Urn, VecDeque<Waiter>>,
+    /// The [`Urn`]s with waiting replications, in the order they take turns.
+    turns: VecDeque<Urn>,
+}
+
+impl State {
+    fn start(&mut self, urn: &Urn, remote_peer: PeerId) {
+        self.running += 1;
+        *self.per_remote.entry(remote_peer).or_default() += 1;
+        *self.per_urn.entry(urn.clone()).or_default() += 1;
+    }
+
+    fn finish(&mut self, urn: &Urn, remote_peer: PeerId) {
+        self.running -= 1;
+        if let Some(n) = self.per_remote.get_mut(&remote_peer) {
+            *n -= 1;
+            if *n == 0 {
+                self.per_remote.remove(&remote_peer);
+            }
+        }
+        if let Some(n) = self.per_urn.get_mut(urn) {
+            *n -= 1;
+            if *n == 0 {
+                self.per_urn.remove(urn);
+            }
+        }
+    }
+
+    /// Take the next waiter which may start, if any, moving its [`Urn`] to
+    /// the end of the turns.
+    fn next(&mut self, limits: &Limits) -> Option<(Urn, Waiter)> {
+        if !admits(limits.total, self.running) {
+            return None;
+        }
+
+        let mut turn = 0;
+        while turn < self.turns.len() {
+            let urn = self.turns[turn].clone();
+            let per_remote = &self.per_remote;
+            let queue = self
+                .queues
+                .get_mut(&urn)
+                .expect("every urn taking turns has a queue");
+            // Forget about waiters which gave up
+            queue.retain(|waiter| !waiter.tx.is_canceled());
+            let waiter = if admits(
+                limits.per_urn,
+                self.per_urn.get(&urn).copied().unwrap_or_default(),
+            ) {
+                queue
+                    .iter()
+                    .position(|waiter| {
+                        admits(
+                            limits.per_remote,
+                            per_remote
+                                .get(&waiter.remote_peer)
+                                .copied()
+                                .unwrap_or_default(),
+                        )
+                    })
+                    .and_then(|pos| queue.remove(pos))
+            } else {
+                None
+            };
+
+            if queue.is_empty() {
+                self.queues.remove(&urn);
+                self.turns.remove(turn);
+            } else if waiter.is_some() {
+                self.turns.remove(turn);
+                self.turns.push_back(urn.clone());
+            } else {
+                turn += 1;
+            }
+
+            if let Some(waiter) = waiter {
+                return Some((urn, waiter));
+            }
+        }
+
+        None
+    }
+}
+
+impl Slots {
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            limits,
+            state: Arc::new(Mutex::new(State::default())),
+        }
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    pub fn stats(&self) -> Stats {
+        let state = self.state.lock();
+        Stats {
+            running: state.running,
+            waiting: state.queues.values().map(VecDeque::len).sum(),
+        }
+    }
+
+    /// Wait for a slot to replicate `urn` from `remote_peer`.
+    pub async fn acquire(&self, urn: &Urn, remote_peer: PeerId) -> Permit {
+        let urn = urn.clone().with_path(None);
+        let (tx, rx) = oneshot::channel();
+        {
+            let mut state = self.state.lock();
+            if !state.queues.contains_key(&urn) {
+                state.turns.push_back(urn.clone());
+            }
+            state
+                .queues
+                .entry(urn)
+                .or_default()
+                .push_back(Waiter { remote_peer, tx });
+        }
+        self.schedule();
+
+        rx.await
+            .expect("waiters are only dropped after they stopped waiting")
+    }
+
+    /// Hand out permits to the waiters which may start.
+    fn schedule(&self) {
+        let mut granted = Vec::new();
+        {
+            let mut state = self.state.lock();
+            while let Some((urn, waiter)) = state.next(&self.limits) {
+                state.start(&urn, waiter.remote_peer);
+                let permit = Permit {
+                    slots: self.clone(),
+                    urn,
+                    remote_peer: waiter.remote_peer,
+                };
+                granted.push((waiter.tx, permit));
+            }
+        }
+        // Send outside of the lock: if the waiter stopped waiting in the
+        // meantime, the permit is dropped right away, which takes the lock.
+        for (tx, permit) in granted {
+            tx.send(permit).ok();
+        }
+    }
+}
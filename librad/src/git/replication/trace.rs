@@ -0,0 +1,352 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Recording of replication runs for offline debugging.
+//!
+//! If [`super::Config::trace`] is set, [`super::replicate`] wraps the
+//! [`Fetcher`] in a [`Recording`], which captures the refs advertised by the
+//! remote peer, every round of fetching (the [`Fetchspecs`], the refspecs
+//! they expanded to, and the metadata of the pack received), and the refs
+//! updated as a result. Once the replication has finished, the [`Trace`] is
+//! written to a file in the `traces` directory alongside the monorepo.
+//!
+//! Traces don't contain any objects. To reconstruct a run, they are replayed
+//! against a storage which has the objects, with a fetcher serving the
+//! recorded rounds in place of the remote peer, see `replay` (requires the
+//! `testing` feature).
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::{self, File},
+    io::{self, BufReader, BufWriter},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use git_ext as ext;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{Error as ReplicationError, Mode, ReplicateResult, Urn};
+use crate::{
+    git::{
+        fetch::{FetchResult, Fetcher, Fetchspecs, PackStats, RemoteHeads, Retry, Skipped},
+        storage::Storage,
+    },
+    identities::git::Revision,
+    net::protocol::Features,
+    PeerId,
+};
+
+#[cfg(feature = "testing")]
+pub mod replay;
+
+const TRACES_DIR: &str = "traces";
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+/// A recorded replication run.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Trace {
+    pub urn: Urn,
+    pub remote_peer: PeerId,
+    /// Start of the run, in milliseconds since the UNIX epoch.
+    pub timestamp: u64,
+    /// The bits of the [`Features`] negotiated with the remote peer, if known.
+    pub features: Option<u64>,
+    /// The refs advertised by the remote peer, including the peeled
+    /// `<name>^{}` entries.
+    pub advertised: BTreeMap<String, ext::Oid>,
+    pub rounds: Vec<Round>,
+    /// The outcome of the run, or `None` if it didn't finish.
+    pub outcome: Option<Outcome>,
+}
+
+impl Trace {
+    /// The [`RemoteHeads`] the remote peer advertised.
+    pub fn remote_heads(&self) -> RemoteHeads {
+        RemoteHeads::from_advertised(
+            self.advertised
+                .iter()
+                .map(|(name, oid)| (name.as_str(), *oid)),
+        )
+    }
+
+    pub fn features(&self) -> Option<Features> {
+        self.features.map(Features::from_bits)
+    }
+}
+
+/// A single call to [`Fetcher::fetch`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Round {
+    pub specs: Specs,
+    /// The limit of the amount of data fetched, in bytes.
+    pub limit: usize,
+    /// The refspecs the [`Fetchspecs`] expanded to.
+    pub refspecs: Vec<String>,
+    pub result: RoundResult,
+}
+
+/// A summary of the [`Fetchspecs`] of a [`Round`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum Specs {
+    PeekAll,
+    Peek {
+        remotes: BTreeSet<PeerId>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Replicate {
+        tracked: BTreeSet<PeerId>,
+        delegates: BTreeSet<Urn>,
+    },
+}
+
+impl From<&Fetchspecs<PeerId, Revision>> for Specs {
+    fn from(specs: &Fetchspecs<PeerId, Revision>) -> Self {
+        match specs {
+            Fetchspecs::PeekAll { .. } => Self::PeekAll,
+            Fetchspecs::Peek { remotes, .. } => Self::Peek {
+                remotes: remotes.clone(),
+            },
+            Fetchspecs::Replicate {
+                tracked_sigrefs,
+                delegates,
+                ..
+            } => Self::Replicate {
+                tracked: tracked_sigrefs.keys().copied().collect(),
+                delegates: delegates.clone(),
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum RoundResult {
+    /// The fetch succeeded, and updated `updated_tips`.
+    #[serde(rename_all = "camelCase")]
+    Fetched {
+        updated_tips: BTreeMap<ext::RefLike, ext::Oid>,
+        pack: Option<PackStats>,
+        /// Set if the fetch was skipped. Absent from traces recorded before
+        /// skipped fetches were reported.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        skipped: Option<Skipped>,
+    },
+    /// The fetch failed.
+    Failed { error: String },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum Outcome {
+    /// The replication succeeded.
+    #[serde(rename_all = "camelCase")]
+    Success {
+        /// `true` if the [`Urn`] was not present locally before.
+        clone: bool,
+        updated_tips: BTreeMap<ext::RefLike, ext::Oid>,
+    },
+    /// The replication failed.
+    Failure { error: String },
+}
+
+impl From<&Result<ReplicateResult, ReplicationError>> for Outcome {
+    fn from(res: &Result<ReplicateResult, ReplicationError>) -> Self {
+        match res {
+            Ok(res) => Self::Success {
+                clone: matches!(res.mode, Mode::Clone),
+                updated_tips: res.updated_tips.clone(),
+            },
+            Err(e) => Self::Failure {
+                error: e.to_string(),
+            },
+        }
+    }
+}
+
+/// Records a [`Trace`] of the [`Recording`] fetchers it creates.
+#[derive(Clone)]
+pub struct Recorder {
+    trace: Arc<Mutex<Trace>>,
+}
+
+impl Recorder {
+    /// Start recording a run using `fetcher`, started at `started`.
+    pub fn new<F>(fetcher: &F, started: SystemTime) -> Self
+    where
+        F: Fetcher<PeerId = PeerId, UrnId = Revision>,
+    {
+        let trace = Trace {
+            urn: Urn::new(fetcher.urn().id),
+            remote_peer: *fetcher.remote_peer(),
+            timestamp: started
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or_default(),
+            features: fetcher.remote_features().map(|f| f.bits()),
+            advertised: fetcher.remote_heads().advertised().collect(),
+            rounds: vec![],
+            outcome: None,
+        };
+        Self {
+            trace: Arc::new(Mutex::new(trace)),
+        }
+    }
+
+    /// Wrap `fetcher`, such that its rounds are recorded.
+    pub fn wrap<F>(&self, fetcher: F) -> Recording<F> {
+        Recording {
+            inner: fetcher,
+            trace: Arc::clone(&self.trace),
+        }
+    }
+
+    /// Finish recording with the outcome `res`.
+    pub fn finish(self, res: &Result<ReplicateResult, ReplicationError>) -> Trace {
+        let mut trace = self.trace.lock().clone();
+        trace.outcome = Some(Outcome::from(res));
+        trace
+    }
+}
+
+/// A [`Fetcher`] which records the rounds of the wrapped fetcher.
+pub struct Recording<F> {
+    inner: F,
+    trace: Arc<Mutex<Trace>>,
+}
+
+impl<F> Fetcher for Recording<F>
+where
+    F: Fetcher<PeerId = PeerId, UrnId = Revision>,
+    F::Error: std::error::Error,
+{
+    type Error = F::Error;
+    type PeerId = PeerId;
+    type UrnId = Revision;
+
+    fn urn(&self) -> &Urn {
+        self.inner.urn()
+    }
+
+    fn remote_peer(&self) -> &PeerId {
+        self.inner.remote_peer()
+    }
+
+    fn remote_heads(&self) -> &RemoteHeads {
+        self.inner.remote_heads()
+    }
+
+    fn fetch(
+        &mut self,
+        fetchspecs: Fetchspecs<PeerId, Revision>,
+    ) -> Result<FetchResult, Self::Error> {
+        let specs = Specs::from(&fetchspecs);
+        let limit = fetchspecs.fetch_limit();
+        let refspecs = fetchspecs
+            .refspecs(
+                self.inner.urn(),
+                *self.inner.remote_peer(),
+                self.inner.remote_heads(),
+            )
+            .into_iter()
+            .map(|spec| spec.to_string())
+            .collect();
+
+        let res = self.inner.fetch(fetchspecs);
+        let result = match &res {
+            Ok(FetchResult {
+                updated_tips,
+                pack,
+                skipped,
+                ..
+            }) => RoundResult::Fetched {
+                updated_tips: updated_tips.clone(),
+                pack: *pack,
+                skipped: *skipped,
+            },
+            Err(e) => RoundResult::Failed {
+                error: e.to_string(),
+            },
+        };
+        self.trace.lock().rounds.push(Round {
+            specs,
+            limit,
+            refspecs,
+            result,
+        });
+
+        res
+    }
+
+    fn remote_features(&self) -> Option<Features> {
+        self.inner.remote_features()
+    }
+
+    fn ls_refs_duration(&self) -> Option<std::time::Duration> {
+        self.inner.ls_refs_duration()
+    }
+
+    fn take_retries(&mut self) -> Vec<Retry> {
+        self.inner.take_retries()
+    }
+}
+
+fn traces_dir(storage: &Storage) -> PathBuf {
+    storage.as_raw().path().join(TRACES_DIR)
+}
+
+/// Write `trace` to the `traces` directory of `storage`, returning the path
+/// of the file written.
+pub fn write(storage: &Storage, trace: &Trace) -> Result<PathBuf, Error> {
+    let dir = traces_dir(storage);
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!(
+        "{}-{}-{}.json",
+        trace.urn.encode_id(),
+        trace.remote_peer,
+        trace.timestamp
+    ));
+    serde_json::to_writer_pretty(BufWriter::new(File::create(&path)?), trace)?;
+
+    Ok(path)
+}
+
+/// Read a [`Trace`] previously written by [`write`].
+pub fn read(path: &Path) -> Result<Trace, Error> {
+    Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+}
+
+/// The paths of the traces recorded in `storage`, in no particular order.
+pub fn list(storage: &Storage) -> Result<Vec<PathBuf>, Error> {
+    let entries = match fs::read_dir(traces_dir(storage)) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().map(|ext| ext == "json").unwrap_or(false) {
+            paths.push(path);
+        }
+    }
+
+    Ok(paths)
+}
@@ -0,0 +1,158 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Replaying recorded replication [`Trace`]s.
+//!
+//! A [`Replayer`] stands in for the remote peer of a recorded run: it
+//! advertises the recorded refs, and answers each fetch with the recorded
+//! round, updating the refs the original fetch updated. The objects must
+//! already be present in the storage replayed against, eg. because it is a
+//! copy of the storage the trace was recorded in.
+//!
+//! Since traces are plain data, they can also be written by hand (or edited),
+//! which allows to script the behaviour of a remote peer, including failing
+//! rounds, when testing code built on top of [`replication::replicate`].
+//!
+//! Only available with the `testing` feature.
+
+use std::{collections::BTreeMap, vec};
+
+use thiserror::Error;
+
+use super::{Round, RoundResult, Trace};
+use crate::{
+    git::{
+        fetch::{FetchResult, Fetcher, Fetchspecs, RemoteHeads, TipUpdate},
+        replication::{self, ReplicateResult},
+        storage::Storage,
+        Urn,
+    },
+    identities::git::Revision,
+    net::protocol::Features,
+    PeerId,
+};
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("round {round} diverged: expected refspecs {expected:?}, got {actual:?}")]
+    Diverged {
+        round: usize,
+        expected: Vec<String>,
+        actual: Vec<String>,
+    },
+
+    #[error("round {0} was not recorded")]
+    Exhausted(usize),
+
+    #[error("round {round} failed during recording: {error}")]
+    Recorded { round: usize, error: String },
+
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+}
+
+pub struct Replayer<'a> {
+    storage: &'a Storage,
+    urn: Urn,
+    remote_peer: PeerId,
+    remote_heads: RemoteHeads,
+    features: Option<Features>,
+    rounds: vec::IntoIter<Round>,
+    round: usize,
+}
+
+impl<'a> Replayer<'a> {
+    pub fn new(storage: &'a Storage, trace: Trace) -> Self {
+        Self {
+            storage,
+            remote_heads: trace.remote_heads(),
+            features: trace.features(),
+            urn: trace.urn,
+            remote_peer: trace.remote_peer,
+            rounds: trace.rounds.into_iter(),
+            round: 0,
+        }
+    }
+}
+
+impl Fetcher for Replayer<'_> {
+    type Error = Error;
+    type PeerId = PeerId;
+    type UrnId = Revision;
+
+    fn urn(&self) -> &Urn {
+        &self.urn
+    }
+
+    fn remote_peer(&self) -> &PeerId {
+        &self.remote_peer
+    }
+
+    fn remote_heads(&self) -> &RemoteHeads {
+        &self.remote_heads
+    }
+
+    fn fetch(&mut self, fetchspecs: Fetchspecs<PeerId, Revision>) -> Result<FetchResult, Error> {
+        let round = self.round;
+        self.round += 1;
+
+        let recorded = self.rounds.next().ok_or(Error::Exhausted(round))?;
+        let actual = fetchspecs
+            .refspecs(&self.urn, self.remote_peer, &self.remote_heads)
+            .into_iter()
+            .map(|spec| spec.to_string())
+            .collect::<Vec<_>>();
+        if actual != recorded.refspecs {
+            return Err(Error::Diverged {
+                round,
+                expected: recorded.refspecs,
+                actual,
+            });
+        }
+
+        match recorded.result {
+            RoundResult::Failed { error } => Err(Error::Recorded { round, error }),
+            RoundResult::Fetched {
+                updated_tips,
+                pack,
+                skipped,
+            } => {
+                let repo = self.storage.as_raw();
+                let mut updates = BTreeMap::new();
+                for (name, oid) in &updated_tips {
+                    let old = repo
+                        .refname_to_id(name.as_str())
+                        .unwrap_or_else(|_| git2::Oid::zero());
+                    repo.reference(name.as_str(), (*oid).into(), true, "replay")?;
+                    updates.insert(name.clone(), TipUpdate::classify(repo, old, (*oid).into()));
+                }
+                Ok(FetchResult {
+                    updated_tips,
+                    updates,
+                    pack,
+                    skipped,
+                })
+            },
+        }
+    }
+
+    fn remote_features(&self) -> Option<Features> {
+        self.features
+    }
+}
+
+/// Replay `trace` against `storage`, using `config` for the replication.
+pub fn replay(
+    storage: &Storage,
+    trace: Trace,
+    config: replication::Config,
+) -> Result<ReplicateResult, replication::Error> {
+    let config = replication::Config {
+        trace: false,
+        ..config
+    };
+    replication::replicate(storage, Replayer::new(storage, trace), config, None)
+}
@@ -0,0 +1,215 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Restoring the refs of a tracked peer to a previously signed state.
+//!
+//! [`plan`] computes the [`Update`]s which bring the remote-tracking refs of
+//! a peer back in line with its signed refs as of a past `rad/signed_refs`
+//! commit (see [`Refs::load_at`]), including `rad/signed_refs` itself.
+//! [`apply`] performs them, refusing updates which are not fast-forwards
+//! unless forced. Note that rolling back usually means rewinding refs, and so
+//! requires force.
+//!
+//! Replicating from the peer again will bring back its current refs, unless
+//! the peer is untracked in the meantime.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use git_ext as ext;
+use thiserror::Error;
+
+use super::{
+    refs::{self, Refs},
+    storage::Storage,
+    types::Namespace,
+    Urn,
+};
+use crate::PeerId;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("only the refs of remote peers can be restored")]
+    LocalPeer,
+
+    #[error("no signed refs found at {0}")]
+    NoSignedRefs(ext::Oid),
+
+    #[error("object {oid} of {name} is missing from the storage")]
+    MissingObject { name: String, oid: ext::Oid },
+
+    #[error("refusing to update {0:?}, which would not be a fast-forward")]
+    NonFastForward(Vec<String>),
+
+    #[error("{0} was updated since the plan was made")]
+    Concurrent(String),
+
+    #[error(transparent)]
+    Refs(#[from] refs::stored::Error),
+
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+}
+
+/// A single ref update of a [`Plan`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Update {
+    /// The fully qualified name of the ref.
+    pub name: String,
+    /// The current target, or `None` if the ref is created.
+    pub old: Option<ext::Oid>,
+    /// The target to restore, or `None` if the ref is deleted.
+    pub new: Option<ext::Oid>,
+    /// `true` if the ref is created, or `new` is a descendant of `old`.
+    pub fast_forward: bool,
+}
+
+/// The ref updates restoring the refs of `peer` in `urn` to the signed state
+/// at `at`.
+#[derive(Clone, Debug)]
+pub struct Plan {
+    pub urn: Urn,
+    pub peer: PeerId,
+    pub at: ext::Oid,
+    pub updates: Vec<Update>,
+}
+
+impl Plan {
+    /// `true` if all updates are fast-forwards, ie. [`apply`] succeeds
+    /// without force.
+    pub fn is_fast_forward(&self) -> bool {
+        self.updates.iter().all(|update| update.fast_forward)
+    }
+}
+
+/// Compute the [`Plan`] restoring the refs of `peer` in `urn` to what they
+/// were signed as at the `rad/signed_refs` commit `at`.
+///
+/// `at` must be in the history of the current `rad/signed_refs` of `peer`,
+/// and all objects to restore must be present in the storage.
+pub fn plan(storage: &Storage, urn: &Urn, peer: PeerId, at: ext::Oid) -> Result<Plan, Error> {
+    if &peer == storage.peer_id() {
+        return Err(Error::LocalPeer);
+    }
+
+    let signed = Refs::load_at(storage, urn, peer, at)?.ok_or(Error::NoSignedRefs(at))?;
+    let prefix = format!(
+        "refs/namespaces/{}/refs/remotes/{}/",
+        Namespace::from(urn),
+        peer
+    );
+
+    let mut desired = signed
+        .iter_categorised()
+        .map(|((name, oid), category)| (format!("{}{}/{}", prefix, category, name), *oid))
+        .collect::<BTreeMap<_, _>>();
+    desired.insert(format!("{}rad/signed_refs", prefix), at);
+
+    let repo = storage.as_raw();
+    let mut current = BTreeMap::new();
+    for r in repo.references_glob(&format!("{}*", prefix))? {
+        let r = r?;
+        if let (Some(name), Some(oid)) = (r.name(), r.target()) {
+            current.insert(name.to_owned(), ext::Oid::from(oid));
+        }
+    }
+
+    let odb = repo.odb()?;
+    let names = desired
+        .keys()
+        .chain(current.keys())
+        .cloned()
+        .collect::<BTreeSet<_>>();
+    let mut updates = Vec::new();
+    for name in names {
+        let old = current.get(&name).copied();
+        let new = desired.get(&name).copied();
+        if old == new {
+            continue;
+        }
+        if let Some(oid) = new {
+            if !odb.exists(oid.into()) {
+                return Err(Error::MissingObject { name, oid });
+            }
+        }
+        let fast_forward = match (old, new) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(old), Some(new)) => repo
+                .graph_descendant_of(new.into(), old.into())
+                .unwrap_or(false),
+        };
+        updates.push(Update {
+            name,
+            old,
+            new,
+            fast_forward,
+        });
+    }
+
+    Ok(Plan {
+        urn: urn.clone(),
+        peer,
+        at,
+        updates,
+    })
+}
+
+/// Perform the updates of `plan`.
+///
+/// Unless `force` is given, nothing is updated if any of the updates is not a
+/// fast-forward.
+///
+/// Every ref is only updated if it still points to [`Update::old`], so that
+/// a replication which happened since the plan was made is not overwritten.
+/// The updates are applied in order, and applying stops at the first ref
+/// which changed, which is reported as [`Error::Concurrent`].
+#[tracing::instrument(skip(storage, plan), fields(urn = %plan.urn, peer = %plan.peer, at = %plan.at))]
+pub fn apply(storage: &Storage, plan: &Plan, force: bool) -> Result<(), Error> {
+    if !force && !plan.is_fast_forward() {
+        return Err(Error::NonFastForward(
+            plan.updates
+                .iter()
+                .filter(|update| !update.fast_forward)
+                .map(|update| update.name.clone())
+                .collect(),
+        ));
+    }
+
+    let repo = storage.as_raw();
+    let msg = format!("restore: signed refs at {}", plan.at);
+    for update in &plan.updates {
+        let concurrent = || Error::Concurrent(update.name.clone());
+        match (update.old, update.new) {
+            (None, Some(new)) => {
+                repo.reference(&update.name, new.into(), false, &msg)
+                    .map_err(|e| match e.code() {
+                        git2::ErrorCode::Exists => concurrent(),
+                        _ => e.into(),
+                    })?;
+            },
+            (Some(old), Some(new)) => {
+                repo.reference_matching(&update.name, new.into(), true, old.into(), &msg)
+                    .map_err(|e| match e.code() {
+                        git2::ErrorCode::Modified | git2::ErrorCode::NotFound => concurrent(),
+                        _ => e.into(),
+                    })?;
+            },
+            (old, None) => match repo.find_reference(&update.name) {
+                Err(e) if ext::is_not_found_err(&e) => {},
+                Err(e) => return Err(e.into()),
+                Ok(mut r) => {
+                    if r.target().map(ext::Oid::from) != old {
+                        return Err(concurrent());
+                    }
+                    r.delete()?
+                },
+            },
+        }
+        tracing::debug!(name = %update.name, old = ?update.old, new = ?update.new, "restored");
+    }
+
+    Ok(())
+}
@@ -16,18 +16,27 @@ use git2::string_array::StringArray;
 use git_ext::{self as ext, is_not_found_err};
 
 use crate::{
-    git::types::{Many, One, Reference},
+    git::{
+        refs::{self, Refs},
+        types::{Many, One, Reference},
+    },
     identities::git::Urn,
     paths::Paths,
     PeerId,
     Signer,
 };
 
+pub mod bitmap;
+pub mod commit_graph;
 pub mod config;
 pub mod fetcher;
 pub mod glob;
+pub mod packed;
 pub mod pool;
 pub mod read;
+pub mod reflog;
+pub mod stats;
+pub mod verifications;
 pub mod watch;
 
 pub use config::Config;
@@ -43,6 +52,7 @@ pub use read::{
     References,
     ReferencesGlob,
 };
+pub use verifications::Verifications;
 pub use watch::{NamespaceEvent, Watcher};
 
 pub mod error {
@@ -127,7 +137,11 @@ impl Storage {
         }
 
         Ok(Self {
-            inner: ReadOnly { backend, peer_id },
+            inner: ReadOnly {
+                verifications: Verifications::new(backend.path()),
+                backend,
+                peer_id,
+            },
             signer: BoxedSigner::from(SomeSigner { signer }),
             fetchers,
         })
@@ -208,6 +222,20 @@ impl Storage {
         watch::Watch { storage: self }
     }
 
+    /// The signed refs of `peer` in the namespace `urn`, as of the
+    /// `rad/signed_refs` commit `at`. See [`ReadOnly::refs_at`].
+    pub fn refs_at<P>(
+        &self,
+        urn: &Urn,
+        peer: P,
+        at: ext::Oid,
+    ) -> Result<Option<Refs>, refs::stored::Error>
+    where
+        P: Into<Option<PeerId>> + Debug,
+    {
+        self.inner.refs_at(urn, peer, at)
+    }
+
     pub(super) fn signer(&self) -> &BoxedSigner {
         &self.signer
     }
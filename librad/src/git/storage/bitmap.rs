@@ -0,0 +1,171 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Reachability bitmaps for serving fetches.
+//!
+//! A [bitmap index] records, for selected commits of a pack, the set of
+//! objects reachable from them. `git upload-pack` uses it to answer
+//! want/have negotiation and to enumerate the objects to send without
+//! walking the history, which makes serving popular projects considerably
+//! cheaper.
+//!
+//! Bitmaps are a property of a pack, not of a ref namespace: since all
+//! projects share the packs of the monorepo, a bitmap written by [`repack`]
+//! covers every namespace at once. Packs received after the last repack are
+//! not covered, so [`repack_if_incomplete`] is meant to be run periodically.
+//!
+//! How well the bitmaps covered the packs at the time a fetch was served is
+//! recorded in [`Counters`].
+//!
+//! [bitmap index]: https://git-scm.com/docs/bitmap-format
+
+use std::{
+    fs,
+    io,
+    path::Path,
+    process::{Command, ExitStatus},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use thiserror::Error;
+
+use super::Storage;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("`git repack` failed with {0}: {1}")]
+    Repack(ExitStatus, String),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// The number of packs of a repository, and how many of them have a bitmap
+/// index.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Coverage {
+    pub packs: usize,
+    pub bitmapped: usize,
+}
+
+impl Coverage {
+    /// Inspect the packs of the repository at `git_dir`.
+    pub fn of(git_dir: &Path) -> io::Result<Self> {
+        let dir = git_dir.join("objects").join("pack");
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e),
+        };
+
+        let mut coverage = Self::default();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().map_or(false, |ext| ext == "pack") {
+                coverage.packs += 1;
+                if path.with_extension("bitmap").is_file() {
+                    coverage.bitmapped += 1;
+                }
+            }
+        }
+
+        Ok(coverage)
+    }
+
+    /// `true` if there are packs, and all of them are covered by a bitmap.
+    ///
+    /// Note that loose objects are not taken into account.
+    pub fn is_complete(&self) -> bool {
+        self.packs > 0 && self.bitmapped == self.packs
+    }
+}
+
+/// Repack all objects of `storage` into a single pack, and write a bitmap
+/// index for it.
+#[tracing::instrument(skip(storage))]
+pub fn repack(storage: &Storage) -> Result<(), Error> {
+    let output = Command::new("git")
+        .current_dir(storage.path())
+        .args(&[
+            "-c",
+            "pack.writeBitmapHashCache=true",
+            "repack",
+            "-a",
+            "-d",
+            "-q",
+            "--write-bitmap-index",
+        ])
+        .output()?;
+    if !output.status.success() {
+        return Err(Error::Repack(
+            output.status,
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// [`repack`] the storage if not all of its packs are covered by a bitmap.
+///
+/// Returns `true` if the storage was repacked.
+pub fn repack_if_incomplete(storage: &Storage) -> Result<bool, Error> {
+    let coverage = Coverage::of(storage.path())?;
+    if coverage.is_complete() {
+        Ok(false)
+    } else {
+        tracing::debug!(
+            packs = coverage.packs,
+            bitmapped = coverage.bitmapped,
+            "writing bitmap index"
+        );
+        repack(storage)?;
+        Ok(true)
+    }
+}
+
+/// The number of fetches served, by [`Coverage`] of the packs at the time.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Stats {
+    /// All packs were covered by a bitmap.
+    pub hits: u64,
+    /// Some, but not all packs were covered by a bitmap.
+    pub partial: u64,
+    /// No pack was covered by a bitmap.
+    pub misses: u64,
+}
+
+/// Shared counters yielding [`Stats`].
+#[derive(Clone, Debug, Default)]
+pub struct Counters {
+    hits: Arc<AtomicU64>,
+    partial: Arc<AtomicU64>,
+    misses: Arc<AtomicU64>,
+}
+
+impl Counters {
+    pub fn record(&self, coverage: &Coverage) {
+        let counter = if coverage.is_complete() {
+            &self.hits
+        } else if coverage.bitmapped > 0 {
+            &self.partial
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> Stats {
+        Stats {
+            hits: self.hits.load(Ordering::Relaxed),
+            partial: self.partial.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
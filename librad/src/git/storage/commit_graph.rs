@@ -0,0 +1,352 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Ancestry checks using the commit-graph file of the storage.
+//!
+//! The [commit-graph] file stores the parents and generation number (ie.
+//! topological level) of every commit reachable from the refs at the time it
+//! was written. Generation numbers allow to stop a history traversal as soon
+//! as it is known that the commit being looked for can no longer be found.
+//!
+//! The file is written by `git commit-graph`, see [`write`]. Commits which
+//! are not contained in it (because they were added after it was written)
+//! are looked up in the object database instead.
+//!
+//! [commit-graph]: https://git-scm.com/docs/commit-graph-format
+
+use std::{
+    collections::{BTreeSet, BinaryHeap, HashMap, HashSet},
+    convert::TryInto as _,
+    fs,
+    io,
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus},
+};
+
+use git_ext::is_not_found_err;
+use std_ext::result::ResultExt as _;
+use thiserror::Error;
+
+use super::{ReadOnly, Storage};
+
+const SIGNATURE: &[u8] = b"CGPH";
+const CHUNK_OID_FANOUT: &[u8] = b"OIDF";
+const CHUNK_OID_LOOKUP: &[u8] = b"OIDL";
+const CHUNK_COMMIT_DATA: &[u8] = b"CDAT";
+const CHUNK_EXTRA_EDGES: &[u8] = b"EDGE";
+
+const HASH_LEN: usize = 20;
+const COMMIT_DATA_LEN: usize = HASH_LEN + 16;
+
+const PARENT_NONE: u32 = 0x7000_0000;
+const PARENT_EXTRA: u32 = 0x8000_0000;
+const EDGE_LAST: u32 = 0x8000_0000;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("malformed commit-graph: {0}")]
+    Malformed(&'static str),
+
+    #[error("`git commit-graph write` failed with {0}: {1}")]
+    Write(ExitStatus, String),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+}
+
+/// The contents of a commit-graph file.
+pub struct CommitGraph {
+    /// Sorted, such that the index of an oid is its graph position.
+    oids: Vec<git2::Oid>,
+    commits: Vec<Commit>,
+    extra_edges: Vec<u32>,
+}
+
+struct Commit {
+    parents: [u32; 2],
+    generation: u32,
+}
+
+impl CommitGraph {
+    /// The location of the commit-graph file in the repository at
+    /// `git_dir`.
+    pub fn path(git_dir: &Path) -> PathBuf {
+        git_dir.join("objects").join("info").join("commit-graph")
+    }
+
+    /// Load the commit-graph file of `storage`, if it exists.
+    pub fn load(storage: &ReadOnly) -> Result<Option<Self>, Error> {
+        match fs::read(Self::path(storage.path())) {
+            Ok(bytes) => Self::parse(&bytes).map(Some),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() < 8 || &bytes[..4] != SIGNATURE {
+            return Err(Error::Malformed("invalid signature"));
+        }
+        if bytes[4] != 1 {
+            return Err(Error::Malformed("unsupported version"));
+        }
+        if bytes[5] != 1 {
+            return Err(Error::Malformed("unsupported hash version"));
+        }
+        let num_chunks = bytes[6] as usize;
+
+        let mut chunks = Vec::with_capacity(num_chunks + 1);
+        for i in 0..=num_chunks {
+            let entry = bytes
+                .get(8 + i * 12..8 + (i + 1) * 12)
+                .ok_or(Error::Malformed("truncated chunk table"))?;
+            let offset = u64::from_be_bytes(entry[4..].try_into().unwrap()) as usize;
+            chunks.push((&entry[..4], offset));
+        }
+        let chunk = |id: &[u8]| {
+            chunks.windows(2).find_map(|w| {
+                let ((this, start), (_, end)) = (w[0], w[1]);
+                (this == id).then(|| bytes.get(start..end)).flatten()
+            })
+        };
+
+        let fanout = chunk(CHUNK_OID_FANOUT).ok_or(Error::Malformed("missing fanout"))?;
+        if fanout.len() != 256 * 4 {
+            return Err(Error::Malformed("invalid fanout"));
+        }
+        let len = u32::from_be_bytes(fanout[255 * 4..].try_into().unwrap()) as usize;
+
+        let lookup = chunk(CHUNK_OID_LOOKUP).ok_or(Error::Malformed("missing oid lookup"))?;
+        let data = chunk(CHUNK_COMMIT_DATA).ok_or(Error::Malformed("missing commit data"))?;
+        if lookup.len() != len * HASH_LEN || data.len() != len * COMMIT_DATA_LEN {
+            return Err(Error::Malformed("inconsistent number of commits"));
+        }
+
+        let oids = lookup
+            .chunks(HASH_LEN)
+            .map(git2::Oid::from_bytes)
+            .collect::<Result<Vec<_>, _>>()?;
+        let commits = data
+            .chunks(COMMIT_DATA_LEN)
+            .map(|entry| {
+                let word = |at: usize| {
+                    u32::from_be_bytes(entry[HASH_LEN + at..HASH_LEN + at + 4].try_into().unwrap())
+                };
+                Commit {
+                    parents: [word(0), word(4)],
+                    generation: word(8) >> 2,
+                }
+            })
+            .collect();
+        let extra_edges = chunk(CHUNK_EXTRA_EDGES)
+            .unwrap_or_default()
+            .chunks(4)
+            .map(|edge| u32::from_be_bytes(edge.try_into().unwrap()))
+            .collect();
+
+        Ok(Self {
+            oids,
+            commits,
+            extra_edges,
+        })
+    }
+
+    /// The number of commits in the graph.
+    pub fn len(&self) -> usize {
+        self.oids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.oids.is_empty()
+    }
+
+    /// The generation number of `oid`, or `None` if it is not in the graph.
+    pub fn generation(&self, oid: &git2::Oid) -> Option<u32> {
+        self.position(oid).map(|pos| self.commits[pos].generation)
+    }
+
+    fn position(&self, oid: &git2::Oid) -> Option<usize> {
+        self.oids.binary_search(oid).ok()
+    }
+
+    fn parents(&self, pos: usize) -> Result<Vec<git2::Oid>, Error> {
+        let [first, second] = self.commits[pos].parents;
+        let mut positions = Vec::new();
+        if first != PARENT_NONE {
+            positions.push(first)
+        }
+        if second & PARENT_EXTRA != 0 {
+            let mut i = (second & !PARENT_EXTRA) as usize;
+            loop {
+                let edge = *self
+                    .extra_edges
+                    .get(i)
+                    .ok_or(Error::Malformed("extra edge out of bounds"))?;
+                positions.push(edge & !EDGE_LAST);
+                if edge & EDGE_LAST != 0 {
+                    break;
+                }
+                i += 1;
+            }
+        } else if second != PARENT_NONE {
+            positions.push(second)
+        }
+
+        positions
+            .into_iter()
+            .map(|pos| {
+                self.oids
+                    .get(pos as usize)
+                    .copied()
+                    .ok_or(Error::Malformed("parent out of bounds"))
+            })
+            .collect()
+    }
+}
+
+/// Write the commit-graph file of `storage`, covering all commits reachable
+/// from any ref.
+#[tracing::instrument(skip(storage))]
+pub fn write(storage: &Storage) -> Result<(), Error> {
+    let output = Command::new("git")
+        .current_dir(storage.path())
+        .args(&["commit-graph", "write", "--reachable"])
+        .output()?;
+    if !output.status.success() {
+        return Err(Error::Write(
+            output.status,
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// For each `(ancestor, descendant)` pair, determine whether `ancestor` is
+/// `descendant`, or contained in its history.
+///
+/// All pairs are answered in a single traversal, in descending order of
+/// generation numbers. Commits not found in the object database are treated
+/// as having no history.
+pub(super) fn ancestry_many(
+    repo: &git2::Repository,
+    graph: Option<&CommitGraph>,
+    pairs: &[(git2::Oid, git2::Oid)],
+) -> Result<Vec<bool>, Error> {
+    // Commits not in the graph can't be ancestors of commits in the graph,
+    // so it is safe to consider them to be of infinite generation.
+    let generation = |oid: &git2::Oid| {
+        graph
+            .and_then(|graph| graph.generation(oid))
+            .unwrap_or(u32::MAX)
+    };
+    let parents = |oid: &git2::Oid| match graph.and_then(|g| g.position(oid).map(|pos| (g, pos))) {
+        Some((graph, pos)) => graph.parents(pos),
+        None => repo
+            .find_commit(*oid)
+            .map(|commit| commit.parent_ids().collect())
+            .or_matches::<Error, _, _>(is_not_found_err, || Ok(vec![])),
+    };
+
+    let descendants = pairs
+        .iter()
+        .map(|(_, descendant)| *descendant)
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    let index = |oid: &git2::Oid| descendants.binary_search(oid).unwrap();
+
+    let mut answers = vec![false; pairs.len()];
+    let mut queries: HashMap<git2::Oid, Vec<usize>> = HashMap::new();
+    for (i, (ancestor, descendant)) in pairs.iter().enumerate() {
+        if ancestor == descendant {
+            answers[i] = true;
+        } else {
+            queries.entry(*ancestor).or_default().push(i);
+        }
+    }
+    let mut pending = queries
+        .keys()
+        .map(|oid| (generation(oid), *oid))
+        .collect::<BTreeSet<_>>();
+
+    // The descendants each visited commit is reachable from
+    let mut reachable: HashMap<git2::Oid, Bits> = HashMap::new();
+    let mut queue = BinaryHeap::new();
+    let mut queued = HashSet::new();
+    for oid in &descendants {
+        reachable.entry(*oid).or_default().set(index(oid));
+        queue.push((generation(oid), *oid));
+        queued.insert(*oid);
+    }
+
+    while let Some((gen, oid)) = queue.pop() {
+        match pending.iter().next() {
+            Some((min, _)) if gen >= *min => {},
+            _ => break,
+        }
+        queued.remove(&oid);
+        let bits = reachable[&oid].clone();
+
+        if let Some(indices) = queries.get_mut(&oid) {
+            indices.retain(|i| {
+                let found = bits.get(index(&pairs[*i].1));
+                if found {
+                    answers[*i] = true;
+                }
+                !found
+            });
+            if indices.is_empty() {
+                pending.remove(&(gen, oid));
+            }
+        }
+
+        for parent in parents(&oid)? {
+            let changed = reachable.entry(parent).or_default().union(&bits);
+            if changed && queued.insert(parent) {
+                queue.push((generation(&parent), parent));
+            }
+        }
+    }
+
+    Ok(answers)
+}
+
+/// A growable bitset.
+#[derive(Clone, Default)]
+struct Bits(Vec<u64>);
+
+impl Bits {
+    fn set(&mut self, i: usize) {
+        if self.0.len() <= i / 64 {
+            self.0.resize(i / 64 + 1, 0);
+        }
+        self.0[i / 64] |= 1 << (i % 64);
+    }
+
+    fn get(&self, i: usize) -> bool {
+        self.0
+            .get(i / 64)
+            .map(|word| word & (1 << (i % 64)) != 0)
+            .unwrap_or(false)
+    }
+
+    /// Add all bits of `other`, returning `true` if any were not set before.
+    fn union(&mut self, other: &Bits) -> bool {
+        if self.0.len() < other.0.len() {
+            self.0.resize(other.0.len(), 0);
+        }
+        let mut changed = false;
+        for (word, other) in self.0.iter_mut().zip(&other.0) {
+            changed |= *other & !*word != 0;
+            *word |= *other;
+        }
+        changed
+    }
+}
@@ -27,6 +27,7 @@ const CONFIG_USER_NAME: &str = "user.name";
 const CONFIG_USER_EMAIL: &str = "user.email";
 const CONFIG_RAD_SELF: &str = "rad.self";
 const CONFIG_RAD_PEER_ID: &str = "rad.peerid";
+const CONFIG_RAD_SIGN_NOTES: &str = "rad.signnotes";
 
 #[derive(Debug, Error)]
 #[non_exhaustive]
@@ -174,6 +175,14 @@ where
         }
     }
 
+    /// Set whether `refs/notes/*` are included in the signed refs, see
+    /// [`Config::sign_notes`].
+    pub fn set_sign_notes(&mut self, sign: bool) -> Result<(), Error> {
+        self.inner
+            .set_bool(CONFIG_RAD_SIGN_NOTES, sign)
+            .map_err(Error::from)
+    }
+
     pub(crate) fn as_raw(&self) -> &git2::Config {
         &self.inner
     }
@@ -208,6 +217,14 @@ impl<S> Config<'_, S> {
             .map(|urn| urn.parse().map_err(Error::from))
             .transpose()
     }
+
+    /// Whether `refs/notes/*` are included in the signed refs, and thus
+    /// replicated. Defaults to `true`.
+    pub fn sign_notes(&self) -> Result<bool, Error> {
+        self.inner
+            .get_bool(CONFIG_RAD_SIGN_NOTES)
+            .or_matches::<Error, _, _>(is_not_found_err, || Ok(true))
+    }
 }
 
 impl Config<'_, PhantomData<!>> {
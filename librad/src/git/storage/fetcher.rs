@@ -8,25 +8,28 @@ use std::{
     convert::TryFrom,
     hash::BuildHasherDefault,
     net::SocketAddr,
+    path::PathBuf,
     sync::Arc,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use dashmap::DashMap;
-use git_ext::RefLike;
+use git_ext::{self as ext, RefLike};
 use rustc_hash::FxHasher;
 use thiserror::Error;
 use url::Url;
 
-use super::{PoolError, Storage};
+use super::{PoolError, ReadOnly, Storage};
 use crate::{
     executor,
     git::{
         fetch::{self, FetchResult, Fetchspecs, RemoteHeads},
-        p2p::url::GitUrlRef,
+        p2p::{transport, url::GitUrlRef},
+        types::Fetchspec,
         Urn,
     },
     identities::{self, git::Revision},
+    net::protocol::Features,
     PeerId,
 };
 
@@ -50,6 +53,7 @@ pub struct Fetchers(Arc<DashMap<Urn, Info, BuildHasherDefault<FxHasher>>>);
 pub struct Fetcher<'a> {
     reg: &'a Fetchers,
     inner: imp::Fetcher<'a>,
+    features: Option<Features>,
 }
 
 impl Drop for Fetcher<'_> {
@@ -81,6 +85,18 @@ impl<'a> fetch::Fetcher for Fetcher<'a> {
     ) -> Result<fetch::FetchResult, Self::Error> {
         self.inner.fetch(specs)
     }
+
+    fn remote_features(&self) -> Option<Features> {
+        self.features
+    }
+
+    fn ls_refs_duration(&self) -> Option<Duration> {
+        Some(self.inner.info().ls_refs)
+    }
+
+    fn take_retries(&mut self) -> Vec<fetch::Retry> {
+        self.inner.take_retries()
+    }
 }
 
 /// Types which can create a [`Fetcher`].
@@ -104,6 +120,8 @@ pub struct PeerToPeer {
     pub remote_peer: PeerId,
     pub addr_hints: Vec<SocketAddr>,
     pub nonced: bool,
+    /// The [`Features`] negotiated with `remote_peer`, if known.
+    pub features: Option<Features>,
 }
 
 impl PeerToPeer {
@@ -116,6 +134,7 @@ impl PeerToPeer {
             remote_peer,
             addr_hints: addr_hints.into_iter().collect(),
             nonced: true,
+            features: None,
         }
     }
 
@@ -126,6 +145,10 @@ impl PeerToPeer {
         }
     }
 
+    pub fn features(self, features: Option<Features>) -> Self {
+        Self { features, ..self }
+    }
+
     pub fn build<'a>(
         &self,
         storage: &'a Storage,
@@ -143,12 +166,16 @@ impl PeerToPeer {
             addr_hints: &self.addr_hints,
             nonce: nonce.as_ref(),
         };
-        AnyUrl {
+        let fetcher = AnyUrl {
             urn: self.urn.clone(),
             remote_peer: self.remote_peer,
             url: Url::from(url),
         }
-        .build(storage)
+        .build(storage)?;
+        Ok(fetcher.map(|mut fetcher| {
+            fetcher.features = self.features;
+            fetcher
+        }))
     }
 }
 
@@ -220,6 +247,7 @@ impl AnyUrl {
                 Ok(Ok(Fetcher {
                     reg: fetchers,
                     inner: fetcher,
+                    features: None,
                 }))
             },
 
@@ -247,6 +275,66 @@ impl BuildFetcher for AnyUrl {
     }
 }
 
+/// A [`BuildFetcher`] which creates [`Fetcher`]s reading from another
+/// monorepo on the same machine, such as the storage of a different profile.
+///
+/// This uses `git`'s local transport, so no network is involved. The remote
+/// monorepo is treated like any other remote peer, identified by the
+/// [`PeerId`] it was opened with.
+#[derive(Debug, Clone)]
+pub struct Local {
+    pub urn: Urn,
+    pub remote_peer: PeerId,
+    pub path: PathBuf,
+}
+
+impl Local {
+    pub fn new(urn: Urn, remote: &ReadOnly) -> Self {
+        Self {
+            urn: Urn::new(urn.id),
+            remote_peer: *remote.peer_id(),
+            path: remote.path().to_path_buf(),
+        }
+    }
+
+    pub fn build<'a>(
+        &self,
+        storage: &'a Storage,
+    ) -> Result<Result<Fetcher<'a>, Info>, git2::Error> {
+        let url = Url::from_file_path(&self.path).map_err(|()| {
+            git2::Error::from_str(&format!(
+                "monorepo path {} is not absolute",
+                self.path.display()
+            ))
+        })?;
+        AnyUrl {
+            urn: self.urn.clone(),
+            remote_peer: self.remote_peer,
+            url,
+        }
+        .build(storage)
+    }
+}
+
+impl BuildFetcher for Local {
+    type Error = git2::Error;
+
+    fn urn(&self) -> &Urn {
+        &self.urn
+    }
+
+    fn remote_peer(&self) -> &PeerId {
+        &self.remote_peer
+    }
+
+    fn build_fetcher<'a>(
+        &self,
+        storage: &'a Storage,
+    ) -> Result<Result<Fetcher<'a>, Info>, Self::Error> {
+        self.build(storage)
+    }
+}
+
 pub mod error {
     use super::*;
     use thiserror::Error;
@@ -395,15 +483,31 @@ where
 mod imp {
     use super::*;
 
+    use crate::git::storage::glob::{Pattern as _, RefspecMatcher};
+
     pub struct Info {
         pub urn: Urn,
         pub remote_peer: PeerId,
         pub remote_heads: RemoteHeads,
+        /// How long connecting and listing the remote refs took.
+        pub ls_refs: Duration,
     }
 
     pub struct Fetcher<'a> {
         info: Info,
+        repo: &'a git2::Repository,
         remote: git2::Remote<'a>,
+        /// The tips updated by previous fetches of this fetcher, by their
+        /// local name.
+        ///
+        /// A replication fetches in several steps, which may ask for the same
+        /// refs (eg. the `rad/*` refs of the remote peer are both peeked at
+        /// and fetched along with the data). Refs which an earlier step
+        /// already updated to the tip the remote advertises are not asked
+        /// for again.
+        fetched: BTreeMap<RefLike, ext::Oid>,
+        /// The retries made since the last call to [`Fetcher::take_retries`].
+        retries: Vec<fetch::Retry>,
     }
 
     impl<'a> Fetcher<'a> {
@@ -420,58 +524,111 @@ mod imp {
                     return Err(e);
                 },
             };
-            remote.connect(git2::Direction::Fetch)?;
-            let remote_heads = remote
-                .list()?
-                .iter()
-                .filter_map(|remote_head| match remote_head.symref_target() {
-                    Some(_) => None,
-                    None => match RefLike::try_from(remote_head.name()) {
-                        Ok(refname) => Some((refname, remote_head.oid().into())),
-                        Err(e) => {
-                            tracing::warn!("invalid refname `{}`: {}", remote_head.name(), e);
-                            None
-                        },
-                    },
-                })
-                .collect::<BTreeMap<_, _>>()
-                .into();
+            let start = Instant::now();
+            let mut retries = Vec::new();
+            let remote_heads = advertised(&mut remote, &mut retries)?;
             let info = Info {
                 urn,
                 remote_peer,
                 remote_heads,
+                ls_refs: start.elapsed(),
             };
 
-            Ok(Self { info, remote })
+            Ok(Self {
+                info,
+                repo: storage.as_raw(),
+                remote,
+                fetched: BTreeMap::new(),
+                retries,
+            })
         }
 
         pub fn info(&self) -> &Info {
             &self.info
         }
 
+        /// Fetch `fetchspecs`, computing the refspecs from the refs advertised
+        /// when the fetcher was created.
+        ///
+        /// The remote end may have moved its refs in the meantime, eg. by
+        /// repacking, such that the pack received lacks some of the tips
+        /// asked for. In this case, the refs are listed again, and the fetch
+        /// retried once against the fresh advertisement, which is recorded as a
+        /// [`fetch::Retry::Renegotiation`].
+        ///
+        /// Likewise, if the remote end resets the stream the fetch is carried
+        /// over, the fetch is retried once on a new stream, which is recorded
+        /// as a [`fetch::Retry::StreamReset`].
         #[tracing::instrument(skip(self))]
         pub fn fetch(
             &mut self,
             fetchspecs: Fetchspecs<PeerId, Revision>,
+        ) -> Result<FetchResult, error::FetchError> {
+            let res = self.fetch_once(fetchspecs.clone());
+            let reset = take_stream_reset(&self.remote);
+            match res {
+                Err(error::FetchError::Git(e)) if self.lacks_tips(&e, &fetchspecs) => {
+                    tracing::warn!(err = %e, "Fetch: pack lacks advertised tips, renegotiating");
+                    self.retries.push(fetch::Retry::Renegotiation);
+                    self.info.remote_heads = advertised(&mut self.remote, &mut self.retries)?;
+                    self.fetch_once(fetchspecs)
+                },
+                Err(error::FetchError::Git(e)) if reset => {
+                    tracing::warn!(err = %e, "Fetch: stream reset, retrying");
+                    self.retries.push(fetch::Retry::StreamReset);
+                    self.fetch_once(fetchspecs)
+                },
+                res => res,
+            }
+        }
+
+        fn fetch_once(
+            &mut self,
+            fetchspecs: Fetchspecs<PeerId, Revision>,
         ) -> Result<FetchResult, error::FetchError> {
             let mut updated_tips = BTreeMap::new();
+            let mut moved = Vec::new();
+            let mut pack = None;
             {
                 let limit = fetchspecs.fetch_limit();
-                let refspecs = fetchspecs
-                    .refspecs(
-                        &self.info.urn,
-                        self.info.remote_peer,
-                        &self.info.remote_heads,
-                    )
+                let specs = fetchspecs.refspecs(
+                    &self.info.urn,
+                    self.info.remote_peer,
+                    &self.info.remote_heads,
+                );
+                let wanted = specs.len();
+                let refspecs = specs
                     .into_iter()
+                    .filter(|spec| !self.is_fetched(spec))
                     .map(|spec| spec.to_string())
                     .collect::<Vec<_>>();
                 tracing::trace!("{:?}", refspecs);
+                if refspecs.len() < wanted {
+                    tracing::debug!(
+                        skipped = wanted - refspecs.len(),
+                        "Fetch: skipping refs fetched in a previous step"
+                    );
+                }
+                if refspecs.is_empty() {
+                    let skipped = if wanted == 0 {
+                        fetch::Skipped::NoMatchingRefs
+                    } else {
+                        fetch::Skipped::WantNothing
+                    };
+                    tracing::debug!(?skipped, "Fetch: skipping");
+                    return Ok(FetchResult {
+                        updated_tips,
+                        updates: BTreeMap::new(),
+                        pack,
+                        skipped: Some(skipped),
+                    });
+                }
 
                 let mut callbacks = git2::RemoteCallbacks::new();
                 let mut excessive_transfer_bytes: Option<usize> = None;
                 callbacks.transfer_progress(|prog| {
                     let received_bytes = prog.received_bytes();
+                    pack = Some(fetch::PackStats::from(&prog));
                     tracing::trace!("Fetch: received {} bytes", received_bytes);
                     if received_bytes > limit {
                         tracing::error!("Fetch: exceeded {} bytes", limit);
@@ -492,7 +649,8 @@ mod imp {
                     tracing::debug!("Fetch: updating tip {}: {} -> {}", name, old, new);
                     match RefLike::try_from(name) {
                         Ok(refname) => {
-                            updated_tips.insert(refname, new.into());
+                            updated_tips.insert(refname.clone(), new.into());
+                            moved.push((refname, old, new));
                         },
                         Err(e) => tracing::warn!("invalid refname `{}`: {}", name, e),
                     }
@@ -525,10 +683,116 @@ mod imp {
                 }?;
             }
 
-            Ok(FetchResult { updated_tips })
+            let updates = moved
+                .into_iter()
+                .map(|(name, old, new)| (name, fetch::TipUpdate::classify(self.repo, old, new)))
+                .collect();
+            self.fetched
+                .extend(updated_tips.iter().map(|(name, oid)| (name.clone(), *oid)));
+            Ok(FetchResult {
+                updated_tips,
+                updates,
+                pack,
+                skipped: None,
+            })
+        }
+
+        /// Whether the fetch of `fetchspecs` failed with `e` because the pack
+        /// received lacked some of the tips the remote end advertised for them.
+        ///
+        /// Only errors about objects or refs are considered, in which case the
+        /// advertised tips are looked up in the object database.
+        fn lacks_tips(&self, e: &git2::Error, fetchspecs: &Fetchspecs<PeerId, Revision>) -> bool {
+            if !matches!(
+                e.class(),
+                git2::ErrorClass::Odb | git2::ErrorClass::Reference
+            ) {
+                return false;
+            }
+            let odb = match self.repo.odb() {
+                Ok(odb) => odb,
+                Err(_) => return false,
+            };
+            let wanted = fetchspecs
+                .refspecs(
+                    &self.info.urn,
+                    self.info.remote_peer,
+                    &self.info.remote_heads,
+                )
+                .into_iter()
+                .map(|spec| RefspecMatcher::from(spec.src().clone()))
+                .collect::<Vec<_>>();
+            self.info.remote_heads.iter().any(|(name, tip)| {
+                wanted.iter().any(|src| src.matches(name.as_str())) && !odb.exists((*tip).into())
+            })
+        }
+
+        pub fn take_retries(&mut self) -> Vec<fetch::Retry> {
+            std::mem::take(&mut self.retries)
+        }
+
+        /// Whether the ref `spec` asks for was already updated to the tip the
+        /// remote advertises by a previous fetch, and still points there.
+        /// Always `false` for globs.
+        fn is_fetched(&self, spec: &Fetchspec) -> bool {
+            let src = RefLike::try_from(spec.src().as_str());
+            let dst = RefLike::try_from(spec.dst().as_str());
+            match (src, dst) {
+                (Ok(src), Ok(dst)) => match self.info.remote_heads.get(&src) {
+                    Some(tip) => {
+                        self.fetched.get(&dst) == Some(tip)
+                            && self
+                                .repo
+                                .refname_to_id(dst.as_str())
+                                .map(|oid| ext::Oid::from(oid) == *tip)
+                                .unwrap_or(false)
+                    },
+                    None => false,
+                },
+                _ => false,
+            }
+        }
+    }
+
+    /// Connect to `remote`, and collect the refs it advertises.
+    ///
+    /// If the remote end resets the stream while advertising, the refs are
+    /// listed once more on a new stream, which is recorded in `retries`.
+    fn advertised(
+        remote: &mut git2::Remote,
+        retries: &mut Vec<fetch::Retry>,
+    ) -> Result<RemoteHeads, git2::Error> {
+        match ls_refs(remote) {
+            Err(e) if take_stream_reset(remote) => {
+                tracing::warn!(err = %e, "Listing refs: stream reset, retrying");
+                retries.push(fetch::Retry::StreamReset);
+                remote.disconnect()?;
+                ls_refs(remote)
+            },
+            res => res,
         }
     }
 
+    /// Whether the remote end reset the stream `remote` was talking to, see
+    /// [`transport::take_stream_reset`].
+    fn take_stream_reset(remote: &git2::Remote) -> bool {
+        remote
+            .url()
+            .map(transport::take_stream_reset)
+            .unwrap_or(false)
+    }
+
+    fn ls_refs(remote: &mut git2::Remote) -> Result<RemoteHeads, git2::Error> {
+        remote.connect(git2::Direction::Fetch)?;
+        Ok(RemoteHeads::from_advertised(
+            remote
+                .list()?
+                .iter()
+                .filter(|remote_head| remote_head.symref_target().is_none())
+                .map(|remote_head| (remote_head.name(), remote_head.oid().into())),
+        ))
+    }
+
     impl fetch::Fetcher for Fetcher<'_> {
         type Error = error::FetchError;
         type PeerId = PeerId;
@@ -552,5 +816,13 @@ mod imp {
         ) -> Result<FetchResult, Self::Error> {
             self.fetch(fetchspecs)
         }
+
+        fn ls_refs_duration(&self) -> Option<Duration> {
+            Some(self.info.ls_refs)
+        }
+
+        fn take_retries(&mut self) -> Vec<fetch::Retry> {
+            self.take_retries()
+        }
     }
 }
@@ -0,0 +1,96 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Keeping refs in the `packed-refs` file of the storage.
+//!
+//! Every ref of every peer replicated for a namespace is a loose file by
+//! default, which amounts to many thousands of tiny files on seeds hosting
+//! popular projects. Compacting moves all loose refs into the single
+//! `packed-refs` file.
+//!
+//! Compaction is delegated to `git pack-refs`, which takes the same lock
+//! libgit2 takes when updating `packed-refs`, replaces the file atomically,
+//! and only prunes loose refs which were not modified concurrently. Readers
+//! thus always see either the loose or the packed version of a ref.
+
+use std::{fs, io, path::Path, process::Command};
+
+use thiserror::Error;
+
+use super::{ReadOnly, Storage};
+use crate::{git::types::Namespace, identities::git::Urn};
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("`git pack-refs` failed with {0}: {1}")]
+    PackRefs(std::process::ExitStatus, String),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Count the loose refs of the namespace `urn`.
+pub fn loose_refs(storage: &ReadOnly, urn: &Urn) -> Result<usize, Error> {
+    let dir = storage
+        .path()
+        .join("refs/namespaces")
+        .join(Namespace::from(urn).to_string());
+    count_files(&dir)
+}
+
+fn count_files(dir: &Path) -> Result<usize, Error> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut count = 0;
+    for entry in entries {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            count += count_files(&entry.path())?;
+        } else if file_type.is_file() && !entry.file_name().to_string_lossy().ends_with(".lock") {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Move all loose refs of the storage into the `packed-refs` file.
+///
+/// Note that this is not confined to a single namespace.
+#[tracing::instrument(skip(storage))]
+pub fn compact(storage: &Storage) -> Result<(), Error> {
+    let output = Command::new("git")
+        .current_dir(storage.path())
+        .args(&["pack-refs", "--all", "--prune"])
+        .output()?;
+    if !output.status.success() {
+        return Err(Error::PackRefs(
+            output.status,
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// [`compact`] the storage if the namespace `urn` has more than `threshold`
+/// loose refs.
+///
+/// Returns `true` if the storage was compacted.
+pub fn compact_if(storage: &Storage, urn: &Urn, threshold: usize) -> Result<bool, Error> {
+    let loose = loose_refs(storage.read_only(), urn)?;
+    if loose > threshold {
+        tracing::debug!(loose, threshold, "compacting refs");
+        compact(storage)?;
+        Ok(true)
+    } else {
+        Ok(false)
+    }
+}
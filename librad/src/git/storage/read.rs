@@ -13,15 +13,21 @@ use git_ext::{self as ext, blob, is_not_found_err, RefLike, RefspecPattern};
 use std_ext::result::ResultExt as _;
 
 use crate::{
-    git::types::{reference, Many, One, Reference},
+    git::{
+        refs::{self, Refs},
+        types::{reference, Many, Namespace, One, Reference},
+    },
     identities::git::{Identities, Urn},
     paths::Paths,
     PeerId,
 };
 
 use super::{
+    commit_graph::{self, CommitGraph},
     config::{self, Config},
     glob::{self, Pattern},
+    stats::{self, Stats},
+    Verifications,
 };
 
 #[derive(Debug, Error)]
@@ -140,6 +146,7 @@ pub trait ReadOnlyStorage {
 pub struct ReadOnly {
     pub(super) backend: git2::Repository,
     pub(super) peer_id: PeerId,
+    pub(super) verifications: Verifications,
 }
 
 impl ReadOnly {
@@ -159,7 +166,22 @@ impl ReadOnly {
         crate::git::init();
         let backend = git2::Repository::open(paths.git_dir())?;
         let peer_id = Config::try_from(&backend)?.peer_id()?;
-        Ok(Self { backend, peer_id })
+        Ok(Self {
+            verifications: Verifications::new(backend.path()),
+            backend,
+            peer_id,
+        })
+    }
+
+    /// Open another handle to the same repository, eg. to use it on a
+    /// different thread.
+    pub fn reopen(&self) -> Result<Self, git2::Error> {
+        let backend = git2::Repository::open(self.path())?;
+        Ok(Self {
+            verifications: Verifications::new(backend.path()),
+            backend,
+            peer_id: self.peer_id,
+        })
     }
 
     pub fn peer_id(&self) -> &PeerId {
@@ -190,12 +212,65 @@ impl ReadOnly {
     /// 3. The SHA of the tag was not the same as the resolved reference
     /// 4. The `oid` was the [`zero`][`git2::Oid::zero`] SHA.
 
+    /// For each `(ancestor, descendant)` pair, determine whether `ancestor` is
+    /// `descendant`, or contained in its history.
+    ///
+    /// In contrast to checking each pair individually, all pairs are answered
+    /// in a single traversal, which is cut short using the generation numbers
+    /// of the commit-graph file if one was written. See [`commit_graph`].
+    pub fn ancestry_many(
+        &self,
+        pairs: &[(git2::Oid, git2::Oid)],
+    ) -> Result<Vec<bool>, commit_graph::Error> {
+        let graph = CommitGraph::load(self)?;
+        commit_graph::ancestry_many(&self.backend, graph.as_ref(), pairs)
+    }
+
+    /// Count the objects reachable from `tips`. See [`stats`].
+    pub fn count_reachable<I>(&self, tips: I) -> Result<Stats, Error>
+    where
+        I: IntoIterator<Item = git2::Oid>,
+    {
+        Ok(stats::count_reachable(&self.backend, tips)?)
+    }
+
+    /// Count the objects reachable from any ref of the namespace `urn`,
+    /// including the refs of tracked peers.
+    pub fn count_namespace(&self, urn: &Urn) -> Result<Stats, Error> {
+        let namespace = Namespace::from(urn);
+        let tips = self
+            .backend
+            .references_glob(&format!("refs/namespaces/{}/*", namespace))?
+            .filter_map(|r| r.ok().and_then(|r| r.target()))
+            .collect::<Vec<_>>();
+        self.count_reachable(tips)
+    }
+
     pub fn config(&self) -> Result<Config<PhantomData<!>>, Error> {
         Ok(Config::try_from(&self.backend)?)
     }
 
+    /// The signed refs of `peer` in the namespace `urn`, as of the
+    /// `rad/signed_refs` commit `at`. See [`Refs::load_at`].
+    pub fn refs_at<P>(
+        &self,
+        urn: &Urn,
+        peer: P,
+        at: ext::Oid,
+    ) -> Result<Option<Refs>, refs::stored::Error>
+    where
+        P: Into<Option<PeerId>> + Debug,
+    {
+        Refs::load_at(self, urn, peer, at)
+    }
+
+    /// The cache of identity verification results.
+    pub fn verifications(&self) -> &Verifications {
+        &self.verifications
+    }
+
     pub(in crate::git) fn identities<'a, T: 'a>(&'a self) -> Identities<'a, T> {
-        Identities::from(&self.backend)
+        Identities::from(&self.backend).with_cache(&self.verifications)
     }
 }
 
@@ -0,0 +1,278 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Expiring reflog entries acc. to a per-category [`Retention`].
+//!
+//! Refs created by this library are forced to have a reflog (see
+//! [`crate::git::types::Reference::create`]), which is useful to recover
+//! from bad updates, but grows without bounds on long-lived nodes.
+//! [`expire`] drops the entries which are no longer covered by the
+//! [`Keep`] rule of the category of the ref (eg. `heads` for both
+//! `refs/heads/main` and `refs/remotes/<peer>/heads/main` of a namespace).
+//!
+//! Since reflogs are re-created whenever such a ref is updated, a category
+//! can't be exempt from logging altogether: [`Keep::Nothing`] deletes the
+//! reflogs of the category each time [`expire`] runs, which is meant to be
+//! done periodically. Note that [`super::watch`] detects new namespaces by
+//! the creation of their reflogs, which is unaffected by expiring them
+//! later.
+
+use std::{
+    fmt,
+    iter::FromIterator,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use thiserror::Error;
+
+use super::Storage;
+use crate::git::types::RefsCategory;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+}
+
+/// Which entries of a reflog to keep.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Keep {
+    /// Keep all entries.
+    Everything,
+    /// Keep the given number of most recent entries.
+    Entries(usize),
+    /// Keep the entries younger than the given age.
+    Age(Duration),
+    /// Delete the reflog.
+    Nothing,
+}
+
+impl Default for Keep {
+    fn default() -> Self {
+        Self::Everything
+    }
+}
+
+impl fmt::Display for Keep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Everything => f.write_str("all"),
+            Self::Entries(n) => write!(f, "{}", n),
+            Self::Age(age) => write!(f, "{}d", age.as_secs() / DAY.as_secs()),
+            Self::Nothing => f.write_str("none"),
+        }
+    }
+}
+
+/// Parsed from `all`, `none`, a number of entries (eg. `100`), or a number
+/// of days (eg. `30d`).
+impl FromStr for Keep {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(Self::Everything),
+            "none" => Ok(Self::Nothing),
+            _ => match s.strip_suffix('d') {
+                Some(days) => days
+                    .parse::<u32>()
+                    .map(|days| Self::Age(DAY * days))
+                    .map_err(|_| format!("invalid number of days: `{}`", days)),
+                None => s
+                    .parse()
+                    .map(Self::Entries)
+                    .map_err(|_| format!("expected all, none, <entries> or <days>d, got `{}`", s)),
+            },
+        }
+    }
+}
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// The [`Keep`] rule for every [`RefsCategory`].
+///
+/// The [`Default`] keeps everything.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Retention {
+    pub heads: Keep,
+    pub rad: Keep,
+    pub tags: Keep,
+    pub notes: Keep,
+    pub cobs: Keep,
+}
+
+impl Retention {
+    pub fn get(&self, category: RefsCategory) -> Keep {
+        match category {
+            RefsCategory::Heads => self.heads,
+            RefsCategory::Rad => self.rad,
+            RefsCategory::Tags => self.tags,
+            RefsCategory::Notes => self.notes,
+            RefsCategory::Cobs => self.cobs,
+        }
+    }
+
+    pub fn set(&mut self, category: RefsCategory, keep: Keep) {
+        match category {
+            RefsCategory::Heads => self.heads = keep,
+            RefsCategory::Rad => self.rad = keep,
+            RefsCategory::Tags => self.tags = keep,
+            RefsCategory::Notes => self.notes = keep,
+            RefsCategory::Cobs => self.cobs = keep,
+        }
+    }
+
+    fn is_everything(&self) -> bool {
+        [self.heads, self.rad, self.tags, self.notes, self.cobs]
+            .iter()
+            .all(|keep| *keep == Keep::Everything)
+    }
+}
+
+/// A [`Keep`] rule for one [`RefsCategory`], parsed from
+/// `<category>=<keep>`, eg. `heads=30d`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Rule {
+    pub category: RefsCategory,
+    pub keep: Keep,
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (category, keep) = s
+            .split_once('=')
+            .ok_or_else(|| "expected <category>=<keep>".to_string())?;
+        let category = match category {
+            "heads" => RefsCategory::Heads,
+            "rad" => RefsCategory::Rad,
+            "tags" => RefsCategory::Tags,
+            "notes" => RefsCategory::Notes,
+            "cobs" => RefsCategory::Cobs,
+            other => return Err(format!("unknown ref category `{}`", other)),
+        };
+        Ok(Self {
+            category,
+            keep: keep.parse()?,
+        })
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.category, self.keep)
+    }
+}
+
+impl<'a> FromIterator<&'a Rule> for Retention {
+    fn from_iter<I>(rules: I) -> Self
+    where
+        I: IntoIterator<Item = &'a Rule>,
+    {
+        let mut retention = Self::default();
+        for rule in rules {
+            retention.set(rule.category, rule.keep);
+        }
+        retention
+    }
+}
+
+/// The outcome of [`expire`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Expired {
+    /// The number of reflog entries removed.
+    pub entries: usize,
+    /// The number of reflogs deleted as per [`Keep::Nothing`].
+    pub reflogs: usize,
+}
+
+/// Expire the reflog entries of all namespaced refs in `storage` acc. to
+/// `retention`.
+pub fn expire(storage: &Storage, retention: &Retention) -> Result<Expired, Error> {
+    let mut expired = Expired::default();
+    if retention.is_everything() {
+        return Ok(expired);
+    }
+
+    let repo = storage.as_raw();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default();
+
+    let mut names = Vec::new();
+    for name in repo.references_glob("refs/namespaces/*")?.names() {
+        names.push(name?.to_owned());
+    }
+    for name in names {
+        let keep = match category(&name) {
+            Some(category) => retention.get(category),
+            None => continue,
+        };
+        match keep {
+            Keep::Everything => {},
+            Keep::Nothing => {
+                if !repo.reflog(&name)?.is_empty() {
+                    repo.reflog_delete(&name)?;
+                    expired.reflogs += 1;
+                }
+            },
+            Keep::Entries(n) => {
+                let mut reflog = repo.reflog(&name)?;
+                let len = reflog.len();
+                if len > n {
+                    // Entry 0 is the most recent one
+                    for i in (n..len).rev() {
+                        reflog.remove(i, false)?;
+                    }
+                    reflog.write()?;
+                    expired.entries += len - n;
+                }
+            },
+            Keep::Age(age) => {
+                let cutoff = now - age.as_secs() as i64;
+                let mut reflog = repo.reflog(&name)?;
+                let stale = reflog
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, entry)| entry.committer().when().seconds() < cutoff)
+                    .map(|(i, _)| i)
+                    .collect::<Vec<_>>();
+                if !stale.is_empty() {
+                    for i in stale.iter().rev() {
+                        reflog.remove(*i, false)?;
+                    }
+                    reflog.write()?;
+                    expired.entries += stale.len();
+                }
+            },
+        }
+    }
+
+    Ok(expired)
+}
+
+/// The category of a namespaced ref, ie. `heads` for
+/// `refs/namespaces/<urn>/refs/heads/main` and
+/// `refs/namespaces/<urn>/refs/remotes/<peer>/heads/main`.
+fn category(name: &str) -> Option<RefsCategory> {
+    let mut components = name.split('/');
+    // refs/namespaces/<urn>/refs/
+    let mut next = components.nth(4)?;
+    if next == "remotes" {
+        next = components.nth(1)?;
+    }
+    match next {
+        "heads" => Some(RefsCategory::Heads),
+        "rad" => Some(RefsCategory::Rad),
+        "tags" => Some(RefsCategory::Tags),
+        "notes" => Some(RefsCategory::Notes),
+        "cobs" => Some(RefsCategory::Cobs),
+        _ => None,
+    }
+}
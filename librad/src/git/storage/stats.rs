@@ -0,0 +1,112 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Statistics about the objects reachable from a set of tips.
+//!
+//! These are intended to inform decisions about how much space a namespace
+//! consumes, eg. for quotas or garbage collection. Note that objects are
+//! shared between namespaces, so the statistics of several namespaces can
+//! not be summed up to obtain the size of the storage.
+
+use std::collections::{HashMap, HashSet};
+
+use git_ext::is_not_found_err;
+use serde::Serialize;
+
+/// The result of [`super::ReadOnly::count_reachable`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Stats {
+    /// The number of distinct objects.
+    pub objects: u64,
+    /// The uncompressed size of all objects, in bytes.
+    pub bytes: u64,
+    /// The number of commits on the longest path through the history.
+    pub max_depth: u64,
+}
+
+/// Count the objects reachable from `tips`. Objects which are missing from
+/// the object database are skipped.
+pub(super) fn count_reachable(
+    repo: &git2::Repository,
+    tips: impl IntoIterator<Item = git2::Oid>,
+) -> Result<Stats, git2::Error> {
+    let odb = repo.odb()?;
+    let mut stats = Stats::default();
+    let mut seen = HashSet::new();
+    let mut parents = HashMap::new();
+
+    let mut queue = tips.into_iter().collect::<Vec<_>>();
+    while let Some(oid) = queue.pop() {
+        if !seen.insert(oid) {
+            continue;
+        }
+        let (size, kind) = match odb.read_header(oid) {
+            Ok(header) => header,
+            Err(e) if is_not_found_err(&e) => continue,
+            Err(e) => return Err(e),
+        };
+        stats.objects += 1;
+        stats.bytes += size as u64;
+
+        match kind {
+            git2::ObjectType::Commit => {
+                let commit = repo.find_commit(oid)?;
+                queue.push(commit.tree_id());
+                queue.extend(commit.parent_ids());
+                parents.insert(oid, commit.parent_ids().collect::<Vec<_>>());
+            },
+            git2::ObjectType::Tree => {
+                let tree = repo.find_tree(oid)?;
+                // Submodule commits are not part of this repository
+                queue.extend(
+                    tree.iter()
+                        .filter(|entry| entry.kind() != Some(git2::ObjectType::Commit))
+                        .map(|entry| entry.id()),
+                );
+            },
+            git2::ObjectType::Tag => queue.push(repo.find_tag(oid)?.target_id()),
+            _ => {},
+        }
+    }
+    stats.max_depth = longest_path(&parents);
+
+    Ok(stats)
+}
+
+/// The number of commits on the longest path through the commit graph given
+/// as `parents`.
+fn longest_path(parents: &HashMap<git2::Oid, Vec<git2::Oid>>) -> u64 {
+    let mut depths: HashMap<git2::Oid, u64> = HashMap::with_capacity(parents.len());
+    for start in parents.keys() {
+        let mut stack = vec![*start];
+        while let Some(oid) = stack.last().copied() {
+            if depths.contains_key(&oid) {
+                stack.pop();
+                continue;
+            }
+            let of = &parents[&oid];
+            let pending = of
+                .iter()
+                .filter(|parent| parents.contains_key(parent) && !depths.contains_key(parent))
+                .copied()
+                .collect::<Vec<_>>();
+            if pending.is_empty() {
+                let depth = 1 + of
+                    .iter()
+                    .filter_map(|parent| depths.get(parent))
+                    .max()
+                    .copied()
+                    .unwrap_or(0);
+                depths.insert(oid, depth);
+                stack.pop();
+            } else {
+                stack.extend(pending);
+            }
+        }
+    }
+
+    depths.values().max().copied().unwrap_or(0)
+}
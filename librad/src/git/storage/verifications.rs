@@ -0,0 +1,84 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! On-disk cache of identity verification results.
+//!
+//! The cache lives in the git directory of the monorepo, and is consulted by
+//! all identity verifications performed through [`super::ReadOnly`] and
+//! [`super::Storage`]. Entries refer to commits by their content id, so an
+//! entry is ignored if the objects it refers to are gone. Whenever the
+//! storage is repaired such that previously verified objects may have changed
+//! or been removed, the cache should nevertheless be invalidated using
+//! [`Verifications::clear`].
+
+use std::{
+    fs,
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::identities::git::{Checkpoint, VerificationCache};
+
+const CACHE_DIR: &str = "identities/verified";
+
+pub struct Verifications {
+    dir: PathBuf,
+}
+
+impl Verifications {
+    pub(super) fn new(git_dir: &Path) -> Self {
+        Self {
+            dir: git_dir.join(CACHE_DIR),
+        }
+    }
+
+    /// Remove all cached verification results.
+    pub fn clear(&self) -> io::Result<()> {
+        match fs::remove_dir_all(&self.dir) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn entry_path(&self, key: git2::Oid) -> PathBuf {
+        let key = key.to_string();
+        let (prefix, rest) = key.split_at(2);
+        self.dir.join(prefix).join(rest)
+    }
+
+    fn read(&self, key: git2::Oid) -> io::Result<Option<Checkpoint>> {
+        match fs::read(self.entry_path(key)) {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn write(&self, key: git2::Oid, result: &Checkpoint) -> io::Result<()> {
+        let path = self.entry_path(key);
+        let dir = path.parent().expect("entry path has a parent");
+        fs::create_dir_all(dir)?;
+        // Write to a temporary file first, so concurrent readers never observe
+        // a partially written entry.
+        let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+        serde_json::to_writer(&mut tmp, result)?;
+        tmp.persist(path).map(|_| ()).map_err(|e| e.error)
+    }
+}
+
+impl VerificationCache for Verifications {
+    fn get(&self, key: git2::Oid) -> Option<Checkpoint> {
+        self.read(key).unwrap_or_else(|e| {
+            tracing::warn!(err = %e, key = %key, "failed to read verification cache");
+            None
+        })
+    }
+
+    fn put(&self, key: git2::Oid, result: Checkpoint) {
+        if let Err(e) = self.write(key, &result) {
+            tracing::warn!(err = %e, key = %key, "failed to write verification cache");
+        }
+    }
+}
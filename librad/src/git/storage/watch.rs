@@ -4,16 +4,20 @@
 // Linking Exception. For full terms see the included LICENSE file.
 
 use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
     fs,
     io,
     path::{Path, PathBuf},
     sync::{mpsc, Arc},
 };
 
+use git_ext as ext;
 use notify::Watcher as _;
 use thiserror::Error;
 
-use super::Storage;
+use super::{glob, read, ReadOnly, ReadOnlyStorage as _, Storage};
+use crate::identities::git::Urn;
 
 #[derive(Debug, Error)]
 #[non_exhaustive]
@@ -26,6 +30,9 @@ pub enum Error {
 
     #[error(transparent)]
     Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Store(#[from] read::Error),
 }
 
 /// A handle to a filesystem watcher.
@@ -135,3 +142,70 @@ impl<'a> Watch<'a> {
         Ok((Watcher(Arc::new(watcher)), rx))
     }
 }
+
+/// A change to a ref within a namespace, as determined by comparing two
+/// [`snapshot`]s.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RefUpdate {
+    /// The name of the ref, relative to the namespace (eg. `refs/heads/main`).
+    pub name: ext::RefLike,
+    /// The previous target, or `None` if the ref was created.
+    pub old: Option<ext::Oid>,
+    /// The new target, or `None` if the ref was deleted.
+    pub new: Option<ext::Oid>,
+}
+
+/// The direct refs within the namespace of `urn`, and their targets.
+///
+/// Symbolic refs are skipped.
+pub fn snapshot(storage: &ReadOnly, urn: &Urn) -> Result<BTreeMap<ext::RefLike, ext::Oid>, Error> {
+    let urn = urn.clone().with_path(None);
+    let prefix = format!("refs/namespaces/{}/", urn.encode_id());
+    let pattern = reflike!("refs/namespaces")
+        .join(&urn)
+        .with_pattern_suffix(refspec_pattern!("*"));
+
+    let mut refs = BTreeMap::new();
+    for r in storage.references_glob(glob::RefspecMatcher::from(pattern))? {
+        let r = r?;
+        let (name, target) = match (r.name(), r.target()) {
+            (Some(name), Some(target)) => (name, target),
+            _ => continue,
+        };
+        if let Some(name) = name
+            .strip_prefix(&prefix)
+            .and_then(|name| ext::RefLike::try_from(name).ok())
+        {
+            refs.insert(name, target.into());
+        }
+    }
+
+    Ok(refs)
+}
+
+/// Compute the [`RefUpdate`]s turning `old` into `new`.
+pub fn diff(
+    old: &BTreeMap<ext::RefLike, ext::Oid>,
+    new: &BTreeMap<ext::RefLike, ext::Oid>,
+) -> Vec<RefUpdate> {
+    let mut updates = new
+        .iter()
+        .filter_map(|(name, target)| match old.get(name) {
+            Some(prev) if prev == target => None,
+            prev => Some(RefUpdate {
+                name: name.clone(),
+                old: prev.copied(),
+                new: Some(*target),
+            }),
+        })
+        .collect::<Vec<_>>();
+    updates.extend(old.iter().filter(|(name, _)| !new.contains_key(*name)).map(
+        |(name, target)| RefUpdate {
+            name: name.clone(),
+            old: Some(*target),
+            new: None,
+        },
+    ));
+
+    updates
+}
@@ -3,13 +3,14 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::{convert::TryFrom, ops::Range, str::FromStr};
+use std::{collections::BTreeSet, convert::TryFrom, ops::Range, str::FromStr};
 
 use git_ext::{is_exists_err, is_not_found_err};
 use std_ext::result::ResultExt as _;
 use thiserror::Error;
 
 use super::{
+    cobs,
     p2p::url::GitUrlRef,
     storage::{self, glob, ReadOnlyStorage, Storage},
 };
@@ -23,6 +24,9 @@ pub enum Error {
     #[error("can't track oneself")]
     SelfReferential,
 
+    #[error("invalid collaborative object typename `{0}`")]
+    InvalidTypename(String),
+
     #[error(transparent)]
     Store(#[from] storage::Error),
 
@@ -74,6 +78,51 @@ pub fn track(storage: &Storage, urn: &Urn, peer: PeerId) -> Result<bool, Error>
     Ok(was_created)
 }
 
+/// Track all of the given peers, each in the context of the accompanying
+/// `urn`.
+///
+/// This is equivalent to calling [`track`] for every entry, but reads the
+/// config of `storage` only once to find the tracking relationships which
+/// exist already, so only the missing ones are written. The entries which
+/// didn't exist before, and were thus created by this call, are returned.
+///
+/// # Errors
+///
+/// Attempting to track oneself (ie. [`Storage::peer_id`]) is an error, in
+/// which case nothing is written.
+#[tracing::instrument(skip(storage, entries))]
+pub fn track_batch<I>(storage: &Storage, entries: I) -> Result<BTreeSet<(Urn, PeerId)>, Error>
+where
+    I: IntoIterator<Item = (Urn, PeerId)>,
+{
+    let local_peer = storage.peer_id();
+    let config = storage.as_raw().config()?.snapshot()?;
+
+    let mut missing = BTreeSet::new();
+    for (urn, peer) in entries {
+        if &peer == local_peer {
+            return Err(Error::SelfReferential);
+        }
+        let urn = urn.with_path(None);
+        let exists = config
+            .get_string(&format!("remote.{}.url", tracking_remote_name(&urn, &peer)))
+            .map(|_| true)
+            .or_matches::<Error, _, _>(is_not_found_err, || Ok(false))?;
+        if !exists {
+            missing.insert((urn, peer));
+        }
+    }
+
+    let mut created = BTreeSet::new();
+    for (urn, peer) in missing {
+        if track(storage, &urn, peer)? {
+            created.insert((urn, peer));
+        }
+    }
+
+    Ok(created)
+}
+
 /// Remove the tracking of `peer` in the context of `urn`.
 ///
 /// `true` is returned if the tracking relationship existed and was removed as a
@@ -86,8 +135,38 @@ pub fn track(storage: &Storage, urn: &Urn, peer: PeerId) -> Result<bool, Error>
 /// (yet) atomic, this may fail, leaving "dangling" refs in the storage. It is
 /// safe to call this function repeatedly, so as to ensure all remote tracking
 /// branches have been pruned.
-#[tracing::instrument(skip(storage))]
 pub fn untrack(storage: &Storage, urn: &Urn, peer: PeerId) -> Result<bool, Error> {
+    untrack_with(storage, urn, peer, UntrackArgs::default())
+}
+
+/// Arguments to [`untrack_with`].
+#[derive(Clone, Copy, Debug)]
+pub struct UntrackArgs {
+    /// Whether to remove the remote branches associated with the untracked
+    /// peer.
+    ///
+    /// Default: `true`
+    pub prune: bool,
+}
+
+impl Default for UntrackArgs {
+    fn default() -> Self {
+        Self { prune: true }
+    }
+}
+
+/// Like [`untrack`], but allows to retain the remote branches of `peer` by
+/// setting [`UntrackArgs::prune`] to `false`.
+///
+/// Note that retained remote branches will no longer be updated, and may be
+/// removed by a subsequent replication of `urn`.
+#[tracing::instrument(skip(storage))]
+pub fn untrack_with(
+    storage: &Storage,
+    urn: &Urn,
+    peer: PeerId,
+    args: UntrackArgs,
+) -> Result<bool, Error> {
     let remote_name = tracking_remote_name(urn, &peer);
     let was_removed = storage
         .as_raw()
@@ -95,6 +174,10 @@ pub fn untrack(storage: &Storage, urn: &Urn, peer: PeerId) -> Result<bool, Error
         .map(|()| true)
         .or_matches::<Error, _, _>(is_not_found_err, || Ok(false))?;
 
+    if !args.prune {
+        return Ok(was_removed);
+    }
+
     // Prune all remote branches
     let prune = storage.references_glob(glob::RefspecMatcher::from(
         reflike!("refs/namespaces")
@@ -120,6 +203,82 @@ where
     storage.as_ref().has_remote(urn, peer).map_err(Error::from)
 }
 
+/// The `typename` to pass to [`exclude_cobs`] and [`include_cobs`] in order to
+/// stop, or resume, fetching all collaborative objects of a peer.
+pub const ALL_COBS: &str = "*";
+
+/// Stop fetching the collaborative objects of type `typename` (ie.
+/// `refs/cobs/<typename>/*`) of `peer` in the context of `urn`.
+///
+/// `true` is returned if `typename` was not excluded before. Exclusions are
+/// stored alongside the tracking relationship, and are thus removed when
+/// `peer` is untracked.
+///
+/// # Errors
+///
+/// `typename` must satisfy [`cobs::is_valid_typename`], or be [`ALL_COBS`].
+#[tracing::instrument(skip(storage))]
+pub fn exclude_cobs(
+    storage: &Storage,
+    urn: &Urn,
+    peer: PeerId,
+    typename: &str,
+) -> Result<bool, Error> {
+    validate_typename(typename)?;
+    if excluded_cobs(storage, urn, peer)?.contains(typename) {
+        return Ok(false);
+    }
+
+    let mut config = storage::Config::try_from(storage)?;
+    config.as_raw_mut().set_multivar(
+        &cobs_exclude_key(urn, &peer),
+        &typename_regex(typename),
+        typename,
+    )?;
+
+    Ok(true)
+}
+
+/// Revert [`exclude_cobs`], ie. resume fetching the collaborative objects of
+/// type `typename` of `peer` in the context of `urn`.
+///
+/// `true` is returned if `typename` was excluded before.
+#[tracing::instrument(skip(storage))]
+pub fn include_cobs(
+    storage: &Storage,
+    urn: &Urn,
+    peer: PeerId,
+    typename: &str,
+) -> Result<bool, Error> {
+    validate_typename(typename)?;
+    let mut config = storage::Config::try_from(storage)?;
+    config
+        .as_raw_mut()
+        .remove_multivar(&cobs_exclude_key(urn, &peer), &typename_regex(typename))
+        .map(|()| true)
+        .or_matches::<Error, _, _>(is_not_found_err, || Ok(false))
+}
+
+/// The typenames of collaborative objects which should not be fetched from
+/// `peer` in the context of `urn`.
+///
+/// See [`exclude_cobs`].
+pub fn excluded_cobs<S>(storage: &S, urn: &Urn, peer: PeerId) -> Result<BTreeSet<String>, Error>
+where
+    S: AsRef<storage::Storage>,
+{
+    let config = storage.as_ref().as_raw().config()?.snapshot()?;
+    let entries = config.multivar(&cobs_exclude_key(urn, &peer), None)?;
+    let mut excluded = BTreeSet::new();
+    for entry in &entries {
+        if let Some(typename) = entry?.value() {
+            excluded.insert(typename.to_owned());
+        }
+    }
+
+    Ok(excluded)
+}
+
 /// Obtain an iterator over the 1st degree tracked peers in the context of
 /// `urn`.
 pub fn tracked<S>(storage: &S, urn: &Urn) -> Result<Tracked, Error>
@@ -178,3 +337,20 @@ impl Iterator for Tracked {
 fn tracking_remote_name(urn: &Urn, peer: &PeerId) -> String {
     format!("{}/{}", urn.encode_id(), peer)
 }
+
+fn cobs_exclude_key(urn: &Urn, peer: &PeerId) -> String {
+    format!("remote.{}.cobsExclude", tracking_remote_name(urn, peer))
+}
+
+fn validate_typename(typename: &str) -> Result<(), Error> {
+    if typename == ALL_COBS || cobs::is_valid_typename(typename) {
+        Ok(())
+    } else {
+        Err(Error::InvalidTypename(typename.to_owned()))
+    }
+}
+
+/// Regex matching exactly `typename`, assuming it passed [`validate_typename`].
+fn typename_regex(typename: &str) -> String {
+    format!("^{}$", typename.replace('.', "\\.").replace('*', "\\*"))
+}
@@ -21,6 +21,8 @@ pub use reference::{
     RefsCategory,
     Single,
     SymbolicRef,
+    SymrefPolicy,
+    TargetUpdate,
 };
 pub use refspec::{Fetchspec, Pushspec, Refspec};
 pub use remote::Remote;
@@ -26,12 +26,13 @@ pub type Many = ext::RefspecPattern;
 /// Alias for [`Many`].
 pub type Multiple = Many;
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum RefsCategory {
     Heads,
     Rad,
     Tags,
     Notes,
+    Cobs,
 }
 
 impl RefsCategory {
@@ -41,6 +42,7 @@ impl RefsCategory {
             "rad" => Some(Self::Rad),
             "tags" => Some(Self::Tags),
             "notes" => Some(Self::Notes),
+            "cobs" => Some(Self::Cobs),
             _ => None,
         }
     }
@@ -53,6 +55,7 @@ impl Display for RefsCategory {
             Self::Rad => f.write_str("rad"),
             Self::Tags => f.write_str("tags"),
             Self::Notes => f.write_str("notes"),
+            Self::Cobs => f.write_str("cobs"),
         }
     }
 }
@@ -422,6 +425,17 @@ impl<N, R> Reference<N, R, Many> {
             namespace: namespace.into(),
         }
     }
+
+    /// Build a reference that points to:
+    ///     * `refs[/namespaces/<namespace>]/refs[/remotes/<remote>]/cobs/*`
+    pub fn cobs(namespace: impl Into<Option<N>>, remote: impl Into<Option<R>>) -> Self {
+        Self {
+            remote: remote.into(),
+            category: RefsCategory::Cobs,
+            name: refspec_pattern!("*"),
+            namespace: namespace.into(),
+        }
+    }
 }
 
 impl<N, R> Display for Reference<N, R, Many>
@@ -567,6 +581,51 @@ pub struct SymbolicRef<S, T> {
     pub force: Force,
 }
 
+/// What to do with the target of a [`SymbolicRef`] if it doesn't point to
+/// the tip given to [`SymbolicRef::create_with`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TargetUpdate {
+    /// Leave the target as it is.
+    Never,
+    /// Move the target to the tip if that is a fast-forward, and fail
+    /// otherwise.
+    FastForward,
+    /// Move the target to the tip unconditionally.
+    Force,
+}
+
+/// Policy determining how strict [`SymbolicRef::create_with`] is about the
+/// target of the symbolic ref.
+///
+/// The [`Default`] corresponds to [`SymbolicRef::create`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SymrefPolicy {
+    /// Whether the target may itself be a symbolic ref, ie. whether a chain
+    /// of symbolic refs may be created.
+    ///
+    /// Default: `true`
+    pub allow_chain: bool,
+    /// Whether a non-existent target is created, pointing to the tip. If
+    /// `false`, or no tip is given, a non-existent target is an error.
+    ///
+    /// Default: `false`
+    pub create_target: bool,
+    /// What to do if the target doesn't point to the tip.
+    ///
+    /// Default: [`TargetUpdate::Never`]
+    pub update_target: TargetUpdate,
+}
+
+impl Default for SymrefPolicy {
+    fn default() -> Self {
+        Self {
+            allow_chain: true,
+            create_target: false,
+            update_target: TargetUpdate::Never,
+        }
+    }
+}
+
 impl<S, T> SymbolicRef<S, T> {
     /// Create a symbolic reference of `target`, where the `source` is the newly
     /// created reference.
@@ -579,6 +638,33 @@ impl<S, T> SymbolicRef<S, T> {
     ///     already exists. Note that this will not be the case if `Force::True`
     ///     is passed.
     pub fn create<'a>(&self, repo: &'a git2::Repository) -> Result<git2::Reference<'a>, git2::Error>
+    where
+        for<'b> &'b S: Into<ext::RefLike>,
+        for<'b> &'b T: Into<ext::RefLike>,
+    {
+        self.create_with(repo, None, SymrefPolicy::default())
+    }
+
+    /// Like [`SymbolicRef::create`], but handles the `target` acc. to
+    /// `policy`, possibly creating or moving it to `tip`.
+    ///
+    /// If the `target` is a chain of symbolic refs, the direct ref at its end
+    /// is the one created or moved.
+    ///
+    /// # Errors
+    ///
+    /// In addition to the errors of [`SymbolicRef::create`]:
+    ///
+    ///   * If the `target` is a symbolic ref, and [`SymrefPolicy::allow_chain`]
+    ///     is `false`.
+    ///   * If moving the `target` to `tip` is not a fast-forward, and
+    ///     [`SymrefPolicy::update_target`] is [`TargetUpdate::FastForward`].
+    pub fn create_with<'a>(
+        &self,
+        repo: &'a git2::Repository,
+        tip: Option<git2::Oid>,
+        policy: SymrefPolicy,
+    ) -> Result<git2::Reference<'a>, git2::Error>
     where
         for<'b> &'b S: Into<ext::RefLike>,
         for<'b> &'b T: Into<ext::RefLike>,
@@ -589,7 +675,54 @@ impl<S, T> SymbolicRef<S, T> {
         let reflog_msg = &format!("creating symbolic ref {} -> {}", source, target);
         tracing::debug!("{}", reflog_msg);
 
-        let _ = repo.refname_to_id(target.as_str())?;
+        let existing = match repo.find_reference(target.as_str()) {
+            Ok(r) => Some(r),
+            Err(e) if ext::is_not_found_err(&e) => None,
+            Err(e) => return Err(e),
+        };
+        match (existing, tip) {
+            (None, Some(tip)) if policy.create_target => {
+                repo.reference_ensure_log(target.as_str())?;
+                repo.reference(target.as_str(), tip, false, reflog_msg)?;
+            },
+            (None, _) => {
+                return Err(git2::Error::new(
+                    git2::ErrorCode::NotFound,
+                    git2::ErrorClass::Reference,
+                    format!("target {} of symbolic ref {} not found", target, source),
+                ))
+            },
+            (Some(r), tip) => {
+                if r.kind() == Some(git2::ReferenceType::Symbolic) && !policy.allow_chain {
+                    return Err(git2::Error::from_str(&format!(
+                        "target {} of symbolic ref {} is itself a symbolic ref",
+                        target, source
+                    )));
+                }
+                let mut direct = r.resolve()?;
+                let current = direct.target();
+                if let (Some(tip), Some(current)) = (tip, current) {
+                    let update = match policy.update_target {
+                        _ if tip == current => false,
+                        TargetUpdate::Never => false,
+                        TargetUpdate::Force => true,
+                        TargetUpdate::FastForward => {
+                            if !repo.graph_descendant_of(tip, current)? {
+                                return Err(git2::Error::from_str(&format!(
+                                    "target {} of symbolic ref {} cannot be fast-forwarded from {} to {}",
+                                    target, source, current, tip
+                                )));
+                            }
+                            true
+                        },
+                    };
+                    if update {
+                        direct.set_target(tip, reflog_msg)?;
+                    }
+                }
+            },
+        }
+
         repo.reference_ensure_log(source.as_str())?;
         repo.reference_symbolic(
             source.as_str(),
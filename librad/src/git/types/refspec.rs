@@ -139,6 +139,37 @@ where
     }
 }
 
+impl Fetchspec {
+    /// The remote side of the spec.
+    pub fn src(&self) -> &ext::RefspecPattern {
+        &self.0.src
+    }
+
+    /// The local side of the spec.
+    pub fn dst(&self) -> &ext::RefspecPattern {
+        &self.0.dst
+    }
+
+    /// The local ref the remote ref `name` is fetched into, or `None` if
+    /// `name` doesn't match [`Fetchspec::src`].
+    ///
+    /// Like git, a `*` in the pattern matches any part of `name`, including
+    /// `/`, which is substituted for the `*` of [`Fetchspec::dst`].
+    pub fn dst_of(&self, name: &ext::RefLike) -> Option<ext::RefLike> {
+        let (src, dst) = (self.src().as_str(), self.dst().as_str());
+        match (src.split_once('*'), dst.split_once('*')) {
+            (None, _) => (src == name.as_str())
+                .then(|| ext::RefLike::try_from(dst).ok())
+                .flatten(),
+            (Some((prefix, suffix)), Some((dst_prefix, dst_suffix))) => {
+                let matched = name.as_str().strip_prefix(prefix)?.strip_suffix(suffix)?;
+                ext::RefLike::try_from(format!("{}{}{}", dst_prefix, matched, dst_suffix)).ok()
+            },
+            (Some(_), None) => None,
+        }
+    }
+}
+
 impl TryFrom<&str> for Fetchspec {
     type Error = ext::reference::name::Error;
 
@@ -5,6 +5,8 @@
 
 use std::{borrow::Cow, fmt::Display, str::FromStr};
 
+pub mod addrbook;
+pub mod capability;
 pub mod codec;
 pub mod connection;
 pub mod discovery;
@@ -12,6 +14,7 @@ pub mod peer;
 pub mod protocol;
 pub mod quic;
 pub mod tls;
+pub mod tofu;
 pub mod upgrade;
 pub mod x509;
 
@@ -0,0 +1,198 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Persistent store of the peers seen on the network.
+//!
+//! The address book records, per peer, the addresses it was reachable at, the
+//! [`Network`]s it was seen on, the RPC [`Versions`] it advertised, and when
+//! it was last seen. It is stored alongside the monorepo of a profile, so a
+//! node can re-establish connectivity after a restart without having to rely
+//! on bootstrap nodes, see [`AddrBook::known`].
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::{
+    protocol::{version::Versions, PartialPeerInfo},
+    Network,
+};
+use crate::{paths::Paths, PeerId};
+
+const ADDRBOOK_FILE: &str = "addrbook.json";
+
+/// The maximum number of addresses retained per peer. The most recently seen
+/// addresses are retained.
+const MAX_ADDRS: usize = 16;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Entry {
+    pub peer_id: PeerId,
+    /// The addresses the peer was seen at, most recent first.
+    pub addrs: Vec<SocketAddr>,
+    /// The names of the networks the peer was seen on, where `None` denotes
+    /// [`Network::Main`].
+    pub networks: BTreeSet<Option<String>>,
+    /// The RPC versions last advertised by the peer, if any.
+    pub versions: Option<Versions>,
+    /// Time the peer was last seen, in seconds since the UNIX epoch.
+    pub last_seen: u64,
+}
+
+/// The persistent store of known peers.
+pub struct AddrBook {
+    path: PathBuf,
+    entries: BTreeMap<PeerId, Entry>,
+}
+
+impl AddrBook {
+    /// The location of the address book of the profile with the given
+    /// `paths`.
+    pub fn path(paths: &Paths) -> PathBuf {
+        paths.git_dir().join(ADDRBOOK_FILE)
+    }
+
+    /// Load the address book of the profile with the given `paths`. If it
+    /// doesn't exist yet, it is empty.
+    pub fn open(paths: &Paths) -> Result<Self, Error> {
+        Self::open_at(Self::path(paths))
+    }
+
+    pub fn open_at(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let entries = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice::<Vec<Entry>>(&bytes)?
+                .into_iter()
+                .map(|entry| (entry.peer_id, entry))
+                .collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self { path, entries })
+    }
+
+    /// Write the address book back to disk.
+    ///
+    /// The file is replaced atomically, so concurrent readers see either the
+    /// previous or the new contents.
+    pub fn save(&self) -> Result<(), Error> {
+        let dir = self.path.parent().unwrap_or_else(|| Path::new("."));
+        let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+        serde_json::to_writer_pretty(&mut tmp, &self.entries.values().collect::<Vec<_>>())?;
+        tmp.persist(&self.path).map_err(|e| e.error)?;
+
+        Ok(())
+    }
+
+    pub fn get(&self, peer: &PeerId) -> Option<&Entry> {
+        self.entries.get(peer)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Record that the peer described by `info` was seen on `network`.
+    pub fn observe(&mut self, info: &PartialPeerInfo<SocketAddr>, network: &Network) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let entry = self.entries.entry(info.peer_id).or_insert_with(|| Entry {
+            peer_id: info.peer_id,
+            addrs: vec![],
+            networks: BTreeSet::new(),
+            versions: None,
+            last_seen: now,
+        });
+
+        let mut addrs = Vec::with_capacity(MAX_ADDRS);
+        let seen = info
+            .advertised_info
+            .iter()
+            .flat_map(|ad| ad.listen_addrs.iter())
+            .chain(info.seen_addrs.iter())
+            .copied();
+        for addr in seen.chain(entry.addrs.drain(..)) {
+            if !addrs.contains(&addr) {
+                addrs.push(addr)
+            }
+        }
+        addrs.truncate(MAX_ADDRS);
+        entry.addrs = addrs;
+
+        entry.networks.insert(network_name(network));
+        if let Some(ad) = &info.advertised_info {
+            entry.versions = Some(ad.versions);
+        }
+        entry.last_seen = now;
+    }
+
+    /// Remove `peer` from the address book.
+    pub fn forget(&mut self, peer: &PeerId) -> Option<Entry> {
+        self.entries.remove(peer)
+    }
+
+    /// Remove all peers not seen within `max_age`, returning the number of
+    /// peers removed.
+    pub fn expire(&mut self, max_age: Duration) -> usize {
+        let cutoff = SystemTime::now()
+            .checked_sub(max_age)
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let before = self.entries.len();
+        self.entries.retain(|_, entry| entry.last_seen >= cutoff);
+        before - self.entries.len()
+    }
+
+    /// The addresses of the peers seen on `network`, eg. to be
+    /// [`Extend`]ed into the [`super::discovery::Static`] of a node at startup.
+    pub fn known<'a>(
+        &'a self,
+        network: &Network,
+    ) -> impl Iterator<Item = (PeerId, Vec<SocketAddr>)> + 'a {
+        let name = network_name(network);
+        self.entries
+            .values()
+            .filter(move |entry| entry.networks.contains(&name) && !entry.addrs.is_empty())
+            .map(|entry| (entry.peer_id, entry.addrs.clone()))
+    }
+}
+
+fn network_name(network: &Network) -> Option<String> {
+    match network {
+        Network::Main => None,
+        Network::Custom(name) => Some(String::from_utf8_lossy(name).into_owned()),
+    }
+}
@@ -0,0 +1,277 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Signed capability tokens, granting limited rights to remote peers.
+//!
+//! Requests made by remote peers are normally subject to local policy, such
+//! as the [`super::protocol::io::graft::Policy`] determining whether a peer
+//! may trigger a replication. A [`Token`] allows the operator of a peer (eg. a
+//! seed) to grant rights to peers which would otherwise not have them, without
+//! relaxing the policy for everyone.
+//!
+//! A token is signed by the peer issuing it, names the peer holding it, and is
+//! scoped to a set of [`Urn`]s and [`Operation`]s until it expires. The holder
+//! attaches it to its requests, eg.
+//! [`super::protocol::interrogation::Request::PleasePull`]. Because the
+//! requesting peer must be the holder named in the token, a token is useless
+//! to anyone else. Peers only honour tokens they issued themselves.
+//!
+//! Tokens are handed to the holder out-of-band, in the textual form provided
+//! by the [`fmt::Display`] and [`FromStr`] impls.
+
+use std::{
+    collections::BTreeSet,
+    fmt,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use minicbor::{Decode, Encode};
+
+use crate::{git::Urn, PeerId, Signature, Signer};
+
+/// Prefix of the payload covered by the signature of a [`Token`], so the
+/// signature can't be mistaken for one over some other data.
+const DOMAIN: &[u8] = b"radicle-link capability\0";
+
+pub mod error {
+    use thiserror::Error;
+
+    use super::Operation;
+    use crate::git::Urn;
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Issue {
+        #[error("failed to sign token")]
+        Sign(#[source] Box<dyn std::error::Error + Send + Sync + 'static>),
+
+        #[error(transparent)]
+        Cbor(#[from] minicbor::encode::Error<std::io::Error>),
+    }
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Check {
+        #[error("token was not issued by this peer")]
+        ForeignIssuer,
+
+        #[error("invalid signature")]
+        InvalidSignature,
+
+        #[error("token is held by another peer")]
+        WrongHolder,
+
+        #[error("token expired")]
+        Expired,
+
+        #[error("token does not apply to {0}")]
+        OutOfScope(Urn),
+
+        #[error("token does not grant {0:?}")]
+        NotGranted(Operation),
+
+        #[error(transparent)]
+        Cbor(#[from] minicbor::encode::Error<std::io::Error>),
+    }
+
+    #[derive(Debug, Error)]
+    #[non_exhaustive]
+    pub enum Parse {
+        #[error(transparent)]
+        Multibase(#[from] multibase::Error),
+
+        #[error(transparent)]
+        Cbor(#[from] minicbor::decode::Error),
+    }
+}
+
+/// The operations a [`Token`] can grant.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[non_exhaustive]
+pub enum Operation {
+    /// Trigger a replication of the [`Urn`] from the holder, regardless of
+    /// the issuer's [`super::protocol::io::graft::Policy`].
+    ///
+    /// The rate limits of the issuer still apply.
+    Replicate,
+
+    /// Catch-all for unknown operations (forwards-compatibility).
+    ///
+    /// This is for decoding, **do not** construct this variant. It is never
+    /// granted.
+    Unknown(u8),
+}
+
+impl Operation {
+    pub fn code(&self) -> u8 {
+        match self {
+            Self::Replicate => 0,
+            Self::Unknown(n) => *n,
+        }
+    }
+}
+
+impl From<u8> for Operation {
+    fn from(n: u8) -> Self {
+        match n {
+            0 => Self::Replicate,
+            x => Self::Unknown(x),
+        }
+    }
+}
+
+impl Encode for Operation {
+    fn encode<W: minicbor::encode::Write>(
+        &self,
+        e: &mut minicbor::Encoder<W>,
+    ) -> Result<(), minicbor::encode::Error<W::Error>> {
+        e.u8(self.code())?;
+        Ok(())
+    }
+}
+
+impl<'b> Decode<'b> for Operation {
+    fn decode(d: &mut minicbor::Decoder<'b>) -> Result<Self, minicbor::decode::Error> {
+        d.u8().map(Self::from)
+    }
+}
+
+/// The rights granted by a [`Token`].
+#[derive(Clone, Debug, Eq, PartialEq, Encode, Decode)]
+#[cbor(array)]
+pub struct Grant {
+    /// The peer which issued (and will honour) the token.
+    #[n(0)]
+    pub issuer: PeerId,
+    /// The peer allowed to make use of the token.
+    #[n(1)]
+    pub holder: PeerId,
+    /// The [`Urn`]s the token applies to, without paths.
+    #[n(2)]
+    pub urns: BTreeSet<Urn>,
+    #[n(3)]
+    pub ops: BTreeSet<Operation>,
+    /// Expiry, in seconds since the UNIX epoch.
+    #[n(4)]
+    pub expires: u64,
+}
+
+/// A [`Grant`] signed by its issuer.
+#[derive(Clone, Debug, Eq, PartialEq, Encode, Decode)]
+#[cbor(array)]
+pub struct Token {
+    #[n(0)]
+    grant: Grant,
+    #[n(1)]
+    signature: Signature,
+}
+
+impl Token {
+    /// Issue a token granting `holder` the right to perform `ops` on `urns`,
+    /// valid for the given duration from now.
+    pub async fn issue<S, U, O>(
+        signer: &S,
+        holder: PeerId,
+        urns: U,
+        ops: O,
+        valid_for: Duration,
+    ) -> Result<Self, error::Issue>
+    where
+        S: Signer,
+        U: IntoIterator<Item = Urn>,
+        O: IntoIterator<Item = Operation>,
+    {
+        let expires = (SystemTime::now() + valid_for)
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let grant = Grant {
+            issuer: PeerId::from_signer(signer),
+            holder,
+            urns: urns.into_iter().map(|urn| urn.with_path(None)).collect(),
+            ops: ops.into_iter().collect(),
+            expires,
+        };
+        let signature = signer
+            .sign(&signed_payload(&grant)?)
+            .await
+            .map_err(|e| error::Issue::Sign(Box::new(e)))?;
+
+        Ok(Self {
+            grant,
+            signature: signature.into(),
+        })
+    }
+
+    /// The rights claimed by the token. They are only trustworthy once the
+    /// token passed [`Token::check`].
+    pub fn grant(&self) -> &Grant {
+        &self.grant
+    }
+
+    /// Check that the token was issued by `issuer`, and permits `holder` to
+    /// perform `op` on `urn` at time `now`.
+    pub fn check(
+        &self,
+        issuer: &PeerId,
+        holder: &PeerId,
+        urn: &Urn,
+        op: Operation,
+        now: SystemTime,
+    ) -> Result<(), error::Check> {
+        let grant = &self.grant;
+        if &grant.issuer != issuer {
+            return Err(error::Check::ForeignIssuer);
+        }
+        if !self
+            .signature
+            .verify(&signed_payload(grant)?, issuer.as_public_key())
+        {
+            return Err(error::Check::InvalidSignature);
+        }
+        if &grant.holder != holder {
+            return Err(error::Check::WrongHolder);
+        }
+        let now = now
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        if grant.expires <= now {
+            return Err(error::Check::Expired);
+        }
+        let urn = urn.clone().with_path(None);
+        if !grant.urns.contains(&urn) {
+            return Err(error::Check::OutOfScope(urn));
+        }
+        if matches!(op, Operation::Unknown(_)) || !grant.ops.contains(&op) {
+            return Err(error::Check::NotGranted(op));
+        }
+
+        Ok(())
+    }
+}
+
+fn signed_payload(grant: &Grant) -> Result<Vec<u8>, minicbor::encode::Error<std::io::Error>> {
+    let mut payload = DOMAIN.to_vec();
+    minicbor::encode(grant, &mut payload)?;
+    Ok(payload)
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes = minicbor::to_vec(self).map_err(|_| fmt::Error)?;
+        f.write_str(&multibase::encode(multibase::Base::Base32Z, bytes))
+    }
+}
+
+impl FromStr for Token {
+    type Err = error::Parse;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (_, bytes) = multibase::decode(s)?;
+        Ok(minicbor::decode(&bytes)?)
+    }
+}
@@ -104,6 +104,25 @@ where
     }
 }
 
+impl<I> Extend<(PeerId, I)> for Static
+where
+    I: IntoIterator<Item = SocketAddr>,
+{
+    fn extend<T>(&mut self, iter: T)
+    where
+        T: IntoIterator<Item = (PeerId, I)>,
+    {
+        for (peer, addrs) in iter {
+            let known = self.peers.entry(peer).or_default();
+            for addr in addrs {
+                if !known.contains(&addr) {
+                    known.push(addr)
+                }
+            }
+        }
+    }
+}
+
 impl Discovery for Static {
     type Addr = SocketAddr;
     type Stream = futures::stream::Iter<btree_map::IntoIter<PeerId, Vec<SocketAddr>>>;
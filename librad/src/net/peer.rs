@@ -3,15 +3,20 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::{net::SocketAddr, sync::Arc, time::Duration};
+use std::{net::SocketAddr, num::NonZeroUsize, sync::Arc, time::Duration};
 
-use futures::{future, StreamExt as _, TryFutureExt as _, TryStreamExt as _};
+use futures::{future, FutureExt as _, StreamExt as _, TryFutureExt as _, TryStreamExt as _};
 use futures_timer::Delay;
 
 use super::protocol::{self, gossip};
 use crate::{
     executor,
-    git::{self, storage::Fetchers, Urn},
+    git::{
+        self,
+        replication,
+        storage::{fetcher, watch, Fetchers},
+        Urn,
+    },
     PeerId,
     Signer,
 };
@@ -28,6 +33,9 @@ pub use super::protocol::{
 
 pub mod error;
 pub mod storage;
+
+/// Interval at which [`Peer::watch`] takes snapshots of a namespace.
+pub const WATCH_INTERVAL: Duration = Duration::from_secs(1);
 pub use storage::Storage as PeerStorage;
 
 #[derive(Clone)]
@@ -74,6 +82,9 @@ pub mod config {
         ///
         /// Applies to fetches initiated by incoming gossip messages.
         pub fetch_slot_wait_timeout: Duration,
+        /// Whether incoming gossip about [`git::Urn`]s not yet in storage
+        /// should cause them to be cloned.
+        pub replicate_unknown: storage::ReplicateUnknown,
     }
 
     impl Default for ProtocolStorage {
@@ -81,6 +92,7 @@ pub mod config {
             Self {
                 pool_size: num_cpus::get_physical(),
                 fetch_slot_wait_timeout: Duration::from_secs(20),
+                replicate_unknown: storage::ReplicateUnknown::default(),
             }
         }
     }
@@ -120,17 +132,24 @@ where
             let store = git::storage::Storage::open(&config.protocol.paths, config.signer.clone())?;
             let phone = phone.clone();
             let urns = protocol::cache::urns::Filter::new(store, move |ev| phone.emit(ev))?;
-            protocol::Caches { urns }
+            protocol::Caches {
+                urns,
+                features: Default::default(),
+                slots: replication::slots::Slots::new(config.protocol.replication.slots),
+            }
         };
         let peer_store = PeerStorage::new(
             spawner.clone(),
             pool,
             storage::Config {
-                replication: config.protocol.replication,
+                replication: config.protocol.replication.clone(),
                 fetch_slot_wait_timeout: config.storage.protocol.fetch_slot_wait_timeout,
                 fetch_quota: config.protocol.rate_limits.gossip.fetches_per_peer_and_urn,
+                replicate_unknown: config.storage.protocol.replicate_unknown,
             },
             caches.urns.clone(),
+            caches.features.clone(),
+            caches.slots.clone(),
         );
         let user_store = git::storage::Pool::new(
             git::storage::pool::Config::with_fetchers(
@@ -241,6 +260,144 @@ where
         self.phone.subscribe()
     }
 
+    /// Watch the namespace `urn` for ref changes.
+    ///
+    /// Both local modifications (eg. pushes via
+    /// [`git::local::transport`], possibly from another process) and
+    /// replicated updates are reported. Changes are detected by comparing
+    /// [`watch::snapshot`]s of the namespace, which are taken every
+    /// [`WATCH_INTERVAL`], and whenever gossip about `urn` was applied to
+    /// the local storage.
+    ///
+    /// Errors obtaining a snapshot are logged, and the snapshot is retried on
+    /// the next tick. The stream ends when the network endpoint shuts down.
+    pub fn watch(&self, urn: Urn) -> impl futures::Stream<Item = watch::RefUpdate> {
+        use protocol::{
+            broadcast::PutResult,
+            event::{upstream::Gossip, Upstream},
+        };
+
+        let peer = self.clone();
+        let urn = urn.with_path(None);
+        async_stream::stream! {
+            let mut events = peer.subscribe().boxed().fuse();
+            let mut last = None;
+            loop {
+                let snapshot = {
+                    let urn = urn.clone();
+                    peer.using_read_only(move |storage| watch::snapshot(storage, &urn))
+                        .await
+                };
+                match snapshot {
+                    Ok(Ok(next)) => {
+                        if let Some(prev) = &last {
+                            for update in watch::diff(prev, &next) {
+                                yield update
+                            }
+                        }
+                        last = Some(next);
+                    },
+                    Ok(Err(e)) => tracing::warn!(urn = %urn, err = %e, "failed to snapshot refs"),
+                    Err(e) => tracing::warn!(urn = %urn, err = %e, "failed to obtain storage"),
+                }
+
+                let mut tick = Delay::new(WATCH_INTERVAL).fuse();
+                loop {
+                    futures::select! {
+                        _ = tick => break,
+                        event = events.next() => match event {
+                            Some(Ok(Upstream::Gossip(box Gossip::Put {
+                                payload,
+                                result: PutResult::Applied(_),
+                                ..
+                            }))) if payload.urn.id == urn.id => break,
+                            Some(Err(protocol::RecvError::Closed)) | None => return,
+                            _ => continue,
+                        },
+                    }
+                }
+            }
+        }
+    }
+
+    /// The notifications derived from replicated data, optionally restricted
+    /// to `urn`, oldest first.
+    ///
+    /// See [`git::inbox`].
+    pub async fn inbox(
+        &self,
+        urn: Option<Urn>,
+    ) -> Result<Vec<git::inbox::Notification>, error::Inbox> {
+        Ok(self
+            .using_storage(move |storage| git::inbox::list(storage, urn.as_ref(), None))
+            .await??)
+    }
+
+    /// Replicate each of `urns` from the peer `from`.
+    ///
+    /// A connection to `from` is established, or an existing one reused, before
+    /// any fetch is started. The replications of up to `parallelism` [`Urn`]s
+    /// are then interleaved over this connection, each on its own stream. This
+    /// avoids the overhead of replicating the [`Urn`]s one by one, eg. when
+    /// syncing with a seed for the first time.
+    ///
+    /// Transient failures are retried as per the [`replication::Config`] of
+    /// the protocol. The failure to replicate one [`Urn`] does not affect the
+    /// others. The results are returned in the order of `urns`.
+    pub async fn replicate_batch<I>(
+        &self,
+        from: impl Into<(PeerId, Vec<SocketAddr>)>,
+        urns: I,
+        parallelism: NonZeroUsize,
+    ) -> Vec<(Urn, Result<replication::ReplicateResult, error::Replicate>)>
+    where
+        I: IntoIterator<Item = Urn>,
+    {
+        let (remote_peer, addr_hints) = from.into();
+        if let Err(e) = self
+            .interrogate((remote_peer, addr_hints.clone()))
+            .ping()
+            .await
+        {
+            tracing::warn!(err = ?e, %remote_peer, "unable to connect ahead of batch replication");
+        }
+
+        let config = &self.config.protocol.replication;
+        let timeout = self.config.storage.protocol.fetch_slot_wait_timeout;
+        let features = self.caches.features.get(&remote_peer);
+        futures::stream::iter(urns)
+            .map(|urn| {
+                let builder =
+                    fetcher::PeerToPeer::new(urn.clone(), remote_peer, addr_hints.clone())
+                        .features(features);
+                async move {
+                    let _permit = self.caches.slots.acquire(&urn, remote_peer).await;
+                    let res = replication::retry::retrying(config.retry, || {
+                        let builder = builder.clone();
+                        let config = config.clone();
+                        async move {
+                            fetcher::retrying(
+                                &self.spawner,
+                                &self.user_store,
+                                builder,
+                                timeout,
+                                move |storage, fetcher| {
+                                    replication::replicate(storage, fetcher, config.clone(), None)
+                                        .map_err(error::Replicate::from)
+                                },
+                            )
+                            .await?
+                        }
+                    })
+                    .await;
+                    (urn, res)
+                }
+            })
+            .buffered(parallelism.get())
+            .collect()
+            .await
+    }
+
     /// Borrow a [`git::storage::Storage`] from the pool, and run a blocking
     /// computation on it.
     pub async fn using_storage<F, A>(&self, blocking: F) -> Result<A, error::Storage>
@@ -5,7 +5,14 @@
 
 use thiserror::Error;
 
-use crate::{git::storage, net::protocol::cache};
+use crate::{
+    git::{
+        inbox,
+        replication::{self, retry},
+        storage::{self, fetcher},
+    },
+    net::protocol::cache,
+};
 
 #[derive(Debug, Error)]
 #[non_exhaustive]
@@ -23,6 +30,41 @@ impl From<storage::PoolError> for Storage {
     }
 }
 
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Inbox {
+    #[error(transparent)]
+    Storage(#[from] Storage),
+
+    #[error(transparent)]
+    Inbox(#[from] inbox::Error),
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Replicate {
+    #[error("unable to obtain fetcher")]
+    Fetcher(#[from] fetcher::error::Retrying<git2::Error>),
+
+    #[error(transparent)]
+    Replication(#[from] Box<replication::Error>),
+}
+
+impl From<replication::Error> for Replicate {
+    fn from(e: replication::Error) -> Self {
+        Self::from(Box::new(e))
+    }
+}
+
+impl retry::Classify for Replicate {
+    fn category(&self) -> retry::Category {
+        match self {
+            Self::Replication(e) => e.category(),
+            Self::Fetcher(_) => retry::Category::Permanent,
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum Init {
     #[error("no async context found, try calling `.enter()` on the runtime")]
@@ -6,6 +6,7 @@
 use std::{net::SocketAddr, sync::Arc, time::Duration};
 
 use crypto::peer::Originates;
+use dashmap::DashSet;
 use either::Either::{self, Left, Right};
 use git_ext::{self as ext, reference};
 use nonzero_ext::nonzero;
@@ -13,13 +14,13 @@ use nonzero_ext::nonzero;
 use crate::{
     executor,
     git::{
-        replication,
+        replication::{self, slots::Slots},
         storage::{self, fetcher, Pool, PoolError, PooledRef, ReadOnlyStorage as _},
         tracking,
         Urn,
     },
     identities::urn,
-    net::protocol::{broadcast, cache, gossip},
+    net::protocol::{broadcast, cache, features, gossip},
     rate_limit::{Keyed, RateLimiter},
     PeerId,
 };
@@ -27,11 +28,35 @@ use crate::{
 mod error;
 pub use error::Error;
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub struct Config {
     pub replication: replication::Config,
     pub fetch_slot_wait_timeout: Duration,
     pub fetch_quota: governor::Quota,
+    pub replicate_unknown: ReplicateUnknown,
+}
+
+/// Policy determining whether gossip about a [`Urn`] which is not yet present
+/// in storage should cause it to be cloned.
+///
+/// Gossip about [`Urn`]s which are present locally is only acted upon if the
+/// origin of the gossip is tracked, regardless of this policy.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReplicateUnknown {
+    /// Never clone unknown [`Urn`]s in response to gossip.
+    Never,
+    /// Clone unknown [`Urn`]s only if the origin of the gossip is tracked in
+    /// the context of the [`Urn`].
+    FromTracked,
+    /// Clone any unknown [`Urn`] announced via gossip. This is what public
+    /// seed nodes typically want.
+    Always,
+}
+
+impl Default for ReplicateUnknown {
+    fn default() -> Self {
+        Self::FromTracked
+    }
 }
 
 #[derive(Clone)]
@@ -39,25 +64,58 @@ pub struct Storage {
     pool: Pool<storage::Storage>,
     config: Config,
     urns: cache::urns::Filter,
+    features: features::Negotiated,
     limits: Arc<RateLimiter<Keyed<(PeerId, Urn)>>>,
+    inflight: Arc<DashSet<(Urn, git2::Oid)>>,
+    slots: Slots,
     spawner: Arc<executor::Spawner>,
 }
 
+/// Idempotency token for a gossip-triggered fetch of a particular revision.
+///
+/// While the token is alive, other attempts to fetch the same revision are
+/// rejected. The token is released when dropped.
+struct InFlight {
+    set: Arc<DashSet<(Urn, git2::Oid)>>,
+    key: (Urn, git2::Oid),
+}
+
+impl InFlight {
+    fn acquire(set: &Arc<DashSet<(Urn, git2::Oid)>>, urn: Urn, head: git2::Oid) -> Option<Self> {
+        let key = (urn, head);
+        set.insert(key.clone()).then(|| Self {
+            set: Arc::clone(set),
+            key,
+        })
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.set.remove(&self.key);
+    }
+}
+
 impl Storage {
     pub fn new(
         spawner: Arc<executor::Spawner>,
         pool: Pool<storage::Storage>,
         config: Config,
         urns: cache::urns::Filter,
+        features: features::Negotiated,
+        slots: Slots,
     ) -> Self {
         Self {
             pool,
             config,
             urns,
+            features,
             limits: Arc::new(RateLimiter::keyed(
                 config.fetch_quota,
                 nonzero!(256 * 1024usize),
             )),
+            inflight: Arc::new(DashSet::new()),
+            slots,
             spawner,
         }
     }
@@ -72,7 +130,8 @@ impl Storage {
         urn: Either<Urn, Originates<Urn>>,
         head: impl Into<Option<git2::Oid>>,
     ) -> Result<replication::ReplicateResult, Error> {
-        if let Some(head) = head.into() {
+        let head = head.into();
+        if let Some(head) = head {
             if self.git_has(urn.clone(), Some(head)).await {
                 return Err(Error::KnownObject(head));
             }
@@ -87,18 +146,36 @@ impl Storage {
             return Err(Error::RateLimited { remote_peer, urn });
         }
 
-        let config = self.config;
-        fetcher::retrying(
-            &self.spawner,
-            &self.pool,
-            fetcher::PeerToPeer::new(urn.clone(), remote_peer, addr_hints),
-            config.fetch_slot_wait_timeout,
-            move |storage, fetcher| {
-                replication::replicate(storage, fetcher, config.replication, None)
-                    .map_err(Error::from)
-            },
-        )
-        .await?
+        let _token = match head {
+            None => None,
+            Some(head) => Some(
+                InFlight::acquire(&self.inflight, urn.clone().with_path(None), head)
+                    .ok_or(Error::InFlight(head))?,
+            ),
+        };
+
+        let _permit = self.slots.acquire(&urn, remote_peer).await;
+        let config = &self.config;
+        let builder = fetcher::PeerToPeer::new(urn.clone(), remote_peer, addr_hints)
+            .features(self.features.get(&remote_peer));
+        replication::retry::retrying(config.replication.retry, || {
+            let builder = builder.clone();
+            let config = config.clone();
+            async move {
+                fetcher::retrying(
+                    &self.spawner,
+                    &self.pool,
+                    builder,
+                    config.fetch_slot_wait_timeout,
+                    move |storage, fetcher| {
+                        replication::replicate(storage, fetcher, config.replication.clone(), None)
+                            .map_err(Error::from)
+                    },
+                )
+                .await?
+            }
+        })
+        .await
     }
 
     /// Determine if we have the given object locally
@@ -199,7 +276,17 @@ impl broadcast::LocalStorage<SocketAddr> for Storage {
             },
         };
 
-        if is_tracked {
+        let is_known = self
+            .git_has(Left(has.urn.clone().with_path(None)), None)
+            .await;
+        let is_wanted = match self.config.replicate_unknown {
+            _ if is_known => is_tracked,
+            ReplicateUnknown::Never => false,
+            ReplicateUnknown::FromTracked => is_tracked,
+            ReplicateUnknown::Always => true,
+        };
+
+        if is_wanted {
             let urn = Right(Originates {
                 from: origin,
                 value: has.urn.clone(),
@@ -238,6 +325,7 @@ impl broadcast::LocalStorage<SocketAddr> for Storage {
 
                 Err(e) => match e {
                     Error::KnownObject(_) => PutResult::Stale,
+                    Error::InFlight(_) => PutResult::InFlight,
                     Error::RateLimited { remote_peer, urn } => {
                         tracing::warn!(
                             "skipped fetch of {} from {} due to rate limiting",
@@ -6,7 +6,13 @@
 use thiserror::Error;
 
 use crate::{
-    git::{self, replication, storage, storage::fetcher, tracking},
+    git::{
+        self,
+        replication::{self, retry},
+        storage,
+        storage::fetcher,
+        tracking,
+    },
     PeerId,
 };
 
@@ -16,6 +22,9 @@ pub enum Error {
     #[error("already have {0}")]
     KnownObject(git2::Oid),
 
+    #[error("already fetching {0}")]
+    InFlight(git2::Oid),
+
     #[error("too many fetches from {remote_peer}")]
     RateLimited { remote_peer: PeerId, urn: git::Urn },
 
@@ -34,3 +43,12 @@ pub enum Error {
     #[error(transparent)]
     Pool(#[from] storage::PoolError),
 }
+
+impl retry::Classify for Error {
+    fn category(&self) -> retry::Category {
+        match self {
+            Self::Replication(e) => e.category(),
+            _ => retry::Category::Permanent,
+        }
+    }
+}
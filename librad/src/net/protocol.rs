@@ -3,7 +3,14 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::{fmt::Debug, future::Future, net::SocketAddr, sync::Arc, time::Duration};
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    future::Future,
+    net::SocketAddr,
+    sync::Arc,
+    time::Duration,
+};
 
 use async_stream::stream;
 use futures::{stream::BoxStream, StreamExt};
@@ -15,6 +22,7 @@ use tracing::Instrument as _;
 use super::{
     connection::{LocalAddr, LocalPeer},
     quic,
+    tofu,
     upgrade,
     Network,
 };
@@ -39,10 +47,16 @@ pub use cache::Caches;
 
 pub mod error;
 pub mod event;
+pub mod features;
+pub use features::Features;
+
 pub mod gossip;
 pub mod interrogation;
 pub mod io;
 pub mod membership;
+pub mod rtt;
+pub mod throttle;
+pub mod version;
 
 mod info;
 pub use info::{Capability, PartialPeerInfo, PeerAdvertisement, PeerInfo};
@@ -66,19 +80,38 @@ pub type Endpoint = quic::Endpoint<2>;
 #[derive(Clone, Debug)]
 pub struct Config {
     pub paths: Paths,
-    pub listen_addr: SocketAddr,
+    /// The addresses to bind to. The endpoint accepts connections on all of
+    /// them.
+    pub listen_addrs: NonEmpty<SocketAddr>,
+    /// The addresses to advertise to other peers, instead of the
+    /// `listen_addrs`, eg. when behind a NAT.
     pub advertised_addrs: Option<NonEmpty<SocketAddr>>,
+    /// How outgoing connections are established, eg. through a SOCKS5 proxy.
+    pub dialer: quic::Dialer,
     pub membership: membership::Params,
     pub network: Network,
     pub replication: replication::Config,
     pub fetch: config::Fetch,
+    pub graft: config::Graft,
+    pub serve: config::Serve,
     pub rate_limits: Quota,
-    // TODO: transport, ...
+    /// Peers to keep a connection to at all times, regardless of the
+    /// membership protocol's view of them. Lost connections are
+    /// re-established with exponential backoff.
+    pub pinned: BTreeMap<PeerId, Vec<SocketAddr>>,
+    /// Keep alive and idle timeout of connections.
+    pub transport: quic::Transport,
+    /// If set, the first peer seen at every address we connect to is pinned,
+    /// and connections to other peers at the address are dealt with as per
+    /// the [`tofu::Policy`].
+    pub tofu: Option<tofu::Policy>,
 }
 
 pub mod config {
     use std::time::Duration;
 
+    use super::io::graft;
+
     #[derive(Clone, Copy, Debug)]
     pub struct Fetch {
         pub fetch_slot_wait_timeout: Duration,
@@ -91,6 +124,20 @@ pub mod config {
             }
         }
     }
+
+    #[derive(Clone, Copy, Debug, Default)]
+    pub struct Graft {
+        /// Which remote peers may cause a replication by fetching from us.
+        pub policy: graft::Policy,
+    }
+
+    #[derive(Clone, Copy, Debug, Default)]
+    pub struct Serve {
+        /// Maximum total size in bytes of the packs cached for reuse by
+        /// subsequent clones, see [`crate::git::p2p::pack_cache`]. If `None`,
+        /// packs are not cached.
+        pub pack_cache_size: Option<u64>,
+    }
 }
 
 /// Binding of a peer to a network socket.
@@ -165,21 +212,34 @@ where
     Store: ProtocolStorage<SocketAddr, Update = gossip::Payload> + Clone + 'static,
 {
     let local_id = PeerId::from_signer(&signer);
-    let git = GitServer::new(&config.paths);
+    let git = match config.serve.pack_cache_size {
+        None => GitServer::new(&config.paths),
+        Some(max_size) => GitServer::new(&config.paths)
+            .with_pack_cache(max_size)
+            .map_err(error::Bootstrap::PackCache)?,
+    };
+    let tofu = config
+        .tofu
+        .map(|policy| tofu::Tofu::open(&config.paths, policy))
+        .transpose()
+        .map_err(error::Bootstrap::Tofu)?;
     let quic::BoundEndpoint { endpoint, incoming } = quic::Endpoint::bind(
         signer,
-        &spawner,
-        config.listen_addr,
+        spawner.clone(),
+        config.listen_addrs,
         config.advertised_addrs,
         config.network,
+        config.dialer,
+        config.transport,
     )
     .await?;
     let (membership, periodic) = membership::Hpv::<_, SocketAddr>::new(
         local_id,
         Pcg64Mcg::new(rand::random()),
         config.membership,
+        config.transport.max_idle_timeout.div_f32(2.0),
     );
-    let storage = Storage::new(storage, config.rate_limits.storage);
+    let storage = Storage::new(storage, &config.rate_limits);
     // TODO: make configurable
     let nonces = nonce::NonceBag::new(Duration::from_secs(300));
     let limits = RateLimits {
@@ -187,6 +247,10 @@ where
             config.rate_limits.membership,
             nonzero!(1024 * 1024usize),
         )),
+        graft: Arc::new(RateLimiter::keyed(
+            config.rate_limits.graft,
+            nonzero!(1024 * 1024usize),
+        )),
     };
 
     let state = State {
@@ -199,11 +263,16 @@ where
         config: StateConfig {
             replication: config.replication,
             fetch: config.fetch,
+            graft: config.graft,
         },
         nonces,
         caches,
         spawner,
         limits,
+        versions: version::Negotiated::default(),
+        rtts: rtt::Rtts::default(),
+        pinned: Arc::new(config.pinned),
+        tofu,
     };
 
     Ok(Bound {
@@ -241,9 +310,10 @@ where
     let endpoint = state.endpoint.clone();
     let spawner = state.spawner.clone();
 
-    let tasks = [
+    let mut tasks = vec![
         spawner.spawn(accept::disco(state.clone(), disco)),
         spawner.spawn(accept::periodic(state.clone(), periodic)),
+        spawner.spawn(accept::ping(state.clone())),
         spawner.spawn(accept::ground_control(
             state.clone(),
             stream! {
@@ -252,6 +322,11 @@ where
             },
         )),
     ];
+    tasks.extend(
+        state.pinned.iter().map(|(peer, addrs)| {
+            spawner.spawn(accept::pinned(state.clone(), *peer, addrs.clone()))
+        }),
+    );
     let run = {
         let endpoint = endpoint.clone();
         async move {
@@ -3,9 +3,14 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::{iter, net::SocketAddr};
+use std::{iter, net::SocketAddr, time::Duration};
 
-use futures::stream::{self, StreamExt as _};
+use backoff::{backoff::Backoff as _, ExponentialBackoff};
+use futures::{
+    future,
+    stream::{self, StreamExt as _},
+};
+use futures_timer::Delay;
 
 use super::{
     control,
@@ -13,13 +18,14 @@ use super::{
     gossip,
     io,
     membership,
+    rtt,
     tick,
     PeerInfo,
     ProtocolStorage,
     RecvError,
     State,
 };
-use crate::PeerId;
+use crate::{net::connection::RemotePeer as _, PeerId};
 
 #[tracing::instrument(skip(state, disco))]
 pub(super) async fn disco<S, D>(state: State<S>, disco: D)
@@ -109,6 +115,76 @@ where
         .await;
 }
 
+/// Periodically ping all peers we hold a connection to, recording their
+/// round-trip times.
+#[tracing::instrument(skip(state))]
+pub(super) async fn ping<S>(state: State<S>)
+where
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + 'static,
+{
+    loop {
+        Delay::new(rtt::PING_INTERVAL).await;
+        let conns = state
+            .endpoint
+            .peers()
+            .into_iter()
+            .filter_map(|peer| state.endpoint.get_connection(peer))
+            .collect::<Vec<_>>();
+        future::join_all(conns.iter().map(|conn| {
+            let state = &state;
+            async move {
+                match control::ping(state, conn).await {
+                    Ok(rtt) => {
+                        tracing::trace!(remote_id = %conn.remote_peer_id(), ?rtt, "pong")
+                    },
+                    Err(e) => {
+                        tracing::debug!(remote_id = %conn.remote_peer_id(), err = %e, "ping failed")
+                    },
+                }
+            }
+        }))
+        .await;
+    }
+}
+
+/// How often the connection to a pinned peer is checked while it is up.
+const PINNED_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The upper bound of the delay between attempts to reconnect to a pinned
+/// peer.
+const PINNED_MAX_BACKOFF: Duration = Duration::from_secs(5 * 60);
+
+/// Keep a connection to the pinned `peer`, reconnecting with exponential
+/// backoff whenever it is lost.
+#[tracing::instrument(skip(state, addrs))]
+pub(super) async fn pinned<S>(state: State<S>, peer: PeerId, addrs: Vec<SocketAddr>)
+where
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + Clone + 'static,
+{
+    let mut backoff = ExponentialBackoff {
+        current_interval: Duration::from_secs(1),
+        initial_interval: Duration::from_secs(1),
+        max_interval: PINNED_MAX_BACKOFF,
+        max_elapsed_time: None,
+        ..Default::default()
+    };
+    loop {
+        if !state.has_connection(peer) {
+            tracing::info!("connecting to pinned peer");
+            io::discovered(state.clone(), peer, addrs.clone()).await;
+        }
+        let delay = if state.has_connection(peer) {
+            backoff.reset();
+            PINNED_CHECK_INTERVAL
+        } else {
+            let delay = backoff.next_backoff().unwrap_or(PINNED_MAX_BACKOFF);
+            tracing::warn!(?delay, "failed to connect to pinned peer, retrying");
+            delay
+        };
+        Delay::new(delay).await;
+    }
+}
+
 #[tracing::instrument(skip(state, rx))]
 pub(super) async fn ground_control<S, E>(state: State<S>, rx: E)
 where
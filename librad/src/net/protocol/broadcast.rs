@@ -42,6 +42,7 @@ pub(super) trait Membership {
 pub enum Limit<'a> {
     Errors,
     Wants { recipient: &'a PeerId },
+    Origin { origin: &'a PeerId },
 }
 
 pub(super) trait RateLimited {
@@ -84,6 +85,14 @@ where
         return Err(self::Error::Unsolicited { remote_id, message });
     }
 
+    let origin = match &message {
+        Have { origin, .. } | Want { origin, .. } => origin.peer_id,
+    };
+    if storage.is_rate_limit_breached(Limit::Origin { origin: &origin }) {
+        tracing::debug!(origin = %origin, "origin rate limit breached, dropping gossip");
+        return Ok((None, vec![]));
+    }
+
     let broadcast = |msg: Message<A, P>, exclude: Option<PeerId>| {
         membership
             .members(exclude)
@@ -141,7 +150,7 @@ where
                 },
 
                 Uninteresting => broadcast(Have { origin, val }, Some(remote_id)),
-                Stale => vec![],
+                Stale | InFlight => vec![],
             };
 
             Ok((Some(event), tocks))
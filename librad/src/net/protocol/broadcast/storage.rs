@@ -31,6 +31,14 @@ pub enum PutResult<Update> {
     /// [`super::Message`] stays unmodified.
     Uninteresting,
 
+    /// The `Update` is already being applied by a concurrent `put`.
+    ///
+    /// Unlike [`PutResult::Stale`], the `Update` is not yet known to be
+    /// available locally, and may be retried should the concurrent `put`
+    /// fail. Broadcast will terminate here, as the concurrent `put` relays the
+    /// `Update` once applied.
+    InFlight,
+
     /// An (intermittent) error occurred while trying to apply the `Update`.
     ///
     /// The `Update` will be relayed, while the `origin` of the
@@ -16,6 +16,7 @@ use thiserror::Error;
 use crate::{
     git::{
         identities,
+        replication::slots::Slots,
         storage::{self, watch},
     },
     identities::{xor, SomeUrn, Xor},
@@ -24,6 +25,11 @@ use crate::{
 #[derive(Clone)]
 pub struct Caches {
     pub urns: urns::Filter,
+    pub features: super::features::Negotiated,
+    /// The slots for replications on behalf of the network, shared by all
+    /// of them regardless of whether they were initiated locally or
+    /// remotely.
+    pub slots: Slots,
 }
 
 pub mod urns {
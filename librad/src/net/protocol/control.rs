@@ -3,12 +3,33 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::{iter, net::SocketAddr};
+use std::{
+    iter,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
 
 use futures::stream::{self, StreamExt as _};
+use tokio::time::timeout;
 
-use super::{broadcast, error, event, gossip, io, tick, PeerInfo, ProtocolStorage, State};
-use crate::PeerId;
+use super::{
+    broadcast,
+    error,
+    event,
+    gossip,
+    interrogation,
+    io,
+    rtt,
+    tick,
+    Features,
+    PeerInfo,
+    ProtocolStorage,
+    State,
+};
+use crate::{
+    net::{connection::RemotePeer as _, quic},
+    PeerId,
+};
 
 pub(super) async fn gossip<S>(
     state: &State<S>,
@@ -86,6 +107,10 @@ where
                     caches: CacheStats {
                         urns: state.caches.urns.stats(),
                     },
+                    rtt: state.rtts.snapshot(),
+                    features: state.caches.features.snapshot(),
+                    gossip: state.storage.throttle_stats(),
+                    bitmaps: state.git.bitmap_stats(),
                 })
                 .ok();
             }
@@ -105,13 +130,67 @@ pub(super) async fn interrogation<S>(
 {
     let chan = reply.lock().take();
     if let Some(tx) = chan {
+        if let Err(e) = supports(&state, peer, request.required_features()) {
+            tx.send(Err(e)).ok();
+            return;
+        }
         let resp = match state.connection(peer, addr_hints).await {
             None => Err(error::Interrogation::NoConnection(peer)),
-            Some(conn) => match io::send::request(&conn, request).await {
-                Err(e) => Err(e.into()),
-                Ok(resp) => resp.ok_or(error::Interrogation::NoResponse(peer)),
+            Some(conn) => match request {
+                interrogation::Request::Ping => ping(&state, &conn)
+                    .await
+                    .map(|_| interrogation::Response::Pong),
+                _ => match io::send::request(&conn, request).await {
+                    Err(e) => Err(e.into()),
+                    Ok(resp) => resp.ok_or(error::Interrogation::NoResponse(peer)),
+                },
             },
         };
         tx.send(resp).ok();
     }
 }
+
+/// Send a [`interrogation::Request::Ping`] over `conn`, and record the
+/// round-trip time if a `Pong` is received in time.
+pub(super) async fn ping<S>(
+    state: &State<S>,
+    conn: &quic::Connection,
+) -> Result<Duration, error::Interrogation> {
+    let peer = conn.remote_peer_id();
+    supports(
+        state,
+        peer,
+        interrogation::Request::Ping.required_features(),
+    )?;
+    let start = Instant::now();
+    let resp = timeout(
+        rtt::PING_TIMEOUT,
+        io::send::request(conn, interrogation::Request::Ping),
+    )
+    .await
+    .map_err(|_| error::Interrogation::NoResponse(peer))??;
+    match resp {
+        Some(interrogation::Response::Pong) => Ok(state.rtts.record(peer, start.elapsed())),
+        Some(interrogation::Response::Error(e)) => Err(error::Interrogation::ErrorResponse(e)),
+        Some(_) => Err(error::Interrogation::InvalidResponse),
+        None => Err(error::Interrogation::NoResponse(peer)),
+    }
+}
+
+/// Fail if `peer` is known not to support the `required` features.
+///
+/// Peers we haven't negotiated with are given the benefit of the doubt, as
+/// they may well support the features: interrogations are commonly sent to
+/// peers which aren't members of our overlay.
+fn supports<S>(
+    state: &State<S>,
+    peer: PeerId,
+    required: Features,
+) -> Result<(), error::Interrogation> {
+    match state.caches.features.get(&peer) {
+        Some(negotiated) if !negotiated.contains(required) => {
+            Err(error::Interrogation::Unsupported { peer, required })
+        },
+        _ => Ok(()),
+    }
+}
@@ -3,12 +3,16 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::fmt::Debug;
+use std::{fmt::Debug, io};
 
 use thiserror::Error;
 
-use super::interrogation;
-use crate::{git::storage::pool::PoolError, net::quic, PeerId};
+use super::{features::Features, interrogation};
+use crate::{
+    git::storage::pool::PoolError,
+    net::{quic, tofu},
+    PeerId,
+};
 
 mod internal;
 pub(super) use internal::*;
@@ -21,6 +25,12 @@ pub enum Bootstrap {
 
     #[error(transparent)]
     Quic(#[from] quic::Error),
+
+    #[error("failed to open pack cache")]
+    PackCache(#[source] io::Error),
+
+    #[error("failed to open peer pins")]
+    Tofu(#[source] tofu::Error),
 }
 
 #[derive(Debug, Error)]
@@ -32,6 +42,9 @@ pub enum Interrogation {
     #[error("no response from {0}")]
     NoResponse(PeerId),
 
+    #[error("{peer} does not support the features {required:?} required by the request")]
+    Unsupported { peer: PeerId, required: Features },
+
     #[error("error response: {0:?}")]
     ErrorResponse(interrogation::Error),
 
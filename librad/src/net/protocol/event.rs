@@ -5,8 +5,17 @@
 
 use std::{collections::HashMap, net::SocketAddr};
 
-use super::{broadcast, cache, error, gossip, interrogation, membership};
-use crate::PeerId;
+use super::{
+    broadcast,
+    cache,
+    error,
+    features::Features,
+    gossip,
+    interrogation,
+    membership,
+    throttle,
+};
+use crate::{git::storage::bitmap, PeerId};
 
 #[derive(Clone)]
 pub enum Downstream {
@@ -18,7 +27,7 @@ pub enum Downstream {
 pub mod downstream {
     use super::*;
 
-    use std::sync::Arc;
+    use std::{sync::Arc, time::Duration};
 
     use parking_lot::Mutex;
     use tokio::sync::oneshot;
@@ -60,6 +69,18 @@ pub mod downstream {
         pub membership_active: usize,
         pub membership_passive: usize,
         pub caches: CacheStats,
+        /// Smoothed round-trip times of connected peers, see
+        /// [`crate::net::protocol::rtt`].
+        pub rtt: HashMap<PeerId, Duration>,
+        /// The optional features negotiated with peers, see
+        /// [`crate::net::protocol::features`].
+        pub features: HashMap<PeerId, Features>,
+        /// Incoming gossip dropped by origin, see
+        /// [`crate::net::protocol::throttle`].
+        pub gossip: throttle::Stats,
+        /// Bitmap coverage of the fetches served, see
+        /// [`crate::git::storage::bitmap`].
+        pub bitmaps: bitmap::Stats,
     }
 
     #[derive(Clone, Copy, Debug, Default)]
@@ -83,6 +104,7 @@ pub enum Upstream {
     Gossip(Box<upstream::Gossip<SocketAddr, gossip::Payload>>),
     Membership(membership::Transition<SocketAddr>),
     Caches(upstream::Caches),
+    Connection(upstream::Connection),
 }
 
 pub mod upstream {
@@ -121,6 +143,33 @@ pub mod upstream {
         },
     }
 
+    #[derive(Clone, Debug)]
+    #[non_exhaustive]
+    pub enum Connection {
+        /// Nothing was received from `remote_peer` at `remote_addr` within
+        /// the idle timeout, so the peer is considered dead and the
+        /// connection was closed.
+        Dead {
+            remote_peer: PeerId,
+            remote_addr: SocketAddr,
+        },
+        /// We connected to `remote_peer` at `remote_addr`, but the address is
+        /// pinned to `pinned`, see [`crate::net::tofu`]. If `refused`, the
+        /// connection was closed.
+        Mismatch {
+            remote_peer: PeerId,
+            remote_addr: SocketAddr,
+            pinned: PeerId,
+            refused: bool,
+        },
+    }
+
+    impl From<Connection> for Upstream {
+        fn from(c: Connection) -> Self {
+            Self::Connection(c)
+        }
+    }
+
     impl From<Gossip<SocketAddr, gossip::Payload>> for Upstream {
         fn from(g: Gossip<SocketAddr, gossip::Payload>) -> Self {
             Self::Gossip(Box::new(g))
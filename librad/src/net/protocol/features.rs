@@ -0,0 +1,173 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Optional capabilities of peers.
+//!
+//! In addition to the RPC schema [`super::version::Versions`], peers advertise
+//! the optional [`Features`] they support as part of their
+//! [`super::PeerAdvertisement`]. The set negotiated with a peer is the
+//! intersection of both sides' features, and determines which code paths are
+//! taken when talking to it, eg. whether it is sent interrogation requests it
+//! wouldn't understand.
+//!
+//! Peers which predate feature negotiation don't advertise any features. Note
+//! the difference between a peer which advertised no features, and one we
+//! haven't [`Negotiated`] with at all: the former is known to support none of
+//! the optional capabilities, while nothing is known about the latter.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    ops::{BitAnd, BitOr},
+    sync::Arc,
+};
+
+use minicbor::{Decode, Decoder, Encode, Encoder};
+use parking_lot::RwLock;
+
+use crate::PeerId;
+
+/// A set of optional capabilities, encoded as a bitmap.
+///
+/// Bits not known to this implementation are retained when decoding, but
+/// never survive negotiation.
+#[derive(Clone, Copy, Default, Eq, Hash, PartialEq)]
+pub struct Features(u64);
+
+impl Features {
+    /// The peer answers the interrogation requests beyond the basic ones
+    /// (advertisement, address echo, and URNs), ie. requests for sigref tips,
+    /// large objects, pings and please-pulls.
+    pub const INTERROGATION: Self = Self(1);
+
+    /// The peer serves collaborative objects, ie. includes them in its
+    /// `rad/signed_refs` and replicates them from others.
+    pub const COBS: Self = Self(1 << 1);
+
+    /// The features supported by this implementation.
+    pub const SUPPORTED: Self = Self(Self::INTERROGATION.0 | Self::COBS.0);
+
+    /// The features of peers which predate feature negotiation.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    pub const fn bits(&self) -> u64 {
+        self.0
+    }
+
+    pub const fn from_bits(bits: u64) -> Self {
+        Self(bits)
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// `true` if all of `other` is contained in `self`.
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// The features contained in both `self` and `other`.
+    pub const fn intersection(&self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+
+    /// The features supported by both this implementation and a peer which
+    /// advertised `theirs`.
+    pub const fn negotiate(theirs: Self) -> Self {
+        Self::SUPPORTED.intersection(theirs)
+    }
+}
+
+impl BitOr for Features {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitAnd for Features {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        self.intersection(rhs)
+    }
+}
+
+impl fmt::Debug for Features {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const NAMES: &[(Features, &str)] = &[
+            (Features::INTERROGATION, "INTERROGATION"),
+            (Features::COBS, "COBS"),
+        ];
+
+        let mut set = f.debug_set();
+        let mut rest = self.0;
+        for (feature, name) in NAMES {
+            if self.contains(*feature) {
+                set.entry(&format_args!("{}", name));
+                rest &= !feature.0;
+            }
+        }
+        if rest != 0 {
+            set.entry(&format_args!("{:#x}", rest));
+        }
+        set.finish()
+    }
+}
+
+impl Encode for Features {
+    fn encode<W: minicbor::encode::Write>(
+        &self,
+        e: &mut Encoder<W>,
+    ) -> Result<(), minicbor::encode::Error<W::Error>> {
+        e.u64(self.0)?;
+        Ok(())
+    }
+}
+
+impl<'b> Decode<'b> for Features {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, minicbor::decode::Error> {
+        d.u64().map(Self)
+    }
+}
+
+/// The features negotiated with remote peers.
+///
+/// Shared between the protocol, which records the features upon receiving a
+/// peer's advertisement, and replication, which consults them.
+#[derive(Clone, Default)]
+pub struct Negotiated {
+    inner: Arc<RwLock<HashMap<PeerId, Features>>>,
+}
+
+impl Negotiated {
+    /// Negotiate the features to use with `peer`, given the features it
+    /// advertised.
+    pub fn record(&self, peer: PeerId, theirs: Features) -> Features {
+        let features = Features::negotiate(theirs);
+        self.inner.write().insert(peer, features);
+        features
+    }
+
+    /// The features negotiated with `peer`, or `None` if we haven't
+    /// negotiated with it.
+    pub fn get(&self, peer: &PeerId) -> Option<Features> {
+        self.inner.read().get(peer).copied()
+    }
+
+    /// Forget the features negotiated with `peer`, eg. because the connection
+    /// was lost. The peer may come back running a different version.
+    pub fn forget(&self, peer: &PeerId) {
+        self.inner.write().remove(peer);
+    }
+
+    pub fn snapshot(&self) -> HashMap<PeerId, Features> {
+        self.inner.read().clone()
+    }
+}
@@ -9,6 +9,7 @@ use data::BoundedVec;
 use minicbor::{Decode, Encode};
 use typenum::U16;
 
+use super::{features::Features, version::Versions};
 use crate::PeerId;
 
 #[derive(Debug, Clone, Eq, Ord, PartialEq, PartialOrd, Encode, Decode)]
@@ -167,6 +168,20 @@ pub struct PeerAdvertisement<Addr> {
 
     #[n(2)]
     pub capabilities: BTreeSet<Capability>,
+
+    /// The RPC schema versions supported by the peer.
+    ///
+    /// Peers which predate versioning omit this field, and are assumed to
+    /// support only [`Versions::LEGACY`].
+    #[n(3)]
+    pub versions: Versions,
+
+    /// The optional features supported by the peer.
+    ///
+    /// Peers which predate feature negotiation omit this field, and are
+    /// assumed to support none.
+    #[n(4)]
+    pub features: Features,
 }
 
 // XXX: derive fails to add the trait bound on Addr
@@ -178,11 +193,15 @@ impl<'__b777, Addr: minicbor::Decode<'__b777>> minicbor::Decode<'__b777>
     ) -> Result<PeerAdvertisement<Addr>, minicbor::decode::Error> {
         let mut listen_addrs: Option<BoundedVec<U16, Addr>> = None;
         let mut capabilities: Option<BTreeSet<Capability>> = None;
+        let mut versions: Option<Versions> = None;
+        let mut features: Option<Features> = None;
         if let Some(__len777) = __d777.array()? {
             for __i777 in 0..__len777 {
                 match __i777 {
                     0 => listen_addrs = Some(radicle_data::bounded::decode_truncate(__d777)?),
                     2 => capabilities = Some(minicbor::Decode::decode(__d777)?),
+                    3 => versions = Some(minicbor::Decode::decode(__d777)?),
+                    4 => features = Some(minicbor::Decode::decode(__d777)?),
                     _ => __d777.skip()?,
                 }
             }
@@ -192,6 +211,8 @@ impl<'__b777, Addr: minicbor::Decode<'__b777>> minicbor::Decode<'__b777>
                 match __i777 {
                     0 => listen_addrs = Some(radicle_data::bounded::decode_truncate(__d777)?),
                     2 => capabilities = Some(minicbor::Decode::decode(__d777)?),
+                    3 => versions = Some(minicbor::Decode::decode(__d777)?),
+                    4 => features = Some(minicbor::Decode::decode(__d777)?),
                     _ => __d777.skip()?,
                 }
                 __i777 += 1
@@ -215,6 +236,8 @@ impl<'__b777, Addr: minicbor::Decode<'__b777>> minicbor::Decode<'__b777>
                     "PeerAdvertisement::capabilities",
                 ));
             },
+            versions: versions.unwrap_or(Versions::LEGACY),
+            features: features.unwrap_or_else(Features::empty),
         })
     }
 }
@@ -223,6 +246,8 @@ impl<Addr> PeerAdvertisement<Addr> {
         Self {
             listen_addrs: BoundedVec::singleton(listen_addr),
             capabilities: BTreeSet::default(),
+            versions: Versions::SUPPORTED,
+            features: Features::SUPPORTED,
         }
     }
 }
@@ -3,7 +3,7 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use super::info::PeerAdvertisement;
+use super::{features::Features, info::PeerAdvertisement};
 
 mod rpc;
 pub use rpc::{Error, Request, Response};
@@ -3,12 +3,18 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::borrow::Cow;
-
-use super::PeerAdvertisement;
-use crate::identities::xor;
-
-#[derive(Clone, Copy, Debug, minicbor::Encode, minicbor::Decode)]
+use std::{borrow::Cow, collections::BTreeMap};
+
+use super::{Features, PeerAdvertisement};
+use crate::{
+    git::{large, Urn},
+    git_ext as ext,
+    identities::xor,
+    net::capability,
+    PeerId,
+};
+
+#[derive(Clone, Debug, minicbor::Encode, minicbor::Decode)]
 pub enum Request {
     /// Request the remote peer's [`PeerAdvertisement`]
     #[n(0)]
@@ -27,6 +33,62 @@ pub enum Request {
     #[n(2)]
     #[cbor(array)]
     GetUrns,
+
+    /// Ask the remote peer to respond immediately, in order to measure the
+    /// round-trip time.
+    #[n(3)]
+    #[cbor(array)]
+    Ping,
+
+    /// Request the tips of the `rad/signed_refs` the remote peer has for the
+    /// given [`Urn`], ie. its own and those of the peers it tracks.
+    ///
+    /// This allows to determine whether replicating from the remote peer
+    /// would yield any updates, without actually fetching.
+    #[n(4)]
+    #[cbor(array)]
+    GetSigrefTips(#[n(0)] Urn),
+
+    /// Ask the remote peer to fetch the given [`Urn`] from the requesting
+    /// peer right away, eg. to get changes onto a seed without waiting for
+    /// gossip to propagate.
+    ///
+    /// Whether the remote peer complies is subject to the same policy and rate
+    /// limits as replications triggered by the requesting peer fetching from
+    /// it, see [`crate::net::protocol::io::graft`]. The policy is bypassed if
+    /// the request carries a [`capability::Token`] issued by the remote peer,
+    /// which grants [`capability::Operation::Replicate`] on the [`Urn`] to the
+    /// requesting peer.
+    ///
+    /// If the remote peer doesn't have the [`Urn`] yet, it clones it if
+    /// permitted. Peers which are not permitted are told [`Error::Forbidden`]
+    /// regardless of whether the remote peer has the [`Urn`].
+    #[n(5)]
+    #[cbor(array)]
+    PleasePull(#[n(0)] Urn, #[n(1)] Option<capability::Token>),
+
+    /// Request the content of the large object with the given [`large::Oid`],
+    /// starting at the given offset.
+    ///
+    /// Large objects are not part of the packfiles exchanged during
+    /// replication, see [`large`].
+    #[n(6)]
+    #[cbor(array)]
+    GetLargeObject(#[n(0)] large::Oid, #[n(1)] u64),
+}
+
+impl Request {
+    /// The [`Features`] the remote peer must support to understand the
+    /// request.
+    pub fn required_features(&self) -> Features {
+        match self {
+            Self::GetAdvertisement | Self::EchoAddr | Self::GetUrns => Features::empty(),
+            Self::Ping
+            | Self::GetSigrefTips(_)
+            | Self::PleasePull(..)
+            | Self::GetLargeObject(..) => Features::INTERROGATION,
+        }
+    }
 }
 
 #[derive(minicbor::Encode, minicbor::Decode)]
@@ -56,6 +118,36 @@ where
     #[n(3)]
     #[cbor(array)]
     Urns(#[n(0)] Cow<'a, xor::Xor>),
+
+    /// Response to a [`Request::Ping`].
+    #[n(4)]
+    #[cbor(array)]
+    Pong,
+
+    /// Response to a [`Request::GetSigrefTips`].
+    ///
+    /// Empty if the responder doesn't have the requested [`Urn`].
+    #[n(5)]
+    #[cbor(array)]
+    SigrefTips(#[n(0)] BTreeMap<PeerId, ext::Oid>),
+
+    /// Response to a [`Request::PleasePull`], carrying the number of refs
+    /// updated by the fetch.
+    ///
+    /// Zero if the requesting peer did not serve any refs the responder is
+    /// interested in, or if they were already up-to-date.
+    #[n(6)]
+    #[cbor(array)]
+    Pulled(#[n(0)] u32),
+
+    /// Response to a [`Request::GetLargeObject`], carrying at most
+    /// [`large::CHUNK_SIZE`] bytes.
+    ///
+    /// If the responder doesn't have the object, the response is
+    /// [`Error::NotFound`].
+    #[n(7)]
+    #[cbor(array)]
+    LargeObject(#[n(0)] large::Chunk),
 }
 
 /// Error response.
@@ -73,6 +165,12 @@ pub enum Error {
     /// A retry after a small timeout is acceptable.
     TemporarilyUnavailable,
 
+    /// The requesting peer is not permitted to make the request.
+    Forbidden,
+
+    /// The requested resource is not present on the responder.
+    NotFound,
+
     /// Catch-all for unknown error codes (forwards-compatibility).
     ///
     /// This is for decoding, **do not** construct this variant.
@@ -84,6 +182,8 @@ impl Error {
         match self {
             Error::Internal => 0,
             Error::TemporarilyUnavailable => 1,
+            Error::Forbidden => 2,
+            Error::NotFound => 3,
             Error::Unknown(n) => *n,
         }
     }
@@ -94,6 +194,8 @@ impl From<u8> for Error {
         match n {
             0 => Self::Internal,
             1 => Self::TemporarilyUnavailable,
+            2 => Self::Forbidden,
+            3 => Self::NotFound,
             x => Self::Unknown(x),
         }
     }
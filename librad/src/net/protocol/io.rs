@@ -8,9 +8,11 @@ use std::{iter, net::SocketAddr};
 use data::BoundedVec;
 
 use super::{
+    features::Features,
     gossip,
     info::{PartialPeerInfo, PeerAdvertisement},
     membership,
+    version::{self, Versions},
     Endpoint,
     ProtocolStorage,
     State,
@@ -41,12 +43,14 @@ where
         return;
     }
 
-    if let Some((conn, ingress)) = connect(&state.endpoint, peer, addrs).await {
+    if let Some((conn, ingress)) = connect(&state, peer, addrs).await {
         let rpc_sent = send_rpc::<_, ()>(
             &conn,
             state
                 .membership
                 .hello(peer_advertisement(&state.endpoint)()),
+            // We don't know the versions `peer` supports until it replies
+            version::LEGACY,
         )
         .await;
 
@@ -86,6 +90,8 @@ pub(super) fn peer_advertisement(
         PeerAdvertisement {
             listen_addrs,
             capabilities: Default::default(),
+            versions: Versions::SUPPORTED,
+            features: Features::SUPPORTED,
         }
     }
 }
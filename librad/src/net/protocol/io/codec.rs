@@ -7,10 +7,10 @@ use std::net::SocketAddr;
 
 use crate::net::{
     codec::CborCodec,
-    protocol::{broadcast, membership},
+    protocol::{broadcast, membership, version::Versioned},
 };
 
 pub type Codec<T> = CborCodec<T, T>;
 
-pub type Gossip<T> = Codec<broadcast::Message<SocketAddr, T>>;
-pub type Membership = Codec<membership::Message<SocketAddr>>;
+pub type Gossip<T> = Codec<Versioned<broadcast::Message<SocketAddr, T>>>;
+pub type Membership = Codec<Versioned<membership::Message<SocketAddr>>>;
@@ -16,7 +16,8 @@ pub use super::error;
 use super::streams;
 use crate::{
     net::{
-        protocol::{event::upstream as event, gossip, Endpoint, ProtocolStorage, State},
+        connection::CloseReason,
+        protocol::{event::upstream as event, gossip, ProtocolStorage, State},
         quic,
     },
     PeerId,
@@ -57,7 +58,7 @@ where
                 Connection(_) | PeerId(_) | RemoteIdUnavailable | SelfConnect => {
                     tracing::warn!(err = %err, "ingress connections error");
                 },
-                Connect(_) | Endpoint(_) | Io(_) | Shutdown | Signer(_) => {
+                Connect(_) | Endpoint(_) | Io(_) | Shutdown | Signer(_) | Transport(_) => {
                     state.phone.emit(event::Endpoint::Down);
                     return Err(err.into());
                 },
@@ -69,9 +70,14 @@ where
     Err(error::Accept::Done)
 }
 
-#[tracing::instrument(skip(endpoint, addrs))]
-pub async fn connect<'a, Addrs>(
-    endpoint: &Endpoint,
+/// Connect to `remote_id` at any of `addrs`.
+///
+/// Connections to a peer other than the one pinned for the address are
+/// refused as per the [`State::trust`] policy, in which case the other
+/// addresses are still tried.
+#[tracing::instrument(skip(state, addrs))]
+pub async fn connect<'a, S, Addrs>(
+    state: &State<S>,
     remote_id: PeerId,
     addrs: Addrs,
 ) -> Option<(
@@ -94,16 +100,21 @@ where
         None
     } else {
         future::select_ok(addrs.iter().map(|addr| {
-            let mut endpoint = endpoint.clone();
+            let mut endpoint = state.endpoint.clone();
             tracing::info!(remote_addr = %addr, "establishing connection");
             Box::pin(async move {
-                endpoint
+                let (conn, ingress) = endpoint
                     .connect(remote_id, addr)
                     .map_err(|e| {
                         tracing::warn!(err = ?e, remote_addr = %addr, "could not connect");
-                        e
                     })
-                    .await
+                    .await?;
+                if state.trust(&conn) {
+                    Ok((conn, ingress))
+                } else {
+                    conn.close(CloseReason::UntrustedPeer);
+                    Err(())
+                }
             })
         }))
         .await
@@ -5,15 +5,21 @@
 
 use std::{collections::BTreeSet, iter, net::SocketAddr, str::FromStr, time::Duration};
 
+use either::Either;
+
 use crate::{
     executor,
     git::{
         fetch::{Fetcher as _, RemoteHeads},
+        identities,
         refs::{self, Refs, Remotes},
         replication,
-        storage::{self, fetcher},
+        storage::{self, fetcher, ReadOnlyStorage as _},
+        tracking,
         Urn,
     },
+    identities::git::SomeIdentity,
+    net::protocol::Features,
     PeerId,
 };
 
@@ -41,6 +47,18 @@ pub mod error {
 
         #[error(transparent)]
         Pool(#[from] storage::PoolError),
+
+        #[error(transparent)]
+        Tracking(#[from] tracking::Error),
+
+        #[error(transparent)]
+        Identities(#[from] Box<identities::Error>),
+    }
+
+    impl From<identities::Error> for Rere {
+        fn from(e: identities::Error) -> Self {
+            Self::from(Box::new(e))
+        }
     }
 
     impl From<replication::Error> for Rere {
@@ -55,7 +73,80 @@ pub mod config {
 
     pub struct Rere {
         pub replication: replication::Config,
+        pub slots: replication::slots::Slots,
         pub fetch_slot_wait_timeout: Duration,
+        /// The [`Features`] negotiated with the remote peer, if known.
+        pub features: Option<Features>,
+    }
+}
+
+/// Policy determining which remote peers are allowed to trigger a [`rere`].
+///
+/// Note that the policy is checked in addition to [`is_interesting`], ie. even
+/// a permitted peer will only cause a replication if it serves refs we care
+/// about. If the [`Urn`] is not present locally, a permitted [`rere`] clones
+/// it instead.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Policy {
+    /// Accept from any peer.
+    ///
+    /// For a [`Urn`] which is not present locally, this is the same as
+    /// [`Policy::Tracked`]: anyone could otherwise make us clone arbitrary
+    /// data.
+    Any,
+    /// Accept only from peers which are tracked in the context of the [`Urn`],
+    /// or which are delegates of the identity.
+    Tracked,
+    /// Accept only from delegates of the identity.
+    Delegates,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self::Any
+    }
+}
+
+/// Determine if `remote_peer` is allowed to trigger a [`rere`] of `urn` acc.
+/// to the given [`Policy`].
+#[tracing::instrument(level = "debug", skip(storage))]
+pub async fn is_permitted<S>(
+    storage: &S,
+    policy: Policy,
+    urn: &Urn,
+    remote_peer: PeerId,
+) -> Result<bool, error::Rere>
+where
+    S: storage::Pooled<storage::Storage> + Send + Sync + 'static,
+{
+    let storage = storage.get().await?;
+    let identity = identities::any::get(&*storage, urn)?;
+    // Without the identity, there are no delegates to go by, and only a
+    // tracking relationship established beforehand can permit a clone.
+    let policy = match (policy, &identity) {
+        (Policy::Any, Some(_)) => return Ok(true),
+        (Policy::Any, None) => Policy::Tracked,
+        (policy, _) => policy,
+    };
+    let is_delegate = match identity {
+        Some(SomeIdentity::Project(proj)) => proj.delegations().iter().any(|d| match d {
+            Either::Left(pk) => PeerId::from(*pk) == remote_peer,
+            Either::Right(person) => person
+                .delegations()
+                .iter()
+                .any(|pk| PeerId::from(*pk) == remote_peer),
+        }),
+        Some(SomeIdentity::Person(person)) => person
+            .delegations()
+            .iter()
+            .any(|pk| PeerId::from(*pk) == remote_peer),
+        _ => false,
+    };
+
+    match policy {
+        Policy::Any => Ok(true),
+        Policy::Delegates => Ok(is_delegate),
+        Policy::Tracked => Ok(is_delegate || tracking::is_tracked(&*storage, urn, remote_peer)?),
     }
 }
 
@@ -67,6 +158,9 @@ pub mod config {
 /// the advertised remote tracking branches of the remote peer (plus the remote
 /// peer itself).
 ///
+/// If the [`Urn`] is not present locally, it is cloned from the `remote_peer`
+/// unconditionally. Callers are expected to check [`is_permitted`] first.
+///
 /// This function is invoked when the `remote_peer` initiates a fetch (hence
 /// "rere" -- replicate replicate), as per [`super::recv::git`].
 ///
@@ -88,12 +182,23 @@ where
     S: storage::Pooled<storage::Storage> + Send + Sync + 'static,
     Addrs: IntoIterator<Item = SocketAddr>,
 {
+    let _permit = config.slots.acquire(&urn, remote_peer).await;
     fetcher::retrying(
         spawner,
         storage,
-        fetcher::PeerToPeer::new(urn.clone(), remote_peer, addr_hints).nonced(false),
+        fetcher::PeerToPeer::new(urn.clone(), remote_peer, addr_hints)
+            .nonced(false)
+            .features(config.features),
         config.fetch_slot_wait_timeout,
         move |storage, fetcher| {
+            if !storage.has_urn(&urn)? {
+                tracing::debug!("cloning");
+                return Ok(Some(
+                    replication::replicate(storage, fetcher, config.replication.clone(), None)
+                        .map_err(error::Rere::from)?,
+                ));
+            }
+
             let remote_heads = fetcher.remote_heads();
             let refs = Refs::load(&storage, &urn, None)
                 .map_err(error::Rere::from)?
@@ -104,7 +209,7 @@ where
             if is_interesting(remote_peer, remote_heads, &refs.remotes) {
                 tracing::debug!("interesting");
                 Ok(Some(
-                    replication::replicate(storage, fetcher, config.replication, None)
+                    replication::replicate(storage, fetcher, config.replication.clone(), None)
                         .map_err(error::Rere::from)?,
                 ))
             } else {
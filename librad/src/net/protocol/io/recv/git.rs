@@ -3,7 +3,7 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::{io, net::SocketAddr};
+use std::{io, net::SocketAddr, time::SystemTime};
 
 use futures::{
     future::{self, TryFutureExt as _},
@@ -15,6 +15,7 @@ use thiserror::Error;
 use crate::{
     git::{replication::ReplicateResult, Urn},
     net::{
+        capability,
         connection::{Duplex, RemoteInfo},
         protocol::{self, control, gossip, io::graft, ProtocolStorage, State},
         upgrade::{self, Upgraded},
@@ -23,7 +24,7 @@ use crate::{
 };
 
 #[derive(Debug, Error)]
-enum Error {
+pub(in crate::net::protocol) enum Error {
     #[error(transparent)]
     Rere(#[from] graft::error::Rere),
 
@@ -31,6 +32,20 @@ enum Error {
     Io(#[from] io::Error),
 }
 
+/// The outcome of a [`rere`].
+#[derive(Clone, Copy, Debug)]
+pub(in crate::net::protocol) enum Rere {
+    /// The remote peer exceeded its [`protocol::Quota::graft`].
+    RateLimited,
+    /// The remote peer is not permitted to trigger a replication, as per the
+    /// [`graft::Policy`].
+    NotPermitted,
+    /// The remote peer doesn't serve any refs we're interested in.
+    Skipped,
+    /// The replication updated this many refs.
+    Updated(usize),
+}
+
 pub(in crate::net::protocol) async fn git<S, T>(state: State<S>, stream: Upgraded<upgrade::Git, T>)
 where
     S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + Clone + 'static,
@@ -53,7 +68,9 @@ where
                     if let Some(n) = nonce {
                         // Only rere if we have a fresh nonce
                         if !state.nonces.contains(&n) {
-                            return rere(state.clone(), repo, remote_peer, remote_addr).await;
+                            return rere(state.clone(), repo, remote_peer, remote_addr, None)
+                                .await
+                                .map(|_| ());
                         }
                     }
 
@@ -72,26 +89,65 @@ where
     }
 }
 
+/// Replicate `urn` from `remote_peer`, if permitted.
+///
+/// A valid capability `token` permits the replication regardless of the
+/// [`graft::Policy`]. Invalid tokens are ignored, ie. the policy applies.
 #[tracing::instrument(
-    skip(state, urn, remote_peer, remote_addr),
+    skip(state, urn, remote_peer, remote_addr, token),
     fields(urn = %urn, remote_peer = %remote_peer)
 )]
-async fn rere<S>(
+pub(in crate::net::protocol) async fn rere<S>(
     state: State<S>,
     urn: Urn,
     remote_peer: PeerId,
     remote_addr: SocketAddr,
-) -> Result<(), Error>
+    token: Option<capability::Token>,
+) -> Result<Rere, Error>
 where
     S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + Clone + 'static,
 {
     use protocol::event::downstream::Gossip::Announce;
 
+    let granted = match token {
+        None => false,
+        Some(token) => match token.check(
+            &state.local_id,
+            &remote_peer,
+            &urn,
+            capability::Operation::Replicate,
+            SystemTime::now(),
+        ) {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::warn!(err = ?e, "ignoring invalid capability token");
+                false
+            },
+        },
+    };
+
+    if !granted
+        && !graft::is_permitted(&state.storage, state.config.graft.policy, &urn, remote_peer)
+            .await?
+    {
+        tracing::info!(policy = ?state.config.graft.policy, "rere not permitted");
+        return Ok(Rere::NotPermitted);
+    }
+
+    // Only permitted replications count towards the rate limit, so peers
+    // which are not allowed to trigger any can't use up the quota of others.
+    if state.limits.graft.check_key(&remote_peer).is_err() {
+        tracing::warn!("rere rate limit breached");
+        return Ok(Rere::RateLimited);
+    }
+
     tracing::info!("attempting rere");
 
     let config = graft::config::Rere {
-        replication: state.config.replication,
+        replication: state.config.replication.clone(),
+        slots: state.caches.slots.clone(),
         fetch_slot_wait_timeout: state.config.fetch.fetch_slot_wait_timeout,
+        features: state.caches.features.get(&remote_peer),
     };
     let updated_tips = graft::rere(
         &state.spawner,
@@ -106,9 +162,13 @@ where
     .map(|ReplicateResult { updated_tips, .. }| updated_tips);
 
     match updated_tips {
-        None => tracing::info!("rere skipped"),
+        None => {
+            tracing::info!("rere skipped");
+            Ok(Rere::Skipped)
+        },
         Some(xs) => {
-            tracing::info!("rere updated {} refs", xs.len());
+            let updated = xs.len();
+            tracing::info!("rere updated {} refs", updated);
             if !xs.is_empty() {
                 tracing::trace!("refs updated by rere: {:?}", xs);
             }
@@ -132,9 +192,9 @@ where
                 })
                 .collect::<FuturesUnordered<_>>()
                 .for_each(future::ready)
-                .await
+                .await;
+
+            Ok(Rere::Updated(updated))
         },
     }
-
-    Ok(())
 }
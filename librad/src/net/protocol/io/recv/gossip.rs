@@ -20,6 +20,7 @@ use crate::{
             info::PeerInfo,
             io::{codec, peer_advertisement},
             membership,
+            version::Versioned,
             ProtocolStorage,
             State,
         },
@@ -46,6 +47,8 @@ pub(in crate::net::protocol) async fn gossip<S, T>(
         match x {
             Err(e) => {
                 tracing::warn!(err = ?e, "gossip recv error");
+                state.versions.forget(&remote_id);
+                state.caches.features.forget(&remote_id);
                 let membership::TnT { trans, ticks } = state.membership.connection_lost(remote_id);
                 state.emit(trans);
                 state
@@ -59,7 +62,7 @@ pub(in crate::net::protocol) async fn gossip<S, T>(
                 break;
             },
 
-            Ok(msg) => {
+            Ok(Versioned { message: msg, .. }) => {
                 let peer_info = || PeerInfo {
                     peer_id: state.local_id,
                     advertised_info: peer_advertisement(&state.endpoint)(),
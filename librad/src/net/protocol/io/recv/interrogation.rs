@@ -15,25 +15,42 @@ use thiserror::Error;
 use typenum::Unsigned as _;
 
 use crate::{
-    git::storage,
+    git::{
+        large,
+        refs,
+        storage::{self, Pooled as _},
+    },
     identities::xor,
     net::{
-        connection::Duplex,
+        connection::{Duplex, RemoteInfo},
         protocol::{
-            cache,
+            gossip,
             interrogation::{self, Request, Response},
             io::{self, codec},
-            Endpoint,
+            ProtocolStorage,
             State,
         },
         upgrade::{self, Upgraded},
     },
+    PeerId,
 };
 
 #[derive(Debug, Error)]
 enum Error {
     #[error(transparent)]
     Cbor(#[from] minicbor::encode::Error<std::io::Error>),
+
+    #[error(transparent)]
+    Large(#[from] large::Error),
+
+    #[error(transparent)]
+    Pool(#[from] storage::PoolError),
+
+    #[error(transparent)]
+    Refs(#[from] refs::stored::Error),
+
+    #[error(transparent)]
+    Rere(#[from] super::git::Error),
 }
 
 lazy_static! {
@@ -45,13 +62,14 @@ pub(in crate::net::protocol) async fn interrogation<S, T>(
     state: State<S>,
     stream: Upgraded<upgrade::Interrogation, T>,
 ) where
-    S: storage::Pooled<storage::Storage> + Send + 'static,
-    T: Duplex<Addr = SocketAddr>,
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + Clone + 'static,
+    T: Duplex + RemoteInfo<Addr = SocketAddr>,
     T::Read: AsyncRead + Unpin,
     T::Write: AsyncWrite + Unpin,
 {
     const BUFSIZ: usize = xor::MaxFingerprints::USIZE * 3;
 
+    let remote_peer = stream.remote_peer_id();
     let remote_addr = stream.remote_addr();
 
     let (recv, send) = stream.into_stream().split();
@@ -63,13 +81,12 @@ pub(in crate::net::protocol) async fn interrogation<S, T>(
         match x {
             Err(e) => tracing::warn!(err = ?e, "interrogation recv error"),
             Ok(req) => {
-                let resp = handle_request(&state.endpoint, &state.caches.urns, remote_addr, req)
+                let resp = handle_request(&state, remote_peer, remote_addr, req)
+                    .await
                     .map(Cow::from)
                     .unwrap_or_else(|e| {
                         tracing::error!(err = ?e, "error handling request");
-                        match e {
-                            Error::Cbor(_) => Cow::from(&*INTERNAL_ERROR),
-                        }
+                        Cow::from(&*INTERNAL_ERROR)
                     });
 
                 if let Err(e) = send.into_sink().send(resp).await {
@@ -80,23 +97,61 @@ pub(in crate::net::protocol) async fn interrogation<S, T>(
     }
 }
 
-fn handle_request(
-    endpoint: &Endpoint,
-    urns: &cache::urns::Filter,
+async fn handle_request<S>(
+    state: &State<S>,
+    remote_peer: PeerId,
     remote_addr: SocketAddr,
     req: interrogation::Request,
-) -> Result<Vec<u8>, Error> {
+) -> Result<Vec<u8>, Error>
+where
+    S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + Clone + 'static,
+{
     use either::Either::*;
 
     match req {
-        Request::GetAdvertisement => {
-            Left(Response::Advertisement(io::peer_advertisement(endpoint)()))
-        },
+        Request::GetAdvertisement => Left(Response::Advertisement(io::peer_advertisement(
+            &state.endpoint,
+        )())),
         Request::EchoAddr => Left(Response::YourAddr(remote_addr)),
+        Request::Ping => Left(Response::Pong),
         Request::GetUrns => {
-            let urns = urns.get();
+            let urns = state.caches.urns.get();
             Right(encode(&Response::<SocketAddr>::Urns(Cow::Borrowed(&urns))))
         },
+        Request::GetSigrefTips(urn) => {
+            let storage = state.storage.get().await?;
+            let tips = state
+                .spawner
+                .blocking(move || refs::tips(&storage, &urn.with_path(None)))
+                .await?;
+            Left(Response::SigrefTips(tips))
+        },
+        Request::PleasePull(urn, token) => {
+            use super::git::Rere;
+
+            // Unknown URNs are subject to the same checks, so as to not tell
+            // peers which aren't permitted to pull whether we have them.
+            let urn = urn.with_path(None);
+            match super::git::rere(state.clone(), urn, remote_peer, remote_addr, token).await? {
+                Rere::RateLimited => Left(Response::Error(
+                    interrogation::Error::TemporarilyUnavailable,
+                )),
+                Rere::NotPermitted => Left(Response::Error(interrogation::Error::Forbidden)),
+                Rere::Skipped => Left(Response::Pulled(0)),
+                Rere::Updated(n) => Left(Response::Pulled(n as u32)),
+            }
+        },
+        Request::GetLargeObject(oid, offset) => {
+            let storage = state.storage.get().await?;
+            let chunk = state
+                .spawner
+                .blocking(move || large::Store::at(storage.path()).read_chunk(&oid, offset))
+                .await?;
+            Left(match chunk {
+                Some(chunk) => Response::LargeObject(chunk),
+                None => Response::Error(interrogation::Error::NotFound),
+            })
+        },
     }
     .right_or_else(|resp| encode(&resp))
 }
@@ -19,6 +19,7 @@ use crate::{
             io::{codec, peer_advertisement},
             membership,
             tick,
+            version::Versioned,
             ProtocolStorage,
             State,
         },
@@ -56,7 +57,7 @@ pub(in crate::net::protocol) async fn membership<S, T>(
                 break;
             },
 
-            Ok(msg) => {
+            Ok(Versioned { message: msg, .. }) => {
                 if state.limits.membership.check_key(&remote_id).is_err() {
                     tracing::warn!(remote_id = %remote_id, "rate limit breached, disconnecting peer");
 
@@ -77,6 +78,15 @@ pub(in crate::net::protocol) async fn membership<S, T>(
                     break;
                 }
 
+                match &msg {
+                    membership::Message::Join { info }
+                    | membership::Message::Neighbour { info, .. } => {
+                        state.versions.record(remote_id, &info.versions);
+                        state.caches.features.record(remote_id, info.features);
+                    },
+                    _ => {},
+                }
+
                 match membership::apply(
                     &state.membership,
                     peer_advertisement(&state.endpoint),
@@ -103,6 +113,9 @@ pub(in crate::net::protocol) async fn connection_lost<S>(state: State<S>, remote
 where
     S: ProtocolStorage<SocketAddr, Update = gossip::Payload> + Clone + 'static,
 {
+    state.versions.forget(&remote_id);
+    state.caches.features.forget(&remote_id);
+    state.rtts.forget(&remote_id);
     let membership::TnT { trans, ticks } = state.membership.connection_lost(remote_id);
     state.emit(trans);
     state
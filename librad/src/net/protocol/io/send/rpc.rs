@@ -10,7 +10,13 @@ use futures_codec::FramedWrite;
 
 use crate::net::{
     connection::{RemoteAddr as _, RemotePeer},
-    protocol::{broadcast, error, io::codec, membership},
+    protocol::{
+        broadcast,
+        error,
+        io::codec,
+        membership,
+        version::{Version, Versioned},
+    },
     quic,
     upgrade,
 };
@@ -45,6 +51,7 @@ impl<A, P> From<broadcast::Message<A, P>> for Rpc<A, P> {
 pub async fn send_rpc<R, P>(
     conn: &quic::Connection,
     rpc: R,
+    version: Version,
 ) -> Result<(), error::Rpc<quic::SendStream>>
 where
     R: Into<Rpc<SocketAddr, P>>,
@@ -82,7 +89,7 @@ where
                 .await
                 .map_err(into_protocol_error)?;
             FramedWrite::new(stream.deref_mut(), codec::Membership::new())
-                .send(msg)
+                .send(Versioned::new(version, msg))
                 .await?;
         },
 
@@ -94,7 +101,7 @@ where
                 .await
                 .map_err(into_protocol_error)?;
             FramedWrite::new(stream.deref_mut(), codec::Gossip::new())
-                .send(msg)
+                .send(Versioned::new(version, msg))
                 .await?;
         },
     }
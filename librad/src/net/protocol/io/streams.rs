@@ -11,7 +11,7 @@ use futures::stream::{Stream, StreamExt as _};
 use super::recv;
 use crate::net::{
     connection::{CloseReason, RemoteAddr as _, RemotePeer},
-    protocol::{gossip, ProtocolStorage, State},
+    protocol::{event::upstream as event, gossip, ProtocolStorage, State},
     quic,
     upgrade,
 };
@@ -38,6 +38,7 @@ pub(in crate::net::protocol) async fn incoming<S, I>(
     use Either::{Left, Right};
 
     let remote_id = streams.remote_peer_id();
+    let remote_addr = streams.remote_addr();
 
     let streams = streams.fuse();
     futures::pin_mut!(streams);
@@ -62,6 +63,13 @@ pub(in crate::net::protocol) async fn incoming<S, I>(
                     },
                     Err(e) => {
                         tracing::warn!(err = ?e, "ingress stream error");
+                        if let quic::Error::Connection(quinn::ConnectionError::TimedOut) = e {
+                            tracing::info!("peer considered dead after idle timeout");
+                            state.phone.emit(event::Connection::Dead {
+                                remote_peer: remote_id,
+                                remote_addr,
+                            });
+                        }
                         recv::connection_lost(state, remote_id).await;
                         break;
                     },
@@ -88,7 +96,7 @@ mod incoming {
                 stream.close(CloseReason::InvalidUpgrade)
             },
 
-            Ok(Git(up)) => recv::git(state, up).await,
+            Ok(Git(up)) => recv::git(state, up.map(quic::BidiStream::allow_reset)).await,
             Ok(Gossip(up)) => recv::gossip(state, up).await,
             Ok(Membership(up)) => recv::membership(state, up).await,
             Ok(Interrogation(up)) => recv::interrogation(state, up).await,
@@ -8,6 +8,7 @@ use std::{
     iter::{self, FromIterator},
     ops::Mul,
     sync::Arc,
+    time::Duration,
 };
 
 use data::BoundedVec;
@@ -122,17 +123,21 @@ where
     Rng: rand::Rng + Clone,
     Addr: Clone + Debug + PartialEq,
 {
+    /// Create the membership state, along with the stream of periodic tasks
+    /// to drive it. Active peers are tickled every `tickle_interval`, which
+    /// should thus be well below the idle timeout of connections.
     pub fn new(
         local_id: PeerId,
         rng: Rng,
         params: Params,
+        tickle_interval: Duration,
     ) -> (Self, impl Stream<Item = Periodic<Addr>>)
     where
         Rng: Send + Sync + 'static,
         Addr: Send + Sync + 'static,
     {
         let this = Self(Arc::new(RwLock::new(HpvInner::new(local_id, rng, params))));
-        let periodic = periodic_tasks(this.clone());
+        let periodic = periodic_tasks(this.clone(), tickle_interval);
 
         (this, periodic)
     }
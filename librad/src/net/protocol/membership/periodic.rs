@@ -19,7 +19,7 @@ use futures_timer::Delay;
 use rand::Rng as _;
 
 use super::{Hpv, Shuffle};
-use crate::net::{protocol::info::PeerInfo, quic::MAX_IDLE_TIMEOUT};
+use crate::net::protocol::info::PeerInfo;
 
 pub enum Periodic<A> {
     RandomPromotion { candidates: Vec<PeerInfo<A>> },
@@ -28,7 +28,10 @@ pub enum Periodic<A> {
 }
 
 #[tracing::instrument(skip(hpv))]
-pub(super) fn periodic_tasks<Rng, Addr>(hpv: Hpv<Rng, Addr>) -> impl Stream<Item = Periodic<Addr>>
+pub(super) fn periodic_tasks<Rng, Addr>(
+    hpv: Hpv<Rng, Addr>,
+    tickle_interval: Duration,
+) -> impl Stream<Item = Periodic<Addr>>
 where
     Rng: rand::Rng + Clone + 'static,
     Addr: Clone + Debug + PartialEq + Send + Sync + 'static,
@@ -57,7 +60,7 @@ where
             }
         });
 
-    let tickle = Interval::new(MAX_IDLE_TIMEOUT.div_f32(2.0), Duration::from_secs(5))
+    let tickle = Interval::new(tickle_interval, Duration::from_secs(5))
         .filter_map(|_| future::ready(Some(Periodic::Tickle)));
 
     // Wrapping the `select` calls is the most effective to combine the three
@@ -0,0 +1,69 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Round-trip time estimates of connected peers.
+//!
+//! Peers we hold a connection to are periodically sent a
+//! [`super::interrogation::Request::Ping`]. The time until the corresponding
+//! `Pong` arrives is smoothed into an estimate per peer, which is reported
+//! via [`super::event::downstream::Stats`], and can be used to prefer nearby
+//! providers, see [`rank`].
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use parking_lot::RwLock;
+
+use super::PeerInfo;
+use crate::PeerId;
+
+/// How often connected peers are pinged.
+pub const PING_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long to wait for a `Pong` before considering a ping lost.
+pub const PING_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The round-trip time estimates of connected peers.
+#[derive(Clone, Default)]
+pub(super) struct Rtts {
+    inner: Arc<RwLock<HashMap<PeerId, Duration>>>,
+}
+
+impl Rtts {
+    /// Record a new RTT `sample` for `peer`, returning the updated estimate.
+    ///
+    /// Like the smoothed RTT of TCP (RFC 6298), the estimate is an
+    /// exponentially weighted moving average giving a weight of 1/8 to the
+    /// new sample.
+    pub fn record(&self, peer: PeerId, sample: Duration) -> Duration {
+        let mut inner = self.inner.write();
+        let rtt = inner
+            .entry(peer)
+            .and_modify(|rtt| *rtt = (*rtt * 7 + sample) / 8)
+            .or_insert(sample);
+        *rtt
+    }
+
+    pub fn get(&self, peer: &PeerId) -> Option<Duration> {
+        self.inner.read().get(peer).copied()
+    }
+
+    /// Forget the estimate for `peer`, eg. because the connection was lost.
+    pub fn forget(&self, peer: &PeerId) {
+        self.inner.write().remove(peer);
+    }
+
+    pub fn snapshot(&self) -> HashMap<PeerId, Duration> {
+        self.inner.read().clone()
+    }
+}
+
+/// Order `providers` by ascending round-trip time, as reported in
+/// [`super::event::downstream::Stats::rtt`].
+///
+/// Providers without an estimate are ordered last, retaining their relative
+/// order.
+pub fn rank<Addr>(providers: &mut [PeerInfo<Addr>], rtt: &HashMap<PeerId, Duration>) {
+    providers.sort_by_key(|info| rtt.get(&info.peer_id).copied().unwrap_or(Duration::MAX))
+}
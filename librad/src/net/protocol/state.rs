@@ -3,7 +3,7 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::{net::SocketAddr, ops::Deref, sync::Arc};
+use std::{collections::BTreeMap, net::SocketAddr, ops::Deref, sync::Arc};
 
 use futures::future::TryFutureExt as _;
 use nonzero_ext::nonzero;
@@ -19,7 +19,10 @@ use super::{
     io,
     membership,
     nonce,
+    rtt,
+    throttle,
     tick,
+    version,
     Endpoint,
     ProtocolStorage,
     TinCans,
@@ -34,15 +37,21 @@ use crate::{
         replication,
         storage::{self, PoolError, PooledRef},
     },
-    net::{quic, upgrade},
+    net::{
+        connection::{RemoteAddr as _, RemotePeer as _},
+        quic,
+        tofu,
+        upgrade,
+    },
     rate_limit::{self, Direct, Keyed, RateLimiter},
     PeerId,
 };
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub(super) struct StateConfig {
     pub replication: replication::Config,
     pub fetch: config::Fetch,
+    pub graft: config::Graft,
 }
 
 /// Runtime state of a protocol instance.
@@ -61,6 +70,10 @@ pub(super) struct State<S> {
     pub caches: cache::Caches,
     pub spawner: Arc<executor::Spawner>,
     pub limits: RateLimits,
+    pub versions: version::Negotiated,
+    pub rtts: rtt::Rtts,
+    pub pinned: Arc<BTreeMap<PeerId, Vec<SocketAddr>>>,
+    pub tofu: Option<tofu::Tofu>,
 }
 
 impl<S> State<S> {
@@ -73,6 +86,55 @@ impl<S> State<S> {
             self.phone.emit(evt)
         }
     }
+
+    /// Check the remote peer of the outgoing connection `conn` against the
+    /// [`tofu::Tofu`] pins, if enabled. Returns `false` if the connection
+    /// must be refused.
+    ///
+    /// New pins are written to disk in the background.
+    pub fn trust(&self, conn: &quic::Connection) -> bool {
+        let tofu = match &self.tofu {
+            None => return true,
+            Some(tofu) => tofu,
+        };
+        let remote_peer = conn.remote_peer_id();
+        let remote_addr = conn.remote_addr();
+        let verdict = tofu.check(remote_addr, remote_peer);
+        if tofu.is_dirty() {
+            let tofu = tofu.clone();
+            let spawner = self.spawner.clone();
+            self.spawner
+                .spawn(async move {
+                    if let Err(e) = spawner.blocking(move || tofu.flush()).await {
+                        tracing::warn!(err = %e, "failed to store peer pins");
+                    }
+                })
+                .detach();
+        }
+        match verdict {
+            tofu::Verdict::Pinned => {
+                tracing::debug!(%remote_addr, "pinned peer");
+                true
+            },
+            tofu::Verdict::Trusted => true,
+            tofu::Verdict::Mismatch { pinned } => {
+                let refused = tofu.policy() == tofu::Policy::Refuse;
+                tracing::warn!(
+                    %remote_addr,
+                    %pinned,
+                    refused,
+                    "peer doesn't match the one pinned for its address"
+                );
+                self.phone.emit(event::upstream::Connection::Mismatch {
+                    remote_peer,
+                    remote_addr,
+                    pinned,
+                    refused,
+                });
+                !refused
+            },
+        }
+    }
 }
 
 impl<S> State<S>
@@ -95,7 +157,7 @@ where
     {
         match self.endpoint.get_connection(to) {
             Some(conn) => Some(conn),
-            None => io::connect(&self.endpoint, to, addr_hints)
+            None => io::connect(self, to, addr_hints)
                 .in_current_span()
                 .await
                 .map(|(conn, ingress)| {
@@ -140,7 +202,7 @@ where
                     .instrument(span.clone())
                     .await
                     .ok()?;
-                let upgraded = upgrade::upgrade(stream, upgrade::Git)
+                let upgraded = upgrade::upgrade(stream.allow_reset(), upgrade::Git)
                     .inspect_err(|e| tracing::error!(err = ?e, "unable to upgrade stream"))
                     .instrument(span)
                     .await
@@ -159,6 +221,7 @@ where
 #[derive(Clone)]
 pub(super) struct RateLimits {
     pub membership: Arc<RateLimiter<Keyed<PeerId>>>,
+    pub graft: Arc<RateLimiter<Keyed<PeerId>>>,
 }
 
 /// Rate limit quota.
@@ -175,6 +238,14 @@ pub struct Quota {
     pub membership: rate_limit::Quota,
     /// See [`StorageQuota`].
     pub storage: StorageQuota,
+    /// Replications triggered by a remote peer fetching from us, per remote
+    /// peer.
+    ///
+    /// When a peer exceeds this rate, its fetches will no longer cause the
+    /// local peer to fetch back (see [`super::io::graft::rere`]).
+    ///
+    /// Default: 6/min (burst: 10)
+    pub graft: rate_limit::Quota,
 }
 
 impl Default for Quota {
@@ -183,6 +254,7 @@ impl Default for Quota {
             gossip: GossipQuota::default(),
             membership: rate_limit::Quota::per_second(nonzero!(1u32)).allow_burst(nonzero!(10u32)),
             storage: StorageQuota::default(),
+            graft: rate_limit::Quota::per_minute(nonzero!(6u32)).allow_burst(nonzero!(10u32)),
         }
     }
 }
@@ -193,6 +265,15 @@ pub struct GossipQuota {
     ///
     /// Default: 1/min (burst: 5)
     pub fetches_per_peer_and_urn: rate_limit::Quota,
+    /// Incoming gossip messages per origin peer.
+    ///
+    /// Messages exceeding this rate are dropped, and earn the origin a
+    /// penalty, see [`super::throttle`].
+    ///
+    /// Default: 10/sec (burst: 100)
+    pub messages_per_origin: rate_limit::Quota,
+    /// See [`throttle::Penalty`].
+    pub penalty: throttle::Penalty,
 }
 
 impl Default for GossipQuota {
@@ -200,6 +281,9 @@ impl Default for GossipQuota {
         Self {
             fetches_per_peer_and_urn: rate_limit::Quota::per_minute(nonzero!(1u32))
                 .allow_burst(nonzero!(5u32)),
+            messages_per_origin: rate_limit::Quota::per_second(nonzero!(10u32))
+                .allow_burst(nonzero!(100u32)),
+            penalty: throttle::Penalty::default(),
         }
     }
 }
@@ -240,6 +324,7 @@ impl Default for StorageQuota {
 struct StorageLimits {
     errors: Arc<RateLimiter<Direct>>,
     wants: Arc<RateLimiter<Keyed<PeerId>>>,
+    origins: throttle::Throttle,
 }
 
 #[derive(Clone)]
@@ -249,15 +334,26 @@ pub(super) struct Storage<S> {
 }
 
 impl<S> Storage<S> {
-    pub fn new(inner: S, quota: StorageQuota) -> Self {
+    pub fn new(inner: S, quota: &Quota) -> Self {
         Self {
             inner,
             limits: StorageLimits {
-                errors: Arc::new(RateLimiter::direct(quota.errors)),
-                wants: Arc::new(RateLimiter::keyed(quota.wants, nonzero!(256 * 1024usize))),
+                errors: Arc::new(RateLimiter::direct(quota.storage.errors)),
+                wants: Arc::new(RateLimiter::keyed(
+                    quota.storage.wants,
+                    nonzero!(256 * 1024usize),
+                )),
+                origins: throttle::Throttle::new(
+                    quota.gossip.messages_per_origin,
+                    quota.gossip.penalty,
+                ),
             },
         }
     }
+
+    pub fn throttle_stats(&self) -> throttle::Stats {
+        self.limits.origins.stats()
+    }
 }
 
 impl<S> Deref for Storage<S> {
@@ -296,6 +392,9 @@ impl<S> broadcast::RateLimited for Storage<S> {
         match lim {
             Limit::Errors => self.limits.errors.check().is_err(),
             Limit::Wants { recipient } => self.limits.wants.check_key(recipient).is_err(),
+            Limit::Origin { origin } => {
+                self.limits.origins.check(origin) != throttle::Verdict::Admit
+            },
         }
     }
 }
@@ -0,0 +1,185 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Rate limiting of incoming gossip by origin.
+//!
+//! Every gossip message names the peer it originates from. Messages are
+//! admitted while the origin's token bucket, as configured by
+//! [`super::GossipQuota::messages_per_origin`], is not exhausted. Each message
+//! dropped because of an exhausted bucket adds to a penalty score of the
+//! origin, which decays exponentially over time. While the score exceeds
+//! [`Penalty::threshold`], all messages of the origin are dropped, and add to
+//! the score in turn. An origin flooding the network is thus silenced until it
+//! calms down, instead of being admitted again as soon as its bucket refills.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use nonzero_ext::nonzero;
+use parking_lot::Mutex;
+
+use crate::{
+    rate_limit::{self, Keyed, RateLimiter},
+    PeerId,
+};
+
+/// Scores are pruned once they decayed below this value.
+const NEGLIGIBLE_SCORE: f64 = 0.5;
+
+/// Prune scores once more than this many origins are tracked.
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// Parameters of the penalty imposed on origins exceeding their quota.
+#[derive(Clone, Copy, Debug)]
+pub struct Penalty {
+    /// The score above which all messages of an origin are dropped. Every
+    /// dropped message adds one to the score.
+    ///
+    /// Default: 10
+    pub threshold: u32,
+    /// The time it takes for a score to decay to half its value.
+    ///
+    /// Default: 1min
+    pub half_life: Duration,
+}
+
+impl Default for Penalty {
+    fn default() -> Self {
+        Self {
+            threshold: 10,
+            half_life: Duration::from_secs(60),
+        }
+    }
+}
+
+impl Penalty {
+    /// Scores are capped at this value, such that an origin recovers within a
+    /// bounded period after it stopped flooding.
+    fn max_score(&self) -> f64 {
+        f64::from(self.threshold) * 4.0
+    }
+}
+
+/// The outcome of [`Throttle::check`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Verdict {
+    Admit,
+    /// The origin exceeded its quota.
+    RateLimited,
+    /// The origin is penalised for having exceeded its quota repeatedly.
+    Penalised,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Stats {
+    /// Messages dropped because their origin exceeded its quota.
+    pub dropped_rate_limited: u64,
+    /// Messages dropped because their origin was penalised.
+    pub dropped_penalised: u64,
+    /// The number of origins currently penalised.
+    pub penalised: usize,
+}
+
+#[derive(Clone, Copy)]
+struct Score {
+    value: f64,
+    updated: Instant,
+}
+
+impl Score {
+    fn decayed(&self, half_life: Duration, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(self.updated);
+        self.value * 0.5f64.powf(elapsed.as_secs_f64() / half_life.as_secs_f64().max(f64::EPSILON))
+    }
+}
+
+#[derive(Clone)]
+pub struct Throttle {
+    limiter: Arc<RateLimiter<Keyed<PeerId>>>,
+    penalty: Penalty,
+    scores: Arc<Mutex<HashMap<PeerId, Score>>>,
+    dropped_rate_limited: Arc<AtomicU64>,
+    dropped_penalised: Arc<AtomicU64>,
+}
+
+impl Throttle {
+    pub fn new(quota: rate_limit::Quota, penalty: Penalty) -> Self {
+        Self {
+            limiter: Arc::new(RateLimiter::keyed(quota, nonzero!(256 * 1024usize))),
+            penalty,
+            scores: Arc::new(Mutex::new(HashMap::new())),
+            dropped_rate_limited: Arc::new(AtomicU64::new(0)),
+            dropped_penalised: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Determine whether a message from `origin` should be admitted.
+    pub fn check(&self, origin: &PeerId) -> Verdict {
+        self.check_at(origin, Instant::now())
+    }
+
+    fn check_at(&self, origin: &PeerId, now: Instant) -> Verdict {
+        let mut scores = self.scores.lock();
+        let current = scores
+            .get(origin)
+            .map(|score| score.decayed(self.penalty.half_life, now))
+            .unwrap_or(0.0);
+
+        let verdict = if current > f64::from(self.penalty.threshold) {
+            Verdict::Penalised
+        } else if self.limiter.check_key(origin).is_err() {
+            Verdict::RateLimited
+        } else {
+            Verdict::Admit
+        };
+
+        match verdict {
+            Verdict::Admit => {},
+            Verdict::RateLimited => {
+                self.dropped_rate_limited.fetch_add(1, Ordering::Relaxed);
+            },
+            Verdict::Penalised => {
+                self.dropped_penalised.fetch_add(1, Ordering::Relaxed);
+            },
+        }
+        if verdict != Verdict::Admit {
+            scores.insert(
+                *origin,
+                Score {
+                    value: (current + 1.0).min(self.penalty.max_score()),
+                    updated: now,
+                },
+            );
+        }
+        if scores.len() > PRUNE_THRESHOLD {
+            let half_life = self.penalty.half_life;
+            scores.retain(|_, score| score.decayed(half_life, now) >= NEGLIGIBLE_SCORE);
+        }
+
+        verdict
+    }
+
+    pub fn stats(&self) -> Stats {
+        let now = Instant::now();
+        let threshold = f64::from(self.penalty.threshold);
+        let penalised = self
+            .scores
+            .lock()
+            .values()
+            .filter(|score| score.decayed(self.penalty.half_life, now) > threshold)
+            .count();
+        Stats {
+            dropped_rate_limited: self.dropped_rate_limited.load(Ordering::Relaxed),
+            dropped_penalised: self.dropped_penalised.load(Ordering::Relaxed),
+            penalised,
+        }
+    }
+}
@@ -86,7 +86,7 @@ where
                 },
 
                 Some(conn) => {
-                    io::send_rpc(&conn, message)
+                    io::send_rpc(&conn, message, state.versions.get(&to))
                         .map_err(|e| {
                             let membership::TnT { trans, ticks: cont } =
                                 state.membership.connection_lost(to);
@@ -102,17 +102,22 @@ where
             },
 
             AttemptSend { to, message } => {
+                let version = state.versions.get(&to.peer_id);
                 let conn = state
                     .connection(to.peer_id, to.addrs().copied().collect::<Vec<_>>())
                     .await
                     .ok_or(error::BestEffortSend::CouldNotConnect { to })?;
-                Ok(io::send_rpc(&conn, message)
+                Ok(io::send_rpc(&conn, message, version)
                     .await
                     .map_err(error::BestEffortSend::SendGossip)?)
             },
 
             Disconnect { peer } => {
-                state.endpoint.disconnect(&peer);
+                if state.pinned.contains_key(&peer) {
+                    tracing::debug!(remote_id = %peer, "not disconnecting pinned peer");
+                } else {
+                    state.endpoint.disconnect(&peer);
+                }
                 Ok(())
             },
         }
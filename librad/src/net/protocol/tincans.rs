@@ -3,7 +3,12 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::{net::SocketAddr, sync::Arc};
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use parking_lot::Mutex;
 pub use tokio::sync::broadcast::error::RecvError;
@@ -16,7 +21,13 @@ use super::{
     info::PeerAdvertisement,
     interrogation,
 };
-use crate::{identities::xor::Xor, PeerId};
+use crate::{
+    git::{large, Urn},
+    git_ext as ext,
+    identities::xor::Xor,
+    net::capability,
+    PeerId,
+};
 
 #[derive(Clone)]
 pub struct TinCans {
@@ -200,6 +211,93 @@ impl Interrogation {
             })
     }
 
+    /// Ask the interrogated peer for the tips of the `rad/signed_refs` it has
+    /// for `urn`, keyed by the peer they belong to.
+    ///
+    /// Comparing them to the local ones tells whether a replication from the
+    /// interrogated peer would yield any updates, or whether it has received
+    /// changes we pushed to it. The result is empty if the peer doesn't have
+    /// `urn`.
+    pub async fn sigref_tips(
+        &self,
+        urn: Urn,
+    ) -> Result<BTreeMap<PeerId, ext::Oid>, error::Interrogation> {
+        use interrogation::{Request, Response};
+
+        self.request(Request::GetSigrefTips(urn))
+            .await
+            .and_then(|resp| match resp {
+                Response::SigrefTips(tips) => Ok(tips),
+                Response::Error(e) => Err(error::Interrogation::ErrorResponse(e)),
+                _ => Err(error::Interrogation::InvalidResponse),
+            })
+    }
+
+    /// Ask the interrogated peer to fetch `urn` from the local peer right away,
+    /// returning the number of refs it updated.
+    ///
+    /// The interrogated peer only complies if its [`super::io::graft::Policy`]
+    /// and rate limits permit the local peer to trigger a replication. A
+    /// capability `token` issued by the interrogated peer can grant the local
+    /// peer the right to do so regardless of the policy. If the interrogated
+    /// peer doesn't have `urn` yet, it clones it, provided it tracks the local
+    /// peer for `urn` or the `token` is valid.
+    pub async fn please_pull(
+        &self,
+        urn: Urn,
+        token: Option<capability::Token>,
+    ) -> Result<usize, error::Interrogation> {
+        use interrogation::{Request, Response};
+
+        self.request(Request::PleasePull(urn, token))
+            .await
+            .and_then(|resp| match resp {
+                Response::Pulled(n) => Ok(n as usize),
+                Response::Error(e) => Err(error::Interrogation::ErrorResponse(e)),
+                _ => Err(error::Interrogation::InvalidResponse),
+            })
+    }
+
+    /// Ask the interrogated peer for the content of the large object `oid`,
+    /// starting at `offset`.
+    ///
+    /// Peers which don't support large objects fail to respond, which is
+    /// reported as [`error::Interrogation::NoResponse`]. The result is
+    /// [`interrogation::Error::NotFound`] if the peer doesn't have the object.
+    pub async fn large_object(
+        &self,
+        oid: large::Oid,
+        offset: u64,
+    ) -> Result<large::Chunk, error::Interrogation> {
+        use interrogation::{Request, Response};
+
+        self.request(Request::GetLargeObject(oid, offset))
+            .await
+            .and_then(|resp| match resp {
+                Response::LargeObject(chunk) => Ok(chunk),
+                Response::Error(e) => Err(error::Interrogation::ErrorResponse(e)),
+                _ => Err(error::Interrogation::InvalidResponse),
+            })
+    }
+
+    /// Ping the interrogated peer, returning the round-trip time as observed
+    /// by the caller.
+    ///
+    /// The smoothed round-trip time of the peer, as reported in
+    /// [`event::downstream::Stats::rtt`], is updated as a side-effect.
+    pub async fn ping(&self) -> Result<Duration, error::Interrogation> {
+        use interrogation::{Request, Response};
+
+        let start = Instant::now();
+        self.request(Request::Ping)
+            .await
+            .and_then(|resp| match resp {
+                Response::Pong => Ok(start.elapsed()),
+                Response::Error(e) => Err(error::Interrogation::ErrorResponse(e)),
+                _ => Err(error::Interrogation::InvalidResponse),
+            })
+    }
+
     async fn request(
         &self,
         request: interrogation::Request,
@@ -0,0 +1,166 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Versioning of the membership and gossip RPC schemas.
+//!
+//! Peers advertise the range of schema [`Versions`] they support as part of
+//! their [`super::PeerAdvertisement`], which is exchanged when joining the
+//! overlay (via `Join` and `Neighbour` messages). The highest version
+//! supported by both ends is [`Versions::negotiate`]d, and subsequent
+//! messages to that peer are sent at this version.
+//!
+//! Messages at a version other than [`LEGACY`] are wrapped in a [`Versioned`]
+//! envelope. Peers which predate versioning never advertise any versions, and
+//! are thus only ever sent unwrapped messages, which they understand.
+//!
+//! Schema changes (eg. new fields of offers, or new kinds of gossip payload)
+//! are introduced by incrementing [`CURRENT`], and must only be sent to peers
+//! whose negotiated version is at least that version.
+
+use std::{collections::HashMap, sync::Arc};
+
+use minicbor::{
+    data::{Tag, Type},
+    Decode,
+    Decoder,
+    Encode,
+    Encoder,
+};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+
+use crate::PeerId;
+
+pub type Version = u8;
+
+/// The version of peers which do not advertise any [`Versions`].
+pub const LEGACY: Version = 0;
+
+/// The highest version supported by this implementation.
+pub const CURRENT: Version = 1;
+
+/// The (unassigned) CBOR tag denoting a [`Versioned`] envelope, `"rad"` in
+/// ASCII.
+const VERSIONED_TAG: u64 = 0x72_61_64;
+
+/// An inclusive range of [`Version`]s.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Encode, Decode, Serialize, Deserialize)]
+#[cbor(array)]
+pub struct Versions {
+    #[n(0)]
+    pub min: Version,
+    #[n(1)]
+    pub max: Version,
+}
+
+impl Versions {
+    /// The versions supported by peers which do not advertise any.
+    pub const LEGACY: Self = Self {
+        min: LEGACY,
+        max: LEGACY,
+    };
+
+    /// The versions supported by this implementation.
+    pub const SUPPORTED: Self = Self {
+        min: LEGACY,
+        max: CURRENT,
+    };
+
+    /// The highest version contained in both `self` and `other`, or `None`
+    /// if the ranges don't overlap.
+    pub fn negotiate(&self, other: &Self) -> Option<Version> {
+        let min = self.min.max(other.min);
+        let max = self.max.min(other.max);
+        (min <= max).then_some(max)
+    }
+}
+
+/// A message sent at a particular [`Version`].
+///
+/// # Wire Encoding
+///
+/// At version [`LEGACY`], the message is encoded as-is. Otherwise, it is
+/// encoded as a 2-element CBOR array of the version and the message, tagged
+/// with an unassigned CBOR tag.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Versioned<T> {
+    pub version: Version,
+    pub message: T,
+}
+
+impl<T> Versioned<T> {
+    pub fn new(version: Version, message: T) -> Self {
+        Self { version, message }
+    }
+}
+
+impl<T: Encode> Encode for Versioned<T> {
+    fn encode<W: minicbor::encode::Write>(
+        &self,
+        e: &mut Encoder<W>,
+    ) -> Result<(), minicbor::encode::Error<W::Error>> {
+        if self.version != LEGACY {
+            e.tag(Tag::Unassigned(VERSIONED_TAG))?
+                .array(2)?
+                .u8(self.version)?;
+        }
+        self.message.encode(e)
+    }
+}
+
+impl<'b, T: Decode<'b>> Decode<'b> for Versioned<T> {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, minicbor::decode::Error> {
+        if Type::Tag != d.datatype()? {
+            return Ok(Self::new(LEGACY, T::decode(d)?));
+        }
+
+        if Tag::Unassigned(VERSIONED_TAG) != d.tag()? {
+            return Err(minicbor::decode::Error::Message("unexpected tag"));
+        }
+        if Some(2) != d.array()? {
+            return Err(minicbor::decode::Error::Message("expected 2-element array"));
+        }
+        let version = d.u8()?;
+        let message = T::decode(d)?;
+
+        Ok(Self::new(version, message))
+    }
+}
+
+/// The versions negotiated with remote peers.
+///
+/// Peers we haven't negotiated with are assumed to be at [`LEGACY`].
+#[derive(Clone, Default)]
+pub(super) struct Negotiated {
+    inner: Arc<RwLock<HashMap<PeerId, Version>>>,
+}
+
+impl Negotiated {
+    /// Negotiate the version to use with `peer`, given the versions it
+    /// advertised.
+    pub fn record(&self, peer: PeerId, theirs: &Versions) -> Version {
+        let version = Versions::SUPPORTED.negotiate(theirs).unwrap_or_else(|| {
+            tracing::warn!(
+                remote_id = %peer,
+                min = theirs.min,
+                max = theirs.max,
+                "no common rpc version, falling back to legacy"
+            );
+            LEGACY
+        });
+        self.inner.write().insert(peer, version);
+        version
+    }
+
+    pub fn get(&self, peer: &PeerId) -> Version {
+        self.inner.read().get(peer).copied().unwrap_or(LEGACY)
+    }
+
+    /// Forget the version negotiated with `peer`, eg. because the connection
+    /// was lost. The peer may come back running a different version.
+    pub fn forget(&self, peer: &PeerId) {
+        self.inner.write().remove(peer);
+    }
+}
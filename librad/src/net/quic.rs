@@ -15,6 +15,9 @@ pub use connection::{
     IncomingStreams,
 };
 
+pub mod dialer;
+pub use dialer::Dialer;
+
 mod endpoint;
 pub use endpoint::{BoundEndpoint, Endpoint, IncomingConnections};
 
@@ -22,24 +25,46 @@ pub mod error;
 pub use error::{Error, Result};
 
 mod stream;
-pub use stream::{BidiStream, RecvStream, SendStream};
+pub use stream::{is_stream_reset, BidiStream, RecvStream, SendStream};
 
 const ALPN_PREFIX: &[u8] = b"rad";
 
-// XXX: we _may_ want to allow runtime configuration of below consts at some
-// point
-
-/// Connection keep alive interval.
+/// Default connection keep alive interval.
 ///
 /// Only set for initiators (clients). The value of 30s is recommended for
 /// keeping middlebox UDP flows alive.
-const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(30);
+pub const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(30);
 
-/// Connection idle timeout.
+/// Default connection idle timeout.
 ///
 /// Only has an effect for responders (servers), which we configure to not send
 /// keep alive probes. Should tolerate the loss of 1-2 keep-alive probes.
-pub(in crate::net) const MAX_IDLE_TIMEOUT: Duration = Duration::from_secs(65);
+pub const MAX_IDLE_TIMEOUT: Duration = Duration::from_secs(65);
 
 /// Maximum number of connections to a single peer.
 const MAX_PEER_CONNECTIONS: usize = 5;
+
+/// Liveness parameters of the connections of an [`Endpoint`].
+///
+/// A connection on which nothing was received for `max_idle_timeout` is
+/// considered dead, and closed. Initiators send keep alive probes every
+/// `keep_alive_interval`, so `max_idle_timeout` should be large enough to
+/// tolerate the loss of 1-2 of them. On flaky links, a larger
+/// `max_idle_timeout` avoids churn, at the expense of detecting dead peers
+/// later.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Transport {
+    /// Default: [`KEEP_ALIVE_INTERVAL`]
+    pub keep_alive_interval: Duration,
+    /// Default: [`MAX_IDLE_TIMEOUT`]
+    pub max_idle_timeout: Duration,
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Self {
+            keep_alive_interval: KEEP_ALIVE_INTERVAL,
+            max_idle_timeout: MAX_IDLE_TIMEOUT,
+        }
+    }
+}
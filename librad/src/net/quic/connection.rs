@@ -70,10 +70,12 @@ fn incoming_streams(
                 send: SendStream {
                     conn: conn.clone(),
                     send,
+                    allow_reset: false,
                 },
                 recv: RecvStream {
                     conn: conn.clone(),
                     recv,
+                    allow_reset: false,
                 },
             })
         })
@@ -85,6 +87,7 @@ fn incoming_streams(
             Right(RecvStream {
                 conn: conn.clone(),
                 recv,
+                allow_reset: false,
             })
         })
     };
@@ -137,6 +140,7 @@ pub enum BorrowUniError<E: std::error::Error + 'static> {
 #[derive(Clone)]
 pub struct Connection {
     peer: PeerId,
+    remote_addr: SocketAddr,
     conn: quinn::Connection,
     track: Conntrack,
     send_streams: Arc<Vec<Mutex<Option<SendStream>>>>,
@@ -147,6 +151,7 @@ impl Connection {
         track: Conntrack,
         reserve_send_streams: usize,
         remote_peer: PeerId,
+        remote_addr: SocketAddr,
         NewConnection {
             connection,
             bi_streams,
@@ -159,6 +164,7 @@ impl Connection {
     ) {
         let conn = Self {
             peer: remote_peer,
+            remote_addr,
             conn: connection,
             track,
             send_streams: Arc::new(
@@ -189,10 +195,12 @@ impl Connection {
             recv: RecvStream {
                 conn: self.clone(),
                 recv,
+                allow_reset: false,
             },
             send: SendStream {
                 conn: self.clone(),
                 send,
+                allow_reset: false,
             },
         })
     }
@@ -208,6 +216,7 @@ impl Connection {
         Ok(SendStream {
             conn: self.clone(),
             send,
+            allow_reset: false,
         })
     }
 
@@ -249,8 +258,14 @@ impl Connection {
         self.track.disconnect(&self.id(), reason);
     }
 
+    /// Close the connection, unless `e` only affects the stream it occurred
+    /// on (see [`super::is_stream_reset`]), and that stream `allow_reset`.
     #[tracing::instrument(skip(self, e))]
-    pub(super) fn on_stream_error(&self, e: &io::Error) {
+    pub(super) fn on_stream_error(&self, e: &io::Error, allow_reset: bool) {
+        if allow_reset && super::is_stream_reset(e) {
+            tracing::debug!(err = ?e, "stream reset");
+            return;
+        }
         tracing::warn!(err = ?e, "stream error");
         self.track
             .disconnect(&self.id(), CloseReason::ConnectionError);
@@ -274,7 +289,11 @@ impl RemotePeer for Connection {
 impl RemoteAddr for Connection {
     type Addr = SocketAddr;
 
+    /// The address of the remote peer.
+    ///
+    /// For outgoing connections relayed through a proxy, this is the address
+    /// dialed, not the address of the relay.
     fn remote_addr(&self) -> SocketAddr {
-        self.conn.remote_address()
+        self.remote_addr
     }
 }
@@ -13,6 +13,7 @@ use std::{
         Weak,
     },
     thread,
+    time::Duration,
 };
 
 use dashmap::DashMap;
@@ -72,6 +73,12 @@ impl Default for Conntrack {
 
 impl Conntrack {
     pub fn new() -> Self {
+        Self::with_idle_timeout(MAX_IDLE_TIMEOUT)
+    }
+
+    /// Create a [`Conntrack`] which closes connections which weren't
+    /// [`Conntrack::tickle`]d for `idle_timeout`.
+    pub fn with_idle_timeout(idle_timeout: Duration) -> Self {
         let epoch = Arc::new(AtomicUsize::new(0));
         let connections = Arc::new(DashMap::with_capacity_and_hasher(1024, Default::default()));
         let peer_connections =
@@ -80,6 +87,7 @@ impl Conntrack {
             Arc::downgrade(&epoch),
             Arc::clone(&connections),
             Arc::downgrade(&peer_connections),
+            idle_timeout,
         );
 
         Self {
@@ -243,13 +251,14 @@ fn spawn_gc(
     epoch: Weak<AtomicUsize>,
     connections: Arc<Connections>,
     peer_connections: Weak<PeerConnections>,
+    idle_timeout: Duration,
 ) {
     use dashmap::mapref::{entry::Entry::*, multiple::RefMutMulti};
 
     thread::spawn({
         const CLOSE_REASON: CloseReason = CloseReason::Timeout;
         move || loop {
-            thread::sleep(MAX_IDLE_TIMEOUT);
+            thread::sleep(idle_timeout);
             match Weak::upgrade(&epoch) {
                 None => break,
                 Some(epoch) => {
@@ -283,7 +292,7 @@ fn spawn_gc(
     });
     thread::spawn({
         move || loop {
-            thread::sleep(MAX_IDLE_TIMEOUT * 2);
+            thread::sleep(idle_timeout * 2);
             match Weak::upgrade(&peer_connections) {
                 None => break,
                 Some(peer_connections) => {
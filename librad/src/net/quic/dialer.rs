@@ -0,0 +1,308 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Establishing the path outgoing connections take.
+//!
+//! By default, outgoing connections are made directly to the remote address.
+//! A [`Dialer::Socks5`] instead relays the QUIC datagrams through a SOCKS5
+//! proxy using the `UDP ASSOCIATE` command ([RFC 1928]), such that the remote
+//! peer only ever sees the address of the proxy.
+//!
+//! Because `quinn` owns the UDP socket of an [`super::Endpoint`], relaying is
+//! implemented by a [`Relay`] bound to the loopback interface: the endpoint
+//! connects to the relay, which wraps every datagram in a SOCKS5 UDP request
+//! header and forwards it to the proxy (and vice versa). The relay only
+//! accepts datagrams from the endpoint's socket, and lives for as long as
+//! datagrams flow, or until the proxy terminates the association.
+//!
+//! Only proxies supporting `UDP ASSOCIATE` can be used. Notably, this rules
+//! out Tor, whose SOCKS port only supports `CONNECT`: QUIC can not be carried
+//! over the TCP streams it provides.
+//!
+//! [RFC 1928]: https://datatracker.ietf.org/doc/html/rfc1928
+
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use futures::FutureExt as _;
+use tokio::{
+    io::{AsyncReadExt as _, AsyncWriteExt as _},
+    net::{TcpStream, UdpSocket},
+    time::timeout,
+};
+
+const VERSION: u8 = 0x05;
+const METHOD_NO_AUTH: u8 = 0x00;
+const METHOD_UNACCEPTABLE: u8 = 0xff;
+const CMD_UDP_ASSOCIATE: u8 = 0x03;
+const REPLY_SUCCEEDED: u8 = 0x00;
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+/// Timeout for establishing an association with the proxy.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Maximum size of a UDP datagram, plus the SOCKS5 request header.
+const MAX_DATAGRAM: usize = 65_535 + 22;
+
+/// How outgoing connections are established.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Dialer {
+    /// Connect directly to the remote address.
+    Direct,
+    /// Relay outgoing connections through the SOCKS5 proxy listening on the
+    /// given address.
+    ///
+    /// Incoming connections are not affected.
+    Socks5 { proxy: SocketAddr },
+}
+
+impl Default for Dialer {
+    fn default() -> Self {
+        Self::Direct
+    }
+}
+
+/// A UDP association with a SOCKS5 proxy, relaying datagrams between an
+/// endpoint and a single remote address.
+pub struct Relay {
+    /// The loopback address the endpoint should connect to.
+    pub local_addr: SocketAddr,
+    control: TcpStream,
+    proxy: UdpSocket,
+    relay_addr: SocketAddr,
+    local: UdpSocket,
+    remote_addr: SocketAddr,
+}
+
+impl Relay {
+    /// Ask the SOCKS5 server at `proxy` to relay datagrams to `remote_addr`,
+    /// on behalf of the endpoint whose socket is bound to `endpoint`.
+    ///
+    /// The [`Relay::local_addr`] handed to the endpoint is of the same address
+    /// family as `endpoint`, and only accepts datagrams sent from it.
+    pub async fn associate(
+        proxy: SocketAddr,
+        remote_addr: SocketAddr,
+        endpoint: SocketAddr,
+    ) -> io::Result<Self> {
+        timeout(
+            HANDSHAKE_TIMEOUT,
+            Self::associate_inner(proxy, remote_addr, endpoint),
+        )
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "SOCKS5 handshake timed out"))?
+    }
+
+    async fn associate_inner(
+        proxy: SocketAddr,
+        remote_addr: SocketAddr,
+        endpoint: SocketAddr,
+    ) -> io::Result<Self> {
+        let mut control = TcpStream::connect(proxy).await?;
+
+        control.write_all(&[VERSION, 1, METHOD_NO_AUTH]).await?;
+        let mut method = [0; 2];
+        control.read_exact(&mut method).await?;
+        match method {
+            [VERSION, METHOD_NO_AUTH] => {},
+            [VERSION, METHOD_UNACCEPTABLE] => {
+                return Err(protocol_error("proxy requires authentication"))
+            },
+            _ => return Err(protocol_error("unexpected method selection")),
+        }
+
+        // The client address is not known until the first datagram is sent,
+        // which the proxy must accept as per RFC 1928, section 7.
+        let proxy_sock = UdpSocket::bind(unspecified(&proxy)).await?;
+        let mut req = vec![VERSION, CMD_UDP_ASSOCIATE, 0];
+        encode_addr(&mut req, &unspecified(&proxy));
+        control.write_all(&req).await?;
+
+        let mut reply = [0; 4];
+        control.read_exact(&mut reply).await?;
+        if reply[0] != VERSION {
+            return Err(protocol_error("unexpected reply version"));
+        }
+        if reply[1] != REPLY_SUCCEEDED {
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                format!("proxy refused UDP association: {}", reply_message(reply[1])),
+            ));
+        }
+        let mut relay_addr = read_addr(&mut control, reply[3]).await?;
+        // Servers may reply with an unspecified address, meaning "same host".
+        if relay_addr.ip().is_unspecified() {
+            relay_addr.set_ip(proxy.ip())
+        }
+
+        let loopback = match endpoint {
+            SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+            SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        };
+        let local = UdpSocket::bind(SocketAddr::new(loopback, 0)).await?;
+        let local_addr = local.local_addr()?;
+        // An endpoint bound to the unspecified address sends from the
+        // loopback address.
+        let endpoint = if endpoint.ip().is_unspecified() {
+            SocketAddr::new(loopback, endpoint.port())
+        } else {
+            endpoint
+        };
+        local.connect(endpoint).await?;
+
+        Ok(Self {
+            local_addr,
+            control,
+            proxy: proxy_sock,
+            relay_addr,
+            local,
+            remote_addr,
+        })
+    }
+
+    /// Forward datagrams until either side has been idle for `idle_timeout`,
+    /// or the proxy closes the control connection.
+    #[tracing::instrument(skip(self), fields(remote_addr = %self.remote_addr, relay_addr = %self.relay_addr))]
+    pub async fn run(mut self, idle_timeout: Duration) {
+        enum Event {
+            Closed,
+            Idle,
+            Outbound(io::Result<usize>),
+            Inbound(io::Result<(usize, SocketAddr)>),
+        }
+
+        let mut inbound = vec![0; MAX_DATAGRAM];
+        let mut outbound = vec![0; MAX_DATAGRAM];
+        let mut control_buf = [0; 1];
+        let mut datagram = Vec::with_capacity(MAX_DATAGRAM);
+
+        loop {
+            let event = futures::select! {
+                _ = self.control.read(&mut control_buf).fuse() => Event::Closed,
+                recv = timeout(idle_timeout, self.local.recv(&mut outbound)).fuse() => {
+                    recv.map_or(Event::Idle, Event::Outbound)
+                },
+                recv = self.proxy.recv_from(&mut inbound).fuse() => Event::Inbound(recv),
+            };
+
+            let res = match event {
+                Event::Closed => {
+                    tracing::debug!("proxy closed association");
+                    break;
+                },
+                Event::Idle => {
+                    tracing::debug!("relay idle");
+                    break;
+                },
+                Event::Outbound(Err(e)) | Event::Inbound(Err(e)) => Err(e),
+                Event::Outbound(Ok(n)) => {
+                    datagram.clear();
+                    datagram.extend_from_slice(&[0, 0, 0]);
+                    encode_addr(&mut datagram, &self.remote_addr);
+                    datagram.extend_from_slice(&outbound[..n]);
+                    self.proxy.send_to(&datagram, self.relay_addr).await
+                },
+                Event::Inbound(Ok((n, from))) => {
+                    if from != self.relay_addr {
+                        Err(protocol_error("datagram from unexpected source"))
+                    } else {
+                        match decode_datagram(&inbound[..n]) {
+                            Ok(payload) => self.local.send(payload).await,
+                            Err(e) => Err(e),
+                        }
+                    }
+                },
+            };
+            if let Err(e) = res {
+                tracing::warn!(err = ?e, "failed to relay datagram");
+            }
+        }
+    }
+}
+
+fn unspecified(addr: &SocketAddr) -> SocketAddr {
+    match addr {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    }
+}
+
+/// Append the SOCKS5 encoding of `addr` (`ATYP`, `ADDR` and `PORT`) to `buf`.
+pub fn encode_addr(buf: &mut Vec<u8>, addr: &SocketAddr) {
+    match addr.ip() {
+        IpAddr::V4(ip) => {
+            buf.push(ATYP_IPV4);
+            buf.extend_from_slice(&ip.octets());
+        },
+        IpAddr::V6(ip) => {
+            buf.push(ATYP_IPV6);
+            buf.extend_from_slice(&ip.octets());
+        },
+    }
+    buf.extend_from_slice(&addr.port().to_be_bytes());
+}
+
+async fn read_addr(control: &mut TcpStream, atyp: u8) -> io::Result<SocketAddr> {
+    let ip = match atyp {
+        ATYP_IPV4 => {
+            let mut octets = [0; 4];
+            control.read_exact(&mut octets).await?;
+            IpAddr::from(octets)
+        },
+        ATYP_IPV6 => {
+            let mut octets = [0; 16];
+            control.read_exact(&mut octets).await?;
+            IpAddr::from(octets)
+        },
+        ATYP_DOMAIN => {
+            return Err(protocol_error(
+                "domain name relay addresses are not supported",
+            ))
+        },
+        _ => return Err(protocol_error("unknown address type")),
+    };
+    let mut port = [0; 2];
+    control.read_exact(&mut port).await?;
+
+    Ok(SocketAddr::new(ip, u16::from_be_bytes(port)))
+}
+
+/// Strip the SOCKS5 UDP request header from a datagram received from the
+/// relay.
+pub fn decode_datagram(datagram: &[u8]) -> io::Result<&[u8]> {
+    let header_len = match datagram {
+        [0, 0, 0, ATYP_IPV4, ..] => 4 + 4 + 2,
+        [0, 0, 0, ATYP_IPV6, ..] => 4 + 16 + 2,
+        [0, 0, 0, ATYP_DOMAIN, len, ..] => 4 + 1 + *len as usize + 2,
+        [0, 0, frag, ..] if *frag != 0 => return Err(protocol_error("fragmented datagram")),
+        _ => return Err(protocol_error("malformed datagram")),
+    };
+    datagram
+        .get(header_len..)
+        .ok_or_else(|| protocol_error("truncated datagram"))
+}
+
+fn reply_message(code: u8) -> &'static str {
+    match code {
+        0x01 => "general failure",
+        0x02 => "not allowed by ruleset",
+        0x03 => "network unreachable",
+        0x04 => "host unreachable",
+        0x05 => "connection refused",
+        0x06 => "TTL expired",
+        0x07 => "command not supported",
+        0x08 => "address type not supported",
+        _ => "unknown error",
+    }
+}
+
+fn protocol_error(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
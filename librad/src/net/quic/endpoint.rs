@@ -11,14 +11,25 @@ use std::{
     sync::{Arc, Weak},
 };
 
-use futures::stream::{BoxStream, StreamExt as _, TryStreamExt as _};
+use futures::{
+    future,
+    stream::{self, BoxStream, StreamExt as _, TryStreamExt as _},
+};
 use if_watch::IfWatcher;
 use nonempty::NonEmpty;
 use parking_lot::RwLock;
 use quinn::{NewConnection, TransportConfig};
 use socket2::{Domain, Protocol, Socket, Type};
 
-use super::{BoxedIncomingStreams, Connection, Conntrack, Error, Result};
+use super::{
+    dialer::{Dialer, Relay},
+    BoxedIncomingStreams,
+    Connection,
+    Conntrack,
+    Error,
+    Result,
+    Transport,
+};
 use crate::{
     executor,
     net::{
@@ -55,12 +66,20 @@ impl<'a, const R: usize> LocalAddr for BoundEndpoint<'a, R> {
 
 /// A QUIC endpoint.
 ///
+/// The endpoint may be bound to several sockets, eg. to listen on both IPv4
+/// and IPv6, or on multiple interfaces. Incoming connections are accepted on
+/// all of them, while outgoing connections are made from the first socket of
+/// the same address family as the remote address.
+///
 /// `R` is the number of reservations for outgoing unidirectional streams, see
 /// [`Connection::borrow_uni`].
 #[derive(Clone)]
 pub struct Endpoint<const R: usize> {
     peer_id: PeerId,
-    endpoint: quinn::Endpoint,
+    endpoints: NonEmpty<(SocketAddr, quinn::Endpoint)>,
+    dialer: Dialer,
+    transport: Transport,
+    spawner: Arc<executor::Spawner>,
     listen_addrs: Arc<RwLock<BTreeSet<SocketAddr>>>,
     conntrack: Conntrack,
     refcount: Arc<()>,
@@ -69,10 +88,12 @@ pub struct Endpoint<const R: usize> {
 impl<const R: usize> Endpoint<R> {
     pub async fn bind<'a, S>(
         signer: S,
-        spawner: &executor::Spawner,
-        listen_addr: SocketAddr,
+        spawner: Arc<executor::Spawner>,
+        listen_addrs: NonEmpty<SocketAddr>,
         advertised_addrs: Option<NonEmpty<SocketAddr>>,
         network: Network,
+        dialer: Dialer,
+        transport: Transport,
     ) -> Result<BoundEndpoint<'a, R>>
     where
         S: Signer + Clone + Send + Sync + 'static,
@@ -80,30 +101,51 @@ impl<const R: usize> Endpoint<R> {
     {
         let peer_id = PeerId::from_signer(&signer);
 
-        let sock = bind_socket(listen_addr)?;
-        let listen_addr = sock.local_addr()?;
+        let socks = listen_addrs
+            .into_iter()
+            .map(|listen_addr| -> Result<(SocketAddr, UdpSocket)> {
+                let sock = bind_socket(listen_addr)?;
+                let bound_addr = sock.local_addr()?;
+                Ok((bound_addr, sock))
+            })
+            .collect::<Result<Vec<_>>>()?;
         let addrs = {
             let listen_addrs = Arc::new(RwLock::new(BTreeSet::new()));
             match advertised_addrs {
                 Some(addrs) => listen_addrs.write().extend(addrs),
-                None if listen_addr.ip().is_unspecified() => {
-                    ifwatch(spawner, listen_addr, Arc::downgrade(&listen_addrs)).await?
+                None => {
+                    for (bound_addr, _) in &socks {
+                        if bound_addr.ip().is_unspecified() {
+                            ifwatch(&spawner, *bound_addr, Arc::downgrade(&listen_addrs)).await?
+                        } else {
+                            listen_addrs.write().insert(*bound_addr);
+                        }
+                    }
                 },
-                None => listen_addrs.write().extend(Some(listen_addr)),
             }
             listen_addrs
         };
 
-        let (endpoint, incoming) = make_endpoint(signer, sock, alpn(network)).await?;
-        let conntrack = Conntrack::new();
+        let mut endpoints = Vec::with_capacity(socks.len());
+        let mut incomings = Vec::with_capacity(socks.len());
+        for (bound_addr, sock) in socks {
+            let (endpoint, incoming) =
+                make_endpoint(signer.clone(), sock, alpn(network.clone()), transport).await?;
+            endpoints.push((bound_addr, endpoint));
+            incomings.push(incoming);
+        }
+        let conntrack = Conntrack::with_idle_timeout(transport.max_idle_timeout);
         let endpoint = Endpoint {
             peer_id,
-            endpoint,
+            endpoints: NonEmpty::from_vec(endpoints).expect("at least one listen addr was given"),
+            dialer,
+            transport,
+            spawner,
             listen_addrs: addrs,
             conntrack: conntrack.clone(),
             refcount: Arc::new(()),
         };
-        let incoming = incoming
+        let incoming = stream::select_all(incomings)
             .map(Ok)
             .and_then(move |connecting| {
                 let conntrack = conntrack.clone();
@@ -114,7 +156,9 @@ impl<const R: usize> Endpoint<R> {
                         remote_peer != peer_id,
                         "self-connections are prevented in the TLS handshake"
                     );
-                    let (conn, streams) = Connection::new(conntrack.clone(), R, remote_peer, conn);
+                    let remote_addr = conn.connection.remote_address();
+                    let (conn, streams) =
+                        Connection::new(conntrack.clone(), R, remote_peer, remote_addr, conn);
                     conntrack.connected(&conn);
 
                     Ok((conn, streams.boxed()))
@@ -150,16 +194,36 @@ impl<const R: usize> Endpoint<R> {
             return Err(Error::SelfConnect);
         }
 
-        let conn = self
-            .endpoint
-            .connect(addr, peer.as_dns_name().as_ref().into())?
+        let endpoint = self.endpoint_for(addr);
+        let dial_addr = match self.dialer {
+            Dialer::Direct => *addr,
+            Dialer::Socks5 { proxy } => {
+                let relay = Relay::associate(proxy, *addr, endpoint.local_addr()?).await?;
+                let local_addr = relay.local_addr;
+                self.spawner
+                    .spawn(relay.run(self.transport.max_idle_timeout))
+                    .detach();
+                local_addr
+            },
+        };
+        let conn = endpoint
+            .connect(&dial_addr, peer.as_dns_name().as_ref().into())?
             .await?;
-        let (conn, streams) = Connection::new(self.conntrack.clone(), R, peer, conn);
+        let (conn, streams) = Connection::new(self.conntrack.clone(), R, peer, *addr, conn);
         self.conntrack.connected(&conn);
 
         Ok((conn, streams.boxed()))
     }
 
+    /// The endpoint to connect to `addr` from.
+    fn endpoint_for(&self, addr: &SocketAddr) -> &quinn::Endpoint {
+        self.endpoints
+            .iter()
+            .find(|(bound, _)| bound.is_ipv4() == addr.is_ipv4())
+            .map(|(_, endpoint)| endpoint)
+            .unwrap_or(&self.endpoints.head.1)
+    }
+
     pub fn get_connection(&self, to: PeerId) -> Option<Connection> {
         self.conntrack.get(to)
     }
@@ -174,13 +238,19 @@ impl<const R: usize> Endpoint<R> {
             "endpoint shutdown requested"
         );
         let reason = CloseReason::ServerShutdown;
-        self.endpoint
-            .close((reason as u32).into(), reason.reason_phrase());
+        for (_, endpoint) in self.endpoints.iter() {
+            endpoint.close((reason as u32).into(), reason.reason_phrase());
+        }
         self.conntrack.disconnect_all();
     }
 
     pub async fn wait_idle(&self) {
-        self.endpoint.wait_idle().await
+        future::join_all(
+            self.endpoints
+                .iter()
+                .map(|(_, endpoint)| endpoint.wait_idle()),
+        )
+        .await;
     }
 }
 
@@ -313,19 +383,24 @@ async fn make_endpoint<S>(
     signer: S,
     sock: UdpSocket,
     alpn: Alpn,
+    transport: Transport,
 ) -> Result<(quinn::Endpoint, quinn::Incoming)>
 where
     S: Signer + Clone + Send + Sync + 'static,
     S::Error: std::error::Error + Send + Sync + 'static,
 {
     let mut builder = quinn::Endpoint::builder();
-    builder.default_client_config(make_client_config(signer.clone(), alpn.clone())?);
-    builder.listen(make_server_config(signer, alpn)?);
+    builder.default_client_config(make_client_config(signer.clone(), alpn.clone(), transport)?);
+    builder.listen(make_server_config(signer, alpn, transport)?);
 
     Ok(builder.with_socket(sock)?)
 }
 
-fn make_client_config<S>(signer: S, alpn: Vec<u8>) -> Result<quinn::ClientConfig>
+fn make_client_config<S>(
+    signer: S,
+    alpn: Vec<u8>,
+    transport: Transport,
+) -> Result<quinn::ClientConfig>
 where
     S: Signer + Clone + Send + Sync + 'static,
     S::Error: std::error::Error + Send + Sync + 'static,
@@ -335,11 +410,10 @@ where
 
     let mut transport_config = TransportConfig::default();
     transport_config
-        .keep_alive_interval(Some(super::KEEP_ALIVE_INTERVAL))
+        .keep_alive_interval(Some(transport.keep_alive_interval))
         // Set idle timeout anyway, as the default is smaller than our
         // keep-alive
-        .max_idle_timeout(Some(super::MAX_IDLE_TIMEOUT))
-        .expect("idle timeout is in vetted range");
+        .max_idle_timeout(Some(transport.max_idle_timeout))?;
 
     let mut quic_config = quinn::ClientConfigBuilder::default().build();
     quic_config.crypto = Arc::new(tls_config);
@@ -348,7 +422,11 @@ where
     Ok(quic_config)
 }
 
-fn make_server_config<S>(signer: S, alpn: Vec<u8>) -> Result<quinn::ServerConfig>
+fn make_server_config<S>(
+    signer: S,
+    alpn: Vec<u8>,
+    transport: Transport,
+) -> Result<quinn::ServerConfig>
 where
     S: Signer + Clone + Send + Sync + 'static,
     S::Error: std::error::Error + Send + Sync + 'static,
@@ -357,9 +435,7 @@ where
     tls_config.alpn_protocols = vec![alpn];
 
     let mut transport_config = TransportConfig::default();
-    transport_config
-        .max_idle_timeout(Some(super::MAX_IDLE_TIMEOUT))
-        .expect("idle timeout is in vetted range");
+    transport_config.max_idle_timeout(Some(transport.max_idle_timeout))?;
 
     let mut quic_config = quinn::ServerConfigBuilder::default().build();
     quic_config.crypto = Arc::new(tls_config);
@@ -35,6 +35,9 @@ pub enum Error {
     #[error(transparent)]
     Connection(#[from] quinn::ConnectionError),
 
+    #[error("invalid transport configuration")]
+    Transport(#[from] quinn::ConfigError),
+
     #[error(transparent)]
     Io(#[from] io::Error),
 }
@@ -19,6 +19,27 @@ use crate::{
     PeerId,
 };
 
+/// Whether `e` was caused by the remote end resetting (or stopping) a single
+/// stream, as opposed to the connection being lost.
+///
+/// The connection a reset stream belongs to remains usable, so new streams can
+/// be opened over it. Only streams which opted in via
+/// [`BidiStream::allow_reset`] keep the connection open when reset, though.
+pub fn is_stream_reset(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::ConnectionReset
+        && e.get_ref()
+            .map(|inner| {
+                matches!(
+                    inner.downcast_ref::<quinn::ReadError>(),
+                    Some(quinn::ReadError::Reset(_))
+                ) || matches!(
+                    inner.downcast_ref::<quinn::WriteError>(),
+                    Some(quinn::WriteError::Stopped(_))
+                )
+            })
+            .unwrap_or(false)
+}
+
 pub struct BidiStream {
     pub(super) conn: Connection,
     pub(super) recv: RecvStream,
@@ -36,6 +57,16 @@ impl BidiStream {
         debug_assert!(x == y);
         x
     }
+
+    /// Keep the connection open if the remote end resets this stream.
+    ///
+    /// By default, any error on a stream closes the connection. Streams whose
+    /// users retry on a new stream, such as git streams, allow resets.
+    pub fn allow_reset(mut self) -> Self {
+        self.recv.allow_reset = true;
+        self.send.allow_reset = true;
+        self
+    }
 }
 
 impl RemotePeer for BidiStream {
@@ -88,6 +119,7 @@ impl AsyncWrite for BidiStream {
 pub struct RecvStream {
     pub(super) conn: Connection,
     pub(super) recv: quinn::RecvStream,
+    pub(super) allow_reset: bool,
 }
 
 impl RecvStream {
@@ -108,7 +140,7 @@ impl RecvStream {
         )
     )]
     fn on_stream_error(&self, e: &io::Error) {
-        self.conn.on_stream_error(e)
+        self.conn.on_stream_error(e, self.allow_reset)
     }
 
     fn tickle(&self) {
@@ -153,6 +185,7 @@ impl AsyncRead for RecvStream {
 pub struct SendStream {
     pub(super) conn: Connection,
     pub(super) send: quinn::SendStream,
+    pub(super) allow_reset: bool,
 }
 
 impl SendStream {
@@ -173,7 +206,7 @@ impl SendStream {
         )
     )]
     fn on_stream_error(&self, e: &io::Error) {
-        self.conn.on_stream_error(e)
+        self.conn.on_stream_error(e, self.allow_reset)
     }
 
     fn tickle(&self) {
@@ -0,0 +1,257 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Trust on first use of the peer found at an address.
+//!
+//! The TLS handshake ensures that the remote end of a connection holds the
+//! key of the [`PeerId`] we dialed. It doesn't ensure that this is the peer
+//! we usually find at the address, though: if eg. the DNS record of a
+//! bootstrap node is hijacked or misconfigured, or a seed was reinstalled with
+//! a fresh key, the address suddenly belongs to a different peer.
+//!
+//! [`Tofu`] pins the first [`PeerId`] seen at every address we connect to,
+//! and reports when a different one shows up, see [`Verdict::Mismatch`]. The
+//! pins are stored alongside the monorepo of a profile. Legitimate changes
+//! are accepted by [`Tofu::forget`]ting the pin.
+//!
+//! Only outgoing connections are considered, as the addresses of incoming
+//! connections are mostly ephemeral. At most [`MAX_PINS`] addresses are
+//! pinned: beyond that, the pins of the addresses we haven't connected to for
+//! the longest time are dropped.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{paths::Paths, PeerId};
+
+const TOFU_FILE: &str = "tofu.json";
+
+/// The maximum number of addresses pinned.
+pub const MAX_PINS: usize = 16 * 1024;
+
+/// How often [`Pin::last_seen`] is updated, in seconds.
+const LAST_SEEN_GRANULARITY: u64 = 24 * 60 * 60;
+
+static MISMATCHES: AtomicU64 = AtomicU64::new(0);
+
+/// The number of connections of this process to a peer other than the one
+/// pinned for the address.
+pub fn mismatches() -> u64 {
+    MISMATCHES.load(Ordering::Relaxed)
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+/// What to do about a connection to a peer other than the one pinned for the
+/// address.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Policy {
+    /// Report the mismatch, but keep the connection.
+    Warn,
+    /// Report the mismatch, and close the connection.
+    Refuse,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Pin {
+    pub addr: SocketAddr,
+    pub peer_id: PeerId,
+    /// Time the peer was first seen at `addr`, in seconds since the UNIX
+    /// epoch.
+    pub first_seen: u64,
+    /// Time the peer was last seen at `addr`, in seconds since the UNIX
+    /// epoch. Only updated once a day, and zero for pins stored before it was
+    /// recorded.
+    #[serde(default)]
+    pub last_seen: u64,
+}
+
+/// The outcome of [`Tofu::check`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Verdict {
+    /// The address wasn't known before, and is now pinned to the peer.
+    Pinned,
+    /// The peer is the one pinned for the address.
+    Trusted,
+    /// The address is pinned to a different peer.
+    Mismatch { pinned: PeerId },
+}
+
+/// The pins of the addresses connected to, shared between clones.
+///
+/// [`Tofu::check`] only updates the pins in memory. They are written to disk
+/// by [`Tofu::flush`], which is meant to be called off the hot path.
+#[derive(Clone)]
+pub struct Tofu {
+    path: PathBuf,
+    policy: Policy,
+    max_pins: usize,
+    pins: Arc<Mutex<BTreeMap<SocketAddr, Pin>>>,
+    dirty: Arc<AtomicBool>,
+    /// Serialises [`Tofu::flush`]es.
+    writing: Arc<Mutex<()>>,
+}
+
+impl Tofu {
+    /// The location of the pins of the profile with the given `paths`.
+    pub fn path(paths: &Paths) -> PathBuf {
+        paths.git_dir().join(TOFU_FILE)
+    }
+
+    /// Load the pins of the profile with the given `paths`. If there are none
+    /// yet, nothing is pinned.
+    pub fn open(paths: &Paths, policy: Policy) -> Result<Self, Error> {
+        Self::open_at(Self::path(paths), policy)
+    }
+
+    pub fn open_at(path: impl AsRef<Path>, policy: Policy) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let pins = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice::<Vec<Pin>>(&bytes)?
+                .into_iter()
+                .map(|pin| (pin.addr, pin))
+                .collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self {
+            path,
+            policy,
+            max_pins: MAX_PINS,
+            pins: Arc::new(Mutex::new(pins)),
+            dirty: Arc::new(AtomicBool::new(false)),
+            writing: Arc::new(Mutex::new(())),
+        })
+    }
+
+    /// Pin at most `max_pins` addresses instead of [`MAX_PINS`].
+    pub fn with_max_pins(self, max_pins: usize) -> Self {
+        Self { max_pins, ..self }
+    }
+
+    pub fn policy(&self) -> Policy {
+        self.policy
+    }
+
+    pub fn get(&self, addr: &SocketAddr) -> Option<Pin> {
+        self.pins.lock().get(addr).cloned()
+    }
+
+    pub fn pins(&self) -> Vec<Pin> {
+        self.pins.lock().values().cloned().collect()
+    }
+
+    /// Whether there are changes to the pins which are not yet written to
+    /// disk.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.load(Ordering::Acquire)
+    }
+
+    /// Check `peer` against the pin for `addr`, pinning it if there is none.
+    pub fn check(&self, addr: SocketAddr, peer: PeerId) -> Verdict {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let mut pins = self.pins.lock();
+        match pins.get_mut(&addr) {
+            Some(pin) if pin.peer_id == peer => {
+                if now.saturating_sub(pin.last_seen) >= LAST_SEEN_GRANULARITY {
+                    pin.last_seen = now;
+                    self.dirty.store(true, Ordering::Release);
+                }
+                Verdict::Trusted
+            },
+            Some(pin) => {
+                MISMATCHES.fetch_add(1, Ordering::Relaxed);
+                Verdict::Mismatch {
+                    pinned: pin.peer_id,
+                }
+            },
+            None => {
+                pins.insert(
+                    addr,
+                    Pin {
+                        addr,
+                        peer_id: peer,
+                        first_seen: now,
+                        last_seen: now,
+                    },
+                );
+                while pins.len() > self.max_pins {
+                    let stale = pins
+                        .values()
+                        .min_by_key(|pin| pin.last_seen.max(pin.first_seen))
+                        .map(|pin| pin.addr);
+                    match stale {
+                        Some(stale) => pins.remove(&stale),
+                        None => break,
+                    };
+                }
+                self.dirty.store(true, Ordering::Release);
+                Verdict::Pinned
+            },
+        }
+    }
+
+    /// Remove the pin for `addr`, such that the next peer seen at `addr` is
+    /// pinned instead.
+    pub fn forget(&self, addr: &SocketAddr) -> Result<Option<Pin>, Error> {
+        let pin = self.pins.lock().remove(addr);
+        if pin.is_some() {
+            self.dirty.store(true, Ordering::Release);
+            self.flush()?;
+        }
+        Ok(pin)
+    }
+
+    /// Write the pins to disk if they changed, replacing the file atomically.
+    ///
+    /// Changes made while writing are left to the next flush.
+    pub fn flush(&self) -> Result<(), Error> {
+        let _writing = self.writing.lock();
+        if !self.dirty.swap(false, Ordering::AcqRel) {
+            return Ok(());
+        }
+        let res = self.write(&self.pins());
+        if res.is_err() {
+            self.dirty.store(true, Ordering::Release);
+        }
+        res
+    }
+
+    fn write(&self, pins: &[Pin]) -> Result<(), Error> {
+        let dir = self.path.parent().unwrap_or_else(|| Path::new("."));
+        let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+        serde_json::to_writer_pretty(&mut tmp, pins)?;
+        tmp.persist(&self.path).map_err(|e| e.error)?;
+
+        Ok(())
+    }
+}
@@ -19,6 +19,8 @@ use zeroize::Zeroize;
 
 use keystore::{sign, SecretKeyExt};
 
+use crate::SecureBytes;
+
 pub const PUBLICKEYBYTES: usize = std::mem::size_of::<ed25519::VerificationKeyBytes>();
 pub use keystore::SecStr;
 
@@ -34,7 +36,6 @@ impl<T: error::Error + Send + Sync + 'static> SignError for T {}
 
 /// A device-specific signing key
 #[derive(Clone, Zeroize)]
-#[cfg_attr(test, derive(Debug))]
 #[zeroize(drop)]
 pub struct SecretKey(ed25519::SigningKey);
 
@@ -73,8 +74,32 @@ impl SecretKey {
         Self(sk)
     }
 
-    pub fn from_seed(seed: [u8; 32]) -> Self {
-        Self(ed25519::SigningKey::from(seed))
+    pub fn from_seed(mut seed: [u8; 32]) -> Self {
+        let sk = ed25519::SigningKey::from(seed);
+        seed.zeroize();
+        Self(sk)
+    }
+
+    /// Derive a key deterministically from a small `seed`, such that tests
+    /// and the fixtures they generate are reproducible.
+    ///
+    /// The same `seed` yields the same key on every platform. The key is only
+    /// as secret as the seed, so this MUST NOT be used outside of tests.
+    #[cfg(feature = "testing")]
+    pub fn from_seed_rng(seed: u64) -> Self {
+        use rand::{RngCore as _, SeedableRng as _};
+
+        let mut bytes = [0; 32];
+        rand_pcg::Pcg64Mcg::seed_from_u64(seed).fill_bytes(&mut bytes);
+        Self::from_seed(bytes)
+    }
+
+    /// Construct the key from raw secret key material, without making a copy
+    /// of it.
+    pub fn from_secure(bytes: &SecureBytes) -> Result<Self, IntoSecretKeyError> {
+        ed25519::SigningKey::try_from(bytes.as_ref())
+            .map(Self::from_secret)
+            .map_err(|_| IntoSecretKeyError::InvalidSliceLength)
     }
 
     pub(crate) fn from_secret(sk: ed25519::SigningKey) -> Self {
@@ -98,6 +123,13 @@ impl fmt::Display for SecretKey {
     }
 }
 
+// Only shows the public key, so the secret doesn't end up in logs.
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SecretKey").field(&self.public()).finish()
+    }
+}
+
 impl AsRef<[u8]> for SecretKey {
     fn as_ref(&self) -> &[u8] {
         self.0.as_ref()
@@ -28,5 +28,8 @@ pub use keys::{
 pub mod peer;
 pub use peer::PeerId;
 
+pub mod secure;
+pub use secure::SecureBytes;
+
 mod signer;
 pub use signer::{BoxedSignError, BoxedSigner, Signer, SomeSigner};
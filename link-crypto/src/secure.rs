@@ -0,0 +1,179 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Memory for secret key material.
+//!
+//! [`SecureBytes`] is zeroized when dropped, and can optionally be locked into
+//! RAM, such that it is never written to swap. It deliberately implements
+//! neither `Clone` nor a `Debug` which reveals its contents.
+
+use std::{fmt, io};
+
+use zeroize::Zeroize;
+
+use super::SecStr;
+
+/// An upper bound for key material read from files or stdin, which is
+/// allocated upfront to avoid leaving copies behind when growing the buffer.
+pub const MAX_READ_SIZE: usize = 8 * 1024;
+
+pub struct SecureBytes {
+    bytes: Vec<u8>,
+    locked: bool,
+}
+
+impl SecureBytes {
+    /// Take ownership of `bytes`.
+    ///
+    /// Note that copies of the data which `bytes` may have left behind while
+    /// it was grown are not zeroized.
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self {
+            bytes,
+            locked: false,
+        }
+    }
+
+    /// A buffer of `len` zero bytes, to be filled via [`AsMut`] and then
+    /// [`SecureBytes::truncate`]d, such that no copies are left behind.
+    pub fn zeroed(len: usize) -> Self {
+        Self::new(vec![0; len])
+    }
+
+    /// Read all of `r`, which must not yield more than [`MAX_READ_SIZE`]
+    /// bytes.
+    pub fn read<R: io::Read>(mut r: R) -> io::Result<Self> {
+        let mut this = Self::zeroed(MAX_READ_SIZE);
+        let mut len = 0;
+        loop {
+            match r.read(&mut this.as_mut()[len..]) {
+                Ok(0) => break,
+                Ok(n) => {
+                    len += n;
+                    if len == MAX_READ_SIZE {
+                        return Err(too_large());
+                    }
+                },
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        this.truncate(len);
+        Ok(this)
+    }
+
+    /// Shorten the contents to `len` bytes. The allocation is kept, and
+    /// zeroized as a whole when dropped.
+    pub fn truncate(&mut self, len: usize) {
+        self.bytes[len..].zeroize();
+        self.bytes.truncate(len);
+    }
+
+    /// Lock the memory into RAM, preventing it from being swapped out.
+    ///
+    /// This may fail if the process exceeds `RLIMIT_MEMLOCK`, in which case
+    /// the memory remains usable, but may end up in swap.
+    pub fn mlock(&mut self) -> io::Result<()> {
+        if !self.locked && self.bytes.capacity() > 0 {
+            mem::lock(self.bytes.as_ptr(), self.bytes.capacity())?;
+            self.locked = true;
+        }
+        Ok(())
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
+
+impl AsRef<[u8]> for SecureBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl AsMut<[u8]> for SecureBytes {
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut self.bytes
+    }
+}
+
+impl From<&SecureBytes> for SecStr {
+    fn from(bytes: &SecureBytes) -> Self {
+        Self::new(bytes.bytes.to_vec())
+    }
+}
+
+impl fmt::Debug for SecureBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecureBytes")
+            .field("len", &self.bytes.len())
+            .field("locked", &self.locked)
+            .finish()
+    }
+}
+
+impl Drop for SecureBytes {
+    fn drop(&mut self) {
+        self.bytes.zeroize();
+        if self.locked {
+            mem::unlock(self.bytes.as_ptr(), self.bytes.capacity()).ok();
+        }
+    }
+}
+
+/// The error returned when key material exceeds [`MAX_READ_SIZE`].
+pub fn too_large() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "secret key material too large")
+}
+
+#[cfg(unix)]
+mod mem {
+    use std::io;
+
+    // N.B. `len` is the capacity of the vector rather than its length, so as
+    // to cover the whole allocation.
+    pub(super) fn lock(ptr: *const u8, len: usize) -> io::Result<()> {
+        let res = unsafe { libc::mlock(ptr as *const libc::c_void, len) };
+        if res == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    pub(super) fn unlock(ptr: *const u8, len: usize) -> io::Result<()> {
+        let res = unsafe { libc::munlock(ptr as *const libc::c_void, len) };
+        if res == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod mem {
+    use std::io;
+
+    pub(super) fn lock(_: *const u8, _: usize) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "locking memory is not supported on this platform",
+        ))
+    }
+
+    pub(super) fn unlock(_: *const u8, _: usize) -> io::Result<()> {
+        Ok(())
+    }
+}
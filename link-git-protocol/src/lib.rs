@@ -13,13 +13,15 @@ use versions::Version;
 pub mod fetch;
 pub mod ls;
 pub mod packwriter;
+pub mod ref_prefix;
 pub mod take;
 pub mod transport;
 pub mod upload_pack;
 
 pub use fetch::{fetch, ObjectId, Ref};
-pub use ls::ls_refs;
+pub use ls::{ls_refs, ls_refs_with};
 pub use packwriter::PackWriter;
+pub use ref_prefix::RefPrefixes;
 pub use upload_pack::upload_pack;
 
 fn remote_git_version(caps: &client::Capabilities) -> Option<Version> {
@@ -3,7 +3,7 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::io;
+use std::{collections::HashSet, fmt, io};
 
 use bstr::{BString, ByteVec as _};
 use futures_lite::io::{AsyncBufRead, AsyncRead, AsyncWrite};
@@ -21,7 +21,7 @@ use versions::Version;
 
 pub use git_repository::protocol::fetch::Ref;
 
-use crate::{remote_git_version, transport};
+use crate::{remote_git_version, transport, RefPrefixes};
 
 // Work around `git-upload-pack` not handling namespaces properly
 //
@@ -53,26 +53,110 @@ pub struct Options {
     ///
     /// If the [`Vec`] is empty, the server is asked to return all refs it knows
     /// about. Otherwise, the server is asked to only return refs matching
-    /// the given prefixes.
+    /// the given prefixes. Exclusions can be expressed using
+    /// [`ls_refs_matching`].
     pub ref_prefixes: Vec<BString>,
+
+    /// The maximum number of refs to accept from the server.
+    ///
+    /// If the server advertises more distinct refs than this (after
+    /// filtering), `ls-refs` fails with [`TooManyRefs`]. `None` means no
+    /// limit.
+    pub max_refs: Option<usize>,
+}
+
+/// The error returned by [`ls_refs`] if the server advertised more refs than
+/// [`Options::max_refs`].
+///
+/// It is returned wrapped in an [`io::Error`] of kind
+/// [`io::ErrorKind::InvalidData`], use [`TooManyRefs::from_io`] to recover it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TooManyRefs {
+    pub limit: usize,
+}
+
+impl TooManyRefs {
+    pub fn from_io(e: &io::Error) -> Option<&Self> {
+        e.get_ref().and_then(|inner| inner.downcast_ref())
+    }
+}
+
+impl fmt::Display for TooManyRefs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "remote advertised more than {} refs", self.limit)
+    }
+}
+
+impl std::error::Error for TooManyRefs {}
+
+impl From<TooManyRefs> for io::Error {
+    fn from(e: TooManyRefs) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
 }
 
 /// [`Delegate`] for running a stateless `ls-refs` command.
-pub struct LsRefs {
+///
+/// The advertised refs are processed in a single pass: refs rejected by the
+/// filter and duplicates are dropped, and the remaining ones are counted
+/// against [`Options::max_refs`] as they are retained. Only the retained refs
+/// are kept in memory beyond the lifetime of the response.
+pub struct LsRefs<F> {
     opt: Options,
+    filter: F,
+    seen: HashSet<BString>,
     out: Vec<Ref>,
+    exceeded: Option<TooManyRefs>,
 }
 
-impl LsRefs {
+impl LsRefs<fn(&Ref) -> bool> {
     pub fn new(opt: Options) -> Self {
+        Self::with_filter(opt, |_| true)
+    }
+}
+
+impl<F> LsRefs<F>
+where
+    F: FnMut(&Ref) -> bool,
+{
+    /// Create an [`LsRefs`] which only retains the refs `filter` returns
+    /// `true` for.
+    pub fn with_filter(opt: Options, filter: F) -> Self {
         Self {
             opt,
+            filter,
+            seen: HashSet::new(),
             out: Vec::new(),
+            exceeded: None,
         }
     }
+
+    fn retain(&mut self, refs: &[Ref]) -> Result<(), TooManyRefs> {
+        for r in refs {
+            if !(self.filter)(r) {
+                continue;
+            }
+            let (name, _, _) = r.unpack();
+            if self.seen.contains(name) {
+                continue;
+            }
+            if let Some(limit) = self.opt.max_refs {
+                if self.out.len() >= limit {
+                    return Err(TooManyRefs { limit });
+                }
+            }
+            self.seen.insert(name.to_owned());
+            self.out.push(r.clone());
+        }
+
+        Ok(())
+    }
 }
 
-impl DelegateBlocking for LsRefs {
+impl<F> DelegateBlocking for LsRefs<F>
+where
+    F: FnMut(&Ref) -> bool,
+{
     fn handshake_extra_parameters(&self) -> Vec<(String, Option<String>)> {
         self.opt.extra_params.clone()
     }
@@ -104,7 +188,10 @@ impl DelegateBlocking for LsRefs {
         _: &mut Vec<(&str, Option<&str>)>,
         refs: &[Ref],
     ) -> io::Result<Action> {
-        self.out.extend_from_slice(refs);
+        if let Err(e) = self.retain(refs) {
+            self.exceeded = Some(e);
+            return Err(e.into());
+        }
         Ok(Action::Cancel)
     }
 
@@ -119,7 +206,10 @@ impl DelegateBlocking for LsRefs {
 }
 
 #[async_trait(?Send)]
-impl Delegate for LsRefs {
+impl<F> Delegate for LsRefs<F>
+where
+    F: FnMut(&Ref) -> bool,
+{
     async fn receive_pack(
         &mut self,
         _: impl AsyncBufRead + Unpin + 'async_trait,
@@ -135,18 +225,64 @@ pub async fn ls_refs<R, W>(opt: Options, recv: R, send: W) -> io::Result<Vec<Ref
 where
     R: AsyncRead + Unpin,
     W: AsyncWrite + Unpin,
+{
+    ls_refs_with(opt, |_| true, recv, send).await
+}
+
+/// Like [`ls_refs`], but only return the refs `filter` returns `true` for.
+///
+/// Refs rejected by `filter` don't count towards [`Options::max_refs`].
+pub async fn ls_refs_with<F, R, W>(
+    opt: Options,
+    filter: F,
+    recv: R,
+    send: W,
+) -> io::Result<Vec<Ref>>
+where
+    F: FnMut(&Ref) -> bool,
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
 {
     let mut conn = transport::Stateless::new(opt.repo.clone(), recv, send);
-    let mut delegate = LsRefs::new(opt);
-    git_repository::protocol::fetch(
+    let mut delegate = LsRefs::with_filter(opt, filter);
+    let res = git_repository::protocol::fetch(
         &mut conn,
         &mut delegate,
         |_| unreachable!("credentials helper requested"),
         progress::Discard,
         protocol::FetchConnection::AllowReuse,
     )
-    .await
-    .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    .await;
+    match (res, delegate.exceeded) {
+        (_, Some(e)) => Err(e.into()),
+        (Err(e), None) => Err(io::Error::new(io::ErrorKind::Other, e)),
+        (Ok(()), None) => Ok(delegate.out),
+    }
+}
 
-    Ok(delegate.out)
+/// Like [`ls_refs`], but only ask for, and return, the refs `prefixes`
+/// includes and doesn't exclude.
+///
+/// [`Options::ref_prefixes`] is computed by [`RefPrefixes::to_ref_prefixes`]
+/// from `categories`. If there is nothing to ask for, no refs are returned
+/// without talking to the server.
+pub async fn ls_refs_matching<'a, C, R, W>(
+    mut opt: Options,
+    prefixes: &RefPrefixes,
+    categories: C,
+    recv: R,
+    send: W,
+) -> io::Result<Vec<Ref>>
+where
+    C: IntoIterator<Item = &'a str>,
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    match prefixes.to_ref_prefixes(categories) {
+        None => Ok(Vec::new()),
+        Some(ref_prefixes) => {
+            opt.ref_prefixes = ref_prefixes;
+            ls_refs_with(opt, |r| !prefixes.is_excluded(r.unpack().0), recv, send).await
+        },
+    }
 }
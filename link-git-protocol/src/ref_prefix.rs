@@ -0,0 +1,147 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::collections::BTreeSet;
+
+use bstr::{BStr, BString, ByteSlice as _, ByteVec as _};
+
+/// A set of ref prefixes, including exclusions.
+///
+/// The `ls-refs` command only understands positive prefixes (`ref-prefix`).
+/// In order to keep the ref advertisement small when whole hierarchies are not
+/// needed (eg. `refs/remotes/<peer>/cobs`), [`RefPrefixes::to_ref_prefixes`]
+/// computes the minimal set of positive prefixes which covers the included
+/// refs, but not the excluded ones.
+///
+/// Exclusions can only be expressed in terms of a known set of categories, ie.
+/// the hierarchies expected directly below a prefix (`heads`, `tags`, etc.).
+/// If an exclusion can not be expressed that way, the including prefix is
+/// retained, and the advertised refs must be filtered using
+/// [`RefPrefixes::is_excluded`]. [`crate::ls::ls_refs_matching`] does both.
+///
+/// Exclusions match on path component boundaries: `refs/notes` excludes
+/// `refs/notes` and `refs/notes/commits`, but not `refs/notes-old`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RefPrefixes {
+    include: BTreeSet<BString>,
+    exclude: BTreeSet<BString>,
+}
+
+impl RefPrefixes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask for refs matching `prefix`.
+    pub fn include(mut self, prefix: impl Into<BString>) -> Self {
+        self.include.insert(prefix.into());
+        self
+    }
+
+    /// Do not ask for refs matching `prefix`, even if they are matched by an
+    /// included prefix.
+    pub fn exclude(mut self, prefix: impl Into<BString>) -> Self {
+        self.exclude.insert(prefix.into());
+        self
+    }
+
+    /// `true` if `refname` is, or is below, any of the excluded prefixes.
+    pub fn is_excluded(&self, refname: impl AsRef<[u8]>) -> bool {
+        let refname = refname.as_ref();
+        self.exclude.iter().any(|e| is_below(e.as_bytes(), refname))
+    }
+
+    /// Compute the minimal set of positive prefixes, suitable for
+    /// [`crate::ls::Options::ref_prefixes`].
+    ///
+    /// Returns `None` if there is nothing to ask for, ie. if nothing is
+    /// included, or everything included is also excluded. Note that an empty
+    /// [`crate::ls::Options::ref_prefixes`] asks for all refs instead.
+    ///
+    /// `categories` enumerates the hierarchies which may appear directly below
+    /// a prefix, eg. `["heads", "tags", "notes", "rad", "cobs"]` for
+    /// `refs/remotes/<peer>/`. A prefix is only expanded into its categories
+    /// if all exclusions below it name one of them as their next path
+    /// component.
+    pub fn to_ref_prefixes<'a, I>(&self, categories: I) -> Option<Vec<BString>>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let categories = categories.into_iter().collect::<BTreeSet<_>>();
+        let mut out = BTreeSet::new();
+        for prefix in minimal(&self.include) {
+            self.expand(prefix.as_bstr(), &categories, &mut out)
+        }
+
+        if out.is_empty() {
+            None
+        } else {
+            Some(minimal(&out))
+        }
+    }
+
+    fn expand(&self, prefix: &BStr, categories: &BTreeSet<&str>, out: &mut BTreeSet<BString>) {
+        if self.is_excluded(prefix) {
+            return;
+        }
+
+        let below = self
+            .exclude
+            .iter()
+            .filter(|e| is_below(prefix.as_bytes(), e.as_bytes()))
+            .filter_map(|e| e.strip_prefix(prefix.as_bytes()))
+            .collect::<Vec<_>>();
+        if below.is_empty() {
+            out.insert(prefix.to_owned());
+            return;
+        }
+
+        let expandable = prefix.ends_with(b"/")
+            && below.iter().all(|rest| {
+                rest.split_str("/")
+                    .next()
+                    .and_then(|c| c.to_str().ok())
+                    .map(|c| categories.contains(c))
+                    .unwrap_or(false)
+            });
+        if !expandable {
+            out.insert(prefix.to_owned());
+            return;
+        }
+
+        for category in categories {
+            let mut sub = prefix.to_owned();
+            sub.push_str(category);
+            sub.push_char('/');
+            self.expand(sub.as_bstr(), categories, out)
+        }
+    }
+}
+
+/// Whether `refname` is `prefix`, or below it.
+///
+/// A `prefix` ending in `/` is a plain byte prefix, otherwise `refname` must
+/// continue with a path separator after `prefix`.
+fn is_below(prefix: &[u8], refname: &[u8]) -> bool {
+    match refname.strip_prefix(prefix) {
+        None => false,
+        Some(rest) => prefix.ends_with(b"/") || rest.is_empty() || rest.starts_with(b"/"),
+    }
+}
+
+/// Remove prefixes which are already covered by a shorter one.
+///
+/// This is a plain byte prefix match, just like `ref-prefix` in `ls-refs`.
+fn minimal(prefixes: &BTreeSet<BString>) -> Vec<BString> {
+    let mut out: Vec<BString> = Vec::with_capacity(prefixes.len());
+    // Lexicographic order guarantees that a covering prefix is visited before
+    // the prefixes it covers.
+    for prefix in prefixes {
+        if !out.iter().any(|p| prefix.starts_with(p.as_bytes())) {
+            out.push(prefix.clone())
+        }
+    }
+    out
+}
@@ -14,7 +14,7 @@ pub mod direct;
 pub mod indirect;
 
 pub use direct::Direct;
-pub use indirect::Indirect;
+pub use indirect::{Indirect, Nested};
 
 /// Types which define trust delegations.
 pub trait Delegations: sealed::Sealed {
@@ -18,7 +18,7 @@ use crypto::PublicKey;
 use either::*;
 
 use super::{Delegations, Direct};
-use crate::{generic, payload, sealed};
+use crate::{generic, payload, sealed, urn::Urn};
 
 pub mod error {
     use std::fmt::{Debug, Display};
@@ -43,12 +43,48 @@ pub mod error {
 
 pub type IndirectlyDelegating<T, R, C> = generic::Identity<generic::Doc<T, Direct, R>, R, C>;
 
+/// A delegation to another [`Indirect`]ly delegating identity, such as an
+/// organisation's project, identified by its root revision.
+///
+/// The keys of a nested delegation are not stored alongside the delegating
+/// identity, but must be [`Indirect::resolve`]d from the history of the nested
+/// identity. Until then, the nested delegation counts towards the
+/// quorum, but can not vote.
+#[derive(Clone, Debug)]
+pub struct Nested<R> {
+    pub root: R,
+    resolved: bool,
+}
+
+impl<R> Nested<R> {
+    pub fn is_resolved(&self) -> bool {
+        self.resolved
+    }
+}
+
+/// The owner of a key in an [`Indirect`] delegation.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+enum Owner {
+    /// The key is delegated to directly.
+    Direct,
+    /// The key belongs to the indirectly delegating identity at the given
+    /// position.
+    Identity(usize),
+    /// The key belongs to the nested delegation at the given position.
+    Nested(usize),
+}
+
 /// [`Delegations`] to either a [`PublicKey`]s directly, or another identity
-/// (which itself must only contain [`Direct`] delegations).
+/// (which itself must only contain [`Direct`] delegations), or a [`Nested`]
+/// identity (which may in turn contain [`Indirect`] delegations).
+///
+/// Every indirectly delegating or nested identity has a single vote, which can
+/// be cast by any of its keys.
 #[derive(Clone, Debug)]
 pub struct Indirect<T, R, C> {
     identities: Vec<IndirectlyDelegating<T, R, C>>,
-    delegations: BTreeMap<PublicKey, Option<usize>>,
+    nested: Vec<Nested<R>>,
+    delegations: BTreeMap<PublicKey, Owner>,
 }
 
 impl<T, R, C> Indirect<T, R, C> {
@@ -72,9 +108,9 @@ impl<T, R, C> Indirect<T, R, C> {
         let mut dels = BTreeMap::new();
         let mut roots = BTreeSet::new();
 
-        let mut insert = |key: PublicKey, pos: Option<usize>| match dels.entry(key) {
+        let mut insert = |key: PublicKey, owner: Owner| match dels.entry(key) {
             Entry::Vacant(entry) => {
-                entry.insert(pos);
+                entry.insert(owner);
                 Ok(())
             },
             Entry::Occupied(entry) => Err(DuplicateKey(*entry.key())),
@@ -82,7 +118,7 @@ impl<T, R, C> Indirect<T, R, C> {
 
         for d in iter {
             match d {
-                Either::Left(key) => insert(key, None)?,
+                Either::Left(key) => insert(key, Owner::Direct)?,
                 Either::Right(id) => {
                     if !roots.insert(id.root.clone()) {
                         return Err(DuplicateIdentity(id.root));
@@ -92,7 +128,7 @@ impl<T, R, C> Indirect<T, R, C> {
                     let pos = ids.len() - 1;
 
                     for key in &ids[pos].doc.delegations {
-                        insert(*key, Some(pos))?
+                        insert(*key, Owner::Identity(pos))?
                     }
                 },
             }
@@ -100,15 +136,94 @@ impl<T, R, C> Indirect<T, R, C> {
 
         Ok(Self {
             identities: ids,
+            nested: vec![],
             delegations: dels,
         })
     }
 
+    /// Add [`Nested`] delegations to the identities with the given root
+    /// revisions.
+    ///
+    /// # Errors
+    ///
+    /// If a root is encountered which refers to the same root revision as a
+    /// previous [`IndirectlyDelegating`] or [`Nested`] identity.
+    pub fn with_nested<I>(mut self, roots: I) -> Result<Self, error::FromIter<R>>
+    where
+        I: IntoIterator<Item = R>,
+        R: Clone + Display + Debug + Ord,
+    {
+        let mut seen = self
+            .identities
+            .iter()
+            .map(|id| id.root.clone())
+            .chain(self.nested.iter().map(|nested| nested.root.clone()))
+            .collect::<BTreeSet<_>>();
+        for root in roots {
+            if !seen.insert(root.clone()) {
+                return Err(error::FromIter::DuplicateIdentity(root));
+            }
+            self.nested.push(Nested {
+                root,
+                resolved: false,
+            })
+        }
+
+        Ok(self)
+    }
+
+    /// Resolve the [`Nested`] delegation with root revision `root` to the
+    /// given `keys`.
+    ///
+    /// Keys which are already delegated to, either directly or through
+    /// another identity, are skipped: they vote on behalf of their existing
+    /// owner. Resolving a nested delegation again replaces its keys.
+    ///
+    /// Returns `false` if there is no nested delegation with root `root`.
+    pub fn resolve<I>(&mut self, root: &R, keys: I) -> bool
+    where
+        I: IntoIterator<Item = PublicKey>,
+        R: PartialEq,
+    {
+        match self.nested.iter().position(|nested| &nested.root == root) {
+            None => false,
+            Some(pos) => {
+                let owner = Owner::Nested(pos);
+                self.delegations.retain(|_, o| *o != owner);
+                for key in keys {
+                    self.delegations.entry(key).or_insert(owner);
+                }
+                self.nested[pos].resolved = true;
+                true
+            },
+        }
+    }
+
     /// Get the owning [`generic::Identity`] of the given key, if any.
     pub fn owner(&self, key: &PublicKey) -> Option<&IndirectlyDelegating<T, R, C>> {
-        self.delegations
-            .get(key)
-            .and_then(|idx| idx.map(|idx| &self.identities[idx]))
+        self.delegations.get(key).and_then(|owner| match owner {
+            Owner::Identity(idx) => Some(&self.identities[*idx]),
+            _ => None,
+        })
+    }
+
+    /// Get the [`Nested`] delegation the given key was resolved from, if any.
+    pub fn nested_owner(&self, key: &PublicKey) -> Option<&Nested<R>> {
+        self.delegations.get(key).and_then(|owner| match owner {
+            Owner::Nested(idx) => Some(&self.nested[*idx]),
+            _ => None,
+        })
+    }
+
+    /// The [`Nested`] delegations, which are not yielded by [`Self::iter`].
+    pub fn nested(&self) -> impl Iterator<Item = &Nested<R>> {
+        self.nested.iter()
+    }
+
+    /// All keys which are eligible to vote, including the ones of resolved
+    /// [`Nested`] delegations.
+    pub fn keys(&self) -> impl Iterator<Item = &PublicKey> {
+        self.delegations.keys()
     }
 
     /// In addition to checking whether the given [`PublicKey`]s are in the set
@@ -125,11 +240,9 @@ impl<T, R, C> Indirect<T, R, C> {
         self.delegations
             .iter()
             .filter(|(k, _)| votes.contains(k))
-            .try_fold(BTreeSet::new(), |mut acc, (k, idx)| {
-                if let Some(id) = idx {
-                    if !id_votes.insert(id) {
-                        return Err(error::DoubleVote);
-                    }
+            .try_fold(BTreeSet::new(), |mut acc, (k, owner)| {
+                if *owner != Owner::Direct && !id_votes.insert(owner) {
+                    return Err(error::DoubleVote);
                 }
 
                 acc.insert(k);
@@ -147,7 +260,8 @@ impl<T, R, C> From<PublicKey> for Indirect<T, R, C> {
     fn from(pk: PublicKey) -> Self {
         Self {
             identities: vec![],
-            delegations: Some((pk, None)).into_iter().collect(),
+            nested: vec![],
+            delegations: Some((pk, Owner::Direct)).into_iter().collect(),
         }
     }
 }
@@ -157,6 +271,7 @@ impl<T, R, C> From<IndirectlyDelegating<T, R, C>> for Indirect<T, R, C> {
     fn from(id: IndirectlyDelegating<T, R, C>) -> Self {
         Self {
             identities: vec![id],
+            nested: vec![],
             delegations: Default::default(),
         }
     }
@@ -166,15 +281,20 @@ impl<T, R, C> From<Indirect<T, R, C>> for payload::ProjectDelegations<R>
 where
     R: Clone + Ord,
 {
-    fn from(this: Indirect<T, R, C>) -> Self {
+    fn from(mut this: Indirect<T, R, C>) -> Self {
+        let nested = std::mem::take(&mut this.nested)
+            .into_iter()
+            .map(|nested| Right(Urn::from(nested.root)));
         this.into_iter()
             .map(|x| x.map_right(|id| id.urn()))
+            .chain(nested)
             .collect()
     }
 }
 
 /// Yields direct delegations as `Left(&PublicKey)`, and indirect ones as
-/// `Right(&IndirectlyDelegating)`, with no duplicates. I.e. it holds that:
+/// `Right(&IndirectlyDelegating)`, with no duplicates. [`Nested`] delegations
+/// are not yielded. I.e. it holds that, in the absence of nested delegations:
 ///
 /// ```text
 /// let x = Indirect::try_from_iter(y)?;
@@ -183,7 +303,7 @@ where
 #[must_use = "iterators are lazy and do nothing unless consumed"]
 pub struct Iter<'a, T, R, C> {
     identities: slice::Iter<'a, IndirectlyDelegating<T, R, C>>,
-    delegations: btree_map::Iter<'a, PublicKey, Option<usize>>,
+    delegations: btree_map::Iter<'a, PublicKey, Owner>,
 }
 
 impl<'a, T, R, C> Iter<'a, T, R, C> {
@@ -204,7 +324,7 @@ impl<'a, T, R, C> Iterator for Iter<'a, T, R, C> {
     fn next(&mut self) -> Option<Self::Item> {
         self.identities.next().map(Right).or_else(|| {
             for (pk, pos) in &mut self.delegations {
-                if pos.is_none() {
+                if *pos == Owner::Direct {
                     return Some(Left(pk));
                 }
             }
@@ -227,7 +347,8 @@ impl<'a, T, R, C> IntoIterator for &'a Indirect<T, R, C> {
 }
 
 /// Yields direct delegations as `Left(PublicKey)`, and indirect ones as
-/// `Right(IndirectlyDelegating)`, with no duplicates. I.e. it holds that:
+/// `Right(IndirectlyDelegating)`, with no duplicates. [`Nested`] delegations
+/// are not yielded. I.e. it holds that, in the absence of nested delegations:
 ///
 /// ```text
 /// let x = Indirect::try_from_iter(y)?;
@@ -236,7 +357,7 @@ impl<'a, T, R, C> IntoIterator for &'a Indirect<T, R, C> {
 #[must_use = "iterators are lazy and do nothing unless consumed"]
 pub struct IntoIter<T, R, C> {
     identities: vec::IntoIter<IndirectlyDelegating<T, R, C>>,
-    delegations: btree_map::IntoIter<PublicKey, Option<usize>>,
+    delegations: btree_map::IntoIter<PublicKey, Owner>,
 }
 
 impl<T, R, C> IntoIter<T, R, C> {
@@ -257,7 +378,7 @@ impl<T, R, C> Iterator for IntoIter<T, R, C> {
     fn next(&mut self) -> Option<Self::Item> {
         self.identities.next().map(Right).or_else(|| {
             for (pk, pos) in &mut self.delegations {
-                if pos.is_none() {
+                if *pos == Owner::Direct {
                     return Some(Left(pk));
                 }
             }
@@ -290,9 +411,9 @@ impl<T, R, C> Delegations for Indirect<T, R, C> {
         let direct = self
             .delegations
             .iter()
-            .filter(|(_, idx)| idx.is_none())
+            .filter(|(_, owner)| **owner == Owner::Direct)
             .count();
-        let indirect = self.identities.len();
+        let indirect = self.identities.len() + self.nested.len();
 
         (direct + indirect) / 2
     }
@@ -312,6 +433,16 @@ pub mod test {
     pub fn eq<T, R: Ord, C: Ord>(this: &Indirect<T, R, C>, other: &Indirect<T, R, C>) -> bool {
         this.delegations.len() == other.delegations.len()
             && this.identities.len() == other.identities.len()
+            && this
+                .nested
+                .iter()
+                .map(|nested| &nested.root)
+                .collect::<BTreeSet<_>>()
+                == other
+                    .nested
+                    .iter()
+                    .map(|nested| &nested.root)
+                    .collect::<BTreeSet<_>>()
             && this
                 .delegations
                 .keys()
@@ -291,6 +291,16 @@ impl<T, S> Verifying<T, S> {
     }
 }
 
+impl<T> Verifying<T, Untrusted> {
+    /// Assume `self` has already been verified.
+    ///
+    /// Only for use with inputs which are known to have passed verification
+    /// before, such as a checkpoint recorded after a successful verification.
+    pub(crate) fn assume_verified(self) -> Verifying<T, Verified> {
+        self.coerce()
+    }
+}
+
 impl<T> From<T> for Verifying<T, Untrusted> {
     fn from(t: T) -> Self {
         Self::from_untrusted(t)
@@ -492,43 +502,65 @@ impl<T, R, C> Verifying<Identity<T, R, C>, Verified> {
 
         E: std::error::Error + Send + Sync + 'static,
     {
-        progeny.try_fold(
-            Folded {
-                head: self,
-                parent: None,
-            },
-            |acc, cur| {
-                // Not signed is an error
-                let signed = cur.map_err(error::Verify::history)?.signed()?;
-                match signed.quorum() {
-                    // Not reaching quorum is ok, skip
-                    Err(_) => Ok(acc),
-                    Ok(quorum) => {
-                        // A confirmation of `self` is ok, but `parent` stays
-                        // the same then. We need to be careful to not let a
-                        // current quorum invalidate our already-confirmed state
-                        // -- so skip if this doesn't pass `verified`, instead
-                        // of returning an error (which would render this
-                        // history invalid).
-                        if quorum.revision == acc.head.revision
-                            && quorum.doc.replaces() == acc.head.doc.replaces()
-                        {
-                            match quorum.verified(acc.parent.as_ref()) {
-                                Err(_) => Ok(acc),
-                                Ok(verified) => Ok(Folded {
-                                    head: verified,
-                                    parent: acc.parent,
-                                }),
-                            }
-                        } else {
-                            quorum.verified(Some(&acc.head)).map(|verified| Folded {
+        Folded {
+            head: self,
+            parent: None,
+        }
+        .resume(progeny)
+    }
+}
+
+impl<T, R, C> Folded<T, R, C> {
+    /// Continue a [`Verifying::verify`] fold with further progeny.
+    ///
+    /// This allows to resume the verification of a history from a previously
+    /// obtained [`Folded`] result, such that only the identities added to the
+    /// history since need to be verified. The same ordering requirements as
+    /// for [`Verifying::verify`] apply.
+    pub fn resume<E>(
+        self,
+        mut progeny: impl Iterator<Item = Result<Verifying<Identity<T, R, C>, Untrusted>, E>>,
+    ) -> Result<Self, error::Verify<R, C>>
+    where
+        T: Delegations + Replaces<Revision = R>,
+        T::Error: std::error::Error + Send + Sync + 'static,
+
+        R: Clone + Debug + Display + PartialEq + AsRef<[u8]>,
+        C: Clone + Debug + Display,
+
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        progeny.try_fold(self, |acc, cur| {
+            // Not signed is an error
+            let signed = cur.map_err(error::Verify::history)?.signed()?;
+            match signed.quorum() {
+                // Not reaching quorum is ok, skip
+                Err(_) => Ok(acc),
+                Ok(quorum) => {
+                    // A confirmation of `self` is ok, but `parent` stays
+                    // the same then. We need to be careful to not let a
+                    // current quorum invalidate our already-confirmed state
+                    // -- so skip if this doesn't pass `verified`, instead
+                    // of returning an error (which would render this
+                    // history invalid).
+                    if quorum.revision == acc.head.revision
+                        && quorum.doc.replaces() == acc.head.doc.replaces()
+                    {
+                        match quorum.verified(acc.parent.as_ref()) {
+                            Err(_) => Ok(acc),
+                            Ok(verified) => Ok(Folded {
                                 head: verified,
-                                parent: Some(acc.head),
-                            })
+                                parent: acc.parent,
+                            }),
                         }
-                    },
-                }
-            },
-        )
+                    } else {
+                        quorum.verified(Some(&acc.head)).map(|verified| Folded {
+                            head: verified,
+                            parent: Some(acc.head),
+                        })
+                    }
+                },
+            }
+        })
     }
 }
@@ -3,7 +3,12 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::{convert::TryFrom, fmt::Debug, marker::PhantomData};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryFrom,
+    fmt::Debug,
+    marker::PhantomData,
+};
 
 use canonical::Cjson;
 use crypto::{PublicKey, Signer};
@@ -11,6 +16,7 @@ use either::*;
 use futures_lite::future::block_on;
 use git_ext as ext;
 use multihash::Multihash;
+use serde::{Deserialize, Serialize};
 
 use crate::{
     delegation::{self, Delegations},
@@ -81,9 +87,64 @@ pub type VerificationError = generic::error::Verify<Revision, ContentId>;
 
 pub type IndirectDelegation = delegation::Indirect<PersonPayload, Revision, ContentId>;
 
+/// The maximum depth of [`delegation::Nested`] delegations of a project.
+pub const MAX_NESTING: usize = 8;
+
+/// A record of a successful verification of an identity history.
+///
+/// Passing a [`Checkpoint`] to eg. [`Identities::<Person>::verify_with`] allows
+/// to resume verification from the recorded state, instead of walking the
+/// entire history again. Note that checkpoints are trusted: they must only be
+/// obtained from a previous verification, and stored in a location only the
+/// local peer can write to.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Checkpoint {
+    /// The commit of the most recent verified identity.
+    pub head: ContentId,
+    /// The commit of the verified parent of `head`, if any.
+    pub parent: Option<ContentId>,
+}
+
+impl<T> From<&generic::Folded<T, Revision, ContentId>> for Checkpoint {
+    fn from(folded: &generic::Folded<T, Revision, ContentId>) -> Self {
+        Self {
+            head: folded.head.content_id,
+            parent: folded.parent.as_ref().map(|parent| parent.content_id),
+        }
+    }
+}
+
+/// Progress of a verification, reported for every identity commit visited.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Progress {
+    /// The commit which is about to be verified.
+    pub content_id: ContentId,
+    /// The number of commits visited so far, including this one.
+    pub visited: usize,
+}
+
+/// A cache of successful verification results.
+///
+/// Entries are keyed by a digest of the head commit of the verified history,
+/// and -- in the case of projects -- the resolved indirect delegations.
+/// Because entries are trusted, implementations must ensure that only the
+/// local peer can write to the cache.
+///
+/// If the objects an entry refers to can no longer be loaded, the entry is
+/// ignored.
+pub trait VerificationCache {
+    /// Look up the result cached for `key`.
+    fn get(&self, key: git2::Oid) -> Option<Checkpoint>;
+
+    /// Cache `result` for `key`.
+    fn put(&self, key: git2::Oid, result: Checkpoint);
+}
+
 #[derive(Clone)]
 pub struct Identities<'a, T> {
     repo: &'a git2::Repository,
+    cache: Option<&'a dyn VerificationCache>,
     _marker: PhantomData<T>,
 }
 
@@ -91,6 +152,7 @@ impl<'a, T: 'a> From<&'a git2::Repository> for Identities<'a, T> {
     fn from(repo: &'a git2::Repository) -> Self {
         Self {
             repo,
+            cache: None,
             _marker: PhantomData,
         }
     }
@@ -98,7 +160,11 @@ impl<'a, T: 'a> From<&'a git2::Repository> for Identities<'a, T> {
 
 impl<'a, T: 'a> From<&Identities<'a, T>> for Identities<'a, T> {
     fn from(other: &Identities<'a, T>) -> Self {
-        Identities::from(other.repo)
+        Identities {
+            repo: other.repo,
+            cache: other.cache,
+            _marker: PhantomData,
+        }
     }
 }
 
@@ -126,10 +192,19 @@ impl<'a, T: 'a> Identities<'a, T> {
     pub fn coerce<U>(&self) -> Identities<'_, U> {
         Identities {
             repo: self.repo,
+            cache: self.cache,
             _marker: PhantomData,
         }
     }
 
+    /// Use `cache` to avoid verifying the same histories repeatedly.
+    pub fn with_cache(self, cache: &'a dyn VerificationCache) -> Self {
+        Self {
+            cache: Some(cache),
+            ..self
+        }
+    }
+
     /// Read an identity whose type is not statically known from commit `oid`.
     ///
     /// The only guarantee about the returned value is that it is well-formed --
@@ -187,19 +262,124 @@ impl<'a, T: 'a> Identities<'a, T> {
 
         Identity<Doc>: TryFrom<ByOid<'a>, Error = error::Load>,
     {
-        let mut progeny = Iter::<'_, Identity<Doc>>::new(self.repo, head)
-            .map_err(generic::error::Verify::history)?;
+        self.fold_verify_generic_with(head, None, |_| {}, |_| {})
+    }
+
+    /// Fold the history with head commit `head`, applying `resolve` to every
+    /// identity before it is verified.
+    fn fold_verify_generic_with<Doc, P, R>(
+        &self,
+        head: git2::Oid,
+        resume_from: Option<Checkpoint>,
+        progress: P,
+        mut resolve: R,
+    ) -> Result<generic::Folded<Doc, Revision, ContentId>, VerificationError>
+    where
+        Doc: Delegations + generic::Replaces<Revision = Revision>,
+        <Doc as Delegations>::Error: std::error::Error + Send + Sync + 'static,
+
+        Identity<Doc>: TryFrom<ByOid<'a>, Error = error::Load>,
+
+        P: FnMut(Progress),
+        R: FnMut(&mut Identity<Doc>),
+    {
+        if let Some(folded) = self.cached(head) {
+            return Ok(resolve_folded(folded, &mut resolve));
+        }
+
+        let folded = self.fold_verify_uncached(head, resume_from, progress, resolve, |_| {})?;
+        if let Some(cache) = self.cache {
+            cache.put(head, Checkpoint::from(&folded));
+        }
+
+        Ok(folded)
+    }
+
+    /// Fold the history with head commit `head`, bypassing the cache, and
+    /// passing the head to `accepted` after every step of the fold.
+    fn fold_verify_uncached<Doc, P, R, A>(
+        &self,
+        head: git2::Oid,
+        resume_from: Option<Checkpoint>,
+        mut progress: P,
+        mut resolve: R,
+        mut accepted: A,
+    ) -> Result<generic::Folded<Doc, Revision, ContentId>, VerificationError>
+    where
+        Doc: Delegations + generic::Replaces<Revision = Revision>,
+        <Doc as Delegations>::Error: std::error::Error + Send + Sync + 'static,
+
+        Identity<Doc>: TryFrom<ByOid<'a>, Error = error::Load>,
+
+        P: FnMut(Progress),
+        R: FnMut(&mut Identity<Doc>),
+        A: FnMut(&VerifiedIdentity<Doc>),
+    {
+        let mut resumed = None;
+        if let Some(checkpoint) = resume_from {
+            if self
+                .is_first_parent_ancestor(*checkpoint.head, head)
+                .map_err(generic::error::Verify::history)?
+            {
+                let folded = self
+                    .load_checkpoint(checkpoint)
+                    .map(|folded| resolve_folded(folded, &mut resolve))
+                    .map_err(generic::error::Verify::history)?;
+                let progeny = Iter::<'_, Identity<Doc>>::after(self.repo, head, *checkpoint.head)
+                    .map_err(generic::error::Verify::history)?;
+                resumed = Some((folded, progeny));
+            } else {
+                tracing::debug!(
+                    checkpoint = %checkpoint.head,
+                    "checkpoint not in history, verifying from the root"
+                );
+            }
+        }
 
-        // TODO(kim): should we skip non-quorum commits at the beginning?
-        let root = progeny
-            .next()
-            .ok_or(generic::error::Verify::EmptyHistory)?
-            .map_err(generic::error::Verify::history)?
-            .signed()?
-            .quorum()?
-            .verified(None)?;
+        let mut visited = 0;
+        let mut report =
+            |item: Result<Verifying<Identity<Doc>, generic::Untrusted>, error::Load>| {
+                item.map(|identity| {
+                    visited += 1;
+                    progress(Progress {
+                        content_id: identity.content_id,
+                        visited,
+                    });
+                    let mut identity = identity.into_inner();
+                    resolve(&mut identity);
+                    Verifying::from(identity)
+                })
+            };
+
+        let (folded, progeny) = match resumed {
+            Some(resumed) => resumed,
+            None => {
+                let mut progeny = Iter::<'_, Identity<Doc>>::new(self.repo, head)
+                    .map_err(generic::error::Verify::history)?;
+
+                // TODO(kim): should we skip non-quorum commits at the beginning?
+                let root = progeny
+                    .next()
+                    .ok_or(generic::error::Verify::EmptyHistory)
+                    .map(&mut report)?
+                    .map_err(generic::error::Verify::history)?
+                    .signed()?
+                    .quorum()?
+                    .verified(None)?;
+                let folded = generic::Folded {
+                    head: root,
+                    parent: None,
+                };
+                (folded, progeny)
+            },
+        };
 
-        root.verify(progeny)
+        accepted(&folded.head);
+        progeny.map(report).try_fold(folded, |folded, item| {
+            let folded = folded.resume(std::iter::once(item))?;
+            accepted(&folded.head);
+            Ok(folded)
+        })
     }
 
     //// Helpers ////
@@ -208,6 +388,58 @@ impl<'a, T: 'a> Identities<'a, T> {
         (self.repo, oid)
     }
 
+    fn cached<Doc>(&self, key: git2::Oid) -> Option<generic::Folded<Doc, Revision, ContentId>>
+    where
+        Identity<Doc>: TryFrom<ByOid<'a>, Error = error::Load>,
+    {
+        let checkpoint = self.cache?.get(key)?;
+        match self.load_checkpoint(checkpoint) {
+            Ok(folded) => Some(folded),
+            Err(e) => {
+                tracing::warn!(err = %e, key = %key, "ignoring invalid cache entry");
+                None
+            },
+        }
+    }
+
+    fn load_checkpoint<Doc>(
+        &self,
+        checkpoint: Checkpoint,
+    ) -> Result<generic::Folded<Doc, Revision, ContentId>, error::Load>
+    where
+        Identity<Doc>: TryFrom<ByOid<'a>, Error = error::Load>,
+    {
+        let load = |oid: ContentId| {
+            Identity::<Doc>::try_from(self.by_oid(*oid))
+                .map(|identity| Verifying::from(identity).assume_verified())
+        };
+
+        Ok(generic::Folded {
+            head: load(checkpoint.head)?,
+            parent: checkpoint.parent.map(load).transpose()?,
+        })
+    }
+
+    /// `true` if `ancestor` is on the first-parent history of `head`, which is
+    /// the history [`Iter`] traverses.
+    fn is_first_parent_ancestor(
+        &self,
+        ancestor: git2::Oid,
+        head: git2::Oid,
+    ) -> Result<bool, git2::Error> {
+        let mut revwalk = self.repo.revwalk()?;
+        revwalk.simplify_first_parent()?;
+        revwalk.push(head)?;
+
+        for oid in revwalk {
+            if oid? == ancestor {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
     fn is_in_ancestry_path(&self, commit: git2::Oid, tree: git2::Oid) -> Result<bool, git2::Error> {
         let mut revwalk = self.repo.revwalk()?;
         revwalk.set_sorting(git2::Sort::TOPOLOGICAL)?;
@@ -442,6 +674,29 @@ impl<'a> Identities<'a, Person> {
         Ok(self.verify_generic(head)?)
     }
 
+    /// Verify the person history with head commit `head`, reporting
+    /// `progress` for every commit visited.
+    ///
+    /// If `resume_from` is given, and is part of the history, only the commits
+    /// added since the [`Checkpoint`] are verified. Otherwise, the whole
+    /// history is verified.
+    ///
+    /// In addition to the [`VerifiedPerson`], a [`Checkpoint`] for resuming
+    /// subsequent verifications is returned.
+    pub fn verify_with<P>(
+        &self,
+        head: git2::Oid,
+        resume_from: Option<Checkpoint>,
+        progress: P,
+    ) -> Result<(VerifiedPerson, Checkpoint), error::VerifyPerson>
+    where
+        P: FnMut(Progress),
+    {
+        let folded = self.fold_verify_generic_with(head, resume_from, progress, |_| {})?;
+        let checkpoint = Checkpoint::from(&folded);
+        Ok((folded.head, checkpoint))
+    }
+
     /// Create a new [`Person`] from a payload and delegations.
     ///
     /// The returned [`Person`] (and the underlying commit) will not have any
@@ -588,20 +843,238 @@ impl<'a> Identities<'a, Project> {
         F: Fn(Urn) -> Result<git2::Oid, E>,
         E: std::error::Error + Send + Sync + 'static,
     {
-        let generic::Folded { head, parent } = self.fold_verify_generic::<ProjectDoc>(head)?;
+        self.verify_with(head, find_latest_head, None, |_| {})
+            .map(|(verified, _)| verified)
+    }
+
+    /// Verify the project history with head commit `head`, reporting
+    /// `progress` for every commit visited.
+    ///
+    /// If `resume_from` is given, and is part of the history, only the commits
+    /// added since the [`Checkpoint`] are verified. Otherwise, the whole
+    /// history is verified. The indirect delegations are always resolved
+    /// anew, cf. [`Self::verify`].
+    ///
+    /// In addition to the [`VerifiedProject`], a [`Checkpoint`] for resuming
+    /// subsequent verifications is returned.
+    ///
+    /// Delegations to other projects (eg. of an organisation) are
+    /// [`delegation::Nested`]: the latest head of the delegated project is
+    /// obtained via `find_latest_head`, and its history verified recursively.
+    /// Keys eligible to vote on the delegated project become eligible to cast
+    /// the single vote of the nested delegation. Verification fails if the
+    /// nested delegations form a cycle, or are nested deeper than
+    /// [`MAX_NESTING`] levels.
+    ///
+    /// The nested delegations of every revision are resolved according to the
+    /// history of the delegated project: a revision committed at a given time
+    /// may be signed by the keys of the delegated project's revision in
+    /// effect at that time, or of any later one. Keys removed from the
+    /// delegated project before that time are not eligible. The `head`,
+    /// however, must be signed by the keys of the delegated project's latest
+    /// head.
+    pub fn verify_with<F, E, P>(
+        &self,
+        head: git2::Oid,
+        find_latest_head: F,
+        resume_from: Option<Checkpoint>,
+        progress: P,
+    ) -> Result<(VerifiedProject, Checkpoint), error::VerifyProject>
+    where
+        F: Fn(Urn) -> Result<git2::Oid, E>,
+        E: std::error::Error + Send + Sync + 'static,
+        P: FnMut(Progress),
+    {
+        self.verify_nested(
+            head,
+            &find_latest_head,
+            resume_from,
+            progress,
+            &mut Vec::new(),
+            None,
+        )
+        .map(|(verified, checkpoint, _)| (verified, checkpoint))
+    }
+
+    /// [`Self::verify_with`], where `path` are the roots of the projects
+    /// currently being verified, which delegate to the one at `head`.
+    ///
+    /// In addition to the result of [`Self::verify_with`], the content ids of
+    /// all resolved delegations are returned.
+    ///
+    /// If `membership` is given, the history of the project's eligible keys is
+    /// recorded into it. This requires to verify the whole history.
+    fn verify_nested<F, E, P>(
+        &self,
+        head: git2::Oid,
+        find_latest_head: &F,
+        resume_from: Option<Checkpoint>,
+        progress: P,
+        path: &mut Vec<Revision>,
+        membership: Option<&mut Membership>,
+    ) -> Result<(VerifiedProject, Checkpoint, Vec<ContentId>), error::VerifyProject>
+    where
+        F: Fn(Urn) -> Result<git2::Oid, E>,
+        E: std::error::Error + Send + Sync + 'static,
+        P: FnMut(Progress),
+    {
+        path.push(self.get(head)?.root);
+        let res = self.verify_nested_in_path(
+            head,
+            find_latest_head,
+            resume_from,
+            progress,
+            path,
+            membership,
+        );
+        path.pop();
+        res
+    }
+
+    fn verify_nested_in_path<F, E, P>(
+        &self,
+        head: git2::Oid,
+        find_latest_head: &F,
+        resume_from: Option<Checkpoint>,
+        progress: P,
+        path: &mut Vec<Revision>,
+        membership: Option<&mut Membership>,
+    ) -> Result<(VerifiedProject, Checkpoint, Vec<ContentId>), error::VerifyProject>
+    where
+        F: Fn(Urn) -> Result<git2::Oid, E>,
+        E: std::error::Error + Send + Sync + 'static,
+        P: FnMut(Progress),
+    {
+        // Nested delegations are resolved as they are encountered in the
+        // history, according to the commit time of the delegating revision.
+        let mut resolved = Vec::new();
+        let mut nested = BTreeMap::<Revision, Membership>::new();
+        let mut failed: Option<error::VerifyProject> = None;
+        let mut accepted: Vec<(ContentId, Revision, BTreeSet<PublicKey>)> = Vec::new();
+        let folded = {
+            let mut resolve = |project: &mut Project| {
+                let time = match self.repo.find_commit(*project.content_id) {
+                    Ok(commit) => commit.time().seconds(),
+                    Err(e) => {
+                        failed.get_or_insert(e.into());
+                        return;
+                    },
+                };
+                let roots = project
+                    .delegations()
+                    .nested()
+                    .map(|nested| nested.root)
+                    .collect::<Vec<_>>();
+                for root in roots {
+                    if !nested.contains_key(&root) {
+                        match self.resolve_nested(root, find_latest_head, path, &mut resolved) {
+                            Ok(membership) => {
+                                nested.insert(root, membership);
+                            },
+                            Err(e) => {
+                                failed.get_or_insert(e);
+                                continue;
+                            },
+                        }
+                    }
+                    project
+                        .doc
+                        .delegations
+                        .resolve(&root, nested[&root].at(time));
+                }
+            };
+
+            if membership.is_none() {
+                self.fold_verify_generic_with::<ProjectDoc, _, _>(
+                    head,
+                    resume_from,
+                    progress,
+                    &mut resolve,
+                )
+            } else {
+                self.fold_verify_uncached::<ProjectDoc, _, _, _>(
+                    head,
+                    resume_from,
+                    progress,
+                    &mut resolve,
+                    |verified: &VerifiedProject| {
+                        // Confirmations of a revision don't change the keys
+                        let revision = accepted.last().map(|(_, revision, _)| *revision);
+                        if revision != Some(verified.revision) {
+                            accepted.push((
+                                verified.content_id,
+                                verified.revision,
+                                verified.delegations().keys().copied().collect(),
+                            ))
+                        }
+                    },
+                )
+            }
+        };
+        if let Some(e) = failed {
+            return Err(e);
+        }
+        let folded = folded?;
+        let checkpoint = Checkpoint::from(&folded);
+        let generic::Folded { head, parent } = folded;
         let head = head
             .into_inner()
             .map(|doc| {
                 doc.try_second(|delegations| {
-                    self.resolve_delegation_updates(delegations, &find_latest_head)
+                    self.resolve_delegation_updates(
+                        delegations,
+                        find_latest_head,
+                        &nested,
+                        &mut resolved,
+                    )
                 })
             })
             .transpose()?;
 
-        Ok(generic::Verifying::from(head)
-            .signed()?
-            .quorum()?
-            .verified(parent.as_ref())?)
+        // The outcome depends on the resolved delegations, so include them
+        // in the cache key.
+        let key = self
+            .cache
+            .and_then(|_| delegations_cache_key(*head.content_id, resolved.iter().map(|id| **id)));
+        let cached = match (self.cache, key) {
+            (Some(cache), Some(key)) => cache
+                .get(key)
+                .filter(|cached| cached.head == head.content_id),
+            _ => None,
+        };
+        let verified = match cached {
+            Some(_) => generic::Verifying::from(head).assume_verified(),
+            None => {
+                let verified = generic::Verifying::from(head)
+                    .signed()?
+                    .quorum()?
+                    .verified(parent.as_ref())?;
+                if let (Some(cache), Some(key)) = (self.cache, key) {
+                    cache.put(
+                        key,
+                        Checkpoint {
+                            head: verified.content_id,
+                            parent: parent.as_ref().map(|parent| parent.content_id),
+                        },
+                    );
+                }
+                verified
+            },
+        };
+
+        if let Some(membership) = membership {
+            // The latest keys are the ones of the head, whose delegations
+            // were updated to their latest versions.
+            if let Some((_, _, keys)) = accepted.last_mut() {
+                *keys = verified.delegations().keys().copied().collect();
+            }
+            for (content_id, _, keys) in accepted {
+                let time = self.repo.find_commit(*content_id)?.time().seconds();
+                membership.revisions.push((time, keys));
+            }
+        }
+
+        Ok((verified, checkpoint, resolved))
     }
 
     /// Create a new [`Project`] from a payload and delegations.
@@ -740,16 +1213,22 @@ impl<'a> Identities<'a, Project> {
 
     //// Helpers ////
 
-    fn resolve_delegation_updates<I, F, E>(
+    fn resolve_delegation_updates<F, E>(
         &self,
-        current: I,
+        current: IndirectDelegation,
         find_latest_head: &F,
+        nested: &BTreeMap<Revision, Membership>,
+        resolved: &mut Vec<ContentId>,
     ) -> Result<IndirectDelegation, error::VerifyProject>
     where
-        I: IntoIterator<Item = Either<PublicKey, Person>>,
         F: Fn(Urn) -> Result<git2::Oid, E>,
         E: std::error::Error + Send + Sync + 'static,
     {
+        let roots = current
+            .nested()
+            .map(|nested| nested.root)
+            .collect::<Vec<_>>();
+
         let mut updated = Vec::new();
         for delegation in current {
             match delegation {
@@ -757,6 +1236,7 @@ impl<'a> Identities<'a, Project> {
                     let head = find_latest_head(id.urn())
                         .map_err(|e| error::VerifyProject::Lookup(Box::new(e)))?;
                     let verified = self.updated_person(id, head)?;
+                    resolved.push(verified.content_id);
                     updated.push(Right(verified.into_inner()))
                 },
 
@@ -764,7 +1244,57 @@ impl<'a> Identities<'a, Project> {
             }
         }
 
-        Ok(delegation::Indirect::try_from_iter(updated)?)
+        let mut updated = delegation::Indirect::try_from_iter(updated)?.with_nested(roots)?;
+        for (root, membership) in nested {
+            updated.resolve(root, membership.latest());
+        }
+
+        Ok(updated)
+    }
+
+    /// Verify the latest head of the project with root `root`, and return the
+    /// history of the keys eligible to vote on it.
+    fn resolve_nested<F, E>(
+        &self,
+        root: Revision,
+        find_latest_head: &F,
+        path: &mut Vec<Revision>,
+        resolved: &mut Vec<ContentId>,
+    ) -> Result<Membership, error::VerifyProject>
+    where
+        F: Fn(Urn) -> Result<git2::Oid, E>,
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        if path.contains(&root) {
+            return Err(error::VerifyProject::Cycle(root));
+        }
+        if path.len() > MAX_NESTING {
+            return Err(error::VerifyProject::TooDeep(MAX_NESTING));
+        }
+
+        let head = find_latest_head(Urn::new(root))
+            .map_err(|e| error::VerifyProject::Lookup(Box::new(e)))?;
+        let mut membership = Membership::default();
+        let (verified, _, nested) = self
+            .verify_nested(
+                head,
+                find_latest_head,
+                None,
+                |_| {},
+                path,
+                Some(&mut membership),
+            )
+            .map_err(|e| match e {
+                e @ error::VerifyProject::Cycle(_) | e @ error::VerifyProject::TooDeep(_) => e,
+                e => error::VerifyProject::Nested {
+                    root,
+                    source: Box::new(e),
+                },
+            })?;
+        resolved.push(verified.content_id);
+        resolved.extend(nested);
+
+        Ok(membership)
     }
 
     fn updated_person(
@@ -818,6 +1348,66 @@ impl<'a> Identities<'a, Project> {
     }
 }
 
+/// The keys eligible to vote on a project delegated to by a
+/// [`delegation::Nested`] delegation, over the course of its history.
+#[derive(Clone, Debug, Default)]
+struct Membership {
+    /// The commit time and eligible keys of every verified revision, in
+    /// history order.
+    revisions: Vec<(i64, BTreeSet<PublicKey>)>,
+}
+
+impl Membership {
+    /// The keys eligible to cast the nested delegation's vote on a revision
+    /// committed at `time`.
+    ///
+    /// These are the keys of all revisions which were not yet superseded at
+    /// `time`. That is, keys can vote on behalf of the project on revisions
+    /// committed before they were added, but not on ones committed after they
+    /// were removed.
+    fn at(&self, time: i64) -> impl Iterator<Item = PublicKey> + '_ {
+        let superseded = self
+            .revisions
+            .iter()
+            .skip(1)
+            .map(|(since, _)| Some(*since))
+            .chain(Some(None));
+        self.revisions
+            .iter()
+            .zip(superseded)
+            .filter(move |(_, superseded)| superseded.map_or(true, |at| at >= time))
+            .flat_map(|((_, keys), _)| keys.iter().copied())
+    }
+
+    /// The keys eligible to vote on the latest revision.
+    fn latest(&self) -> impl Iterator<Item = PublicKey> + '_ {
+        self.revisions
+            .last()
+            .into_iter()
+            .flat_map(|(_, keys)| keys.iter().copied())
+    }
+}
+
+/// Apply `resolve` to the identities of a [`generic::Folded`] obtained from a
+/// [`Checkpoint`].
+fn resolve_folded<Doc, R>(
+    folded: generic::Folded<Doc, Revision, ContentId>,
+    resolve: &mut R,
+) -> generic::Folded<Doc, Revision, ContentId>
+where
+    R: FnMut(&mut Identity<Doc>),
+{
+    let mut resolved = |verified: VerifiedIdentity<Doc>| {
+        let mut identity = verified.into_inner();
+        resolve(&mut identity);
+        Verifying::from(identity).assume_verified()
+    };
+    generic::Folded {
+        head: resolved(folded.head),
+        parent: folded.parent.map(&mut resolved),
+    }
+}
+
 fn sign<S>(signer: &S, rev: Revision) -> Result<Signature, S::Error>
 where
     S: Signer,
@@ -825,3 +1415,23 @@ where
     let sig = block_on(signer.sign(rev.as_bytes()))?;
     Ok(Signature::from((signer.public_key().into(), sig.into())))
 }
+
+/// Compute the [`VerificationCache`] key for the verification of a project
+/// with head commit `head`, given the content ids of the resolved indirect
+/// `delegations`.
+///
+/// Note that the verification of the history alone is keyed by the head
+/// commit.
+fn delegations_cache_key<I>(head: git2::Oid, delegations: I) -> Option<git2::Oid>
+where
+    I: IntoIterator<Item = git2::Oid>,
+{
+    let mut delegations = delegations.into_iter().collect::<Vec<_>>();
+    delegations.sort();
+
+    let mut input = head.as_bytes().to_vec();
+    for delegation in delegations {
+        input.extend_from_slice(delegation.as_bytes())
+    }
+    git2::Oid::hash_object(git2::ObjectType::Blob, &input).ok()
+}
@@ -119,6 +119,19 @@ pub enum VerifyProject {
     #[error(transparent)]
     Delegation(#[from] DelegationsFromIterError<Revision>),
 
+    #[error("cyclic delegation through {0}")]
+    Cycle(Revision),
+
+    #[error("delegations nested deeper than {0} levels")]
+    TooDeep(usize),
+
+    #[error("error verifying nested delegation {root}")]
+    Nested {
+        root: Revision,
+        #[source]
+        source: Box<VerifyProject>,
+    },
+
     #[error(transparent)]
     Load(#[from] self::Load),
 
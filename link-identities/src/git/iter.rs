@@ -28,6 +28,18 @@ impl<'a, T> Iter<'a, T> {
             _marker: PhantomData,
         })
     }
+
+    /// Like [`Iter::new`], but only yield the commits which are not reachable
+    /// from `after`.
+    pub fn after(
+        repo: &'a git2::Repository,
+        head: git2::Oid,
+        after: git2::Oid,
+    ) -> Result<Self, error::Load> {
+        let mut this = Self::new(repo, head)?;
+        this.iter.hide(after)?;
+        Ok(this)
+    }
 }
 
 impl<'a, T> Iterator for Iter<'a, T>
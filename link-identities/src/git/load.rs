@@ -105,17 +105,25 @@ impl<'a> TryFrom<AnyProject<'a>> for Project {
         identity
             .map(|doc| {
                 doc.try_second(|delegations| {
-                    let delegations = delegations
-                        .into_iter()
-                        .map(|d| match d.into() {
-                            Either::Left(key) => Ok(Either::Left(key)),
+                    // Delegations to other projects are not inlined, but
+                    // resolved during verification.
+                    let mut nested = Vec::new();
+                    let mut inlined = Vec::new();
+                    for d in delegations {
+                        match d.into() {
+                            Either::Left(key) => inlined.push(Either::Left(key)),
                             Either::Right(urn) => {
-                                resolve_inlined_person(repo, &tree, urn).map(Either::Right)
+                                match resolve_inlined_person(repo, &tree, &urn)? {
+                                    Some(person) => inlined.push(Either::Right(person)),
+                                    None => nested.push(urn.id),
+                                }
                             },
-                        })
-                        .collect::<Result<Vec<Either<_, _>>, _>>()?;
+                        }
+                    }
 
-                    delegation::Indirect::try_from_iter(delegations).map_err(error::Load::from)
+                    delegation::Indirect::try_from_iter(inlined)
+                        .and_then(|indirect| indirect.with_nested(nested))
+                        .map_err(error::Load::from)
                 })
             })
             .transpose()
@@ -258,16 +266,22 @@ type InlinedPerson = generic::Identity<Doc<PersonPayload, PersonDelegations>, Re
 fn resolve_inlined_person(
     repo: &git2::Repository,
     tree: &git2::Tree,
-    urn: Urn<Revision>,
-) -> Result<Person, error::Load> {
+    urn: &Urn<Revision>,
+) -> Result<Option<Person>, error::Load> {
     let path = PathBuf::from(format!("delegations/{}", urn.encode_id()));
-    let blob = tree
-        .get_path(&path)?
+    let entry = match tree.get_path(&path) {
+        Ok(entry) => entry,
+        Err(e) if is_not_found_err(&e) => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let blob = entry
         .to_object(repo)?
         .into_blob()
         .map_err(|obj| error::Load::NotABlob(path, obj.kind()))?;
 
-    Ok(Cjson::<InlinedPerson>::from_slice(blob.content())?
-        .into_inner()
-        .map(|doc| doc.second(delegation::Direct::from)))
+    Ok(Some(
+        Cjson::<InlinedPerson>::from_slice(blob.content())?
+            .into_inner()
+            .map(|doc| doc.second(delegation::Direct::from)),
+    ))
 }
@@ -26,6 +26,9 @@ use crate::{
     urn::{HasProtocol, Urn},
 };
 
+pub mod person;
+pub mod project;
+
 lazy_static! {
     /// Base [`Url`] for [`Person`]
     static ref PERSON_NAMESPACE_BASE: Url =
@@ -0,0 +1,86 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Well-known extensions of [`PersonPayload`]s.
+//!
+//! Extensions are part of the signed identity document, and so are preserved
+//! through verification just like the [`super::Person`] subject. Other
+//! extensions can be defined by implementing [`HasNamespace`] for a
+//! serialisable type, and attached via [`super::Payload::with_ext`].
+
+use std::collections::BTreeSet;
+
+use canonical::Cstring;
+use crypto::PeerId;
+use url::Url;
+
+use super::{HasNamespace, PersonPayload};
+
+lazy_static! {
+    static ref AVATAR_NAMESPACE_V1: Url =
+        Url::parse("https://radicle.xyz/link/ext/person/avatar/v1").unwrap();
+    static ref CONTACTS_NAMESPACE_V1: Url =
+        Url::parse("https://radicle.xyz/link/ext/person/contacts/v1").unwrap();
+    static ref SEEDS_NAMESPACE_V1: Url =
+        Url::parse("https://radicle.xyz/link/ext/person/seeds/v1").unwrap();
+}
+
+/// The avatar image of a person.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Avatar {
+    /// Multibase-encoded multihash of the image.
+    pub hash: Cstring,
+}
+
+impl HasNamespace for Avatar {
+    fn namespace() -> &'static Url {
+        &AVATAR_NAMESPACE_V1
+    }
+}
+
+/// Endpoints a person can be contacted at, eg. `mailto:` URLs.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Contacts {
+    pub endpoints: BTreeSet<Url>,
+}
+
+impl HasNamespace for Contacts {
+    fn namespace() -> &'static Url {
+        &CONTACTS_NAMESPACE_V1
+    }
+}
+
+/// Seeds a person's projects can be replicated from.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Seeds {
+    pub seeds: Vec<Seed>,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Seed {
+    pub peer: PeerId,
+    /// The address of the seed, in `host:port` form.
+    pub addr: Cstring,
+}
+
+impl HasNamespace for Seeds {
+    fn namespace() -> &'static Url {
+        &SEEDS_NAMESPACE_V1
+    }
+}
+
+impl PersonPayload {
+    pub fn avatar(&self) -> Result<Option<Avatar>, serde_json::Error> {
+        self.get_ext()
+    }
+
+    pub fn contacts(&self) -> Result<Option<Contacts>, serde_json::Error> {
+        self.get_ext()
+    }
+
+    pub fn seeds(&self) -> Result<Option<Seeds>, serde_json::Error> {
+        self.get_ext()
+    }
+}
@@ -0,0 +1,36 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Well-known extensions of [`ProjectPayload`]s.
+//!
+//! See [`super::person`] for how extensions relate to the identity document.
+
+use url::Url;
+
+use super::{HasNamespace, ProjectPayload};
+use crate::git::Urn;
+
+lazy_static! {
+    static ref FORK_NAMESPACE_V1: Url =
+        Url::parse("https://radicle.xyz/link/ext/project/fork/v1").unwrap();
+}
+
+/// The project a project was forked from.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Fork {
+    pub upstream: Urn,
+}
+
+impl HasNamespace for Fork {
+    fn namespace() -> &'static Url {
+        &FORK_NAMESPACE_V1
+    }
+}
+
+impl ProjectPayload {
+    pub fn fork(&self) -> Result<Option<Fork>, serde_json::Error> {
+        self.get_ext()
+    }
+}
@@ -29,6 +29,9 @@ pub mod error {
         #[error("invalid id")]
         InvalidId(#[source] E),
 
+        #[error("unknown hash algorithm `{0}`")]
+        UnknownAlgorithm(String),
+
         #[error(transparent)]
         Encoding(#[from] multibase::Error),
 
@@ -75,6 +78,66 @@ pub mod error {
     }
 }
 
+/// The hash algorithms which can be named in the `v2` encoding of [`Urn`] ids,
+/// using their names from the [multicodec table].
+///
+/// [multicodec table]: https://github.com/multiformats/multicodec/blob/master/table.csv
+const ALGORITHMS: &[(&str, multihash::Code)] = &[
+    ("identity", multihash::Code::Identity),
+    ("sha1", multihash::Code::Sha1),
+    ("sha2-256", multihash::Code::Sha2_256),
+    ("sha2-512", multihash::Code::Sha2_512),
+    ("sha3-224", multihash::Code::Sha3_224),
+    ("sha3-256", multihash::Code::Sha3_256),
+    ("sha3-384", multihash::Code::Sha3_384),
+    ("sha3-512", multihash::Code::Sha3_512),
+    ("keccak-224", multihash::Code::Keccak224),
+    ("keccak-256", multihash::Code::Keccak256),
+    ("keccak-384", multihash::Code::Keccak384),
+    ("keccak-512", multihash::Code::Keccak512),
+    ("blake2b-256", multihash::Code::Blake2b256),
+    ("blake2b-512", multihash::Code::Blake2b512),
+    ("blake2s-128", multihash::Code::Blake2s128),
+    ("blake2s-256", multihash::Code::Blake2s256),
+];
+
+/// The name of the hash algorithm `code` in the `v2` encoding of [`Urn`] ids.
+///
+/// Algorithms without a name are rendered as their hexadecimal multicodec,
+/// eg. `0x1e`.
+pub fn algorithm_name(code: multihash::Code) -> String {
+    ALGORITHMS
+        .iter()
+        .find_map(|(name, known)| (*known == code).then(|| (*name).to_owned()))
+        .unwrap_or_else(|| format!("0x{:x}", u64::from(code)))
+}
+
+/// Parse the name of a hash algorithm, as rendered by [`algorithm_name`].
+pub fn algorithm_code(name: &str) -> Option<multihash::Code> {
+    ALGORITHMS
+        .iter()
+        .find_map(|(known, code)| (*known == name).then(|| *code))
+        .or_else(|| {
+            name.strip_prefix("0x")
+                .and_then(|hex| u64::from_str_radix(hex, 16).ok())
+                .and_then(|code| multihash::Code::try_from(code).ok())
+        })
+}
+
+/// Parse `s` as a [`Urn`] in any supported encoding, and render it in the
+/// canonical encoding.
+///
+/// This should be applied to URNs obtained from user input or from other
+/// peers before they are compared or used as keys.
+pub fn canonicalize<R, E>(s: &str) -> Result<String, error::FromStr<E>>
+where
+    R: HasProtocol + TryFrom<Multihash, Error = E>,
+    for<'a> &'a R: Into<Multihash>,
+    E: std::error::Error + 'static,
+{
+    s.parse::<Urn<R>>().map(|urn| urn.to_string())
+}
+
 pub trait HasProtocol: sealed::Sealed {
     const PROTOCOL: &'static str;
 }
@@ -133,6 +196,9 @@ impl<R> Urn<R> {
     }
 
     /// Render [`Self::id`] into the canonical string encoding.
+    ///
+    /// This is the multibase (`z-base-32`) encoding of the multihash of the
+    /// id, which we refer to as the `v1` encoding.
     pub fn encode_id<'a>(&'a self) -> String
     where
         &'a R: Into<Multihash>,
@@ -140,13 +206,47 @@ impl<R> Urn<R> {
         multibase::encode(multibase::Base::Base32Z, (&self.id).into())
     }
 
+    /// Render [`Self::id`] into the `v2` string encoding, which names the
+    /// hash algorithm explicitly: `<algorithm>.<multibase digest>`, eg.
+    /// `sha1.hnrkyyyy...`.
+    ///
+    /// See [`algorithm_name`]. Note that [`Self::encode_id`] remains the
+    /// canonical encoding.
+    pub fn encode_id_v2<'a>(&'a self) -> String
+    where
+        &'a R: Into<Multihash>,
+    {
+        let mhash: Multihash = (&self.id).into();
+        format!(
+            "{}.{}",
+            algorithm_name(mhash.algorithm()),
+            multibase::encode(multibase::Base::Base32Z, mhash.digest())
+        )
+    }
+
+    /// Parse an id rendered by either [`Self::encode_id`] or
+    /// [`Self::encode_id_v2`].
+    ///
+    /// Any multibase encoding is accepted, not only the one used when
+    /// rendering.
     pub fn try_from_id(s: impl AsRef<str>) -> Result<Self, error::DecodeId<R::Error>>
     where
         R: TryFrom<Multihash>,
         R::Error: std::error::Error + 'static,
     {
-        let bytes = multibase::decode(s.as_ref()).map(|x| x.1)?;
-        let mhash = Multihash::from_bytes(bytes)?;
+        let s = s.as_ref();
+        let mhash = match s.split_once('.') {
+            None => {
+                let bytes = multibase::decode(s).map(|x| x.1)?;
+                Multihash::from_bytes(bytes)?
+            },
+            Some((algorithm, digest)) => {
+                let code = algorithm_code(algorithm)
+                    .ok_or_else(|| error::DecodeId::UnknownAlgorithm(algorithm.to_owned()))?;
+                let digest = multibase::decode(digest).map(|x| x.1)?;
+                multihash::wrap(code, &digest)
+            },
+        };
         let id = R::try_from(mhash).map_err(error::DecodeId::InvalidId)?;
         Ok(Self::new(id))
     }
@@ -0,0 +1,71 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Keep the [`AddrBook`] of the profile up to date with the peers the node
+//! joins in the membership overlay.
+
+use std::time::Duration;
+
+use futures::{Stream, StreamExt as _};
+use tokio::{select, time::interval};
+use tracing::{instrument, warn};
+
+use librad::net::{
+    addrbook::AddrBook,
+    peer::ProtocolEvent,
+    protocol::{membership::Transition, PartialPeerInfo, RecvError},
+    Network,
+};
+
+/// How often changes to the address book are written to disk.
+const SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+#[instrument(name = "addrbook subroutine", skip(addrbook, events))]
+pub async fn routine<E>(mut addrbook: AddrBook, network: Network, events: E) -> anyhow::Result<()>
+where
+    E: Stream<Item = Result<ProtocolEvent, RecvError>> + Send + 'static,
+{
+    futures::pin_mut!(events);
+    let mut save = interval(SAVE_INTERVAL);
+    let mut dirty = false;
+
+    loop {
+        select! {
+            event = events.next() => match event {
+                Some(Ok(ProtocolEvent::Membership(transition))) => {
+                    let info = match transition {
+                        Transition::Promoted(info) => Some(info),
+                        Transition::Demoted(info) => Some(PartialPeerInfo::from(info)),
+                        Transition::Evicted(_) => None,
+                    };
+                    if let Some(info) = info {
+                        addrbook.observe(&info, &network);
+                        dirty = true;
+                    }
+                },
+                Some(Ok(_)) => {},
+                Some(Err(RecvError::Lagged(n))) => {
+                    warn!(skipped = n, "addrbook lagging behind protocol events")
+                },
+                Some(Err(RecvError::Closed)) | None => break,
+            },
+
+            _ = save.tick() => {
+                if dirty {
+                    match addrbook.save() {
+                        Ok(()) => dirty = false,
+                        Err(err) => warn!(?err, "failed to save addrbook"),
+                    }
+                }
+            },
+        }
+    }
+
+    if dirty {
+        addrbook.save()?;
+    }
+
+    Ok(())
+}
@@ -7,37 +7,164 @@
 // TODO(xla): Expose storage args.
 // TODO(xla): Expose logging args.
 
-use std::{fmt, net::SocketAddr, path::PathBuf, str::FromStr};
+use std::{
+    fmt,
+    net::SocketAddr,
+    num::{NonZeroU32, NonZeroUsize},
+    path::PathBuf,
+    str::FromStr,
+};
 
 use structopt::StructOpt;
 
 use librad::{
     crypto,
+    git::{storage::reflog, Urn},
     net::Network,
     profile::{ProfileId, RadHome},
     PeerId,
 };
 
+use rad_clib::keys::SshKey;
+
+use crate::{
+    ci::Check,
+    mirror::Mirror,
+    notify::{Kind, Notifier},
+};
+
+pub mod file;
+
 #[derive(Debug, Default, Eq, PartialEq, StructOpt)]
 pub struct Args {
-    /// List of bootstrap nodes for initial discovery.
-    #[structopt(long = "bootstrap", name = "bootstrap")]
+    /// Path of a TOML file to read options from, keyed by their long names,
+    /// eg. `protocol-listen = ["0.0.0.0:8776"]`. Options given on the command
+    /// line take precedence over the ones in the file.
+    #[structopt(long, env = "LINKD_CONFIG", parse(from_os_str))]
+    pub config: Option<PathBuf>,
+
+    /// List of bootstrap nodes for initial discovery, comma-separated if given
+    /// via the environment.
+    #[structopt(
+        long = "bootstrap",
+        env = "LINKD_BOOTSTRAP",
+        use_delimiter = true,
+        name = "bootstrap"
+    )]
     pub bootstraps: Vec<Bootstrap>,
 
     /// Identifier of the profile the daemon will run for. This value determines
     /// which monorepo (if existing) on disk will be the backing storage.
-    #[structopt(long)]
+    #[structopt(long, env = "LINKD_PROFILE_ID")]
     pub profile_id: Option<ProfileId>,
 
     /// Home of the profile data, if not provided is read from the environment
     /// and falls back to project dirs.
-    #[structopt(long, default_value, parse(from_str = parse_rad_home))]
+    #[structopt(long, env = "LINKD_RAD_HOME", default_value, parse(from_str = parse_rad_home))]
     pub rad_home: RadHome,
 
     /// Configures the type of signer used to get access to the storage.
-    #[structopt(long, default_value)]
+    #[structopt(long, env = "LINKD_SIGNER", default_value)]
     pub signer: Signer,
 
+    /// Key in the ssh-agent to sign with if the signer is 'ssh-agent', given
+    /// as its fingerprint (eg. 'SHA256:...', as shown by 'ssh-add -l') or as
+    /// a peer id. Defaults to the key of the profile.
+    #[structopt(long, env = "LINKD_SSH_KEY_FINGERPRINT")]
+    pub ssh_key_fingerprint: Option<SshKey>,
+
+    /// Path to bind the control socket at, which local tools use to talk to
+    /// the daemon. Ignored if the socket is passed in via socket activation.
+    /// Defaults to 'linkd.sock' in the git directory of the profile.
+    #[structopt(long, env = "LINKD_CONTROL_SOCKET", parse(from_os_str))]
+    pub control_socket: Option<PathBuf>,
+
+    /// Address to serve the '/healthz' and '/readyz' HTTP endpoints on. If
+    /// not provided, the endpoints are disabled.
+    #[structopt(long, env = "LINKD_HEALTH_LISTEN")]
+    pub health_listen: Option<SocketAddr>,
+
+    /// Address to serve the read-only HTTP gateway on, which exposes project
+    /// data as JSON under '/v1/projects'. If not provided, the gateway is
+    /// disabled.
+    #[structopt(long, env = "LINKD_GATEWAY_LISTEN")]
+    pub gateway_listen: Option<SocketAddr>,
+
+    /// Address to serve projects to plain git clients on, via the smart-HTTP
+    /// protocol. If not provided, the bridge is disabled.
+    #[structopt(long, env = "LINKD_GIT_HTTP_LISTEN")]
+    pub git_http_listen: Option<SocketAddr>,
+
+    /// Projects to push to external git hosts after they were replicated,
+    /// given as '<urn>=<url>'. Credentials may be provided as part of the
+    /// URL, eg. 'https://<user>:<token>@github.com/<org>/<repo>.git'. May be
+    /// given multiple times, or comma-separated via the environment.
+    #[structopt(
+        long = "mirror",
+        env = "LINKD_MIRROR",
+        use_delimiter = true,
+        name = "mirror"
+    )]
+    pub mirrors: Vec<Mirror>,
+
+    /// Commands to run, or webhooks to POST to, on events of the node, given
+    /// as '<events>[@<urn>]=<action>'. Events are a comma-separated list of
+    /// 'replication-completed', 'identity-update' and 'cob-changed', or '*'.
+    /// Actions are either 'exec:<command>', which receives the event as JSON
+    /// on stdin, or a http(s) URL. May be given multiple times.
+    #[structopt(long = "notify", env = "LINKD_NOTIFY", name = "notify")]
+    pub notifiers: Vec<Notifier>,
+
+    /// Commands to check projects with after they were replicated, given as
+    /// '<urn>=<command>'. The command is run by 'sh -c' in a checkout of
+    /// every head of the project, and the outcome published as a
+    /// 'xyz.radicle.ci' collaborative object. May be given multiple times.
+    #[structopt(long = "ci-check", env = "LINKD_CI_CHECK", name = "ci-check")]
+    pub ci_checks: Vec<Check>,
+
+    /// Projects to maintain a code search index for, which can be queried
+    /// using 'rad grep'. The index covers the canonical heads of the project,
+    /// and is updated after replication. May be given multiple times, or
+    /// comma-separated via the environment.
+    #[structopt(
+        long = "grep-index",
+        env = "LINKD_GREP_INDEX",
+        use_delimiter = true,
+        name = "grep-index"
+    )]
+    pub grep_index: Vec<Urn>,
+
+    /// Maximum size in bytes of the large objects to download from the peers
+    /// providing replicated commits which reference them. If not provided,
+    /// large objects are not downloaded.
+    #[structopt(long, env = "LINKD_LARGE_OBJECTS_MAX_SIZE")]
+    pub large_objects_max_size: Option<u64>,
+
+    /// Interval in seconds at which to check whether all packs of the
+    /// monorepo are covered by a bitmap index, and to repack if not. Bitmaps
+    /// speed up serving fetches. If not provided, no bitmaps are written.
+    #[structopt(long, env = "LINKD_BITMAP_MAINTENANCE_INTERVAL")]
+    pub bitmap_maintenance_interval: Option<u64>,
+
+    /// Interval in seconds at which to expire reflog entries acc. to
+    /// '--reflog-retention'. If not provided, reflogs are kept forever.
+    #[structopt(long, env = "LINKD_REFLOG_EXPIRY_INTERVAL")]
+    pub reflog_expiry_interval: Option<u64>,
+
+    /// How many reflog entries to keep per category of refs, given as
+    /// '<category>=<keep>'. The category is one of 'heads', 'rad', 'tags',
+    /// 'notes' and 'cobs', and <keep> is 'all', 'none', a number of entries
+    /// or a number of days (eg. '30d'). Categories not mentioned keep all
+    /// entries. May be given multiple times, or comma-separated via the
+    /// environment.
+    #[structopt(
+        long = "reflog-retention",
+        env = "LINKD_REFLOG_RETENTION",
+        use_delimiter = true,
+        name = "reflog-retention"
+    )]
+    pub reflog_retention: Vec<reflog::Rule>,
+
     #[structopt(flatten)]
     pub key: KeyArgs,
 
@@ -47,10 +174,69 @@ pub struct Args {
     #[structopt(flatten)]
     pub protocol: ProtocolArgs,
 
+    #[structopt(flatten)]
+    pub tracing: TracingArgs,
+
     /// Forces the creation of a temporary root for the local state, should be
-    /// used for debug and testing only.
+    /// used for debug and testing only. Also enabled by setting
+    /// 'LINKD_TMP_ROOT' to 'true'.
     #[structopt(long)]
     pub tmp_root: bool,
+
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Eq, PartialEq, StructOpt)]
+pub enum Command {
+    /// Inspect the configuration.
+    Config(ConfigCommand),
+    /// Manage the webhooks of the profile.
+    Webhook(WebhookCommand),
+}
+
+#[derive(Debug, Eq, PartialEq, StructOpt)]
+pub enum ConfigCommand {
+    /// Validate the options given on the command line and in the config
+    /// file, and print the effective configuration instead of starting the
+    /// node.
+    Check,
+}
+
+#[derive(Debug, Eq, PartialEq, StructOpt)]
+pub enum WebhookCommand {
+    /// Register a webhook, replacing any webhook of the same name.
+    Add {
+        /// Name of the webhook, consisting of alphanumeric characters, '-'
+        /// and '_'.
+        #[structopt(long)]
+        name: String,
+        /// URL to POST events to.
+        #[structopt(long)]
+        url: String,
+        /// Secret to sign the events with, see the 'X-Radicle-Signature'
+        /// header.
+        #[structopt(long, env = "LINKD_WEBHOOK_SECRET", hide_env_values = true)]
+        secret: String,
+        /// Event to subscribe to, one of 'replication-completed',
+        /// 'identity-update' or 'cob-changed'. May be given multiple times.
+        #[structopt(long = "event", name = "event", required = true, number_of_values = 1)]
+        events: Vec<Kind>,
+        /// Project to restrict the webhook to. Required for 'cob-changed'.
+        #[structopt(long)]
+        urn: Option<Urn>,
+    },
+    /// Remove a webhook.
+    Remove {
+        /// Name of the webhook.
+        name: String,
+    },
+    /// List the registered webhooks.
+    List,
+    /// List the deliveries which failed repeatedly.
+    DeadLetters,
+    /// Attempt the deliveries which failed repeatedly once more.
+    Redeliver,
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -127,6 +313,7 @@ pub struct KeyArgs {
     /// Location of the key file on disk.
     #[structopt(
         long = "key-file-path",
+        env = "LINKD_KEY_FILE_PATH",
         name = "key-file-path",
         parse(from_str),
         required_if("key-source", "file")
@@ -135,6 +322,7 @@ pub struct KeyArgs {
     /// Format of the key input data.
     #[structopt(
         long = "key-format",
+        env = "LINKD_KEY_FORMAT",
         name = "key-format",
         default_value,
         required_if("signer", "key")
@@ -143,6 +331,7 @@ pub struct KeyArgs {
     /// Specifies from which source the secret should be read.
     #[structopt(
         long = "key-source",
+        env = "LINKD_KEY_SOURCE",
         name = "key-source",
         default_value,
         required_if("signer", "key")
@@ -231,12 +420,17 @@ fn parse_rad_home(src: &str) -> RadHome {
 #[derive(Debug, Eq, PartialEq, StructOpt)]
 pub struct MetricsArgs {
     /// Provider for metrics collection.
-    #[structopt(long = "metrics-provider", name = "metrics-provider")]
+    #[structopt(
+        long = "metrics-provider",
+        env = "LINKD_METRICS_PROVIDER",
+        name = "metrics-provider"
+    )]
     pub provider: Option<MetricsProvider>,
 
     /// Address of the graphite collector to send stats to.
     #[structopt(
         long,
+        env = "LINKD_GRAPHITE_ADDR",
         default_value = "localhost:2003",
         required_if("metrics-provider", "graphite")
     )]
@@ -269,25 +463,459 @@ impl FromStr for MetricsProvider {
 }
 
 #[derive(Debug, Default, Eq, PartialEq, StructOpt)]
+pub struct TracingArgs {
+    /// Endpoint of an OpenTelemetry collector to export spans to via OTLP,
+    /// eg. 'http://localhost:4317'. Requires the 'otlp' feature.
+    #[structopt(
+        long = "tracing-otlp-endpoint",
+        env = "LINKD_TRACING_OTLP_ENDPOINT",
+        name = "tracing-otlp-endpoint"
+    )]
+    pub otlp_endpoint: Option<String>,
+
+    /// Fraction of traces to export, between 0.0 and 1.0.
+    #[structopt(
+        long = "tracing-sample-ratio",
+        env = "LINKD_TRACING_SAMPLE_RATIO",
+        name = "tracing-sample-ratio",
+        default_value
+    )]
+    pub sample_ratio: SampleRatio,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SampleRatio(f64);
+
+// `SampleRatio::from_str` rejects NaN.
+impl Eq for SampleRatio {}
+
+impl SampleRatio {
+    pub fn as_f64(&self) -> f64 {
+        self.0
+    }
+}
+
+impl Default for SampleRatio {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+impl fmt::Display for SampleRatio {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for SampleRatio {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let ratio = f64::from_str(input).map_err(|e| e.to_string())?;
+        if (0.0..=1.0).contains(&ratio) {
+            Ok(Self(ratio))
+        } else {
+            Err(format!("sample ratio `{}` not between 0.0 and 1.0", input))
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, StructOpt)]
 pub struct ProtocolArgs {
     /// Address to bind to for the protocol to accept connections. Must be
     /// provided, shortcuts for any (0.0.0.0:0) and localhost (127.0.0.1:0)
-    /// are valid values.
-    #[structopt(long = "protocol-listen", name = "protocol-listen", parse(try_from_str = ProtocolListen::parse))]
-    pub listen: ProtocolListen,
+    /// are valid values. May be given multiple times to listen on several
+    /// addresses, eg. both IPv4 and IPv6, or comma-separated via the
+    /// environment.
+    #[structopt(
+        long = "protocol-listen",
+        env = "LINKD_PROTOCOL_LISTEN",
+        use_delimiter = true,
+        name = "protocol-listen",
+        required = true,
+        number_of_values = 1,
+        parse(try_from_str = ProtocolListen::parse)
+    )]
+    pub listen: Vec<ProtocolListen>,
+
+    /// Address to advertise to other peers instead of the listen addresses,
+    /// eg. the public address of a node behind NAT. May be given multiple
+    /// times, or comma-separated via the environment.
+    #[structopt(
+        long = "advertise-addr",
+        env = "LINKD_ADVERTISE_ADDR",
+        use_delimiter = true,
+        name = "advertise-addr",
+        number_of_values = 1
+    )]
+    pub advertise_addrs: Vec<SocketAddr>,
+
+    /// Address of a SOCKS5 proxy to relay outgoing connections through. The
+    /// proxy must support UDP associations, which rules out Tor.
+    #[structopt(
+        long = "socks5-proxy",
+        env = "LINKD_SOCKS5_PROXY",
+        name = "socks5-proxy"
+    )]
+    pub socks5_proxy: Option<SocketAddr>,
 
     /// Network name to be used during handshake, if 'main' is passed the
     /// default main network is used.
     #[structopt(
         long = "protocol-network",
+        env = "LINKD_PROTOCOL_NETWORK",
         name = "protocol-network",
         default_value,
         parse(try_from_str = parse_protocol_network))
     ]
     pub network: Network,
+
+    /// Determines which remote peers may trigger a replication by fetching
+    /// from this node, one of 'any', 'tracked' or 'delegates'.
+    #[structopt(
+        long = "graft-policy",
+        env = "LINKD_GRAFT_POLICY",
+        name = "graft-policy",
+        default_value
+    )]
+    pub graft_policy: GraftPolicy,
+
+    /// Number of replications per minute a single remote peer may trigger by
+    /// fetching from this node.
+    #[structopt(
+        long = "graft-rate-limit",
+        env = "LINKD_GRAFT_RATE_LIMIT",
+        name = "graft-rate-limit"
+    )]
+    pub graft_rate_limit: Option<NonZeroU32>,
+
+    /// Number of replications a single remote peer may trigger at once, before
+    /// the `graft-rate-limit` applies.
+    #[structopt(
+        long = "graft-rate-burst",
+        env = "LINKD_GRAFT_RATE_BURST",
+        name = "graft-rate-burst"
+    )]
+    pub graft_rate_burst: Option<NonZeroU32>,
+
+    /// Number of gossip messages per second accepted from a single origin
+    /// peer. Origins exceeding the rate repeatedly are ignored until they calm
+    /// down.
+    #[structopt(
+        long = "gossip-rate-limit",
+        env = "LINKD_GOSSIP_RATE_LIMIT",
+        name = "gossip-rate-limit"
+    )]
+    pub gossip_rate_limit: Option<NonZeroU32>,
+
+    /// Interval in seconds at which keep alive probes are sent on outgoing
+    /// connections. Defaults to 30.
+    #[structopt(
+        long = "keep-alive-interval",
+        env = "LINKD_KEEP_ALIVE_INTERVAL",
+        name = "keep-alive-interval"
+    )]
+    pub keep_alive_interval: Option<u64>,
+
+    /// Seconds after which a connection on which nothing was received is
+    /// closed, and the remote peer considered dead. Should tolerate the loss
+    /// of 1-2 keep alive probes. Larger values reduce connection churn on
+    /// flaky links, but delay noticing peers which went away. Defaults to 65.
+    #[structopt(
+        long = "idle-timeout",
+        env = "LINKD_IDLE_TIMEOUT",
+        name = "idle-timeout"
+    )]
+    pub idle_timeout: Option<u64>,
+
+    /// Pin the first peer seen at every address this node connects to, and
+    /// determine what happens if a different peer shows up there later, one
+    /// of 'disabled', 'warn' or 'refuse'. Mismatches are reported as events
+    /// either way.
+    #[structopt(long = "tofu", env = "LINKD_TOFU", name = "tofu", default_value)]
+    pub tofu: Tofu,
+
+    /// Peers to keep a connection to at all times, regardless of the
+    /// membership protocol, given as '<peer id>@<addr>'. Lost connections are
+    /// re-established with backoff. Comma-separated if given via the
+    /// environment.
+    #[structopt(
+        long = "pin-peer",
+        env = "LINKD_PIN_PEER",
+        use_delimiter = true,
+        name = "pin-peer"
+    )]
+    pub pinned: Vec<Bootstrap>,
+
+    /// Determines whether gossip about identities not yet present on this node
+    /// causes them to be cloned, one of 'never', 'from-tracked' or 'always'.
+    #[structopt(
+        long = "replicate-unknown",
+        env = "LINKD_REPLICATE_UNKNOWN",
+        name = "replicate-unknown",
+        default_value
+    )]
+    pub replicate_unknown: ReplicateUnknown,
+
+    /// Pack the refs of the storage whenever a replication leaves more than
+    /// this many loose refs in a namespace. Recommended for seeds hosting
+    /// many peers, where `0` keeps all refs packed.
+    #[structopt(
+        long = "pack-refs-threshold",
+        env = "LINKD_PACK_REFS_THRESHOLD",
+        name = "pack-refs-threshold"
+    )]
+    pub pack_refs_threshold: Option<usize>,
+
+    /// Maximum total size in bytes of the packs sent in response to clones
+    /// which are kept for reuse by subsequent clones of the same tips.
+    /// Recommended for seeds. If not provided, packs are not cached.
+    #[structopt(
+        long = "pack-cache-size",
+        env = "LINKD_PACK_CACHE_SIZE",
+        name = "pack-cache-size"
+    )]
+    pub pack_cache_size: Option<u64>,
+
+    /// Maximum number of replications in progress at the same time. Waiting
+    /// replications take turns per URN, so that a busy project doesn't starve
+    /// all others. If not provided, replications are not bounded.
+    #[structopt(
+        long = "replication-slots",
+        env = "LINKD_REPLICATION_SLOTS",
+        name = "replication-slots"
+    )]
+    pub replication_slots: Option<NonZeroUsize>,
+
+    /// Maximum number of replications from a single remote peer in progress
+    /// at the same time.
+    #[structopt(
+        long = "replication-slots-per-remote",
+        env = "LINKD_REPLICATION_SLOTS_PER_REMOTE",
+        name = "replication-slots-per-remote"
+    )]
+    pub replication_slots_per_remote: Option<NonZeroUsize>,
+
+    /// Maximum number of replications of a single URN in progress at the same
+    /// time.
+    #[structopt(
+        long = "replication-slots-per-urn",
+        env = "LINKD_REPLICATION_SLOTS_PER_URN",
+        name = "replication-slots-per-urn"
+    )]
+    pub replication_slots_per_urn: Option<NonZeroUsize>,
+
+    /// Bound the data fetched for a namespace by this multiple of the largest
+    /// pack received for it before, instead of a fixed size. Namespaces
+    /// fetched for the first time are bounded by the fixed size.
+    #[structopt(
+        long = "adaptive-fetch-limit",
+        env = "LINKD_ADAPTIVE_FETCH_LIMIT",
+        name = "adaptive-fetch-limit"
+    )]
+    pub adaptive_fetch_limit: Option<NonZeroUsize>,
+
+    /// Record a trace of every replication in the `traces` directory of the
+    /// monorepo, for debugging replication issues. Traces contain the refs
+    /// exchanged with the remote peer, but no objects.
+    #[structopt(long = "trace-replication")]
+    pub trace_replication: bool,
+
+    /// Reject namespaces for which a peer advertises more than this many
+    /// refs, before fetching anything.
+    #[structopt(
+        long = "max-advertised-refs",
+        env = "LINKD_MAX_ADVERTISED_REFS",
+        name = "max-advertised-refs"
+    )]
+    pub max_advertised_refs: Option<usize>,
+
+    /// Reject namespaces in which the history of an identity has more than
+    /// this many revisions, before verifying it.
+    #[structopt(
+        long = "max-id-revisions",
+        env = "LINKD_MAX_ID_REVISIONS",
+        name = "max-id-revisions"
+    )]
+    pub max_id_revisions: Option<usize>,
+
+    /// Executable to run before the ref updates of a replication are applied.
+    /// It receives the namespace, remote peer and pending updates as JSON on
+    /// stdin, and vetoes the updates by exiting unsuccessfully, giving the
+    /// reason on stderr. Updates are also vetoed if it cannot be run, or
+    /// doesn't finish within 10 seconds.
+    #[structopt(
+        long = "pre-apply-hook",
+        env = "LINKD_PRE_APPLY_HOOK",
+        name = "pre-apply-hook",
+        parse(from_os_str)
+    )]
+    pub pre_apply_hook: Option<PathBuf>,
+
+    /// Executable to run after the ref updates of a replication were applied,
+    /// receiving them as JSON on stdin like the pre-apply hook. Its outcome
+    /// is only logged.
+    #[structopt(
+        long = "post-apply-hook",
+        env = "LINKD_POST_APPLY_HOOK",
+        name = "post-apply-hook",
+        parse(from_os_str)
+    )]
+    pub post_apply_hook: Option<PathBuf>,
     // TODO(xla): Expose protocol args (membership, replication, etc.).
 }
 
+impl Default for ProtocolArgs {
+    fn default() -> Self {
+        Self {
+            listen: vec![ProtocolListen::default()],
+            advertise_addrs: vec![],
+            socks5_proxy: None,
+            network: Network::default(),
+            graft_policy: GraftPolicy::default(),
+            graft_rate_limit: None,
+            graft_rate_burst: None,
+            gossip_rate_limit: None,
+            keep_alive_interval: None,
+            idle_timeout: None,
+            tofu: Tofu::default(),
+            pinned: vec![],
+            replicate_unknown: ReplicateUnknown::default(),
+            pack_refs_threshold: None,
+            pack_cache_size: None,
+            replication_slots: None,
+            replication_slots_per_remote: None,
+            replication_slots_per_urn: None,
+            adaptive_fetch_limit: None,
+            trace_replication: false,
+            max_advertised_refs: None,
+            max_id_revisions: None,
+            pre_apply_hook: None,
+            post_apply_hook: None,
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, StructOpt)]
+pub enum GraftPolicy {
+    /// Accept from any peer.
+    Any,
+    /// Accept from tracked peers and delegates only.
+    Tracked,
+    /// Accept from delegates only.
+    Delegates,
+}
+
+impl Default for GraftPolicy {
+    fn default() -> Self {
+        Self::Any
+    }
+}
+
+impl fmt::Display for GraftPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let policy = match self {
+            Self::Any => "any",
+            Self::Tracked => "tracked",
+            Self::Delegates => "delegates",
+        };
+        write!(f, "{}", policy)
+    }
+}
+
+impl FromStr for GraftPolicy {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "any" => Ok(Self::Any),
+            "tracked" => Ok(Self::Tracked),
+            "delegates" => Ok(Self::Delegates),
+            _ => Err(format!("unsupported graft policy `{}`", input)),
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, StructOpt)]
+pub enum ReplicateUnknown {
+    /// Never clone identities announced via gossip.
+    Never,
+    /// Clone identities announced by tracked peers only.
+    FromTracked,
+    /// Clone any identity announced via gossip.
+    Always,
+}
+
+impl Default for ReplicateUnknown {
+    fn default() -> Self {
+        Self::FromTracked
+    }
+}
+
+impl fmt::Display for ReplicateUnknown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let policy = match self {
+            Self::Never => "never",
+            Self::FromTracked => "from-tracked",
+            Self::Always => "always",
+        };
+        write!(f, "{}", policy)
+    }
+}
+
+impl FromStr for ReplicateUnknown {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "never" => Ok(Self::Never),
+            "from-tracked" => Ok(Self::FromTracked),
+            "always" => Ok(Self::Always),
+            _ => Err(format!("unsupported replicate-unknown policy `{}`", input)),
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, StructOpt)]
+pub enum Tofu {
+    /// Don't pin peers.
+    Disabled,
+    /// Report peers which don't match the pinned one.
+    Warn,
+    /// Report and disconnect peers which don't match the pinned one.
+    Refuse,
+}
+
+impl Default for Tofu {
+    fn default() -> Self {
+        Self::Warn
+    }
+}
+
+impl fmt::Display for Tofu {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let policy = match self {
+            Self::Disabled => "disabled",
+            Self::Warn => "warn",
+            Self::Refuse => "refuse",
+        };
+        write!(f, "{}", policy)
+    }
+}
+
+impl FromStr for Tofu {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "disabled" => Ok(Self::Disabled),
+            "warn" => Ok(Self::Warn),
+            "refuse" => Ok(Self::Refuse),
+            _ => Err(format!("unsupported tofu policy `{}`", input)),
+        }
+    }
+}
+
 #[derive(Debug, Eq, PartialEq, StructOpt)]
 pub enum ProtocolListen {
     Any,
@@ -0,0 +1,381 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Reading [`Args`] from a TOML config file, and writing them as one.
+//!
+//! The file is a flat table, whose keys are the long names of the command
+//! line options, without the leading dashes:
+//!
+//! ```toml
+//! bootstrap = ["hynkyndc6w3p8urucakobzna7sxwgcqny7xxtw88dtx3pkf7m3nrzc@seed.example.com:8776"]
+//! protocol-listen = ["0.0.0.0:8776"]
+//! graft-policy = "tracked"
+//! pack-refs-threshold = 0
+//! tmp-root = false
+//! ```
+//!
+//! Arrays are equivalent to giving an option multiple times, and booleans
+//! determine whether a flag is present.
+//!
+//! Every option may also be given via an environment variable named after it,
+//! eg. `LINKD_PROTOCOL_LISTEN` for `--protocol-listen`, see [`env_var`].
+//! Options given on the command line take precedence over the environment,
+//! which takes precedence over the file.
+//!
+//! [`to_table`] produces a file in this format from parsed [`Args`], which is
+//! what `linkd config check` prints.
+
+use std::{convert::TryFrom as _, env, ffi::OsString, fs, io, path::PathBuf};
+
+use librad::{net::Network, profile::RadHome};
+use structopt::StructOpt as _;
+use toml::{value::Table, Value};
+
+use super::{Args, MetricsProvider, ProtocolListen};
+
+/// The option pointing to the config file.
+pub const CONFIG_FLAG: &str = "--config";
+
+/// The prefix of the environment variables options are read from.
+pub const ENV_PREFIX: &str = "LINKD_";
+
+/// Flags, ie. options without a value, which clap can't read from the
+/// environment. They are enabled by setting their variable to `true` or `1`.
+const ENV_FLAGS: &[&str] = &["--tmp-root"];
+
+/// The name of the environment variable for the option `flag`, eg.
+/// `LINKD_PROTOCOL_LISTEN` for `--protocol-listen`.
+pub fn env_var(flag: &str) -> String {
+    format!(
+        "{}{}",
+        ENV_PREFIX,
+        flag.trim_start_matches('-')
+            .to_uppercase()
+            .replace('-', "_")
+    )
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to read config file `{}`", .path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("failed to parse config file `{}`", .path.display())]
+    Parse {
+        path: PathBuf,
+        #[source]
+        source: toml::de::Error,
+    },
+
+    #[error("unsupported value for `{0}`, expected a string, number, boolean or array")]
+    Value(String),
+}
+
+/// Parse [`Args`] from the command line and environment of the current
+/// process, complemented by the config file if one was given via `--config`.
+///
+/// Like [`structopt::StructOpt::from_args`], this exits the process if the
+/// resulting arguments are invalid.
+pub fn from_args() -> Result<Args, Error> {
+    Ok(Args::from_iter(merge(env::args_os())?))
+}
+
+/// Extend the command line `argv` with the options of the config file given
+/// via `--config`, if any, which are neither present in `argv` nor set in the
+/// environment.
+pub fn merge<I, T>(argv: I) -> Result<Vec<OsString>, Error>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString>,
+{
+    let mut argv = argv.into_iter().map(Into::into).collect::<Vec<_>>();
+    for flag in ENV_FLAGS {
+        let enabled = matches!(env::var(env_var(flag)).as_deref(), Ok("true") | Ok("1"));
+        if enabled && !is_given(&argv, flag) {
+            argv.insert(argv.len().min(1), OsString::from(flag));
+        }
+    }
+
+    match config_path(&argv) {
+        None => Ok(argv),
+        Some(path) => {
+            let contents = fs::read_to_string(&path).map_err(|source| Error::Io {
+                path: path.clone(),
+                source,
+            })?;
+            let table = toml::from_str::<toml::value::Table>(&contents)
+                .map_err(|source| Error::Parse { path, source })?;
+            merge_table(argv, &table)
+        },
+    }
+}
+
+/// The options of `args` as a table in the format of the config file, such
+/// that reading it back yields the same [`Args`].
+///
+/// Options which have a default are always included. Options without one
+/// are left out if they are not set, as are `config` and any subcommand. Note
+/// that the credentials in the URLs of mirrors are redacted.
+pub fn to_table(args: &Args) -> Table {
+    let Args {
+        config: _,
+        bootstraps,
+        profile_id,
+        rad_home,
+        signer,
+        ssh_key_fingerprint,
+        control_socket,
+        health_listen,
+        gateway_listen,
+        git_http_listen,
+        mirrors,
+        notifiers,
+        ci_checks,
+        grep_index,
+        large_objects_max_size,
+        bitmap_maintenance_interval,
+        reflog_expiry_interval,
+        reflog_retention,
+        key,
+        metrics,
+        protocol,
+        tracing,
+        tmp_root,
+        command: _,
+    } = args;
+
+    let rad_home = match rad_home {
+        RadHome::ProjectDirs => RadHome::ProjectDirs.to_string(),
+        RadHome::Root(root) => root.display().to_string(),
+    };
+    let network = match &protocol.network {
+        Network::Main => "main".to_owned(),
+        Network::Custom(name) => String::from_utf8_lossy(name).into_owned(),
+    };
+    let listen = protocol.listen.iter().map(|listen| match listen {
+        ProtocolListen::Any => "any".to_owned(),
+        ProtocolListen::Localhost => "localhost".to_owned(),
+        ProtocolListen::Provided { addr } => addr.to_string(),
+    });
+    let provider = metrics.provider.as_ref().map(|provider| match provider {
+        MetricsProvider::Graphite => "graphite",
+    });
+    let path = |path: &Option<PathBuf>| path.as_ref().map(|path| string(path.display()));
+
+    vec![
+        ("bootstrap", array(bootstraps)),
+        ("profile-id", profile_id.as_ref().map(string)),
+        ("rad-home", Some(string(rad_home))),
+        ("signer", Some(string(signer))),
+        (
+            "ssh-key-fingerprint",
+            ssh_key_fingerprint.as_ref().map(string),
+        ),
+        ("control-socket", path(control_socket)),
+        ("health-listen", health_listen.as_ref().map(string)),
+        ("gateway-listen", gateway_listen.as_ref().map(string)),
+        ("git-http-listen", git_http_listen.as_ref().map(string)),
+        ("mirror", array(mirrors)),
+        ("notify", array(notifiers)),
+        ("ci-check", array(ci_checks)),
+        ("grep-index", array(grep_index)),
+        (
+            "large-objects-max-size",
+            large_objects_max_size.map(integer),
+        ),
+        (
+            "bitmap-maintenance-interval",
+            bitmap_maintenance_interval.map(integer),
+        ),
+        (
+            "reflog-expiry-interval",
+            reflog_expiry_interval.map(integer),
+        ),
+        ("reflog-retention", array(reflog_retention)),
+        ("key-file-path", path(&key.file_path)),
+        ("key-format", Some(string(&key.format))),
+        ("key-source", Some(string(&key.source))),
+        ("metrics-provider", provider.map(string)),
+        ("graphite-addr", Some(string(&metrics.graphite_addr))),
+        ("protocol-listen", array(listen)),
+        ("advertise-addr", array(&protocol.advertise_addrs)),
+        ("socks5-proxy", protocol.socks5_proxy.as_ref().map(string)),
+        ("protocol-network", Some(string(network))),
+        ("graft-policy", Some(string(&protocol.graft_policy))),
+        (
+            "graft-rate-limit",
+            protocol.graft_rate_limit.map(|n| integer(n.get().into())),
+        ),
+        (
+            "graft-rate-burst",
+            protocol.graft_rate_burst.map(|n| integer(n.get().into())),
+        ),
+        (
+            "gossip-rate-limit",
+            protocol.gossip_rate_limit.map(|n| integer(n.get().into())),
+        ),
+        (
+            "keep-alive-interval",
+            protocol.keep_alive_interval.map(integer),
+        ),
+        ("idle-timeout", protocol.idle_timeout.map(integer)),
+        ("tofu", Some(string(&protocol.tofu))),
+        ("pin-peer", array(&protocol.pinned)),
+        (
+            "replicate-unknown",
+            Some(string(&protocol.replicate_unknown)),
+        ),
+        (
+            "pack-refs-threshold",
+            protocol.pack_refs_threshold.map(|n| integer(n as u64)),
+        ),
+        ("pack-cache-size", protocol.pack_cache_size.map(integer)),
+        (
+            "replication-slots",
+            protocol.replication_slots.map(|n| integer(n.get() as u64)),
+        ),
+        (
+            "replication-slots-per-remote",
+            protocol
+                .replication_slots_per_remote
+                .map(|n| integer(n.get() as u64)),
+        ),
+        (
+            "replication-slots-per-urn",
+            protocol
+                .replication_slots_per_urn
+                .map(|n| integer(n.get() as u64)),
+        ),
+        (
+            "adaptive-fetch-limit",
+            protocol
+                .adaptive_fetch_limit
+                .map(|n| integer(n.get() as u64)),
+        ),
+        (
+            "trace-replication",
+            Some(Value::Boolean(protocol.trace_replication)),
+        ),
+        (
+            "max-advertised-refs",
+            protocol.max_advertised_refs.map(|n| integer(n as u64)),
+        ),
+        (
+            "max-id-revisions",
+            protocol.max_id_revisions.map(|n| integer(n as u64)),
+        ),
+        ("pre-apply-hook", path(&protocol.pre_apply_hook)),
+        ("post-apply-hook", path(&protocol.post_apply_hook)),
+        (
+            "tracing-otlp-endpoint",
+            tracing.otlp_endpoint.as_ref().map(string),
+        ),
+        (
+            "tracing-sample-ratio",
+            Some(Value::Float(tracing.sample_ratio.as_f64())),
+        ),
+        ("tmp-root", Some(Value::Boolean(*tmp_root))),
+    ]
+    .into_iter()
+    .filter_map(|(key, value)| value.map(|value| (key.to_owned(), value)))
+    .collect()
+}
+
+fn string<T: ToString>(value: T) -> Value {
+    Value::String(value.to_string())
+}
+
+/// Integers beyond the range of TOML are given as strings, which are parsed
+/// all the same.
+fn integer(value: u64) -> Value {
+    i64::try_from(value).map_or_else(|_| string(value), Value::Integer)
+}
+
+fn array<I>(values: I) -> Option<Value>
+where
+    I: IntoIterator,
+    I::Item: ToString,
+{
+    let values = values.into_iter().map(string).collect::<Vec<_>>();
+    (!values.is_empty()).then(|| Value::Array(values))
+}
+
+fn merge_table(argv: Vec<OsString>, table: &toml::value::Table) -> Result<Vec<OsString>, Error> {
+    let mut from_file = Vec::new();
+    for (key, value) in table {
+        let flag = format!("--{}", key);
+        if is_given(&argv, &flag) || env::var_os(env_var(&flag)).is_some() {
+            continue;
+        }
+        match value {
+            Value::Boolean(true) => from_file.push(OsString::from(&flag)),
+            Value::Boolean(false) => {},
+            Value::Array(values) => {
+                for value in values {
+                    from_file.push(OsString::from(&flag));
+                    from_file.push(scalar(key, value)?);
+                }
+            },
+            value => {
+                from_file.push(OsString::from(&flag));
+                from_file.push(scalar(key, value)?);
+            },
+        }
+    }
+
+    // Options from the file go before the ones of the command line, so they
+    // end up before any subcommand.
+    let mut argv = argv.into_iter();
+    Ok(argv
+        .next()
+        .into_iter()
+        .chain(from_file)
+        .chain(argv)
+        .collect())
+}
+
+fn scalar(key: &str, value: &Value) -> Result<OsString, Error> {
+    match value {
+        Value::String(s) => Ok(s.into()),
+        Value::Integer(i) => Ok(i.to_string().into()),
+        Value::Float(f) => Ok(f.to_string().into()),
+        _ => Err(Error::Value(key.to_owned())),
+    }
+}
+
+fn config_path(argv: &[OsString]) -> Option<PathBuf> {
+    let mut argv = argv.iter().skip(1);
+    while let Some(arg) = argv.next() {
+        match arg.to_str() {
+            Some(CONFIG_FLAG) => return argv.next().map(PathBuf::from),
+            Some(arg) => {
+                if let Some(path) = arg
+                    .strip_prefix(CONFIG_FLAG)
+                    .and_then(|s| s.strip_prefix('='))
+                {
+                    return Some(PathBuf::from(path));
+                }
+            },
+            None => {},
+        }
+    }
+    env::var_os(env_var(CONFIG_FLAG)).map(PathBuf::from)
+}
+
+fn is_given(argv: &[OsString], flag: &str) -> bool {
+    argv.iter()
+        .skip(1)
+        .filter_map(|arg| arg.to_str())
+        .any(|arg| {
+            arg == flag
+                || arg
+                    .strip_prefix(flag)
+                    .map_or(false, |rest| rest.starts_with('='))
+        })
+}
@@ -0,0 +1,32 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Keep the packs of the monorepo covered by bitmap indices, such that
+//! serving fetches stays cheap as new objects are replicated. See
+//! [`librad::git::storage::bitmap`].
+
+use std::time::Duration;
+
+use tokio::time::interval;
+use tracing::{info, instrument, warn};
+
+use librad::{git::storage::bitmap, net::peer::Peer, Signer};
+
+#[instrument(name = "bitmaps subroutine", skip(peer))]
+pub async fn routine<S>(peer: Peer<S>, every: Duration) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+{
+    let mut tick = interval(every);
+    loop {
+        tick.tick().await;
+        match peer.using_storage(bitmap::repack_if_incomplete).await {
+            Ok(Ok(true)) => info!("wrote bitmap index"),
+            Ok(Ok(false)) => {},
+            Ok(Err(err)) => warn!(?err, "failed to write bitmap index"),
+            Err(err) => warn!(?err, "failed to obtain storage"),
+        }
+    }
+}
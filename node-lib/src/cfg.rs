@@ -4,33 +4,52 @@
 // Linking Exception. For full terms see the included LICENSE file.
 
 use std::{
+    collections::BTreeMap,
     convert::TryFrom,
     io,
     net::{Ipv4Addr, SocketAddr, SocketAddrV4, ToSocketAddrs as _},
+    path::PathBuf,
+    sync::Arc,
     time::Duration,
 };
 
 use anyhow::{bail, Context, Result};
+use nonempty::NonEmpty;
 use thrussh_agent::client::ClientStream;
 use tokio::{
     fs::File,
-    io::{stdin, AsyncReadExt as _},
+    io::{stdin, AsyncRead, AsyncReadExt as _},
     time::{error::Elapsed, timeout},
 };
-use tracing::warn;
+use tracing::{debug, warn};
 
 use librad::{
-    crypto::{BoxedSigner, IntoSecretKeyError},
-    git::storage,
-    keystore::SecretKeyExt as _,
+    crypto::{secure, BoxedSigner, IntoSecretKeyError, SecureBytes},
+    git::{
+        replication,
+        storage::{self, reflog},
+        Urn,
+    },
     net,
-    net::{discovery, peer::Config as PeerConfig},
+    net::{
+        addrbook::{self, AddrBook},
+        discovery,
+        peer::{self, Config as PeerConfig},
+        protocol::io::graft,
+    },
     profile::{Profile, RadHome},
+    rate_limit,
     SecretKey,
 };
-use rad_clib::keys;
-
-use crate::args;
+use rad_clib::{control, keys};
+
+use crate::{
+    args,
+    ci::Check,
+    mirror::Mirror,
+    notify::Notifier,
+    webhooks::{self, Webhook},
+};
 
 mod seed;
 pub use seed::{Seed, Seeds};
@@ -47,6 +66,9 @@ lazy_static::lazy_static! {
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
+    #[error(transparent)]
+    AddrBook(#[from] addrbook::Error),
+
     #[error("decoding base64 key")]
     Base64(#[from] base64::DecodeError),
 
@@ -73,12 +95,35 @@ pub enum Error {
 
     #[error(transparent)]
     Timeout(#[from] Elapsed),
+
+    #[error("keep alive interval {keep_alive_interval:?} must be smaller than the idle timeout {max_idle_timeout:?}")]
+    Transport {
+        keep_alive_interval: Duration,
+        max_idle_timeout: Duration,
+    },
+
+    #[error(transparent)]
+    Webhooks(#[from] webhooks::Error),
 }
 
 pub struct Cfg<Disco, Signer> {
+    pub addrbook: AddrBook,
+    pub bitmap_maintenance: Option<Duration>,
+    pub ci_checks: Vec<Check>,
+    pub control_socket: PathBuf,
     pub disco: Disco,
+    pub gateway: Option<SocketAddr>,
+    pub git_http: Option<SocketAddr>,
+    pub grep_index: Vec<Urn>,
+    pub health: Option<Health>,
+    pub large_objects_max_size: Option<u64>,
     pub metrics: Option<Metrics>,
+    pub mirrors: Vec<Mirror>,
+    pub notifiers: Vec<Notifier>,
     pub peer: PeerConfig<Signer>,
+    pub reflog_expiry: Option<Duration>,
+    pub reflog_retention: reflog::Retention,
+    pub webhooks: Vec<Webhook>,
 }
 
 impl Cfg<discovery::Static, BoxedSigner> {
@@ -87,17 +132,86 @@ impl Cfg<discovery::Static, BoxedSigner> {
         S: ClientStream + Unpin + 'static,
     {
         let seeds = Seeds::resolve(&args.bootstraps).await?;
-        let disco = discovery::Static::try_from(seeds)?;
+        let mut disco = discovery::Static::try_from(seeds)?;
+        let pinned = Seeds::resolve(&args.protocol.pinned)
+            .await?
+            .0
+            .into_iter()
+            .fold(BTreeMap::<_, Vec<_>>::new(), |mut pinned, seed| {
+                pinned.entry(seed.peer_id).or_default().extend(seed.addrs);
+                pinned
+            });
         let profile = Profile::try_from(args)?;
+        // Reconnect to the peers we knew about, even if no (or unreachable)
+        // bootstrap nodes were given.
+        let addrbook = AddrBook::open(profile.paths())?;
+        disco.extend(addrbook.known(&args.protocol.network));
         let signer = construct_signer::<S>(args, &profile).await?;
 
         // Ensure the storage is accessible for the created profile and signer.
         storage::Storage::init(profile.paths(), signer.clone())?;
+        let webhooks = webhooks::list(profile.paths())?;
+
+        let listen_addrs = args
+            .protocol
+            .listen
+            .iter()
+            .map(|listen| match listen {
+                args::ProtocolListen::Any => *ANY,
+                args::ProtocolListen::Localhost => *LOCALHOST,
+                args::ProtocolListen::Provided { addr } => *addr,
+            })
+            .collect::<Vec<_>>();
+        let listen_addrs =
+            NonEmpty::from_vec(listen_addrs).unwrap_or_else(|| NonEmpty::new(*LOCALHOST));
+
+        let graft = net::protocol::config::Graft {
+            policy: match args.protocol.graft_policy {
+                args::GraftPolicy::Any => graft::Policy::Any,
+                args::GraftPolicy::Tracked => graft::Policy::Tracked,
+                args::GraftPolicy::Delegates => graft::Policy::Delegates,
+            },
+        };
+        let mut rate_limits = net::protocol::Quota::default();
+        if let Some(per_minute) = args.protocol.graft_rate_limit {
+            rate_limits.graft = rate_limit::Quota::per_minute(per_minute);
+        }
+        if let Some(burst) = args.protocol.graft_rate_burst {
+            rate_limits.graft = rate_limits.graft.allow_burst(burst);
+        }
+        if let Some(per_second) = args.protocol.gossip_rate_limit {
+            rate_limits.gossip.messages_per_origin = rate_limit::Quota::per_second(per_second);
+        }
+
+        let transport = {
+            let mut transport = net::quic::Transport::default();
+            if let Some(secs) = args.protocol.keep_alive_interval {
+                transport.keep_alive_interval = Duration::from_secs(secs.max(1));
+            }
+            if let Some(secs) = args.protocol.idle_timeout {
+                transport.max_idle_timeout = Duration::from_secs(secs.max(1));
+            }
+            if transport.keep_alive_interval >= transport.max_idle_timeout {
+                return Err(Error::Transport {
+                    keep_alive_interval: transport.keep_alive_interval,
+                    max_idle_timeout: transport.max_idle_timeout,
+                });
+            }
+            transport
+        };
 
-        let listen_addr = match args.protocol.listen {
-            args::ProtocolListen::Any => *ANY,
-            args::ProtocolListen::Localhost => *LOCALHOST,
-            args::ProtocolListen::Provided { addr } => addr,
+        let storage = peer::config::Storage {
+            protocol: peer::config::ProtocolStorage {
+                replicate_unknown: match args.protocol.replicate_unknown {
+                    args::ReplicateUnknown::Never => peer::storage::ReplicateUnknown::Never,
+                    args::ReplicateUnknown::FromTracked => {
+                        peer::storage::ReplicateUnknown::FromTracked
+                    },
+                    args::ReplicateUnknown::Always => peer::storage::ReplicateUnknown::Always,
+                },
+                ..Default::default()
+            },
+            ..Default::default()
         };
 
         let metrics = match args.metrics.provider {
@@ -111,23 +225,114 @@ impl Cfg<discovery::Static, BoxedSigner> {
             None => None,
         };
 
+        let replication_metrics = match metrics {
+            Some(_) => replication::metrics::Sink::new(&crate::metrics::REPLICATION),
+            None => Default::default(),
+        };
+
+        let replication_hooks = match (
+            args.protocol.pre_apply_hook.clone(),
+            args.protocol.post_apply_hook.clone(),
+        ) {
+            (None, None) => Default::default(),
+            (pre_apply, post_apply) => {
+                replication::hooks::Sink::new(Arc::new(crate::hooks::Scripts {
+                    pre_apply,
+                    post_apply,
+                }))
+            },
+        };
+
+        let sanity = {
+            let defaults = replication::sanity::Limits::default();
+            replication::sanity::Limits {
+                advertised_refs: args
+                    .protocol
+                    .max_advertised_refs
+                    .unwrap_or(defaults.advertised_refs),
+                id_revisions: args
+                    .protocol
+                    .max_id_revisions
+                    .unwrap_or(defaults.id_revisions),
+            }
+        };
+
+        let health = args.health_listen.map(|addr| Health {
+            addr,
+            bootstraps: args.bootstraps.len(),
+        });
+
         Ok(Self {
+            addrbook,
+            bitmap_maintenance: args
+                .bitmap_maintenance_interval
+                .map(|secs| Duration::from_secs(secs.max(1))),
+            ci_checks: args.ci_checks.clone(),
+            control_socket: args
+                .control_socket
+                .clone()
+                .unwrap_or_else(|| control::socket_path(profile.paths())),
             disco,
+            gateway: args.gateway_listen,
+            git_http: args.git_http_listen,
+            grep_index: args.grep_index.clone(),
+            health,
+            large_objects_max_size: args.large_objects_max_size,
             metrics,
+            mirrors: args.mirrors.clone(),
+            notifiers: args.notifiers.clone(),
             peer: PeerConfig {
                 signer,
                 protocol: net::protocol::Config {
                     paths: profile.paths().clone(),
-                    listen_addr,
-                    advertised_addrs: None,
+                    listen_addrs,
+                    advertised_addrs: NonEmpty::from_vec(args.protocol.advertise_addrs.clone()),
+                    dialer: match args.protocol.socks5_proxy {
+                        Some(proxy) => net::quic::Dialer::Socks5 { proxy },
+                        None => net::quic::Dialer::Direct,
+                    },
                     membership: Default::default(),
                     network: args.protocol.network.clone(),
-                    replication: Default::default(),
+                    replication: replication::Config {
+                        adaptive_fetch_limit: args.protocol.adaptive_fetch_limit.map(|factor| {
+                            replication::pack_sizes::Adaptive {
+                                factor,
+                                ..Default::default()
+                            }
+                        }),
+                        pack_refs: args.protocol.pack_refs_threshold,
+                        sanity,
+                        slots: replication::slots::Limits {
+                            total: args.protocol.replication_slots,
+                            per_remote: args.protocol.replication_slots_per_remote,
+                            per_urn: args.protocol.replication_slots_per_urn,
+                        },
+                        trace: args.protocol.trace_replication,
+                        metrics: replication_metrics,
+                        hooks: replication_hooks,
+                        ..Default::default()
+                    },
                     fetch: Default::default(),
-                    rate_limits: Default::default(),
+                    graft,
+                    serve: net::protocol::config::Serve {
+                        pack_cache_size: args.protocol.pack_cache_size,
+                    },
+                    rate_limits,
+                    pinned,
+                    transport,
+                    tofu: match args.protocol.tofu {
+                        args::Tofu::Disabled => None,
+                        args::Tofu::Warn => Some(net::tofu::Policy::Warn),
+                        args::Tofu::Refuse => Some(net::tofu::Policy::Refuse),
+                    },
                 },
-                storage: Default::default(),
+                storage,
             },
+            reflog_expiry: args
+                .reflog_expiry_interval
+                .map(|secs| Duration::from_secs(secs.max(1))),
+            reflog_retention: args.reflog_retention.iter().collect(),
+            webhooks,
         })
     }
 }
@@ -136,6 +341,13 @@ pub enum Metrics {
     Graphite(SocketAddr),
 }
 
+pub struct Health {
+    /// Address to serve the HTTP endpoints on.
+    pub addr: SocketAddr,
+    /// Number of configured bootstrap peers.
+    pub bootstraps: usize,
+}
+
 impl TryFrom<&args::Args> for Profile {
     type Error = Error;
 
@@ -156,50 +368,74 @@ where
     S: ClientStream + Unpin + 'static,
 {
     match args.signer {
-        args::Signer::SshAgent => keys::signer_ssh::<S>(profile)
-            .await
-            .map_err(anyhow::Error::from),
+        args::Signer::SshAgent => {
+            keys::signer_ssh_with::<S>(profile, args.ssh_key_fingerprint.as_ref())
+                .await
+                .map_err(anyhow::Error::from)
+        },
         args::Signer::Key => {
-            let bytes = match args.key.source {
+            let mut bytes = match args.key.source {
                 args::KeySource::Ephemeral => {
                     warn!("generating key in-memory which is ephemeral and should only be used for debug and testing");
 
-                    SecretKey::new().as_ref().to_vec()
+                    return Ok(BoxedSigner::from(SecretKey::new()));
                 },
                 args::KeySource::File => {
                     if args.key.file_path.is_none() {
                         bail!("file path must be present when file source is set");
                     }
 
-                    let mut file = File::open(args.key.file_path.clone().unwrap())
+                    let file = File::open(args.key.file_path.clone().unwrap())
                         .await
                         .context("opening key file")?;
-                    let mut bytes = vec![];
-
-                    timeout(Duration::from_secs(5), file.read_to_end(&mut bytes))
-                        .await?
-                        .context("reading key file")?;
 
-                    bytes
-                },
-                args::KeySource::Stdin => {
-                    let mut bytes = vec![];
-                    timeout(Duration::from_secs(5), stdin().read_to_end(&mut bytes))
+                    timeout(Duration::from_secs(5), read_secret(file))
                         .await?
-                        .context("reading stdin")?;
-                    bytes
+                        .context("reading key file")?
                 },
+                args::KeySource::Stdin => timeout(Duration::from_secs(5), read_secret(stdin()))
+                    .await?
+                    .context("reading stdin")?,
             };
+            if let Err(err) = bytes.mlock() {
+                debug!(?err, "failed to lock key material into memory");
+            }
 
             let key = match args.key.format {
                 args::KeyFormat::Base64 => {
-                    let bs = base64::decode(&bytes)?;
-                    SecretKey::from_bytes_and_meta(bs.into(), &())?
+                    let mut decoded = SecureBytes::new(base64::decode(bytes.as_ref())?);
+                    if let Err(err) = decoded.mlock() {
+                        debug!(?err, "failed to lock key material into memory");
+                    }
+                    SecretKey::from_secure(&decoded)?
                 },
-                args::KeyFormat::Binary => SecretKey::from_bytes_and_meta(bytes.into(), &())?,
+                args::KeyFormat::Binary => SecretKey::from_secure(&bytes)?,
             };
 
             Ok(BoxedSigner::from(key))
         },
     }
 }
+
+/// Read key material from `r` into memory which is zeroized after use.
+async fn read_secret<R>(mut r: R) -> io::Result<SecureBytes>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut bytes = SecureBytes::zeroed(secure::MAX_READ_SIZE);
+    let mut len = 0;
+    loop {
+        match r.read(&mut bytes.as_mut()[len..]).await? {
+            0 => break,
+            n => {
+                len += n;
+                if len == secure::MAX_READ_SIZE {
+                    return Err(secure::too_large());
+                }
+            },
+        }
+    }
+    bytes.truncate(len);
+
+    Ok(bytes)
+}
@@ -0,0 +1,292 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Run checks, eg. a test suite, against the heads of projects as they are
+//! replicated, and publish the results for other peers to replicate.
+//!
+//! For every configured [`Check`], the branches of the canonical view of the
+//! project (see [`git_http::canonical_refs`]) are checked out into a
+//! temporary directory, in which the command is run by `sh -c`. The exit
+//! status of the command and the SHA-256 of its output are published as a
+//! [`ci::Report`] collaborative object. The output itself is kept in the
+//! `ci-logs` directory alongside the monorepo.
+//!
+//! Commits the local peer already published a report about are not checked
+//! again.
+//!
+//! The command can inspect the following environment variables:
+//!
+//! * `LINKD_CI_URN`: the URN of the project
+//! * `LINKD_CI_COMMIT`: the id of the checked commit
+//! * `LINKD_CI_BRANCH`: the name of the branch, without `refs/heads/`
+
+use std::{
+    collections::BTreeSet,
+    fmt,
+    fs,
+    path::{Path, PathBuf},
+    process::Stdio,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use futures::{Stream, StreamExt as _};
+use sha2::{Digest as _, Sha256};
+use tokio::{
+    process::Command,
+    select,
+    task::spawn_blocking,
+    time::{interval, timeout},
+};
+use tracing::{info, instrument, warn};
+
+use librad::{
+    git::{
+        cobs::ci::{self, Report, Status},
+        Urn,
+    },
+    git_ext as ext,
+    net::{
+        peer::{Peer, ProtocolEvent},
+        protocol::{broadcast::PutResult, event::upstream::Gossip, RecvError},
+    },
+    Signer,
+};
+
+use crate::git_http;
+
+const LOG_DIR: &str = "ci-logs";
+
+/// How often checks are run for projects which were replicated since.
+const TICK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Time after which a running check is aborted and reported as failed.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// A project to check, and the command to check it with.
+///
+/// Parsed from `<urn>=<command>`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Check {
+    pub urn: Urn,
+    pub command: String,
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.urn, self.command)
+    }
+}
+
+impl FromStr for Check {
+    type Err = String;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        match src.split_once('=') {
+            Some((_, command)) if command.trim().is_empty() => Err("missing command".to_string()),
+            Some((urn, command)) => {
+                let urn = urn.parse::<Urn>().map_err(|e| e.to_string())?;
+                Ok(Self {
+                    urn: urn.with_path(None),
+                    command: command.to_string(),
+                })
+            },
+            None => Err("expected <urn>=<command>".to_string()),
+        }
+    }
+}
+
+/// The location of the output of the check of `commit`.
+pub fn log_path(git_dir: &Path, commit: ext::Oid) -> PathBuf {
+    git_dir.join(LOG_DIR).join(format!("{}.log", commit))
+}
+
+/// The hex-encoded SHA-256 of `log`, as recorded in [`Report::log_digest`].
+pub fn digest(log: &[u8]) -> String {
+    Sha256::digest(log)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[instrument(name = "ci subroutine", skip(peer, checks, events))]
+pub async fn routine<S, E>(peer: Peer<S>, checks: Vec<Check>, events: E) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+    E: Stream<Item = Result<ProtocolEvent, RecvError>> + Send + 'static,
+{
+    let git_dir = peer.protocol_config().paths.git_dir().to_path_buf();
+
+    futures::pin_mut!(events);
+    let mut tick = interval(TICK_INTERVAL);
+    // Whether each project was updated since it was last checked. Heads
+    // which changed while the node was offline are picked up on startup.
+    let mut pending = vec![true; checks.len()];
+
+    loop {
+        select! {
+            event = events.next() => match event {
+                Some(Ok(ProtocolEvent::Gossip(gossip))) => {
+                    let Gossip::Put { payload, result, .. } = gossip.as_ref();
+                    if let PutResult::Applied(_) = result {
+                        let urn = payload.urn.clone().with_path(None);
+                        for (check, pending) in checks.iter().zip(pending.iter_mut()) {
+                            if check.urn == urn {
+                                *pending = true;
+                            }
+                        }
+                    }
+                },
+                Some(Ok(_)) => {},
+                Some(Err(RecvError::Lagged(n))) => {
+                    warn!(skipped = n, "ci lagging behind protocol events")
+                },
+                Some(Err(RecvError::Closed)) | None => break,
+            },
+
+            _ = tick.tick() => {
+                for (check, pending) in checks.iter().zip(pending.iter_mut()) {
+                    if !*pending {
+                        continue;
+                    }
+                    *pending = false;
+                    if let Err(err) = run_checks(&peer, &git_dir, check).await {
+                        warn!(check = %check.urn, ?err, "failed to run checks");
+                    }
+                }
+            },
+        }
+    }
+
+    Ok(())
+}
+
+/// Check all heads of `check.urn` which the local peer did not publish a
+/// report about yet.
+async fn run_checks<S>(peer: &Peer<S>, git_dir: &Path, check: &Check) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+{
+    let urn = check.urn.clone();
+    let heads = peer
+        .using_storage(move |storage| -> anyhow::Result<_> {
+            let view = match git_http::canonical_refs(storage, &urn)? {
+                Some(view) => view,
+                None => anyhow::bail!("{} is not a project in the local storage", urn),
+            };
+            let local = *storage.peer_id();
+            let mut seen = BTreeSet::new();
+            let mut heads = Vec::new();
+            for (name, oid) in view {
+                let branch = match name.strip_prefix("refs/heads/") {
+                    Some(branch) => branch.to_owned(),
+                    None => continue,
+                };
+                let commit = ext::Oid::from(oid);
+                // Branches pointing to the same commit are checked once
+                if !seen.insert(commit) {
+                    continue;
+                }
+                let reports = ci::reports(storage, &urn, commit)?;
+                if !reports.iter().any(|published| published.peer == local) {
+                    heads.push((branch, commit));
+                }
+            }
+            Ok(heads)
+        })
+        .await??;
+
+    for (branch, commit) in heads {
+        let report = run(git_dir, check, &branch, commit).await?;
+        info!(
+            check = %check.urn,
+            %branch,
+            %commit,
+            status = ?report.status,
+            "ran checks"
+        );
+
+        let urn = check.urn.clone();
+        peer.using_storage(move |storage| ci::publish(storage, &urn, commit, &report))
+            .await??;
+    }
+
+    Ok(())
+}
+
+/// Run `check.command` against a checkout of `commit`.
+async fn run(
+    git_dir: &Path,
+    check: &Check,
+    branch: &str,
+    commit: ext::Oid,
+) -> anyhow::Result<Report> {
+    let worktree = tempfile::tempdir()?;
+    {
+        let git_dir = git_dir.to_path_buf();
+        let path = worktree.path().to_path_buf();
+        spawn_blocking(move || checkout(&git_dir, &path, commit)).await??;
+    }
+
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c")
+        .arg(format!("exec 2>&1\n{}", check.command))
+        .env("LINKD_CI_URN", check.urn.to_string())
+        .env("LINKD_CI_COMMIT", commit.to_string())
+        .env("LINKD_CI_BRANCH", branch)
+        .current_dir(worktree.path())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true);
+
+    let (status, log) = match timeout(CHECK_TIMEOUT, cmd.output()).await {
+        Ok(out) => {
+            let out = out?;
+            let status = if out.status.success() {
+                Status::Success
+            } else {
+                Status::Failure
+            };
+            (status, out.stdout)
+        },
+        Err(_) => (
+            Status::Failure,
+            format!("checks timed out after {}s\n", CHECK_TIMEOUT.as_secs()).into_bytes(),
+        ),
+    };
+
+    let path = log_path(git_dir, commit);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(&path, &log)?;
+
+    Ok(Report {
+        status,
+        branch: Some(branch.to_owned()),
+        log_digest: digest(&log),
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default(),
+    })
+}
+
+/// Write the tree of `commit` to `path`, without touching the index or `HEAD`
+/// of the monorepo.
+fn checkout(git_dir: &Path, path: &Path, commit: ext::Oid) -> Result<(), git2::Error> {
+    let repo = git2::Repository::open_bare(git_dir)?;
+    let tree = repo.find_commit(*commit)?.tree()?;
+    repo.checkout_tree(
+        tree.as_object(),
+        Some(
+            git2::build::CheckoutBuilder::new()
+                .target_dir(path)
+                .update_index(false)
+                .force(),
+        ),
+    )
+}
@@ -0,0 +1,362 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Serve the control socket, see [`rad_clib::control`] for the protocol and a
+//! client.
+//!
+//! The socket is either passed in via socket activation, or bound at the
+//! configured path. A socket bound by the node is only accessible by the
+//! user running it.
+//!
+//! Subscribers receive the events of the protocol, and the events derived
+//! from replications as per [`crate::notify`], through bounded buffers. A
+//! subscriber which doesn't read its events fast enough thus never holds up
+//! the node, but misses events, which it is told about via
+//! [`Event::Lagged`].
+
+use std::{
+    collections::BTreeSet,
+    fs,
+    io,
+    num::NonZeroUsize,
+    os::unix::{fs::PermissionsExt as _, net},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use anyhow::anyhow;
+use futures::StreamExt as _;
+use tokio::{
+    io::{AsyncBufReadExt as _, BufReader},
+    net::{unix::OwnedWriteHalf, UnixListener, UnixStream},
+    select,
+    spawn,
+    sync::broadcast,
+};
+use tracing::{debug, info, instrument, warn};
+
+use librad::{
+    git::{refs, replication, tracking},
+    net::{
+        peer::{Peer, ProtocolEvent},
+        protocol::{
+            broadcast::PutResult,
+            event::upstream::{Connection, Gossip},
+            membership::Transition,
+            RecvError,
+        },
+    },
+    Signer,
+};
+use rad_clib::control::{
+    write_line,
+    Event,
+    Filter,
+    Kind,
+    Pushed,
+    Replicated,
+    Request,
+    Response,
+    Status,
+};
+
+use crate::notify;
+
+/// The number of events originating from the control socket itself, eg.
+/// [`Event::Tracked`], which are buffered for each subscriber.
+const LOCAL_EVENTS_BUFFER: usize = 64;
+
+/// Where to accept connections on.
+#[derive(Clone, Debug)]
+pub enum Listener {
+    /// A socket passed in via socket activation.
+    Activated(Arc<net::UnixListener>),
+    /// A path to bind a socket at. A stale socket file at the path is
+    /// replaced.
+    Path(PathBuf),
+}
+
+impl Listener {
+    fn bind(&self) -> io::Result<UnixListener> {
+        match self {
+            Self::Activated(listener) => {
+                let listener = listener.try_clone()?;
+                listener.set_nonblocking(true)?;
+                UnixListener::from_std(listener)
+            },
+            Self::Path(path) => {
+                match fs::remove_file(path) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                    _ => {},
+                }
+                let listener = UnixListener::bind(path)?;
+                fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+                Ok(listener)
+            },
+        }
+    }
+}
+
+#[instrument(name = "control subroutine", skip(peer))]
+pub async fn routine<S>(peer: Peer<S>, listener: Listener) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+{
+    let listener = listener.bind()?;
+    info!("serving control socket on {:?}", listener.local_addr()?);
+    let (local, _) = broadcast::channel(LOCAL_EVENTS_BUFFER);
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let peer = peer.clone();
+        let local = local.clone();
+        spawn(async move {
+            if let Err(err) = serve(peer, local, stream).await {
+                debug!(?err, "control connection failed");
+            }
+        });
+    }
+}
+
+async fn serve<S>(
+    peer: Peer<S>,
+    local: broadcast::Sender<Event>,
+    stream: UnixStream,
+) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+{
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        let response = match serde_json::from_str(&line) {
+            Ok(Request::Subscribe { filter }) => {
+                // Subscribe before acknowledging, so the client doesn't miss
+                // events caused by its subsequent actions.
+                let events = peer.subscribe();
+                let local = local.subscribe();
+                write_line(&mut writer, &Response::Subscribed).await?;
+                let hangup = async move {
+                    // Nothing is expected from the client anymore, but wait
+                    // for it to hang up.
+                    while lines.next_line().await?.is_some() {}
+                    Ok::<_, anyhow::Error>(())
+                };
+                return select! {
+                    res = subscription(&peer, filter, events, local, &mut writer) => res,
+                    res = hangup => res,
+                };
+            },
+            Ok(request) => {
+                handle(&peer, &local, request)
+                    .await
+                    .unwrap_or_else(|err| Response::Error {
+                        message: err.to_string(),
+                    })
+            },
+            Err(err) => Response::Error {
+                message: format!("invalid request: {}", err),
+            },
+        };
+        write_line(&mut writer, &response).await?;
+    }
+
+    Ok(())
+}
+
+async fn subscription<S, E>(
+    peer: &Peer<S>,
+    filter: Filter,
+    events: E,
+    mut local: broadcast::Receiver<Event>,
+    writer: &mut OwnedWriteHalf,
+) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+    E: futures::Stream<Item = Result<ProtocolEvent, RecvError>>,
+{
+    let kinds = [
+        (
+            Kind::ReplicationCompleted,
+            notify::Kind::ReplicationCompleted,
+        ),
+        (Kind::IdentityUpdate, notify::Kind::IdentityUpdate),
+        (Kind::CobChanged, notify::Kind::CobChanged),
+    ]
+    .iter()
+    .filter(|(kind, _)| filter.matches_kind(*kind))
+    .map(|(_, kind)| *kind)
+    .collect::<BTreeSet<_>>();
+    let restrictions = if filter.urns.is_empty() {
+        vec![(&kinds, None)]
+    } else {
+        filter.urns.iter().map(|urn| (&kinds, Some(urn))).collect()
+    };
+    let mut derive = notify::Derive::new(peer, &restrictions).await?;
+
+    futures::pin_mut!(events);
+    loop {
+        let batch = select! {
+            event = events.next() => match event {
+                Some(Ok(ProtocolEvent::Gossip(gossip))) => {
+                    let Gossip::Put {
+                        provider,
+                        payload,
+                        result,
+                    } = gossip.as_ref();
+                    let urn = payload.urn.clone().with_path(None);
+                    match result {
+                        PutResult::Applied(_) if !kinds.is_empty() && filter.matches_urn(Some(&urn)) => {
+                            match derive.events(peer, urn, provider.peer_id).await {
+                                Ok(events) => events.into_iter().map(from_notify).collect(),
+                                Err(err) => {
+                                    warn!(?err, "failed to derive events for control subscriber");
+                                    vec![]
+                                },
+                            }
+                        },
+                        _ => vec![],
+                    }
+                },
+                Some(Ok(ProtocolEvent::Membership(Transition::Promoted(info)))) => {
+                    vec![Event::PeerJoined { peer: info.peer_id }]
+                },
+                Some(Ok(ProtocolEvent::Membership(Transition::Evicted(info)))) => {
+                    vec![Event::PeerLeft { peer: info.peer_id }]
+                },
+                Some(Ok(ProtocolEvent::Connection(Connection::Dead { remote_peer, remote_addr }))) => {
+                    vec![Event::PeerDead { peer: remote_peer, addr: remote_addr }]
+                },
+                Some(Ok(ProtocolEvent::Connection(Connection::Mismatch {
+                    remote_peer,
+                    remote_addr,
+                    pinned,
+                    refused,
+                }))) => {
+                    vec![Event::PeerMismatch {
+                        peer: remote_peer,
+                        addr: remote_addr,
+                        pinned,
+                        refused,
+                    }]
+                },
+                Some(Ok(_)) => vec![],
+                Some(Err(RecvError::Lagged(skipped))) => vec![Event::Lagged { skipped }],
+                Some(Err(RecvError::Closed)) | None => return Ok(()),
+            },
+            event = local.recv() => match event {
+                Ok(event) => vec![event],
+                Err(RecvError::Lagged(skipped)) => vec![Event::Lagged { skipped }],
+                Err(RecvError::Closed) => return Ok(()),
+            },
+        };
+        for event in batch.iter().filter(|event| filter.matches(event)) {
+            write_line(writer, event).await?;
+        }
+    }
+}
+
+async fn handle<S>(
+    peer: &Peer<S>,
+    local: &broadcast::Sender<Event>,
+    request: Request,
+) -> anyhow::Result<Response>
+where
+    S: Signer + Clone,
+{
+    match request {
+        Request::Status => {
+            let stats = peer.stats().await;
+            Ok(Response::Status(Status {
+                peer_id: peer.peer_id(),
+                connected_peers: stats.connected_peers.keys().copied().collect(),
+                membership_active: stats.membership_active,
+                membership_passive: stats.membership_passive,
+            }))
+        },
+        Request::Replicate { urn, from, addrs } => {
+            let (_, result) = peer
+                .replicate_batch((from, addrs), Some(urn), NonZeroUsize::new(1).unwrap())
+                .await
+                .pop()
+                .ok_or_else(|| anyhow!("replication yielded no result"))?;
+            let result = result?;
+            Ok(Response::Replicated(Replicated {
+                updated_tips: result.updated_tips,
+                cloned: matches!(result.mode, replication::Mode::Clone),
+            }))
+        },
+        Request::Push { urn, seed, addrs } => {
+            let urn = urn.with_path(None);
+            let local = {
+                let urn = urn.clone();
+                peer.using_storage(move |storage| refs::tips(storage, &urn))
+                    .await??
+                    .remove(&peer.peer_id())
+            };
+            let interrogation = peer.interrogate((seed, addrs));
+            let updated = interrogation.please_pull(urn.clone(), None).await?;
+            // Ask for the tips rather than trusting `updated`: the seed may
+            // have had our signed refs already.
+            let seed = interrogation
+                .sigref_tips(urn)
+                .await?
+                .remove(&peer.peer_id());
+            Ok(Response::Pushed(Pushed {
+                updated,
+                local,
+                seed,
+            }))
+        },
+        Request::Track { urn, peer: remote } => {
+            let changed = {
+                let urn = urn.clone();
+                peer.using_storage(move |storage| tracking::track(storage, &urn, remote))
+                    .await??
+            };
+            if changed {
+                // Fails only if nobody is subscribed
+                local.send(Event::Tracked { urn, peer: remote }).ok();
+            }
+            Ok(Response::Tracked { changed })
+        },
+        Request::Untrack { urn, peer: remote } => {
+            let changed = {
+                let urn = urn.clone();
+                peer.using_storage(move |storage| tracking::untrack(storage, &urn, remote))
+                    .await??
+            };
+            if changed {
+                local.send(Event::Untracked { urn, peer: remote }).ok();
+            }
+            Ok(Response::Untracked { changed })
+        },
+        Request::Subscribe { .. } => unreachable!("subscriptions are handled by the caller"),
+    }
+}
+
+fn from_notify(event: notify::Event) -> Event {
+    match event {
+        notify::Event::ReplicationCompleted { urn, provider } => {
+            Event::ReplicationCompleted { urn, provider }
+        },
+        notify::Event::IdentityUpdate { urn, remote_peer } => {
+            Event::IdentityUpdate { urn, remote_peer }
+        },
+        notify::Event::CobChanged {
+            urn,
+            peer,
+            object,
+            old,
+            new,
+        } => Event::CobChanged {
+            urn,
+            peer,
+            object,
+            old,
+            new,
+        },
+    }
+}
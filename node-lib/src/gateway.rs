@@ -0,0 +1,394 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Read-only HTTP gateway to the projects in the storage of the node.
+//!
+//! Allows web frontends to serve project pages straight from a seed, without
+//! a separate indexer. All endpoints respond with JSON:
+//!
+//! * `GET /v1/projects/<urn>` responds with the verified identity document of
+//!   the project.
+//! * `GET /v1/projects/<urn>/heads` responds with the branch heads of the local
+//!   peer and all tracked peers, as per their signed refs.
+//! * `GET /v1/projects/<urn>/tree/<commit>[/<path>]` responds with the entries
+//!   of the tree, or the contents of the blob, at `path` in `commit`.
+//! * `GET /v1/projects/<urn>/cobs/<typename>[/<id>]` responds with the state of
+//!   the collaborative object `id`, or of all objects of the given type. Only
+//!   the built-in [`patch`] type is supported.
+//!
+//! Commits are only served if they are reachable from one of the heads, so as
+//! to not expose objects of other namespaces. Errors are reported as a JSON
+//! object with a single `error` field, except for requests which are not
+//! valid HTTP, see [`crate::http::serve`].
+
+use std::{collections::BTreeMap, convert::TryFrom as _, net::SocketAddr, path::Path};
+
+use either::Either;
+use serde_json::{json, Value};
+use thiserror::Error;
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{info, instrument};
+
+use librad::{
+    git::{
+        cobs::{self, patch},
+        identities::{self, project, VerifiedProject},
+        refs::{self, Refs},
+        storage::{self, ReadOnlyStorage as _, Storage},
+        tracking,
+        Urn,
+    },
+    git_ext as ext,
+    net::peer::Peer,
+    PeerId,
+    Signer,
+};
+
+use crate::http::{self, Request};
+
+#[derive(Debug, Error)]
+enum Error {
+    #[error("{0}")]
+    BadRequest(String),
+
+    #[error("{0}")]
+    NotFound(String),
+
+    #[error(transparent)]
+    Identities(#[from] identities::Error),
+
+    #[error(transparent)]
+    Patch(#[from] patch::Error),
+
+    #[error(transparent)]
+    Refs(#[from] refs::stored::Error),
+
+    #[error(transparent)]
+    Storage(#[from] storage::Error),
+
+    #[error(transparent)]
+    Tracking(#[from] tracking::Error),
+
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+}
+
+/// The HTTP status of a [`Response`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Status {
+    Ok,
+    BadRequest,
+    NotFound,
+    MethodNotAllowed,
+    Internal,
+}
+
+impl Status {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ok => "200 OK",
+            Self::BadRequest => "400 Bad Request",
+            Self::NotFound => "404 Not Found",
+            Self::MethodNotAllowed => "405 Method Not Allowed",
+            Self::Internal => "500 Internal Server Error",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Response {
+    pub status: Status,
+    pub body: Value,
+}
+
+impl Response {
+    fn error(status: Status, msg: impl ToString) -> Self {
+        Self {
+            status,
+            body: json!({ "error": msg.to_string() }),
+        }
+    }
+}
+
+impl From<Error> for Response {
+    fn from(e: Error) -> Self {
+        let status = match e {
+            Error::BadRequest(_) => Status::BadRequest,
+            Error::NotFound(_) => Status::NotFound,
+            _ => Status::Internal,
+        };
+        Self::error(status, e)
+    }
+}
+
+#[instrument(name = "gateway subroutine", skip(peer))]
+pub async fn routine<S>(peer: Peer<S>, addr: SocketAddr) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+{
+    let listener = TcpListener::bind(addr).await?;
+    info!("serving HTTP gateway on {}", listener.local_addr()?);
+
+    http::serve(listener, http::Limits::default(), move |req, stream| {
+        serve(req, stream, peer.clone())
+    })
+    .await?;
+
+    Ok(())
+}
+
+async fn serve<S>(req: Request, mut stream: TcpStream, peer: Peer<S>) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+{
+    let response = match req.method.as_str() {
+        "GET" => {
+            let path = req.target;
+            peer.using_storage(move |storage| handle(storage, &path))
+                .await
+                .unwrap_or_else(|e| Response::error(Status::Internal, e))
+        },
+        _ => Response::error(Status::MethodNotAllowed, "only GET requests are supported"),
+    };
+
+    let body = serde_json::to_vec(&response.body)?;
+    http::respond(
+        &mut stream,
+        response.status.as_str(),
+        &[
+            ("Content-Type", "application/json"),
+            ("Access-Control-Allow-Origin", "*"),
+        ],
+        &body,
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Respond to a `GET` request for `path`, which may be percent-encoded.
+pub fn handle(storage: &Storage, path: &str) -> Response {
+    let path = http::percent_decode(path.split('?').next().unwrap_or_default());
+    let segments = path.trim_matches('/').split('/').collect::<Vec<_>>();
+    let res = match segments.as_slice() {
+        ["v1", "projects", urn, rest @ ..] => match urn.parse::<Urn>() {
+            Ok(urn) => route_project(storage, urn, rest),
+            Err(_) => Err(Error::BadRequest(format!("invalid urn `{}`", urn))),
+        },
+        _ => Err(Error::NotFound(format!("no such endpoint `{}`", path))),
+    };
+
+    match res {
+        Ok(body) => Response {
+            status: Status::Ok,
+            body,
+        },
+        Err(e) => {
+            if !matches!(e, Error::BadRequest(_) | Error::NotFound(_)) {
+                tracing::error!(err = ?e, %path, "gateway error");
+            }
+            Response::from(e)
+        },
+    }
+}
+
+fn route_project(storage: &Storage, urn: Urn, rest: &[&str]) -> Result<Value, Error> {
+    let urn = urn.with_path(None);
+    let proj = project::verify(storage, &urn)?
+        .ok_or_else(|| Error::NotFound(format!("project {} not found", urn)))?;
+
+    match rest {
+        [] => Ok(identity(&proj)),
+        ["heads"] => heads(storage, &urn),
+        ["tree", commit, path @ ..] => tree(storage, &urn, commit, path),
+        ["cobs", typename] => cob(storage, &urn, typename, None),
+        ["cobs", typename, id] => cob(storage, &urn, typename, Some(*id)),
+        _ => Err(Error::NotFound(format!(
+            "no such endpoint `{}`",
+            rest.join("/")
+        ))),
+    }
+}
+
+fn identity(proj: &VerifiedProject) -> Value {
+    let subject = proj.subject();
+    let delegates = proj
+        .delegations()
+        .iter()
+        .flat_map(|delegation| match delegation {
+            Either::Left(pk) => vec![json!({ "peerId": PeerId::from(*pk) })],
+            Either::Right(person) => person
+                .delegations()
+                .iter()
+                .map(|pk| {
+                    json!({
+                        "peerId": PeerId::from(*pk),
+                        "person": {
+                            "urn": person.urn().to_string(),
+                            "name": person.subject().name.to_string(),
+                        },
+                    })
+                })
+                .collect(),
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "urn": proj.urn().to_string(),
+        "contentId": proj.content_id.to_string(),
+        "name": subject.name.to_string(),
+        "description": subject.description.as_ref().map(|d| d.to_string()),
+        "defaultBranch": subject.default_branch.as_ref().map(|b| b.to_string()),
+        "delegates": delegates,
+    })
+}
+
+/// The signed refs of the local peer and all tracked peers of `urn`.
+fn published(storage: &Storage, urn: &Urn) -> Result<BTreeMap<PeerId, (bool, Refs)>, Error> {
+    let local = *storage.peer_id();
+    let mut published = BTreeMap::new();
+    for peer in Some(None)
+        .into_iter()
+        .chain(tracking::tracked(storage, urn)?.map(Some))
+    {
+        if let Some(refs) = Refs::load(storage, urn, peer)? {
+            published.insert(peer.unwrap_or(local), (peer.is_none(), refs));
+        }
+    }
+
+    Ok(published)
+}
+
+fn heads(storage: &Storage, urn: &Urn) -> Result<Value, Error> {
+    let heads = published(storage, urn)?
+        .into_iter()
+        .map(|(peer, (_, refs))| {
+            let heads = refs
+                .heads
+                .iter()
+                .map(|(name, oid)| (name.to_string(), Value::from(oid.to_string())))
+                .collect::<serde_json::Map<_, _>>();
+            (peer.to_string(), Value::from(heads))
+        })
+        .collect::<serde_json::Map<_, _>>();
+
+    Ok(Value::from(heads))
+}
+
+/// `true` if `commit` is reachable from one of the heads [`published`] for
+/// `urn`.
+fn is_reachable(storage: &Storage, urn: &Urn, commit: git2::Oid) -> Result<bool, Error> {
+    for (peer, (local, refs)) in published(storage, urn)? {
+        for name in refs.heads.keys() {
+            let path = if local {
+                format!("refs/heads/{}", name)
+            } else {
+                format!("refs/remotes/{}/heads/{}", peer, name)
+            };
+            let head = match ext::RefLike::try_from(path.as_str()) {
+                Ok(path) => urn.clone().with_path(path),
+                Err(_) => continue,
+            };
+            if storage.has_commit(&head, ext::Oid::from(commit))? {
+                return Ok(true);
+            }
+        }
+    }
+
+    Ok(false)
+}
+
+fn tree(storage: &Storage, urn: &Urn, commit: &str, path: &[&str]) -> Result<Value, Error> {
+    let not_found = || Error::NotFound(format!("commit {} not found", commit));
+    let oid = git2::Oid::from_str(commit)
+        .map_err(|_| Error::BadRequest(format!("invalid commit `{}`", commit)))?;
+    if !is_reachable(storage, urn, oid)? {
+        return Err(not_found());
+    }
+    let commit = storage
+        .find_object(ext::Oid::from(oid))?
+        .and_then(|object| object.into_commit().ok())
+        .ok_or_else(not_found)?;
+    let tree = commit.tree()?;
+
+    let path = path.join("/");
+    let object = if path.is_empty() {
+        tree.into_object()
+    } else {
+        let entry = tree
+            .get_path(Path::new(&path))
+            .map_err(|_| Error::NotFound(format!("path `{}` not found", path)))?;
+        storage
+            .find_object(ext::Oid::from(entry.id()))?
+            .ok_or_else(|| Error::NotFound(format!("path `{}` not found", path)))?
+    };
+
+    if let Some(tree) = object.as_tree() {
+        let entries = tree
+            .iter()
+            .map(|entry| {
+                let name = String::from_utf8_lossy(entry.name_bytes());
+                json!({
+                    "name": name,
+                    "kind": entry.kind().map(|kind| kind.str()),
+                    "oid": entry.id().to_string(),
+                })
+            })
+            .collect::<Vec<_>>();
+        Ok(json!({
+            "path": path,
+            "oid": tree.id().to_string(),
+            "entries": entries,
+        }))
+    } else if let Some(blob) = object.as_blob() {
+        let (binary, content) = if blob.is_binary() {
+            (true, base64::encode(blob.content()))
+        } else {
+            (false, String::from_utf8_lossy(blob.content()).into_owned())
+        };
+        Ok(json!({
+            "path": path,
+            "oid": blob.id().to_string(),
+            "size": blob.size(),
+            "binary": binary,
+            "content": content,
+        }))
+    } else {
+        Err(Error::NotFound(format!("path `{}` not found", path)))
+    }
+}
+
+fn cob(storage: &Storage, urn: &Urn, typename: &str, id: Option<&str>) -> Result<Value, Error> {
+    if !cobs::is_valid_typename(typename) {
+        return Err(Error::BadRequest(format!(
+            "invalid typename `{}`",
+            typename
+        )));
+    }
+    if typename != patch::TYPENAME {
+        return Err(Error::NotFound(format!(
+            "unsupported typename `{}`",
+            typename
+        )));
+    }
+
+    let to_value = |p: &patch::Patch| serde_json::to_value(p).expect("patches serialise to JSON");
+    match id {
+        None => Ok(Value::from(
+            patch::list(storage, urn)?
+                .iter()
+                .map(to_value)
+                .collect::<Vec<_>>(),
+        )),
+        Some(id) => {
+            let oid = git2::Oid::from_str(id)
+                .map_err(|_| Error::BadRequest(format!("invalid object id `{}`", id)))?;
+            patch::get(storage, urn, oid.into())?
+                .as_ref()
+                .map(to_value)
+                .ok_or_else(|| Error::NotFound(format!("object {} not found", id)))
+        },
+    }
+}
@@ -0,0 +1,429 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Smart-HTTP bridge, allowing plain git clients to clone and fetch projects
+//! from the storage of the node:
+//!
+//! ```text
+//! git clone http://<host>:<port>/<urn>.git
+//! ```
+//!
+//! The refs advertised to the client form the canonical view of the project:
+//! the `heads` and `tags` signed by its delegates, where a branch is only
+//! advertised if the delegates agree on it (ie. all of their tips are
+//! ancestors of one of them). The default branch of the project becomes
+//! `HEAD`. Alternatively, `/<urn>/<peer>.git` serves the view of the given
+//! peer.
+//!
+//! Only `git-upload-pack` (ie. fetching) is supported. Once the client
+//! selected what it wants, the request is handed to `git upload-pack`
+//! restricted to the namespace of the project, which refuses any object not
+//! reachable from it.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryFrom as _,
+    fmt::Write as _,
+    io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+
+use either::Either;
+use thiserror::Error;
+use tokio::{
+    io::AsyncWriteExt as _,
+    net::{TcpListener, TcpStream},
+    process::Command,
+};
+use tracing::{debug, info, instrument};
+
+use librad::{
+    git::{
+        identities::{self, project, VerifiedProject},
+        refs::{self, Refs},
+        storage::{self, ReadOnlyStorage as _, Storage},
+        types::Namespace,
+        Urn,
+    },
+    git_ext as ext,
+    net::peer::Peer,
+    PeerId,
+    Signer,
+};
+
+use crate::http::{self, Request};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Maximum size of a (decoded) request body. Upload-pack requests only carry
+/// the wants and haves of the client.
+const MAX_BODY: usize = 32 * 1024 * 1024;
+
+/// The capabilities advertised to clients, which must all be supported by
+/// `git upload-pack`.
+const CAPABILITIES: &str = "multi_ack_detailed no-done side-band-64k thin-pack ofs-delta shallow no-progress include-tag allow-reachable-sha1-in-want";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Identities(#[from] identities::Error),
+
+    #[error(transparent)]
+    Refs(#[from] refs::stored::Error),
+
+    #[error(transparent)]
+    Storage(#[from] storage::Error),
+}
+
+/// The repository a request is made for.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Repo {
+    pub urn: Urn,
+    /// The peer whose view to serve, or `None` for the view of the
+    /// delegates.
+    pub peer: Option<PeerId>,
+}
+
+/// The endpoints of the smart-HTTP protocol.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Endpoint {
+    /// `GET <repo>/info/refs?service=<service>`
+    InfoRefs { service: Option<String> },
+    /// `POST <repo>/git-upload-pack`
+    UploadPack,
+    /// `POST <repo>/git-receive-pack`
+    ReceivePack,
+}
+
+/// Parse a request target into the [`Repo`] and [`Endpoint`] it refers to.
+///
+/// The `.git` suffix of the repository path is optional.
+pub fn parse_target(target: &str) -> Option<(Repo, Endpoint)> {
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (target, None),
+    };
+    let path = path.trim_start_matches('/');
+    let (repo, endpoint) = if let Some(repo) = path.strip_suffix("/info/refs") {
+        let service = query.and_then(|query| {
+            query
+                .split('&')
+                .find_map(|param| param.strip_prefix("service="))
+                .map(|s| s.to_owned())
+        });
+        (repo, Endpoint::InfoRefs { service })
+    } else if let Some(repo) = path.strip_suffix("/git-upload-pack") {
+        (repo, Endpoint::UploadPack)
+    } else if let Some(repo) = path.strip_suffix("/git-receive-pack") {
+        (repo, Endpoint::ReceivePack)
+    } else {
+        return None;
+    };
+
+    let repo = http::percent_decode(repo.strip_suffix(".git").unwrap_or(repo));
+    let repo = match repo.split_once('/') {
+        None => Repo {
+            urn: repo.parse().ok()?,
+            peer: None,
+        },
+        Some((urn, peer)) => Repo {
+            urn: urn.parse().ok()?,
+            peer: Some(peer.parse().ok()?),
+        },
+    };
+
+    Some((repo, endpoint))
+}
+
+/// Compute the ref advertisement of `repo`, as sent in response to
+/// `GET <repo>/info/refs?service=git-upload-pack`.
+///
+/// `None` is returned if `repo` is not a (valid) project.
+pub fn advertise(storage: &Storage, repo: &Repo) -> Result<Option<Vec<u8>>, Error> {
+    let urn = repo.urn.clone().with_path(None);
+    let proj = match project::verify(storage, &urn)? {
+        Some(proj) => proj,
+        None => return Ok(None),
+    };
+    let peers = match repo.peer {
+        Some(peer) => Some(peer).into_iter().collect(),
+        None => delegates(&proj),
+    };
+    let refs = view(storage, &urn, &peers)?;
+    let head = proj
+        .subject()
+        .default_branch
+        .as_ref()
+        .map(|branch| format!("refs/heads/{}", branch))
+        .filter(|branch| refs.contains_key(branch));
+
+    let mut caps = CAPABILITIES.to_owned();
+    if let Some(head) = &head {
+        write!(caps, " symref=HEAD:{}", head).ok();
+    }
+    write!(caps, " agent=radicle-link/{}", env!("CARGO_PKG_VERSION")).ok();
+
+    let mut lines = Vec::with_capacity(refs.len() + 1);
+    if let Some(head) = &head {
+        lines.push((refs[head], "HEAD".to_owned()));
+    }
+    lines.extend(refs.into_iter().map(|(name, oid)| (oid, name)));
+    if lines.is_empty() {
+        lines.push((git2::Oid::zero(), "capabilities^{}".to_owned()));
+    }
+
+    let mut adv = pkt_line("# service=git-upload-pack\n");
+    adv.extend_from_slice(b"0000");
+    for (i, (oid, name)) in lines.into_iter().enumerate() {
+        let line = if i == 0 {
+            format!("{} {}\0{}\n", oid, name, caps)
+        } else {
+            format!("{} {}\n", oid, name)
+        };
+        adv.extend(pkt_line(&line));
+    }
+    adv.extend_from_slice(b"0000");
+
+    Ok(Some(adv))
+}
+
+/// The canonical view of the project `urn`, ie. the `heads` and `tags` its
+/// delegates agree on, keyed by their fully qualified name.
+///
+/// `None` is returned if `urn` is not a (valid) project.
+pub fn canonical_refs(
+    storage: &Storage,
+    urn: &Urn,
+) -> Result<Option<BTreeMap<String, git2::Oid>>, Error> {
+    let urn = urn.clone().with_path(None);
+    match project::verify(storage, &urn)? {
+        Some(proj) => Ok(Some(view(storage, &urn, &delegates(&proj))?)),
+        None => Ok(None),
+    }
+}
+
+fn delegates(proj: &VerifiedProject) -> BTreeSet<PeerId> {
+    proj.delegations()
+        .iter()
+        .flat_map(|delegation| match delegation {
+            Either::Left(pk) => vec![PeerId::from(*pk)],
+            Either::Right(person) => person
+                .delegations()
+                .iter()
+                .map(|pk| PeerId::from(*pk))
+                .collect(),
+        })
+        .collect()
+}
+
+/// The `heads` and `tags` signed by `peers`, keyed by their fully qualified
+/// name.
+///
+/// Where the peers disagree on a ref, the tip which has all other tips as
+/// ancestors is chosen. If there is no such tip, the ref is omitted.
+fn view(
+    storage: &Storage,
+    urn: &Urn,
+    peers: &BTreeSet<PeerId>,
+) -> Result<BTreeMap<String, git2::Oid>, Error> {
+    let local = *storage.peer_id();
+
+    // Qualified name -> [(path of the ref in the namespace, tip)]
+    let mut candidates: BTreeMap<String, Vec<(ext::RefLike, git2::Oid)>> = BTreeMap::new();
+    for peer in peers {
+        let remote = (*peer != local).then(|| *peer);
+        let refs = match Refs::load(storage, urn, remote)? {
+            Some(refs) => refs,
+            None => continue,
+        };
+        let categories = [("heads", &refs.heads), ("tags", &refs.tags)];
+        for (category, refs) in categories.iter() {
+            for (name, oid) in refs.iter() {
+                let path = match remote {
+                    None => format!("refs/{}/{}", category, name),
+                    Some(peer) => format!("refs/remotes/{}/{}/{}", peer, category, name),
+                };
+                if let Ok(path) = ext::RefLike::try_from(path.as_str()) {
+                    candidates
+                        .entry(format!("refs/{}/{}", category, name))
+                        .or_default()
+                        .push((path, git2::Oid::from(*oid)));
+                }
+            }
+        }
+    }
+
+    let mut view = BTreeMap::new();
+    for (name, tips) in candidates {
+        let mut canonical = None;
+        for (path, tip) in &tips {
+            let urn = urn.clone().with_path(path.clone());
+            let mut descends = true;
+            for (_, other) in &tips {
+                if other != tip && !storage.has_commit(&urn, Box::new(*other))? {
+                    descends = false;
+                    break;
+                }
+            }
+            if descends {
+                canonical = Some(*tip);
+                break;
+            }
+        }
+        match canonical {
+            Some(tip) => {
+                view.insert(name, tip);
+            },
+            None => debug!(%urn, %name, "omitting diverged ref"),
+        }
+    }
+
+    Ok(view)
+}
+
+fn pkt_line(msg: &str) -> Vec<u8> {
+    format!("{:04x}{}", 4 + msg.len(), msg).into_bytes()
+}
+
+#[instrument(name = "git http subroutine", skip(peer))]
+pub async fn routine<S>(peer: Peer<S>, addr: SocketAddr) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+{
+    let listener = TcpListener::bind(addr).await?;
+    info!("serving git smart-HTTP on {}", listener.local_addr()?);
+
+    let git_dir = peer.protocol_config().paths.git_dir().to_path_buf();
+    let limits = http::Limits {
+        request_timeout: REQUEST_TIMEOUT,
+        max_body: MAX_BODY,
+        ..http::Limits::default()
+    };
+    http::serve(listener, limits, move |req, stream| {
+        serve(req, stream, peer.clone(), git_dir.clone())
+    })
+    .await?;
+
+    Ok(())
+}
+
+async fn serve<S>(
+    req: Request,
+    mut stream: TcpStream,
+    peer: Peer<S>,
+    git_dir: PathBuf,
+) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+{
+    match (req.method.as_str(), parse_target(&req.target)) {
+        ("GET", Some((repo, Endpoint::InfoRefs { service }))) => {
+            if service.as_deref() != Some("git-upload-pack") {
+                return respond(
+                    &mut stream,
+                    "403 Forbidden",
+                    "only git-upload-pack is supported",
+                )
+                .await;
+            }
+            match peer
+                .using_storage(move |storage| advertise(storage, &repo))
+                .await?
+            {
+                Ok(Some(adv)) => Ok(http::respond(
+                    &mut stream,
+                    "200 OK",
+                    &[
+                        (
+                            "Content-Type",
+                            "application/x-git-upload-pack-advertisement",
+                        ),
+                        ("Cache-Control", "no-cache"),
+                    ],
+                    &adv,
+                )
+                .await?),
+                Ok(None) => respond(&mut stream, "404 Not Found", "repository not found").await,
+                Err(e) => {
+                    tracing::error!(err = ?e, "error computing ref advertisement");
+                    respond(&mut stream, "500 Internal Server Error", "internal error").await
+                },
+            }
+        },
+        ("POST", Some((repo, Endpoint::UploadPack))) => {
+            upload_pack(&mut stream, &git_dir, &repo, req.body).await
+        },
+        (_, Some((_, Endpoint::ReceivePack))) => {
+            respond(&mut stream, "403 Forbidden", "pushing is not supported").await
+        },
+        (_, Some(_)) => respond(&mut stream, "405 Method Not Allowed", "method not allowed").await,
+        (_, None) => respond(&mut stream, "404 Not Found", "not found").await,
+    }
+}
+
+/// Hand the upload-pack request `body` to `git upload-pack`, streaming its
+/// output back to the client.
+async fn upload_pack(
+    stream: &mut TcpStream,
+    git_dir: &Path,
+    repo: &Repo,
+    body: Vec<u8>,
+) -> anyhow::Result<()> {
+    let namespace = Namespace::from(repo.urn.clone().with_path(None));
+    let mut git = Command::new("git");
+    git.args(&["-c", "uploadpack.allowReachableSHA1InWant=true"])
+        .args(&[
+            "upload-pack",
+            "--strict",
+            "--timeout=30",
+            "--stateless-rpc",
+            ".",
+        ])
+        .env("GIT_NAMESPACE", namespace.to_string())
+        .envs(std::env::vars().filter(|(key, _)| key.starts_with("GIT_TRACE")))
+        .current_dir(git_dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .kill_on_drop(true);
+    let mut child = git.spawn()?;
+
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = child.stdout.take().unwrap();
+
+    http::write_head(
+        stream,
+        "200 OK",
+        &[
+            ("Content-Type", "application/x-git-upload-pack-result"),
+            ("Cache-Control", "no-cache"),
+        ],
+        None,
+    )
+    .await?;
+    let write = async move {
+        stdin.write_all(&body).await?;
+        drop(stdin);
+        Ok::<_, io::Error>(())
+    };
+    let (_, _, status) =
+        futures::try_join!(write, tokio::io::copy(&mut stdout, stream), child.wait())?;
+    stream.shutdown().await?;
+
+    if !status.success() {
+        anyhow::bail!("upload-pack exited non-zero: {:?}", status)
+    }
+
+    Ok(())
+}
+
+async fn respond(stream: &mut TcpStream, status: &str, body: &str) -> anyhow::Result<()> {
+    http::respond_text(stream, status, &format!("{}\n", body)).await?;
+
+    Ok(())
+}
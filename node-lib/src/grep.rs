@@ -0,0 +1,102 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Maintain [`grep`] indexes over the canonical heads of selected projects
+//! (see [`git_http::canonical_refs`]), such that they can be searched using
+//! `rad grep`.
+//!
+//! Indexes are updated on startup, and whenever an update of the project was
+//! replicated.
+
+use std::{collections::BTreeMap, time::Duration};
+
+use futures::{Stream, StreamExt as _};
+use tokio::{select, time::interval};
+use tracing::{debug, instrument, warn};
+
+use librad::{
+    git::{grep, Urn},
+    git_ext as ext,
+    net::{
+        peer::{Peer, ProtocolEvent},
+        protocol::{broadcast::PutResult, event::upstream::Gossip, RecvError},
+    },
+    Signer,
+};
+
+use crate::git_http;
+
+/// How often indexes are updated for projects which were replicated since.
+const TICK_INTERVAL: Duration = Duration::from_secs(5);
+
+#[instrument(name = "grep subroutine", skip(peer, urns, events))]
+pub async fn routine<S, E>(peer: Peer<S>, urns: Vec<Urn>, events: E) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+    E: Stream<Item = Result<ProtocolEvent, RecvError>> + Send + 'static,
+{
+    futures::pin_mut!(events);
+    let mut tick = interval(TICK_INTERVAL);
+    // Whether each project was updated since it was last indexed.
+    let mut pending = vec![true; urns.len()];
+
+    loop {
+        select! {
+            event = events.next() => match event {
+                Some(Ok(ProtocolEvent::Gossip(gossip))) => {
+                    let Gossip::Put { payload, result, .. } = gossip.as_ref();
+                    if let PutResult::Applied(_) = result {
+                        let urn = payload.urn.clone().with_path(None);
+                        for (indexed, pending) in urns.iter().zip(pending.iter_mut()) {
+                            if *indexed == urn {
+                                *pending = true;
+                            }
+                        }
+                    }
+                },
+                Some(Ok(_)) => {},
+                Some(Err(RecvError::Lagged(n))) => {
+                    warn!(skipped = n, "grep lagging behind protocol events")
+                },
+                Some(Err(RecvError::Closed)) | None => break,
+            },
+
+            _ = tick.tick() => {
+                for (urn, pending) in urns.iter().zip(pending.iter_mut()) {
+                    if !*pending {
+                        continue;
+                    }
+                    *pending = false;
+                    match index(&peer, urn).await {
+                        Ok(()) => debug!(%urn, "updated grep index"),
+                        Err(err) => warn!(%urn, ?err, "failed to update grep index"),
+                    }
+                }
+            },
+        }
+    }
+
+    Ok(())
+}
+
+async fn index<S>(peer: &Peer<S>, urn: &Urn) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+{
+    let urn = urn.clone();
+    peer.using_storage(move |storage| {
+        let view = match git_http::canonical_refs(storage, &urn)? {
+            Some(view) => view,
+            None => anyhow::bail!("{} is not a project in the local storage", urn),
+        };
+        let heads = view
+            .into_iter()
+            .filter(|(name, _)| name.starts_with("refs/heads/"))
+            .map(|(name, oid)| (name, ext::Oid::from(oid)))
+            .collect::<BTreeMap<_, _>>();
+        Ok(grep::update(storage, &urn, &heads)?)
+    })
+    .await?
+}
@@ -0,0 +1,131 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Minimal HTTP endpoint for liveness and readiness probes.
+//!
+//! * `GET /healthz` responds with `200 OK` if the process is alive and the
+//!   storage can be opened.
+//! * `GET /readyz` responds with `200 OK` if the protocol endpoint is bound,
+//!   and at least one peer is connected (unless no bootstrap peers were
+//!   configured).
+//!
+//! Readiness does not wait for an initial sync: `linkd` does not perform one
+//! on startup, but replicates identities as they are announced or requested.
+//! Once a peer is connected, the node is able to serve and fetch.
+//!
+//! Otherwise, `503 Service Unavailable` is returned, along with a plain text
+//! body listing the state of each check.
+
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use futures::{Stream, StreamExt as _};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    spawn,
+};
+use tracing::{info, instrument};
+
+use librad::{
+    net::{
+        peer::{Peer, ProtocolEvent},
+        protocol::{event::upstream::Endpoint, RecvError},
+    },
+    Signer,
+};
+
+use crate::http::{self, Request};
+
+#[instrument(name = "health subroutine", skip(peer, events))]
+pub async fn routine<S, E>(
+    peer: Peer<S>,
+    events: E,
+    addr: SocketAddr,
+    bootstraps: usize,
+) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+    E: Stream<Item = Result<ProtocolEvent, RecvError>> + Send + 'static,
+{
+    let bound = Arc::new(AtomicBool::new(false));
+    spawn(track_endpoint(events, Arc::clone(&bound)));
+
+    let listener = TcpListener::bind(addr).await?;
+    info!("serving health checks on {}", listener.local_addr()?);
+
+    http::serve(listener, http::Limits::default(), move |req, stream| {
+        serve(req, stream, peer.clone(), Arc::clone(&bound), bootstraps)
+    })
+    .await?;
+
+    Ok(())
+}
+
+async fn track_endpoint<E>(events: E, bound: Arc<AtomicBool>)
+where
+    E: Stream<Item = Result<ProtocolEvent, RecvError>>,
+{
+    futures::pin_mut!(events);
+    while let Some(event) = events.next().await {
+        match event {
+            Ok(ProtocolEvent::Endpoint(Endpoint::Up { .. })) => bound.store(true, Ordering::SeqCst),
+            Ok(ProtocolEvent::Endpoint(Endpoint::Down)) => bound.store(false, Ordering::SeqCst),
+            Ok(_) => {},
+            Err(RecvError::Lagged(_)) => {},
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
+
+async fn serve<S>(
+    req: Request,
+    mut stream: TcpStream,
+    peer: Peer<S>,
+    bound: Arc<AtomicBool>,
+    bootstraps: usize,
+) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+{
+    let path = match req.method.as_str() {
+        "GET" => Some(req.target.as_str()),
+        _ => None,
+    };
+
+    let checks = match path {
+        Some("/healthz") => {
+            let storage = peer.using_read_only(|_| ()).await.is_ok();
+            vec![("storage", storage)]
+        },
+        Some("/readyz") => {
+            let bound = bound.load(Ordering::SeqCst);
+            let bootstrapped = bootstraps == 0 || !peer.connected_peers().await.is_empty();
+            vec![("bound", bound), ("bootstrap", bootstrapped)]
+        },
+        _ => {
+            http::respond_text(&mut stream, "404 Not Found", "not found\n").await?;
+            return Ok(());
+        },
+    };
+
+    let body = checks
+        .iter()
+        .map(|(check, ok)| format!("{}: {}\n", check, if *ok { "ok" } else { "failed" }))
+        .collect::<String>();
+    let status = if checks.iter().all(|(_, ok)| *ok) {
+        "200 OK"
+    } else {
+        "503 Service Unavailable"
+    };
+
+    http::respond_text(&mut stream, status, &body).await?;
+
+    Ok(())
+}
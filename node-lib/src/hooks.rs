@@ -0,0 +1,110 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Executables run as the [`Hooks`] of replications, see
+//! [`crate::args::ProtocolArgs::pre_apply_hook`] and
+//! [`crate::args::ProtocolArgs::post_apply_hook`].
+//!
+//! Both receive the [`Pending`] updates as JSON on stdin, and the namespace
+//! and remote peer in the `LINKD_URN` and `LINKD_REMOTE_PEER` environment
+//! variables. The pre-apply hook is run before every fetch of the
+//! replication which would update refs, and fails closed: the updates are
+//! vetoed unless it exits successfully within [`HOOK_TIMEOUT`]. The
+//! post-apply hook is run in the background.
+
+use std::{
+    io::{self, Read as _, Write as _},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail};
+use tracing::warn;
+
+use librad::git::replication::hooks::{Hooks, Pending, Veto};
+
+/// How long a hook may take before it is killed.
+const HOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Default)]
+pub struct Scripts {
+    pub pre_apply: Option<PathBuf>,
+    pub post_apply: Option<PathBuf>,
+}
+
+impl Hooks for Scripts {
+    fn pre_apply(&self, pending: &Pending) -> Result<(), Veto> {
+        match &self.pre_apply {
+            None => Ok(()),
+            Some(script) => run(script, pending).map_err(|e| Veto {
+                reason: e.to_string(),
+            }),
+        }
+    }
+
+    fn post_apply(&self, applied: &Pending) {
+        if let Some(script) = &self.post_apply {
+            let script = script.clone();
+            let applied = applied.clone();
+            thread::spawn(move || {
+                if let Err(err) = run(&script, &applied) {
+                    warn!(%err, urn = %applied.urn, "post-apply hook failed");
+                }
+            });
+        }
+    }
+}
+
+/// Run `script` with `pending` on its standard input, failing if it exits
+/// unsuccessfully or takes longer than [`HOOK_TIMEOUT`].
+fn run(script: &Path, pending: &Pending) -> anyhow::Result<()> {
+    let input = serde_json::to_vec(pending)?;
+    let mut child = Command::new(script)
+        .env("LINKD_URN", pending.urn.to_string())
+        .env("LINKD_REMOTE_PEER", pending.remote_peer.to_string())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| anyhow!("failed to run {}: {}", script.display(), e))?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        // Hooks are free to ignore their input
+        match stdin.write_all(&input) {
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {},
+            res => res?,
+        }
+    }
+    // Drain stderr while waiting, so a chatty hook doesn't block on a full
+    // pipe.
+    let stderr = child.stderr.take();
+    let stderr = thread::spawn(move || {
+        let mut out = String::new();
+        if let Some(mut stderr) = stderr {
+            stderr.read_to_string(&mut out).ok();
+        }
+        out
+    });
+
+    let deadline = Instant::now() + HOOK_TIMEOUT;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            child.kill().ok();
+            child.wait().ok();
+            bail!("{} timed out after {:?}", script.display(), HOOK_TIMEOUT);
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    if !status.success() {
+        bail!("{}: {}", status, stderr.join().unwrap_or_default().trim());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,337 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Minimal HTTP/1.1 server, shared by the health checks, the
+//! [`crate::gateway`] and the [`crate::git_http`] bridge.
+//!
+//! Every connection carries a single request, and is closed once the response
+//! was sent. At most [`Limits::max_connections`] connections are served at a
+//! time: further ones are only accepted once one of them is closed.
+
+use std::{
+    future::Future,
+    io::{self, Read as _},
+    sync::Arc,
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncReadExt as _, AsyncWrite, AsyncWriteExt as _},
+    net::{TcpListener, TcpStream},
+    spawn,
+    sync::Semaphore,
+    time::timeout,
+};
+use tracing::debug;
+
+/// Maximum size of the request line and headers.
+pub const MAX_HEAD: usize = 16 * 1024;
+
+/// The limits imposed on the connections of [`serve`].
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    /// Maximum number of connections served at the same time.
+    ///
+    /// Default: 64
+    pub max_connections: usize,
+    /// Maximum time to receive a request in, including its body.
+    ///
+    /// Default: 5s
+    pub request_timeout: Duration,
+    /// Maximum size of a (decoded) request body.
+    ///
+    /// Default: 0, ie. no body is accepted
+    pub max_body: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_connections: 64,
+            request_timeout: Duration::from_secs(5),
+            max_body: 0,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub method: String,
+    pub target: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// The value of the first header called `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Accept connections on `listener`, and pass every request to `handler`
+/// along with the connection to respond on.
+///
+/// Requests which are malformed or exceed the [`Limits`] are answered with
+/// `400 Bad Request`, or `408 Request Timeout`, without invoking `handler`.
+pub async fn serve<H, F>(listener: TcpListener, limits: Limits, handler: H) -> io::Result<()>
+where
+    H: Fn(Request, TcpStream) -> F + Clone + Send + 'static,
+    F: Future<Output = anyhow::Result<()>> + Send + 'static,
+{
+    let permits = Arc::new(Semaphore::new(limits.max_connections));
+    loop {
+        let permit = Arc::clone(&permits)
+            .acquire_owned()
+            .await
+            .expect("semaphore is never closed");
+        let (mut stream, remote) = listener.accept().await?;
+        let handler = handler.clone();
+        spawn(async move {
+            let res = match timeout(
+                limits.request_timeout,
+                read_request(&mut stream, limits.max_body),
+            )
+            .await
+            {
+                Ok(Ok(req)) => handler(req, stream).await,
+                Ok(Err(e)) => respond_text(&mut stream, "400 Bad Request", &format!("{}\n", e))
+                    .await
+                    .map_err(anyhow::Error::from),
+                Err(_) => respond_text(&mut stream, "408 Request Timeout", "request timeout\n")
+                    .await
+                    .map_err(anyhow::Error::from),
+            };
+            if let Err(err) = res {
+                debug!(%remote, ?err, "HTTP request failed");
+            }
+            drop(permit);
+        });
+    }
+}
+
+/// Write a response with the given `headers` and `body`, and close the
+/// connection.
+pub async fn respond<W>(
+    stream: &mut W,
+    status: &str,
+    headers: &[(&str, &str)],
+    body: &[u8],
+) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    write_head(stream, status, headers, Some(body.len())).await?;
+    stream.write_all(body).await?;
+    stream.shutdown().await
+}
+
+/// [`respond`] with a plain text `body`.
+pub async fn respond_text<W>(stream: &mut W, status: &str, body: &str) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    respond(
+        stream,
+        status,
+        &[("Content-Type", "text/plain")],
+        body.as_bytes(),
+    )
+    .await
+}
+
+/// Write the status line and headers of a response, after which the body can
+/// be written to `stream`.
+///
+/// If `content_length` is `None`, the body extends until the connection is
+/// closed.
+pub async fn write_head<W>(
+    stream: &mut W,
+    status: &str,
+    headers: &[(&str, &str)],
+    content_length: Option<usize>,
+) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut head = format!("HTTP/1.1 {}\r\n", status);
+    for (name, value) in headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    if let Some(len) = content_length {
+        head.push_str(&format!("Content-Length: {}\r\n", len));
+    }
+    head.push_str("Connection: close\r\n\r\n");
+
+    stream.write_all(head.as_bytes()).await
+}
+
+/// Decode `%XX` escapes in `s`, eg. so URNs can be given as `rad%3Agit%3A...`.
+pub fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| bytes.get(i + 1..i + 3))
+            .flatten()
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                i += 3;
+            },
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            },
+        }
+    }
+
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Read a request from `stream`, whose body may be at most `max_body` bytes.
+///
+/// Bodies in chunked transfer encoding, and `gzip` content encoding, are
+/// decoded.
+pub async fn read_request<R>(stream: &mut R, max_body: usize) -> io::Result<Request>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut buf = Vec::with_capacity(4096);
+    let head_len = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        if buf.len() > MAX_HEAD {
+            return Err(invalid("request head too large"));
+        }
+        fill(stream, &mut buf).await?;
+    };
+    if head_len > MAX_HEAD {
+        return Err(invalid("request head too large"));
+    }
+
+    let head = String::from_utf8_lossy(&buf[..head_len]).into_owned();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let (method, target) = match (request_line.next(), request_line.next()) {
+        (Some(method), Some(target)) => (method.to_owned(), target.to_owned()),
+        _ => return Err(invalid("malformed request line")),
+    };
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(k, v)| (k.trim().to_owned(), v.trim().to_owned()))
+        .collect::<Vec<_>>();
+
+    let mut req = Request {
+        method,
+        target,
+        headers,
+        body: buf.split_off(head_len),
+    };
+
+    let chunked = req
+        .header("Transfer-Encoding")
+        .map(|te| te.eq_ignore_ascii_case("chunked"))
+        .unwrap_or(false);
+    if chunked {
+        req.body = read_chunked(stream, std::mem::take(&mut req.body), max_body).await?;
+    } else {
+        let len = match req.header("Content-Length") {
+            None => 0,
+            Some(len) => len
+                .parse::<usize>()
+                .map_err(|_| invalid("invalid content length"))?,
+        };
+        if len > max_body {
+            return Err(invalid("request body too large"));
+        }
+        while req.body.len() < len {
+            fill(stream, &mut req.body).await?;
+        }
+        req.body.truncate(len);
+    }
+
+    if req
+        .header("Content-Encoding")
+        .map(|ce| ce.eq_ignore_ascii_case("gzip"))
+        .unwrap_or(false)
+    {
+        // Read one more byte than allowed, to tell a body which is too large
+        // from one which is exactly `max_body` bytes long
+        let mut body = Vec::new();
+        flate2::read::GzDecoder::new(req.body.as_slice())
+            .take((max_body as u64).saturating_add(1))
+            .read_to_end(&mut body)?;
+        if body.len() > max_body {
+            return Err(invalid("request body too large"));
+        }
+        req.body = body;
+    }
+
+    Ok(req)
+}
+
+/// Read a body in chunked transfer encoding, where `buf` holds the part
+/// already read.
+async fn read_chunked<R>(stream: &mut R, mut buf: Vec<u8>, max_body: usize) -> io::Result<Vec<u8>>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut body = Vec::new();
+    let mut pos = 0;
+    loop {
+        // Make sure the chunk size line is complete
+        let line_end = loop {
+            if let Some(end) = buf[pos..].windows(2).position(|w| w == b"\r\n") {
+                break pos + end;
+            }
+            fill(stream, &mut buf).await?;
+        };
+        let size = std::str::from_utf8(&buf[pos..line_end])
+            .ok()
+            .and_then(|line| line.split(';').next())
+            .and_then(|size| usize::from_str_radix(size.trim(), 16).ok())
+            .ok_or_else(|| invalid("invalid chunk size"))?;
+        pos = line_end + 2;
+
+        if size == 0 {
+            return Ok(body);
+        }
+        // `size` is chosen by the client, so guard against overflow
+        let end = match (body.len().checked_add(size), pos.checked_add(size)) {
+            (Some(len), Some(end)) if len <= max_body => end,
+            _ => return Err(invalid("request body too large")),
+        };
+        while buf.len() < end + 2 {
+            fill(stream, &mut buf).await?;
+        }
+        body.extend_from_slice(&buf[pos..end]);
+        pos = end + 2;
+    }
+}
+
+async fn fill<R>(stream: &mut R, buf: &mut Vec<u8>) -> io::Result<()>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut chunk = [0; 8192];
+    let n = stream.read(&mut chunk).await?;
+    if n == 0 {
+        return Err(invalid("unexpected end of request"));
+    }
+    buf.extend_from_slice(&chunk[..n]);
+    Ok(())
+}
+
+fn invalid(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
@@ -0,0 +1,126 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Download the large objects referenced by replicated commits.
+//!
+//! Whenever a gossip update was applied, the tree of the announced commit is
+//! inspected for [`large::Pointer`]s. Objects missing from the local
+//! [`large::Store`] are downloaded chunk by chunk from the peer which provided
+//! the update. A download interrupted by an error is resumed the next time the
+//! object is referenced by an update.
+
+use std::net::SocketAddr;
+
+use futures::{Stream, StreamExt as _};
+use tokio::task::spawn_blocking;
+use tracing::{debug, info, instrument, warn};
+
+use librad::{
+    git::large::{self, Pointer, Progress},
+    net::{
+        peer::{Peer, ProtocolEvent},
+        protocol::{
+            broadcast::PutResult,
+            event::upstream::Gossip,
+            gossip::{Payload, Rev},
+            Interrogation,
+            PeerInfo,
+            RecvError,
+        },
+    },
+    Signer,
+};
+
+#[instrument(name = "large objects subroutine", skip(peer, events))]
+pub async fn routine<S, E>(peer: Peer<S>, max_size: u64, events: E) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+    E: Stream<Item = Result<ProtocolEvent, RecvError>> + Send + 'static,
+{
+    let store = large::Store::open(&peer.protocol_config().paths);
+
+    futures::pin_mut!(events);
+    loop {
+        match events.next().await {
+            Some(Ok(ProtocolEvent::Gossip(gossip))) => {
+                let Gossip::Put {
+                    provider,
+                    payload,
+                    result,
+                } = *gossip;
+                if let PutResult::Applied(_) = result {
+                    if let Err(err) = sync(&peer, &store, max_size, provider, payload).await {
+                        warn!(?err, "failed to download large objects")
+                    }
+                }
+            },
+            Some(Ok(_)) => {},
+            Some(Err(RecvError::Lagged(n))) => {
+                warn!(skipped = n, "large objects lagging behind protocol events")
+            },
+            Some(Err(RecvError::Closed)) | None => break,
+        }
+    }
+
+    Ok(())
+}
+
+async fn sync<S>(
+    peer: &Peer<S>,
+    store: &large::Store,
+    max_size: u64,
+    provider: PeerInfo<SocketAddr>,
+    payload: Payload,
+) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+{
+    let tip = match payload.rev {
+        Some(Rev::Git(oid)) => oid,
+        None => return Ok(()),
+    };
+    let pointers = peer
+        .using_storage(move |storage| large::pointers(storage, Some(tip)))
+        .await??;
+
+    let interrogation = peer.interrogate(provider);
+    for pointer in pointers {
+        if store.has(&pointer.oid) {
+            continue;
+        }
+        if pointer.size > max_size {
+            debug!(oid = %pointer.oid, size = pointer.size, "skipping large object");
+            continue;
+        }
+        download(&interrogation, store, &pointer).await?;
+        info!(oid = %pointer.oid, size = pointer.size, "downloaded large object");
+    }
+
+    Ok(())
+}
+
+async fn download(
+    interrogation: &Interrogation,
+    store: &large::Store,
+    pointer: &Pointer,
+) -> anyhow::Result<()> {
+    let mut offset = store.resume_offset(&pointer.oid)?;
+    loop {
+        let chunk = interrogation.large_object(pointer.oid, offset).await?;
+        if chunk.data.is_empty() {
+            anyhow::bail!("large object {} ended prematurely", pointer.oid);
+        }
+        // Completing a download entails hashing the whole object.
+        let written = {
+            let store = store.clone();
+            let pointer = *pointer;
+            spawn_blocking(move || store.write_chunk(&pointer, &chunk)).await??
+        };
+        match written {
+            Progress::Partial(len) => offset = len,
+            Progress::Complete => return Ok(()),
+        }
+    }
+}
@@ -5,14 +5,33 @@
 
 pub mod args;
 
+mod addrbook;
+mod bitmaps;
+pub mod ci;
+
 mod cfg;
 pub use cfg::{Seed, Seeds};
 
+#[cfg(unix)]
+pub mod control;
+
+pub mod gateway;
+pub mod git_http;
+mod grep;
+mod health;
+mod hooks;
+pub mod http;
+mod large;
 mod logging;
 mod metrics;
+pub mod mirror;
 pub mod node;
+pub mod notify;
 mod protocol;
+mod reflogs;
 mod signals;
+pub mod supervisor;
+pub mod webhooks;
 
 #[cfg(unix)]
 pub mod socket_activation;
@@ -7,7 +7,9 @@ use std::env;
 
 use log::{log_enabled, Level};
 use tracing::subscriber::set_global_default as set_subscriber;
-use tracing_subscriber::{EnvFilter, FmtSubscriber};
+use tracing_subscriber::{layer::SubscriberExt as _, EnvFilter, FmtSubscriber};
+
+use crate::args::TracingArgs;
 
 /// Initialise logging / tracing
 ///
@@ -20,7 +22,11 @@ use tracing_subscriber::{EnvFilter, FmtSubscriber};
 ///
 /// If the variable is not set, or set to any other value, the
 /// [`tracing_subscriber::fmt::format::Full`] format is used.
-pub fn init() {
+///
+/// If [`TracingArgs::otlp_endpoint`] is set, spans are additionally exported
+/// to an OpenTelemetry collector. This requires the `otlp` feature, and must be
+/// called from within a tokio runtime.
+pub fn init(args: &TracingArgs) -> anyhow::Result<()> {
     if env_logger::builder().try_init().is_ok() {
         let mut builder = FmtSubscriber::builder()
             .with_env_filter(
@@ -39,11 +45,77 @@ pub fn init() {
         }
 
         match env::var("TRACING_FMT").ok().as_deref() {
-            Some("pretty") => set_subscriber(builder.pretty().finish()),
-            Some("compact") => set_subscriber(builder.compact().finish()),
-            Some("json") => set_subscriber(builder.json().flatten_event(true).finish()),
-            _ => set_subscriber(builder.finish()),
+            Some("pretty") => set_subscriber(builder.pretty().finish().with(otlp::layer(args)?)),
+            Some("compact") => set_subscriber(builder.compact().finish().with(otlp::layer(args)?)),
+            Some("json") => set_subscriber(
+                builder
+                    .json()
+                    .flatten_event(true)
+                    .finish()
+                    .with(otlp::layer(args)?),
+            ),
+            _ => set_subscriber(builder.finish().with(otlp::layer(args)?)),
         }
         .expect("setting tracing subscriber failed")
     }
+
+    Ok(())
+}
+
+/// Flush outstanding spans, if exporting to an OpenTelemetry collector.
+pub fn shutdown() {
+    otlp::shutdown()
+}
+
+#[cfg(feature = "otlp")]
+mod otlp {
+    use opentelemetry::sdk::trace::{self, Sampler, Tracer};
+    use tracing::Subscriber;
+    use tracing_opentelemetry::OpenTelemetryLayer;
+    use tracing_subscriber::registry::LookupSpan;
+
+    use crate::args::TracingArgs;
+
+    pub fn layer<S>(args: &TracingArgs) -> anyhow::Result<Option<OpenTelemetryLayer<S, Tracer>>>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        match &args.otlp_endpoint {
+            None => Ok(None),
+            Some(endpoint) => {
+                // Respect the sampling decision of a remote parent, if any.
+                let sampler = Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+                    args.sample_ratio.as_f64(),
+                )));
+                let tracer = opentelemetry_otlp::new_pipeline()
+                    .with_endpoint(endpoint)
+                    .with_trace_config(trace::config().with_sampler(sampler))
+                    .install_batch(opentelemetry::runtime::Tokio)?;
+
+                Ok(Some(tracing_opentelemetry::layer().with_tracer(tracer)))
+            },
+        }
+    }
+
+    pub fn shutdown() {
+        opentelemetry::global::shutdown_tracer_provider()
+    }
+}
+
+#[cfg(not(feature = "otlp"))]
+mod otlp {
+    use tracing_subscriber::layer::Identity;
+
+    use crate::args::TracingArgs;
+
+    pub fn layer(args: &TracingArgs) -> anyhow::Result<Option<Identity>> {
+        match &args.otlp_endpoint {
+            None => Ok(None),
+            Some(_) => Err(anyhow::anyhow!(
+                "--tracing-otlp-endpoint requires node-lib to be built with the `otlp` feature"
+            )),
+        }
+    }
+
+    pub fn shutdown() {}
 }
@@ -3,4 +3,10 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+use librad::git::replication::metrics::Counters;
+
 pub mod graphite;
+
+/// The replication metrics of this process, installed in the replication
+/// config if a metrics provider is configured.
+pub static REPLICATION: Counters = Counters::new();
@@ -11,12 +11,28 @@ use std::{
 use tokio::{net::UdpSocket, time};
 use tracing::{debug, info, instrument};
 
-use librad::{net::peer::Peer, Signer};
+use librad::{
+    net::{peer::Peer, tofu},
+    Signer,
+};
 
 const CONNECTIONS_TOTAL: &str = "connections_total";
 const CONNECTED_PEERS: &str = "connected_peers";
 const MEMBERSHIP_ACTIVE: &str = "membership_active";
 const MEMBERSHIP_PASSIVE: &str = "membership_passive";
+const RTT_MS: &str = "rtt_ms";
+const BITMAP_HITS: &str = "bitmap_hits";
+const BITMAP_PARTIAL: &str = "bitmap_partial";
+const BITMAP_MISSES: &str = "bitmap_misses";
+const REPLICATION_LS_REFS_MS: &str = "replication_ls_refs_ms";
+const REPLICATION_FETCHES: &str = "replication_fetches";
+const REPLICATION_WANTS: &str = "replication_wants";
+const REPLICATION_PACK_BYTES: &str = "replication_pack_bytes";
+const REPLICATION_VERIFICATION_MS: &str = "replication_verification_ms";
+const REPLICATION_TRANSACTION_MS: &str = "replication_transaction_ms";
+const FETCH_RENEGOTIATIONS: &str = "fetch_renegotiations";
+const FETCH_STREAM_RESETS: &str = "fetch_stream_resets";
+const TOFU_MISMATCHES: &str = "tofu_mismatches";
 
 #[instrument(name = "graphite subroutine", skip(peer))]
 pub async fn routine<S>(peer: Peer<S>, graphite_addr: SocketAddr) -> anyhow::Result<()>
@@ -46,6 +62,42 @@ where
             sock.send(line(peer_id.clone(), metric, *value as f32, now).as_bytes())
                 .await?;
         }
+        for (metric, value) in &[
+            (BITMAP_HITS, stats.bitmaps.hits),
+            (BITMAP_PARTIAL, stats.bitmaps.partial),
+            (BITMAP_MISSES, stats.bitmaps.misses),
+        ] {
+            sock.send(line(peer_id.clone(), metric, *value as f32, now).as_bytes())
+                .await?;
+        }
+        let replication = super::REPLICATION.stats();
+        for (metric, value) in &[
+            (REPLICATION_FETCHES, replication.fetches),
+            (REPLICATION_WANTS, replication.wants),
+            (REPLICATION_PACK_BYTES, replication.pack_bytes),
+            (FETCH_RENEGOTIATIONS, replication.renegotiations),
+            (FETCH_STREAM_RESETS, replication.stream_resets),
+            (TOFU_MISMATCHES, tofu::mismatches()),
+        ] {
+            sock.send(line(peer_id.clone(), metric, *value as f32, now).as_bytes())
+                .await?;
+        }
+        for (metric, timer) in &[
+            (REPLICATION_LS_REFS_MS, replication.ls_refs),
+            (REPLICATION_VERIFICATION_MS, replication.verification),
+            (REPLICATION_TRANSACTION_MS, replication.transaction),
+        ] {
+            if let Some(mean) = timer.mean() {
+                let value = mean.as_secs_f32() * 1000.0;
+                sock.send(line(peer_id.clone(), metric, value, now).as_bytes())
+                    .await?;
+            }
+        }
+        for (remote, rtt) in &stats.rtt {
+            let tags = format!("{};remote={}", peer_id, remote);
+            sock.send(line(tags, RTT_MS, rtt.as_secs_f32() * 1000.0, now).as_bytes())
+                .await?;
+        }
     }
 }
 
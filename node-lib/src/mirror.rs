@@ -0,0 +1,368 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Push projects to external git hosts, eg. to maintain read-only mirrors on
+//! GitHub or GitLab.
+//!
+//! For every configured [`Mirror`], the canonical view of the project (see
+//! [`git_http::canonical_refs`]) is pushed to the remote whenever an update of
+//! the project was replicated, as well as periodically to pick up local
+//! changes. Refs which are no longer part of the view are deleted from the
+//! remote. As the remote is assumed to be a mirror, refs are force-pushed.
+//!
+//! Credentials are provided as part of the URL, eg.
+//! `https://<user>:<token>@github.com/<org>/<repo>.git`. They are neither
+//! logged nor written to disk.
+//!
+//! The refs last pushed to each mirror are recorded in the [`State`] stored
+//! alongside the monorepo, such that only what changed is pushed after a
+//! restart. Failed pushes are retried with exponential backoff.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs,
+    io,
+    path::{Path, PathBuf},
+    process::Stdio,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use futures::{Stream, StreamExt as _};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::{
+    process::Command,
+    select,
+    time::{interval, timeout, Instant},
+};
+use tracing::{info, instrument, warn};
+
+use librad::{
+    git::Urn,
+    net::{
+        peer::{Peer, ProtocolEvent},
+        protocol::{broadcast::PutResult, event::upstream::Gossip, RecvError},
+    },
+    paths::Paths,
+    Signer,
+};
+
+use crate::git_http;
+
+const STATE_FILE: &str = "mirrors.json";
+
+/// How often mirrors are checked for pending pushes.
+const TICK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often mirrors are pushed to in the absence of replication events.
+const RESYNC_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Delay before retrying a failed push, doubling with every consecutive
+/// failure up to [`RETRY_MAX`].
+const RETRY_BASE: Duration = Duration::from_secs(30);
+const RETRY_MAX: Duration = Duration::from_secs(60 * 60);
+
+const PUSH_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+/// A project to mirror, and the URL of the remote to push it to.
+///
+/// Parsed from `<urn>=<url>`.
+#[derive(Clone, Eq, PartialEq)]
+pub struct Mirror {
+    pub urn: Urn,
+    pub url: String,
+}
+
+impl Mirror {
+    /// The URL of the remote, with credentials removed.
+    pub fn redacted_url(&self) -> String {
+        redact(&self.url)
+    }
+
+    fn key(&self) -> String {
+        format!("{} {}", self.urn, self.redacted_url())
+    }
+}
+
+impl fmt::Debug for Mirror {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Mirror")
+            .field("urn", &self.urn)
+            .field("url", &self.redacted_url())
+            .finish()
+    }
+}
+
+impl fmt::Display for Mirror {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.urn, self.redacted_url())
+    }
+}
+
+impl FromStr for Mirror {
+    type Err = String;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        match src.split_once('=') {
+            Some((_, "")) => Err("missing url".to_string()),
+            Some((urn, url)) => {
+                let urn = urn.parse::<Urn>().map_err(|e| e.to_string())?;
+                Ok(Self {
+                    urn: urn.with_path(None),
+                    url: url.to_string(),
+                })
+            },
+            None => Err("expected <urn>=<url>".to_string()),
+        }
+    }
+}
+
+/// Remove the userinfo (ie. credentials) from `url`.
+pub(crate) fn redact(url: &str) -> String {
+    let (scheme, rest) = match url.split_once("://") {
+        Some(parts) => parts,
+        None => return url.to_string(),
+    };
+    let authority = rest.split('/').next().unwrap_or_default();
+    match authority.rsplit_once('@') {
+        Some((_, host)) => format!("{}://***@{}{}", scheme, host, &rest[authority.len()..]),
+        None => url.to_string(),
+    }
+}
+
+/// The mirroring state of a [`Mirror`].
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Status {
+    /// The refs last pushed successfully, keyed by their fully qualified
+    /// name.
+    pub pushed: BTreeMap<String, String>,
+    /// Time of the last successful push, in seconds since the UNIX epoch.
+    pub last_success: Option<u64>,
+    /// The number of consecutive failed pushes.
+    pub failures: u32,
+    /// The error of the last failed push, if the last push failed.
+    pub last_error: Option<String>,
+}
+
+/// The persistent state of all mirrors of a profile.
+pub struct State {
+    path: PathBuf,
+    entries: BTreeMap<String, Status>,
+}
+
+impl State {
+    /// The location of the mirroring state of the profile with the given
+    /// `paths`.
+    pub fn path(paths: &Paths) -> PathBuf {
+        paths.git_dir().join(STATE_FILE)
+    }
+
+    /// Load the state stored at `path`. If it doesn't exist yet, it is empty.
+    pub fn open_at(path: impl Into<PathBuf>) -> Result<Self, Error> {
+        let path = path.into();
+        let entries = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self { path, entries })
+    }
+
+    pub fn get(&self, mirror: &Mirror) -> Option<&Status> {
+        self.entries.get(&mirror.key())
+    }
+
+    fn entry(&mut self, mirror: &Mirror) -> &mut Status {
+        self.entries.entry(mirror.key()).or_default()
+    }
+
+    /// Write the state to disk, atomically replacing the previous version.
+    pub fn save(&self) -> Result<(), Error> {
+        let dir = self.path.parent().unwrap_or_else(|| Path::new("."));
+        let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+        serde_json::to_writer_pretty(&mut tmp, &self.entries)?;
+        tmp.persist(&self.path).map_err(|e| e.error)?;
+        Ok(())
+    }
+}
+
+/// The ref updates needed to bring a remote which has the refs `pushed` up
+/// to date with `view`, as `(name, new tip)`. A tip of `None` denotes a
+/// deletion.
+pub fn updates(
+    view: &BTreeMap<String, git2::Oid>,
+    pushed: &BTreeMap<String, String>,
+) -> Vec<(String, Option<git2::Oid>)> {
+    let changed = view
+        .iter()
+        .filter(|(name, oid)| pushed.get(*name) != Some(&oid.to_string()))
+        .map(|(name, oid)| (name.clone(), Some(*oid)));
+    let deleted = pushed
+        .keys()
+        .filter(|name| !view.contains_key(*name))
+        .map(|name| (name.clone(), None));
+    changed.chain(deleted).collect()
+}
+
+/// The delay before retrying after the given number of consecutive failures.
+pub fn backoff(failures: u32) -> Duration {
+    RETRY_BASE
+        .checked_mul(2u32.saturating_pow(failures.saturating_sub(1)))
+        .map_or(RETRY_MAX, |delay| delay.min(RETRY_MAX))
+}
+
+#[instrument(name = "mirror subroutine", skip(peer, mirrors, events))]
+pub async fn routine<S, E>(peer: Peer<S>, mirrors: Vec<Mirror>, events: E) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+    E: Stream<Item = Result<ProtocolEvent, RecvError>> + Send + 'static,
+{
+    let paths = &peer.protocol_config().paths;
+    let git_dir = paths.git_dir().to_path_buf();
+    let mut state = State::open_at(State::path(paths))?;
+
+    futures::pin_mut!(events);
+    let mut tick = interval(TICK_INTERVAL);
+    // When each mirror is due to be pushed next.
+    let mut due = vec![Instant::now(); mirrors.len()];
+
+    loop {
+        select! {
+            event = events.next() => match event {
+                Some(Ok(ProtocolEvent::Gossip(gossip))) => {
+                    let Gossip::Put { payload, result, .. } = gossip.as_ref();
+                    if let PutResult::Applied(_) = result {
+                        let urn = payload.urn.clone().with_path(None);
+                        for (mirror, due) in mirrors.iter().zip(due.iter_mut()) {
+                            if mirror.urn == urn {
+                                *due = Instant::now();
+                            }
+                        }
+                    }
+                },
+                Some(Ok(_)) => {},
+                Some(Err(RecvError::Lagged(n))) => {
+                    warn!(skipped = n, "mirror lagging behind protocol events")
+                },
+                Some(Err(RecvError::Closed)) | None => break,
+            },
+
+            _ = tick.tick() => {
+                let now = Instant::now();
+                let mut dirty = false;
+                for (mirror, due) in mirrors.iter().zip(due.iter_mut()) {
+                    if *due > now {
+                        continue;
+                    }
+                    let status = state.entry(mirror);
+                    match sync(&peer, &git_dir, mirror, status).await {
+                        Ok(()) => {
+                            status.failures = 0;
+                            status.last_error = None;
+                            *due = now + RESYNC_INTERVAL;
+                        },
+                        Err(err) => {
+                            warn!(mirror = %mirror, ?err, "failed to push mirror");
+                            status.failures = status.failures.saturating_add(1);
+                            status.last_error = Some(err.to_string());
+                            *due = now + backoff(status.failures);
+                        },
+                    }
+                    dirty = true;
+                }
+                if dirty {
+                    if let Err(err) = state.save() {
+                        warn!(?err, "failed to save mirror state")
+                    }
+                }
+            },
+        }
+    }
+
+    Ok(())
+}
+
+/// Push the canonical view of `mirror.urn` to the remote, if it changed since
+/// the last successful push.
+async fn sync<S>(
+    peer: &Peer<S>,
+    git_dir: &Path,
+    mirror: &Mirror,
+    status: &mut Status,
+) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+{
+    let urn = mirror.urn.clone();
+    let view = match peer
+        .using_storage(move |storage| git_http::canonical_refs(storage, &urn))
+        .await??
+    {
+        Some(view) => view,
+        None => anyhow::bail!("{} is not a project in the local storage", mirror.urn),
+    };
+
+    let updates = updates(&view, &status.pushed);
+    if !updates.is_empty() {
+        push(git_dir, mirror, &updates).await?;
+        info!(mirror = %mirror, refs = updates.len(), "pushed mirror");
+    }
+
+    status.pushed = view
+        .into_iter()
+        .map(|(name, oid)| (name, oid.to_string()))
+        .collect();
+    status.last_success = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_secs());
+
+    Ok(())
+}
+
+async fn push(
+    git_dir: &Path,
+    mirror: &Mirror,
+    updates: &[(String, Option<git2::Oid>)],
+) -> anyhow::Result<()> {
+    let refspecs = updates.iter().map(|(name, oid)| match oid {
+        Some(oid) => format!("+{}:{}", oid, name),
+        None => format!(":{}", name),
+    });
+
+    let mut git = Command::new("git");
+    git.args(&["push", "--porcelain", "--quiet"])
+        .arg(&mirror.url)
+        .args(refspecs)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .current_dir(git_dir)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    let out = timeout(PUSH_TIMEOUT, git.output()).await??;
+    if !out.status.success() {
+        // The remote URL may be echoed back, including credentials.
+        let stderr =
+            String::from_utf8_lossy(&out.stderr).replace(&mirror.url, &mirror.redacted_url());
+        anyhow::bail!("git push failed ({}): {}", out.status, stderr.trim());
+    }
+
+    Ok(())
+}
@@ -3,31 +3,54 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::panic;
+use std::{convert::TryFrom as _, panic};
 
 use futures::future::{select_all, FutureExt as _};
-use structopt::StructOpt as _;
 use tokio::{spawn, sync::mpsc};
 use tracing::info;
 
 use librad::{
     crypto::BoxedSigner,
     net::{discovery, peer::Peer},
+    profile::Profile,
 };
 
 use crate::{
-    args::Args,
+    addrbook,
+    args::{self, Args},
+    bitmaps,
     cfg::{self, Cfg},
+    ci,
+    gateway,
+    git_http,
+    grep,
+    health,
+    large,
     logging,
     metrics::graphite,
+    mirror::{self, redact},
+    notify,
     protocol,
+    reflogs,
     signals,
+    supervisor::{self, Supervisor},
+    webhooks::{self, Webhook},
 };
+#[cfg(unix)]
+use crate::{control, socket_activation};
 
 pub async fn run() -> anyhow::Result<()> {
-    logging::init();
+    let args = args::file::from_args()?;
+    match &args.command {
+        Some(args::Command::Config(args::ConfigCommand::Check)) => {
+            print!("{}", toml::to_string(&args::file::to_table(&args))?);
+            return Ok(());
+        },
+        Some(args::Command::Webhook(cmd)) => return webhook(&args, cmd).await,
+        None => {},
+    }
+    logging::init(&args.tracing)?;
 
-    let args = Args::from_args();
     let cfg: Cfg<discovery::Static, BoxedSigner> = cfg(&args).await?;
 
     let (shutdown_tx, shutdown_rx) = mpsc::channel(1);
@@ -35,24 +58,148 @@ pub async fn run() -> anyhow::Result<()> {
 
     let mut coalesced = vec![];
     let peer = Peer::new(cfg.peer)?;
+
+    // Subscribe before the protocol is started, so as to not miss the endpoint
+    // coming up.
+    if let Some(cfg::Health { addr, bootstraps }) = cfg.health {
+        let events = peer.subscribe();
+        let health_task = spawn(health::routine(peer.clone(), events, addr, bootstraps)).fuse();
+        coalesced.push(health_task);
+    }
+
+    // Subroutines which can be restarted from scratch without affecting the
+    // rest of the node.
+    let supervisor = Supervisor::new(supervisor::Policy::default());
+
+    if let Some(addr) = cfg.gateway {
+        let peer = peer.clone();
+        let gateway_task = supervisor
+            .spawn("gateway", move || gateway::routine(peer.clone(), addr))
+            .fuse();
+        coalesced.push(gateway_task);
+    }
+
+    if let Some(addr) = cfg.git_http {
+        let peer = peer.clone();
+        let git_http_task = supervisor
+            .spawn("git-http", move || git_http::routine(peer.clone(), addr))
+            .fuse();
+        coalesced.push(git_http_task);
+    }
+
+    if let Some(max_size) = cfg.large_objects_max_size {
+        let peer = peer.clone();
+        let large_task = supervisor
+            .spawn("large objects", move || {
+                large::routine(peer.clone(), max_size, peer.subscribe())
+            })
+            .fuse();
+        coalesced.push(large_task);
+    }
+
+    if !cfg.mirrors.is_empty() {
+        let peer = peer.clone();
+        let mirrors = cfg.mirrors;
+        let mirror_task = supervisor
+            .spawn("mirror", move || {
+                mirror::routine(peer.clone(), mirrors.clone(), peer.subscribe())
+            })
+            .fuse();
+        coalesced.push(mirror_task);
+    }
+
+    if !cfg.ci_checks.is_empty() {
+        let peer = peer.clone();
+        let checks = cfg.ci_checks;
+        let ci_task = supervisor
+            .spawn("ci", move || {
+                ci::routine(peer.clone(), checks.clone(), peer.subscribe())
+            })
+            .fuse();
+        coalesced.push(ci_task);
+    }
+
+    if !cfg.grep_index.is_empty() {
+        let peer = peer.clone();
+        let urns = cfg.grep_index;
+        let grep_task = supervisor
+            .spawn("grep", move || {
+                grep::routine(peer.clone(), urns.clone(), peer.subscribe())
+            })
+            .fuse();
+        coalesced.push(grep_task);
+    }
+
+    if !cfg.notifiers.is_empty() || !cfg.webhooks.is_empty() {
+        let peer = peer.clone();
+        let notifiers = cfg.notifiers;
+        let webhooks = cfg.webhooks;
+        let notify_task = supervisor
+            .spawn("notify", move || {
+                notify::routine(
+                    peer.clone(),
+                    notifiers.clone(),
+                    webhooks.clone(),
+                    peer.subscribe(),
+                )
+            })
+            .fuse();
+        coalesced.push(notify_task);
+    }
+
+    if let Some(every) = cfg.bitmap_maintenance {
+        let peer = peer.clone();
+        let bitmaps_task = supervisor
+            .spawn("bitmaps", move || bitmaps::routine(peer.clone(), every))
+            .fuse();
+        coalesced.push(bitmaps_task);
+    }
+
+    if let Some(every) = cfg.reflog_expiry {
+        let peer = peer.clone();
+        let retention = cfg.reflog_retention;
+        let reflogs_task = supervisor
+            .spawn("reflogs", move || {
+                reflogs::routine(peer.clone(), every, retention)
+            })
+            .fuse();
+        coalesced.push(reflogs_task);
+    }
+
+    let addrbook_task = spawn(addrbook::routine(
+        cfg.addrbook,
+        peer.protocol_config().network.clone(),
+        peer.subscribe(),
+    ))
+    .fuse();
+    coalesced.push(addrbook_task);
+
     let peer_task = spawn(protocol::routine(peer.clone(), cfg.disco, shutdown_rx)).fuse();
     coalesced.push(peer_task);
 
+    #[cfg(unix)]
+    {
+        let listener = match socket_activation::env()? {
+            Some(listener) => control::Listener::Activated(std::sync::Arc::new(listener)),
+            None => control::Listener::Path(cfg.control_socket),
+        };
+        let peer = peer.clone();
+        let control_task = supervisor
+            .spawn("control", move || {
+                control::routine(peer.clone(), listener.clone())
+            })
+            .fuse();
+        coalesced.push(control_task);
+    }
+
     if let Some(cfg::Metrics::Graphite(addr)) = cfg.metrics {
-        let graphite_task = spawn(graphite::routine(peer, addr)).fuse();
+        let graphite_task = supervisor
+            .spawn("graphite", move || graphite::routine(peer.clone(), addr))
+            .fuse();
         coalesced.push(graphite_task);
     }
 
-    // if let Some(_listener) = socket_activation::env()? {
-    // TODO(xla): Schedule listen loop.
-    // } else {
-    // TODO(xla): Bind to configured/default socket path, constructed from
-    // profile info.
-    // TODO(xla): Schedule listen loop.
-    // }
-
     // TODO(xla): Setup subroutines.
-    //  - Public API
     //  - Anncouncemnets
     //  - Replication Requests
     //  - Tracking
@@ -60,13 +207,79 @@ pub async fn run() -> anyhow::Result<()> {
     info!("starting node");
     let (res, _idx, _rest) = select_all(coalesced).await;
 
-    if let Err(e) = res {
-        if e.is_panic() {
-            panic::resume_unwind(e.into_panic());
-        }
+    match res {
+        Err(e) if e.is_panic() => panic::resume_unwind(e.into_panic()),
+        // Includes subroutines the supervisor gave up on.
+        Ok(Err(e)) => {
+            logging::shutdown();
+            return Err(e);
+        },
+        _ => {},
     }
 
     signals_task.await??;
+    logging::shutdown();
+
+    Ok(())
+}
+
+async fn webhook(args: &Args, cmd: &args::WebhookCommand) -> anyhow::Result<()> {
+    use args::WebhookCommand::*;
+
+    let profile = Profile::try_from(args)?;
+    let paths = profile.paths();
+    match cmd {
+        Add {
+            name,
+            url,
+            secret,
+            events,
+            urn,
+        } => webhooks::add(
+            paths,
+            &Webhook {
+                name: name.clone(),
+                url: url.clone(),
+                secret: secret.clone(),
+                kinds: events.iter().copied().collect(),
+                urn: urn.clone().map(|urn| urn.with_path(None)),
+            },
+        )?,
+        Remove { name } => {
+            if !webhooks::remove(paths, name)? {
+                anyhow::bail!("webhook `{}` does not exist", name)
+            }
+        },
+        List => {
+            for webhook in webhooks::list(paths)? {
+                let events = webhook
+                    .kinds
+                    .iter()
+                    .map(|kind| kind.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",");
+                match &webhook.urn {
+                    Some(urn) => println!(
+                        "{}\t{}\t{}\t{}",
+                        webhook.name,
+                        redact(&webhook.url),
+                        events,
+                        urn
+                    ),
+                    None => println!("{}\t{}\t{}", webhook.name, redact(&webhook.url), events),
+                }
+            }
+        },
+        DeadLetters => {
+            for delivery in webhooks::dead_letters(paths)? {
+                println!("{}", serde_json::to_string(&delivery)?);
+            }
+        },
+        Redeliver => {
+            let (delivered, remaining) = webhooks::redeliver(paths).await?;
+            println!("delivered {}, remaining {}", delivered, remaining);
+        },
+    }
 
     Ok(())
 }
@@ -0,0 +1,591 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Notify external programs of events on the node, eg. to send an email
+//! whenever a project was updated.
+//!
+//! A [`Notifier`] fires on a set of [`Kind`]s of [`Event`]s, optionally
+//! restricted to a single project. It either executes a command, passing the
+//! event on standard input, or POSTs the event to a webhook URL. In both
+//! cases, the event is encoded as JSON.
+//!
+//! Events are derived from the replications triggered by gossip:
+//!
+//! * [`Event::ReplicationCompleted`] for every replicated update
+//! * [`Event::IdentityUpdate`] whenever the [`inbox`] records that the local
+//!   view of an identity may need to be confirmed
+//! * [`Event::CobChanged`] whenever a collaborative object of a remote peer was
+//!   created, changed or deleted. Finding those requires a snapshot of the
+//!   namespace, so this is only supported for notifiers restricted to a
+//!   project.
+//!
+//! Notifiers are fired one at a time, in the order they were configured.
+//! Failures are logged, but not retried. For reliable delivery, register a
+//! [`Webhook`] instead.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryFrom as _,
+    fmt,
+    io,
+    process::Stdio,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, bail};
+use futures::{Stream, StreamExt as _};
+use serde::Serialize;
+use tokio::{io::AsyncWriteExt as _, process::Command, sync::mpsc, time::timeout};
+use tracing::{info, instrument, warn, Instrument as _};
+
+use librad::{
+    git::{
+        inbox::{self, Notification},
+        storage::watch,
+        Urn,
+    },
+    git_ext as ext,
+    net::{
+        peer::{Peer, ProtocolEvent},
+        protocol::{broadcast::PutResult, event::upstream::Gossip, RecvError},
+    },
+    PeerId,
+    Signer,
+};
+
+use crate::{
+    mirror::redact,
+    webhooks::{self, Webhook},
+};
+
+/// How long a command or webhook may take before it is aborted.
+const NOTIFY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The kinds of [`Event`]s a [`Notifier`] can subscribe to.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Kind {
+    ReplicationCompleted,
+    IdentityUpdate,
+    CobChanged,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 3] = [
+        Self::ReplicationCompleted,
+        Self::IdentityUpdate,
+        Self::CobChanged,
+    ];
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            Self::ReplicationCompleted => "replication-completed",
+            Self::IdentityUpdate => "identity-update",
+            Self::CobChanged => "cob-changed",
+        };
+        f.write_str(kind)
+    }
+}
+
+impl FromStr for Kind {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "replication-completed" => Ok(Self::ReplicationCompleted),
+            "identity-update" => Ok(Self::IdentityUpdate),
+            "cob-changed" => Ok(Self::CobChanged),
+            _ => Err(format!("unsupported event `{}`", input)),
+        }
+    }
+}
+
+/// An event passed to [`Notifier`]s.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum Event {
+    /// An update announced by `provider` was replicated.
+    #[serde(rename_all = "camelCase")]
+    ReplicationCompleted { urn: Urn, provider: PeerId },
+    /// The identity document was updated by the delegates, and the local view
+    /// may need to be confirmed.
+    #[serde(rename_all = "camelCase")]
+    IdentityUpdate { urn: Urn, remote_peer: PeerId },
+    /// The collaborative object `<typename>/<object id>` of `peer` changed
+    /// from `old` to `new`, where `None` denotes its absence.
+    #[serde(rename_all = "camelCase")]
+    CobChanged {
+        urn: Urn,
+        peer: PeerId,
+        object: ext::RefLike,
+        old: Option<ext::Oid>,
+        new: Option<ext::Oid>,
+    },
+}
+
+impl Event {
+    pub fn kind(&self) -> Kind {
+        match self {
+            Self::ReplicationCompleted { .. } => Kind::ReplicationCompleted,
+            Self::IdentityUpdate { .. } => Kind::IdentityUpdate,
+            Self::CobChanged { .. } => Kind::CobChanged,
+        }
+    }
+
+    pub fn urn(&self) -> &Urn {
+        match self {
+            Self::ReplicationCompleted { urn, .. }
+            | Self::IdentityUpdate { urn, .. }
+            | Self::CobChanged { urn, .. } => urn,
+        }
+    }
+}
+
+/// What a [`Notifier`] does when it fires.
+#[derive(Clone, Eq, PartialEq)]
+pub enum Action {
+    /// Run the command using `sh -c`, with the event on standard input. The
+    /// kind of the event and the URN are also provided via the environment
+    /// variables `LINKD_EVENT` and `LINKD_URN`.
+    Exec(String),
+    /// POST the event to the URL using `curl`, which must be on the `PATH`.
+    /// Credentials may be provided as part of the URL, and are redacted from
+    /// logs.
+    Webhook(String),
+}
+
+impl fmt::Debug for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exec(command) => f.debug_tuple("Exec").field(command).finish(),
+            Self::Webhook(url) => f.debug_tuple("Webhook").field(&redact(url)).finish(),
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exec(command) => write!(f, "exec:{}", command),
+            Self::Webhook(url) => f.write_str(&redact(url)),
+        }
+    }
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        if let Some(command) = input.strip_prefix("exec:") {
+            if command.trim().is_empty() {
+                return Err("missing command".to_string());
+            }
+            Ok(Self::Exec(command.to_string()))
+        } else if input.starts_with("http://") || input.starts_with("https://") {
+            Ok(Self::Webhook(input.to_string()))
+        } else {
+            Err(format!(
+                "expected `exec:<command>` or a http(s) URL, got `{}`",
+                redact(input)
+            ))
+        }
+    }
+}
+
+/// A notifier, parsed from `<events>[@<urn>]=<action>`.
+///
+/// `<events>` is a comma-separated list of [`Kind`]s, or `*` for all of
+/// them which are supported given the restriction, and `<action>` is either
+/// `exec:<command>` or a webhook URL. For example:
+///
+/// ```text
+/// identity-update,cob-changed@rad:git:hnrk...=exec:notify-send "Radicle" "$LINKD_EVENT"
+/// *=https://ci.example.com/hooks/radicle
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Notifier {
+    pub kinds: BTreeSet<Kind>,
+    /// The project to restrict the notifier to, if any.
+    pub urn: Option<Urn>,
+    pub action: Action,
+}
+
+impl Notifier {
+    /// `true` if the notifier should fire on `event`.
+    pub fn matches(&self, event: &Event) -> bool {
+        self.kinds.contains(&event.kind())
+            && self
+                .urn
+                .as_ref()
+                .map(|urn| urn.id == event.urn().id)
+                .unwrap_or(true)
+    }
+}
+
+impl fmt::Display for Notifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kinds = self
+            .kinds
+            .iter()
+            .map(|kind| kind.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        f.write_str(&kinds)?;
+        if let Some(urn) = &self.urn {
+            write!(f, "@{}", urn)?;
+        }
+        write!(f, "={}", self.action)
+    }
+}
+
+impl FromStr for Notifier {
+    type Err = String;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        let (filter, action) = src
+            .split_once('=')
+            .ok_or_else(|| "expected <events>[@<urn>]=<action>".to_string())?;
+        let (kinds, urn) = match filter.split_once('@') {
+            Some((kinds, urn)) => {
+                let urn = urn.parse::<Urn>().map_err(|e| e.to_string())?;
+                (kinds, Some(urn.with_path(None)))
+            },
+            None => (filter, None),
+        };
+        let kinds = match kinds {
+            "*" => Kind::ALL
+                .iter()
+                .copied()
+                .filter(|kind| urn.is_some() || *kind != Kind::CobChanged)
+                .collect(),
+            kinds => kinds
+                .split(',')
+                .map(Kind::from_str)
+                .collect::<Result<BTreeSet<_>, _>>()?,
+        };
+        if kinds.contains(&Kind::CobChanged) && urn.is_none() {
+            return Err(
+                "`cob-changed` requires the notifier to be restricted to a urn".to_string(),
+            );
+        }
+
+        Ok(Self {
+            kinds,
+            urn,
+            action: action.parse()?,
+        })
+    }
+}
+
+/// The position up to which the [`inbox`] of a project was read.
+struct Cursor {
+    timestamp: u64,
+    /// The notifications read which were recorded at `timestamp`, as more
+    /// may be recorded within the same second.
+    seen: Vec<Notification>,
+}
+
+impl Cursor {
+    fn new() -> Self {
+        Self {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            seen: vec![],
+        }
+    }
+
+    /// Advance past `notifications`, which were recorded at or after
+    /// `timestamp`, returning the ones not seen before.
+    fn advance(&mut self, notifications: Vec<Notification>) -> Vec<Notification> {
+        let fresh = notifications
+            .into_iter()
+            .filter(|n| n.timestamp > self.timestamp || !self.seen.contains(n))
+            .collect::<Vec<_>>();
+        if let Some(latest) = fresh.iter().map(|n| n.timestamp).max() {
+            if latest > self.timestamp {
+                self.timestamp = latest;
+                self.seen.clear();
+            }
+            self.seen
+                .extend(fresh.iter().filter(|n| n.timestamp == latest).cloned());
+        }
+        fresh
+    }
+}
+
+/// The state needed to derive [`Event`]s from applied gossip.
+pub(crate) struct Derive {
+    identity_updates: bool,
+    /// The collaborative objects of remote peers, for the projects whose
+    /// changes to them are subscribed to.
+    cobs: BTreeMap<Urn, BTreeMap<ext::RefLike, ext::Oid>>,
+    cursors: BTreeMap<Urn, Cursor>,
+}
+
+impl Derive {
+    pub(crate) async fn new<S>(
+        peer: &Peer<S>,
+        filters: &[(&BTreeSet<Kind>, Option<&Urn>)],
+    ) -> anyhow::Result<Self>
+    where
+        S: Signer + Clone,
+    {
+        let mut cobs = BTreeMap::new();
+        for (_, urn) in filters
+            .iter()
+            .filter(|(kinds, _)| kinds.contains(&Kind::CobChanged))
+        {
+            if let Some(urn) = urn {
+                let snapshot = cobs_snapshot(peer, (*urn).clone()).await?;
+                cobs.insert((*urn).clone(), snapshot);
+            }
+        }
+
+        Ok(Self {
+            identity_updates: filters
+                .iter()
+                .any(|(kinds, _)| kinds.contains(&Kind::IdentityUpdate)),
+            cobs,
+            cursors: BTreeMap::new(),
+        })
+    }
+
+    /// The events caused by replicating an update of `urn` from `provider`.
+    pub(crate) async fn events<S>(
+        &mut self,
+        peer: &Peer<S>,
+        urn: Urn,
+        provider: PeerId,
+    ) -> anyhow::Result<Vec<Event>>
+    where
+        S: Signer + Clone,
+    {
+        let mut events = vec![Event::ReplicationCompleted {
+            urn: urn.clone(),
+            provider,
+        }];
+        if self.identity_updates {
+            let cursor = self.cursors.entry(urn.clone()).or_insert_with(Cursor::new);
+            let since = cursor.timestamp;
+            let notifications = {
+                let urn = urn.clone();
+                peer.using_storage(move |storage| inbox::list(storage, Some(&urn), Some(since)))
+                    .await??
+            };
+            events.extend(
+                cursor
+                    .advance(notifications)
+                    .into_iter()
+                    .filter(|n| matches!(n.kind, inbox::Kind::IdentityUpdate))
+                    .map(|n| Event::IdentityUpdate {
+                        urn: urn.clone(),
+                        remote_peer: n.remote_peer,
+                    }),
+            );
+        }
+        if let Some(prev) = self.cobs.get_mut(&urn) {
+            let next = cobs_snapshot(peer, urn.clone()).await?;
+            events.extend(watch::diff(prev, &next).into_iter().filter_map(|update| {
+                let (peer, object) = split_cob(&update.name)?;
+                Some(Event::CobChanged {
+                    urn: urn.clone(),
+                    peer,
+                    object,
+                    old: update.old,
+                    new: update.new,
+                })
+            }));
+            *prev = next;
+        }
+
+        Ok(events)
+    }
+}
+
+#[instrument(name = "notify subroutine", skip(peer, notifiers, webhooks, events))]
+pub async fn routine<S, E>(
+    peer: Peer<S>,
+    notifiers: Vec<Notifier>,
+    webhooks: Vec<Webhook>,
+    events: E,
+) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+    E: Stream<Item = Result<ProtocolEvent, RecvError>> + Send + 'static,
+{
+    let filters = notifiers
+        .iter()
+        .map(|n| (&n.kinds, n.urn.as_ref()))
+        .chain(webhooks.iter().map(|w| (&w.kinds, w.urn.as_ref())))
+        .collect::<Vec<_>>();
+    let mut derive = Derive::new(&peer, &filters).await?;
+    let (deliveries, queued) = mpsc::channel(webhooks::CHANNEL_CAPACITY);
+    let delivering = tokio::spawn(
+        webhooks::Queue::new(&peer.protocol_config().paths, webhooks.clone())?
+            .run(queued)
+            .in_current_span(),
+    );
+
+    futures::pin_mut!(events);
+    loop {
+        let (urn, provider) = match events.next().await {
+            Some(Ok(ProtocolEvent::Gossip(gossip))) => {
+                let Gossip::Put {
+                    provider,
+                    payload,
+                    result,
+                } = gossip.as_ref();
+                match result {
+                    PutResult::Applied(_) => {
+                        (payload.urn.clone().with_path(None), provider.peer_id)
+                    },
+                    _ => continue,
+                }
+            },
+            Some(Ok(_)) => continue,
+            Some(Err(RecvError::Lagged(n))) => {
+                warn!(skipped = n, "notifiers lagging behind protocol events");
+                continue;
+            },
+            Some(Err(RecvError::Closed)) | None => break,
+        };
+        let relevant = filters
+            .iter()
+            .any(|(_, u)| u.map(|u| u.id == urn.id).unwrap_or(true));
+        if !relevant {
+            continue;
+        }
+
+        for event in derive.events(&peer, urn, provider).await? {
+            for notifier in notifiers.iter().filter(|n| n.matches(&event)) {
+                match notify(&notifier.action, &event).await {
+                    Ok(()) => info!(notifier = %notifier, event = %event.kind(), "notified"),
+                    Err(err) => warn!(notifier = %notifier, ?err, "failed to notify"),
+                }
+            }
+            // Waits for the delivery task if it is lagging behind
+            if deliveries.send(event).await.is_err() {
+                warn!("webhook delivery task terminated");
+            }
+        }
+    }
+    drop(deliveries);
+    delivering.await?;
+
+    Ok(())
+}
+
+/// The refs of the collaborative objects of remote peers in the namespace
+/// `urn`.
+async fn cobs_snapshot<S>(
+    peer: &Peer<S>,
+    urn: Urn,
+) -> anyhow::Result<BTreeMap<ext::RefLike, ext::Oid>>
+where
+    S: Signer + Clone,
+{
+    let mut snapshot = peer
+        .using_read_only(move |storage| watch::snapshot(storage, &urn))
+        .await??;
+    snapshot.retain(|name, _| split_cob(name).is_some());
+    Ok(snapshot)
+}
+
+/// Split `refs/remotes/<peer>/cobs/<typename>/<object id>` into the peer and
+/// `<typename>/<object id>`.
+fn split_cob(name: &ext::RefLike) -> Option<(PeerId, ext::RefLike)> {
+    let mut components = name.as_str().splitn(5, '/');
+    match (
+        components.next(),
+        components.next(),
+        components.next(),
+        components.next(),
+        components.next(),
+    ) {
+        (Some("refs"), Some("remotes"), Some(peer), Some("cobs"), Some(object)) => {
+            Some((peer.parse().ok()?, ext::RefLike::try_from(object).ok()?))
+        },
+        _ => None,
+    }
+}
+
+async fn notify(action: &Action, event: &Event) -> anyhow::Result<()> {
+    let payload = serde_json::to_vec(event)?;
+    match action {
+        Action::Exec(command) => {
+            let mut cmd = Command::new("sh");
+            cmd.arg("-c")
+                .arg(command)
+                .env("LINKD_EVENT", event.kind().to_string())
+                .env("LINKD_URN", event.urn().to_string());
+            run(cmd, &payload)
+                .await
+                .map_err(|e| anyhow!("{} failed: {}", action, e))
+        },
+        Action::Webhook(url) => post(url, &[], &payload).await,
+    }
+}
+
+/// POST the JSON `body` to `url` using `curl`, along with the given extra
+/// `headers`.
+pub(crate) async fn post(url: &str, headers: &[(&str, String)], body: &[u8]) -> anyhow::Result<()> {
+    let mut cmd = Command::new("curl");
+    cmd.args(&[
+        "--silent",
+        "--show-error",
+        "--fail",
+        "--request",
+        "POST",
+        "--header",
+        "Content-Type: application/json",
+        "--data-binary",
+        "@-",
+    ]);
+    for (name, value) in headers {
+        cmd.arg("--header").arg(format!("{}: {}", name, value));
+    }
+    cmd.arg(url);
+
+    run(cmd, body).await.map_err(|e| {
+        // The URL may be echoed back, including credentials.
+        anyhow!(
+            "POST to {} failed: {}",
+            redact(url),
+            e.to_string().replace(url, &redact(url))
+        )
+    })
+}
+
+/// Run `cmd` with `input` on its standard input, failing if it exits
+/// unsuccessfully or takes longer than [`NOTIFY_TIMEOUT`].
+async fn run(mut cmd: Command, input: &[u8]) -> anyhow::Result<()> {
+    cmd.stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    let mut child = cmd.spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // Commands are free to ignore their input
+        match stdin.write_all(input).await {
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {},
+            res => res?,
+        }
+    }
+    let out = timeout(NOTIFY_TIMEOUT, child.wait_with_output()).await??;
+    if !out.status.success() {
+        bail!(
+            "{}: {}",
+            out.status,
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+
+    Ok(())
+}
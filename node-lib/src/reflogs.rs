@@ -0,0 +1,40 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Expire reflog entries periodically, such that long-lived nodes don't
+//! accumulate them without bounds. See [`librad::git::storage::reflog`].
+
+use std::time::Duration;
+
+use tokio::time::interval;
+use tracing::{info, instrument, warn};
+
+use librad::{git::storage::reflog, net::peer::Peer, Signer};
+
+#[instrument(name = "reflogs subroutine", skip(peer))]
+pub async fn routine<S>(
+    peer: Peer<S>,
+    every: Duration,
+    retention: reflog::Retention,
+) -> anyhow::Result<()>
+where
+    S: Signer + Clone,
+{
+    let mut tick = interval(every);
+    loop {
+        tick.tick().await;
+        match peer
+            .using_storage(move |storage| reflog::expire(storage, &retention))
+            .await
+        {
+            Ok(Ok(expired)) if expired == reflog::Expired::default() => {},
+            Ok(Ok(reflog::Expired { entries, reflogs })) => {
+                info!(entries, reflogs, "expired reflogs")
+            },
+            Ok(Err(err)) => warn!(?err, "failed to expire reflogs"),
+            Err(err) => warn!(?err, "failed to obtain storage"),
+        }
+    }
+}
@@ -0,0 +1,157 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Restarting of failed subroutines.
+//!
+//! A subroutine spawned via [`Supervisor::spawn`] is restarted whenever it
+//! returns an error or panics, after a delay which grows exponentially with
+//! the number of recent failures. If it fails more than
+//! [`Policy::max_restarts`] times within [`Policy::window`], the supervisor
+//! gives up and returns the last error, which shuts down the node. An
+//! [`Event`] is emitted for every restart, and when giving up.
+
+use std::{any::Any, collections::VecDeque, future::Future, time::Duration};
+
+use tokio::{
+    sync::broadcast,
+    task::JoinHandle,
+    time::{sleep, Instant},
+};
+use tracing::{error, warn};
+
+#[derive(Clone, Copy, Debug)]
+pub struct Policy {
+    /// The number of restarts permitted within `window`.
+    pub max_restarts: usize,
+    /// The period over which failures are counted.
+    pub window: Duration,
+    /// The delay before the first restart.
+    pub backoff_base: Duration,
+    /// The upper bound of the delay between restarts.
+    pub backoff_max: Duration,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self {
+            max_restarts: 5,
+            window: Duration::from_secs(5 * 60),
+            backoff_base: Duration::from_secs(1),
+            backoff_max: Duration::from_secs(60),
+        }
+    }
+}
+
+impl Policy {
+    /// The delay before restarting after the given number of recent failures.
+    pub fn backoff(&self, failures: u32) -> Duration {
+        self.backoff_base
+            .checked_mul(2u32.saturating_pow(failures.saturating_sub(1)))
+            .map_or(self.backoff_max, |delay| delay.min(self.backoff_max))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Event {
+    /// The subroutine `task` failed, and is restarted after `delay`.
+    Restarting {
+        task: &'static str,
+        failures: usize,
+        delay: Duration,
+        reason: String,
+    },
+    /// The subroutine `task` failed too often, and is not restarted.
+    GaveUp { task: &'static str, reason: String },
+}
+
+#[derive(Clone)]
+pub struct Supervisor {
+    policy: Policy,
+    events: broadcast::Sender<Event>,
+}
+
+impl Supervisor {
+    pub fn new(policy: Policy) -> Self {
+        let (events, _) = broadcast::channel(32);
+        Self { policy, events }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.events.subscribe()
+    }
+
+    /// Spawn the future created by `routine`, and create and spawn it again
+    /// whenever it fails.
+    ///
+    /// The returned task completes once the subroutine completes
+    /// successfully, or when the supervisor gave up on it.
+    pub fn spawn<F, Fut>(&self, task: &'static str, routine: F) -> JoinHandle<anyhow::Result<()>>
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        tokio::spawn(self.clone().supervise(task, routine))
+    }
+
+    async fn supervise<F, Fut>(self, task: &'static str, mut routine: F) -> anyhow::Result<()>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        let mut failures = VecDeque::new();
+        loop {
+            let err = match tokio::spawn(routine()).await {
+                Ok(Ok(())) => return Ok(()),
+                Ok(Err(err)) => err,
+                Err(err) if err.is_panic() => {
+                    anyhow::anyhow!("panicked: {}", panic_message(err.into_panic()))
+                },
+                Err(err) => return Err(err.into()),
+            };
+            let reason = format!("{:#}", err);
+
+            let now = Instant::now();
+            failures.push_back(now);
+            while failures.front().map_or(false, |failed| {
+                now.duration_since(*failed) > self.policy.window
+            }) {
+                failures.pop_front();
+            }
+
+            if failures.len() > self.policy.max_restarts {
+                error!(task, %reason, "giving up after repeated failures");
+                self.events.send(Event::GaveUp { task, reason }).ok();
+                return Err(err.context(format!(
+                    "{} failed {} times within {:?}",
+                    task,
+                    failures.len(),
+                    self.policy.window
+                )));
+            }
+
+            let delay = self.policy.backoff(failures.len() as u32);
+            warn!(task, %reason, ?delay, "restarting failed subroutine");
+            self.events
+                .send(Event::Restarting {
+                    task,
+                    failures: failures.len(),
+                    delay,
+                    reason,
+                })
+                .ok();
+            sleep(delay).await;
+        }
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(msg) => *msg,
+        Err(payload) => payload.downcast_ref::<&str>().map_or_else(
+            || "<non-string payload>".to_owned(),
+            |msg| (*msg).to_owned(),
+        ),
+    }
+}
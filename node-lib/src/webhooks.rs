@@ -0,0 +1,566 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Webhooks registered in the profile config, ie. the config of the monorepo.
+//!
+//! A [`Webhook`] is stored as
+//!
+//! ```text
+//! [webhook "ci"]
+//!     url = https://ci.example.com/hooks/radicle
+//!     secret = s3cr3t
+//!     event = replication-completed
+//!     event = cob-changed
+//!     urn = rad:git:hnrk...
+//! ```
+//!
+//! and managed using `linkd webhook`. Whenever an [`Event`] the webhook is
+//! subscribed to occurs, it is POSTed as JSON to the URL. The body is signed
+//! using HMAC-SHA256 keyed with the secret, and the signature is sent in the
+//! [`SIGNATURE_HEADER`], such that the receiver can verify the payload was
+//! sent by this node. See [`sign`].
+//!
+//! Deliveries are made by a task of their own, so slow webhooks don't hold up
+//! the node. Failed deliveries are retried with exponential backoff. Pending
+//! deliveries are stored alongside the monorepo, and resumed when the node
+//! restarts. After [`MAX_ATTEMPTS`], or if more than [`MAX_PENDING`]
+//! deliveries are pending, they are appended to the dead-letter queue stored
+//! alongside the monorepo, from where they can be inspected and redelivered
+//! using `linkd webhook`.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    fs::{self, OpenOptions},
+    io::{self, BufRead as _, BufReader, Write as _},
+    os::unix::io::AsRawFd as _,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use hmac::{Hmac, Mac as _, NewMac as _};
+use nix::fcntl::{flock, FlockArg};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use thiserror::Error;
+use tokio::{
+    select,
+    sync::mpsc,
+    time::{interval, Instant},
+};
+use tracing::{info, warn};
+
+use librad::{git::Urn, git_ext::is_not_found_err, paths::Paths};
+
+use crate::{
+    mirror::{backoff, redact},
+    notify::{post, Event, Kind},
+};
+
+const DEAD_LETTERS_FILE: &str = "webhooks-dead-letters.jsonl";
+const PENDING_FILE: &str = "webhooks-pending.jsonl";
+
+/// The header carrying the signature of the body, as `sha256=<hex>`.
+pub const SIGNATURE_HEADER: &str = "X-Radicle-Signature";
+/// The header carrying the [`Kind`] of the event.
+pub const EVENT_HEADER: &str = "X-Radicle-Event";
+/// The header carrying the id of the [`Delivery`], which stays the same
+/// across retries.
+pub const DELIVERY_HEADER: &str = "X-Radicle-Delivery";
+
+/// How many times a delivery is attempted before it is dead-lettered.
+pub const MAX_ATTEMPTS: u32 = 8;
+
+/// How many deliveries may be pending retry before further ones are
+/// dead-lettered right away.
+pub const MAX_PENDING: usize = 1024;
+
+/// How often pending deliveries are checked.
+const TICK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How many events may be waiting for the delivery task, see [`Queue::run`].
+pub(crate) const CHANNEL_CAPACITY: usize = 256;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("invalid webhook name `{0}`")]
+    InvalidName(String),
+
+    #[error("webhook `{name}` is malformed: {reason}")]
+    Malformed { name: String, reason: String },
+
+    #[error("`cob-changed` requires webhook `{0}` to be restricted to a urn")]
+    CobsUnrestricted(String),
+
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+/// A webhook, see the module documentation.
+#[derive(Clone, Eq, PartialEq)]
+pub struct Webhook {
+    /// The name of the webhook, consisting of alphanumeric characters, `-`
+    /// and `_`.
+    pub name: String,
+    pub url: String,
+    pub secret: String,
+    pub kinds: BTreeSet<Kind>,
+    /// The project to restrict the webhook to, if any.
+    pub urn: Option<Urn>,
+}
+
+impl Webhook {
+    /// `true` if `event` should be delivered to the webhook.
+    pub fn matches(&self, event: &Event) -> bool {
+        self.kinds.contains(&event.kind())
+            && self
+                .urn
+                .as_ref()
+                .map(|urn| urn.id == event.urn().id)
+                .unwrap_or(true)
+    }
+}
+
+impl fmt::Debug for Webhook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Webhook")
+            .field("name", &self.name)
+            .field("url", &redact(&self.url))
+            .field("secret", &"***")
+            .field("kinds", &self.kinds)
+            .field("urn", &self.urn)
+            .finish()
+    }
+}
+
+/// A POST of an [`Event`] to a [`Webhook`].
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Delivery {
+    pub id: String,
+    /// The name of the [`Webhook`].
+    pub webhook: String,
+    pub event: String,
+    /// The JSON encoded [`Event`].
+    pub body: String,
+    pub attempts: u32,
+    /// The error of the last failed attempt.
+    pub last_error: Option<String>,
+}
+
+/// The HMAC-SHA256 of `body` keyed with `secret`, as sent in the
+/// [`SIGNATURE_HEADER`], ie. `sha256=<hex>`.
+pub fn sign(secret: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    let hex = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    format!("sha256={}", hex)
+}
+
+fn config_path(paths: &Paths) -> PathBuf {
+    paths.git_dir().join("config")
+}
+
+fn dead_letters_path(paths: &Paths) -> PathBuf {
+    paths.git_dir().join(DEAD_LETTERS_FILE)
+}
+
+fn pending_path(paths: &Paths) -> PathBuf {
+    paths.git_dir().join(PENDING_FILE)
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+}
+
+/// Register `webhook`, replacing any webhook of the same name.
+pub fn add(paths: &Paths, webhook: &Webhook) -> Result<(), Error> {
+    if !is_valid_name(&webhook.name) {
+        return Err(Error::InvalidName(webhook.name.clone()));
+    }
+    if webhook.kinds.contains(&Kind::CobChanged) && webhook.urn.is_none() {
+        return Err(Error::CobsUnrestricted(webhook.name.clone()));
+    }
+
+    remove(paths, &webhook.name)?;
+    let mut config = git2::Config::open(&config_path(paths))?;
+    let key = |field: &str| format!("webhook.{}.{}", webhook.name, field);
+    config.set_str(&key("url"), &webhook.url)?;
+    config.set_str(&key("secret"), &webhook.secret)?;
+    for kind in &webhook.kinds {
+        config.set_multivar(&key("event"), "^$", &kind.to_string())?;
+    }
+    if let Some(urn) = &webhook.urn {
+        config.set_str(&key("urn"), &urn.to_string())?;
+    }
+
+    Ok(())
+}
+
+/// Remove the webhook `name`.
+///
+/// `true` is returned if it was registered before.
+pub fn remove(paths: &Paths, name: &str) -> Result<bool, Error> {
+    if !is_valid_name(name) {
+        return Err(Error::InvalidName(name.to_owned()));
+    }
+
+    let mut config = git2::Config::open(&config_path(paths))?;
+    let mut removed = false;
+    for field in &["url", "secret", "urn", "event"] {
+        let key = format!("webhook.{}.{}", name, field);
+        match config.remove_multivar(&key, ".*") {
+            Ok(()) => removed = true,
+            Err(e) if is_not_found_err(&e) => {},
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Ok(removed)
+}
+
+/// The registered webhooks, ordered by name.
+pub fn list(paths: &Paths) -> Result<Vec<Webhook>, Error> {
+    let config = git2::Config::open(&config_path(paths))?.snapshot()?;
+    let mut names = BTreeSet::new();
+    let entries = config.entries(Some(r"^webhook\..*\.url$"))?;
+    for entry in &entries {
+        if let Some(name) = entry?.name().and_then(|key| {
+            key.strip_prefix("webhook.")
+                .and_then(|key| key.strip_suffix(".url"))
+        }) {
+            names.insert(name.to_owned());
+        }
+    }
+
+    let mut webhooks = Vec::with_capacity(names.len());
+    for name in names {
+        let malformed = |reason: String| Error::Malformed {
+            name: name.clone(),
+            reason,
+        };
+        let key = |field: &str| format!("webhook.{}.{}", name, field);
+        let secret = match config.get_string(&key("secret")) {
+            Ok(secret) => secret,
+            Err(e) if is_not_found_err(&e) => return Err(malformed("missing secret".to_owned())),
+            Err(e) => return Err(e.into()),
+        };
+        let mut kinds = BTreeSet::new();
+        let events = config.multivar(&key("event"), None)?;
+        for entry in &events {
+            if let Some(kind) = entry?.value() {
+                kinds.insert(kind.parse().map_err(malformed)?);
+            }
+        }
+        let urn = match config.get_string(&key("urn")) {
+            Ok(urn) => Some(urn.parse::<Urn>().map_err(|e| malformed(e.to_string()))?),
+            Err(e) if is_not_found_err(&e) => None,
+            Err(e) => return Err(e.into()),
+        };
+
+        webhooks.push(Webhook {
+            url: config.get_string(&key("url"))?,
+            secret,
+            kinds,
+            urn,
+            name,
+        });
+    }
+
+    Ok(webhooks)
+}
+
+/// The deliveries which failed [`MAX_ATTEMPTS`] times, oldest first.
+pub fn dead_letters(paths: &Paths) -> Result<Vec<Delivery>, Error> {
+    read_deliveries(&dead_letters_path(paths))
+}
+
+/// Read the deliveries stored in `path`, one JSON object per line.
+fn read_deliveries(path: &Path) -> Result<Vec<Delivery>, Error> {
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut deliveries = Vec::new();
+    for line in BufReader::new(file).lines() {
+        match serde_json::from_str(&line?) {
+            Ok(delivery) => deliveries.push(delivery),
+            Err(e) => warn!(err = %e, path = %path.display(), "skipping malformed delivery"),
+        }
+    }
+
+    Ok(deliveries)
+}
+
+/// Replace the contents of `path` with `deliveries` atomically.
+fn write_deliveries<'a, I>(path: &Path, deliveries: I) -> Result<(), Error>
+where
+    I: IntoIterator<Item = &'a Delivery>,
+{
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+    for delivery in deliveries {
+        serde_json::to_writer(&mut tmp, delivery)?;
+        tmp.write_all(b"\n")?;
+    }
+    tmp.persist(path).map_err(|e| e.error)?;
+    Ok(())
+}
+
+/// Take the lock of the dead-letter queue at `path`, which is held until the
+/// returned file is dropped.
+///
+/// The lock is taken on a file of its own, as [`redeliver`] replaces the
+/// queue.
+fn lock_dead_letters(path: &Path) -> Result<fs::File, Error> {
+    let lock = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .open(path.with_extension("lock"))?;
+    flock(lock.as_raw_fd(), FlockArg::LockExclusive)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    Ok(lock)
+}
+
+fn dead_letter(path: &Path, delivery: &Delivery) -> Result<(), Error> {
+    let _lock = lock_dead_letters(path)?;
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    let mut line = serde_json::to_vec(delivery)?;
+    line.push(b'\n');
+    file.write_all(&line)?;
+    Ok(())
+}
+
+/// Attempt every dead-lettered delivery once more, using the current
+/// configuration of its webhook. Deliveries which fail again, or whose
+/// webhook is no longer registered, stay in the queue.
+///
+/// The node may dead-letter deliveries while this is running. Those are kept,
+/// as the queue is only locked while reading and updating it, not while
+/// delivering.
+///
+/// Returns the number of successful deliveries, and of those remaining.
+pub async fn redeliver(paths: &Paths) -> Result<(usize, usize), Error> {
+    let webhooks = list(paths)?;
+    let path = dead_letters_path(paths);
+    let queued = {
+        let _lock = lock_dead_letters(&path)?;
+        read_deliveries(&path)?
+    };
+
+    let mut delivered = BTreeSet::new();
+    let mut failed = BTreeMap::new();
+    for mut delivery in queued {
+        let webhook = match webhooks.iter().find(|w| w.name == delivery.webhook) {
+            Some(webhook) => webhook,
+            None => continue,
+        };
+        match attempt(webhook, &delivery).await {
+            Ok(()) => {
+                delivered.insert(delivery.id);
+            },
+            Err(err) => {
+                delivery.attempts = delivery.attempts.saturating_add(1);
+                delivery.last_error = Some(err.to_string());
+                failed.insert(delivery.id.clone(), delivery);
+            },
+        }
+    }
+
+    let _lock = lock_dead_letters(&path)?;
+    let remaining = read_deliveries(&path)?
+        .into_iter()
+        .filter(|delivery| !delivered.contains(&delivery.id))
+        .map(|delivery| failed.remove(&delivery.id).unwrap_or(delivery))
+        .collect::<Vec<_>>();
+    write_deliveries(&path, &remaining)?;
+
+    Ok((delivered.len(), remaining.len()))
+}
+
+async fn attempt(webhook: &Webhook, delivery: &Delivery) -> anyhow::Result<()> {
+    let headers = [
+        (EVENT_HEADER, delivery.event.clone()),
+        (DELIVERY_HEADER, delivery.id.clone()),
+        (
+            SIGNATURE_HEADER,
+            sign(webhook.secret.as_bytes(), delivery.body.as_bytes()),
+        ),
+    ];
+    post(&webhook.url, &headers, delivery.body.as_bytes()).await
+}
+
+/// The pending deliveries of the node.
+pub(crate) struct Queue {
+    dead_letters: PathBuf,
+    path: PathBuf,
+    webhooks: Vec<Webhook>,
+    pending: Vec<(Instant, Delivery)>,
+    sequence: u64,
+}
+
+impl Queue {
+    /// Load the deliveries left pending by a previous run of the node, which
+    /// are due right away.
+    ///
+    /// Deliveries to webhooks which are no longer registered are
+    /// dead-lettered.
+    pub fn new(paths: &Paths, webhooks: Vec<Webhook>) -> Result<Self, Error> {
+        let path = pending_path(paths);
+        let dead_letters = dead_letters_path(paths);
+        let now = Instant::now();
+        let mut pending = Vec::new();
+        for delivery in read_deliveries(&path)? {
+            if webhooks.iter().any(|w| w.name == delivery.webhook) {
+                pending.push((now, delivery));
+            } else {
+                warn!(webhook = %delivery.webhook, delivery = %delivery.id, "webhook no longer registered");
+                dead_letter(&dead_letters, &delivery)?;
+            }
+        }
+        let queue = Self {
+            dead_letters,
+            path,
+            webhooks,
+            pending,
+            sequence: 0,
+        };
+        queue.persist()?;
+
+        Ok(queue)
+    }
+
+    /// Deliver the events received on `events`, until the sending half is
+    /// dropped.
+    pub async fn run(mut self, mut events: mpsc::Receiver<Event>) {
+        let mut tick = interval(TICK_INTERVAL);
+        loop {
+            select! {
+                event = events.recv() => match event {
+                    Some(event) => self.push(&event),
+                    None => break,
+                },
+                _ = tick.tick() => self.deliver_due().await,
+            }
+        }
+    }
+
+    /// Schedule the delivery of `event` to the webhooks subscribed to it.
+    fn push(&mut self, event: &Event) {
+        let matching = self
+            .webhooks
+            .iter()
+            .filter(|w| w.matches(event))
+            .map(|w| w.name.clone())
+            .collect::<Vec<_>>();
+        if matching.is_empty() {
+            return;
+        }
+
+        let body = match serde_json::to_string(event) {
+            Ok(body) => body,
+            Err(err) => {
+                warn!(?err, "failed to encode event");
+                return;
+            },
+        };
+        for webhook in matching {
+            self.sequence += 1;
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis())
+                .unwrap_or_default();
+            let delivery = Delivery {
+                id: format!("{}-{}", now, self.sequence),
+                webhook,
+                event: event.kind().to_string(),
+                body: body.clone(),
+                attempts: 0,
+                last_error: None,
+            };
+            if self.pending.len() < MAX_PENDING {
+                self.pending.push((Instant::now(), delivery));
+            } else {
+                warn!(webhook = %delivery.webhook, delivery = %delivery.id, "too many pending deliveries");
+                self.give_up(Delivery {
+                    last_error: Some("too many pending deliveries".to_owned()),
+                    ..delivery
+                });
+            }
+        }
+        if let Err(err) = self.persist() {
+            warn!(?err, "failed to store pending deliveries")
+        }
+    }
+
+    /// Attempt the deliveries which are due, rescheduling or dead-lettering
+    /// the ones which fail.
+    async fn deliver_due(&mut self) {
+        let now = Instant::now();
+        if self.pending.iter().all(|(due, _)| *due > now) {
+            return;
+        }
+
+        let mut pending = Vec::with_capacity(self.pending.len());
+        for (due, mut delivery) in std::mem::take(&mut self.pending) {
+            let webhook = match self.webhooks.iter().find(|w| w.name == delivery.webhook) {
+                Some(webhook) if due <= now => webhook,
+                _ => {
+                    pending.push((due, delivery));
+                    continue;
+                },
+            };
+            match attempt(webhook, &delivery).await {
+                Ok(()) => info!(webhook = %webhook.name, delivery = %delivery.id, "delivered"),
+                Err(err) => {
+                    delivery.attempts += 1;
+                    delivery.last_error = Some(err.to_string());
+                    if delivery.attempts < MAX_ATTEMPTS {
+                        warn!(webhook = %webhook.name, delivery = %delivery.id, ?err, "delivery failed, retrying");
+                        pending.push((now + backoff(delivery.attempts), delivery));
+                    } else {
+                        warn!(webhook = %webhook.name, delivery = %delivery.id, ?err, "delivery failed, giving up");
+                        self.give_up(delivery);
+                    }
+                },
+            }
+        }
+        self.pending = pending;
+        if let Err(err) = self.persist() {
+            warn!(?err, "failed to store pending deliveries")
+        }
+    }
+
+    fn give_up(&self, delivery: Delivery) {
+        if let Err(err) = dead_letter(&self.dead_letters, &delivery) {
+            warn!(?err, "failed to record dead letter")
+        }
+    }
+
+    /// Store the pending deliveries, so they survive a restart of the node.
+    fn persist(&self) -> Result<(), Error> {
+        write_deliveries(
+            &self.path,
+            self.pending.iter().map(|(_, delivery)| delivery),
+        )
+    }
+}
@@ -0,0 +1,47 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{convert::TryFrom as _, path::PathBuf};
+
+use structopt::StructOpt;
+
+use librad::{
+    git::Urn,
+    git_ext::{OneLevel, RefLike},
+    PeerId,
+};
+
+/// Create a working copy of a project found in the monorepo.
+#[derive(Debug, StructOpt)]
+pub struct Checkout {
+    /// the project to check out
+    #[structopt(long)]
+    pub urn: Urn,
+    /// check out the view of this peer, instead of your own. The branch is
+    /// published to your own view.
+    #[structopt(long)]
+    pub peer: Option<PeerId>,
+    /// the branch to check out, if none is provided then the default branch
+    /// of the project is used
+    #[structopt(long, parse(try_from_str = parse_branch))]
+    pub branch: Option<OneLevel>,
+    /// the directory to create the working copy in
+    #[structopt(parse(from_os_str))]
+    pub path: PathBuf,
+}
+
+/// Update the remotes of a working copy, and fetch from them.
+#[derive(Debug, StructOpt)]
+pub struct Refresh {
+    /// the working copy to refresh
+    #[structopt(parse(from_os_str), default_value = ".")]
+    pub path: PathBuf,
+}
+
+fn parse_branch(s: &str) -> Result<OneLevel, String> {
+    RefLike::try_from(s)
+        .map(OneLevel::from)
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,74 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use thrussh_agent::client::ClientStream;
+
+use librad::{
+    git::{
+        checkout::{self, Options, View},
+        local::transport,
+    },
+    profile::Profile,
+};
+use rad_clib::keys;
+
+use super::args::*;
+
+pub async fn checkout<S>(
+    Checkout {
+        urn,
+        peer,
+        branch,
+        path,
+    }: Checkout,
+) -> anyhow::Result<()>
+where
+    S: ClientStream + Unpin + 'static,
+{
+    let (profile, settings) = settings::<S>().await?;
+    let repo = checkout::checkout(
+        profile.paths(),
+        settings,
+        &urn,
+        Options {
+            path,
+            view: peer.map_or(View::Local, View::Peer),
+            branch,
+        },
+    )?;
+    println!(
+        "checked out `{}` in {}",
+        urn,
+        repo.workdir().unwrap_or_else(|| repo.path()).display()
+    );
+
+    Ok(())
+}
+
+pub async fn refresh<S>(Refresh { path }: Refresh) -> anyhow::Result<()>
+where
+    S: ClientStream + Unpin + 'static,
+{
+    let (profile, settings) = settings::<S>().await?;
+    let repo = git2::Repository::open(path)?;
+    for (name, oid) in checkout::refresh(profile.paths(), settings, &repo)? {
+        println!("{}\t{}", oid, name);
+    }
+
+    Ok(())
+}
+
+async fn settings<S>() -> anyhow::Result<(Profile, transport::Settings)>
+where
+    S: ClientStream + Unpin + 'static,
+{
+    let profile = Profile::load()?;
+    let signer = keys::signer_ssh::<S>(&profile).await?;
+    let settings = transport::Settings {
+        paths: profile.paths().clone(),
+        signer,
+    };
+    Ok((profile, settings))
+}
@@ -0,0 +1,535 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! A typed client of the control socket of a running node.
+//!
+//! The node accepts connections on a Unix socket, by default at
+//! [`socket_path`] of its profile. The protocol is line-based: every
+//! [`Request`] is sent as a single line of JSON, which the node answers with
+//! a single [`Response`] line. [`Request::Subscribe`] is answered with
+//! [`Response::Subscribed`], after which the node only sends [`Event`]s, one
+//! per line, until either side closes the connection.
+//!
+//! Subscriptions are filtered by the node, see [`Filter`]. A client which
+//! doesn't keep up with the events isn't waited for: once the node has
+//! buffered too many events for it, the oldest ones are dropped, and the
+//! client is told how many by an [`Event::Lagged`].
+//!
+//! [`Client`] takes care of the framing, so tools like editor plugins or bots
+//! can talk to the node without shelling out to the CLI:
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), rad_clib::control::Error> {
+//! use rad_clib::control::{socket_path, Client, Filter};
+//!
+//! let profile = librad::profile::Profile::load().unwrap();
+//! let mut client = Client::connect(socket_path(profile.paths())).await?;
+//! println!("{:?}", client.status().await?);
+//!
+//! let mut events = client.subscribe(Filter::default()).await?;
+//! while let Some(event) = events.next().await? {
+//!     println!("{:?}", event);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    io,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncBufReadExt as _, AsyncWrite, AsyncWriteExt as _, BufReader, Lines},
+    net::{
+        unix::{OwnedReadHalf, OwnedWriteHalf},
+        UnixStream,
+    },
+};
+
+use librad::{git::Urn, git_ext as ext, paths::Paths, PeerId};
+
+/// The file name of the control socket in the git directory of a profile.
+pub const SOCKET_NAME: &str = "linkd.sock";
+
+/// The default location of the control socket of the node running for the
+/// profile with the given `paths`.
+pub fn socket_path(paths: &Paths) -> PathBuf {
+    paths.git_dir().join(SOCKET_NAME)
+}
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error("failed to connect to the control socket at {path}")]
+    Connect {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("the node closed the connection")]
+    Closed,
+
+    #[error("the node failed to handle the request: {0}")]
+    Node(String),
+
+    #[error("unexpected response from the node: {0:?}")]
+    Unexpected(Response),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+/// A request to the node.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "method")]
+pub enum Request {
+    /// Ask for the [`Status`] of the node.
+    Status,
+    /// Replicate `urn` from the peer `from`, which is reachable at `addrs` if
+    /// the node doesn't know of any addresses of it.
+    #[serde(rename_all = "camelCase")]
+    Replicate {
+        urn: Urn,
+        from: PeerId,
+        #[serde(default)]
+        addrs: Vec<SocketAddr>,
+    },
+    /// Track `peer` for `urn`.
+    #[serde(rename_all = "camelCase")]
+    Track { urn: Urn, peer: PeerId },
+    /// Stop tracking `peer` for `urn`.
+    #[serde(rename_all = "camelCase")]
+    Untrack { urn: Urn, peer: PeerId },
+    /// Connect to `seed`, which is reachable at `addrs` if the node doesn't
+    /// know of any addresses of it, and ask it to fetch `urn` from the node
+    /// right away instead of waiting for gossip.
+    #[serde(rename_all = "camelCase")]
+    Push {
+        urn: Urn,
+        seed: PeerId,
+        #[serde(default)]
+        addrs: Vec<SocketAddr>,
+    },
+    /// Turn the connection into a stream of the [`Event`]s matching
+    /// `filter`.
+    #[serde(rename_all = "camelCase")]
+    Subscribe {
+        #[serde(default)]
+        filter: Filter,
+    },
+}
+
+/// The response of the node to a [`Request`].
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum Response {
+    Status(Status),
+    Replicated(Replicated),
+    Pushed(Pushed),
+    /// Whether tracking the peer changed anything, ie. it wasn't tracked
+    /// before.
+    #[serde(rename_all = "camelCase")]
+    Tracked {
+        changed: bool,
+    },
+    /// Whether untracking the peer changed anything, ie. it was tracked
+    /// before.
+    #[serde(rename_all = "camelCase")]
+    Untracked {
+        changed: bool,
+    },
+    /// The node will send [`Event`]s from now on.
+    Subscribed,
+    /// The request could not be handled.
+    #[serde(rename_all = "camelCase")]
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Status {
+    pub peer_id: PeerId,
+    pub connected_peers: Vec<PeerId>,
+    pub membership_active: usize,
+    pub membership_passive: usize,
+}
+
+/// The outcome of a [`Request::Replicate`].
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Replicated {
+    /// The refs which were updated, and the objects they now point to.
+    pub updated_tips: BTreeMap<ext::RefLike, ext::Oid>,
+    /// Whether the [`Urn`] was not present on the node before.
+    pub cloned: bool,
+}
+
+/// The outcome of a [`Request::Push`].
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Pushed {
+    /// The number of refs the seed updated.
+    pub updated: usize,
+    /// The tip of the `rad/signed_refs` of the node, if it has any for the
+    /// [`Urn`].
+    pub local: Option<ext::Oid>,
+    /// The tip of the `rad/signed_refs` of the node the seed has after
+    /// fetching, if any.
+    pub seed: Option<ext::Oid>,
+}
+
+impl Pushed {
+    /// `true` if the seed has the signed refs announced by the node.
+    pub fn synced(&self) -> bool {
+        self.local.is_some() && self.local == self.seed
+    }
+}
+
+/// An event on the node, sent after [`Request::Subscribe`].
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum Event {
+    /// An update announced by `provider` was replicated.
+    #[serde(rename_all = "camelCase")]
+    ReplicationCompleted { urn: Urn, provider: PeerId },
+    /// The identity document was updated by the delegates, and the local view
+    /// may need to be confirmed.
+    #[serde(rename_all = "camelCase")]
+    IdentityUpdate { urn: Urn, remote_peer: PeerId },
+    /// The collaborative object `<typename>/<object id>` of `peer` changed
+    /// from `old` to `new`, where `None` denotes its absence.
+    ///
+    /// Only sent to subscriptions restricted to a set of [`Urn`]s, as
+    /// finding those changes requires keeping track of the objects of each
+    /// project.
+    #[serde(rename_all = "camelCase")]
+    CobChanged {
+        urn: Urn,
+        peer: PeerId,
+        object: ext::RefLike,
+        old: Option<ext::Oid>,
+        new: Option<ext::Oid>,
+    },
+    /// `peer` was tracked for `urn` via the control socket.
+    #[serde(rename_all = "camelCase")]
+    Tracked { urn: Urn, peer: PeerId },
+    /// `peer` was untracked for `urn` via the control socket.
+    #[serde(rename_all = "camelCase")]
+    Untracked { urn: Urn, peer: PeerId },
+    /// `peer` joined the active membership view of the node.
+    #[serde(rename_all = "camelCase")]
+    PeerJoined { peer: PeerId },
+    /// `peer` was evicted from the membership views of the node.
+    #[serde(rename_all = "camelCase")]
+    PeerLeft { peer: PeerId },
+    /// Nothing was received from `peer` at `addr` within the idle timeout,
+    /// so the connection to it was closed.
+    #[serde(rename_all = "camelCase")]
+    PeerDead { peer: PeerId, addr: SocketAddr },
+    /// The node connected to `peer` at `addr`, where it previously found
+    /// `pinned`. If `refused`, the connection was closed.
+    #[serde(rename_all = "camelCase")]
+    PeerMismatch {
+        peer: PeerId,
+        addr: SocketAddr,
+        pinned: PeerId,
+        refused: bool,
+    },
+    /// The subscriber fell behind, and `skipped` events were dropped. Sent
+    /// regardless of the [`Filter`].
+    #[serde(rename_all = "camelCase")]
+    Lagged { skipped: u64 },
+}
+
+impl Event {
+    /// The kind of the event, or `None` for [`Event::Lagged`].
+    pub fn kind(&self) -> Option<Kind> {
+        match self {
+            Self::ReplicationCompleted { .. } => Some(Kind::ReplicationCompleted),
+            Self::IdentityUpdate { .. } => Some(Kind::IdentityUpdate),
+            Self::CobChanged { .. } => Some(Kind::CobChanged),
+            Self::Tracked { .. } => Some(Kind::Tracked),
+            Self::Untracked { .. } => Some(Kind::Untracked),
+            Self::PeerJoined { .. } => Some(Kind::PeerJoined),
+            Self::PeerLeft { .. } => Some(Kind::PeerLeft),
+            Self::PeerDead { .. } => Some(Kind::PeerDead),
+            Self::PeerMismatch { .. } => Some(Kind::PeerMismatch),
+            Self::Lagged { .. } => None,
+        }
+    }
+
+    /// The project the event is about, if any.
+    pub fn urn(&self) -> Option<&Urn> {
+        match self {
+            Self::ReplicationCompleted { urn, .. }
+            | Self::IdentityUpdate { urn, .. }
+            | Self::CobChanged { urn, .. }
+            | Self::Tracked { urn, .. }
+            | Self::Untracked { urn, .. } => Some(urn),
+            Self::PeerJoined { .. }
+            | Self::PeerLeft { .. }
+            | Self::PeerDead { .. }
+            | Self::PeerMismatch { .. }
+            | Self::Lagged { .. } => None,
+        }
+    }
+
+    /// The remote peer the event is about, if any.
+    pub fn peer(&self) -> Option<&PeerId> {
+        match self {
+            Self::ReplicationCompleted { provider: peer, .. }
+            | Self::IdentityUpdate {
+                remote_peer: peer, ..
+            }
+            | Self::CobChanged { peer, .. }
+            | Self::Tracked { peer, .. }
+            | Self::Untracked { peer, .. }
+            | Self::PeerJoined { peer }
+            | Self::PeerLeft { peer }
+            | Self::PeerDead { peer, .. }
+            | Self::PeerMismatch { peer, .. } => Some(peer),
+            Self::Lagged { .. } => None,
+        }
+    }
+}
+
+/// The kinds of [`Event`]s a subscription can be restricted to.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Kind {
+    ReplicationCompleted,
+    IdentityUpdate,
+    CobChanged,
+    Tracked,
+    Untracked,
+    PeerJoined,
+    PeerLeft,
+    PeerDead,
+    PeerMismatch,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            Self::ReplicationCompleted => "replication-completed",
+            Self::IdentityUpdate => "identity-update",
+            Self::CobChanged => "cob-changed",
+            Self::Tracked => "tracked",
+            Self::Untracked => "untracked",
+            Self::PeerJoined => "peer-joined",
+            Self::PeerLeft => "peer-left",
+            Self::PeerDead => "peer-dead",
+            Self::PeerMismatch => "peer-mismatch",
+        };
+        f.write_str(kind)
+    }
+}
+
+impl FromStr for Kind {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "replication-completed" => Ok(Self::ReplicationCompleted),
+            "identity-update" => Ok(Self::IdentityUpdate),
+            "cob-changed" => Ok(Self::CobChanged),
+            "tracked" => Ok(Self::Tracked),
+            "untracked" => Ok(Self::Untracked),
+            "peer-joined" => Ok(Self::PeerJoined),
+            "peer-left" => Ok(Self::PeerLeft),
+            "peer-dead" => Ok(Self::PeerDead),
+            "peer-mismatch" => Ok(Self::PeerMismatch),
+            _ => Err(format!("unsupported event `{}`", input)),
+        }
+    }
+}
+
+/// The [`Event`]s a subscription is interested in.
+///
+/// An event matches if it matches each of the fields, where an empty field
+/// matches everything. Events which are not about a project or a peer don't
+/// match a non-empty `urns` or `peers`, respectively.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Filter {
+    #[serde(default)]
+    pub urns: Vec<Urn>,
+    #[serde(default)]
+    pub peers: Vec<PeerId>,
+    #[serde(default)]
+    pub kinds: BTreeSet<Kind>,
+}
+
+impl Filter {
+    /// `true` if `event` should be sent to the subscriber.
+    pub fn matches(&self, event: &Event) -> bool {
+        let kind = match event.kind() {
+            Some(kind) => kind,
+            None => return true,
+        };
+        self.matches_kind(kind)
+            && self.matches_urn(event.urn())
+            && (self.peers.is_empty()
+                || event
+                    .peer()
+                    .map(|peer| self.peers.contains(peer))
+                    .unwrap_or(false))
+    }
+
+    /// `true` if events about `urn` may match.
+    pub fn matches_urn(&self, urn: Option<&Urn>) -> bool {
+        self.urns.is_empty()
+            || urn
+                .map(|urn| self.urns.iter().any(|u| u.id == urn.id))
+                .unwrap_or(false)
+    }
+
+    /// `true` if events of `kind` may match.
+    pub fn matches_kind(&self, kind: Kind) -> bool {
+        self.kinds.is_empty() || self.kinds.contains(&kind)
+    }
+}
+
+/// Write `msg` as a single line of JSON to `w`.
+pub async fn write_line<W, T>(w: &mut W, msg: &T) -> Result<(), Error>
+where
+    W: AsyncWrite + Unpin,
+    T: Serialize,
+{
+    let mut line = serde_json::to_vec(msg)?;
+    line.push(b'\n');
+    w.write_all(&line).await?;
+    Ok(w.flush().await?)
+}
+
+/// A connection to the control socket.
+pub struct Client {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    writer: OwnedWriteHalf,
+}
+
+impl Client {
+    pub async fn connect(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let stream = UnixStream::connect(path)
+            .await
+            .map_err(|source| Error::Connect {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        let (reader, writer) = stream.into_split();
+        Ok(Self {
+            lines: BufReader::new(reader).lines(),
+            writer,
+        })
+    }
+
+    pub async fn status(&mut self) -> Result<Status, Error> {
+        match self.call(&Request::Status).await? {
+            Response::Status(status) => Ok(status),
+            other => Err(Error::Unexpected(other)),
+        }
+    }
+
+    /// Replicate `urn` from `from`, see [`Request::Replicate`].
+    ///
+    /// Returns once the replication is done, which may take a while.
+    pub async fn replicate(
+        &mut self,
+        urn: Urn,
+        from: PeerId,
+        addrs: Vec<SocketAddr>,
+    ) -> Result<Replicated, Error> {
+        match self.call(&Request::Replicate { urn, from, addrs }).await? {
+            Response::Replicated(replicated) => Ok(replicated),
+            other => Err(Error::Unexpected(other)),
+        }
+    }
+
+    /// Track `peer` for `urn`, returning `true` if it wasn't tracked before.
+    pub async fn track(&mut self, urn: Urn, peer: PeerId) -> Result<bool, Error> {
+        match self.call(&Request::Track { urn, peer }).await? {
+            Response::Tracked { changed } => Ok(changed),
+            other => Err(Error::Unexpected(other)),
+        }
+    }
+
+    /// Stop tracking `peer` for `urn`, returning `true` if it was tracked
+    /// before.
+    pub async fn untrack(&mut self, urn: Urn, peer: PeerId) -> Result<bool, Error> {
+        match self.call(&Request::Untrack { urn, peer }).await? {
+            Response::Untracked { changed } => Ok(changed),
+            other => Err(Error::Unexpected(other)),
+        }
+    }
+
+    /// Ask `seed` to fetch `urn` from the node, see [`Request::Push`].
+    ///
+    /// Returns once the seed is done fetching.
+    pub async fn push(
+        &mut self,
+        urn: Urn,
+        seed: PeerId,
+        addrs: Vec<SocketAddr>,
+    ) -> Result<Pushed, Error> {
+        match self.call(&Request::Push { urn, seed, addrs }).await? {
+            Response::Pushed(pushed) => Ok(pushed),
+            other => Err(Error::Unexpected(other)),
+        }
+    }
+
+    /// Subscribe to the [`Event`]s of the node matching `filter`.
+    ///
+    /// No other requests can be made on a subscribed connection, so this
+    /// consumes the client.
+    pub async fn subscribe(mut self, filter: Filter) -> Result<Events, Error> {
+        match self.call(&Request::Subscribe { filter }).await? {
+            Response::Subscribed => Ok(Events {
+                lines: self.lines,
+                _writer: self.writer,
+            }),
+            other => Err(Error::Unexpected(other)),
+        }
+    }
+
+    async fn call(&mut self, request: &Request) -> Result<Response, Error> {
+        write_line(&mut self.writer, request).await?;
+        let line = self.lines.next_line().await?.ok_or(Error::Closed)?;
+        match serde_json::from_str(&line)? {
+            Response::Error { message } => Err(Error::Node(message)),
+            response => Ok(response),
+        }
+    }
+}
+
+/// The [`Event`]s of a subscribed connection.
+pub struct Events {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    // Dropping the write half would shut down the connection.
+    _writer: OwnedWriteHalf,
+}
+
+impl Events {
+    /// The next event, or `None` if the node closed the connection.
+    pub async fn next(&mut self) -> Result<Option<Event>, Error> {
+        match self.lines.next_line().await? {
+            Some(line) => Ok(Some(serde_json::from_str(&line)?)),
+            None => Ok(None),
+        }
+    }
+}
@@ -3,8 +3,9 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::sync::Arc;
+use std::{fmt, str::FromStr, sync::Arc};
 
+use sha2::{Digest as _, Sha256};
 use thiserror::Error;
 use thrussh_agent::client::ClientStream;
 
@@ -14,7 +15,10 @@ use librad::{
             crypto::{Crypto, KdfParams, Pwhash, SecretBoxError},
             file,
             pinentry::Prompt,
-            sign::ssh::{self, SshAgent},
+            sign::{
+                self,
+                ssh::{self, SshAgent},
+            },
             FileStorage,
             Keystore as _,
         },
@@ -24,6 +28,7 @@ use librad::{
     },
     git::storage::{self, ReadOnly},
     profile::Profile,
+    PeerId,
     PublicKey,
     SecretKey,
 };
@@ -38,6 +43,18 @@ pub enum Error {
     #[error(transparent)]
     SshConnect(#[from] ssh::error::Connect),
     #[error(transparent)]
+    SshListKeys(#[from] ssh::error::ListKeys),
+    #[error(
+        "no key matching `{wanted}` is loaded in the ssh-agent, available keys are: [{}]",
+        .available.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+    )]
+    NoSuchSshKey {
+        wanted: SshKey,
+        available: Vec<AgentKey>,
+    },
+    #[error("the ssh-agent key `{key}` does not belong to the profile of peer `{profile}`")]
+    SshKeyMismatch { key: AgentKey, profile: PeerId },
+    #[error(transparent)]
     StorageInit(#[from] storage::read::error::Init),
 }
 
@@ -77,13 +94,141 @@ pub fn signer_prompt(profile: &Profile) -> Result<BoxedSigner, Error> {
     Ok(key.into())
 }
 
+/// Selects one of the keys loaded in the ssh-agent, either by the [`PeerId`]
+/// it corresponds to, or by its OpenSSH fingerprint, eg. `SHA256:...`, as
+/// shown by `ssh-add -l`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SshKey {
+    PeerId(PeerId),
+    Fingerprint(String),
+}
+
+impl SshKey {
+    pub fn matches(&self, key: &AgentKey) -> bool {
+        match self {
+            Self::PeerId(peer_id) => *peer_id == key.peer_id,
+            Self::Fingerprint(fingerprint) => *fingerprint == key.fingerprint,
+        }
+    }
+}
+
+impl fmt::Display for SshKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PeerId(peer_id) => peer_id.fmt(f),
+            Self::Fingerprint(fingerprint) => f.write_str(fingerprint),
+        }
+    }
+}
+
+impl FromStr for SshKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with(FINGERPRINT_PREFIX) {
+            Ok(Self::Fingerprint(s.to_owned()))
+        } else {
+            s.parse()
+                .map(Self::PeerId)
+                .map_err(|_| format!("`{}` is neither a peer id nor a SHA256 fingerprint", s))
+        }
+    }
+}
+
+const FINGERPRINT_PREFIX: &str = "SHA256:";
+
+/// An ed25519 key loaded in the ssh-agent.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AgentKey {
+    pub peer_id: PeerId,
+    /// The OpenSSH fingerprint of the key.
+    pub fingerprint: String,
+}
+
+impl From<PublicKey> for AgentKey {
+    fn from(key: PublicKey) -> Self {
+        Self {
+            fingerprint: fingerprint(&key),
+            peer_id: PeerId::from(key),
+        }
+    }
+}
+
+impl fmt::Display for AgentKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.fingerprint, self.peer_id)
+    }
+}
+
+/// The OpenSSH fingerprint of `key`, ie. the unpadded base64 encoding of the
+/// SHA256 digest of its wire format.
+pub fn fingerprint(key: &PublicKey) -> String {
+    const KEY_TYPE: &[u8] = b"ssh-ed25519";
+
+    let mut blob = Vec::with_capacity(4 + KEY_TYPE.len() + 4 + key.as_ref().len());
+    blob.extend_from_slice(&(KEY_TYPE.len() as u32).to_be_bytes());
+    blob.extend_from_slice(KEY_TYPE);
+    blob.extend_from_slice(&(key.as_ref().len() as u32).to_be_bytes());
+    blob.extend_from_slice(key.as_ref());
+
+    format!(
+        "{}{}",
+        FINGERPRINT_PREFIX,
+        base64::encode_config(Sha256::digest(&blob), base64::STANDARD_NO_PAD)
+    )
+}
+
+/// List the ed25519 keys loaded in the ssh-agent.
+pub async fn agent_keys<S>() -> Result<Vec<AgentKey>, Error>
+where
+    S: ClientStream + Unpin + 'static,
+{
+    let keys = ssh::list_keys::<S>().await?;
+    Ok(keys
+        .into_iter()
+        .map(|key: sign::PublicKey| AgentKey::from(PublicKey::from(key)))
+        .collect())
+}
+
+/// Get the signer for the key of the profile from the ssh-agent.
 pub async fn signer_ssh<S>(profile: &Profile) -> Result<BoxedSigner, Error>
+where
+    S: ClientStream + Unpin + 'static,
+{
+    signer_ssh_with::<S>(profile, None).await
+}
+
+/// Get the signer for the key selected by `wanted` from the ssh-agent, or the
+/// key of the profile if `None`.
+///
+/// # Errors
+///
+/// * If no key loaded in the agent matches, the available keys are reported.
+/// * If the selected key is not the one the profile was created with.
+pub async fn signer_ssh_with<S>(
+    profile: &Profile,
+    wanted: Option<&SshKey>,
+) -> Result<BoxedSigner, Error>
 where
     S: ClientStream + Unpin + 'static,
 {
     let storage = ReadOnly::open(profile.paths())?;
-    let peer_id = storage.peer_id();
-    let agent = SshAgent::new((**peer_id).into());
+    let peer_id = *storage.peer_id();
+    let wanted = wanted.cloned().unwrap_or(SshKey::PeerId(peer_id));
+
+    let available = agent_keys::<S>().await?;
+    let key = match available.iter().find(|key| wanted.matches(key)) {
+        Some(key) => key.clone(),
+        None => return Err(Error::NoSuchSshKey { wanted, available }),
+    };
+    if key.peer_id != peer_id {
+        return Err(Error::SshKeyMismatch {
+            key,
+            profile: peer_id,
+        });
+    }
+
+    let agent = SshAgent::new((*key.peer_id).into());
     let signer = agent.connect::<S>().await?;
     Ok(SomeSigner {
         signer: Arc::new(signer),
@@ -0,0 +1,461 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Diagnostics of the environment `rad` and `linkd` run in.
+//!
+//! Every check results in a [`Check`], which suggests a fix unless it passed.
+//! Checks which depend on the storage are skipped by [`run`] if it can't be
+//! opened.
+
+use std::{
+    env,
+    fmt,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use thrussh_agent::client::ClientStream;
+
+use librad::{
+    git::storage::ReadOnly,
+    profile::{self, Profile, ProfileId, RadHome},
+    PeerId,
+};
+use rad_clib::keys;
+
+/// The outcome of a [`Check`].
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Ok => "ok",
+            Self::Warn => "warn",
+            Self::Fail => "FAIL",
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub detail: String,
+    /// What to do about a failed check.
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Warn,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Fail,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{:>4}] {}: {}", self.status, self.name, self.detail)?;
+        if let Some(fix) = &self.fix {
+            write!(f, "\n       fix: {}", fix)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Options {
+    /// How far into the future signed refs may be dated before the clock of
+    /// their signer, or ours, is considered wrong.
+    pub clock_skew: Duration,
+}
+
+/// Run all checks against the active profile.
+pub async fn run<S>(options: Options) -> Vec<Check>
+where
+    S: ClientStream + Unpin + 'static,
+{
+    let mut checks = profile_checks::<S>(options).await;
+    checks.extend(git());
+    checks
+}
+
+async fn profile_checks<S>(options: Options) -> Vec<Check>
+where
+    S: ClientStream + Unpin + 'static,
+{
+    let (profile, check) = match self::profile() {
+        Ok(found) => found,
+        Err(check) => return vec![check],
+    };
+    let mut checks = vec![check, layout(&profile)];
+
+    let storage = match ReadOnly::open(profile.paths()) {
+        Ok(storage) => {
+            checks.push(Check::ok(
+                "storage",
+                format!("{} (peer {})", storage.path().display(), storage.peer_id()),
+            ));
+            storage
+        },
+        Err(e) => {
+            checks.push(Check::fail(
+                "storage",
+                format!("can't open {}: {}", profile.paths().git_dir().display(), e),
+                "if the profile was never used, create one with `rad profile create`, otherwise \
+                 check that the directory is a bare git repository",
+            ));
+            return checks;
+        },
+    };
+
+    let key_file = key_file(&profile);
+    let agent = agent::<S>(storage.peer_id()).await;
+    if key_file.status != Status::Ok && agent.status != Status::Ok {
+        checks.push(Check::fail(
+            "keys",
+            format!("no key is available for peer {}", storage.peer_id()),
+            "import the key with `rad profile import-key`, or add it to the ssh-agent with \
+             `rad profile ssh-add`",
+        ));
+    }
+    checks.push(key_file);
+    checks.push(agent);
+    checks.push(config(&storage));
+    checks.push(clock(&storage, options.clock_skew));
+
+    checks
+}
+
+/// The profile selected by `RAD_PROFILE`, or the active one.
+pub fn profile() -> Result<(Profile, Check), Check> {
+    const NAME: &str = "profile";
+
+    let home = RadHome::default();
+    let location = match &home {
+        RadHome::Root(root) => format!("{} (RAD_HOME)", root.display()),
+        RadHome::ProjectDirs => "the system default directories".to_owned(),
+    };
+    let found = ProfileId::from_env()
+        .map_err(profile::Error::from)
+        .and_then(|id| match id {
+            Some(id) => Profile::get(&home, id),
+            None => Profile::active(&home),
+        });
+    match found {
+        Ok(Some(profile)) => {
+            let detail = format!("{} in {}", profile.id(), location);
+            Ok((profile, Check::ok(NAME, detail)))
+        },
+        Ok(None) => Err(Check::fail(
+            NAME,
+            format!("no such profile in {}", location),
+            "create one with `rad profile create`, or select an existing one with `rad profile \
+             set` or RAD_PROFILE",
+        )),
+        Err(e) => Err(Check::fail(
+            NAME,
+            e.to_string(),
+            "check RAD_HOME and RAD_PROFILE",
+        )),
+    }
+}
+
+/// The directories of the profile exist and are writable.
+pub fn layout(profile: &Profile) -> Check {
+    const NAME: &str = "layout";
+
+    let broken = profile
+        .paths()
+        .all_dirs()
+        .filter(|dir| match fs::metadata(dir) {
+            Ok(meta) => !meta.is_dir() || meta.permissions().readonly(),
+            Err(_) => true,
+        })
+        .map(|dir| dir.display().to_string())
+        .collect::<Vec<_>>();
+    if broken.is_empty() {
+        Check::ok(NAME, "all profile directories are writable")
+    } else {
+        Check::fail(
+            NAME,
+            format!("not a writable directory: {}", broken.join(", ")),
+            "make sure these are directories owned by you",
+        )
+    }
+}
+
+/// The secret key of the profile is stored in a file only readable by us.
+pub fn key_file(profile: &Profile) -> Check {
+    const NAME: &str = "key file";
+
+    let path = profile.paths().keys_dir().join(keys::LIBRAD_KEY_FILE);
+    match fs::metadata(&path) {
+        Err(_) => Check::warn(
+            NAME,
+            format!("{} does not exist", path.display()),
+            "not needed if the key is only kept in the ssh-agent, otherwise import it with `rad \
+             profile import-key`",
+        ),
+        Ok(meta) if is_shared(&meta) => Check::warn(
+            NAME,
+            format!("{} is accessible by other users", path.display()),
+            format!("chmod 600 {}", path.display()),
+        ),
+        Ok(_) => Check::ok(NAME, path.display().to_string()),
+    }
+}
+
+/// The ssh-agent is reachable via a socket only accessible by us, and has the
+/// key of `peer_id` loaded.
+pub async fn agent<S>(peer_id: &PeerId) -> Check
+where
+    S: ClientStream + Unpin + 'static,
+{
+    const NAME: &str = "ssh-agent";
+
+    let socket = match env::var_os("SSH_AUTH_SOCK") {
+        Some(socket) => PathBuf::from(socket),
+        None => {
+            return Check::warn(
+                NAME,
+                "SSH_AUTH_SOCK is not set",
+                "start an agent with `eval $(ssh-agent)` to sign without entering a passphrase",
+            )
+        },
+    };
+    match fs::metadata(&socket) {
+        Err(e) => {
+            return Check::fail(
+                NAME,
+                format!("SSH_AUTH_SOCK points to {}: {}", socket.display(), e),
+                "restart the ssh-agent, or unset SSH_AUTH_SOCK",
+            )
+        },
+        Ok(meta) if is_shared(&meta) && parent_is_shared(&socket) => {
+            return Check::warn(
+                NAME,
+                format!("{} is accessible by other users", socket.display()),
+                format!("chmod 600 {}", socket.display()),
+            )
+        },
+        Ok(_) => {},
+    }
+
+    match keys::agent_keys::<S>().await {
+        Err(e) => Check::fail(
+            NAME,
+            format!("can't list the keys of the agent: {}", e),
+            "check that the ssh-agent is running",
+        ),
+        Ok(loaded) if loaded.iter().any(|key| key.peer_id == *peer_id) => {
+            Check::ok(NAME, format!("key of peer {} is loaded", peer_id))
+        },
+        Ok(_) => Check::warn(
+            NAME,
+            format!("key of peer {} is not loaded", peer_id),
+            "add it with `rad profile ssh-add`",
+        ),
+    }
+}
+
+/// The storage config has the settings newer versions initialise storages
+/// with.
+pub fn config(storage: &ReadOnly) -> Check {
+    const NAME: &str = "storage config";
+
+    let log_all_ref_updates = git2::Repository::open(storage.path())
+        .and_then(|repo| repo.config())
+        .and_then(|config| config.get_bool("core.logAllRefUpdates"));
+    match log_all_ref_updates {
+        Ok(true) => Check::ok(NAME, "up to date"),
+        Ok(false) | Err(_) => Check::warn(
+            NAME,
+            "core.logAllRefUpdates is not set, so reflogs are not kept for new refs",
+            format!(
+                "git --git-dir {} config core.logAllRefUpdates true",
+                storage.path().display()
+            ),
+        ),
+    }
+}
+
+/// No `rad/signed_refs` are dated further into the future than `skew`, which
+/// would indicate that the clock of their signer, or ours, is wrong.
+pub fn clock(storage: &ReadOnly, skew: Duration) -> Check {
+    const NAME: &str = "clock";
+
+    let now = match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(now) => now.as_secs() as i64,
+        Err(_) => {
+            return Check::fail(
+                NAME,
+                "the system time is before 1970",
+                "set the system clock",
+            )
+        },
+    };
+    let cutoff = now + skew.as_secs() as i64;
+
+    let mut checked = 0;
+    let mut ours = Ahead::default();
+    let mut theirs = Ahead::default();
+    let found = (|| -> Result<(), git2::Error> {
+        let repo = git2::Repository::open(storage.path())?;
+        for reference in repo.references_glob("refs/namespaces/*/rad/signed_refs")? {
+            let reference = reference?;
+            let time = reference.peel_to_commit()?.time().seconds();
+            checked += 1;
+            if time > cutoff {
+                let ahead = if reference
+                    .name()
+                    .map_or(false, |name| name.contains("/refs/remotes/"))
+                {
+                    &mut theirs
+                } else {
+                    &mut ours
+                };
+                ahead.record(time - now);
+            }
+        }
+        Ok(())
+    })();
+
+    if let Err(e) = found {
+        Check::fail(
+            NAME,
+            format!("can't read signed refs: {}", e),
+            "run `git fsck` on the storage",
+        )
+    } else if ours.count > 0 {
+        Check::warn(
+            NAME,
+            format!(
+                "{} of your signed refs are dated up to {}s in the future",
+                ours.count, ours.max
+            ),
+            "the system clock was ahead, or is behind now: check that it is synchronised, eg. \
+             via `timedatectl`",
+        )
+    } else if theirs.count > 0 {
+        Check::warn(
+            NAME,
+            format!(
+                "{} signed refs of other peers are dated up to {}s in the future",
+                theirs.count, theirs.max
+            ),
+            "if many peers are affected, the system clock is likely behind: check that it is \
+             synchronised, eg. via `timedatectl`",
+        )
+    } else {
+        Check::ok(
+            NAME,
+            format!("{} signed refs are not from the future", checked),
+        )
+    }
+}
+
+#[derive(Default)]
+struct Ahead {
+    count: usize,
+    max: i64,
+}
+
+impl Ahead {
+    fn record(&mut self, secs: i64) {
+        self.count += 1;
+        self.max = self.max.max(secs);
+    }
+}
+
+/// `git` and the `rad://` remote helper can be found on the `PATH`.
+pub fn git() -> Vec<Check> {
+    let git = match Command::new("git").arg("--version").output() {
+        Ok(out) if out.status.success() => Check::ok(
+            "git",
+            String::from_utf8_lossy(&out.stdout).trim().to_owned(),
+        ),
+        Ok(out) => Check::fail(
+            "git",
+            format!(
+                "`git --version` failed: {}",
+                String::from_utf8_lossy(&out.stderr).trim()
+            ),
+            "reinstall git",
+        ),
+        Err(e) => Check::fail(
+            "git",
+            format!("can't run git: {}", e),
+            "install git, and make sure it is on the PATH",
+        ),
+    };
+
+    let helper = env::var_os("PATH")
+        .and_then(|path| {
+            env::split_paths(&path)
+                .map(|dir| dir.join(REMOTE_HELPER))
+                .find(|exe| exe.is_file())
+        })
+        .map_or_else(
+            || {
+                Check::warn(
+                    REMOTE_HELPER,
+                    "not found on the PATH, so git can't use rad:// remotes",
+                    "install it, eg. via `cargo install --path git-helpers`",
+                )
+            },
+            |exe| Check::ok(REMOTE_HELPER, exe.display().to_string()),
+        );
+
+    vec![git, helper]
+}
+
+const REMOTE_HELPER: &str = "git-remote-rad";
+
+#[cfg(unix)]
+fn is_shared(meta: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt as _;
+    meta.permissions().mode() & 0o077 != 0
+}
+
+#[cfg(not(unix))]
+fn is_shared(_: &fs::Metadata) -> bool {
+    false
+}
+
+/// Sockets are usually protected by their directory, eg. `/tmp/ssh-XXXX/`.
+fn parent_is_shared(path: &Path) -> bool {
+    path.parent()
+        .and_then(|parent| fs::metadata(parent).ok())
+        .map_or(true, |meta| is_shared(&meta))
+}
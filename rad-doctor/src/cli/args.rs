@@ -0,0 +1,19 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use structopt::StructOpt;
+
+/// Check the environment and storage of the active profile, and suggest fixes
+/// for any problems found
+#[derive(Debug, StructOpt)]
+pub struct Args {
+    /// the number of seconds signed refs may be dated into the future before
+    /// the clock is considered wrong
+    #[structopt(long, default_value = "300")]
+    pub clock_skew: u64,
+    /// only show the checks which didn't pass
+    #[structopt(long)]
+    pub problems: bool,
+}
@@ -0,0 +1,53 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::time::Duration;
+
+use thrussh_agent::client::ClientStream;
+
+use crate::checks::{self, Check, Options, Status};
+
+use super::args::Args;
+
+pub async fn main<S>(
+    Args {
+        clock_skew,
+        problems,
+    }: Args,
+) -> anyhow::Result<()>
+where
+    S: ClientStream + Unpin + 'static,
+{
+    let checks = checks::run::<S>(Options {
+        clock_skew: Duration::from_secs(clock_skew),
+    })
+    .await;
+
+    print!("{}", render(&checks, problems));
+    outcome(&checks)
+}
+
+/// Render one `check` per line, followed by its fix if any. If `problems` is
+/// set, the checks which passed are left out.
+pub fn render(checks: &[Check], problems: bool) -> String {
+    checks
+        .iter()
+        .filter(|check| !problems || check.status != Status::Ok)
+        .map(|check| format!("{}\n", check))
+        .collect()
+}
+
+/// Fail if any of the `checks` failed, warnings are fine.
+pub fn outcome(checks: &[Check]) -> anyhow::Result<()> {
+    let failed = checks
+        .iter()
+        .filter(|check| check.status == Status::Fail)
+        .count();
+    if failed > 0 {
+        anyhow::bail!("{} of {} checks failed", failed, checks.len())
+    }
+
+    Ok(())
+}
@@ -39,8 +39,36 @@ pub struct Args {
 
 #[derive(Debug, StructOpt)]
 pub enum Command {
+    /// Exchange the refs of a project offline, as git bundles
+    Bundle(rad_storage::cli::args::Bundle),
+    /// Create a working copy of a project
+    Checkout(rad_checkout::cli::args::Checkout),
+    /// Check the environment and storage of the active profile
+    Doctor(rad_doctor::cli::args::Args),
+    /// Search the projects indexed by the node
+    Grep(rad_grep::cli::args::Args),
+    /// Create a project from a git repository hosted elsewhere
+    Import(rad_import::cli::args::Args),
+    /// List the notifications derived from replicated data
+    Inbox(rad_inbox::cli::args::Args),
+    /// List the identities in your monorepo
+    Ls(rad_ls::cli::args::Args),
+    /// Inspect the state kept by, and the events of, the node
+    Node(rad_node::cli::args::Args),
     /// Manage your Radicle profiles
     Profile(rad_profile::cli::args::Args),
+    /// Manage the delegates of a project
+    Project(rad_project::cli::args::Args),
+    /// Ask a seed to fetch your projects right away
+    Push(rad_node::cli::args::Push),
+    /// Update the remotes of a working copy, and fetch from them
+    Refresh(rad_checkout::cli::args::Refresh),
+    /// Inspect the monorepo
+    Storage(rad_storage::cli::args::Args),
+    /// Track a peer in the context of an identity
+    Track(rad_track::cli::args::Track),
+    /// Stop tracking a peer in the context of an identity
+    Untrack(rad_track::cli::args::Untrack),
     #[structopt(external_subcommand)]
     External(Vec<String>),
 }
@@ -19,7 +19,21 @@ where
 {
     let args = sanitise_globals(Args::from_args());
     match args.command {
+        args::Command::Bundle(args) => rad_storage::cli::bundle(args),
+        args::Command::Checkout(args) => rad_checkout::cli::checkout::<S>(args).await,
+        args::Command::Doctor(args) => rad_doctor::cli::main::<S>(args).await,
+        args::Command::Grep(args) => rad_grep::cli::main(args),
+        args::Command::Import(args) => rad_import::cli::main::<S>(args).await,
+        args::Command::Inbox(args) => rad_inbox::cli::main(args),
+        args::Command::Ls(args) => rad_ls::cli::main(args),
+        args::Command::Node(args) => rad_node::cli::main(args).await,
         args::Command::Profile(args) => rad_profile::cli::main::<S>(args).await,
+        args::Command::Project(args) => rad_project::cli::main::<S>(args).await,
+        args::Command::Push(args) => rad_node::cli::push(args).await,
+        args::Command::Refresh(args) => rad_checkout::cli::refresh::<S>(args).await,
+        args::Command::Storage(args) => rad_storage::cli::main(args),
+        args::Command::Track(args) => rad_track::cli::track::<S>(args).await,
+        args::Command::Untrack(args) => rad_track::cli::untrack::<S>(args).await,
         args::Command::External(external) => {
             let exe = external.first();
             match exe {
@@ -0,0 +1,20 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use structopt::StructOpt;
+
+use librad::git::Urn;
+
+/// Search the projects indexed by the node for lines containing a pattern.
+///
+/// Projects are indexed by running the node with `--grep-index <urn>`.
+#[derive(Debug, StructOpt)]
+pub struct Args {
+    /// the text to search for, matched literally and case-sensitively
+    pub pattern: String,
+    /// only search this project
+    #[structopt(long)]
+    pub urn: Option<Urn>,
+}
@@ -0,0 +1,21 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::{git::grep, profile::Profile};
+use rad_clib::storage;
+
+use super::args::Args;
+
+pub fn main(Args { pattern, urn }: Args) -> anyhow::Result<()> {
+    let profile = Profile::load()?;
+    let storage = storage::read_only(&profile)?;
+
+    for m in grep::search(&storage, &pattern, urn.as_ref())? {
+        let branch = m.branch.strip_prefix("refs/heads/").unwrap_or(&m.branch);
+        println!("{}\t{}:{}:{}:{}", m.urn, branch, m.path, m.line, m.text);
+    }
+
+    Ok(())
+}
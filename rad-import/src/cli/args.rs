@@ -0,0 +1,30 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use structopt::StructOpt;
+
+/// Create a project from a git repository hosted elsewhere, delegating to
+/// your default identity.
+#[derive(Debug, StructOpt)]
+pub struct Args {
+    /// the URL of the repository to import. Credentials may be provided as
+    /// part of the URL.
+    pub url: String,
+    /// the name of the project, if none is provided then it is derived from
+    /// the URL
+    #[structopt(long)]
+    pub name: Option<String>,
+    /// the description of the project
+    #[structopt(long)]
+    pub description: Option<String>,
+    /// the default branch of the project, if none is provided then the branch
+    /// `HEAD` of the repository points to is used
+    #[structopt(long)]
+    pub default_branch: Option<String>,
+    /// keep running after the import, pulling the repository every given
+    /// number of seconds to publish its updates
+    #[structopt(long)]
+    pub watch: Option<u64>,
+}
@@ -0,0 +1,68 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{thread, time::Duration};
+
+use anyhow::anyhow;
+use thrussh_agent::client::ClientStream;
+
+use librad::{
+    git::{
+        identities::local,
+        import::{self, Options},
+        refs,
+    },
+    profile::Profile,
+};
+use rad_clib::storage::ssh;
+
+use super::args::Args;
+
+pub async fn main<S>(
+    Args {
+        url,
+        name,
+        description,
+        default_branch,
+        watch,
+    }: Args,
+) -> anyhow::Result<()>
+where
+    S: ClientStream + Unpin + 'static,
+{
+    let profile = Profile::load()?;
+    let (_, storage) = ssh::storage::<S>(&profile).await?;
+    let whoami = local::default(&storage)?
+        .ok_or_else(|| anyhow!("no default identity is set for this profile"))?;
+
+    let proj = import::import(
+        &storage,
+        whoami,
+        &url,
+        Options {
+            name,
+            description,
+            default_branch,
+        },
+    )?;
+    let urn = proj.urn();
+    println!("imported `{}` as `{}`", proj.subject().name, urn);
+
+    if let Some(secs) = watch {
+        loop {
+            thread::sleep(Duration::from_secs(secs));
+            match import::pull(&storage, &urn, &url) {
+                Ok(refs::Updated::Updated { at, .. }) => println!("published updates at {}", at),
+                Ok(refs::Updated::Unchanged { .. }) => {},
+                Ok(refs::Updated::ConcurrentlyModified) => {
+                    eprintln!("signed refs were concurrently modified, retrying later")
+                },
+                Err(e) => eprintln!("failed to pull the repository: {}", e),
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,23 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use structopt::StructOpt;
+
+use librad::git::Urn;
+
+/// List the notifications derived from replicated data.
+#[derive(Debug, StructOpt)]
+pub struct Args {
+    /// only list notifications for this identity
+    #[structopt(long)]
+    pub urn: Option<Urn>,
+    /// only list notifications recorded at or after this time, in seconds
+    /// since the UNIX epoch
+    #[structopt(long)]
+    pub since: Option<u64>,
+    /// remove all notifications after listing them
+    #[structopt(long)]
+    pub clear: bool,
+}
@@ -0,0 +1,42 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::{
+    git::inbox::{self, Kind},
+    profile::Profile,
+};
+use rad_clib::storage;
+
+use super::args::Args;
+
+pub fn main(Args { urn, since, clear }: Args) -> anyhow::Result<()> {
+    let profile = Profile::load()?;
+    let storage = storage::read_only(&profile)?;
+
+    for notification in inbox::list(&storage, urn.as_ref(), since)? {
+        let summary = match notification.kind {
+            Kind::Commits {
+                peer,
+                branch,
+                count,
+                ..
+            } => format!("{} new commit(s) on `{}` of {}", count, branch, peer),
+            Kind::Mention { peer, object, .. } => {
+                format!("mentioned in `{}` by {}", object, peer)
+            },
+            Kind::IdentityUpdate => "identity update needs confirmation".to_owned(),
+        };
+        println!(
+            "{}\t{}\t{}",
+            notification.timestamp, notification.urn, summary
+        );
+    }
+
+    if clear {
+        inbox::clear(&storage)?;
+    }
+
+    Ok(())
+}
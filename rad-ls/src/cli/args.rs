@@ -0,0 +1,29 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use structopt::StructOpt;
+
+use librad::PeerId;
+use rad_clib::ser::Format;
+
+use crate::Kind;
+
+/// List the projects and persons found in the monorepo of the active profile.
+#[derive(Debug, StructOpt)]
+pub struct Args {
+    /// only list identities of this kind (project or person)
+    #[structopt(long)]
+    pub kind: Option<Kind>,
+    /// only list identities for which this peer is tracked
+    #[structopt(long)]
+    pub tracked_by: Option<PeerId>,
+    /// only list identities which pass verification
+    #[structopt(long)]
+    pub verified_only: bool,
+    /// the output format (json or cbor), if none is provided then a
+    /// human-readable listing is printed
+    #[structopt(long)]
+    pub format: Option<Format>,
+}
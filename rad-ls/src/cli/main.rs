@@ -0,0 +1,70 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    fmt::Write as _,
+    io::{self, Write as _},
+};
+
+use librad::profile::Profile;
+use rad_clib::{ser::Format, storage};
+
+use crate::{ls, Entry, Filter};
+
+use super::args::Args;
+
+pub fn main(
+    Args {
+        kind,
+        tracked_by,
+        verified_only,
+        format,
+    }: Args,
+) -> anyhow::Result<()> {
+    let profile = Profile::load()?;
+    let storage = storage::read_only(&profile)?;
+    let entries = ls(
+        &storage,
+        &Filter {
+            kind,
+            tracked_by,
+            verified_only,
+        },
+    )?;
+
+    io::stdout().write_all(&render(entries, format)?)?;
+
+    Ok(())
+}
+
+/// Render `entries` in `format`, or as a human-readable listing with one
+/// identity per line if no format is given.
+///
+/// The CBOR encoding is returned as is, as it is not valid UTF-8.
+pub fn render(entries: Vec<Entry>, format: Option<Format>) -> anyhow::Result<Vec<u8>> {
+    match format {
+        Some(Format::Json) => {
+            let mut out = serde_json::to_vec(&entries)?;
+            out.push(b'\n');
+            Ok(out)
+        },
+        Some(Format::Cbor) => Ok(minicbor::to_vec(&entries)?),
+        None => {
+            let mut out = String::new();
+            for entry in entries {
+                writeln!(
+                    out,
+                    "{}\t{}\t{}\t{}{}",
+                    entry.urn,
+                    entry.kind,
+                    entry.name,
+                    entry.head,
+                    if entry.verified { "" } else { "\t(unverified)" }
+                )?;
+            }
+            Ok(out.into_bytes())
+        },
+    }
+}
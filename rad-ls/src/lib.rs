@@ -0,0 +1,176 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{fmt, str::FromStr};
+
+use serde::Serialize;
+use thiserror::Error;
+
+use librad::{
+    git::{
+        identities::{self, SomeIdentity},
+        storage::ReadOnly,
+        tracking,
+        Urn,
+    },
+    git_ext::Oid,
+    PeerId,
+};
+
+pub mod cli;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error(transparent)]
+    Identities(#[from] identities::Error),
+
+    #[error(transparent)]
+    Tracking(#[from] tracking::Error),
+}
+
+/// The kind of identity stored under a namespace.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Person,
+    Project,
+}
+
+impl Kind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Person => "person",
+            Self::Project => "project",
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Kind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "person" => Ok(Self::Person),
+            "project" => Ok(Self::Project),
+            _ => Err(format!("unknown identity kind `{}`", s)),
+        }
+    }
+}
+
+impl minicbor::Encode for Kind {
+    fn encode<W: minicbor::encode::Write>(
+        &self,
+        e: &mut minicbor::Encoder<W>,
+    ) -> Result<(), minicbor::encode::Error<W::Error>> {
+        e.str(self.as_str())?;
+        Ok(())
+    }
+}
+
+/// Criteria for which identities to list.
+#[derive(Clone, Debug, Default)]
+pub struct Filter {
+    /// Only list identities of this kind.
+    pub kind: Option<Kind>,
+    /// Only list identities for which the given peer is tracked.
+    pub tracked_by: Option<PeerId>,
+    /// Only list identities which pass verification.
+    pub verified_only: bool,
+}
+
+/// An identity found in storage.
+#[derive(Clone, Debug, Serialize, minicbor::Encode)]
+#[serde(rename_all = "camelCase")]
+#[cbor(map)]
+pub struct Entry {
+    #[n(0)]
+    pub urn: Urn,
+    #[n(1)]
+    pub kind: Kind,
+    #[n(2)]
+    pub name: String,
+    /// The commit the `rad/id` branch points to.
+    #[n(3)]
+    pub head: Oid,
+    /// The default branch, if this is a project which has one.
+    #[n(4)]
+    pub default_branch: Option<String>,
+    /// Whether the identity passed verification.
+    #[n(5)]
+    pub verified: bool,
+}
+
+/// List the identities in `storage` matching `filter`.
+///
+/// Identities which fail to load are skipped.
+pub fn ls(storage: &ReadOnly, filter: &Filter) -> Result<Vec<Entry>, Error> {
+    let mut entries = Vec::new();
+    for identity in identities::any::list(storage)? {
+        let identity = match identity {
+            Ok(identity) => identity,
+            Err(e) => {
+                tracing::warn!(err = %e, "skipping identity");
+                continue;
+            },
+        };
+        let (urn, kind, name, head, default_branch) = match identity {
+            SomeIdentity::Person(person) => (
+                person.urn(),
+                Kind::Person,
+                person.subject().name.to_string(),
+                person.content_id,
+                None,
+            ),
+            SomeIdentity::Project(proj) => (
+                proj.urn(),
+                Kind::Project,
+                proj.subject().name.to_string(),
+                proj.content_id,
+                proj.subject()
+                    .default_branch
+                    .as_ref()
+                    .map(|branch| branch.to_string()),
+            ),
+            _ => continue,
+        };
+
+        if filter.kind.map(|k| k != kind).unwrap_or(false) {
+            continue;
+        }
+
+        if let Some(peer) = filter.tracked_by {
+            if !tracking::tracked(storage, &urn)?.any(|tracked| tracked == peer) {
+                continue;
+            }
+        }
+
+        let verified = match kind {
+            Kind::Person => identities::person::verify(storage, &urn).map(|p| p.is_some()),
+            Kind::Project => identities::project::verify(storage, &urn).map(|p| p.is_some()),
+        }
+        .unwrap_or(false);
+        if filter.verified_only && !verified {
+            continue;
+        }
+
+        entries.push(Entry {
+            urn,
+            kind,
+            name,
+            head,
+            default_branch,
+            verified,
+        })
+    }
+
+    Ok(entries)
+}
@@ -0,0 +1,110 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{fmt, path::PathBuf, str::FromStr};
+
+use structopt::StructOpt;
+
+use librad::{crypto, git::Urn, PeerId};
+use rad_clib::control::Kind;
+
+/// Inspect the state kept by, and the events of, the node
+#[derive(Debug, StructOpt)]
+pub struct Args {
+    #[structopt(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    /// List the peers in the address book, most recently seen first
+    Peers(Peers),
+    /// Print the events of the running node as JSON, one per line
+    Events(Events),
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Peers {
+    /// only list peers seen within this many seconds
+    #[structopt(long)]
+    pub max_age: Option<u64>,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Events {
+    /// keep printing events until interrupted, instead of exiting after the
+    /// first one
+    #[structopt(long)]
+    pub follow: bool,
+    /// only print events about this project, may be given multiple times
+    #[structopt(long = "urn", number_of_values = 1)]
+    pub urns: Vec<Urn>,
+    /// only print events about this peer, may be given multiple times
+    #[structopt(long = "peer", number_of_values = 1)]
+    pub peers: Vec<PeerId>,
+    /// only print events of this kind, one of 'replication-completed',
+    /// 'identity-update', 'cob-changed', 'tracked', 'untracked',
+    /// 'peer-joined', 'peer-left', 'peer-dead' and 'peer-mismatch', may be
+    /// given multiple times. Changes to collaborative objects are only
+    /// reported if '--urn' is given
+    #[structopt(long = "event", number_of_values = 1)]
+    pub kinds: Vec<Kind>,
+    /// path of the control socket of the node, defaults to the one of the
+    /// active profile
+    #[structopt(long, parse(from_os_str))]
+    pub socket: Option<PathBuf>,
+}
+
+/// Ask a seed to fetch your projects from the running node right away,
+/// instead of waiting for it to learn about them via gossip.
+///
+/// A seed which doesn't have a project yet only clones it if it tracks you
+/// for the project.
+#[derive(Debug, StructOpt)]
+pub struct Push {
+    /// the seed to push to, as '<peer id>@<host>:<port>'
+    #[structopt(long)]
+    pub seed: Seed,
+    /// the project to push, may be given multiple times. Defaults to all
+    /// projects you have signed refs for
+    #[structopt(long = "urn", number_of_values = 1)]
+    pub urns: Vec<Urn>,
+    /// path of the control socket of the node, defaults to the one of the
+    /// active profile
+    #[structopt(long, parse(from_os_str))]
+    pub socket: Option<PathBuf>,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct Seed {
+    pub peer_id: PeerId,
+    pub addr: String,
+}
+
+impl fmt::Display for Seed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}@{}", self.peer_id, self.addr)
+    }
+}
+
+impl FromStr for Seed {
+    type Err = String;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        match src.split_once('@') {
+            Some((peer_id, addr)) => {
+                let peer_id = peer_id
+                    .parse()
+                    .map_err(|e: crypto::peer::conversion::Error| e.to_string())?;
+
+                Ok(Self {
+                    peer_id,
+                    addr: addr.to_string(),
+                })
+            },
+            None => Err("missing peer id".to_string()),
+        }
+    }
+}
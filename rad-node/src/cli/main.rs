@@ -0,0 +1,175 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    net::ToSocketAddrs as _,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, bail};
+
+use librad::{
+    git::{identities, refs, Urn},
+    net::addrbook::AddrBook,
+    profile::Profile,
+};
+use rad_clib::{
+    control::{self, Client, Event, Filter},
+    storage,
+};
+
+use super::args::{Args, Command, Events, Peers, Push};
+
+pub async fn main(Args { command }: Args) -> anyhow::Result<()> {
+    match command {
+        Command::Peers(peers) => self::peers(peers),
+        Command::Events(events) => self::events(events).await,
+    }
+}
+
+fn peers(Peers { max_age }: Peers) -> anyhow::Result<()> {
+    let profile = Profile::load()?;
+    let addrbook = AddrBook::open(profile.paths())?;
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let mut entries = addrbook
+        .entries()
+        .filter(|entry| {
+            max_age
+                .map(|max_age| now.saturating_sub(entry.last_seen) <= max_age)
+                .unwrap_or(true)
+        })
+        .collect::<Vec<_>>();
+    entries.sort_by(|a, b| b.last_seen.cmp(&a.last_seen));
+
+    for entry in entries {
+        let addrs = entry
+            .addrs
+            .iter()
+            .map(|addr| addr.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let networks = entry
+            .networks
+            .iter()
+            .map(|network| network.as_deref().unwrap_or("main"))
+            .collect::<Vec<_>>()
+            .join(",");
+        let versions = entry
+            .versions
+            .map(|v| format!("v{}-v{}", v.min, v.max))
+            .unwrap_or_else(|| "-".to_owned());
+        println!(
+            "{}\t{}s ago\t{}\t{}\t{}",
+            entry.peer_id,
+            now.saturating_sub(entry.last_seen),
+            networks,
+            versions,
+            addrs
+        );
+    }
+
+    Ok(())
+}
+
+async fn events(
+    Events {
+        follow,
+        urns,
+        peers,
+        kinds,
+        socket,
+    }: Events,
+) -> anyhow::Result<()> {
+    let socket = match socket {
+        Some(socket) => socket,
+        None => control::socket_path(Profile::load()?.paths()),
+    };
+    let filter = Filter {
+        urns,
+        peers,
+        kinds: kinds.into_iter().collect(),
+    };
+    let mut events = Client::connect(socket).await?.subscribe(filter).await?;
+    while let Some(event) = events.next().await? {
+        if let Event::Lagged { skipped } = event {
+            eprintln!(
+                "warning: missed {} events, as they were not read in time",
+                skipped
+            );
+            continue;
+        }
+        println!("{}", serde_json::to_string(&event)?);
+        if !follow {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn push(Push { seed, urns, socket }: Push) -> anyhow::Result<()> {
+    let profile = Profile::load()?;
+    let socket = socket.unwrap_or_else(|| control::socket_path(profile.paths()));
+    let addrs = seed
+        .addr
+        .to_socket_addrs()
+        .map_err(|e| anyhow!("failed to resolve {}: {}", seed.addr, e))?
+        .collect::<Vec<_>>();
+    let urns = if urns.is_empty() {
+        signed(&profile)?
+    } else {
+        urns
+    };
+
+    let mut client = Client::connect(socket).await?;
+    let mut behind = 0;
+    for urn in urns {
+        match client.push(urn.clone(), seed.peer_id, addrs.clone()).await {
+            Ok(pushed) if pushed.synced() => {
+                println!("{}\tup to date\t{} refs updated", urn, pushed.updated)
+            },
+            Ok(pushed) => {
+                behind += 1;
+                println!(
+                    "{}\tbehind\t{} refs updated, the seed is at {} instead of {}",
+                    urn,
+                    pushed.updated,
+                    pushed
+                        .seed
+                        .map(|oid| oid.to_string())
+                        .unwrap_or_else(|| "nothing".to_owned()),
+                    pushed
+                        .local
+                        .map(|oid| oid.to_string())
+                        .unwrap_or_else(|| "nothing".to_owned()),
+                )
+            },
+            Err(err) => {
+                behind += 1;
+                println!("{}\tfailed\t{}", urn, err)
+            },
+        }
+    }
+
+    if behind > 0 {
+        bail!("{} does not have the tips of {} projects", seed, behind)
+    }
+    Ok(())
+}
+
+/// The identities the local peer has signed refs for.
+fn signed(profile: &Profile) -> anyhow::Result<Vec<Urn>> {
+    let storage = storage::read_only(profile)?;
+    let local = *storage.peer_id();
+    let mut urns = Vec::new();
+    for urn in identities::any::list_urns(&storage)? {
+        let urn = urn?;
+        if refs::tips(&storage, &urn)?.contains_key(&local) {
+            urns.push(urn);
+        }
+    }
+    Ok(urns)
+}
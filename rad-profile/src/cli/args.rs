@@ -3,9 +3,12 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+use std::path::PathBuf;
+
 use structopt::StructOpt;
+use thrussh_agent::Constraint;
 
-use librad::profile::ProfileId;
+use librad::{git::Urn, profile::ProfileId};
 
 /// Management of Radicle profiles and their associated configuration data.
 #[derive(Debug, StructOpt)]
@@ -18,11 +21,19 @@ pub struct Args {
 pub enum Command {
     Create(Create),
     Get(Get),
+    #[structopt(alias = "switch")]
     Set(Set),
     List(List),
     Peer(GetPeerId),
     Paths(GetPaths),
+    #[structopt(alias = "add-to-agent")]
     SshAdd(SshAdd),
+    #[structopt(alias = "rm-from-agent")]
+    SshRm(SshRm),
+    SshLs(SshLs),
+    ExportKey(ExportKey),
+    ImportKey(ImportKey),
+    Replicate(Replicate),
 }
 
 /// Create a new profile, generating a new secret key and initialising
@@ -79,4 +90,74 @@ pub struct SshAdd {
     /// provided then agent will ask to confirm each time
     #[structopt(long, short)]
     pub time: Option<u32>,
+    /// ask to confirm each use of the key, even if a lifetime is given
+    #[structopt(long)]
+    pub confirm: bool,
+}
+
+impl SshAdd {
+    /// The constraints to add the key to the ssh-agent with.
+    ///
+    /// Confirmation is required unless a lifetime is given without `--confirm`.
+    pub fn constraints(&self) -> Vec<Constraint> {
+        let mut constraints = vec![];
+        if let Some(seconds) = self.time {
+            constraints.push(Constraint::KeyLifetime { seconds });
+        }
+        if self.confirm || self.time.is_none() {
+            constraints.push(Constraint::Confirm);
+        }
+        constraints
+    }
+}
+
+/// Remove the profile's associated secret key from the ssh-agent. If no
+/// profile was provided, then the active one is used.
+#[derive(Debug, StructOpt)]
+pub struct SshRm {
+    /// the identifier to look up
+    #[structopt(long)]
+    pub id: Option<ProfileId>,
+}
+
+/// List all profiles, and whether their secret keys are loaded in the
+/// ssh-agent.
+#[derive(Debug, StructOpt)]
+pub struct SshLs {}
+
+/// Export the profile's secret key to a file. If no profile was provided, then
+/// the active one is used.
+///
+/// The key is written unencrypted, and can be imported again via `import-key`,
+/// or used as the key file for `linkd`.
+#[derive(Debug, StructOpt)]
+pub struct ExportKey {
+    /// the identifier to look up
+    #[structopt(long)]
+    pub id: Option<ProfileId>,
+    /// the file to write the secret key to, which must not exist yet
+    #[structopt(long, parse(from_os_str))]
+    pub output: PathBuf,
+}
+
+/// Create a new profile from a secret key previously exported via
+/// `export-key`, and set it as the active profile.
+#[derive(Debug, StructOpt)]
+pub struct ImportKey {
+    /// the file to read the secret key from
+    #[structopt(long, parse(from_os_str))]
+    pub input: PathBuf,
+}
+
+/// Replicate an identity from the storage of another profile on this machine
+/// into the storage of the active profile, without going through the
+/// network.
+#[derive(Debug, StructOpt)]
+pub struct Replicate {
+    /// the identifier of the profile to replicate from
+    #[structopt(long)]
+    pub from: ProfileId,
+    /// the identity to replicate
+    #[structopt(long)]
+    pub urn: Urn,
 }
@@ -3,11 +3,31 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use thrussh_agent::{client::ClientStream, Constraint};
+use std::{
+    fs::{self, OpenOptions},
+    io::Write as _,
+    os::unix::fs::OpenOptionsExt as _,
+};
 
+use thrussh_agent::client::ClientStream;
+
+use librad::{crypto::SecureBytes, git::fetch};
 use rad_clib::keys;
 
-use crate::{create, get, list, paths, peer_id, set, ssh_add};
+use crate::{
+    create,
+    export_key,
+    get,
+    import_key,
+    list,
+    paths,
+    peer_id,
+    replicate,
+    set,
+    ssh_add,
+    ssh_list,
+    ssh_remove,
+};
 
 use super::args::*;
 
@@ -57,16 +77,74 @@ where
             println!("git includes: {}", paths.git_includes_dir().display());
             println!("keys: {}", paths.keys_dir().display());
         },
-        Command::SshAdd(SshAdd { id, time }) => {
-            let constraint = time.map_or(Constraint::Confirm, |seconds| Constraint::KeyLifetime {
-                seconds,
-            });
-            let (id, peer_id) = ssh_add::<S, _, _>(id, keys::prompt(), &[constraint]).await?;
+        Command::SshAdd(args) => {
+            let constraints = args.constraints();
+            let (id, peer_id) = ssh_add::<S, _, _>(args.id, keys::prompt(), &constraints).await?;
             println!(
                 "added key for profile id `{}` and peer id `{}`",
                 id, peer_id
             );
         },
+        Command::SshRm(SshRm { id }) => {
+            let (id, peer_id) = ssh_remove::<S, _>(id).await?;
+            println!(
+                "removed key for profile id `{}` and peer id `{}`",
+                id, peer_id
+            );
+        },
+        Command::SshLs(SshLs {}) => {
+            for status in ssh_list::<S>().await? {
+                let loaded = if status.loaded {
+                    "loaded"
+                } else {
+                    "not loaded"
+                };
+                println!("{} {} {}", status.profile, status.peer_id, loaded);
+            }
+        },
+        Command::ExportKey(ExportKey { id, output }) => {
+            let (id, peer_id, key) = export_key(id, keys::prompt())?;
+            let mut file = OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(&output)?;
+            file.write_all(key.as_ref())?;
+            println!(
+                "exported key for profile id `{}` and peer id `{}` to {}",
+                id,
+                peer_id,
+                output.display()
+            );
+        },
+        Command::ImportKey(ImportKey { input }) => {
+            let key = SecureBytes::read(fs::File::open(&input)?)?;
+            let (profile, peer_id) = import_key(keys::prompt(), &key)?;
+            println!("profile id: {}", profile.id());
+            println!("peer id: {}", peer_id);
+        },
+        Command::Replicate(Replicate { from, urn }) => {
+            let result = replicate::<S>(from.clone(), urn.clone()).await?;
+            if !result.updated_tips.is_empty() {
+                println!(
+                    "replicated `{}` from profile id `{}`, {} ref(s) updated",
+                    urn,
+                    from,
+                    result.updated_tips.len()
+                );
+            } else if result
+                .skipped
+                .iter()
+                .any(|skipped| skipped.reason == fetch::Skipped::NoMatchingRefs)
+            {
+                println!(
+                    "profile id `{}` has nothing to replicate for `{}`",
+                    from, urn
+                );
+            } else {
+                println!("`{}` is already up to date with profile id `{}`", urn, from);
+            }
+        },
     }
 
     Ok(())
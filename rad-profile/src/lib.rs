@@ -16,8 +16,14 @@ use librad::{
         PeerId,
         PublicKey,
         SecretKey,
+        SecureBytes,
+    },
+    git::{
+        replication::{self, ReplicateResult},
+        storage::{self, fetcher, read, ReadOnly, Storage},
+        tracking,
+        Urn,
     },
-    git::storage::{self, read, ReadOnly, Storage},
     paths::Paths,
     profile::{self, Profile, ProfileId, RadHome},
 };
@@ -31,17 +37,35 @@ pub enum Error {
     #[error(transparent)]
     AddKey(#[from] ssh::error::AddKey),
     #[error(transparent)]
+    Agent(#[from] keys::Error),
+    #[error(transparent)]
     Keystore(Box<dyn error::Error + Send + Sync + 'static>),
+    #[error(transparent)]
+    SecretKey(#[from] IntoSecretKeyError),
     #[error("no active profile was found, perhaps you need to create one")]
     NoActiveProfile,
     #[error("no profile was found for `{0}`")]
     NoProfile(ProfileId),
+    #[error("cannot replicate from `{0}` into itself")]
+    SameProfile(ProfileId),
+    #[error(transparent)]
+    Concurrent(#[from] fetcher::Info),
+    #[error(transparent)]
+    Git(#[from] git2::Error),
     #[error(transparent)]
     Profile(#[from] profile::Error),
     #[error(transparent)]
     Storage(#[from] storage::error::Init),
     #[error(transparent)]
     ReadOnly(#[from] read::error::Init),
+    #[error(transparent)]
+    RemoveKey(#[from] ssh::error::RemoveKey),
+    #[error(transparent)]
+    Replication(#[from] replication::Error),
+    #[error(transparent)]
+    Signer(#[from] rad_clib::storage::Error),
+    #[error(transparent)]
+    Tracking(#[from] tracking::Error),
 }
 
 impl<C> From<file::Error<C, IntoSecretKeyError>> for Error
@@ -65,6 +89,47 @@ where
 
 /// Initialise a [`Profile`], generating a new [`SecretKey`] and [`Storage`].
 pub fn create<C: Crypto>(crypto: C) -> Result<(Profile, PeerId), Error>
+where
+    C::Error: fmt::Debug + fmt::Display + Send + Sync + 'static,
+    C::SecretBox: Serialize + DeserializeOwned,
+{
+    init(crypto, SecretKey::new())
+}
+
+/// Initialise a [`Profile`] for an existing [`SecretKey`], as obtained from
+/// [`export_key`].
+///
+/// `key` is the raw secret key material.
+pub fn import_key<C: Crypto>(crypto: C, key: &SecureBytes) -> Result<(Profile, PeerId), Error>
+where
+    C::Error: fmt::Debug + fmt::Display + Send + Sync + 'static,
+    C::SecretBox: Serialize + DeserializeOwned,
+{
+    let key = SecretKey::from_secure(key)?;
+    init(crypto, key)
+}
+
+/// Get the raw secret key material of the given [`ProfileId`], or the active
+/// profile if no identifier is given.
+pub fn export_key<P, C>(id: P, crypto: C) -> Result<(ProfileId, PeerId, SecureBytes), Error>
+where
+    C: Crypto,
+    C::Error: fmt::Debug + fmt::Display + Send + Sync + 'static,
+    C::SecretBox: Serialize + DeserializeOwned,
+    P: Into<Option<ProfileId>>,
+{
+    let home = RadHome::default();
+    let profile = get_or_active(&home, id)?;
+    let store = keys::file_storage(&profile, crypto);
+    let key = store.get_key()?;
+    Ok((
+        profile.id().clone(),
+        PeerId::from(key.public_key),
+        SecureBytes::new(key.secret_key.as_ref().to_vec()),
+    ))
+}
+
+fn init<C: Crypto>(crypto: C, key: SecretKey) -> Result<(Profile, PeerId), Error>
 where
     C::Error: fmt::Debug + fmt::Display + Send + Sync + 'static,
     C::SecretBox: Serialize + DeserializeOwned,
@@ -72,7 +137,6 @@ where
     let home = RadHome::default();
     let profile = Profile::new(&home)?;
     Profile::set(&home, profile.id().clone())?;
-    let key = SecretKey::new();
     let mut store: FileStorage<C, PublicKey, SecretKey, _> = keys::file_storage(&profile, crypto);
     store.put_key(key.clone())?;
     Storage::open(profile.paths(), key.clone())?;
@@ -120,6 +184,33 @@ where
     get_or_active(&home, id).map(|p| p.paths().clone())
 }
 
+/// Replicate `urn` from the monorepo of the profile `from` into the monorepo
+/// of the active profile, using the local transport rather than the network.
+///
+/// The peer of `from` is tracked for `urn`, so that subsequent calls fetch
+/// its updates.
+pub async fn replicate<S>(from: ProfileId, urn: Urn) -> Result<ReplicateResult, Error>
+where
+    S: ClientStream + Unpin + 'static,
+{
+    let home = RadHome::default();
+    let profile = get_or_active(&home, None)?;
+    if *profile.id() == from {
+        return Err(Error::SameProfile(from));
+    }
+    let remote = ReadOnly::open(get_or_active(&home, from)?.paths())?;
+    let (_, storage) = rad_clib::storage::ssh::storage::<S>(&profile).await?;
+
+    tracking::track(&storage, &urn, *remote.peer_id())?;
+    let fetcher = fetcher::Local::new(urn, &remote).build(&storage)??;
+    Ok(replication::replicate(
+        &storage,
+        fetcher,
+        replication::Config::default(),
+        None,
+    )?)
+}
+
 /// Add a profile's [`SecretKey`] to the `ssh-agent`.
 pub async fn ssh_add<S, P, C>(
     id: P,
@@ -141,3 +232,46 @@ where
     ssh::add_key::<S>(key.secret_key.into(), constraints).await?;
     Ok((profile.id().clone(), peer_id))
 }
+
+/// Remove a profile's key from the `ssh-agent`.
+///
+/// Unlike [`ssh_add`], this does not require unlocking the key storage.
+pub async fn ssh_remove<S, P>(id: P) -> Result<(ProfileId, PeerId), Error>
+where
+    P: Into<Option<ProfileId>>,
+    S: ClientStream + Unpin + 'static,
+{
+    let home = RadHome::default();
+    let profile = get_or_active(&home, id)?;
+    let peer_id = *ReadOnly::open(profile.paths())?.peer_id();
+    ssh::remove_key::<S>(&(*peer_id).into()).await?;
+    Ok((profile.id().clone(), peer_id))
+}
+
+/// A profile, and whether its key is loaded in the `ssh-agent`.
+#[derive(Clone, Debug)]
+pub struct AgentStatus {
+    pub profile: ProfileId,
+    pub peer_id: PeerId,
+    pub loaded: bool,
+}
+
+/// Determine for all profiles whether their keys are loaded in the
+/// `ssh-agent`.
+pub async fn ssh_list<S>() -> Result<Vec<AgentStatus>, Error>
+where
+    S: ClientStream + Unpin + 'static,
+{
+    let loaded = keys::agent_keys::<S>().await?;
+    list()?
+        .into_iter()
+        .map(|profile| {
+            let peer_id = *ReadOnly::open(profile.paths())?.peer_id();
+            Ok(AgentStatus {
+                profile: profile.id().clone(),
+                peer_id,
+                loaded: loaded.iter().any(|key| key.peer_id == peer_id),
+            })
+        })
+        .collect()
+}
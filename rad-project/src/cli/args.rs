@@ -0,0 +1,75 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use structopt::StructOpt;
+
+use librad::{git::Urn, git_ext::Oid, PeerId};
+
+/// Manage the delegates of a project
+#[derive(Debug, StructOpt)]
+pub struct Args {
+    #[structopt(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    /// Add or remove delegates, and exchange the signatures needed to do so
+    Delegate(Delegate),
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Delegate {
+    #[structopt(subcommand)]
+    pub command: DelegateCommand,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum DelegateCommand {
+    /// Propose adding a delegate, signing the new project revision
+    Add(Change),
+    /// Propose removing a delegate, signing the new project revision
+    Remove(Change),
+    /// Sign the project revision proposed by a handoff
+    Sign(Handoff),
+    /// Merge the signatures collected by a handoff into the project identity
+    Collect(Handoff),
+    /// Withdraw a handoff you proposed
+    Withdraw(Handoff),
+    /// List the handoffs of a project, and who signed them
+    List(List),
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Change {
+    /// the project to change the delegates of
+    #[structopt(long)]
+    pub urn: Urn,
+    /// the key to add or remove
+    #[structopt(long, required_unless = "person", conflicts_with = "person")]
+    pub peer: Option<PeerId>,
+    /// the person identity to add or remove
+    #[structopt(long)]
+    pub person: Option<Urn>,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Handoff {
+    /// the project the handoff belongs to
+    #[structopt(long)]
+    pub urn: Urn,
+    /// the id of the handoff
+    pub id: Oid,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct List {
+    /// the project to list the handoffs of
+    #[structopt(long)]
+    pub urn: Urn,
+    /// output the handoffs as JSON
+    #[structopt(long)]
+    pub json: bool,
+}
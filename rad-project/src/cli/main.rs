@@ -0,0 +1,117 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use thrussh_agent::client::ClientStream;
+
+use librad::{
+    git::{
+        cobs::handoff::{self, Action},
+        identities::project,
+        storage::Storage,
+        Urn,
+    },
+    profile::Profile,
+};
+use rad_clib::storage::ssh;
+
+use super::args::*;
+
+pub async fn main<S>(Args { command }: Args) -> anyhow::Result<()>
+where
+    S: ClientStream + Unpin + 'static,
+{
+    let storage = storage::<S>().await?;
+    match command {
+        Command::Delegate(args) => delegate(&storage, args),
+    }
+}
+
+fn delegate(storage: &Storage, Delegate { command }: Delegate) -> anyhow::Result<()> {
+    match command {
+        DelegateCommand::Add(change) => {
+            let urn = change.urn.clone();
+            propose(storage, urn, Action::Add(delegate_of(change)))
+        },
+        DelegateCommand::Remove(change) => {
+            let urn = change.urn.clone();
+            propose(storage, urn, Action::Remove(delegate_of(change)))
+        },
+        DelegateCommand::Sign(Handoff { urn, id }) => {
+            let project = handoff::sign(storage, &urn, id)?;
+            println!("signed revision {} of `{}`", project.revision, urn);
+            Ok(())
+        },
+        DelegateCommand::Collect(Handoff { urn, id }) => {
+            let collected = handoff::collect(storage, &urn, id)?;
+            match project::verify(storage, &urn)? {
+                Some(verified) if verified.revision == collected.revision => {
+                    println!("revision {} of `{}` is in effect", collected.revision, urn)
+                },
+                _ => println!(
+                    "revision {} of `{}` has not been signed by a quorum of delegates yet",
+                    collected.revision, urn
+                ),
+            }
+            Ok(())
+        },
+        DelegateCommand::Withdraw(Handoff { urn, id }) => {
+            handoff::withdraw(storage, &urn, id)?;
+            Ok(())
+        },
+        DelegateCommand::List(List { urn, json }) => {
+            let handoffs = handoff::list(storage, &urn)?;
+            if json {
+                println!("{}", serde_json::to_string(&handoffs)?);
+            } else {
+                for handoff in handoffs {
+                    print(&handoff);
+                }
+            }
+            Ok(())
+        },
+    }
+}
+
+fn propose(storage: &Storage, urn: Urn, action: Action) -> anyhow::Result<()> {
+    let id = handoff::propose(storage, &urn, action)?;
+    println!("{}", id);
+    Ok(())
+}
+
+fn delegate_of(change: Change) -> handoff::Delegate {
+    match (change.peer, change.person) {
+        (Some(peer), _) => handoff::Delegate::Peer(peer),
+        (None, Some(person)) => handoff::Delegate::Person(person),
+        (None, None) => unreachable!("either `--peer` or `--person` is required"),
+    }
+}
+
+fn print(handoff: &handoff::Handoff) {
+    let action = match &handoff.action {
+        Action::Add(delegate) => format!("add {}", delegate),
+        Action::Remove(delegate) => format!("remove {}", delegate),
+    };
+    let status = if handoff.withdrawn {
+        " (withdrawn)"
+    } else {
+        ""
+    };
+    println!(
+        "{}\t{}\tproposed by {}{}",
+        handoff.id, action, handoff.proposer, status
+    );
+    for (peer, commit) in &handoff.signatures {
+        println!("\tsigned by {} at {}", peer, commit);
+    }
+}
+
+async fn storage<S>() -> anyhow::Result<Storage>
+where
+    S: ClientStream + Unpin + 'static,
+{
+    let profile = Profile::load()?;
+    let (_, storage) = ssh::storage::<S>(&profile).await?;
+    Ok(storage)
+}
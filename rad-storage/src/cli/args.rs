@@ -0,0 +1,114 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::path::PathBuf;
+
+use structopt::StructOpt;
+
+use librad::{git::Urn, git_ext::Oid, PeerId};
+
+/// Inspect the monorepo
+#[derive(Debug, StructOpt)]
+pub struct Args {
+    #[structopt(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    /// Show the number and size of the objects reachable from the refs of
+    /// an identity, or of every identity if none is given
+    Du(Du),
+    /// Show the refs, signed refs and identity history of a namespace. If
+    /// none of `--refs`, `--sigrefs` or `--identity` is given, all of them
+    /// are shown
+    Inspect(Inspect),
+    /// Restore the refs of a tracked peer to what they were signed as at a
+    /// previous `rad/signed_refs` commit
+    Restore(Restore),
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Du {
+    /// the identity to inspect
+    #[structopt(long)]
+    pub urn: Option<Urn>,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Inspect {
+    /// the identity to inspect
+    pub urn: Urn,
+    /// only show the refs, signed refs and identity of this peer
+    #[structopt(long)]
+    pub peer: Option<PeerId>,
+    /// show the refs of the namespace
+    #[structopt(long)]
+    pub refs: bool,
+    /// show the signed refs, and whether they are in sync with the refs
+    #[structopt(long)]
+    pub sigrefs: bool,
+    /// show the revisions of the identity document
+    #[structopt(long)]
+    pub identity: bool,
+    /// print JSON instead of text
+    #[structopt(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Restore {
+    /// the identity to restore the refs of
+    pub urn: Urn,
+    /// the peer whose refs to restore
+    #[structopt(long)]
+    pub peer: PeerId,
+    /// the `rad/signed_refs` commit of the peer to restore the refs to
+    #[structopt(long)]
+    pub to: Oid,
+    /// also perform updates which are not fast-forwards, eg. rewinding a
+    /// branch
+    #[structopt(long)]
+    pub force: bool,
+    /// only show the updates which would be performed
+    #[structopt(long)]
+    pub dry_run: bool,
+}
+
+/// Exchange the refs of a project offline, as git bundles
+#[derive(Debug, StructOpt)]
+pub struct Bundle {
+    #[structopt(subcommand)]
+    pub command: BundleCommand,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum BundleCommand {
+    /// Write your signed refs of a project to a bundle
+    Create(Create),
+    /// Verify a bundle created by a tracked peer, and update its refs to the
+    /// ones signed in the bundle
+    Apply(Apply),
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Create {
+    /// the identity to bundle the refs of
+    pub urn: Urn,
+    /// a previous `rad/signed_refs` commit of yours the receiver already has,
+    /// only the objects added since are bundled
+    #[structopt(long)]
+    pub since: Option<Oid>,
+    /// the file to write the bundle to, defaults to stdout
+    #[structopt(long, short, parse(from_os_str))]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct Apply {
+    /// the bundle to apply
+    #[structopt(parse(from_os_str))]
+    pub file: PathBuf,
+}
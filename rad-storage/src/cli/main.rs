@@ -0,0 +1,175 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    fs::File,
+    io::{self, BufReader},
+};
+
+use librad::{
+    git::{bundle, identities, restore},
+    profile::Profile,
+};
+use rad_clib::storage;
+
+use crate::inspect::{self, Options};
+
+use super::args::{Apply, Args, Bundle, BundleCommand, Command, Create, Du, Inspect, Restore};
+
+pub fn main(Args { command }: Args) -> anyhow::Result<()> {
+    match command {
+        Command::Du(du) => self::du(du),
+        Command::Inspect(args) => self::inspect(args),
+        Command::Restore(args) => self::restore(args),
+    }
+}
+
+fn du(Du { urn }: Du) -> anyhow::Result<()> {
+    let profile = Profile::load()?;
+    let storage = storage::read_only(&profile)?;
+
+    let urns = match urn {
+        Some(urn) => vec![urn],
+        None => identities::any::list_urns(&storage)?.collect::<Result<Vec<_>, _>>()?,
+    };
+    for urn in urns {
+        let stats = storage.count_namespace(&urn)?;
+        println!(
+            "{}\t{} objects\t{} bytes\tdepth {}",
+            urn, stats.objects, stats.bytes, stats.max_depth
+        );
+    }
+
+    Ok(())
+}
+
+fn inspect(
+    Inspect {
+        urn,
+        peer,
+        refs,
+        sigrefs,
+        identity,
+        json,
+    }: Inspect,
+) -> anyhow::Result<()> {
+    let profile = Profile::load()?;
+    let storage = storage::read_only(&profile)?;
+
+    let all = !(refs || sigrefs || identity);
+    let inspection = inspect::inspect(
+        &storage,
+        &urn,
+        Options {
+            peer,
+            refs: refs || all,
+            sigrefs: sigrefs || all,
+            identity: identity || all,
+        },
+    )?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&inspection)?);
+    } else {
+        print!("{}", inspection);
+    }
+
+    Ok(())
+}
+
+fn restore(
+    Restore {
+        urn,
+        peer,
+        to,
+        force,
+        dry_run,
+    }: Restore,
+) -> anyhow::Result<()> {
+    let profile = Profile::load()?;
+    let (_, storage) = storage::prompt::storage(&profile)?;
+
+    let plan = restore::plan(&storage, &urn, peer, to)?;
+    for update in &plan.updates {
+        let show = |oid: Option<_>| oid.map_or_else(|| "(none)".to_owned(), |oid| oid.to_string());
+        println!(
+            "{}{} {} -> {}",
+            if update.fast_forward { "  " } else { "! " },
+            update.name,
+            show(update.old),
+            show(update.new)
+        );
+    }
+    if plan.updates.is_empty() {
+        println!("nothing to restore");
+    } else if dry_run {
+        if !plan.is_fast_forward() {
+            println!("updates marked with `!` are not fast-forwards, and require --force");
+        }
+    } else {
+        restore::apply(&storage, &plan, force)?;
+        println!("restored {} refs", plan.updates.len());
+    }
+
+    Ok(())
+}
+
+pub fn bundle(Bundle { command }: Bundle) -> anyhow::Result<()> {
+    match command {
+        BundleCommand::Create(args) => self::create(args),
+        BundleCommand::Apply(args) => self::apply(args),
+    }
+}
+
+fn create(Create { urn, since, output }: Create) -> anyhow::Result<()> {
+    let profile = Profile::load()?;
+    let (_, storage) = storage::prompt::storage(&profile)?;
+
+    let created = match output {
+        Some(path) => bundle::create(&storage, &urn, since, File::create(path)?)?,
+        None => bundle::create(&storage, &urn, since, io::stdout())?,
+    };
+    // The bundle may be written to stdout
+    eprintln!(
+        "bundled {} refs and {} objects at {}",
+        created.refs.len(),
+        created.objects,
+        created.at
+    );
+    if !created.prerequisites.is_empty() {
+        eprintln!(
+            "the receiver needs to have {} already",
+            created
+                .prerequisites
+                .iter()
+                .map(|oid| oid.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
+    Ok(())
+}
+
+fn apply(Apply { file }: Apply) -> anyhow::Result<()> {
+    let profile = Profile::load()?;
+    let (_, storage) = storage::prompt::storage(&profile)?;
+
+    let plan = bundle::apply(&storage, BufReader::new(File::open(file)?))?;
+    for update in &plan.updates {
+        let show = |oid: Option<_>| oid.map_or_else(|| "(none)".to_owned(), |oid| oid.to_string());
+        println!(
+            "{} {} -> {}",
+            update.name,
+            show(update.old),
+            show(update.new)
+        );
+    }
+    println!(
+        "applied the signed refs of {} at {} to {}",
+        plan.peer, plan.at, plan.urn
+    );
+
+    Ok(())
+}
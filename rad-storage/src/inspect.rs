@@ -0,0 +1,297 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! Dump the layout of a namespace for debugging.
+//!
+//! An [`Inspection`] collects the refs of a namespace, the `rad/signed_refs`
+//! of the local and tracked peers along with whether they are still in sync
+//! with the refs they sign, and the history of the `rad/id` identity
+//! document.
+
+use std::{collections::BTreeMap, convert::TryFrom, fmt};
+
+use serde::Serialize;
+
+use librad::{
+    git::{
+        identities::SomeIdentity,
+        refs::{self, Refs},
+        storage::ReadOnly,
+        types::Namespace,
+        Urn,
+    },
+    git_ext::Oid,
+    PeerId,
+};
+
+/// What to include in an [`Inspection`].
+#[derive(Clone, Copy, Debug)]
+pub struct Options {
+    /// Restrict the refs and sigrefs to those of this peer.
+    pub peer: Option<PeerId>,
+    pub refs: bool,
+    pub sigrefs: bool,
+    pub identity: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Inspection {
+    pub urn: Urn,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refs: Option<BTreeMap<String, Target>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sigrefs: Option<Vec<Sigrefs>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identity: Option<Vec<Revision>>,
+}
+
+/// The target of a ref, relative to the namespace.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Target {
+    Direct(Oid),
+    Symbolic(String),
+}
+
+/// The `rad/signed_refs` of a peer.
+#[derive(Debug, Serialize)]
+pub struct Sigrefs {
+    pub peer: PeerId,
+    pub local: bool,
+    /// The commit `rad/signed_refs` points to.
+    pub at: Oid,
+    /// The commit time of [`Sigrefs::at`], in seconds since the epoch.
+    pub timestamp: i64,
+    /// The number of refs signed.
+    pub signed: usize,
+    /// The signed refs whose current target differs from the signed one.
+    pub stale: Vec<Stale>,
+}
+
+impl Sigrefs {
+    /// `true` if all signed refs point to what they were signed at.
+    pub fn is_fresh(&self) -> bool {
+        self.stale.is_empty()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Stale {
+    pub name: String,
+    pub signed: Oid,
+    /// The current target, or `None` if the ref is missing.
+    pub actual: Option<Oid>,
+}
+
+/// A revision of the identity document, starting from the most recent one.
+#[derive(Debug, Serialize)]
+pub struct Revision {
+    pub commit: Oid,
+    pub revision: Oid,
+    pub kind: &'static str,
+    pub name: String,
+    pub signers: Vec<PeerId>,
+}
+
+pub fn inspect(storage: &ReadOnly, urn: &Urn, opts: Options) -> anyhow::Result<Inspection> {
+    let repo = git2::Repository::open(storage.path())?;
+    let namespace = Namespace::from(urn);
+    let prefix = format!("refs/namespaces/{}/", namespace);
+    let peer_prefix = |peer: &PeerId| {
+        if peer == storage.peer_id() {
+            "refs/".to_owned()
+        } else {
+            format!("refs/remotes/{}/", peer)
+        }
+    };
+    let owned_by = |name: &str, peer: &PeerId| {
+        if peer == storage.peer_id() {
+            !name.starts_with("refs/remotes/")
+        } else {
+            name.starts_with(&peer_prefix(peer))
+        }
+    };
+
+    let mut all = BTreeMap::new();
+    for r in repo.references_glob(&format!("{}*", prefix))? {
+        let r = r?;
+        let name = match r.name() {
+            Some(name) => name.trim_start_matches(&prefix).to_owned(),
+            None => continue,
+        };
+        let target = match (r.target(), r.symbolic_target()) {
+            (Some(oid), _) => Target::Direct(oid.into()),
+            (None, Some(sym)) => Target::Symbolic(sym.trim_start_matches(&prefix).to_owned()),
+            (None, None) => continue,
+        };
+        all.insert(name, target);
+    }
+
+    let refs = if opts.refs {
+        Some(
+            all.iter()
+                .filter(|(name, _)| opts.peer.map(|peer| owned_by(name, &peer)).unwrap_or(true))
+                .map(|(name, target)| (name.clone(), target.clone()))
+                .collect(),
+        )
+    } else {
+        None
+    };
+
+    let sigrefs = if opts.sigrefs {
+        let mut sigrefs = Vec::new();
+        for (peer, at) in refs::tips(storage, urn)? {
+            if opts.peer.map(|p| p != peer).unwrap_or(false) {
+                continue;
+            }
+            let local = &peer == storage.peer_id();
+            let signed = match Refs::load(storage, urn, if local { None } else { Some(peer) })? {
+                Some(signed) => signed,
+                None => continue,
+            };
+            let prefix = peer_prefix(&peer);
+            let mut count = 0;
+            let mut stale = Vec::new();
+            for ((name, oid), category) in signed.iter_categorised() {
+                count += 1;
+                let name = format!("{}{}/{}", prefix, category, name);
+                let actual = match all.get(&name) {
+                    Some(Target::Direct(oid)) => Some(*oid),
+                    _ => None,
+                };
+                if actual.as_ref() != Some(oid) {
+                    stale.push(Stale {
+                        name,
+                        signed: *oid,
+                        actual,
+                    })
+                }
+            }
+            sigrefs.push(Sigrefs {
+                peer,
+                local,
+                at,
+                timestamp: repo.find_commit(at.into())?.time().seconds(),
+                signed: count,
+                stale,
+            })
+        }
+        Some(sigrefs)
+    } else {
+        None
+    };
+
+    let identity = if opts.identity {
+        let tip = match &opts.peer {
+            Some(peer) if peer != storage.peer_id() => format!("refs/remotes/{}/rad/id", peer),
+            _ => "refs/rad/id".to_owned(),
+        };
+        let mut revisions = Vec::new();
+        if let Some(Target::Direct(tip)) = all.get(&tip) {
+            let mut walk = repo.revwalk()?;
+            walk.simplify_first_parent()?;
+            walk.push((*tip).into())?;
+            for commit in walk {
+                revisions.push(revision(SomeIdentity::try_from((&repo, commit?))?));
+            }
+        }
+        Some(revisions)
+    } else {
+        None
+    };
+
+    Ok(Inspection {
+        urn: urn.clone(),
+        refs,
+        sigrefs,
+        identity,
+    })
+}
+
+fn revision(identity: SomeIdentity) -> Revision {
+    let (commit, revision, kind, name, signatures) = match identity {
+        SomeIdentity::Person(person) => (
+            person.content_id,
+            person.revision,
+            "person",
+            person.subject().name.to_string(),
+            person.signatures,
+        ),
+        SomeIdentity::Project(project) => (
+            project.content_id,
+            project.revision,
+            "project",
+            project.subject().name.to_string(),
+            project.signatures,
+        ),
+    };
+    Revision {
+        commit,
+        revision,
+        kind,
+        name,
+        signers: signatures.keys().cloned().map(PeerId::from).collect(),
+    }
+}
+
+impl fmt::Display for Inspection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.urn)?;
+        if let Some(refs) = &self.refs {
+            writeln!(f, "\nrefs:")?;
+            for (name, target) in refs {
+                match target {
+                    Target::Direct(oid) => writeln!(f, "  {} {}", oid, name)?,
+                    Target::Symbolic(sym) => writeln!(f, "  {} -> {}", name, sym)?,
+                }
+            }
+        }
+        if let Some(sigrefs) = &self.sigrefs {
+            writeln!(f, "\nsigrefs:")?;
+            for sigrefs in sigrefs {
+                writeln!(
+                    f,
+                    "  {}{} at {} ({}), {} refs, {}",
+                    sigrefs.peer,
+                    if sigrefs.local { " (local)" } else { "" },
+                    sigrefs.at,
+                    sigrefs.timestamp,
+                    sigrefs.signed,
+                    if sigrefs.is_fresh() {
+                        "fresh".to_owned()
+                    } else {
+                        format!("{} stale", sigrefs.stale.len())
+                    }
+                )?;
+                for stale in &sigrefs.stale {
+                    match &stale.actual {
+                        Some(actual) => writeln!(
+                            f,
+                            "    {}: signed {}, actual {}",
+                            stale.name, stale.signed, actual
+                        )?,
+                        None => {
+                            writeln!(f, "    {}: signed {}, missing", stale.name, stale.signed)?
+                        },
+                    }
+                }
+            }
+        }
+        if let Some(identity) = &self.identity {
+            writeln!(f, "\nidentity:")?;
+            for rev in identity {
+                writeln!(
+                    f,
+                    "  {} {} {:?} revision {}",
+                    rev.commit, rev.kind, rev.name, rev.revision
+                )?;
+                for signer in &rev.signers {
+                    writeln!(f, "    signed by {}", signer)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,55 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::path::PathBuf;
+
+use structopt::StructOpt;
+
+use librad::{git::Urn, PeerId};
+use rad_clib::ser::Format;
+
+/// Track a peer in the context of an identity, so that their view of it is
+/// replicated.
+#[derive(Debug, StructOpt)]
+pub struct Track {
+    /// the identity to track the peer for
+    #[structopt(long)]
+    pub urn: Urn,
+    /// the peer to track
+    #[structopt(long)]
+    pub peer: PeerId,
+    /// don't ask the running node to fetch from the peer right away
+    #[structopt(long)]
+    pub no_fetch: bool,
+    /// don't fetch the collaborative objects of the peer
+    #[structopt(long)]
+    pub no_cobs: bool,
+    /// path of the control socket of the node, defaults to the one of the
+    /// current profile
+    #[structopt(long)]
+    pub socket: Option<PathBuf>,
+    /// the output format (json or cbor), if none is provided then a
+    /// human-readable summary is printed
+    #[structopt(long)]
+    pub format: Option<Format>,
+}
+
+/// Stop tracking a peer in the context of an identity.
+#[derive(Debug, StructOpt)]
+pub struct Untrack {
+    /// the identity to untrack the peer for
+    #[structopt(long)]
+    pub urn: Urn,
+    /// the peer to untrack
+    #[structopt(long)]
+    pub peer: PeerId,
+    /// also remove the remote branches of the peer
+    #[structopt(long)]
+    pub prune: bool,
+    /// the output format (json or cbor), if none is provided then a
+    /// human-readable summary is printed
+    #[structopt(long)]
+    pub format: Option<Format>,
+}
@@ -0,0 +1,97 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use thrussh_agent::client::ClientStream;
+
+use librad::{git::storage::Storage, profile::Profile};
+use rad_clib::{
+    control::{self, Client},
+    ser::Format,
+    storage::ssh,
+};
+
+use crate::Tracking;
+
+use super::args::*;
+
+pub async fn track<S>(
+    Track {
+        urn,
+        peer,
+        no_fetch,
+        no_cobs,
+        socket,
+        format,
+    }: Track,
+) -> anyhow::Result<()>
+where
+    S: ClientStream + Unpin + 'static,
+{
+    let profile = Profile::load()?;
+    let (_, storage) = ssh::storage::<S>(&profile).await?;
+    let tracking = crate::track(&storage, urn, peer, !no_cobs)?;
+    print(&tracking, format)?;
+
+    if !no_fetch {
+        let socket = socket.unwrap_or_else(|| control::socket_path(profile.paths()));
+        let fetched = async {
+            let mut client = Client::connect(socket).await?;
+            client
+                .replicate(tracking.urn.clone(), tracking.peer, vec![])
+                .await
+        };
+        // Tracking succeeded, the node will fetch eventually
+        if let Err(err) = fetched.await {
+            eprintln!("failed to fetch from `{}`: {}", tracking.peer, err);
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn untrack<S>(
+    Untrack {
+        urn,
+        peer,
+        prune,
+        format,
+    }: Untrack,
+) -> anyhow::Result<()>
+where
+    S: ClientStream + Unpin + 'static,
+{
+    let storage = storage::<S>().await?;
+    let tracking = crate::untrack(&storage, urn, peer, prune)?;
+    print(&tracking, format)
+}
+
+async fn storage<S>() -> anyhow::Result<Storage>
+where
+    S: ClientStream + Unpin + 'static,
+{
+    let profile = Profile::load()?;
+    let (_, storage) = ssh::storage::<S>(&profile).await?;
+    Ok(storage)
+}
+
+fn print(tracking: &Tracking, format: Option<Format>) -> anyhow::Result<()> {
+    match format {
+        Some(format) => println!("{}", format.format(tracking)?),
+        None => {
+            let status = match (tracking.tracked, tracking.changed) {
+                (true, true) => "now tracking",
+                (true, false) => "already tracking",
+                (false, true) => "no longer tracking",
+                (false, false) => "was not tracking",
+            };
+            println!("{} `{}` for `{}`", status, tracking.peer, tracking.urn);
+            if tracking.tracked && !tracking.cobs {
+                println!("not fetching collaborative objects of `{}`", tracking.peer);
+            }
+        },
+    }
+
+    Ok(())
+}
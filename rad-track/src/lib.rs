@@ -0,0 +1,77 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use serde::Serialize;
+use thiserror::Error;
+
+use librad::{
+    git::{
+        storage::Storage,
+        tracking::{self, UntrackArgs},
+        Urn,
+    },
+    PeerId,
+};
+
+pub mod cli;
+
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    #[error(transparent)]
+    Tracking(#[from] tracking::Error),
+}
+
+/// The tracking relationship of a peer in the context of a [`Urn`], after a
+/// [`track`] or [`untrack`].
+#[derive(Clone, Debug, Serialize, minicbor::Encode)]
+#[serde(rename_all = "camelCase")]
+#[cbor(map)]
+pub struct Tracking {
+    #[n(0)]
+    pub urn: Urn,
+    #[n(1)]
+    pub peer: PeerId,
+    /// Whether `peer` is now tracked.
+    #[n(2)]
+    pub tracked: bool,
+    /// Whether the operation changed the tracking relationship.
+    #[n(3)]
+    pub changed: bool,
+    /// Whether the collaborative objects of `peer` are fetched.
+    #[n(4)]
+    pub cobs: bool,
+}
+
+/// Track `peer` in the context of `urn`, fetching its collaborative objects
+/// only if `cobs` is `true`.
+pub fn track(storage: &Storage, urn: Urn, peer: PeerId, cobs: bool) -> Result<Tracking, Error> {
+    let changed = tracking::track(storage, &urn, peer)?;
+    let cobs_changed = if cobs {
+        tracking::include_cobs(storage, &urn, peer, tracking::ALL_COBS)?
+    } else {
+        tracking::exclude_cobs(storage, &urn, peer, tracking::ALL_COBS)?
+    };
+    Ok(Tracking {
+        urn,
+        peer,
+        tracked: true,
+        changed: changed || cobs_changed,
+        cobs,
+    })
+}
+
+/// Untrack `peer` in the context of `urn`, optionally pruning its remote
+/// branches.
+pub fn untrack(storage: &Storage, urn: Urn, peer: PeerId, prune: bool) -> Result<Tracking, Error> {
+    let changed = tracking::untrack_with(storage, &urn, peer, UntrackArgs { prune })?;
+    Ok(Tracking {
+        urn,
+        peer,
+        tracked: false,
+        changed,
+        cobs: false,
+    })
+}
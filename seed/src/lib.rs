@@ -437,7 +437,7 @@ impl Node {
         let addr_hints = peer_info.seen_addrs.iter().copied().collect::<Vec<_>>();
 
         let result = {
-            let cfg = api.protocol_config().replication;
+            let cfg = api.protocol_config().replication.clone();
             let urn = urn.clone();
             api.using_storage(move |storage| {
                 let fetcher = fetcher::PeerToPeer::new(urn.clone(), peer_id, addr_hints)
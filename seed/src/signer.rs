@@ -5,7 +5,11 @@
 
 use std::io;
 
-use librad::{crypto::keystore::sign::ed25519, PeerId, SecStr, SecretKey};
+use librad::{
+    crypto::{keystore::sign::ed25519, SecureBytes},
+    PeerId,
+    SecretKey,
+};
 
 #[derive(Clone)]
 pub struct Signer {
@@ -19,14 +23,9 @@ impl From<Signer> for PeerId {
 }
 
 impl Signer {
-    pub fn new<R: io::Read>(mut r: R) -> Result<Self, io::Error> {
-        use librad::crypto::keystore::SecretKeyExt;
-
-        let mut bytes = Vec::new();
-        r.read_to_end(&mut bytes)?;
-
-        let sbytes: SecStr = bytes.into();
-        match SecretKey::from_bytes_and_meta(sbytes, &()) {
+    pub fn new<R: io::Read>(r: R) -> Result<Self, io::Error> {
+        let bytes = SecureBytes::read(r)?;
+        match SecretKey::from_secure(&bytes) {
             Ok(key) => Ok(Self { key }),
             Err(err) => Err(io::Error::new(io::ErrorKind::InvalidData, err)),
         }
@@ -212,6 +212,12 @@ impl<'a> Project<'a> {
         &self.cur
     }
 
+    /// Continue the history of this project as `dev`, without signing
+    /// anything.
+    pub fn handover(self, dev: Device<'a>) -> Self {
+        Self { dev, ..self }
+    }
+
     pub fn update(
         self,
         delegations: impl Into<Option<IndirectDelegation>>,
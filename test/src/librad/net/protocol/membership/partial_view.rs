@@ -8,7 +8,13 @@ use std::{collections::BTreeSet, iter};
 use proptest::prelude::*;
 
 use librad::{
-    net::protocol::{membership::PartialView, PartialPeerInfo, PeerAdvertisement},
+    net::protocol::{
+        membership::PartialView,
+        version::Versions,
+        Features,
+        PartialPeerInfo,
+        PeerAdvertisement,
+    },
     PeerId,
 };
 
@@ -28,6 +34,8 @@ pub fn blank_peer_info<A: Ord + Clone>(peer_id: PeerId) -> PartialPeerInfo<A> {
         advertised_info: Some(PeerAdvertisement {
             listen_addrs: iter::empty().into(),
             capabilities: BTreeSet::new(),
+            versions: Versions::LEGACY,
+            features: Features::empty(),
         }),
         seen_addrs: iter::empty().into(),
     }
@@ -3,5 +3,6 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+pub mod agent;
 pub mod identities;
 pub mod testnet;
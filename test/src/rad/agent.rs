@@ -0,0 +1,197 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! A mock `ssh-agent`, serving the part of the protocol the `rad` tools use:
+//! listing, adding and removing ed25519 keys.
+//!
+//! Clients find the agent via `SSH_AUTH_SOCK`, so tests using it need to run
+//! in a subprocess.
+
+use std::{
+    io::{self, Read, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use tempfile::TempDir;
+
+use librad::PublicKey;
+
+const FAILURE: u8 = 5;
+const SUCCESS: u8 = 6;
+const REQUEST_IDENTITIES: u8 = 11;
+const IDENTITIES_ANSWER: u8 = 12;
+const ADD_IDENTITY: u8 = 17;
+const REMOVE_IDENTITY: u8 = 18;
+const ADD_ID_CONSTRAINED: u8 = 25;
+
+const CONSTRAIN_LIFETIME: u8 = 1;
+const CONSTRAIN_CONFIRM: u8 = 2;
+
+const KEY_TYPE: &[u8] = b"ssh-ed25519";
+
+/// A key held by the [`Agent`], along with the constraints it was added with.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Key {
+    pub public: PublicKey,
+    pub lifetime: Option<u32>,
+    pub confirm: bool,
+}
+
+impl From<PublicKey> for Key {
+    fn from(public: PublicKey) -> Self {
+        Self {
+            public,
+            lifetime: None,
+            confirm: false,
+        }
+    }
+}
+
+pub struct Agent {
+    socket: PathBuf,
+    keys: Arc<Mutex<Vec<Key>>>,
+    _tmp: TempDir,
+}
+
+impl Agent {
+    /// Serve on a socket in a new temporary directory, only accessible by the
+    /// current user.
+    pub fn spawn() -> io::Result<Self> {
+        let tmp = tempfile::tempdir()?;
+        let socket = tmp.path().join("agent.sock");
+        let listener = UnixListener::bind(&socket)?;
+        let keys = Arc::new(Mutex::new(Vec::new()));
+        thread::spawn({
+            let keys = Arc::clone(&keys);
+            move || {
+                for stream in listener.incoming() {
+                    match stream {
+                        Ok(stream) => {
+                            // A broken connection doesn't affect the next one
+                            serve(stream, &keys).ok();
+                        },
+                        Err(_) => break,
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            socket,
+            keys,
+            _tmp: tmp,
+        })
+    }
+
+    /// The value of `SSH_AUTH_SOCK` for clients to find the agent.
+    pub fn socket(&self) -> &Path {
+        &self.socket
+    }
+
+    /// The keys currently held.
+    pub fn keys(&self) -> Vec<Key> {
+        self.keys.lock().unwrap().clone()
+    }
+
+    /// Add `key`, as if by `ssh-add`.
+    pub fn add(&self, key: impl Into<Key>) {
+        self.keys.lock().unwrap().push(key.into())
+    }
+}
+
+fn serve(mut stream: UnixStream, keys: &Mutex<Vec<Key>>) -> io::Result<()> {
+    loop {
+        let mut len = [0; 4];
+        match stream.read_exact(&mut len) {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            res => res?,
+        }
+        let mut msg = vec![0; u32::from_be_bytes(len) as usize];
+        stream.read_exact(&mut msg)?;
+
+        let response = match handle(&msg, &mut keys.lock().unwrap()) {
+            Some(response) => response,
+            None => vec![FAILURE],
+        };
+        stream.write_all(&(response.len() as u32).to_be_bytes())?;
+        stream.write_all(&response)?;
+    }
+}
+
+fn handle(msg: &[u8], keys: &mut Vec<Key>) -> Option<Vec<u8>> {
+    let (typ, mut msg) = msg.split_first()?;
+    match *typ {
+        REQUEST_IDENTITIES => {
+            let mut response = vec![IDENTITIES_ANSWER];
+            response.extend_from_slice(&(keys.len() as u32).to_be_bytes());
+            for key in keys.iter() {
+                put_string(&mut response, &key_blob(&key.public));
+                put_string(&mut response, b"");
+            }
+            Some(response)
+        },
+        ADD_IDENTITY | ADD_ID_CONSTRAINED => {
+            if get_string(&mut msg)? != KEY_TYPE {
+                return None;
+            }
+            let public = PublicKey::from_slice(get_string(&mut msg)?)?;
+            let _secret = get_string(&mut msg)?;
+            let _comment = get_string(&mut msg)?;
+            let mut key = Key::from(public);
+            while let Some((constraint, rest)) = msg.split_first() {
+                msg = rest;
+                match *constraint {
+                    CONSTRAIN_LIFETIME => key.lifetime = Some(get_u32(&mut msg)?),
+                    CONSTRAIN_CONFIRM => key.confirm = true,
+                    _ => return None,
+                }
+            }
+            keys.retain(|k| k.public != key.public);
+            keys.push(key);
+            Some(vec![SUCCESS])
+        },
+        REMOVE_IDENTITY => {
+            let blob = get_string(&mut msg)?;
+            let before = keys.len();
+            keys.retain(|k| key_blob(&k.public) != blob);
+            (keys.len() < before).then(|| vec![SUCCESS])
+        },
+        _ => None,
+    }
+}
+
+fn key_blob(key: &PublicKey) -> Vec<u8> {
+    let mut blob = Vec::new();
+    put_string(&mut blob, KEY_TYPE);
+    put_string(&mut blob, key.as_ref());
+    blob
+}
+
+fn put_string(buf: &mut Vec<u8>, s: &[u8]) {
+    buf.extend_from_slice(&(s.len() as u32).to_be_bytes());
+    buf.extend_from_slice(s);
+}
+
+fn get_u32(buf: &mut &[u8]) -> Option<u32> {
+    if buf.len() < 4 {
+        return None;
+    }
+    let (n, rest) = buf.split_at(4);
+    *buf = rest;
+    Some(u32::from_be_bytes([n[0], n[1], n[2], n[3]]))
+}
+
+fn get_string<'a>(buf: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let len = get_u32(buf)? as usize;
+    if buf.len() < len {
+        return None;
+    }
+    let (s, rest) = buf.split_at(len);
+    *buf = rest;
+    Some(s)
+}
@@ -56,7 +56,7 @@ impl TestPerson {
         let remote_peer = from.local_peer_id();
         let remote_addrs = from.listen_addrs();
         let urn = self.owner.urn();
-        let cfg = to.protocol_config().replication;
+        let cfg = to.protocol_config().replication.clone();
         let res = to
             .using_storage(move |storage| {
                 let fetcher = fetcher::PeerToPeer::new(urn, remote_peer, remote_addrs)
@@ -151,12 +151,29 @@ impl TestProject {
         A: Deref<Target = Peer<S>> + LocalInfo<Addr = SocketAddr>,
         B: Deref<Target = Peer<S>>,
 
+        S: Signer + Clone,
+    {
+        self.pull_with(from, to, to.protocol_config().replication.clone())
+            .await
+    }
+
+    /// Like [`TestProject::pull`], but using the given replication config
+    /// instead of the one of peer `B`.
+    pub async fn pull_with<A, B, S>(
+        &self,
+        from: &A,
+        to: &B,
+        cfg: replication::Config,
+    ) -> anyhow::Result<ReplicateResult>
+    where
+        A: Deref<Target = Peer<S>> + LocalInfo<Addr = SocketAddr>,
+        B: Deref<Target = Peer<S>>,
+
         S: Signer + Clone,
     {
         let remote_peer = from.local_peer_id();
         let remote_addrs = from.listen_addrs();
         let urn = self.project.urn();
-        let cfg = to.protocol_config().replication;
         let res = to
             .using_storage(move |storage| -> anyhow::Result<ReplicateResult> {
                 let fetcher = fetcher::PeerToPeer::new(urn, remote_peer, remote_addrs)
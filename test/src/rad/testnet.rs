@@ -10,21 +10,24 @@ use std::{
     net::{Ipv4Addr, SocketAddr, SocketAddrV4, ToSocketAddrs as _},
     num::NonZeroUsize,
     ops::Deref,
+    time::Duration,
 };
 
 use futures::{
     future::{self, FutureExt as _},
     stream::{StreamExt as _, TryStreamExt as _},
 };
+use nonempty::NonEmpty;
 use tempfile::{tempdir, TempDir};
 
 use librad::{
-    git,
+    git::{self, replication},
     net::{
         connection::{LocalAddr, LocalPeer},
         discovery::{self, Discovery as _},
         peer::{self, Peer},
         protocol,
+        tofu,
         Network,
     },
     paths::Paths,
@@ -32,6 +35,8 @@ use librad::{
     SecretKey,
 };
 
+mod latency;
+
 lazy_static! {
     static ref LOCALHOST_ANY: SocketAddr =
         SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 0));
@@ -42,11 +47,16 @@ pub struct BoundTestPeer {
     bound: protocol::Bound<peer::PeerStorage>,
     disco: discovery::Static,
     tmp: TempDir,
+    /// The address of the [`latency::Relay`] in front of the peer, if any.
+    relay: Option<SocketAddr>,
 }
 
 impl BoundTestPeer {
     pub fn listen_addrs(&self) -> Vec<SocketAddr> {
-        self.bound.listen_addrs()
+        match self.relay {
+            Some(relay) => vec![relay],
+            None => self.bound.listen_addrs(),
+        }
     }
 }
 
@@ -60,7 +70,7 @@ impl LocalAddr for BoundTestPeer {
     type Addr = SocketAddr;
 
     fn listen_addrs(&self) -> Vec<Self::Addr> {
-        self.bound.listen_addrs()
+        BoundTestPeer::listen_addrs(self)
     }
 }
 
@@ -99,7 +109,31 @@ impl LocalAddr for RunningTestPeer {
     }
 }
 
-async fn boot<I, J>(seeds: I) -> anyhow::Result<BoundTestPeer>
+/// The configuration of a single peer of the test network.
+///
+/// The [`Default`] is the same for every peer.
+#[derive(Clone, Debug, Default)]
+pub struct PeerConfig {
+    /// Limits and policies applied when the peer replicates.
+    pub replication: replication::Config,
+    /// Which remote peers may cause a replication by fetching from the peer.
+    pub graft: protocol::config::Graft,
+    /// Whether gossip about identities the peer doesn't have yet causes them
+    /// to be cloned.
+    pub replicate_unknown: peer::storage::ReplicateUnknown,
+    /// If set, every datagram sent to or from the peer is delayed by this
+    /// much.
+    ///
+    /// Only applies to connections other peers establish to the peer, which
+    /// reach it through a relay. Connections the peer establishes itself are
+    /// not delayed.
+    pub latency: Option<Duration>,
+    /// If set, the peer pins the first peer seen at every address it connects
+    /// to, see [`tofu`].
+    pub tofu: Option<tofu::Policy>,
+}
+
+async fn boot<I, J>(config: &PeerConfig, seeds: I) -> anyhow::Result<BoundTestPeer>
 where
     I: IntoIterator<Item = (PeerId, J)>,
     J: IntoIterator<Item = SocketAddr>,
@@ -111,30 +145,56 @@ where
     // eagerly init so we error out early when it fails
     git::storage::Storage::init(&paths, key.clone())?;
 
+    let relay = match config.latency {
+        Some(latency) => {
+            let relay = latency::Relay::bind().await?;
+            Some((relay.local_addr()?, relay, latency))
+        },
+        None => None,
+    };
+
     let listen_addr = *LOCALHOST_ANY;
     let protocol = protocol::Config {
         paths,
-        listen_addr,
-        advertised_addrs: None,
+        listen_addrs: NonEmpty::new(listen_addr),
+        advertised_addrs: relay.as_ref().map(|(addr, _, _)| NonEmpty::new(*addr)),
+        dialer: Default::default(),
         membership: Default::default(),
         network: Network::Custom(b"localtestnet".as_ref().into()),
-        replication: Default::default(),
+        replication: config.replication.clone(),
         fetch: Default::default(),
+        graft: config.graft,
+        serve: Default::default(),
         rate_limits: Default::default(),
+        pinned: Default::default(),
+        transport: Default::default(),
+        tofu: config.tofu,
     };
     let disco = seeds.into_iter().collect::<discovery::Static>();
     let peer = Peer::new(peer::Config {
         signer: key,
         protocol,
-        storage: Default::default(),
+        storage: peer::config::Storage {
+            protocol: peer::config::ProtocolStorage {
+                replicate_unknown: config.replicate_unknown,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
     })?;
     let bound = peer.bind().await?;
 
+    let relay = relay.map(|(addr, relay, latency)| {
+        tokio::spawn(relay.forward(bound.listen_addrs()[0], latency));
+        addr
+    });
+
     Ok(BoundTestPeer {
         peer,
         bound,
         disco,
         tmp,
+        relay,
     })
 }
 
@@ -215,50 +275,137 @@ impl Default for Bootstrap {
     }
 }
 
+/// A test network of `num_peers` peers with the same [`PeerConfig`].
+///
+/// Use a [`Builder`] to configure peers individually.
 pub struct Config {
     pub num_peers: NonZeroUsize,
     pub min_connected: usize,
     pub bootstrap: Bootstrap,
 }
 
-async fn bootstrap(config: Config) -> anyhow::Result<Vec<BoundTestPeer>> {
-    let num_peers = config.num_peers.get();
-    let mut peers = Vec::with_capacity(num_peers);
+impl From<Config> for Builder {
+    fn from(
+        Config {
+            num_peers,
+            min_connected,
+            bootstrap,
+        }: Config,
+    ) -> Self {
+        Self::new()
+            .peers(num_peers.get(), PeerConfig::default())
+            .min_connected(min_connected)
+            .bootstrap(bootstrap)
+    }
+}
+
+/// Declares the peers of a test network one by one, each with its own
+/// [`PeerConfig`].
+///
+/// Peers are started in the order they were added, which is also their order
+/// in [`Testnet::peers`].
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use radicle_link_test::rad::testnet::{Builder, PeerConfig};
+/// let net = Builder::new()
+///     .peer(PeerConfig::default())
+///     .peer(PeerConfig {
+///         latency: Some(Duration::from_millis(50)),
+///         ..Default::default()
+///     })
+///     .min_connected(2)
+///     .run()
+///     .unwrap();
+/// ```
+#[derive(Default)]
+pub struct Builder {
+    peers: Vec<PeerConfig>,
+    min_connected: usize,
+    bootstrap: Bootstrap,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a peer.
+    pub fn peer(mut self, config: PeerConfig) -> Self {
+        self.peers.push(config);
+        self
+    }
+
+    /// Add `n` peers with the same `config`.
+    pub fn peers(mut self, n: usize, config: PeerConfig) -> Self {
+        self.peers.extend(std::iter::repeat(config).take(n));
+        self
+    }
+
+    /// Wait for this many peers to have joined the membership before
+    /// returning from [`Builder::run`].
+    pub fn min_connected(mut self, min_connected: usize) -> Self {
+        self.min_connected = min_connected;
+        self
+    }
+
+    pub fn bootstrap(mut self, bootstrap: Bootstrap) -> Self {
+        self.bootstrap = bootstrap;
+        self
+    }
+
+    /// Start the test network.
+    ///
+    /// # Errors
+    ///
+    /// If no peers were added, or any of them fails to start.
+    pub fn run(self) -> anyhow::Result<Testnet> {
+        run_with(self)
+    }
+}
 
-    match config.bootstrap {
+async fn bootstrap(
+    configs: Vec<PeerConfig>,
+    bootstrap: Bootstrap,
+) -> anyhow::Result<Vec<BoundTestPeer>> {
+    let mut peers = Vec::with_capacity(configs.len());
+
+    match bootstrap {
         Bootstrap::None => {
-            for _ in 0..num_peers {
-                let peer = boot::<Option<_>, Option<_>>(None).await?;
+            for config in &configs {
+                let peer = boot::<Option<_>, Option<_>>(config, None).await?;
                 peers.push(peer);
             }
         },
 
         Bootstrap::First => {
-            let bootstrap_node = boot::<Option<_>, Option<_>>(None).await?;
+            let bootstrap_node = boot::<Option<_>, Option<_>>(&configs[0], None).await?;
             let bootstrap = Some((
                 bootstrap_node.bound.peer_id(),
                 bootstrap_node.listen_addrs(),
             ));
             peers.push(bootstrap_node);
 
-            for _ in 1..num_peers {
-                let peer = boot(bootstrap.clone()).await?;
+            for config in &configs[1..] {
+                let peer = boot(config, bootstrap.clone()).await?;
                 peers.push(peer);
             }
         },
 
         Bootstrap::Prev => {
             let mut bootstrap: Option<(PeerId, Vec<SocketAddr>)> = None;
-            for _ in 0..num_peers {
-                let peer = boot(bootstrap.take()).await?;
-                bootstrap = Some((peer.bound.peer_id(), peer.bound.listen_addrs()));
+            for config in &configs {
+                let peer = boot(config, bootstrap.take()).await?;
+                bootstrap = Some((peer.bound.peer_id(), peer.listen_addrs()));
                 peers.push(peer);
             }
         },
 
         Bootstrap::Fixed(bootstrap) => {
-            for _ in 0..num_peers {
-                let peer = boot(bootstrap.clone()).await?;
+            for config in &configs {
+                let peer = boot(config, bootstrap.clone()).await?;
                 peers.push(peer);
             }
         },
@@ -304,12 +451,23 @@ impl Drop for Testnet {
 }
 
 pub fn run(config: Config) -> anyhow::Result<Testnet> {
+    run_with(config.into())
+}
+
+fn run_with(
+    Builder {
+        peers,
+        min_connected,
+        bootstrap,
+    }: Builder,
+) -> anyhow::Result<Testnet> {
+    anyhow::ensure!(!peers.is_empty(), "a testnet needs at least one peer");
+
     let rt = tokio::runtime::Builder::new_current_thread()
         .enable_all()
         .build()?;
 
-    let min_connected = config.min_connected;
-    let bootstrapped = rt.block_on(bootstrap(config))?;
+    let bootstrapped = rt.block_on(self::bootstrap(peers, bootstrap))?;
     let num_peers = bootstrapped.len();
 
     let mut sig = Vec::with_capacity(num_peers);
@@ -319,17 +477,16 @@ pub fn run(config: Config) -> anyhow::Result<Testnet> {
     let mut events = Vec::with_capacity(num_peers);
 
     for bound in bootstrapped {
+        let listen_addrs = bound.listen_addrs();
         let BoundTestPeer {
             tmp,
             peer,
             bound,
             disco,
+            relay: _,
         } = bound;
         events.push(peer.subscribe());
-        peers.push(RunningTestPeer {
-            peer,
-            listen_addrs: bound.listen_addrs(),
-        });
+        peers.push(RunningTestPeer { peer, listen_addrs });
         let (shutdown, run) = bound.accept(disco.discover());
         sig.push(Box::new(shutdown) as Box<dyn FnOnce()>);
         main.push(rt.spawn(async move {
@@ -0,0 +1,78 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+//! A UDP relay which delays every datagram, to simulate latency between
+//! peers.
+
+use std::{collections::HashMap, io, net::SocketAddr, sync::Arc, time::Duration};
+
+use tokio::{net::UdpSocket, time::sleep};
+
+use super::LOCALHOST_ANY;
+
+/// A relay socket, which doesn't forward anything until [`Relay::forward`] is
+/// called.
+pub struct Relay {
+    socket: UdpSocket,
+}
+
+impl Relay {
+    pub async fn bind() -> io::Result<Self> {
+        Ok(Self {
+            socket: UdpSocket::bind(*LOCALHOST_ANY).await?,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Forward datagrams received by the relay to `upstream`, and the replies
+    /// of `upstream` back to their sender, delaying each of them by `delay`.
+    ///
+    /// Every sender is relayed from a socket of its own, such that `upstream`
+    /// can tell them apart.
+    pub async fn forward(self, upstream: SocketAddr, delay: Duration) -> io::Result<()> {
+        let socket = Arc::new(self.socket);
+        let mut senders: HashMap<SocketAddr, Arc<UdpSocket>> = HashMap::new();
+        let mut buf = vec![0; u16::MAX as usize];
+        loop {
+            let (n, sender) = socket.recv_from(&mut buf).await?;
+            let relayed = match senders.get(&sender) {
+                Some(relayed) => relayed.clone(),
+                None => {
+                    let relayed = Arc::new(UdpSocket::bind(*LOCALHOST_ANY).await?);
+                    relayed.connect(upstream).await?;
+                    tokio::spawn(backward(relayed.clone(), socket.clone(), sender, delay));
+                    senders.insert(sender, relayed.clone());
+                    relayed
+                },
+            };
+            let datagram = buf[..n].to_vec();
+            tokio::spawn(async move {
+                sleep(delay).await;
+                relayed.send(&datagram).await.ok();
+            });
+        }
+    }
+}
+
+async fn backward(
+    relayed: Arc<UdpSocket>,
+    socket: Arc<UdpSocket>,
+    sender: SocketAddr,
+    delay: Duration,
+) -> io::Result<()> {
+    let mut buf = vec![0; u16::MAX as usize];
+    loop {
+        let n = relayed.recv(&mut buf).await?;
+        let datagram = buf[..n].to_vec();
+        let socket = socket.clone();
+        tokio::spawn(async move {
+            sleep(delay).await;
+            socket.send_to(&datagram, sender).await.ok();
+        });
+    }
+}
@@ -3,8 +3,12 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+mod checkout;
 mod collaboration;
+mod handoff;
 mod menage;
+mod parallel_verification;
+mod per_peer_config;
 mod tracked_references;
 mod updated_delegate;
 mod working_copy;
@@ -0,0 +1,163 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{convert::TryFrom as _, ops::Index as _};
+
+use tempfile::tempdir;
+
+use librad::{
+    git::{
+        checkout::{self, Options, View},
+        identities,
+        local::url::LocalUrl,
+        storage::{self, ReadOnlyStorage as _},
+        types::{remote::LocalPushspec, Fetchspec, Force, Namespace, Reference, Remote},
+        Urn,
+    },
+    git_ext as ext,
+    net::peer::Peer,
+    reflike,
+    refspec_pattern,
+    Signer,
+};
+
+use crate::{
+    git::create_commit,
+    logging,
+    rad::{
+        identities::{TestPerson, TestProject},
+        testnet,
+    },
+};
+
+fn config() -> testnet::Config {
+    testnet::Config {
+        num_peers: nonzero!(2usize),
+        min_connected: 2,
+        bootstrap: testnet::Bootstrap::from_env(),
+    }
+}
+
+/// peer2 checks out the view of peer1, which forks the default branch into
+/// the view of peer2. After peer1 publishes another commit and peer2
+/// replicates it, refreshing the working copy of peer2 fetches it.
+#[test]
+fn checkout_peer_and_refresh() {
+    logging::init();
+
+    let net = testnet::run(config()).unwrap();
+    net.enter(async {
+        let peer1 = net.peers().index(0);
+        let peer2 = net.peers().index(1);
+
+        let proj = peer1
+            .using_storage(move |store| TestProject::create(store))
+            .await
+            .unwrap()
+            .unwrap();
+        peer2
+            .using_storage(move |store| -> anyhow::Result<()> {
+                let person = TestPerson::create(store)?;
+                let local_id = identities::local::load(store, person.owner.urn())?
+                    .expect("local id must exist as we just created it");
+                storage::Config::try_from(store)?.set_user(local_id)?;
+                Ok(())
+            })
+            .await
+            .unwrap()
+            .unwrap();
+
+        let tmp = tempdir().unwrap();
+        let urn = proj.project.urn();
+
+        let publisher = git2::Repository::init(tmp.path().join("peer1")).unwrap();
+        let first = create_commit(&publisher, reflike!("refs/heads/next")).unwrap();
+        publish(peer1, &publisher, &urn);
+        proj.pull(peer1, peer2).await.unwrap();
+
+        let repo = checkout::checkout(
+            &peer2.protocol_config().paths,
+            (*peer2).clone(),
+            &urn,
+            Options {
+                path: tmp.path().join("peer2"),
+                view: View::Peer(peer1.peer_id()),
+                branch: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(repo.head().unwrap().target(), Some(first));
+        assert_eq!(
+            repo.config()
+                .unwrap()
+                .get_string("branch.next.remote")
+                .unwrap(),
+            "rad"
+        );
+
+        let forked = peer2
+            .using_storage({
+                let urn = urn.clone();
+                move |store| {
+                    store
+                        .reference(&Reference::head(
+                            Namespace::from(&urn),
+                            None,
+                            reflike!("next"),
+                        ))
+                        .unwrap()
+                        .and_then(|r| r.target())
+                }
+            })
+            .await
+            .unwrap();
+        assert_eq!(forked, Some(first));
+
+        let second = {
+            let parent = publisher.find_commit(first).unwrap();
+            let tree = parent.tree().unwrap();
+            let author = git2::Signature::now("The Animal", "animal@muppets.com").unwrap();
+            publisher
+                .commit(
+                    Some("refs/heads/next"),
+                    &author,
+                    &author,
+                    "Second commit",
+                    &tree,
+                    &[&parent],
+                )
+                .unwrap()
+        };
+        publish(peer1, &publisher, &urn);
+        proj.pull(peer1, peer2).await.unwrap();
+
+        let updated =
+            checkout::refresh(&peer2.protocol_config().paths, (*peer2).clone(), &repo).unwrap();
+        let remote_next = ext::RefLike::try_from(format!(
+            "refs/remotes/{}@{}/next",
+            proj.owner.subject().name,
+            peer1.peer_id()
+        ))
+        .unwrap();
+        assert!(updated.contains(&(remote_next, second)));
+    })
+}
+
+fn publish<S>(peer: &Peer<S>, repo: &git2::Repository, urn: &Urn)
+where
+    S: Signer + Clone,
+{
+    let mut rad = Remote::rad_remote::<_, Fetchspec>(LocalUrl::from(urn.clone()), None);
+    rad.push(
+        peer.clone(),
+        repo,
+        LocalPushspec::Matching {
+            pattern: refspec_pattern!("refs/heads/*"),
+            force: Force::True,
+        },
+    )
+    .unwrap()
+    .for_each(drop);
+}
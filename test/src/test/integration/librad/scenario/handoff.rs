@@ -0,0 +1,101 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::ops::Index as _;
+
+use librad::git::{
+    cobs::handoff::{self, Action, Delegate},
+    identities,
+    tracking,
+};
+
+use crate::{
+    logging,
+    rad::{identities::TestProject, testnet},
+};
+
+fn config() -> testnet::Config {
+    testnet::Config {
+        num_peers: nonzero!(2usize),
+        min_connected: 2,
+        bootstrap: testnet::Bootstrap::from_env(),
+    }
+}
+
+#[test]
+fn add_delegate_via_handoff() {
+    logging::init();
+
+    let net = testnet::run(config()).unwrap();
+    net.enter(async {
+        let alice = net.peers().index(0);
+        let bob = net.peers().index(1);
+        let alice_id = alice.peer_id();
+        let bob_id = bob.peer_id();
+
+        let proj = alice
+            .using_storage(move |s| TestProject::create(s))
+            .await
+            .unwrap()
+            .unwrap();
+        proj.pull(alice, bob).await.unwrap();
+        let urn = proj.project.urn();
+
+        let id = alice
+            .using_storage({
+                let urn = urn.clone();
+                move |s| -> anyhow::Result<_> {
+                    tracking::track(s, &urn, bob_id)?;
+                    Ok(handoff::propose(
+                        s,
+                        &urn,
+                        Action::Add(Delegate::Peer(bob_id)),
+                    )?)
+                }
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        proj.pull(alice, bob).await.unwrap();
+
+        let signed = bob
+            .using_storage({
+                let urn = urn.clone();
+                move |s| handoff::sign(s, &urn, id)
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        proj.pull(bob, alice).await.unwrap();
+
+        let (handoff, verified) = alice
+            .using_storage({
+                let urn = urn.clone();
+                move |s| -> anyhow::Result<_> {
+                    let handoff = handoff::get(s, &urn, id)?.unwrap();
+                    handoff::collect(s, &urn, id)?;
+                    Ok((handoff, identities::project::verify(s, &urn)?.unwrap()))
+                }
+            })
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(handoff.proposer, alice_id);
+        assert_eq!(handoff.revision, signed.revision);
+        assert_eq!(handoff.signatures.keys().copied().collect::<Vec<_>>(), {
+            let mut peers = vec![alice_id, bob_id];
+            peers.sort();
+            peers
+        });
+        assert_eq!(verified.revision, handoff.revision);
+        assert!(verified
+            .delegations()
+            .iter()
+            .direct()
+            .any(|key| key == bob_id.as_public_key()));
+        assert!(verified.signatures.contains_key(bob_id.as_public_key()));
+    })
+}
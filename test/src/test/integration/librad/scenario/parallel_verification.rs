@@ -0,0 +1,73 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::ops::Index as _;
+
+use librad::git::{identities, replication};
+
+use crate::{
+    logging,
+    rad::{identities::TestProject, testnet},
+};
+
+fn config() -> testnet::Config {
+    testnet::Config {
+        num_peers: nonzero!(3usize),
+        min_connected: 3,
+        bootstrap: testnet::Bootstrap::from_env(),
+    }
+}
+
+#[test]
+fn parallel_verification_yields_same_identity() {
+    logging::init();
+
+    let net = testnet::run(config()).unwrap();
+    net.enter(async {
+        let peer1 = net.peers().index(0);
+        let peer2 = net.peers().index(1);
+        let peer3 = net.peers().index(2);
+
+        let proj = peer1
+            .using_storage(move |storage| TestProject::create(storage))
+            .await
+            .unwrap()
+            .unwrap();
+
+        let sequential = replication::Config {
+            parallel_verification: None,
+            ..peer2.protocol_config().replication.clone()
+        };
+        let parallel = replication::Config {
+            parallel_verification: Some(nonzero!(4usize)),
+            ..peer3.protocol_config().replication.clone()
+        };
+        proj.pull_with(peer1, peer2, sequential).await.unwrap();
+        proj.pull_with(peer1, peer3, parallel).await.unwrap();
+
+        // Fetching again verifies the local view
+        proj.pull_with(peer1, peer3, parallel).await.unwrap();
+
+        let seq = peer2
+            .using_storage({
+                let urn = proj.project.urn();
+                move |storage| identities::project::verify(storage, &urn)
+            })
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let par = peer3
+            .using_storage({
+                let urn = proj.project.urn();
+                move |storage| identities::project::verify(storage, &urn)
+            })
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(seq.content_id, par.content_id);
+    })
+}
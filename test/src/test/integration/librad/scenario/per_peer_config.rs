@@ -0,0 +1,71 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{ops::Index as _, time::Duration};
+
+use librad::{
+    git::{fetch, replication},
+    net::protocol::{config::Graft, io::graft},
+};
+
+use crate::{
+    logging,
+    rad::{
+        identities::TestProject,
+        testnet::{self, PeerConfig},
+    },
+};
+
+#[test]
+fn peers_replicate_acc_to_their_own_config() {
+    logging::init();
+
+    let tiny = PeerConfig {
+        replication: replication::Config {
+            fetch_limit: fetch::Limit { peek: 1, data: 1 },
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let slow = PeerConfig {
+        graft: Graft {
+            policy: graft::Policy::Delegates,
+        },
+        latency: Some(Duration::from_millis(20)),
+        ..Default::default()
+    };
+    let net = testnet::Builder::new()
+        .peer(PeerConfig::default())
+        .peer(tiny)
+        .peer(slow)
+        .min_connected(3)
+        .bootstrap(testnet::Bootstrap::from_env())
+        .run()
+        .unwrap();
+    net.enter(async {
+        let origin = net.peers().index(0);
+        let tiny = net.peers().index(1);
+        let slow = net.peers().index(2);
+
+        assert_eq!(
+            slow.protocol_config().graft.policy,
+            graft::Policy::Delegates
+        );
+
+        let proj = origin
+            .using_storage(move |storage| TestProject::create(storage))
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert!(proj.pull(origin, tiny).await.is_err());
+        proj.pull(origin, slow).await.unwrap();
+        // Fetching from the peer behind the relay
+        proj.pull(slow, tiny).await.unwrap_err();
+        proj.pull_with(slow, tiny, Default::default())
+            .await
+            .unwrap();
+    })
+}
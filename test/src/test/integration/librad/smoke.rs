@@ -3,10 +3,18 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+mod batch;
+mod bundle;
 mod clone;
+mod default_branch;
 mod fetch_limit;
 mod gossip;
 mod graft;
+mod hooks;
 mod interrogation;
+mod metrics;
 mod regression;
+mod restore;
 mod saturation;
+mod tofu;
+mod trace;
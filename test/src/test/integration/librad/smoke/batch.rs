@@ -0,0 +1,77 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::ops::Index as _;
+
+use crate::{
+    logging,
+    rad::{identities::TestProject, testnet},
+};
+use librad::{git::storage::ReadOnlyStorage as _, identities::payload};
+
+fn config() -> testnet::Config {
+    testnet::Config {
+        num_peers: nonzero!(2usize),
+        min_connected: 0,
+        bootstrap: testnet::Bootstrap::None,
+    }
+}
+
+#[test]
+fn replicate_batch() {
+    logging::init();
+
+    const NUM_PROJECTS: usize = 8;
+
+    let net = testnet::run(config()).unwrap();
+    net.enter(async {
+        let seed = net.peers().index(0);
+        let leecher = net.peers().index(1);
+
+        let payloads = (1..NUM_PROJECTS).map(|n| payload::Project {
+            name: format!("radicle-{}", n).into(),
+            description: None,
+            default_branch: Some("next".into()),
+        });
+        let urns = seed
+            .using_storage(move |storage| {
+                let proj = TestProject::create(storage)?;
+                let owner = proj.owner.clone();
+                let mut urns = vec![proj.project.urn()];
+                for payload in payloads {
+                    let proj = TestProject::from_project_payload(storage, owner.clone(), payload)?;
+                    urns.push(proj.project.urn());
+                }
+                Ok::<_, anyhow::Error>(urns)
+            })
+            .await
+            .unwrap()
+            .unwrap();
+
+        let seed_addrs = seed.listen_addrs().iter().copied().collect::<Vec<_>>();
+        let results = leecher
+            .replicate_batch((seed.peer_id(), seed_addrs), urns.clone(), nonzero!(4usize))
+            .await;
+        assert_eq!(
+            results
+                .iter()
+                .map(|(urn, _)| urn.clone())
+                .collect::<Vec<_>>(),
+            urns
+        );
+        for (urn, res) in results {
+            assert!(res.is_ok(), "failed to replicate {}: {:?}", urn, res);
+        }
+
+        leecher
+            .using_storage(move |storage| {
+                for urn in urns {
+                    assert!(storage.has_urn(&urn).unwrap(), "{} not replicated", urn);
+                }
+            })
+            .await
+            .unwrap();
+    })
+}
@@ -0,0 +1,283 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::ops::Index as _;
+
+use crate::{
+    logging,
+    rad::{identities::TestProject, testnet},
+};
+use librad::{
+    git::{
+        bundle,
+        refs::{self, Refs},
+        storage::Storage,
+        tracking,
+        util,
+    },
+    git_ext::{tree, Oid, OneLevel},
+    paths::Paths,
+    reflike,
+    PeerId,
+    SecretKey,
+};
+
+fn config() -> testnet::Config {
+    testnet::Config {
+        num_peers: nonzero!(2usize),
+        min_connected: 2,
+        bootstrap: testnet::Bootstrap::from_env(),
+    }
+}
+
+/// Replace the header of the bundle `data` with `f(header)`, keeping the pack.
+fn rewrite_header<F>(data: &[u8], f: F) -> Vec<u8>
+where
+    F: FnOnce(&str) -> String,
+{
+    let end = data.windows(2).position(|w| w == b"\n\n").unwrap() + 1;
+    let mut rewritten = f(std::str::from_utf8(&data[..end]).unwrap()).into_bytes();
+    rewritten.extend_from_slice(&data[end..]);
+    rewritten
+}
+
+#[test]
+fn rejects_malformed_header() {
+    let tmp = tempfile::tempdir().unwrap();
+    let paths = Paths::from_root(tmp.path()).unwrap();
+    let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+
+    let malformed: &[&[u8]] = &[
+        b"PACK",
+        b"# v3 git bundle\n\n",
+        b"# v2 git bundle\n",
+        b"# v2 git bundle\n-nope\n\n",
+        b"# v2 git bundle\n0123456789abcdef0123456789abcdef01234567\n\n",
+    ];
+    for data in malformed {
+        assert_matches!(
+            bundle::apply(&storage, *data),
+            Err(bundle::Error::Malformed(_))
+        );
+    }
+}
+
+#[test]
+fn applies_incremental_bundle() {
+    logging::init();
+
+    let net = testnet::run(config()).unwrap();
+    net.enter(async {
+        let alice = net.peers().index(0);
+        let bob = net.peers().index(1);
+        let project = alice
+            .using_storage(move |s| TestProject::create(s))
+            .await
+            .unwrap()
+            .unwrap();
+        project.pull(alice, bob).await.unwrap();
+        let urn = project.project.urn();
+        let alice_id = alice.peer_id();
+        let since = alice
+            .using_storage({
+                let urn = urn.clone();
+                move |s| refs::tips(s, &urn)
+            })
+            .await
+            .unwrap()
+            .unwrap()[&alice_id];
+
+        // Alice commits while offline, and hands a bundle to bob
+        let offline = alice
+            .using_storage({
+                let urn = urn.clone().with_path(reflike!("refs/heads/next"));
+                move |s| {
+                    util::quick_commit(
+                        s,
+                        &urn,
+                        vec![("HI", tree::blob(b"offline"))].into_iter().collect(),
+                        "offline",
+                    )
+                }
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        let (created, data) = alice
+            .using_storage({
+                let urn = urn.clone();
+                move |s| -> anyhow::Result<_> {
+                    let mut data = Vec::new();
+                    let created = bundle::create(s, &urn, Some(since), &mut data)?;
+                    Ok((created, data))
+                }
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(created.prerequisites.contains(&since));
+
+        let own = {
+            let data = data.clone();
+            alice
+                .using_storage(move |s| bundle::apply(s, data.as_slice()))
+                .await
+                .unwrap()
+        };
+        assert_matches!(own, Err(bundle::Error::LocalPeer));
+
+        let (plan, applied) = bob
+            .using_storage(move |s| -> anyhow::Result<_> {
+                let plan = bundle::apply(s, data.as_slice())?;
+                let applied = Refs::load(s, &urn, alice_id)?.unwrap();
+                Ok((plan, applied))
+            })
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(plan.at, created.at);
+        assert_eq!(
+            applied.heads.get(&OneLevel::from(reflike!("next"))),
+            Some(&Oid::from(offline))
+        );
+    })
+}
+
+#[test]
+fn rejects_non_fast_forward() {
+    logging::init();
+
+    let net = testnet::run(config()).unwrap();
+    net.enter(async {
+        let alice = net.peers().index(0);
+        let bob = net.peers().index(1);
+        let project = alice
+            .using_storage(move |s| TestProject::create(s))
+            .await
+            .unwrap()
+            .unwrap();
+        project.pull(alice, bob).await.unwrap();
+        let urn = project.project.urn();
+
+        let stale = alice
+            .using_storage({
+                let urn = urn.clone();
+                move |s| -> anyhow::Result<_> {
+                    let mut data = Vec::new();
+                    bundle::create(s, &urn, None, &mut data)?;
+                    Ok(data)
+                }
+            })
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Bob replicates a newer state before the stale bundle arrives
+        alice
+            .using_storage({
+                let urn = urn.clone().with_path(reflike!("refs/heads/next"));
+                move |s| {
+                    util::quick_commit(
+                        s,
+                        &urn,
+                        vec![("HI", tree::blob(b"newer"))].into_iter().collect(),
+                        "newer",
+                    )
+                }
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        project.pull(alice, bob).await.unwrap();
+
+        let applied = bob
+            .using_storage(move |s| bundle::apply(s, stale.as_slice()))
+            .await
+            .unwrap();
+        assert_matches!(applied, Err(bundle::Error::NonFastForward { .. }));
+    })
+}
+
+#[test]
+fn rejects_unsigned_refs() {
+    logging::init();
+
+    let net = testnet::run(config()).unwrap();
+    net.enter(async {
+        let alice = net.peers().index(0);
+        let bob = net.peers().index(1);
+        let project = alice
+            .using_storage(move |s| TestProject::create(s))
+            .await
+            .unwrap()
+            .unwrap();
+        let urn = project.project.urn();
+        let alice_id = alice.peer_id();
+        alice
+            .using_storage({
+                let urn = urn.clone().with_path(reflike!("refs/heads/next"));
+                move |s| {
+                    util::quick_commit(
+                        s,
+                        &urn,
+                        vec![("HI", tree::blob(b"signed"))].into_iter().collect(),
+                        "signed",
+                    )
+                }
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        project.pull(alice, bob).await.unwrap();
+
+        let (created, data) = alice
+            .using_storage({
+                let urn = urn.clone();
+                move |s| -> anyhow::Result<_> {
+                    let mut data = Vec::new();
+                    let created = bundle::create(s, &urn, None, &mut data)?;
+                    Ok((created, data))
+                }
+            })
+            .await
+            .unwrap()
+            .unwrap();
+
+        // A branch pointing elsewhere than what was signed
+        let retargeted = rewrite_header(&data, |header| {
+            header
+                .lines()
+                .map(|line| match line.split_once(' ') {
+                    Some((_, name)) if name.ends_with("/heads/next") => {
+                        format!("{} {}", created.at, name)
+                    },
+                    _ => line.to_owned(),
+                })
+                .map(|line| line + "\n")
+                .collect()
+        });
+        // The refs of alice, passed off as the ones of another peer
+        let impostor = PeerId::from(SecretKey::new());
+        let relabelled = rewrite_header(&data, |header| {
+            header.replace(&alice_id.to_string(), &impostor.to_string())
+        });
+
+        let (retargeted, relabelled) = bob
+            .using_storage(move |s| -> anyhow::Result<_> {
+                tracking::track(s, &urn, impostor)?;
+                Ok((
+                    bundle::apply(s, retargeted.as_slice()),
+                    bundle::apply(s, relabelled.as_slice()),
+                ))
+            })
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_matches!(retargeted, Err(bundle::Error::Unsigned(name)) if name.ends_with("/heads/next"));
+        assert_matches!(relabelled, Err(bundle::Error::Signed(_)));
+    })
+}
@@ -57,7 +57,7 @@ fn not_present() {
 
         contributor.clone_from(maintainer, true).await.unwrap();
 
-        let cfg = contributor.0.protocol_config().replication;
+        let cfg = contributor.0.protocol_config().replication.clone();
         contributor
             .0
             .using_storage(move |storage| {
@@ -131,6 +131,35 @@ fn when_disconnected_and_no_addr_hints() {
     )
 }
 
+#[test]
+fn audit_log() {
+    logging::init();
+
+    let net = testnet::run(default_config()).unwrap();
+    net.enter(async {
+        let host = Host::init(&net.peers()[0]).await;
+        let urn = host.project.project.urn();
+        let host_peer = host.peer.peer_id();
+        let leecher = Leecher(&net.peers()[1]);
+        leecher.clone_from(host, true).await.unwrap();
+
+        leecher
+            .0
+            .using_storage(move |storage| {
+                let entries = replication::audit::recent(storage, Some(&urn), 10).unwrap();
+                assert_eq!(entries.len(), 1);
+                assert_eq!(entries[0].urn, urn);
+                assert_eq!(entries[0].remote_peer, host_peer);
+                assert!(matches!(
+                    entries[0].outcome,
+                    replication::audit::Outcome::Success { clone: true, updated } if updated > 0
+                ));
+            })
+            .await
+            .unwrap();
+    })
+}
+
 struct Host<'a> {
     project: TestProject,
     peer: &'a RunningTestPeer,
@@ -152,7 +181,7 @@ struct Leecher<'a>(&'a RunningTestPeer);
 
 impl Leecher<'_> {
     async fn clone_from(&self, host: Host<'_>, supply_addr_hints: bool) -> anyhow::Result<()> {
-        let cfg = self.0.protocol_config().replication;
+        let cfg = self.0.protocol_config().replication.clone();
         let urn = host.project.project.urn();
         let owner = host.project.owner;
         let host_peer = host.peer.peer_id();
@@ -0,0 +1,123 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::ops::Index as _;
+
+use crate::{
+    logging,
+    rad::{identities::TestProject, testnet},
+};
+use librad::{
+    git::{
+        replication::{
+            head::{self, Converged, Convergence},
+            Warning,
+        },
+        util,
+    },
+    git_ext::tree,
+    reflike,
+};
+
+fn config() -> testnet::Config {
+    testnet::Config {
+        num_peers: nonzero!(2usize),
+        min_connected: 2,
+        bootstrap: testnet::Bootstrap::from_env(),
+    }
+}
+
+#[test]
+fn converges_on_delegate_head() {
+    logging::init();
+
+    let net = testnet::run(config()).unwrap();
+    net.enter(async {
+        let alice = net.peers().index(0);
+        let bob = net.peers().index(1);
+        let project = alice
+            .using_storage(move |s| TestProject::create(s))
+            .await
+            .unwrap()
+            .unwrap();
+        // `TestProject`s have "next" as their default branch
+        let head = alice
+            .using_storage({
+                let urn = project.project.urn().with_path(reflike!("refs/heads/next"));
+                move |s| {
+                    util::quick_commit(
+                        s,
+                        &urn,
+                        vec![("HI", tree::blob(b"Hello"))].into_iter().collect(),
+                        "initial",
+                    )
+                }
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        project.pull(alice, bob).await.unwrap();
+
+        let urn = project.project.urn();
+        let (first, second, symref) = bob
+            .using_storage(move |s| {
+                let first = head::converge(s, &urn, Convergence::All)?;
+                let second = head::converge(s, &urn, Convergence::All)?;
+                let symref = git2::Repository::open(s.path())?
+                    .find_reference(&format!("refs/namespaces/{}/HEAD", urn.encode_id()))?
+                    .symbolic_target()
+                    .map(ToOwned::to_owned);
+                Ok::<_, head::Error>((first, second, symref))
+            })
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(first, Converged::Updated(head.into()));
+        assert_eq!(second, Converged::Unchanged(head.into()));
+        assert_eq!(
+            symref,
+            Some(format!(
+                "refs/namespaces/{}/refs/heads/next",
+                project.project.urn().encode_id()
+            ))
+        );
+    })
+}
+
+#[test]
+fn warns_about_missing_delegate_head() {
+    logging::init();
+
+    let net = testnet::run(config()).unwrap();
+    net.enter(async {
+        let alice = net.peers().index(0);
+        let bob = net.peers().index(1);
+        let project = alice
+            .using_storage(move |s| TestProject::create(s))
+            .await
+            .unwrap()
+            .unwrap();
+        // Alice never commits to the default branch
+        project.pull(alice, bob).await.unwrap();
+
+        let urn = project.project.urn();
+        let heads = bob
+            .using_storage(move |s| head::delegate_heads(s, &urn))
+            .await
+            .unwrap()
+            .unwrap()
+            .expect("project has a default branch");
+
+        assert!(heads.heads.is_empty());
+        assert_eq!(
+            heads.warnings(),
+            vec![Warning::DefaultBranchMissing {
+                peer: alice.peer_id(),
+                branch: reflike!("next"),
+            }]
+        );
+    })
+}
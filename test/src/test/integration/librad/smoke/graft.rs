@@ -5,6 +5,8 @@
 
 use std::{ops::Index as _, time::Duration};
 
+use assert_matches::assert_matches;
+
 use crate::{
     logging,
     rad::{identities::TestProject, testnet},
@@ -12,6 +14,7 @@ use crate::{
 use librad::{
     git::{storage::ReadOnlyStorage as _, tracking, util},
     git_ext::tree,
+    net::protocol::{error, interrogation},
     reflike,
 };
 
@@ -129,3 +132,91 @@ fn mutual_fetch() {
         assert!(bob_has_alice, "bob is missing alice's commit");
     })
 }
+
+#[test]
+fn please_pull() {
+    logging::init();
+
+    let net = testnet::run(config()).unwrap();
+    net.enter(async {
+        let alice = net.peers().index(0);
+        let bob = net.peers().index(1);
+        let project = alice
+            .using_storage(move |s| TestProject::create(s))
+            .await
+            .unwrap()
+            .unwrap();
+
+        let interrogation = alice.interrogate((bob.peer_id(), bob.listen_addrs().to_vec()));
+        // Bob doesn't have the project yet, and doesn't track alice
+        assert_matches!(
+            interrogation.please_pull(project.project.urn(), None).await,
+            Err(error::Interrogation::ErrorResponse(
+                interrogation::Error::Forbidden
+            ))
+        );
+
+        // Once he does, he clones it
+        bob.using_storage({
+            let urn = project.project.urn();
+            let alice = alice.peer_id();
+            move |s| tracking::track(s, &urn, alice)
+        })
+        .await
+        .unwrap()
+        .unwrap();
+        let cloned = interrogation
+            .please_pull(project.project.urn(), None)
+            .await
+            .unwrap();
+        assert!(cloned > 0, "expected bob to clone the project");
+        let bob_has_project = bob
+            .using_storage({
+                let urn = project.project.urn();
+                move |s| s.has_urn(&urn)
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(bob_has_project, "bob is missing the project");
+
+        let commit_urn = project.project.urn().with_path(reflike!("refs/heads/hi"));
+        let hi_bob = alice
+            .using_storage({
+                let urn = commit_urn.clone();
+                move |s| {
+                    util::quick_commit(
+                        s,
+                        &urn,
+                        vec![("HI", tree::blob(b"Hi Bob"))].into_iter().collect(),
+                        "say hi to bob",
+                    )
+                }
+            })
+            .await
+            .unwrap()
+            .unwrap();
+
+        let updated = interrogation
+            .please_pull(project.project.urn(), None)
+            .await
+            .unwrap();
+        assert!(updated > 0, "expected bob to update some refs");
+
+        let bob_has_alice = bob
+            .using_storage({
+                let urn = commit_urn.map_path(|path| {
+                    path.map(|path| {
+                        reflike!("refs/remotes")
+                            .join(alice.peer_id())
+                            .join(path.strip_prefix("refs").unwrap())
+                    })
+                });
+                move |s| s.has_commit(&urn, Box::new(hi_bob))
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(bob_has_alice, "bob is missing alice's commit");
+    })
+}
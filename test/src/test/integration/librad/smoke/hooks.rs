@@ -0,0 +1,120 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    ops::Index as _,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use crate::{
+    logging,
+    rad::{identities::TestProject, testnet},
+};
+use librad::{
+    git::{
+        refs,
+        replication::{
+            self,
+            hooks::{Hooks, Pending, Sink, Veto},
+        },
+        util,
+    },
+    git_ext::tree,
+    reflike,
+};
+
+/// Refuses updates to `protected`, and counts the replications applied.
+#[derive(Debug)]
+struct Protect {
+    applied: AtomicUsize,
+}
+
+impl Hooks for Protect {
+    fn pre_apply(&self, pending: &Pending) -> Result<(), Veto> {
+        match pending
+            .updates
+            .keys()
+            .find(|name| name.as_str().ends_with("heads/protected"))
+        {
+            Some(name) => Err(Veto {
+                reason: format!("{} is protected", name),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn post_apply(&self, _applied: &Pending) {
+        self.applied.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+fn config() -> testnet::Config {
+    testnet::Config {
+        num_peers: nonzero!(2usize),
+        min_connected: 2,
+        bootstrap: testnet::Bootstrap::from_env(),
+    }
+}
+
+#[test]
+fn vetoed_updates_are_rolled_back() {
+    logging::init();
+
+    let net = testnet::run(config()).unwrap();
+    net.enter(async {
+        let alice = net.peers().index(0);
+        let bob = net.peers().index(1);
+        let project = alice
+            .using_storage(move |s| TestProject::create(s))
+            .await
+            .unwrap()
+            .unwrap();
+        let protect = Arc::new(Protect {
+            applied: AtomicUsize::new(0),
+        });
+        let cfg = replication::Config {
+            hooks: Sink::new(protect.clone()),
+            ..bob.protocol_config().replication
+        };
+
+        project.pull_with(alice, bob, cfg.clone()).await.unwrap();
+        assert_eq!(protect.applied.load(Ordering::Relaxed), 1);
+
+        let urn = project.project.urn();
+        let alice_id = alice.peer_id();
+        let tips = || {
+            let urn = urn.clone();
+            bob.using_storage(move |s| refs::tips(s, &urn))
+        };
+        let before = tips().await.unwrap().unwrap()[&alice_id];
+
+        alice
+            .using_storage({
+                let urn = urn.clone().with_path(reflike!("refs/heads/protected"));
+                move |s| {
+                    util::quick_commit(
+                        s,
+                        &urn,
+                        vec![("HI", tree::blob(b"protected"))].into_iter().collect(),
+                        "protected",
+                    )
+                }
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        let vetoed = project.pull_with(alice, bob, cfg).await;
+
+        assert_matches!(
+            vetoed.unwrap_err().downcast_ref::<replication::Error>(),
+            Some(replication::Error::Vetoed(_))
+        );
+        assert_eq!(tips().await.unwrap().unwrap()[&alice_id], before);
+        assert_eq!(protect.applied.load(Ordering::Relaxed), 1);
+    })
+}
@@ -7,9 +7,12 @@ use std::{ops::Index as _, time::Duration};
 
 use librad::{
     data::BoundedVec,
+    git::{large, refs},
     identities::SomeUrn,
     net::protocol::{
         event::{self, upstream::predicate},
+        version::Versions,
+        Features,
         PeerAdvertisement,
     },
 };
@@ -72,6 +75,8 @@ fn responds() {
                 )
                 .unwrap(),
                 capabilities: Default::default(),
+                versions: Versions::SUPPORTED,
+                features: Features::SUPPORTED,
             },
             interrogation.peer_advertisement().await.unwrap()
         );
@@ -83,5 +88,31 @@ fn responds() {
         for urn in &[SomeUrn::Git(project.urn()), SomeUrn::Git(owner.urn())] {
             assert!(urns.contains(urn), "{} not in set", urn)
         }
+
+        let tips = interrogation.sigref_tips(project.urn()).await.unwrap();
+        let expected = responder
+            .using_storage({
+                let urn = project.urn();
+                move |s| refs::tips(s, &urn)
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(tips.contains_key(&responder.peer_id()));
+        assert_eq!(expected, tips);
+
+        let store = large::Store::open(&responder.protocol_config().paths);
+        let pointer = store.offload(b"not actually large").unwrap();
+        let chunk = interrogation.large_object(pointer.oid, 4).await.unwrap();
+        assert_eq!(pointer.size, chunk.size);
+        assert_eq!(b"actually large".to_vec(), chunk.data);
+        assert!(interrogation
+            .large_object(large::Oid::hash(b"missing"), 0)
+            .await
+            .is_err());
+
+        interrogation.ping().await.unwrap();
+        let stats = requester.stats().await;
+        assert!(stats.rtt.contains_key(&responder.peer_id()));
     })
 }
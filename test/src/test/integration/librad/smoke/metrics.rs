@@ -0,0 +1,58 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::ops::Index as _;
+
+use crate::{
+    logging,
+    rad::{identities::TestProject, testnet},
+};
+use librad::git::replication::{
+    self,
+    metrics::{Counters, Sink},
+};
+
+static COUNTERS: Counters = Counters::new();
+
+fn config() -> testnet::Config {
+    testnet::Config {
+        num_peers: nonzero!(2usize),
+        min_connected: 2,
+        bootstrap: testnet::Bootstrap::from_env(),
+    }
+}
+
+#[test]
+fn replication_is_measured() {
+    logging::init();
+
+    let net = testnet::run(config()).unwrap();
+    net.enter(async {
+        let alice = net.peers().index(0);
+        let bob = net.peers().index(1);
+        let project = alice
+            .using_storage(move |s| TestProject::create(s))
+            .await
+            .unwrap()
+            .unwrap();
+
+        let cfg = replication::Config {
+            metrics: Sink::new(&COUNTERS),
+            ..bob.protocol_config().replication.clone()
+        };
+        project.pull_with(alice, bob, cfg).await.unwrap();
+
+        let stats = COUNTERS.stats();
+        assert_eq!(stats.ls_refs.count, 1);
+        assert!(stats.fetches > 0);
+        assert!(stats.wants > 0);
+        assert!(stats.packs > 0);
+        assert!(stats.pack_bytes > 0);
+        assert_eq!(stats.renegotiations, 0);
+        assert_eq!(stats.stream_resets, 0);
+        assert_eq!(stats.verification.count, 1);
+        assert_eq!(stats.transaction.count, 1);
+    })
+}
@@ -0,0 +1,161 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::ops::Index as _;
+
+use crate::{
+    logging,
+    rad::{identities::TestProject, testnet},
+};
+use librad::{
+    git::{
+        refs::{self, Refs},
+        restore,
+        util,
+    },
+    git_ext::{tree, Oid, OneLevel},
+    reflike,
+};
+
+fn config() -> testnet::Config {
+    testnet::Config {
+        num_peers: nonzero!(2usize),
+        min_connected: 2,
+        bootstrap: testnet::Bootstrap::from_env(),
+    }
+}
+
+#[test]
+fn rewinds_to_past_sigrefs() {
+    logging::init();
+
+    let net = testnet::run(config()).unwrap();
+    net.enter(async {
+        let alice = net.peers().index(0);
+        let bob = net.peers().index(1);
+        let project = alice
+            .using_storage(move |s| TestProject::create(s))
+            .await
+            .unwrap()
+            .unwrap();
+        let commit = |message: &'static str| {
+            let urn = project.project.urn().with_path(reflike!("refs/heads/next"));
+            alice.using_storage(move |s| {
+                util::quick_commit(
+                    s,
+                    &urn,
+                    vec![("HI", tree::blob(message.as_bytes()))]
+                        .into_iter()
+                        .collect(),
+                    message,
+                )
+            })
+        };
+
+        let good = commit("good").await.unwrap().unwrap();
+        project.pull(alice, bob).await.unwrap();
+        let urn = project.project.urn();
+        let alice_id = alice.peer_id();
+        let at = bob
+            .using_storage({
+                let urn = urn.clone();
+                move |s| refs::tips(s, &urn)
+            })
+            .await
+            .unwrap()
+            .unwrap()[&alice_id];
+
+        let bad = commit("bad").await.unwrap().unwrap();
+        project.pull(alice, bob).await.unwrap();
+
+        let next = OneLevel::from(reflike!("next"));
+        let (refused, restored) = bob
+            .using_storage(move |s| -> anyhow::Result<_> {
+                let plan = restore::plan(s, &urn, alice_id, at)?;
+                assert!(!plan.is_fast_forward());
+                let refused = restore::apply(s, &plan, false);
+                restore::apply(s, &plan, true)?;
+                let restored = Refs::load(s, &urn, alice_id)?.unwrap();
+                Ok((refused, restored))
+            })
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_matches!(refused, Err(restore::Error::NonFastForward(_)));
+        assert_ne!(good, bad);
+        assert_eq!(restored.heads.get(&next), Some(&Oid::from(good)));
+    })
+}
+
+#[test]
+fn refuses_refs_changed_since_plan() {
+    logging::init();
+
+    let net = testnet::run(config()).unwrap();
+    net.enter(async {
+        let alice = net.peers().index(0);
+        let bob = net.peers().index(1);
+        let project = alice
+            .using_storage(move |s| TestProject::create(s))
+            .await
+            .unwrap()
+            .unwrap();
+        let commit = |message: &'static str| {
+            let urn = project.project.urn().with_path(reflike!("refs/heads/next"));
+            alice.using_storage(move |s| {
+                util::quick_commit(
+                    s,
+                    &urn,
+                    vec![("HI", tree::blob(message.as_bytes()))]
+                        .into_iter()
+                        .collect(),
+                    message,
+                )
+            })
+        };
+
+        commit("good").await.unwrap().unwrap();
+        project.pull(alice, bob).await.unwrap();
+        let urn = project.project.urn();
+        let alice_id = alice.peer_id();
+        let at = bob
+            .using_storage({
+                let urn = urn.clone();
+                move |s| refs::tips(s, &urn)
+            })
+            .await
+            .unwrap()
+            .unwrap()[&alice_id];
+
+        commit("bad").await.unwrap().unwrap();
+        project.pull(alice, bob).await.unwrap();
+        let plan = bob
+            .using_storage({
+                let urn = urn.clone();
+                move |s| restore::plan(s, &urn, alice_id, at)
+            })
+            .await
+            .unwrap()
+            .unwrap();
+
+        let worse = commit("worse").await.unwrap().unwrap();
+        project.pull(alice, bob).await.unwrap();
+
+        let next = OneLevel::from(reflike!("next"));
+        let (applied, current) = bob
+            .using_storage(move |s| -> anyhow::Result<_> {
+                let applied = restore::apply(s, &plan, true);
+                let current = Refs::load(s, &urn, alice_id)?.unwrap();
+                Ok((applied, current))
+            })
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_matches!(applied, Err(restore::Error::Concurrent(_)));
+        assert_eq!(current.heads.get(&next), Some(&Oid::from(worse)));
+    })
+}
@@ -0,0 +1,122 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    collections::HashMap,
+    io,
+    net::SocketAddr,
+    ops::Index as _,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::net::UdpSocket;
+
+use librad::net::{
+    protocol::event::{self, upstream::Connection, Upstream},
+    tofu,
+};
+
+use crate::{
+    logging,
+    rad::testnet::{self, PeerConfig},
+};
+
+/// A UDP relay forwarding to whichever address `upstream` is currently set
+/// to, standing in for an address which changes hands.
+async fn relay(socket: UdpSocket, upstream: Arc<Mutex<SocketAddr>>) -> io::Result<()> {
+    let socket = Arc::new(socket);
+    let mut relayed: HashMap<(SocketAddr, SocketAddr), Arc<UdpSocket>> = HashMap::new();
+    let mut buf = vec![0; u16::MAX as usize];
+    loop {
+        let (n, sender) = socket.recv_from(&mut buf).await?;
+        let upstream = *upstream.lock().unwrap();
+        let out = match relayed.get(&(sender, upstream)) {
+            Some(out) => out.clone(),
+            None => {
+                let out = Arc::new(UdpSocket::bind("127.0.0.1:0").await?);
+                out.connect(upstream).await?;
+                tokio::spawn({
+                    let out = out.clone();
+                    let socket = socket.clone();
+                    async move {
+                        let mut buf = vec![0; u16::MAX as usize];
+                        while let Ok(n) = out.recv(&mut buf).await {
+                            socket.send_to(&buf[..n], sender).await.ok();
+                        }
+                    }
+                });
+                relayed.insert((sender, upstream), out.clone());
+                out
+            },
+        };
+        out.send(&buf[..n]).await.ok();
+    }
+}
+
+#[test]
+fn refuses_different_peer_at_pinned_address() {
+    logging::init();
+
+    let net = testnet::Builder::new()
+        .peer(PeerConfig {
+            tofu: Some(tofu::Policy::Refuse),
+            ..Default::default()
+        })
+        .peers(2, PeerConfig::default())
+        .bootstrap(testnet::Bootstrap::None)
+        .run()
+        .unwrap();
+    net.enter(async {
+        let alice = net.peers().index(0);
+        let bob = net.peers().index(1);
+        let carol = net.peers().index(2);
+
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        let upstream = Arc::new(Mutex::new(bob.listen_addrs()[0]));
+        tokio::spawn(relay(socket, upstream.clone()));
+
+        // Bob is pinned to the address when alice first connects to it
+        alice
+            .interrogate((bob.peer_id(), vec![addr]))
+            .peer_advertisement()
+            .await
+            .unwrap();
+
+        // Now carol shows up at the same address
+        *upstream.lock().unwrap() = carol.listen_addrs()[0];
+        let events = alice.subscribe();
+        futures::pin_mut!(events);
+        assert!(alice
+            .interrogate((carol.peer_id(), vec![addr]))
+            .peer_advertisement()
+            .await
+            .is_err());
+        let mismatch = event::upstream::expect(
+            events,
+            |evt| matches!(evt, Upstream::Connection(Connection::Mismatch { .. })),
+            Duration::from_secs(5),
+        )
+        .await
+        .unwrap();
+        assert_matches!(
+            mismatch,
+            Upstream::Connection(Connection::Mismatch {
+                remote_peer,
+                remote_addr,
+                pinned,
+                refused: true,
+            }) if remote_peer == carol.peer_id() && remote_addr == addr && pinned == bob.peer_id()
+        );
+
+        // Carol's own address is not pinned to anyone else
+        alice
+            .interrogate((carol.peer_id(), carol.listen_addrs().to_vec()))
+            .peer_advertisement()
+            .await
+            .unwrap();
+    })
+}
@@ -0,0 +1,114 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::ops::Index as _;
+
+use crate::{
+    logging,
+    rad::{identities::TestProject, testnet},
+};
+use librad::{
+    git::{
+        replication::{
+            self,
+            trace::{self, Outcome, Specs},
+            Mode,
+        },
+        tracking,
+        util,
+    },
+    git_ext::tree,
+    reflike,
+};
+
+fn config() -> testnet::Config {
+    testnet::Config {
+        num_peers: nonzero!(2usize),
+        min_connected: 2,
+        bootstrap: testnet::Bootstrap::from_env(),
+    }
+}
+
+#[test]
+fn record_and_replay() {
+    logging::init();
+
+    let net = testnet::run(config()).unwrap();
+    net.enter(async {
+        let alice = net.peers().index(0);
+        let bob = net.peers().index(1);
+        let project = alice
+            .using_storage(move |s| TestProject::create(s))
+            .await
+            .unwrap()
+            .unwrap();
+        alice
+            .using_storage({
+                let urn = project.project.urn().with_path(reflike!("refs/heads/next"));
+                move |s| {
+                    util::quick_commit(
+                        s,
+                        &urn,
+                        vec![("HI", tree::blob(b"Hello"))].into_iter().collect(),
+                        "initial",
+                    )
+                }
+            })
+            .await
+            .unwrap()
+            .unwrap();
+
+        let cfg = replication::Config {
+            trace: true,
+            ..bob.protocol_config().replication.clone()
+        };
+        let recorded = project.pull_with(alice, bob, cfg.clone()).await.unwrap();
+
+        let urn = project.project.urn();
+        let replayed = bob
+            .using_storage(move |s| -> anyhow::Result<_> {
+                let traces = trace::list(s)?;
+                assert_eq!(traces.len(), 1);
+                let trace = trace::read(&traces[0])?;
+
+                // Forget about the project, but keep the objects around
+                let tracked = tracking::tracked(s, &urn)?.collect::<Vec<_>>();
+                for peer in tracked {
+                    tracking::untrack(s, &urn, peer)?;
+                }
+                let repo = git2::Repository::open(s.path())?;
+                let names = repo
+                    .references_glob("refs/namespaces/*")?
+                    .names()
+                    .map(|name| name.map(ToOwned::to_owned))
+                    .collect::<Result<Vec<_>, _>>()?;
+                for name in names {
+                    repo.find_reference(&name)?.delete()?;
+                }
+
+                let replayed = trace::replay::replay(s, trace.clone(), cfg)?;
+                Ok((trace, replayed))
+            })
+            .await
+            .unwrap()
+            .unwrap();
+        let (trace, replayed) = replayed;
+
+        assert_eq!(trace.remote_peer, alice.peer_id());
+        assert_eq!(
+            trace.rounds.first().map(|r| &r.specs),
+            Some(&Specs::PeekAll)
+        );
+        assert_eq!(
+            trace.outcome,
+            Some(Outcome::Success {
+                clone: true,
+                updated_tips: recorded.updated_tips.clone(),
+            })
+        );
+        assert_matches!(replayed.mode, Mode::Clone);
+        assert_eq!(replayed.updated_tips, recorded.updated_tips);
+    })
+}
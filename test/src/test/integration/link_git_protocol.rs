@@ -17,7 +17,16 @@ use git::{
     refs::transaction::{Change, PreviousValue, RefEdit},
 };
 use git_repository as git;
-use link_git_protocol::{fetch, ls, packwriter, upload_pack, ObjectId, PackWriter, Ref};
+use link_git_protocol::{
+    fetch,
+    ls,
+    packwriter,
+    upload_pack,
+    ObjectId,
+    PackWriter,
+    Ref,
+    RefPrefixes,
+};
 use tempfile::{tempdir, TempDir};
 
 fn upstream() -> TempDir {
@@ -107,10 +116,18 @@ fn collect_history(
 }
 
 fn run_ls_refs<R: AsRef<Path>>(remote: R, opt: ls::Options) -> io::Result<Vec<Ref>> {
+    run_ls_refs_with(remote, opt, |_| true)
+}
+
+fn run_ls_refs_with<R, F>(remote: R, opt: ls::Options, filter: F) -> io::Result<Vec<Ref>>
+where
+    R: AsRef<Path>,
+    F: FnMut(&Ref) -> bool,
+{
     let (client, server) = futures_ringbuf::Endpoint::pair(256, 256);
     let client = async move {
         let (recv, send) = client.split();
-        ls::ls_refs(opt, recv, send).await
+        ls::ls_refs_with(opt, filter, recv, send).await
     };
     let server = {
         let (recv, send) = server.split();
@@ -159,6 +176,7 @@ fn smoke() {
             repo: "foo".into(),
             extra_params: vec![],
             ref_prefixes: vec!["refs/heads/".into(), "refs/pulls/".into()],
+            max_refs: None,
         },
     )
     .unwrap();
@@ -190,6 +208,87 @@ fn smoke() {
     assert!(out.pack.is_some());
 }
 
+#[test]
+fn ls_refs_matching_exclusions() {
+    let remote = upstream();
+    let prefixes = RefPrefixes::new()
+        .include("refs/")
+        .exclude("refs/heads/next")
+        .exclude("refs/pulls");
+    let (client, server) = futures_ringbuf::Endpoint::pair(256, 256);
+    let client = async move {
+        let (recv, send) = client.split();
+        ls::ls_refs_matching(
+            ls::Options {
+                repo: "foo".into(),
+                extra_params: vec![],
+                ref_prefixes: vec![],
+                max_refs: None,
+            },
+            &prefixes,
+            ["heads", "pulls"].iter().copied(),
+            recv,
+            send,
+        )
+        .await
+    };
+    let server = {
+        let (recv, send) = server.split();
+        upload_pack::upload_pack(&remote, recv, send).and_then(|(_hdr, run)| run)
+    };
+    let (refs, server_out) =
+        futures::executor::block_on(futures::future::try_join(client, server)).unwrap();
+
+    assert!(server_out.success());
+    assert_eq!(
+        refs.iter().map(|r| r.unpack().0).collect::<BTreeSet<_>>(),
+        ["refs/heads/main".into()].iter().collect::<BTreeSet<_>>()
+    );
+}
+
+#[test]
+fn ls_refs_max_refs() {
+    let remote = upstream();
+    let err = run_ls_refs(
+        &remote,
+        ls::Options {
+            repo: "foo".into(),
+            extra_params: vec![],
+            ref_prefixes: vec!["refs/heads/".into(), "refs/pulls/".into()],
+            max_refs: Some(2),
+        },
+    )
+    .unwrap_err();
+
+    assert_eq!(
+        ls::TooManyRefs::from_io(&err),
+        Some(&ls::TooManyRefs { limit: 2 })
+    );
+}
+
+#[test]
+fn ls_refs_filtered_refs_dont_count() {
+    let remote = upstream();
+    let refs = run_ls_refs_with(
+        &remote,
+        ls::Options {
+            repo: "foo".into(),
+            extra_params: vec![],
+            ref_prefixes: vec!["refs/heads/".into(), "refs/pulls/".into()],
+            max_refs: Some(2),
+        },
+        |r| r.unpack().0.starts_with(b"refs/heads/"),
+    )
+    .unwrap();
+
+    assert_eq!(
+        refs.iter().map(|r| r.unpack().0).collect::<BTreeSet<_>>(),
+        ["refs/heads/main".into(), "refs/heads/next".into()]
+            .iter()
+            .collect::<BTreeSet<_>>()
+    );
+}
+
 #[test]
 fn want_ref() {
     let remote = upstream();
@@ -251,6 +350,7 @@ where
             repo: "foo".into(),
             extra_params: vec![],
             ref_prefixes: vec!["refs/heads/".into(), "refs/pulls/".into()],
+            max_refs: None,
         },
     )
     .unwrap();
@@ -8,4 +8,9 @@ mod git_trailers;
 mod librad;
 mod link_git_protocol;
 mod node_lib;
+mod rad_clib;
+mod rad_doctor;
 mod rad_exe;
+mod rad_ls;
+mod rad_profile;
+mod rad_storage;
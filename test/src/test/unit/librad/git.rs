@@ -3,8 +3,13 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+mod cobs;
 mod fetch;
+mod grep;
+mod import;
+mod inbox;
 mod include;
+mod large;
 mod local;
 mod p2p;
 mod project;
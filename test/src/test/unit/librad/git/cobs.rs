@@ -0,0 +1,537 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{collections::BTreeSet, convert::TryFrom};
+
+use librad::{
+    git::{
+        cobs::{
+            self,
+            attachment::{self, Attachment},
+            ci::{self, Report, Status},
+            gc,
+            graph,
+            patch::{self, Proposal, Verdict},
+            policy::{self, Authorization},
+        },
+        refs::{Refs, Remotes},
+        storage::Storage,
+        tracking::track,
+        Urn,
+    },
+    git_ext as ext,
+    paths::Paths,
+    reflike,
+    PeerId,
+    SecretKey,
+};
+
+#[test]
+fn valid_typenames() {
+    assert!(cobs::is_valid_typename("xyz.radicle.issue"));
+    assert!(cobs::is_valid_typename("ci_logs-v2"));
+    assert!(!cobs::is_valid_typename(""));
+    assert!(!cobs::is_valid_typename("xyz/radicle"));
+    assert!(!cobs::is_valid_typename("xyz radicle"));
+}
+
+#[test]
+fn policy_roundtrip() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+
+        assert_eq!(
+            Authorization::Anyone,
+            policy::get(&storage, "xyz.radicle.issue").unwrap()
+        );
+        policy::set(&storage, "xyz.radicle.issue", Authorization::Delegates).unwrap();
+        assert_eq!(
+            Authorization::Delegates,
+            policy::get(&storage, "xyz.radicle.issue").unwrap()
+        );
+        assert_eq!(
+            Authorization::Anyone,
+            policy::get(&storage, "xyz.radicle.patch").unwrap()
+        );
+        assert!(policy::unset(&storage, "xyz.radicle.issue").unwrap());
+        assert!(!policy::unset(&storage, "xyz.radicle.issue").unwrap());
+        assert_eq!(
+            Authorization::Anyone,
+            policy::get(&storage, "xyz.radicle.issue").unwrap()
+        );
+    }
+}
+
+#[test]
+fn parse_authorization() {
+    assert_eq!(Ok(Authorization::Delegates), "delegates".parse());
+    assert_eq!(Ok(Authorization::Anyone), "anyone".parse());
+    assert!("tracked".parse::<Authorization>().is_err());
+}
+
+#[test]
+fn nothing_quarantined() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        let urn = Urn::new(git2::Oid::zero().into());
+
+        assert!(policy::quarantined(&storage, &urn).unwrap().is_empty())
+    }
+}
+
+#[test]
+fn gc_prunes_orphans() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        let repo = git2::Repository::open(paths.git_dir()).unwrap();
+        let urn = Urn::new(git2::Oid::zero().into());
+        let peer_key = SecretKey::new();
+        let peer = PeerId::from(&peer_key);
+        track(&storage, &urn, peer).unwrap();
+
+        let remote = format!("refs/namespaces/{}/refs/remotes/{}", urn.encode_id(), peer);
+        let empty_tree = {
+            let oid = repo.treebuilder(None).unwrap().write().unwrap();
+            repo.find_tree(oid).unwrap()
+        };
+        let sig = git2::Signature::now("gc", "gc@example.com").unwrap();
+        let object = repo
+            .commit(None, &sig, &sig, "change", &empty_tree, &[])
+            .unwrap();
+        for name in &["xyz.radicle.issue/live", "xyz.radicle.issue/deleted"] {
+            repo.reference(&format!("{}/cobs/{}", remote, name), object, true, "")
+                .unwrap();
+        }
+
+        // Only `live` is published by the peer
+        let refs = Refs {
+            heads: Default::default(),
+            rad: Default::default(),
+            tags: Default::default(),
+            notes: Default::default(),
+            cobs: Some((
+                ext::OneLevel::from(reflike!("xyz.radicle.issue/live")),
+                object.into(),
+            ))
+            .into_iter()
+            .collect(),
+            remotes: Remotes::new(),
+        }
+        .sign(&peer_key)
+        .unwrap();
+        let blob = repo.blob(&serde_json::to_vec(&refs).unwrap()).unwrap();
+        let tree = {
+            let mut builder = repo.treebuilder(None).unwrap();
+            builder.insert("refs", blob, 0o100_644).unwrap();
+            repo.find_tree(builder.write().unwrap()).unwrap()
+        };
+        repo.commit(
+            Some(&format!("{}/rad/signed_refs", remote)),
+            &sig,
+            &sig,
+            "signed refs",
+            &tree,
+            &[],
+        )
+        .unwrap();
+
+        let collected = gc::collect(&storage, &urn).unwrap();
+        assert_eq!(
+            vec![(
+                peer,
+                ext::RefLike::try_from(format!("{}/cobs/xyz.radicle.issue/deleted", remote))
+                    .unwrap()
+            )],
+            collected.pruned
+        );
+        assert!(collected.skipped.is_empty());
+        assert!(repo
+            .find_reference(&format!("{}/cobs/xyz.radicle.issue/live", remote))
+            .is_ok());
+    }
+}
+
+#[test]
+fn gc_skips_peers_without_sigrefs() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        let urn = Urn::new(git2::Oid::zero().into());
+        let peer = PeerId::from(SecretKey::new());
+        track(&storage, &urn, peer).unwrap();
+
+        let collected = gc::collect(&storage, &urn).unwrap();
+        assert!(collected.pruned.is_empty());
+        assert_eq!(
+            Some(peer).into_iter().collect::<BTreeSet<_>>(),
+            collected.skipped
+        );
+    }
+}
+
+#[test]
+fn patch_lifecycle() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        let repo = git2::Repository::open(paths.git_dir()).unwrap();
+        let urn = Urn::new(git2::Oid::zero().into());
+        let local = *storage.peer_id();
+
+        let empty_tree = {
+            let oid = repo.treebuilder(None).unwrap().write().unwrap();
+            repo.find_tree(oid).unwrap()
+        };
+        let sig = git2::Signature::now("patch", "patch@example.com").unwrap();
+        let base = repo
+            .commit(None, &sig, &sig, "base", &empty_tree, &[])
+            .unwrap();
+        let commit = |parent: git2::Oid, msg: &str| {
+            let parent = repo.find_commit(parent).unwrap();
+            repo.commit(None, &sig, &sig, msg, &empty_tree, &[&parent])
+                .unwrap()
+        };
+        let v1 = commit(base, "v1");
+        let v2 = commit(base, "v2");
+
+        let id = patch::open(
+            &storage,
+            &urn,
+            "Fix all the things".to_owned(),
+            "".to_owned(),
+            Proposal {
+                base: base.into(),
+                head: v1.into(),
+                peer: local,
+                note: None,
+            },
+        )
+        .unwrap();
+
+        // The revision head is anchored in the change history
+        let root = repo.find_commit(*id).unwrap();
+        assert!(root.parent_ids().any(|parent| parent == v1));
+
+        patch::review(
+            &storage,
+            &urn,
+            id,
+            0,
+            Verdict::Reject,
+            Some("nope".to_owned()),
+            vec![],
+        )
+        .unwrap();
+        patch::revise(
+            &storage,
+            &urn,
+            id,
+            Proposal {
+                base: base.into(),
+                head: v2.into(),
+                peer: local,
+                note: Some("addressed review".to_owned()),
+            },
+        )
+        .unwrap();
+        patch::review(&storage, &urn, id, 1, Verdict::Accept, None, vec![]).unwrap();
+        assert!(matches!(
+            patch::review(&storage, &urn, id, 2, Verdict::Accept, None, vec![]),
+            Err(patch::Error::NoSuchRevision { revision: 2, .. })
+        ));
+        patch::merge(&storage, &urn, id, 1, v2.into()).unwrap();
+
+        let patch = patch::get(&storage, &urn, id).unwrap().unwrap();
+        assert_eq!(patch.author, local);
+        assert_eq!(patch.revisions.len(), 2);
+        assert_eq!(patch.revisions[0].reviews[0].verdict, Verdict::Reject);
+        assert_eq!(patch.latest().head, v2.into());
+        assert_eq!(patch.latest().reviews[0].verdict, Verdict::Accept);
+        assert_eq!(patch.merge.as_ref().map(|merge| merge.revision), Some(1));
+
+        assert_eq!(patch::list(&storage, &urn).unwrap(), vec![patch]);
+        assert!(Refs::load(&storage, &urn, None)
+            .unwrap()
+            .unwrap()
+            .cobs
+            .contains_key(&ext::OneLevel::from(
+                ext::RefLike::try_from(format!("{}/{}", patch::TYPENAME, id)).unwrap()
+            )));
+    }
+}
+
+#[test]
+fn patch_concurrent_merges() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        let repo = git2::Repository::open(paths.git_dir()).unwrap();
+        let urn = Urn::new(git2::Oid::zero().into());
+        let local = *storage.peer_id();
+        let peer = PeerId::from(SecretKey::new());
+        track(&storage, &urn, peer).unwrap();
+
+        let empty_tree = {
+            let oid = repo.treebuilder(None).unwrap().write().unwrap();
+            repo.find_tree(oid).unwrap()
+        };
+        let sig = git2::Signature::now("patch", "patch@example.com").unwrap();
+        let base = repo
+            .commit(None, &sig, &sig, "base", &empty_tree, &[])
+            .unwrap();
+        let head = {
+            let base = repo.find_commit(base).unwrap();
+            repo.commit(None, &sig, &sig, "head", &empty_tree, &[&base])
+                .unwrap()
+        };
+
+        let id = patch::open(
+            &storage,
+            &urn,
+            "Concurrency".to_owned(),
+            "".to_owned(),
+            Proposal {
+                base: base.into(),
+                head: head.into(),
+                peer: local,
+                note: None,
+            },
+        )
+        .unwrap();
+        patch::merge(&storage, &urn, id, 0, head.into()).unwrap();
+
+        // `peer` merges the patch without having seen the local merge
+        {
+            let change = serde_json::json!({
+                "author": peer,
+                "change": { "type": "merge", "revision": 0, "commit": ext::Oid::from(head) },
+            });
+            let blob = repo.blob(&serde_json::to_vec(&change).unwrap()).unwrap();
+            let tree = {
+                let mut builder = repo.treebuilder(None).unwrap();
+                builder.insert(patch::CHANGE_PATH, blob, 0o100_644).unwrap();
+                repo.find_tree(builder.write().unwrap()).unwrap()
+            };
+            let root = repo.find_commit(*id).unwrap();
+            let anchor = repo.find_commit(head).unwrap();
+            repo.commit(
+                Some(&format!(
+                    "refs/namespaces/{}/refs/remotes/{}/cobs/{}/{}",
+                    urn.encode_id(),
+                    peer,
+                    patch::TYPENAME,
+                    id
+                )),
+                &sig,
+                &sig,
+                "change",
+                &tree,
+                &[&root, &anchor],
+            )
+            .unwrap();
+        }
+
+        let patch = patch::get(&storage, &urn, id).unwrap().unwrap();
+        let concurrency = patch::concurrency(&storage, &urn, id).unwrap().unwrap();
+
+        assert_eq!(concurrency.frontier.len(), 2);
+        assert_eq!(concurrency.conflicts.len(), 1);
+        let conflict = &concurrency.conflicts[0];
+        assert_eq!(conflict.field, patch::Field::Merge);
+        assert_eq!(conflict.discarded.len(), 1);
+        assert_eq!(
+            Some(conflict.applied.author),
+            patch.merge.map(|merge| merge.merger)
+        );
+        assert_eq!(
+            vec![conflict.applied.author, conflict.discarded[0].author]
+                .into_iter()
+                .collect::<BTreeSet<_>>(),
+            vec![local, peer].into_iter().collect()
+        );
+    }
+}
+
+#[test]
+fn patch_review_attachments() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        let repo = git2::Repository::open(paths.git_dir()).unwrap();
+        let urn = Urn::new(git2::Oid::zero().into());
+        let local = *storage.peer_id();
+
+        let empty_tree = {
+            let oid = repo.treebuilder(None).unwrap().write().unwrap();
+            repo.find_tree(oid).unwrap()
+        };
+        let sig = git2::Signature::now("patch", "patch@example.com").unwrap();
+        let base = repo
+            .commit(None, &sig, &sig, "base", &empty_tree, &[])
+            .unwrap();
+
+        let id = patch::open(
+            &storage,
+            &urn,
+            "Screenshots".to_owned(),
+            "".to_owned(),
+            Proposal {
+                base: base.into(),
+                head: base.into(),
+                peer: local,
+                note: None,
+            },
+        )
+        .unwrap();
+
+        let screenshot =
+            attachment::store(&storage, &urn, "screenshot.png".to_owned(), b"PNG").unwrap();
+        patch::review(
+            &storage,
+            &urn,
+            id,
+            0,
+            Verdict::Comment,
+            Some("looks off".to_owned()),
+            vec![screenshot.clone()],
+        )
+        .unwrap();
+
+        // Attachments not present in the storage can't be referred to
+        let unknown = Attachment {
+            oid: git2::Oid::hash_object(git2::ObjectType::Blob, b"nope")
+                .unwrap()
+                .into(),
+            name: "nope".to_owned(),
+            size: 4,
+        };
+        assert!(matches!(
+            patch::review(
+                &storage,
+                &urn,
+                id,
+                0,
+                Verdict::Comment,
+                None,
+                vec![unknown.clone()]
+            ),
+            Err(patch::Error::Attachment(attachment::Error::NotFound(_)))
+        ));
+        assert_eq!(
+            attachment::missing(&storage, vec![&screenshot, &unknown]).unwrap(),
+            vec![&unknown]
+        );
+        assert_eq!(attachment::read(&storage, &unknown).unwrap(), None);
+
+        let patch = patch::get(&storage, &urn, id).unwrap().unwrap();
+        let review = &patch.latest().reviews[0];
+        assert_eq!(review.attachments, vec![screenshot.clone()]);
+        assert_eq!(
+            attachment::read(&storage, &review.attachments[0]).unwrap(),
+            Some(b"PNG".to_vec())
+        );
+
+        // The attachment is covered by the signed refs, and so replicated
+        // along with the review
+        assert!(Refs::load(&storage, &urn, None)
+            .unwrap()
+            .unwrap()
+            .cobs
+            .contains_key(&ext::OneLevel::from(
+                ext::RefLike::try_from(format!("{}/{}", attachment::TYPENAME, screenshot.oid))
+                    .unwrap()
+            )));
+
+        assert!(matches!(
+            attachment::store(
+                &storage,
+                &urn,
+                "huge".to_owned(),
+                &vec![0; attachment::MAX_SIZE + 1]
+            ),
+            Err(attachment::Error::TooLarge { .. })
+        ));
+    }
+}
+
+#[test]
+fn ci_reports() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        let repo = git2::Repository::open(paths.git_dir()).unwrap();
+        let urn = Urn::new(git2::Oid::zero().into());
+        let local = *storage.peer_id();
+
+        let empty_tree = {
+            let oid = repo.treebuilder(None).unwrap().write().unwrap();
+            repo.find_tree(oid).unwrap()
+        };
+        let sig = git2::Signature::now("ci", "ci@example.com").unwrap();
+        let commit: ext::Oid = repo
+            .commit(None, &sig, &sig, "checked", &empty_tree, &[])
+            .unwrap()
+            .into();
+
+        assert!(ci::reports(&storage, &urn, commit).unwrap().is_empty());
+
+        let failure = Report {
+            status: Status::Failure,
+            branch: Some("main".to_owned()),
+            log_digest: "0".repeat(64),
+            timestamp: 1,
+        };
+        let success = Report {
+            status: Status::Success,
+            timestamp: 2,
+            ..failure.clone()
+        };
+        ci::publish(&storage, &urn, commit, &failure).unwrap();
+        ci::publish(&storage, &urn, commit, &success).unwrap();
+
+        let reports = ci::reports(&storage, &urn, commit).unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].peer, local);
+        assert_eq!(reports[0].commit, commit);
+        assert_eq!(reports[0].report, success);
+
+        // The history of reports is kept, and anchored to the checked commit
+        let tip = Refs::load(&storage, &urn, None)
+            .unwrap()
+            .unwrap()
+            .cobs
+            .get(&ext::OneLevel::from(
+                ext::RefLike::try_from(format!("{}/{}", ci::TYPENAME, commit)).unwrap(),
+            ))
+            .copied()
+            .unwrap();
+        let parents = repo
+            .find_commit(*tip)
+            .unwrap()
+            .parent_ids()
+            .collect::<Vec<_>>();
+        assert_eq!(parents.len(), 2);
+        assert_eq!(parents[1], *commit);
+
+        let unknown = git2::Oid::hash_object(git2::ObjectType::Commit, b"nope")
+            .unwrap()
+            .into();
+        assert!(matches!(
+            ci::publish(&storage, &urn, unknown, &success),
+            Err(ci::Error::Graph(graph::Error::MissingCommit(_)))
+        ));
+    }
+}
@@ -0,0 +1,61 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::{
+    git::{
+        fetch::haves::{self, Scope},
+        identities,
+        storage::Storage,
+        util,
+        Urn,
+    },
+    git_ext::{tree, Oid},
+    reflike,
+    SecretKey,
+};
+
+use crate::{librad::git::storage::storage, rad::identities::TestProject};
+
+#[test]
+fn fork_includes_upstream() -> anyhow::Result<()> {
+    let storage = storage(SecretKey::new());
+    let proj = TestProject::create(&storage)?;
+    let urn = proj.project.urn();
+    let head = util::quick_commit(
+        &storage,
+        &urn.with_path(reflike!("refs/heads/next")),
+        vec![("HI", tree::blob(b"Hi"))].into_iter().collect(),
+        "initial",
+    )?;
+    let head = Oid::from(head);
+
+    let whoami = identities::local::load(&storage, proj.owner.urn())?.unwrap();
+    let fork = identities::project::fork(&storage, &urn, whoami)?.urn();
+
+    assert_eq!(
+        haves::related(&storage, &fork)?,
+        vec![fork.clone(), urn.clone()]
+    );
+    assert_eq!(haves::related(&storage, &urn)?, vec![urn.clone()]);
+
+    let upstream_id = rad_id(&storage, &urn)?;
+    let fork_id = rad_id(&storage, &fork)?;
+    let namespace = haves::haves(&storage, &fork, Scope::Namespace)?;
+    assert!(namespace.contains(&fork_id));
+    assert!(namespace.contains(&head));
+    assert!(!namespace.contains(&upstream_id));
+
+    let related = haves::haves(&storage, &fork, Scope::Related)?;
+    assert!(related.contains(&fork_id));
+    assert!(related.contains(&upstream_id));
+
+    Ok(())
+}
+
+fn rad_id(storage: &Storage, urn: &Urn) -> anyhow::Result<Oid> {
+    let repo = git2::Repository::open(storage.path())?;
+    let oid = repo.refname_to_id(&format!("refs/namespaces/{}/refs/rad/id", urn.encode_id()))?;
+    Ok(Oid::from(oid))
+}
@@ -3,12 +3,18 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::collections::{BTreeMap, BTreeSet};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryFrom,
+};
 
 use pretty_assertions::assert_eq;
 
 use librad::{
-    git::fetch::Fetchspecs,
+    git::{
+        fetch::{Fetchspecs, RemoteHeads},
+        types::Fetchspec,
+    },
     git_ext as ext,
     identities::{urn::test::FakeId, Urn},
     reflike,
@@ -98,6 +104,7 @@ fn replicate_looks_legit() {
                 rad: Default::default(),
                 tags: Default::default(),
                 notes: Default::default(),
+                cobs: Default::default(),
                 remotes: Remotes::new(),
             },
         ),
@@ -114,6 +121,7 @@ fn replicate_looks_legit() {
                 rad: Default::default(),
                 tags: Default::default(),
                 notes: Default::default(),
+                cobs: Default::default(),
                 remotes: Remotes::new(),
             },
         ),
@@ -315,3 +323,95 @@ fn replicate_looks_legit() {
         .collect::<BTreeSet<String>>()
     )
 }
+
+#[test]
+fn remote_heads_peeled() {
+    let tag = ext::Oid::from(git2::Oid::from_str("11111111").unwrap());
+    let commit = ext::Oid::from(git2::Oid::from_str("22222222").unwrap());
+    let remote_heads = RemoteHeads::from_advertised(vec![
+        ("refs/heads/main", commit),
+        ("refs/tags/v1", tag),
+        ("refs/tags/v1^{}", commit),
+    ]);
+
+    assert_eq!(remote_heads.len(), 2);
+    assert_eq!(remote_heads.get(&reflike!("refs/tags/v1")), Some(&tag));
+    assert_eq!(
+        remote_heads.peeled(&reflike!("refs/tags/v1")),
+        Some(&commit)
+    );
+    assert_eq!(
+        remote_heads.peeled(&reflike!("refs/heads/main")),
+        Some(&commit)
+    );
+}
+
+#[test]
+fn replicate_peeled_tags() {
+    use librad::git::refs::{Refs, Remotes};
+
+    let tag = ext::Oid::from(git2::Oid::from_str("11111111").unwrap());
+    let commit = ext::Oid::from(git2::Oid::from_str("22222222").unwrap());
+    let other = ext::Oid::from(git2::Oid::from_str("33333333").unwrap());
+
+    // Lolek signed the commit his tags peel to
+    let tracked_sigrefs = Some((
+        LOLEK.clone(),
+        Refs {
+            heads: Default::default(),
+            rad: Default::default(),
+            tags: [
+                (ext::OneLevel::from(reflike!("v1")), commit),
+                (ext::OneLevel::from(reflike!("v2")), commit),
+            ]
+            .iter()
+            .cloned()
+            .collect(),
+            notes: Default::default(),
+            cobs: Default::default(),
+            remotes: Remotes::new(),
+        },
+    ))
+    .into_iter()
+    .collect::<BTreeMap<_, _>>();
+
+    let tags = PROJECT_NAMESPACE.join(reflike!("refs/remotes/lolek/tags"));
+    let v1 = tags.join(reflike!("v1")).to_string();
+    let v2 = tags.join(reflike!("v2")).to_string();
+    let remote_heads = RemoteHeads::from_advertised(vec![
+        (v1.as_str(), tag),
+        (format!("{}^{{}}", v1).as_str(), commit),
+        (v2.as_str(), tag),
+        (format!("{}^{{}}", v2).as_str(), other),
+    ]);
+
+    let specs = Fetchspecs::Replicate {
+        tracked_sigrefs,
+        delegates: BTreeSet::new(),
+        limit: Default::default(),
+    }
+    .refspecs(&*PROJECT_URN, TOLA.clone(), &remote_heads)
+    .into_iter()
+    .map(|spec| spec.to_string())
+    .collect::<BTreeSet<_>>();
+
+    assert!(specs.contains(&format!("{}:{}", v1, v1)));
+    assert!(!specs.contains(&format!("{}:{}", v2, v2)));
+}
+
+#[test]
+fn dst_of_maps_matching_refs() {
+    let exact = Fetchspec::try_from("refs/heads/main:refs/remotes/origin/main").unwrap();
+    assert_eq!(
+        exact.dst_of(&reflike!("refs/heads/main")),
+        Some(reflike!("refs/remotes/origin/main"))
+    );
+    assert_eq!(exact.dst_of(&reflike!("refs/heads/next")), None);
+
+    let glob = Fetchspec::try_from("refs/remotes/*/rad/id:refs/remotes/*/rad/id").unwrap();
+    assert_eq!(
+        glob.dst_of(&reflike!("refs/remotes/lolek/bolek/rad/id")),
+        Some(reflike!("refs/remotes/lolek/bolek/rad/id"))
+    );
+    assert_eq!(glob.dst_of(&reflike!("refs/remotes/lolek/rad/self")), None);
+}
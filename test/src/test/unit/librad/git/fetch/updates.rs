@@ -0,0 +1,51 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::git::fetch::{TipUpdate, UpdateKind};
+
+fn commit(
+    repo: &git2::Repository,
+    msg: &str,
+    parents: &[&git2::Commit],
+) -> anyhow::Result<git2::Oid> {
+    let sig = git2::Signature::now("dylan", "dylan@example.com")?;
+    let tree = repo.find_tree(repo.treebuilder(None)?.write()?)?;
+    Ok(repo.commit(None, &sig, &sig, msg, &tree, parents)?)
+}
+
+#[test]
+fn classify_and_combine() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let repo = git2::Repository::init_bare(tmp.path())?;
+    let base = commit(&repo, "base", &[])?;
+    let next = commit(&repo, "next", &[&repo.find_commit(base)?])?;
+    let other = commit(&repo, "unrelated", &[])?;
+
+    let created = TipUpdate::classify(&repo, git2::Oid::zero(), base);
+    assert_eq!(created.kind, UpdateKind::Created);
+    assert_eq!(created.old, None);
+
+    let ff = TipUpdate::classify(&repo, base, next);
+    assert_eq!(ff.kind, UpdateKind::FastForward);
+    assert_eq!(ff.old, Some(base.into()));
+    assert_eq!(ff.new, next.into());
+
+    let forced = TipUpdate::classify(&repo, next, other);
+    assert_eq!(forced.kind, UpdateKind::Forced);
+
+    let combined = ff.then(forced);
+    assert_eq!(combined.kind, UpdateKind::Forced);
+    assert_eq!(combined.old, Some(base.into()));
+    assert_eq!(combined.new, other.into());
+    assert_eq!(created.then(ff).kind, UpdateKind::Created);
+
+    let missing = git2::Oid::hash_object(git2::ObjectType::Blob, b"not fetched yet")?;
+    let unresolved = TipUpdate::classify(&repo, base, missing);
+    assert_eq!(unresolved.kind, UpdateKind::Unresolved);
+    assert_eq!(ff.then(unresolved).kind, UpdateKind::Unresolved);
+    assert_eq!(unresolved.then(forced).kind, UpdateKind::Forced);
+
+    Ok(())
+}
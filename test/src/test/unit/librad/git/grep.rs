@@ -0,0 +1,84 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::collections::BTreeMap;
+
+use librad::{
+    git::{grep, storage::Storage, Urn},
+    git_ext as ext,
+    paths::Paths,
+    SecretKey,
+};
+
+fn commit(repo: &git2::Repository, files: &[(&str, &[u8])]) -> ext::Oid {
+    let mut builder = repo.treebuilder(None).unwrap();
+    for (name, content) in files {
+        let blob = repo.blob(content).unwrap();
+        builder.insert(*name, blob, 0o100_644).unwrap();
+    }
+    let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+    let sig = git2::Signature::now("grep", "grep@example.com").unwrap();
+    repo.commit(None, &sig, &sig, "files", &tree, &[])
+        .unwrap()
+        .into()
+}
+
+#[test]
+fn search_indexed_heads() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        let repo = git2::Repository::open(paths.git_dir()).unwrap();
+        let urn = Urn::new(git2::Oid::zero().into());
+
+        let main = commit(
+            &repo,
+            &[
+                ("lib.rs", b"fn main() {\n    println!(\"hello\");\n}\n"),
+                ("README", b"Say hello to the world\n"),
+                ("logo.png", b"\x89PNG\x00hello"),
+            ],
+        );
+        let heads = vec![("refs/heads/main".to_owned(), main)]
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+        grep::update(&storage, &urn, &heads).unwrap();
+
+        let matches = grep::search(&storage, "hello", None).unwrap();
+        assert_eq!(
+            matches
+                .iter()
+                .map(|m| (m.path.as_str(), m.line))
+                .collect::<Vec<_>>(),
+            vec![("README", 1), ("lib.rs", 2)]
+        );
+        assert_eq!(matches[1].text, "    println!(\"hello\");");
+        assert_eq!(matches[1].branch, "refs/heads/main");
+        assert!(grep::search(&storage, "goodbye", Some(&urn))
+            .unwrap()
+            .is_empty());
+
+        // Patterns shorter than a trigram are matched against all files
+        assert_eq!(grep::search(&storage, "fn", Some(&urn)).unwrap().len(), 1);
+
+        // Moving the head drops what is no longer part of it
+        let next = commit(&repo, &[("lib.rs", b"fn main() {}\n")]);
+        let heads = vec![("refs/heads/main".to_owned(), next)]
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+        grep::update(&storage, &urn, &heads).unwrap();
+        assert!(grep::search(&storage, "hello", None).unwrap().is_empty());
+        assert_eq!(grep::search(&storage, "main", None).unwrap().len(), 1);
+
+        assert!(matches!(
+            grep::search(&storage, "", None),
+            Err(grep::Error::EmptyPattern)
+        ));
+
+        assert!(grep::remove(&storage, &urn).unwrap());
+        assert!(grep::search(&storage, "main", None).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,94 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::{
+    git::{
+        identities,
+        import::{self, name_from_url, Options},
+        refs::Refs,
+        storage::{ReadOnlyStorage as _, Storage},
+    },
+    git_ext as ext,
+    paths::Paths,
+    reflike,
+    SecretKey,
+};
+
+use crate::{git::create_commit, rad::identities::TestPerson};
+
+#[test]
+fn names_from_urls() {
+    assert_eq!(
+        Some("radicle-link".to_owned()),
+        name_from_url("https://github.com/radicle-dev/radicle-link.git")
+    );
+    assert_eq!(
+        Some("radicle-link".to_owned()),
+        name_from_url("git@github.com:radicle-dev/radicle-link/")
+    );
+    assert_eq!(Some("repo".to_owned()), name_from_url("/tmp/repo"));
+    assert_eq!(None, name_from_url("https://example.com/.git"));
+}
+
+#[test]
+fn import_and_pull() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let source = {
+            let mut options = git2::RepositoryInitOptions::new();
+            options.bare(true).initial_head("main");
+            git2::Repository::init_opts(tmp.path().join("source"), &options).unwrap()
+        };
+        let main = create_commit(&source, reflike!("refs/heads/main")).unwrap();
+        source
+            .reference("refs/tags/v1", main, false, "tag")
+            .unwrap();
+        let url = source.path().to_str().unwrap().to_owned();
+
+        let paths = Paths::from_root(tmp.path().join("storage")).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        let person = TestPerson::create(&storage).unwrap();
+        let whoami = identities::local::load(&storage, person.owner.urn())
+            .unwrap()
+            .unwrap();
+
+        let proj = import::import(&storage, whoami, &url, Options::default()).unwrap();
+        let urn = proj.urn();
+        assert_eq!("source", proj.subject().name.as_str());
+        assert_eq!(
+            Some("main"),
+            proj.subject().default_branch.as_ref().map(|b| b.as_str())
+        );
+        assert!(storage
+            .has_commit(
+                &urn.clone().with_path(reflike!("refs/heads/main")),
+                ext::Oid::from(main)
+            )
+            .unwrap());
+
+        let refs = Refs::load(&storage, &urn, None).unwrap().unwrap();
+        assert_eq!(
+            Some(&ext::Oid::from(main)),
+            refs.heads.get(&ext::OneLevel::from(reflike!("main")))
+        );
+        assert!(refs.tags.contains_key(&ext::OneLevel::from(reflike!("v1"))));
+
+        // Upstream gains a branch and loses a tag.
+        let dev = create_commit(&source, reflike!("refs/heads/dev")).unwrap();
+        source
+            .find_reference("refs/tags/v1")
+            .unwrap()
+            .delete()
+            .unwrap();
+        import::pull(&storage, &urn, &url).unwrap();
+
+        let refs = Refs::load(&storage, &urn, None).unwrap().unwrap();
+        assert_eq!(
+            Some(&ext::Oid::from(dev)),
+            refs.heads.get(&ext::OneLevel::from(reflike!("dev")))
+        );
+        assert!(refs.tags.is_empty());
+    }
+}
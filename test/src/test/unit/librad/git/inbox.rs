@@ -0,0 +1,71 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::{
+    git::{
+        inbox::{self, Kind, Notification},
+        storage::Storage,
+        Urn,
+    },
+    paths::Paths,
+    reflike,
+    PeerId,
+    SecretKey,
+};
+
+#[test]
+fn record_list_clear() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        let peer = PeerId::from(SecretKey::new());
+        let urn1 = Urn::new(git2::Oid::zero().into());
+        let urn2 = Urn::new(
+            git2::Oid::hash_object(git2::ObjectType::Blob, b"2")
+                .unwrap()
+                .into(),
+        );
+
+        assert!(inbox::list(&storage, None, None).unwrap().is_empty());
+
+        let commits = Notification {
+            urn: urn1.clone(),
+            remote_peer: peer,
+            timestamp: 10,
+            kind: Kind::Commits {
+                peer,
+                branch: reflike!("main"),
+                old: None,
+                new: git2::Oid::zero().into(),
+                count: 1,
+            },
+        };
+        let identity = Notification {
+            urn: urn2.clone(),
+            remote_peer: peer,
+            timestamp: 20,
+            kind: Kind::IdentityUpdate,
+        };
+        inbox::record(&storage, &[commits.clone()]).unwrap();
+        inbox::record(&storage, &[identity.clone()]).unwrap();
+
+        assert_eq!(
+            inbox::list(&storage, None, None).unwrap(),
+            vec![commits.clone(), identity.clone()]
+        );
+        assert_eq!(
+            inbox::list(&storage, Some(&urn1), None).unwrap(),
+            vec![commits]
+        );
+        assert_eq!(
+            inbox::list(&storage, None, Some(15)).unwrap(),
+            vec![identity]
+        );
+
+        inbox::clear(&storage).unwrap();
+        assert!(inbox::list(&storage, None, None).unwrap().is_empty());
+    }
+}
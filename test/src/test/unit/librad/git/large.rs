@@ -0,0 +1,136 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{collections::BTreeSet, iter};
+
+use librad::{
+    git::{
+        large::{self, Chunk, Pointer, Progress, Store},
+        storage::Storage,
+        util::quick_commit,
+    },
+    git_ext::tree,
+    paths::Paths,
+    reflike,
+    SecretKey,
+};
+
+use crate::{rad::identities::TestProject, roundtrip::cbor_roundtrip};
+
+#[test]
+fn pointer_roundtrip() {
+    let pointer = Pointer {
+        oid: large::Oid::hash(b"data"),
+        size: 4,
+    };
+    let text = pointer.to_string();
+    assert_eq!(Some(pointer), Pointer::parse(text.as_bytes()));
+    assert_eq!(
+        pointer.oid,
+        pointer.oid.to_string().parse::<large::Oid>().unwrap()
+    );
+
+    assert_eq!(None, Pointer::parse(b"data"));
+    assert_eq!(
+        None,
+        Pointer::parse(format!("{}trailer\n", text).as_bytes())
+    );
+}
+
+#[test]
+fn chunk_cbor() {
+    cbor_roundtrip(Chunk {
+        size: 10,
+        offset: 2,
+        data: b"abc".to_vec(),
+    })
+}
+
+#[test]
+fn resumable_download() {
+    let tmp = tempfile::tempdir().unwrap();
+    let source = Store::at(&tmp.path().join("source"));
+    let dest = Store::at(&tmp.path().join("dest"));
+
+    let data = iter::repeat(b"0123456789".iter().copied())
+        .take(large::CHUNK_SIZE / 5)
+        .flatten()
+        .collect::<Vec<_>>();
+    let pointer = source.offload(&data).unwrap();
+    assert!(source.has(&pointer.oid));
+    assert!(!dest.has(&pointer.oid));
+
+    let first = source.read_chunk(&pointer.oid, 0).unwrap().unwrap();
+    assert_eq!(large::CHUNK_SIZE, first.data.len());
+    assert_eq!(
+        Progress::Partial(large::CHUNK_SIZE as u64),
+        dest.write_chunk(&pointer, &first).unwrap()
+    );
+    // Out of order chunks are rejected
+    assert!(dest.write_chunk(&pointer, &first).is_err());
+
+    // Resume, as if after a restart
+    let offset = dest.resume_offset(&pointer.oid).unwrap();
+    let second = source.read_chunk(&pointer.oid, offset).unwrap().unwrap();
+    assert_eq!(
+        Progress::Complete,
+        dest.write_chunk(&pointer, &second).unwrap()
+    );
+    assert_eq!(Some(data), dest.read(&pointer.oid).unwrap());
+}
+
+#[test]
+fn corrupt_download() {
+    let tmp = tempfile::tempdir().unwrap();
+    let store = Store::at(tmp.path());
+    let pointer = Pointer {
+        oid: large::Oid::hash(b"data"),
+        size: 4,
+    };
+    let chunk = Chunk {
+        size: 4,
+        offset: 0,
+        data: b"nope".to_vec(),
+    };
+
+    assert!(matches!(
+        store.write_chunk(&pointer, &chunk),
+        Err(large::Error::Corrupt(_))
+    ));
+    assert!(!store.has(&pointer.oid));
+    assert_eq!(0, store.resume_offset(&pointer.oid).unwrap());
+}
+
+#[test]
+fn pointers_in_tree() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        let store = Store::open(&paths);
+        let pointer = store.offload(b"big").unwrap();
+        let text = pointer.to_string();
+
+        let proj = TestProject::create(&storage).unwrap();
+        let urn = proj.project.urn().with_path(reflike!("refs/heads/next"));
+        let tip = quick_commit(
+            &storage,
+            &urn,
+            vec![
+                ("README", tree::blob(b"not a pointer")),
+                ("big.bin", tree::blob(text.as_bytes())),
+            ]
+            .into_iter()
+            .collect(),
+            "large",
+        )
+        .unwrap();
+
+        assert_eq!(
+            Some(pointer).into_iter().collect::<BTreeSet<_>>(),
+            large::pointers(&storage, Some(tip)).unwrap()
+        );
+    }
+}
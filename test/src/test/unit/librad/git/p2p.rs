@@ -4,5 +4,6 @@
 // Linking Exception. For full terms see the included LICENSE file.
 
 mod header;
+mod pack_cache;
 mod server;
 mod url;
@@ -0,0 +1,92 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::io::Read as _;
+
+use futures::{executor::block_on, io::Cursor};
+use librad::git::{
+    p2p::{
+        pack_cache::{read_request, Key, PackCache, Stats},
+        server::pkt_line,
+    },
+    Urn,
+};
+
+const WANT: &str = "want 1111111111111111111111111111111111111111 ofs-delta\n";
+const HAVE: &str = "have 2222222222222222222222222222222222222222\n";
+
+#[test]
+fn clone_request() {
+    let req = format!("{}0000{}", pkt_line(WANT), pkt_line("done\n"));
+    let parsed = block_on(read_request(&mut Cursor::new(req.as_bytes()))).unwrap();
+    assert!(parsed.is_clone);
+    assert_eq!(parsed.bytes, req.as_bytes());
+}
+
+#[test]
+fn fetch_request() {
+    let req = format!(
+        "{}0000{}{}",
+        pkt_line(WANT),
+        pkt_line(HAVE),
+        pkt_line("done\n")
+    );
+    let parsed = block_on(read_request(&mut Cursor::new(req.as_bytes()))).unwrap();
+    assert!(!parsed.is_clone);
+    // Only read up to the first have
+    assert_eq!(
+        parsed.bytes,
+        format!("{}0000{}", pkt_line(WANT), pkt_line(HAVE)).as_bytes()
+    );
+}
+
+#[test]
+fn evicts_least_recently_used() {
+    let tmp = tempfile::tempdir().unwrap();
+    let urn = Urn::new(git2::Oid::zero().into());
+    let keys = ["a", "b", "c"]
+        .iter()
+        .map(|req| Key::new(&urn, req.as_bytes()))
+        .collect::<Vec<_>>();
+
+    let cache = PackCache::open(tmp.path(), 8).unwrap();
+    let put = |key: &Key, data: &[u8]| {
+        let mut pending = cache.pending().unwrap();
+        pending.write(data).unwrap();
+        cache.insert(key.clone(), pending).unwrap()
+    };
+
+    assert!(put(&keys[0], b"aaaa"));
+    assert!(put(&keys[1], b"bbbb"));
+    assert!(cache.get(&keys[0]).unwrap().is_some());
+    // Evicts "b", which was used less recently than "a"
+    assert!(put(&keys[2], b"cccc"));
+    assert!(cache.get(&keys[1]).unwrap().is_none());
+
+    let mut data = String::new();
+    cache
+        .get(&keys[0])
+        .unwrap()
+        .unwrap()
+        .read_to_string(&mut data)
+        .unwrap();
+    assert_eq!(data, "aaaa");
+
+    // Larger than the whole cache
+    assert!(!put(&keys[1], b"bbbbbbbbb"));
+    assert_eq!(
+        cache.stats(),
+        Stats {
+            hits: 2,
+            misses: 1,
+            entries: 2,
+            size: 8
+        }
+    );
+
+    // Picked up again
+    let cache = PackCache::open(tmp.path(), 8).unwrap();
+    assert_eq!(cache.stats().entries, 2);
+}
@@ -6,12 +6,17 @@
 use either::Either::Left;
 
 use librad::{
-    git::identities,
+    git::{identities, util},
+    git_ext::tree,
     identities::{delegation, payload, SomeIdentity},
+    reflike,
     SecretKey,
 };
 
-use crate::librad::git::{self, storage::storage};
+use crate::{
+    librad::git::{self, storage::storage},
+    rad::identities::TestProject,
+};
 
 lazy_static! {
     static ref DYLAN: SecretKey = SecretKey::from_seed([
@@ -46,3 +51,36 @@ fn create_anonymous() -> anyhow::Result<()> {
     );
     Ok(())
 }
+
+#[test]
+fn fork_shares_history() -> anyhow::Result<()> {
+    let storage = storage(SecretKey::new());
+    let proj = TestProject::create(&storage)?;
+    let urn = proj.project.urn();
+    let next = reflike!("refs/heads/next");
+    let head = util::quick_commit(
+        &storage,
+        &urn.with_path(next.clone()),
+        vec![("HI", tree::blob(b"Hi"))].into_iter().collect(),
+        "initial",
+    )?;
+
+    let whoami = identities::local::load(&storage, proj.owner.urn())?.unwrap();
+    let fork = identities::project::fork(&storage, &urn, whoami)?;
+    let fork_urn = fork.urn();
+    assert_ne!(fork_urn, urn);
+    assert_eq!(
+        fork.payload().fork()?.map(|fork| fork.upstream),
+        Some(urn.clone())
+    );
+    assert!(identities::project::verify(&*storage, &fork_urn)?.is_some());
+
+    let repo = git2::Repository::open(storage.path())?;
+    let forked = repo.refname_to_id(&format!(
+        "refs/namespaces/{}/refs/heads/next",
+        fork_urn.encode_id()
+    ))?;
+    assert_eq!(forked, head);
+
+    Ok(())
+}
@@ -54,6 +54,24 @@ mod remotes {
         )
     }
 
+    #[test]
+    fn flatten_bounded() {
+        let (all, omitted) = REMOTES.flatten_bounded(100);
+        assert_eq!(
+            vec!["alice", "bob", "bolek", "carol", "dylan", "lolek", "tola"],
+            all.into_iter().map(String::as_str).collect::<Vec<_>>()
+        );
+        assert_eq!(0, omitted);
+
+        // Closest first
+        let (some, omitted) = REMOTES.flatten_bounded(3);
+        assert_eq!(
+            vec!["alice", "bob", "lolek"],
+            some.into_iter().map(String::as_str).collect::<Vec<_>>()
+        );
+        assert_eq!(4, omitted);
+    }
+
     #[test]
     fn cutoff() {
         assert_eq!(
@@ -95,3 +113,103 @@ mod remotes {
         )
     }
 }
+
+mod notes {
+    use std::convert::TryFrom as _;
+
+    use librad::{
+        git::{refs::Refs, storage::config::Config, types::Namespace},
+        git_ext as ext,
+        reflike,
+        SecretKey,
+    };
+
+    use crate::{librad::git::storage::storage, rad::identities::TestProject};
+
+    #[test]
+    fn signed_unless_opted_out() {
+        let store = storage(SecretKey::new());
+        let TestProject { project, .. } = TestProject::create(&store).unwrap();
+        let urn = project.urn();
+
+        {
+            let repo = git2::Repository::open(store.path()).unwrap();
+            let sig = git2::Signature::now("alice", "alice@example.com").unwrap();
+            let target = repo.blob(b"reviewed").unwrap();
+            repo.note(
+                &sig,
+                &sig,
+                Some(&format!(
+                    "refs/namespaces/{}/refs/notes/reviews",
+                    Namespace::from(&urn)
+                )),
+                target,
+                "lgtm",
+                false,
+            )
+            .unwrap();
+        }
+        let reviews = ext::OneLevel::from(reflike!("reviews"));
+
+        let refs = Refs::compute(&*store, &urn).unwrap();
+        assert!(refs.notes.contains_key(&reviews));
+
+        Config::try_from(&*store)
+            .unwrap()
+            .set_sign_notes(false)
+            .unwrap();
+        let refs = Refs::compute(&*store, &urn).unwrap();
+        assert!(refs.notes.is_empty());
+    }
+}
+
+mod load_at {
+    use librad::{
+        git::{
+            refs::{self, stored},
+            util,
+        },
+        git_ext::{tree, Oid, OneLevel},
+        reflike,
+        SecretKey,
+    };
+
+    use crate::{librad::git::storage::storage, rad::identities::TestProject};
+
+    #[test]
+    fn resolves_past_state() {
+        let store = storage(SecretKey::new());
+        let TestProject { project, .. } = TestProject::create(&store).unwrap();
+        let urn = project.urn();
+        let local = *store.peer_id();
+
+        let before = refs::tips(&*store, &urn).unwrap()[&local];
+        let head = util::quick_commit(
+            &store,
+            &urn.clone().with_path(reflike!("refs/heads/next")),
+            vec![("HI", tree::blob(b"Hello"))].into_iter().collect(),
+            "initial",
+        )
+        .unwrap();
+        let after = refs::tips(&*store, &urn).unwrap()[&local];
+        assert_ne!(before, after);
+
+        let next = OneLevel::from(reflike!("next"));
+        let past = store.refs_at(&urn, None, before).unwrap().unwrap();
+        assert!(!past.heads.contains_key(&next));
+        let present = store.refs_at(&urn, None, after).unwrap().unwrap();
+        assert_eq!(present.heads.get(&next), Some(&Oid::from(head)));
+    }
+
+    #[test]
+    fn rejects_foreign_commit() {
+        let store = storage(SecretKey::new());
+        let TestProject { project, .. } = TestProject::create(&store).unwrap();
+        let urn = project.urn();
+
+        assert_matches!(
+            store.refs_at(&urn, None, project.content_id),
+            Err(stored::Error::NotInHistory { .. })
+        );
+    }
+}
@@ -2,3 +2,457 @@
 //
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    cell::Cell,
+    convert::TryFrom as _,
+    io,
+    num::NonZeroUsize,
+    time::{Duration, SystemTime},
+};
+
+use futures::poll;
+
+use librad::{
+    git::{
+        fetch::RemoteHeads,
+        refs::{Refs, Remotes},
+        replication::{
+            self,
+            audit::{self, Entry},
+            error,
+            hygiene::{self, Action, Policy, Violation},
+            pack_sizes::{self, Adaptive},
+            retry::{self, Category, Classify as _},
+            sanity,
+            slots::{Limits, Slots, Stats},
+        },
+        storage::Storage,
+        tracking,
+        Urn,
+    },
+    git_ext as ext,
+    paths::Paths,
+    PeerId,
+    SecretKey,
+};
+
+fn refs(heads: &[&str]) -> Refs {
+    let oid = ext::Oid::from(git2::Oid::zero());
+    Refs {
+        heads: heads
+            .iter()
+            .map(|name| {
+                (
+                    ext::OneLevel::from(ext::RefLike::try_from(*name).unwrap()),
+                    oid,
+                )
+            })
+            .collect(),
+        rad: Default::default(),
+        tags: Default::default(),
+        notes: Default::default(),
+        cobs: Default::default(),
+        remotes: Remotes::new(),
+    }
+}
+
+#[test]
+fn hygiene_violations() {
+    let policy = Policy {
+        action: Action::Reject,
+        max_component_len: 10,
+        max_len: 32,
+        case_insensitive: false,
+    };
+
+    assert_eq!(policy.check("feature/x"), None);
+    assert_eq!(policy.check(&"a/".repeat(20)), Some(Violation::TooLong));
+    assert_eq!(
+        policy.check("feature/overlong-name"),
+        Some(Violation::ComponentTooLong)
+    );
+    assert_eq!(
+        policy.check("x/namespaces/y/refs/z"),
+        Some(Violation::Traversal)
+    );
+    assert_eq!(policy.check("main\u{202e}"), Some(Violation::Unprintable));
+}
+
+#[test]
+fn hygiene_drop_and_reject() {
+    let peer = PeerId::from(SecretKey::new());
+    let names = [
+        "main",
+        "namespaces/x/refs/heads/main",
+        "evil\u{202e}txt.exe",
+    ];
+    let before = hygiene::metrics();
+
+    let mut dropped = refs(&names);
+    let offending = Policy::default().apply(peer, &mut dropped).unwrap();
+    assert_eq!(
+        dropped.heads.keys().map(|k| k.as_str()).collect::<Vec<_>>(),
+        vec!["main"]
+    );
+    assert_eq!(offending.len(), 2);
+
+    let mut allowed = refs(&names);
+    Policy {
+        action: Action::Allow,
+        ..Policy::default()
+    }
+    .apply(peer, &mut allowed)
+    .unwrap();
+    assert_eq!(allowed.heads.len(), 3);
+
+    let mut rejected = refs(&names);
+    assert!(Policy {
+        action: Action::Reject,
+        ..Policy::default()
+    }
+    .apply(peer, &mut rejected)
+    .is_err());
+
+    let after = hygiene::metrics();
+    assert!(after.checked >= before.checked + 7);
+    let count =
+        |metrics: &hygiene::Metrics, key| metrics.violations.get(&key).copied().unwrap_or(0);
+    assert!(
+        count(&after, (Action::Drop, Violation::Traversal))
+            > count(&before, (Action::Drop, Violation::Traversal))
+    );
+    assert!(
+        count(&after, (Action::Allow, Violation::Unprintable))
+            > count(&before, (Action::Allow, Violation::Unprintable))
+    );
+}
+
+#[test]
+fn hygiene_case_collisions() {
+    let peer = PeerId::from(SecretKey::new());
+    let policy = Policy {
+        case_insensitive: true,
+        ..Policy::default()
+    };
+
+    let mut sigrefs = refs(&["Main", "main", "feature", "Feature/x", "fix/A", "Fix/b"]);
+    let mut dropped = policy.apply(peer, &mut sigrefs).unwrap();
+    dropped.sort();
+    assert_eq!(dropped, vec!["heads/feature", "heads/main"]);
+    assert_eq!(
+        sigrefs.heads.keys().map(|k| k.as_str()).collect::<Vec<_>>(),
+        vec!["Feature/x", "Fix/b", "Main", "fix/A"]
+    );
+
+    let err = Policy {
+        action: Action::Reject,
+        ..policy
+    }
+    .apply(peer, &mut refs(&["Main", "main"]))
+    .unwrap_err();
+    assert!(matches!(
+        err,
+        hygiene::Error::Rejected {
+            violation: Violation::CaseCollision,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn sanity_advertised_refs() {
+    let limits = sanity::Limits {
+        advertised_refs: 2,
+        ..Default::default()
+    };
+    let advertised = |n: usize| {
+        (0..n)
+            .map(|i| {
+                (
+                    ext::RefLike::try_from(format!("refs/heads/{}", i)).unwrap(),
+                    ext::Oid::from(git2::Oid::zero()),
+                )
+            })
+            .collect::<RemoteHeads>()
+    };
+
+    assert!(limits.check_advertised(&advertised(2)).is_ok());
+    let err = limits.check_advertised(&advertised(3)).unwrap_err();
+    assert!(matches!(
+        err,
+        sanity::Error::AdvertisedRefs {
+            advertised: 3,
+            limit: 2
+        }
+    ));
+
+    // A rejected namespace won't become acceptable by retrying
+    let err = replication::Error::from(err);
+    assert_eq!(err.category(), Category::Verification);
+    assert!(!err.is_retryable());
+}
+
+fn transient() -> replication::Error {
+    replication::Error::Fetch(error::Fetch::new(io::Error::new(
+        io::ErrorKind::ConnectionReset,
+        "connection reset",
+    )))
+}
+
+fn unverified() -> replication::Error {
+    replication::Error::Verification(error::Verification::MissingIdentity)
+}
+
+fn retry_policy() -> retry::Policy {
+    retry::Policy {
+        max_attempts: 3,
+        initial_backoff: Duration::from_millis(1),
+        max_backoff: Duration::from_millis(10),
+    }
+}
+
+#[test]
+fn retry_categories() {
+    assert_eq!(transient().category(), Category::Transient);
+    assert!(transient().is_retryable());
+    assert_eq!(unverified().category(), Category::Verification);
+    assert!(!unverified().is_retryable());
+    assert_eq!(
+        replication::Error::SelfReplication.category(),
+        Category::Permanent
+    );
+
+    // Local failures while determining what to fetch are attributed to the
+    // negotiation, not to the data fetched
+    let negotiation = replication::Error::from(error::Negotiation::Tracked(
+        tracking::Error::SelfReferential,
+    ));
+    assert_eq!(negotiation.category(), Category::Permanent);
+    assert_matches!(
+        std::error::Error::source(&negotiation)
+            .and_then(|source| source.downcast_ref::<error::Negotiation>()),
+        Some(error::Negotiation::Tracked(_))
+    );
+}
+
+#[test]
+fn retry_backoff_is_capped() {
+    let policy = retry_policy();
+    assert_eq!(policy.backoff(1), Duration::from_millis(1));
+    assert_eq!(policy.backoff(3), Duration::from_millis(4));
+    assert_eq!(policy.backoff(5), Duration::from_millis(10));
+    assert_eq!(policy.backoff(u32::MAX), Duration::from_millis(10));
+}
+
+#[tokio::test]
+async fn retry_transient_until_success() {
+    let attempts = Cell::new(0);
+    let res = retry::retrying(retry_policy(), || {
+        attempts.set(attempts.get() + 1);
+        let attempt = attempts.get();
+        async move {
+            if attempt < 3 {
+                Err(transient())
+            } else {
+                Ok(attempt)
+            }
+        }
+    })
+    .await;
+
+    assert_eq!(res.unwrap(), 3);
+}
+
+#[tokio::test]
+async fn retry_gives_up_after_max_attempts() {
+    let attempts = Cell::new(0);
+    let res = retry::retrying(retry_policy(), || {
+        attempts.set(attempts.get() + 1);
+        async { Err::<(), _>(transient()) }
+    })
+    .await;
+
+    assert!(res.unwrap_err().is_retryable());
+    assert_eq!(attempts.get(), 3);
+}
+
+#[tokio::test]
+async fn retry_not_on_verification_failure() {
+    let attempts = Cell::new(0);
+    let res = retry::retrying(retry_policy(), || {
+        attempts.set(attempts.get() + 1);
+        async { Err::<(), _>(unverified()) }
+    })
+    .await;
+
+    assert_matches!(res, Err(replication::Error::Verification(_)));
+    assert_eq!(attempts.get(), 1);
+}
+
+fn urns() -> (Urn, Urn) {
+    let other = git2::Oid::hash_object(git2::ObjectType::Blob, b"other").unwrap();
+    (Urn::new(git2::Oid::zero().into()), Urn::new(other.into()))
+}
+
+#[tokio::test]
+async fn slots_take_turns_per_urn() {
+    let slots = Slots::new(Limits {
+        total: NonZeroUsize::new(1),
+        ..Limits::default()
+    });
+    let (big, small) = urns();
+    let alice = PeerId::from(SecretKey::new());
+    let bob = PeerId::from(SecretKey::new());
+
+    let first = slots.acquire(&big, alice).await;
+    let mut big1 = Box::pin(slots.acquire(&big, alice));
+    let mut big2 = Box::pin(slots.acquire(&big, bob));
+    let mut small1 = Box::pin(slots.acquire(&small, bob));
+    assert!(poll!(big1.as_mut()).is_pending());
+    assert!(poll!(big2.as_mut()).is_pending());
+    assert!(poll!(small1.as_mut()).is_pending());
+    assert_eq!(
+        slots.stats(),
+        Stats {
+            running: 1,
+            waiting: 3
+        }
+    );
+
+    drop(first);
+    let next = big1.await;
+    // `small` was waiting longer than the second replication of `big`
+    drop(next);
+    assert!(poll!(big2.as_mut()).is_pending());
+    let next = small1.await;
+    drop(next);
+    drop(big2.await);
+    assert_eq!(slots.stats(), Stats::default());
+}
+
+#[tokio::test]
+async fn slots_per_remote() {
+    let slots = Slots::new(Limits {
+        per_remote: NonZeroUsize::new(1),
+        ..Limits::default()
+    });
+    let (urn1, urn2) = urns();
+    let alice = PeerId::from(SecretKey::new());
+    let bob = PeerId::from(SecretKey::new());
+
+    let first = slots.acquire(&urn1, alice).await;
+    let mut second = Box::pin(slots.acquire(&urn2, alice));
+    assert!(poll!(second.as_mut()).is_pending());
+    // Doesn't queue behind alice
+    let _other = slots.acquire(&urn2, bob).await;
+
+    drop(first);
+    let _second = second.await;
+    assert_eq!(
+        slots.stats(),
+        Stats {
+            running: 2,
+            waiting: 0
+        }
+    );
+}
+
+#[tokio::test]
+async fn slots_forget_abandoned_waiters() {
+    let slots = Slots::new(Limits {
+        total: NonZeroUsize::new(1),
+        ..Limits::default()
+    });
+    let (urn, _) = urns();
+    let alice = PeerId::from(SecretKey::new());
+
+    let first = slots.acquire(&urn, alice).await;
+    {
+        let mut abandoned = Box::pin(slots.acquire(&urn, alice));
+        assert!(poll!(abandoned.as_mut()).is_pending());
+    }
+    drop(first);
+    let _next = slots.acquire(&urn, alice).await;
+    assert_eq!(
+        slots.stats(),
+        Stats {
+            running: 1,
+            waiting: 0
+        }
+    );
+}
+
+#[test]
+fn adaptive_fetch_limit() {
+    let adaptive = Adaptive {
+        factor: NonZeroUsize::new(4).unwrap(),
+        floor: 100,
+    };
+    let ceiling = 10_000;
+
+    // Never fetched
+    assert_eq!(adaptive.limit(None, ceiling), ceiling);
+    assert_eq!(adaptive.limit(Some(1), ceiling), 100);
+    assert_eq!(adaptive.limit(Some(1_000), ceiling), 4_000);
+    assert_eq!(adaptive.limit(Some(5_000), ceiling), ceiling);
+    assert_eq!(adaptive.limit(Some(usize::MAX), ceiling), ceiling);
+}
+
+#[test]
+fn pack_sizes_keep_the_largest() {
+    let tmp = tempfile::tempdir().unwrap();
+    let paths = Paths::from_root(&tmp).unwrap();
+    let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+    let (urn, other) = urns();
+
+    assert_eq!(pack_sizes::largest(&storage, &urn).unwrap(), None);
+    pack_sizes::record(&storage, &urn, 1_000).unwrap();
+    pack_sizes::record(&storage, &urn, 10).unwrap();
+    assert_eq!(pack_sizes::largest(&storage, &urn).unwrap(), Some(1_000));
+    pack_sizes::record(&storage, &urn, 2_000).unwrap();
+    assert_eq!(pack_sizes::largest(&storage, &urn).unwrap(), Some(2_000));
+    assert_eq!(pack_sizes::largest(&storage, &other).unwrap(), None);
+}
+
+#[test]
+fn audit_log_rotates_and_reads_from_the_tail() {
+    let tmp = tempfile::tempdir().unwrap();
+    let paths = Paths::from_root(&tmp).unwrap();
+    let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+    let (urn, other) = urns();
+    let peer = PeerId::from(SecretKey::new());
+    let entry = |urn: &Urn, i: usize| {
+        Entry::new(
+            urn.clone(),
+            peer,
+            SystemTime::now(),
+            Duration::from_millis(i as u64),
+            &Err(replication::Error::SelfReplication),
+        )
+    };
+
+    assert!(audit::recent(&storage, None, 10).unwrap().is_empty());
+    // Rotates about every 4 entries
+    let max_size = serde_json::to_vec(&entry(&urn, 0)).unwrap().len() as u64 * 4;
+    for i in 0..12 {
+        let urn = if i % 2 == 0 { &urn } else { &other };
+        audit::record_with_limit(&storage, &entry(urn, i), max_size).unwrap();
+    }
+
+    let durations = |entries: Vec<Entry>| entries.iter().map(|e| e.duration).collect::<Vec<_>>();
+    assert_eq!(
+        durations(audit::recent(&storage, None, 3).unwrap()),
+        vec![9, 10, 11]
+    );
+    assert_eq!(
+        durations(audit::recent(&storage, Some(&urn), 4).unwrap()),
+        vec![4, 6, 8, 10]
+    );
+    // Older entries were rotated out
+    assert_eq!(
+        durations(audit::recent(&storage, None, 100).unwrap()).len(),
+        8
+    );
+    assert!(audit::recent(&storage, None, 0).unwrap().is_empty());
+}
@@ -3,5 +3,11 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+mod bitmap;
+mod commit_graph;
 mod config;
+mod fetcher;
+mod packed;
+mod reflog;
+mod stats;
 mod watch;
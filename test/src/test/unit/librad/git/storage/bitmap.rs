@@ -0,0 +1,46 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::{
+    git::storage::bitmap::{self, Counters, Coverage, Stats},
+    SecretKey,
+};
+
+use crate::{librad::git::storage::storage, rad::identities::TestProject};
+
+#[test]
+fn repack_writes_bitmap() {
+    let store = storage(SecretKey::new());
+    TestProject::create(&store).unwrap();
+
+    // Freshly created objects are loose
+    assert!(!Coverage::of(store.path()).unwrap().is_complete());
+
+    assert!(bitmap::repack_if_incomplete(&store).unwrap());
+    assert_eq!(
+        Coverage::of(store.path()).unwrap(),
+        Coverage {
+            packs: 1,
+            bitmapped: 1
+        }
+    );
+    assert!(!bitmap::repack_if_incomplete(&store).unwrap());
+}
+
+#[test]
+fn counters() {
+    let counters = Counters::default();
+    for &(packs, bitmapped) in &[(1, 1), (2, 2), (2, 1), (0, 0), (3, 0)] {
+        counters.record(&Coverage { packs, bitmapped });
+    }
+    assert_eq!(
+        counters.stats(),
+        Stats {
+            hits: 2,
+            partial: 1,
+            misses: 2
+        }
+    );
+}
@@ -0,0 +1,80 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::{
+    git::storage::commit_graph::{self, CommitGraph},
+    SecretKey,
+};
+
+use crate::librad::git::storage::storage;
+
+fn commit(repo: &git2::Repository, refname: &str, parents: &[git2::Oid]) -> git2::Oid {
+    let sig = git2::Signature::now("graph", "graph@example.com").unwrap();
+    let tree = {
+        let empty = repo.treebuilder(None).unwrap().write().unwrap();
+        repo.find_tree(empty).unwrap()
+    };
+    let parents = parents
+        .iter()
+        .map(|oid| repo.find_commit(*oid).unwrap())
+        .collect::<Vec<_>>();
+    repo.commit(
+        Some(refname),
+        &sig,
+        &sig,
+        refname,
+        &tree,
+        &parents.iter().collect::<Vec<_>>(),
+    )
+    .unwrap()
+}
+
+#[test]
+fn ancestry_many() {
+    let store = storage(SecretKey::new());
+    let repo = git2::Repository::open(store.path()).unwrap();
+
+    // a <- b <- c <- m
+    //  \           /
+    //   d <- e ---
+    // f
+    let a = commit(&repo, "refs/heads/a", &[]);
+    let b = commit(&repo, "refs/heads/b", &[a]);
+    let c = commit(&repo, "refs/heads/c", &[b]);
+    let d = commit(&repo, "refs/heads/d", &[a]);
+    let e = commit(&repo, "refs/heads/e", &[d]);
+    let m = commit(&repo, "refs/heads/m", &[c, e]);
+    let f = commit(&repo, "refs/heads/f", &[]);
+
+    let pairs = vec![
+        (a, m),
+        (d, m),
+        (m, a),
+        (c, e),
+        (e, e),
+        (f, m),
+        (a, c),
+        (b, e),
+    ];
+    let expected = vec![true, true, false, false, true, false, true, false];
+
+    assert_eq!(store.read_only().ancestry_many(&pairs).unwrap(), expected);
+
+    commit_graph::write(&store).unwrap();
+    let graph = CommitGraph::load(store.read_only()).unwrap().unwrap();
+    assert!(graph.generation(&m).unwrap() > graph.generation(&c).unwrap());
+    assert_eq!(graph.generation(&a), Some(1));
+
+    // Commits created after the graph was written are looked up in the odb
+    let n = commit(&repo, "refs/heads/n", &[m]);
+    let mut pairs = pairs;
+    pairs.push((d, n));
+    pairs.push((n, m));
+    let mut expected = expected;
+    expected.push(true);
+    expected.push(false);
+
+    assert_eq!(store.read_only().ancestry_many(&pairs).unwrap(), expected);
+}
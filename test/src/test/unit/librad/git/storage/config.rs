@@ -41,3 +41,12 @@ fn reinit_with_different_key() {
         Err(Error::AlreadyInitialised(pid)) if pid == *ALICE_PEER_ID
     )
 }
+
+#[test]
+fn sign_notes() {
+    let mut config = setup(&*ALICE_KEY);
+
+    assert!(config.sign_notes().unwrap());
+    config.set_sign_notes(false).unwrap();
+    assert!(!config.sign_notes().unwrap());
+}
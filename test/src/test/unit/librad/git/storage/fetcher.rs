@@ -0,0 +1,214 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{collections::BTreeSet, fs};
+
+use librad::{
+    git::{
+        fetch::{self, Fetcher as _, Fetchspecs},
+        identities,
+        replication::{self, Mode, Warning},
+        storage::{fetcher, ReadOnlyStorage as _},
+        tracking,
+        util,
+    },
+    git_ext::tree,
+    reflike,
+    SecretKey,
+};
+
+use crate::{librad::git::storage::storage, rad::identities::TestProject};
+
+#[test]
+fn replicate_from_local_monorepo() {
+    let work = storage(SecretKey::new());
+    let personal = storage(SecretKey::new());
+    let proj = TestProject::create(&work).unwrap();
+    let urn = proj.project.urn();
+
+    let remote = work.read_only();
+    let remote_peer = *remote.peer_id();
+    let fetcher = fetcher::Local::new(urn.clone(), remote)
+        .build(&personal)
+        .unwrap()
+        .unwrap();
+    let result =
+        replication::replicate(&personal, fetcher, replication::Config::default(), None).unwrap();
+
+    assert_matches!(result.mode, Mode::Clone);
+    assert!(personal.has_urn(&urn).unwrap());
+    assert!(tracking::is_tracked(&*personal, &urn, remote_peer).unwrap());
+    assert_eq!(
+        identities::project::verify(&*personal, &urn)
+            .unwrap()
+            .map(|proj| proj.content_id),
+        Some(proj.project.content_id)
+    );
+}
+
+#[test]
+fn report_skipped_fetch() {
+    let work = storage(SecretKey::new());
+    let personal = storage(SecretKey::new());
+    let proj = TestProject::create(&work).unwrap();
+    let urn = proj.project.urn();
+
+    let mut fetcher = fetcher::Local::new(urn, work.read_only())
+        .build(&personal)
+        .unwrap()
+        .unwrap();
+    let limit = fetch::Limit::default();
+
+    let specs = Fetchspecs::Peek {
+        remotes: BTreeSet::new(),
+        limit,
+    };
+    assert_eq!(fetch::Step::from(&specs), fetch::Step::Peek);
+    let res = fetcher.fetch(specs).unwrap();
+    assert_eq!(res.skipped, Some(fetch::Skipped::NoMatchingRefs));
+    assert!(res.updated_tips.is_empty());
+
+    let res = fetcher.fetch(Fetchspecs::PeekAll { limit }).unwrap();
+    assert_eq!(res.skipped, None);
+    assert!(!res.updated_tips.is_empty());
+}
+
+#[test]
+fn keep_newer_sigrefs() {
+    let work = storage(SecretKey::new());
+    let personal = storage(SecretKey::new());
+    let proj = TestProject::create(&work).unwrap();
+    let urn = proj.project.urn();
+    let remote_peer = *work.peer_id();
+
+    let replicate = || {
+        let fetcher = fetcher::Local::new(urn.clone(), work.read_only())
+            .build(&personal)
+            .unwrap()
+            .unwrap();
+        replication::replicate(&personal, fetcher, replication::Config::default(), None).unwrap()
+    };
+    let ns = format!("refs/namespaces/{}/refs", urn.encode_id());
+    let theirs = format!("{}/rad/signed_refs", ns);
+    let ours = format!("{}/remotes/{}/rad/signed_refs", ns, remote_peer);
+    let work_repo = git2::Repository::open(work.path()).unwrap();
+    let personal_repo = git2::Repository::open(personal.path()).unwrap();
+
+    replicate();
+    let old = work_repo.refname_to_id(&theirs).unwrap();
+    util::quick_commit(
+        &work,
+        &urn.clone().with_path(reflike!("refs/heads/next")),
+        vec![("HI", tree::blob(b"Hello"))].into_iter().collect(),
+        "initial",
+    )
+    .unwrap();
+    let new = work_repo.refname_to_id(&theirs).unwrap();
+    assert_ne!(old, new);
+    replicate();
+    assert_eq!(personal_repo.refname_to_id(&ours).unwrap(), new);
+
+    // Serve the older signed refs
+    work_repo.reference(&theirs, old, true, "rewind").unwrap();
+    let result = replicate();
+    assert_eq!(personal_repo.refname_to_id(&ours).unwrap(), new);
+    for warning in &result.warnings {
+        if let Warning::StaleSigrefs {
+            peer,
+            stored,
+            fetched,
+            behind,
+            ..
+        } = warning
+        {
+            assert_eq!(*peer, remote_peer);
+            assert_eq!(**stored, new);
+            assert_eq!(**fetched, old);
+            assert!(*behind);
+        }
+    }
+    assert!(!result.updated_tips.keys().any(|name| name.as_str() == ours));
+}
+
+#[test]
+fn renegotiate_once_if_pack_lacks_tips() {
+    let work = storage(SecretKey::new());
+    let personal = storage(SecretKey::new());
+    let proj = TestProject::create(&work).unwrap();
+    let urn = proj.project.urn();
+
+    // Advertise a tip the remote end doesn't have, as if it was pruned between
+    // listing the refs and packing them
+    let missing = git2::Oid::hash_object(git2::ObjectType::Blob, b"gone").unwrap();
+    let gone = work.path().join(format!(
+        "refs/namespaces/{}/refs/rad/ids/gone",
+        urn.encode_id()
+    ));
+    fs::create_dir_all(gone.parent().unwrap()).unwrap();
+    fs::write(&gone, format!("{}\n", missing)).unwrap();
+
+    let mut fetcher = fetcher::Local::new(urn, work.read_only())
+        .build(&personal)
+        .unwrap()
+        .unwrap();
+    let res = fetcher.fetch(Fetchspecs::PeekAll {
+        limit: fetch::Limit::default(),
+    });
+
+    assert!(res.is_err());
+    assert_eq!(fetcher.take_retries(), vec![fetch::Retry::Renegotiation]);
+    assert!(fetcher.take_retries().is_empty());
+}
+
+#[test]
+fn refetch_moved_tips() {
+    let work = storage(SecretKey::new());
+    let personal = storage(SecretKey::new());
+    let proj = TestProject::create(&work).unwrap();
+    let urn = proj.project.urn();
+    let remote_peer = *work.peer_id();
+
+    let ns = format!("refs/namespaces/{}/refs", urn.encode_id());
+    let theirs = format!("{}/rad/signed_refs", ns);
+    let ours = format!("{}/remotes/{}/rad/signed_refs", ns, remote_peer);
+    let work_repo = git2::Repository::open(work.path()).unwrap();
+    let personal_repo = git2::Repository::open(personal.path()).unwrap();
+    let peek = || Fetchspecs::Peek {
+        remotes: Some(remote_peer).into_iter().collect(),
+        limit: fetch::Limit::default(),
+    };
+    let fetcher = || {
+        fetcher::Local::new(urn.clone(), work.read_only())
+            .build(&personal)
+            .unwrap()
+            .unwrap()
+    };
+
+    fetcher().fetch(peek()).unwrap();
+    let old = work_repo.refname_to_id(&theirs).unwrap();
+    assert_eq!(personal_repo.refname_to_id(&ours).unwrap(), old);
+
+    util::quick_commit(
+        &work,
+        &urn.clone().with_path(reflike!("refs/heads/next")),
+        vec![("HI", tree::blob(b"Hello"))].into_iter().collect(),
+        "initial",
+    )
+    .unwrap();
+    let new = work_repo.refname_to_id(&theirs).unwrap();
+    assert_ne!(old, new);
+
+    // The tip the remote advertises now was not fetched before
+    let mut fetcher = fetcher();
+    let res = fetcher.fetch(peek()).unwrap();
+    assert_eq!(res.skipped, None);
+    assert_eq!(personal_repo.refname_to_id(&ours).unwrap(), new);
+
+    // Our ref was moved since the previous step, so it is fetched again
+    personal_repo.reference(&ours, old, true, "rewind").unwrap();
+    let res = fetcher.fetch(peek()).unwrap();
+    assert!(res.updated_tips.keys().any(|name| name.as_str() == ours));
+    assert_eq!(personal_repo.refname_to_id(&ours).unwrap(), new);
+}
@@ -0,0 +1,30 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::{
+    git::storage::{packed, watch},
+    SecretKey,
+};
+
+use crate::{librad::git::storage::storage, rad::identities::TestProject};
+
+#[test]
+fn compact_namespace() {
+    let store = storage(SecretKey::new());
+    let TestProject { project, .. } = TestProject::create(&store).unwrap();
+    let urn = project.urn();
+
+    let before = watch::snapshot(store.read_only(), &urn).unwrap();
+    let loose = packed::loose_refs(store.read_only(), &urn).unwrap();
+    assert!(loose > 0);
+
+    assert!(!packed::compact_if(&store, &urn, loose).unwrap());
+    assert!(packed::compact_if(&store, &urn, 0).unwrap());
+    assert_eq!(packed::loose_refs(store.read_only(), &urn).unwrap(), 0);
+
+    // All refs are still there
+    let after = watch::snapshot(store.read_only(), &urn).unwrap();
+    assert_eq!(before, after);
+}
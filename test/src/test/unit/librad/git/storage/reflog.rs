@@ -0,0 +1,81 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::time::Duration;
+
+use librad::{
+    git::{
+        storage::reflog::{self, Expired, Keep, Retention, Rule},
+        types::RefsCategory,
+    },
+    SecretKey,
+};
+
+use crate::{librad::git::storage::storage, rad::identities::TestProject};
+
+#[test]
+fn expire_per_category() {
+    let store = storage(SecretKey::new());
+    let TestProject { project, .. } = TestProject::create(&store).unwrap();
+    let repo = git2::Repository::open(store.path()).unwrap();
+
+    let ns = format!("refs/namespaces/{}/refs", project.urn().encode_id());
+    let head = format!("{}/heads/main", ns);
+    let id = format!("{}/rad/id", ns);
+    let tip = repo.refname_to_id(&id).unwrap();
+    repo.reference_ensure_log(&head).unwrap();
+    for i in 0..5 {
+        repo.reference(&head, tip, true, &format!("update {}", i))
+            .unwrap();
+    }
+    let rad_entries = repo.reflog(&id).unwrap().len();
+    assert!(repo.reflog(&head).unwrap().len() >= 5);
+
+    // Keeping everything is a no-op
+    assert_eq!(
+        reflog::expire(&store, &Retention::default()).unwrap(),
+        Expired::default()
+    );
+
+    let mut retention = Retention::default();
+    retention.set(RefsCategory::Heads, Keep::Entries(2));
+    let expired = reflog::expire(&store, &retention).unwrap();
+    assert!(expired.entries >= 3);
+    let log = repo.reflog(&head).unwrap();
+    assert_eq!(log.len(), 2);
+    assert_eq!(log.get(0).unwrap().message(), Some("update 4"));
+    assert_eq!(repo.reflog(&id).unwrap().len(), rad_entries);
+
+    retention.set(RefsCategory::Heads, Keep::Nothing);
+    let expired = reflog::expire(&store, &retention).unwrap();
+    assert_eq!(expired.reflogs, 1);
+    assert!(repo.reflog(&head).unwrap().is_empty());
+    assert_eq!(repo.reflog(&id).unwrap().len(), rad_entries);
+}
+
+#[test]
+fn parse_rules() {
+    let rules = ["heads=30d", "rad=all", "cobs=100", "tags=none"]
+        .iter()
+        .map(|s| s.parse::<Rule>().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        rules.iter().collect::<Retention>(),
+        Retention {
+            heads: Keep::Age(Duration::from_secs(30 * 24 * 60 * 60)),
+            rad: Keep::Everything,
+            tags: Keep::Nothing,
+            notes: Keep::Everything,
+            cobs: Keep::Entries(100),
+        }
+    );
+    for rule in &rules {
+        assert_eq!(rule.to_string().parse::<Rule>().unwrap(), *rule)
+    }
+
+    assert!("heads".parse::<Rule>().is_err());
+    assert!("branches=1".parse::<Rule>().is_err());
+    assert!("heads=xd".parse::<Rule>().is_err());
+}
@@ -0,0 +1,60 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::{git::storage::stats::Stats, SecretKey};
+
+use crate::{librad::git::storage::storage, rad::identities::TestProject};
+
+#[test]
+fn count_reachable() {
+    let store = storage(SecretKey::new());
+    let repo = git2::Repository::open(store.path()).unwrap();
+
+    let sig = git2::Signature::now("stats", "stats@example.com").unwrap();
+    let blob = repo.blob(b"hello").unwrap();
+    let tree = {
+        let mut builder = repo.treebuilder(None).unwrap();
+        builder.insert("hello", blob, 0o100_644).unwrap();
+        repo.find_tree(builder.write().unwrap()).unwrap()
+    };
+    let first = repo.commit(None, &sig, &sig, "first", &tree, &[]).unwrap();
+    let second = {
+        let first = repo.find_commit(first).unwrap();
+        repo.commit(None, &sig, &sig, "second", &tree, &[&first])
+            .unwrap()
+    };
+
+    let odb = repo.odb().unwrap();
+    let size = |oid| odb.read_header(oid).unwrap().0 as u64;
+    assert_eq!(
+        store
+            .read_only()
+            .count_reachable(vec![second, first])
+            .unwrap(),
+        Stats {
+            objects: 4,
+            bytes: size(first) + size(second) + size(tree.id()) + size(blob),
+            max_depth: 2,
+        }
+    );
+    assert_eq!(
+        store
+            .read_only()
+            .count_reachable(vec![first])
+            .unwrap()
+            .max_depth,
+        1
+    );
+}
+
+#[test]
+fn count_namespace() {
+    let store = storage(SecretKey::new());
+    let TestProject { project, .. } = TestProject::create(&store).unwrap();
+
+    let stats = store.read_only().count_namespace(&project.urn()).unwrap();
+    assert!(stats.objects > 0);
+    assert!(stats.max_depth > 0);
+}
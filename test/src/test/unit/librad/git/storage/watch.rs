@@ -7,10 +7,11 @@ use std::{collections::BTreeSet, convert::TryFrom as _};
 
 use librad::{
     git::{
-        storage::watch::{EventKind, NamespaceEvent},
+        storage::watch::{self, EventKind, NamespaceEvent, RefUpdate},
         Urn,
     },
     git_ext::RefLike,
+    reflike,
     SecretKey,
 };
 
@@ -43,3 +44,35 @@ fn namespaces() {
 
     assert_eq!(expected, events)
 }
+
+#[test]
+fn snapshot_diff() {
+    let store = storage(SecretKey::new());
+    let TestProject { project, .. } = TestProject::create(&store).unwrap();
+    let urn = project.urn();
+
+    let before = watch::snapshot(store.read_only(), &urn).unwrap();
+    assert!(before.contains_key(&reflike!("refs/rad/id")));
+    assert!(watch::diff(&before, &before).is_empty());
+
+    let id = before[&reflike!("refs/rad/id")];
+    let mut after = before.clone();
+    after.insert(reflike!("refs/heads/next"), id);
+    after.remove(&reflike!("refs/rad/id"));
+
+    assert_eq!(
+        vec![
+            RefUpdate {
+                name: reflike!("refs/heads/next"),
+                old: None,
+                new: Some(id),
+            },
+            RefUpdate {
+                name: reflike!("refs/rad/id"),
+                old: Some(id),
+                new: None,
+            },
+        ],
+        watch::diff(&before, &after)
+    );
+}
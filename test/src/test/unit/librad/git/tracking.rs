@@ -8,7 +8,19 @@ use std::collections::BTreeSet;
 use librad::{
     git::{
         storage::Storage,
-        tracking::{is_tracked, track, tracked, untrack},
+        tracking::{
+            exclude_cobs,
+            excluded_cobs,
+            include_cobs,
+            is_tracked,
+            track,
+            track_batch,
+            tracked,
+            untrack,
+            untrack_with,
+            UntrackArgs,
+            ALL_COBS,
+        },
         Urn,
     },
     paths::Paths,
@@ -47,6 +59,57 @@ fn track_untrack_is_not_tracked() {
     }
 }
 
+#[test]
+fn track_untrack_without_prune_is_not_tracked() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        let remote_peer = PeerId::from(SecretKey::new());
+        let urn = Urn::new(git2::Oid::zero().into());
+
+        track(&storage, &urn, remote_peer).unwrap();
+        assert!(untrack_with(&storage, &urn, remote_peer, UntrackArgs { prune: false }).unwrap());
+        assert!(!is_tracked(&storage, &urn, remote_peer).unwrap())
+    }
+}
+
+#[test]
+fn untrack_prunes_only_the_untracked_peer() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        let alice = PeerId::from(SecretKey::new());
+        let bob = PeerId::from(SecretKey::new());
+        let carol = PeerId::from(SecretKey::new());
+        let urn = Urn::new(git2::Oid::zero().into());
+
+        let repo = git2::Repository::open(paths.git_dir()).unwrap();
+        let blob = repo.blob(b"ref").unwrap();
+        let remote_ref = |peer: &PeerId| {
+            format!(
+                "refs/namespaces/{}/refs/remotes/{}/heads/main",
+                urn.encode_id(),
+                peer
+            )
+        };
+        for peer in &[alice, bob, carol] {
+            track(&storage, &urn, *peer).unwrap();
+            repo.reference(&remote_ref(peer), blob, false, "test")
+                .unwrap();
+        }
+
+        assert!(untrack(&storage, &urn, bob).unwrap());
+        assert!(untrack_with(&storage, &urn, carol, UntrackArgs { prune: false }).unwrap());
+
+        assert!(repo.find_reference(&remote_ref(&bob)).is_err());
+        assert!(repo.find_reference(&remote_ref(&alice)).is_ok());
+        assert!(repo.find_reference(&remote_ref(&carol)).is_ok());
+        assert!(is_tracked(&storage, &urn, alice).unwrap());
+    }
+}
+
 #[test]
 fn track_track_is_tracked() {
     let tmp = tempfile::tempdir().unwrap();
@@ -96,6 +159,45 @@ fn track_yields_tracked() {
     }
 }
 
+#[test]
+fn track_batch_yields_tracked() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        let peer1 = PeerId::from(SecretKey::new());
+        let peer2 = PeerId::from(SecretKey::new());
+        let urn = Urn::new(git2::Oid::zero().into());
+        let other = Urn::new(git2::Oid::from_bytes(&[1; 20]).unwrap().into());
+
+        track(&storage, &urn, peer1).unwrap();
+        let created = track_batch(
+            &storage,
+            vec![
+                (urn.clone(), peer1),
+                (urn.clone(), peer2),
+                (urn.clone(), peer2),
+                (other.clone(), peer1),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            vec![(urn.clone(), peer2), (other.clone(), peer1)]
+                .into_iter()
+                .collect::<BTreeSet<_>>(),
+            created
+        );
+        assert_eq!(
+            [peer1, peer2].iter().copied().collect::<BTreeSet<_>>(),
+            tracked(&storage, &urn).unwrap().collect::<BTreeSet<_>>()
+        );
+        assert!(is_tracked(&storage, &other, peer1).unwrap());
+
+        let local = *storage.peer_id();
+        assert!(track_batch(&storage, vec![(urn, local)]).is_err());
+    }
+}
+
 #[test]
 fn tracked_ignores_urn_path() {
     let tmp = tempfile::tempdir().unwrap();
@@ -111,3 +213,69 @@ fn tracked_ignores_urn_path() {
         assert_eq!(Some(remote_peer), tracked(&storage, &urn).unwrap().next())
     }
 }
+
+#[test]
+fn exclude_include_cobs() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        let remote_peer = PeerId::from(SecretKey::new());
+        let urn = Urn::new(git2::Oid::zero().into());
+
+        track(&storage, &urn, remote_peer).unwrap();
+        assert!(exclude_cobs(&storage, &urn, remote_peer, "xyz.radicle.ci").unwrap());
+        assert!(!exclude_cobs(&storage, &urn, remote_peer, "xyz.radicle.ci").unwrap());
+        assert!(exclude_cobs(&storage, &urn, remote_peer, "xyz.radicle.issue").unwrap());
+        assert_eq!(
+            vec!["xyz.radicle.ci", "xyz.radicle.issue"],
+            excluded_cobs(&storage, &urn, remote_peer)
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>()
+        );
+
+        // `.` must not act as a wildcard
+        assert!(!include_cobs(&storage, &urn, remote_peer, "xyz-radicle-ci").unwrap());
+        assert!(include_cobs(&storage, &urn, remote_peer, "xyz.radicle.issue").unwrap());
+        assert_eq!(
+            vec!["xyz.radicle.ci"],
+            excluded_cobs(&storage, &urn, remote_peer)
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>()
+        );
+
+        untrack(&storage, &urn, remote_peer).unwrap();
+        assert!(excluded_cobs(&storage, &urn, remote_peer)
+            .unwrap()
+            .is_empty());
+    }
+}
+
+#[test]
+fn exclude_all_cobs() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        let remote_peer = PeerId::from(SecretKey::new());
+        let urn = Urn::new(git2::Oid::zero().into());
+
+        track(&storage, &urn, remote_peer).unwrap();
+        assert!(exclude_cobs(&storage, &urn, remote_peer, "xyz.radicle.ci").unwrap());
+        assert!(exclude_cobs(&storage, &urn, remote_peer, ALL_COBS).unwrap());
+        assert!(!exclude_cobs(&storage, &urn, remote_peer, ALL_COBS).unwrap());
+        assert!(exclude_cobs(&storage, &urn, remote_peer, "**").is_err());
+
+        // `*` must not act as a wildcard
+        assert!(include_cobs(&storage, &urn, remote_peer, ALL_COBS).unwrap());
+        assert_eq!(
+            vec!["xyz.radicle.ci"],
+            excluded_cobs(&storage, &urn, remote_peer)
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -6,7 +6,13 @@
 use std::convert::TryFrom;
 
 use librad::{
-    git::{types::reference::Reference, Urn},
+    git::{
+        types::{
+            reference::{Reference, SymbolicRef, SymrefPolicy, TargetUpdate},
+            Force,
+        },
+        Urn,
+    },
     git_ext as ext,
     identities,
     reflike,
@@ -52,3 +58,69 @@ fn onelevel_path_urn_roundtrip() {
         Urn::try_from(as_ref).unwrap()
     )
 }
+
+#[test]
+fn symref_target_policy() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let repo = git2::Repository::init_bare(tmp.path())?;
+    let sig = git2::Signature::now("dylan", "dylan@example.com")?;
+    let tree = repo.find_tree(repo.treebuilder(None)?.write()?)?;
+    let base = repo.commit(None, &sig, &sig, "base", &tree, &[])?;
+    let next = repo.commit(None, &sig, &sig, "next", &tree, &[&repo.find_commit(base)?])?;
+    let other = repo.commit(None, &sig, &sig, "other", &tree, &[])?;
+
+    let main = reflike!("refs/heads/main");
+    let symref = SymbolicRef {
+        source: reflike!("refs/heads/alias"),
+        target: main.clone(),
+        force: Force::True,
+    };
+
+    // The target must exist, unless it may be created
+    assert!(symref.create(&repo).is_err());
+    let create = SymrefPolicy {
+        create_target: true,
+        ..SymrefPolicy::default()
+    };
+    symref.create_with(&repo, Some(base), create)?;
+    assert_eq!(repo.refname_to_id(main.as_str())?, base);
+
+    // By default, the target is left alone
+    symref.create_with(&repo, Some(next), SymrefPolicy::default())?;
+    assert_eq!(repo.refname_to_id(main.as_str())?, base);
+
+    let ff = SymrefPolicy {
+        update_target: TargetUpdate::FastForward,
+        ..SymrefPolicy::default()
+    };
+    symref.create_with(&repo, Some(next), ff)?;
+    assert_eq!(repo.refname_to_id(main.as_str())?, next);
+    assert!(symref.create_with(&repo, Some(other), ff).is_err());
+
+    let force = SymrefPolicy {
+        update_target: TargetUpdate::Force,
+        ..SymrefPolicy::default()
+    };
+    symref.create_with(&repo, Some(other), force)?;
+    assert_eq!(repo.refname_to_id(main.as_str())?, other);
+
+    // Chains of symrefs can be ruled out
+    let chained = SymbolicRef {
+        source: reflike!("refs/heads/alias2"),
+        target: reflike!("refs/heads/alias"),
+        force: Force::True,
+    };
+    assert!(chained
+        .create_with(
+            &repo,
+            None,
+            SymrefPolicy {
+                allow_chain: false,
+                ..SymrefPolicy::default()
+            }
+        )
+        .is_err());
+    chained.create(&repo)?;
+
+    Ok(())
+}
@@ -3,9 +3,11 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+use std::{cell::RefCell, collections::HashMap};
+
 use librad::{
     identities::{
-        git::{error, VerificationError},
+        git::{error, Checkpoint, Person, VerificationCache, VerificationError},
         Identities,
     },
     SecretKey,
@@ -121,3 +123,69 @@ fn revoke_a_trois() -> anyhow::Result<()> {
         desktop.assert_verifies()
     }
 }
+
+#[test]
+fn verify_from_checkpoint() -> anyhow::Result<()> {
+    let repo = repo()?;
+    {
+        let git = Identities::<Person>::from(&*repo);
+        let desktop = Device::new(&*DESKTOP, Identities::from(&*repo))?;
+        let (_, checkpoint) = git.verify_with(*desktop.current().content_id, None, |_| {})?;
+
+        let desktop = desktop.update(Some(
+            vec![DESKTOP.public(), LAPTOP.public()]
+                .into_iter()
+                .collect(),
+        ))?;
+        let laptop = Device::create_from(&*LAPTOP, &desktop)?;
+
+        let mut visited = Vec::new();
+        let (verified, _) =
+            git.verify_with(*laptop.current().content_id, Some(checkpoint), |progress| {
+                visited.push(progress.content_id)
+            })?;
+
+        // Only the commits after the checkpoint are visited
+        assert_eq!(
+            visited,
+            vec![desktop.current().content_id, laptop.current().content_id]
+        );
+        assert_eq!(&verified.into_inner(), laptop.current());
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct MemCache(RefCell<HashMap<git2::Oid, Checkpoint>>);
+
+impl VerificationCache for MemCache {
+    fn get(&self, key: git2::Oid) -> Option<Checkpoint> {
+        self.0.borrow().get(&key).copied()
+    }
+
+    fn put(&self, key: git2::Oid, result: Checkpoint) {
+        self.0.borrow_mut().insert(key, result);
+    }
+}
+
+#[test]
+fn verify_cached() -> anyhow::Result<()> {
+    let repo = repo()?;
+    {
+        let cache = MemCache::default();
+        let git = Identities::<Person>::from(&*repo).with_cache(&cache);
+        let desktop = Device::new(&*DESKTOP, Identities::from(&*repo))?;
+        let head = *desktop.current().content_id;
+
+        let (verified, _) = git.verify_with(head, None, |_| {})?;
+        assert_eq!(cache.0.borrow().len(), 1);
+
+        let mut visited = 0;
+        let (cached, _) = git.verify_with(head, None, |_| visited += 1)?;
+        assert_eq!(visited, 0, "history was walked despite cache entry");
+        assert_eq!(cached, verified);
+
+        Ok(())
+    }
+}
@@ -3,7 +3,7 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, thread, time::Duration};
 
 use either::Either::*;
 
@@ -333,6 +333,116 @@ fn fork() -> anyhow::Result<()> {
     }
 }
 
+#[test]
+fn nested() -> anyhow::Result<()> {
+    let repo = repo()?;
+    {
+        let cheyenne = Device::new(&*CHEYENNE_DESKTOP, Identities::from(&*repo))?;
+        let dylan = Device::new(&*DYLAN, Identities::from(&*repo))?;
+
+        // Dylan's organisation
+        let org = Project::new(dylan.clone())?;
+
+        let cheyenne_project = {
+            let update =
+                IndirectDelegation::try_from_iter(vec![Right(cheyenne.current().clone())])?
+                    .with_nested(Some(org.current().root))?;
+            Project::new(cheyenne.clone())?.update(update)
+        }?;
+        // Without resolving the nested delegation, the members of the
+        // organisation can't vote
+        cheyenne_project.assert_no_quorum()?;
+
+        let mut heads = current_heads_from(vec![&cheyenne, &dylan]);
+        heads.insert(org.current().urn(), *org.current().content_id);
+
+        // Dylan votes on behalf of the organisation
+        let dylan_project = Project::create_from(dylan, &cheyenne_project)?;
+        dylan_project.assert_verifies(lookup(&heads))
+    }
+}
+
+#[test]
+fn nested_membership_change() -> anyhow::Result<()> {
+    let repo = repo()?;
+    {
+        let cheyenne = Device::new(&*CHEYENNE_DESKTOP, Identities::from(&*repo))?;
+        let dylan = Device::new(&*DYLAN, Identities::from(&*repo))?;
+
+        // Dylan's organisation is the only delegation of the project
+        let org = Project::new(dylan.clone())?;
+        let project = {
+            let update =
+                IndirectDelegation::try_from_iter(None)?.with_nested(Some(org.current().root))?;
+            Project::new(dylan.clone())?.update(update)
+        }?;
+
+        // Dylan hands the organisation over to Cheyenne
+        let org = {
+            let update =
+                IndirectDelegation::try_from_iter(vec![Right(cheyenne.current().clone())])?;
+            Project::create_from(cheyenne.clone(), &org.update(update)?)
+        }?;
+
+        let mut heads = current_heads_from(vec![&cheyenne, &dylan]);
+        heads.insert(org.current().urn(), *org.current().content_id);
+
+        // Dylan's revision stays valid, and Cheyenne can build on it
+        let project = project
+            .handover(cheyenne)
+            .change_description("Now maintained by Cheyenne")?;
+        project.assert_verifies(lookup(&heads))?;
+
+        // Commit times have a resolution of one second
+        thread::sleep(Duration::from_secs(1));
+
+        // Dylan can no longer vote on behalf of the organisation
+        let rogue = project
+            .clone()
+            .handover(dylan)
+            .change_description("Still maintained by Dylan")?;
+        assert_eq!(
+            rogue.verify(lookup(&heads))?.content_id,
+            project.current().content_id
+        );
+
+        Ok(())
+    }
+}
+
+#[test]
+fn nested_cycle() -> anyhow::Result<()> {
+    let repo = repo()?;
+    {
+        let cheyenne = Device::new(&*CHEYENNE_DESKTOP, Identities::from(&*repo))?;
+        let dylan = Device::new(&*DYLAN, Identities::from(&*repo))?;
+
+        let org = Project::new(dylan.clone())?;
+        let project = {
+            let update =
+                IndirectDelegation::try_from_iter(vec![Right(cheyenne.current().clone())])?
+                    .with_nested(Some(org.current().root))?;
+            Project::new(cheyenne.clone())?.update(update)
+        }?;
+        let org = {
+            let update = IndirectDelegation::try_from_iter(vec![Right(dylan.current().clone())])?
+                .with_nested(Some(project.current().root))?;
+            org.update(update)
+        }?;
+
+        let mut heads = current_heads_from(vec![&cheyenne, &dylan]);
+        heads.insert(org.current().urn(), *org.current().content_id);
+        heads.insert(project.current().urn(), *project.current().content_id);
+
+        assert_matches!(
+            project.verify(lookup(&heads)),
+            Err(error::VerifyProject::Cycle(root)) if root == project.current().root
+        );
+
+        Ok(())
+    }
+}
+
 fn current_heads_from<'a>(
     devs: impl IntoIterator<Item = &'a Device<'a>>,
 ) -> BTreeMap<Urn, git2::Oid> {
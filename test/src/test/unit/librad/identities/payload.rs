@@ -11,6 +11,7 @@ use proptest::prelude::*;
 use librad::{
     git_ext::Oid,
     identities::payload::{
+        person,
         Person,
         PersonDelegations,
         PersonPayload,
@@ -18,8 +19,10 @@ use librad::{
         ProjectDelegations,
         ProjectPayload,
     },
+    PeerId,
     SecretKey,
 };
+use url::Url;
 
 use crate::{librad::identities::payload::*, roundtrip::*};
 
@@ -75,6 +78,56 @@ fn remove_ext() {
     assert_eq!(payload.get_ext::<UpstreamUser>().unwrap(), None);
 }
 
+#[test]
+fn person_well_known_ext() {
+    let payload = PersonPayload::new(Person {
+        name: "cloudhead".into(),
+    })
+    .with_ext(person::Avatar {
+        hash: "zQmYtUc4iTCbbfVSDNKvtQqrfyezPPnFvE33wFmutw9PBBk".into(),
+    })
+    .unwrap()
+    .with_ext(person::Contacts {
+        endpoints: vec![Url::parse("mailto:cloudhead@radicle.xyz").unwrap()]
+            .into_iter()
+            .collect(),
+    })
+    .unwrap()
+    .with_ext(person::Seeds {
+        seeds: vec![person::Seed {
+            peer: PeerId::from(SecretKey::from_seed([0; 32])),
+            addr: "seed.radicle.xyz:12345".into(),
+        }],
+    })
+    .unwrap();
+
+    assert_eq!(
+        payload.avatar().unwrap(),
+        Some(person::Avatar {
+            hash: "zQmYtUc4iTCbbfVSDNKvtQqrfyezPPnFvE33wFmutw9PBBk".into(),
+        })
+    );
+    assert_eq!(
+        payload.contacts().unwrap().unwrap().endpoints,
+        vec![Url::parse("mailto:cloudhead@radicle.xyz").unwrap()]
+            .into_iter()
+            .collect()
+    );
+    assert_eq!(
+        payload
+            .seeds()
+            .unwrap()
+            .unwrap()
+            .seeds
+            .into_iter()
+            .map(|seed| seed.addr)
+            .collect::<Vec<_>>(),
+        vec!["seed.radicle.xyz:12345".into()]
+    );
+
+    cjson_roundtrip(payload)
+}
+
 #[test]
 fn project_example() {
     let descr = "nom, eating data byte by byte
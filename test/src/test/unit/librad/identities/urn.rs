@@ -5,7 +5,10 @@
 
 use std::convert::TryFrom as _;
 
-use librad::{git_ext as ext, identities::urn::Urn};
+use librad::{
+    git_ext as ext,
+    identities::urn::{self, error, Urn},
+};
 
 #[test]
 fn is_reflike() {
@@ -38,3 +41,48 @@ fn is_reflike_with_qualified_path() {
         .as_str()
     )
 }
+
+const ZERO_V1: &str = "hnrkyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyy";
+const ZERO_V2: &str = "sha1.hyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyy";
+
+#[test]
+fn encode_id_v2() {
+    let urn = Urn::new(ext::Oid::from(git2::Oid::zero()));
+    assert_eq!(ZERO_V1, urn.encode_id());
+    assert_eq!(ZERO_V2, urn.encode_id_v2());
+}
+
+#[test]
+fn try_from_id_v1_and_v2() {
+    let urn = Urn::new(ext::Oid::from(git2::Oid::zero()));
+    assert_eq!(urn, Urn::try_from_id(ZERO_V1).unwrap());
+    assert_eq!(urn, Urn::try_from_id(ZERO_V2).unwrap());
+    assert_eq!(
+        urn,
+        Urn::try_from_id("0x11.hyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyy").unwrap()
+    );
+}
+
+#[test]
+fn try_from_id_any_multibase() {
+    let urn = Urn::new(ext::Oid::from(git2::Oid::zero()));
+    let digest = multibase::encode(multibase::Base::Base58Btc, &[0u8; 20]);
+    assert_eq!(urn, Urn::try_from_id(format!("sha1.{}", digest)).unwrap())
+}
+
+#[test]
+fn try_from_id_unknown_algorithm() {
+    assert!(matches!(
+        Urn::<ext::Oid>::try_from_id("sha4.hyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyyy"),
+        Err(error::DecodeId::UnknownAlgorithm(name)) if name == "sha4"
+    ))
+}
+
+#[test]
+fn canonicalize() {
+    let v2 = format!("rad:git:{}/refs/heads/lolek", ZERO_V2);
+    assert_eq!(
+        format!("rad:git:{}/refs/heads/lolek", ZERO_V1),
+        urn::canonicalize::<ext::Oid, _>(&v2).unwrap()
+    )
+}
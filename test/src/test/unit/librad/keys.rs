@@ -27,6 +27,38 @@ fn test_sign_verify_via_pubkey() {
     assert!(key.public().verify(&sig, DATA_TO_SIGN))
 }
 
+#[test]
+fn test_from_seed_rng_is_deterministic() {
+    assert_eq!(
+        SecretKey::from_seed_rng(42).public().to_string(),
+        "hyn69c4n6b79c8wbigaaf9sftw4yz6nac8xmzmrtwy8db988izarxr"
+    );
+    assert_ne!(
+        SecretKey::from_seed_rng(42).public(),
+        SecretKey::from_seed_rng(43).public()
+    );
+}
+
+#[test]
+fn test_public_key_cbor_golden() {
+    assert_eq!(
+        minicbor::to_vec(SecretKey::from_seed_rng(42).public()).unwrap(),
+        vec![
+            0x82, 0x00, 0x58, 0x20, 0xbd, 0xf6, 0x68, 0x5e, 0x0f, 0x7e, 0xc3, 0xd0, 0x35, 0x36,
+            0x30, 0x5f, 0xd8, 0xb1, 0xa6, 0x81, 0x7f, 0x0b, 0x0c, 0x3b, 0xd7, 0x75, 0x92, 0x34,
+            0x01, 0xc6, 0x1f, 0x9c, 0xf5, 0xbe, 0x08, 0xf2
+        ]
+    )
+}
+
+#[test]
+fn test_signature_json_golden() {
+    assert_eq!(
+        serde_json::to_string(&SecretKey::from_seed_rng(42).sign(DATA_TO_SIGN)).unwrap(),
+        "\"hyb7kxmqddtsasra4xn5utc74daowo3jdfmg1yrj1rf59sizouduyi1gmz73gb9m7rmwxwkj1mrgkm36y5j875b9qg5q5hxhn7efxg7o8\""
+    )
+}
+
 #[test]
 fn test_public_key_json() {
     json_roundtrip(SecretKey::new().public())
@@ -72,3 +104,36 @@ fn test_signature_deserialize_wrong_version() {
     );
     assert!(serde_json::from_str::<Signature>(&ser).is_err())
 }
+
+#[test]
+fn test_secret_key_from_secure() {
+    let key = SecretKey::new();
+    let bytes = SecureBytes::read(key.as_ref()).unwrap();
+    assert_eq!(
+        key.public(),
+        SecretKey::from_secure(&bytes).unwrap().public()
+    )
+}
+
+#[test]
+fn test_secret_key_from_secure_invalid_length() {
+    let bytes = SecureBytes::new(vec![0; 16]);
+    assert!(SecretKey::from_secure(&bytes).is_err())
+}
+
+#[test]
+fn test_secure_bytes_read_too_large() {
+    let input = vec![1; secure::MAX_READ_SIZE];
+    assert!(SecureBytes::read(input.as_slice()).is_err())
+}
+
+#[test]
+fn test_debug_does_not_reveal_secrets() {
+    let key = SecretKey::new();
+    let secret = format!("{:?}", key.as_ref());
+    let bytes = SecureBytes::read(key.as_ref()).unwrap();
+
+    assert!(!format!("{:?}", key).contains(&secret));
+    assert!(!format!("{:?}", bytes).contains(&secret));
+    assert!(format!("{:?}", key).contains(&format!("{:?}", key.public())))
+}
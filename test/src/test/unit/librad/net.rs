@@ -3,9 +3,13 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+mod addrbook;
+mod capability;
 mod codec;
 mod peer;
 mod protocol;
+mod quic;
 mod tls;
+mod tofu;
 mod upgrade;
 mod x509;
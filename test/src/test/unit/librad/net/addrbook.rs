@@ -0,0 +1,112 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{iter, net::SocketAddr};
+
+use librad::{
+    data::BoundedVec,
+    net::{
+        addrbook::AddrBook,
+        protocol::{version::Versions, PartialPeerInfo, PeerAdvertisement},
+        Network,
+    },
+    PeerId,
+    SecretKey,
+};
+
+fn info(peer_id: PeerId, listen: SocketAddr, seen: SocketAddr) -> PartialPeerInfo<SocketAddr> {
+    PartialPeerInfo {
+        peer_id,
+        advertised_info: Some(PeerAdvertisement::new(listen)),
+        seen_addrs: BoundedVec::singleton(seen),
+    }
+}
+
+#[test]
+fn observe_and_reload() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("addrbook.json");
+    let peer = PeerId::from(SecretKey::new());
+    let listen: SocketAddr = "10.0.0.1:8776".parse().unwrap();
+    let seen: SocketAddr = "192.0.2.1:8776".parse().unwrap();
+
+    let mut book = AddrBook::open_at(&path).unwrap();
+    assert!(book.is_empty());
+    book.observe(&info(peer, listen, seen), &Network::Main);
+    book.save().unwrap();
+
+    let book = AddrBook::open_at(&path).unwrap();
+    let entry = book.get(&peer).unwrap();
+    assert_eq!(vec![listen, seen], entry.addrs);
+    assert_eq!(Some(Versions::SUPPORTED), entry.versions);
+    assert_eq!(
+        vec![(peer, vec![listen, seen])],
+        book.known(&Network::Main).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn most_recent_addrs_first() {
+    let tmp = tempfile::tempdir().unwrap();
+    let peer = PeerId::from(SecretKey::new());
+    let old: SocketAddr = "192.0.2.1:8776".parse().unwrap();
+    let new: SocketAddr = "192.0.2.2:8776".parse().unwrap();
+
+    let mut book = AddrBook::open_at(tmp.path().join("addrbook.json")).unwrap();
+    book.observe(
+        &PartialPeerInfo {
+            peer_id: peer,
+            advertised_info: None,
+            seen_addrs: BoundedVec::singleton(old),
+        },
+        &Network::Main,
+    );
+    book.observe(
+        &PartialPeerInfo {
+            peer_id: peer,
+            advertised_info: None,
+            seen_addrs: BoundedVec::singleton(new),
+        },
+        &Network::Main,
+    );
+
+    let entry = book.get(&peer).unwrap();
+    assert_eq!(vec![new, old], entry.addrs);
+    assert_eq!(None, entry.versions);
+}
+
+#[test]
+fn known_by_network() {
+    let tmp = tempfile::tempdir().unwrap();
+    let main = PeerId::from(SecretKey::new());
+    let dev = PeerId::from(SecretKey::new());
+    let addr: SocketAddr = "192.0.2.1:8776".parse().unwrap();
+    let devnet = "devnet".parse::<Network>().unwrap();
+
+    let mut book = AddrBook::open_at(tmp.path().join("addrbook.json")).unwrap();
+    book.observe(&info(main, addr, addr), &Network::Main);
+    book.observe(&info(dev, addr, addr), &devnet);
+    book.observe(
+        &PartialPeerInfo {
+            peer_id: PeerId::from(SecretKey::new()),
+            advertised_info: None,
+            seen_addrs: iter::empty().into(),
+        },
+        &devnet,
+    );
+
+    assert_eq!(
+        vec![main],
+        book.known(&Network::Main)
+            .map(|(peer, _)| peer)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec![dev],
+        book.known(&devnet)
+            .map(|(peer, _)| peer)
+            .collect::<Vec<_>>()
+    );
+}
@@ -0,0 +1,145 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::time::{Duration, SystemTime};
+
+use assert_matches::assert_matches;
+use futures::executor::block_on;
+
+use librad::{
+    git::Urn,
+    git_ext as ext,
+    net::capability::{error, Operation, Token},
+    reflike,
+    PeerId,
+    SecretKey,
+};
+
+use crate::roundtrip::*;
+
+lazy_static! {
+    static ref URN: Urn = Urn::new(ext::Oid::from(git2::Oid::zero()));
+    static ref OTHER_URN: Urn = Urn::new(ext::Oid::from(
+        git2::Oid::hash_object(git2::ObjectType::Blob, b"other").unwrap()
+    ));
+}
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+fn issue(issuer: &SecretKey, holder: PeerId, valid_for: Duration) -> Token {
+    block_on(Token::issue(
+        issuer,
+        holder,
+        Some(URN.clone()),
+        Some(Operation::Replicate),
+        valid_for,
+    ))
+    .unwrap()
+}
+
+#[test]
+fn grants_holder() {
+    let issuer = SecretKey::new();
+    let holder = PeerId::from(SecretKey::new());
+    let token = issue(&issuer, holder, DAY);
+
+    assert_matches!(
+        token.check(
+            &PeerId::from(&issuer),
+            &holder,
+            &URN.clone().with_path(reflike!("refs/heads/main")),
+            Operation::Replicate,
+            SystemTime::now(),
+        ),
+        Ok(())
+    )
+}
+
+#[test]
+fn rejects_other_holder() {
+    let issuer = SecretKey::new();
+    let token = issue(&issuer, PeerId::from(SecretKey::new()), DAY);
+
+    assert_matches!(
+        token.check(
+            &PeerId::from(&issuer),
+            &PeerId::from(SecretKey::new()),
+            &URN,
+            Operation::Replicate,
+            SystemTime::now(),
+        ),
+        Err(error::Check::WrongHolder)
+    )
+}
+
+#[test]
+fn rejects_foreign_issuer() {
+    let holder = PeerId::from(SecretKey::new());
+    let token = issue(&SecretKey::new(), holder, DAY);
+
+    assert_matches!(
+        token.check(
+            &PeerId::from(SecretKey::new()),
+            &holder,
+            &URN,
+            Operation::Replicate,
+            SystemTime::now(),
+        ),
+        Err(error::Check::ForeignIssuer)
+    )
+}
+
+#[test]
+fn rejects_expired() {
+    let issuer = SecretKey::new();
+    let holder = PeerId::from(SecretKey::new());
+    let token = issue(&issuer, holder, DAY);
+
+    assert_matches!(
+        token.check(
+            &PeerId::from(&issuer),
+            &holder,
+            &URN,
+            Operation::Replicate,
+            SystemTime::now() + 2 * DAY,
+        ),
+        Err(error::Check::Expired)
+    )
+}
+
+#[test]
+fn rejects_out_of_scope() {
+    let issuer = SecretKey::new();
+    let holder = PeerId::from(SecretKey::new());
+    let token = issue(&issuer, holder, DAY);
+
+    assert_matches!(
+        token.check(
+            &PeerId::from(&issuer),
+            &holder,
+            &OTHER_URN,
+            Operation::Replicate,
+            SystemTime::now(),
+        ),
+        Err(error::Check::OutOfScope(_))
+    );
+    assert_matches!(
+        token.check(
+            &PeerId::from(&issuer),
+            &holder,
+            &URN,
+            Operation::Unknown(42),
+            SystemTime::now(),
+        ),
+        Err(error::Check::NotGranted(_))
+    )
+}
+
+#[test]
+fn roundtrip_token() {
+    let token = issue(&SecretKey::new(), PeerId::from(SecretKey::new()), DAY);
+    cbor_roundtrip(token.clone());
+    str_roundtrip(token)
+}
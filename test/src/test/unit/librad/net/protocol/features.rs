@@ -0,0 +1,104 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{collections::BTreeSet, net::SocketAddr};
+
+use librad::{
+    data::BoundedVec,
+    net::protocol::{
+        features::Negotiated,
+        interrogation::Request,
+        version::Versions,
+        Capability,
+        Features,
+        PeerAdvertisement,
+    },
+    PeerId,
+    SecretKey,
+};
+
+use crate::roundtrip::*;
+
+#[test]
+fn negotiate() {
+    assert_eq!(
+        Features::SUPPORTED,
+        Features::negotiate(Features::SUPPORTED)
+    );
+    assert_eq!(Features::empty(), Features::negotiate(Features::empty()));
+    assert_eq!(
+        Features::COBS,
+        Features::negotiate(Features::COBS | Features::from_bits(1 << 63))
+    );
+}
+
+#[test]
+fn contains() {
+    assert!(Features::SUPPORTED.contains(Features::INTERROGATION));
+    assert!(Features::SUPPORTED.contains(Features::empty()));
+    assert!(!Features::COBS.contains(Features::INTERROGATION));
+    assert!(!Features::COBS.contains(Features::SUPPORTED));
+}
+
+#[test]
+fn debug() {
+    assert_eq!(
+        "{INTERROGATION, COBS}",
+        format!("{:?}", Features::SUPPORTED)
+    );
+    assert_eq!(
+        "{COBS, 0x100}",
+        format!("{:?}", Features::COBS | Features::from_bits(0x100))
+    )
+}
+
+#[test]
+fn roundtrip_unknown_bits() {
+    cbor_roundtrip(Features::from_bits(u64::MAX))
+}
+
+#[test]
+fn legacy_advertisement() {
+    let addr: SocketAddr = "127.0.0.1:12345".parse().unwrap();
+
+    // The encoding of a `PeerAdvertisement` prior to feature negotiation
+    let mut buf = Vec::new();
+    minicbor::Encoder::new(&mut buf)
+        .array(4)
+        .unwrap()
+        .encode(BoundedVec::<typenum::U16, _>::singleton(addr))
+        .unwrap()
+        .null()
+        .unwrap()
+        .encode(BTreeSet::<Capability>::new())
+        .unwrap()
+        .encode(Versions::SUPPORTED)
+        .unwrap();
+
+    let ad: PeerAdvertisement<SocketAddr> = minicbor::decode(&buf).unwrap();
+    assert_eq!(Features::empty(), ad.features);
+    assert_eq!(Features::SUPPORTED, PeerAdvertisement::new(addr).features);
+}
+
+#[test]
+fn negotiated() {
+    let peer = PeerId::from(SecretKey::new());
+    let negotiated = Negotiated::default();
+
+    assert_eq!(None, negotiated.get(&peer));
+    assert_eq!(
+        Features::COBS,
+        negotiated.record(peer, Features::COBS | Features::from_bits(1 << 42))
+    );
+    assert_eq!(Some(Features::COBS), negotiated.get(&peer));
+    negotiated.forget(&peer);
+    assert_eq!(None, negotiated.get(&peer));
+}
+
+#[test]
+fn required_features() {
+    assert_eq!(Features::empty(), Request::GetUrns.required_features());
+    assert_eq!(Features::INTERROGATION, Request::Ping.required_features());
+}
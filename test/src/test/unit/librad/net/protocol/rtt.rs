@@ -0,0 +1,47 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{collections::HashMap, iter, net::SocketAddr, time::Duration};
+
+use librad::{
+    net::protocol::{rtt, PeerAdvertisement, PeerInfo},
+    PeerId,
+    SecretKey,
+};
+
+fn info(peer_id: PeerId) -> PeerInfo<SocketAddr> {
+    PeerInfo {
+        peer_id,
+        advertised_info: PeerAdvertisement::new(([127, 0, 0, 1], 12345).into()),
+        seen_addrs: iter::empty().into(),
+    }
+}
+
+#[test]
+fn rank_by_rtt() {
+    let (near, far, unknown1, unknown2) = (
+        PeerId::from(SecretKey::new()),
+        PeerId::from(SecretKey::new()),
+        PeerId::from(SecretKey::new()),
+        PeerId::from(SecretKey::new()),
+    );
+    let rtts = vec![
+        (near, Duration::from_millis(12)),
+        (far, Duration::from_millis(230)),
+    ]
+    .into_iter()
+    .collect::<HashMap<_, _>>();
+
+    let mut providers = vec![info(unknown1), info(far), info(unknown2), info(near)];
+    rtt::rank(&mut providers, &rtts);
+
+    assert_eq!(
+        vec![near, far, unknown1, unknown2],
+        providers
+            .into_iter()
+            .map(|info| info.peer_id)
+            .collect::<Vec<_>>()
+    )
+}
@@ -0,0 +1,67 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{thread, time::Duration};
+
+use nonzero_ext::nonzero;
+
+use librad::{
+    net::protocol::throttle::{Penalty, Stats, Throttle, Verdict},
+    rate_limit::Quota,
+    PeerId,
+    SecretKey,
+};
+
+fn throttle(half_life: Duration) -> Throttle {
+    Throttle::new(
+        Quota::per_hour(nonzero!(1u32)).allow_burst(nonzero!(2u32)),
+        Penalty {
+            threshold: 3,
+            half_life,
+        },
+    )
+}
+
+#[test]
+fn penalise_repeat_offenders() {
+    let chatty = PeerId::from(SecretKey::new());
+    let quiet = PeerId::from(SecretKey::new());
+    let throttle = throttle(Duration::from_secs(3600));
+
+    assert_eq!(Verdict::Admit, throttle.check(&chatty));
+    assert_eq!(Verdict::Admit, throttle.check(&chatty));
+    for _ in 0..4 {
+        assert_eq!(Verdict::RateLimited, throttle.check(&chatty));
+    }
+    assert_eq!(Verdict::Penalised, throttle.check(&chatty));
+    assert_eq!(Verdict::Admit, throttle.check(&quiet));
+
+    assert_eq!(
+        Stats {
+            dropped_rate_limited: 4,
+            dropped_penalised: 1,
+            penalised: 1,
+        },
+        throttle.stats()
+    )
+}
+
+#[test]
+fn penalty_decays() {
+    let chatty = PeerId::from(SecretKey::new());
+    let throttle = throttle(Duration::from_millis(10));
+
+    for _ in 0..2 {
+        throttle.check(&chatty);
+    }
+    for _ in 0..10 {
+        throttle.check(&chatty);
+    }
+    thread::sleep(Duration::from_millis(200));
+
+    // The bucket is still empty, but the penalty is gone
+    assert_eq!(Verdict::RateLimited, throttle.check(&chatty));
+    assert_eq!(0, throttle.stats().penalised);
+}
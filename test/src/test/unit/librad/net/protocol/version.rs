@@ -0,0 +1,81 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{collections::BTreeSet, net::SocketAddr};
+
+use librad::{
+    data::BoundedVec,
+    net::protocol::{
+        gossip::Rev,
+        version::{Version, Versioned, Versions, CURRENT, LEGACY},
+        Capability,
+        PeerAdvertisement,
+    },
+};
+
+use crate::roundtrip::*;
+
+#[test]
+fn negotiate() {
+    assert_eq!(
+        Some(CURRENT),
+        Versions::SUPPORTED.negotiate(&Versions::SUPPORTED)
+    );
+    assert_eq!(
+        Some(LEGACY),
+        Versions::SUPPORTED.negotiate(&Versions::LEGACY)
+    );
+    assert_eq!(
+        Some(CURRENT),
+        Versions::SUPPORTED.negotiate(&Versions {
+            min: LEGACY,
+            max: Version::MAX
+        })
+    );
+    assert_eq!(
+        None,
+        Versions::SUPPORTED.negotiate(&Versions {
+            min: CURRENT + 1,
+            max: Version::MAX
+        })
+    )
+}
+
+#[test]
+fn roundtrip_versioned() {
+    let rev = Rev::Git(git2::Oid::zero());
+    cbor_roundtrip(Versioned::new(LEGACY, rev.clone()));
+    cbor_roundtrip(Versioned::new(CURRENT, rev))
+}
+
+#[test]
+fn legacy_is_unwrapped() {
+    let rev = Rev::Git(git2::Oid::zero());
+    assert_eq!(
+        minicbor::to_vec(&rev).unwrap(),
+        minicbor::to_vec(&Versioned::new(LEGACY, rev)).unwrap()
+    )
+}
+
+#[test]
+fn legacy_advertisement() {
+    let addr: SocketAddr = "127.0.0.1:12345".parse().unwrap();
+
+    // The encoding of a `PeerAdvertisement` prior to versioning
+    let mut buf = Vec::new();
+    minicbor::Encoder::new(&mut buf)
+        .array(3)
+        .unwrap()
+        .encode(BoundedVec::<typenum::U16, _>::singleton(addr))
+        .unwrap()
+        .null()
+        .unwrap()
+        .encode(BTreeSet::<Capability>::new())
+        .unwrap();
+
+    let ad: PeerAdvertisement<SocketAddr> = minicbor::decode(&buf).unwrap();
+    assert_eq!(Versions::LEGACY, ad.versions);
+    cbor_roundtrip(PeerAdvertisement::new(addr))
+}
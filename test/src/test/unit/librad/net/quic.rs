@@ -0,0 +1,24 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+mod dialer;
+
+use std::io;
+
+use librad::net::quic;
+use quinn::{ConnectionError, ReadError, VarInt, WriteError};
+
+#[test]
+fn stream_reset_is_not_connection_loss() {
+    let reset = io::Error::from(ReadError::Reset(VarInt::from_u32(0)));
+    let stopped = io::Error::from(WriteError::Stopped(VarInt::from_u32(0)));
+    let lost = io::Error::from(ReadError::ConnectionClosed(ConnectionError::TimedOut));
+    let other = io::Error::new(io::ErrorKind::ConnectionReset, "reset by peer");
+
+    assert!(quic::is_stream_reset(&reset));
+    assert!(quic::is_stream_reset(&stopped));
+    assert!(!quic::is_stream_reset(&lost));
+    assert!(!quic::is_stream_reset(&other));
+}
@@ -0,0 +1,205 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{io, net::SocketAddr, time::Duration};
+
+use librad::net::quic::dialer::{decode_datagram, encode_addr, Relay};
+use tokio::{
+    io::{AsyncReadExt as _, AsyncWriteExt as _},
+    net::{TcpListener, TcpStream, UdpSocket},
+    time::timeout,
+};
+
+const METHOD_NO_AUTH: u8 = 0x00;
+const METHOD_UNACCEPTABLE: u8 = 0xff;
+const REPLY_SUCCEEDED: u8 = 0x00;
+const REPLY_NOT_ALLOWED: u8 = 0x02;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[test]
+fn encode_ipv4() {
+    let mut buf = vec![];
+    encode_addr(&mut buf, &"192.0.2.1:1080".parse().unwrap());
+    assert_eq!(buf, [0x01, 192, 0, 2, 1, 0x04, 0x38])
+}
+
+#[test]
+fn encode_ipv6() {
+    let mut buf = vec![];
+    encode_addr(&mut buf, &"[2001:db8::1]:443".parse().unwrap());
+    assert_eq!(
+        buf,
+        [0x04, 0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01, 0x01, 0xbb]
+    )
+}
+
+#[test]
+fn decode_roundtrip() {
+    for addr in &["192.0.2.1:1080", "[2001:db8::1]:443"] {
+        let mut datagram = vec![0, 0, 0];
+        encode_addr(&mut datagram, &addr.parse().unwrap());
+        datagram.extend_from_slice(b"payload");
+        assert_eq!(decode_datagram(&datagram).unwrap(), b"payload")
+    }
+}
+
+#[test]
+fn decode_domain() {
+    let datagram = [0, 0, 0, 0x03, 4, b'h', b'o', b's', b't', 0, 80, b'h', b'i'];
+    assert_eq!(decode_datagram(&datagram).unwrap(), b"hi")
+}
+
+#[test]
+fn decode_rejects_fragments() {
+    let datagram = [0, 0, 1, 0x01, 192, 0, 2, 1, 0, 80, b'h', b'i'];
+    assert_eq!(
+        decode_datagram(&datagram).unwrap_err().kind(),
+        io::ErrorKind::InvalidData
+    )
+}
+
+#[test]
+fn decode_rejects_truncated() {
+    let datagram = [0, 0, 0, 0x04, 0x20, 0x01];
+    assert_eq!(
+        decode_datagram(&datagram).unwrap_err().kind(),
+        io::ErrorKind::InvalidData
+    )
+}
+
+#[test]
+fn decode_rejects_malformed() {
+    let malformed: &[&[u8]] = &[&[], &[0, 0], &[1, 0, 0, 0x01], &[0, 0, 0, 0x02, 0, 0]];
+    for datagram in malformed {
+        assert_eq!(
+            decode_datagram(datagram).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        )
+    }
+}
+
+#[tokio::test]
+async fn relays_datagrams_of_the_endpoint() -> anyhow::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let proxy_addr = listener.local_addr()?;
+    let udp_relay = UdpSocket::bind("127.0.0.1:0").await?;
+    let proxy = tokio::spawn(mock_proxy(
+        listener,
+        METHOD_NO_AUTH,
+        REPLY_SUCCEEDED,
+        udp_relay.local_addr()?,
+    ));
+
+    let endpoint = UdpSocket::bind("0.0.0.0:0").await?;
+    let remote_addr: SocketAddr = "192.0.2.1:12345".parse()?;
+    let relay = Relay::associate(proxy_addr, remote_addr, endpoint.local_addr()?).await?;
+    // Keep the association alive
+    let _control = proxy.await??;
+    let relay_addr = relay.local_addr;
+    tokio::spawn(relay.run(TIMEOUT));
+
+    // Only the endpoint can use the relay
+    let stranger = UdpSocket::bind("127.0.0.1:0").await?;
+    stranger.send_to(b"intruder", relay_addr).await?;
+    endpoint.send_to(b"hello", relay_addr).await?;
+
+    let mut buf = [0; 64];
+    let (n, client) = timeout(TIMEOUT, udp_relay.recv_from(&mut buf)).await??;
+    assert_eq!(&buf[..n], datagram(&remote_addr, b"hello").as_slice());
+
+    udp_relay
+        .send_to(&datagram(&remote_addr, b"world"), client)
+        .await?;
+    let (n, from) = timeout(TIMEOUT, endpoint.recv_from(&mut buf)).await??;
+    assert_eq!(&buf[..n], b"world");
+    assert_eq!(from, relay_addr);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn requires_no_auth() -> anyhow::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let proxy_addr = listener.local_addr()?;
+    let proxy = tokio::spawn(mock_proxy(
+        listener,
+        METHOD_UNACCEPTABLE,
+        REPLY_SUCCEEDED,
+        "127.0.0.1:0".parse()?,
+    ));
+
+    let err = Relay::associate(proxy_addr, "192.0.2.1:12345".parse()?, "0.0.0.0:0".parse()?)
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    proxy.await??;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn association_refused() -> anyhow::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let proxy_addr = listener.local_addr()?;
+    let proxy = tokio::spawn(mock_proxy(
+        listener,
+        METHOD_NO_AUTH,
+        REPLY_NOT_ALLOWED,
+        "127.0.0.1:0".parse()?,
+    ));
+
+    let err = Relay::associate(proxy_addr, "192.0.2.1:12345".parse()?, "0.0.0.0:0".parse()?)
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+    proxy.await??;
+
+    Ok(())
+}
+
+/// Prefix `payload` with the SOCKS5 UDP request header for `addr`.
+fn datagram(addr: &SocketAddr, payload: &[u8]) -> Vec<u8> {
+    let mut buf = vec![0, 0, 0];
+    encode_addr(&mut buf, addr);
+    buf.extend_from_slice(payload);
+    buf
+}
+
+/// Accept a single association on `listener`, selecting `method`, and
+/// answering the `UDP ASSOCIATE` request with `reply` and the relay address
+/// `relay`.
+///
+/// Returns the control connection, which terminates the association when
+/// dropped.
+async fn mock_proxy(
+    listener: TcpListener,
+    method: u8,
+    reply: u8,
+    relay: SocketAddr,
+) -> io::Result<TcpStream> {
+    let (mut control, _) = listener.accept().await?;
+
+    let mut greeting = [0; 3];
+    control.read_exact(&mut greeting).await?;
+    assert_eq!(greeting, [0x05, 1, METHOD_NO_AUTH]);
+    control.write_all(&[0x05, method]).await?;
+    if method != METHOD_NO_AUTH {
+        return Ok(control);
+    }
+
+    // UDP ASSOCIATE, with the unspecified IPv4 address and port
+    let mut request = [0; 10];
+    control.read_exact(&mut request).await?;
+    assert_eq!(request, [0x05, 0x03, 0, 0x01, 0, 0, 0, 0, 0, 0]);
+
+    let mut response = vec![0x05, reply, 0];
+    encode_addr(&mut response, &relay);
+    control.write_all(&response).await?;
+
+    Ok(control)
+}
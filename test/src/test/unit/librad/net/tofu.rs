@@ -0,0 +1,105 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::net::SocketAddr;
+
+use librad::{
+    net::tofu::{self, Policy, Tofu, Verdict},
+    PeerId,
+    SecretKey,
+};
+
+#[test]
+fn pin_on_first_use() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("tofu.json");
+    let peer = PeerId::from(SecretKey::new());
+    let imposter = PeerId::from(SecretKey::new());
+    let addr: SocketAddr = "192.0.2.1:8776".parse().unwrap();
+    let other: SocketAddr = "192.0.2.2:8776".parse().unwrap();
+
+    let tofu = Tofu::open_at(&path, Policy::Warn).unwrap();
+    assert_eq!(Verdict::Pinned, tofu.check(addr, peer));
+    assert_eq!(Verdict::Trusted, tofu.check(addr, peer));
+    // The same peer may be reachable at several addresses
+    assert_eq!(Verdict::Pinned, tofu.check(other, peer));
+
+    // Pins are only written when flushed
+    assert!(!path.exists());
+    assert!(tofu.is_dirty());
+    tofu.flush().unwrap();
+    assert!(!tofu.is_dirty());
+
+    // Pins survive a restart
+    let tofu = Tofu::open_at(&path, Policy::Refuse).unwrap();
+    let mismatches = tofu::mismatches();
+    assert_eq!(
+        Verdict::Mismatch { pinned: peer },
+        tofu.check(addr, imposter)
+    );
+    assert!(tofu::mismatches() > mismatches);
+    // A mismatch doesn't replace the pin
+    assert_eq!(Some(peer), tofu.get(&addr).map(|pin| pin.peer_id));
+    assert!(!tofu.is_dirty());
+}
+
+#[test]
+fn forget_repins() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("tofu.json");
+    let old = PeerId::from(SecretKey::new());
+    let new = PeerId::from(SecretKey::new());
+    let addr: SocketAddr = "192.0.2.1:8776".parse().unwrap();
+
+    let tofu = Tofu::open_at(&path, Policy::Warn).unwrap();
+    tofu.check(addr, old);
+    assert_eq!(
+        Some(old),
+        tofu.forget(&addr).unwrap().map(|pin| pin.peer_id)
+    );
+    assert_eq!(Verdict::Pinned, tofu.check(addr, new));
+    tofu.flush().unwrap();
+
+    let tofu = Tofu::open_at(&path, Policy::Warn).unwrap();
+    assert_eq!(
+        vec![new],
+        tofu.pins()
+            .into_iter()
+            .map(|pin| pin.peer_id)
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn drops_least_recently_seen() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("tofu.json");
+    let peer = PeerId::from(SecretKey::new());
+    let addrs = (1..=3)
+        .map(|port| SocketAddr::from(([192, 0, 2, 1], port)))
+        .collect::<Vec<_>>();
+
+    // A pin recorded long ago, before `last_seen` was
+    std::fs::write(
+        &path,
+        format!(
+            r#"[{{"addr":"{}","peerId":"{}","firstSeen":1}}]"#,
+            addrs[0], peer
+        ),
+    )
+    .unwrap();
+    let tofu = Tofu::open_at(&path, Policy::Warn).unwrap().with_max_pins(2);
+    assert_eq!(Verdict::Pinned, tofu.check(addrs[1], peer));
+    assert_eq!(Verdict::Pinned, tofu.check(addrs[2], peer));
+
+    assert_eq!(None, tofu.get(&addrs[0]));
+    assert_eq!(
+        addrs[1..].to_vec(),
+        tofu.pins()
+            .into_iter()
+            .map(|pin| pin.addr)
+            .collect::<Vec<_>>()
+    );
+}
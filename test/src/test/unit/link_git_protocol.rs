@@ -3,5 +3,6 @@
 // This file is part of radicle-link, distributed under the GPLv3 with Radicle
 // Linking Exception. For full terms see the included LICENSE file.
 
+mod ref_prefix;
 mod take;
 mod upload_pack;
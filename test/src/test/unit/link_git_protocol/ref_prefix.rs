@@ -0,0 +1,99 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use bstr::BString;
+use link_git_protocol::RefPrefixes;
+
+const CATEGORIES: [&str; 5] = ["cobs", "heads", "notes", "rad", "tags"];
+
+fn bstrings(xs: &[&str]) -> Vec<BString> {
+    xs.iter().copied().map(BString::from).collect()
+}
+
+#[test]
+fn no_exclusions() {
+    let prefixes = RefPrefixes::new()
+        .include("refs/rad/")
+        .include("refs/remotes/")
+        .include("refs/remotes/alice/")
+        .to_ref_prefixes(CATEGORIES.iter().copied());
+
+    assert_eq!(prefixes, Some(bstrings(&["refs/rad/", "refs/remotes/"])))
+}
+
+#[test]
+fn exclude_category() {
+    let prefixes = RefPrefixes::new()
+        .include("refs/remotes/alice/")
+        .exclude("refs/remotes/alice/cobs")
+        .to_ref_prefixes(CATEGORIES.iter().copied());
+
+    assert_eq!(
+        prefixes,
+        Some(bstrings(&[
+            "refs/remotes/alice/heads/",
+            "refs/remotes/alice/notes/",
+            "refs/remotes/alice/rad/",
+            "refs/remotes/alice/tags/",
+        ]))
+    )
+}
+
+#[test]
+fn exclude_multiple() {
+    let prefixes = RefPrefixes::new()
+        .include("refs/")
+        .exclude("refs/cobs/")
+        .exclude("refs/notes")
+        .to_ref_prefixes(CATEGORIES.iter().copied());
+
+    assert_eq!(
+        prefixes,
+        Some(bstrings(&["refs/heads/", "refs/rad/", "refs/tags/"]))
+    )
+}
+
+#[test]
+fn exclude_inexpressible() {
+    let refs = RefPrefixes::new()
+        .include("refs/remotes/")
+        .exclude("refs/remotes/alice/cobs");
+    let prefixes = refs.to_ref_prefixes(CATEGORIES.iter().copied());
+
+    assert_eq!(prefixes, Some(bstrings(&["refs/remotes/"])));
+    assert!(refs.is_excluded("refs/remotes/alice/cobs/xyz.radicle.issue/1"));
+    assert!(!refs.is_excluded("refs/remotes/alice/heads/main"));
+}
+
+#[test]
+fn exclude_everything() {
+    let prefixes = RefPrefixes::new()
+        .include("refs/remotes/alice/heads/")
+        .exclude("refs/remotes/alice/")
+        .to_ref_prefixes(CATEGORIES.iter().copied());
+
+    assert_eq!(prefixes, None)
+}
+
+#[test]
+fn include_nothing() {
+    let prefixes = RefPrefixes::new().to_ref_prefixes(CATEGORIES.iter().copied());
+
+    assert_eq!(prefixes, None)
+}
+
+#[test]
+fn exclude_on_component_boundaries() {
+    let refs = RefPrefixes::new()
+        .include("refs/remotes/")
+        .exclude("refs/remotes/alice")
+        .exclude("refs/remotes/bob/cobs/");
+
+    assert!(refs.is_excluded("refs/remotes/alice"));
+    assert!(refs.is_excluded("refs/remotes/alice/heads/main"));
+    assert!(!refs.is_excluded("refs/remotes/alicia/heads/main"));
+    assert!(refs.is_excluded("refs/remotes/bob/cobs/xyz.radicle.issue/1"));
+    assert!(!refs.is_excluded("refs/remotes/bob/cobsolete"));
+}
@@ -5,3 +5,11 @@
 
 mod args;
 mod cfg;
+mod ci;
+mod gateway;
+mod git_http;
+mod http;
+mod mirror;
+mod notify;
+mod supervisor;
+mod webhooks;
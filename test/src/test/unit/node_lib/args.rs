@@ -4,12 +4,16 @@
 // Linking Exception. For full terms see the included LICENSE file.
 
 use std::{
+    env,
+    io::Write as _,
     net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    num::NonZeroU32,
     path::PathBuf,
     str::FromStr,
 };
 
 use anyhow::Result;
+use rusty_fork::rusty_fork_test;
 use structopt::StructOpt as _;
 
 use librad::{
@@ -21,13 +25,20 @@ use node_lib::args::{
     self,
     Args,
     Bootstrap,
+    Command,
+    ConfigCommand,
+    GraftPolicy,
     KeyArgs,
     MetricsArgs,
     MetricsProvider,
     ProtocolArgs,
     ProtocolListen,
+    ReplicateUnknown,
+    SampleRatio,
     Signer,
+    TracingArgs,
 };
+use rad_clib::keys::SshKey;
 
 #[test]
 fn defaults() -> Result<()> {
@@ -88,6 +99,246 @@ fn bootstraps() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn graft() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--graft-policy", "tracked",
+            "--graft-rate-limit", "12",
+            "--graft-rate-burst", "3",
+    ];
+    let parsed = Args::from_iter_safe(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            protocol: ProtocolArgs {
+                graft_policy: GraftPolicy::Tracked,
+                graft_rate_limit: NonZeroU32::new(12),
+                graft_rate_burst: NonZeroU32::new(3),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn gossip_rate_limit() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--gossip-rate-limit", "5",
+    ];
+    let parsed = Args::from_iter_safe(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            protocol: ProtocolArgs {
+                gossip_rate_limit: NonZeroU32::new(5),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn transport_timeouts() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--keep-alive-interval", "10",
+            "--idle-timeout", "300",
+    ];
+    let parsed = Args::from_iter_safe(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            protocol: ProtocolArgs {
+                keep_alive_interval: Some(10),
+                idle_timeout: Some(300),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn apply_hooks() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--pre-apply-hook", "/etc/linkd/protect-branches",
+            "--post-apply-hook", "/etc/linkd/log-updates",
+    ];
+    let parsed = Args::from_iter_safe(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            protocol: ProtocolArgs {
+                pre_apply_hook: Some(PathBuf::from("/etc/linkd/protect-branches")),
+                post_apply_hook: Some(PathBuf::from("/etc/linkd/log-updates")),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn pinned() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--pin-peer", "hynkyndc6w3p8urucakobzna7sxwgcqny7xxtw88dtx3pkf7m3nrzc@sprout.radicle.xyz:12345",
+    ];
+    let parsed = Args::from_iter_safe(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            protocol: ProtocolArgs {
+                pinned: vec![Bootstrap {
+                    addr: "sprout.radicle.xyz:12345".to_string(),
+                    peer_id: "hynkyndc6w3p8urucakobzna7sxwgcqny7xxtw88dtx3pkf7m3nrzc".parse()?,
+                }],
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn replicate_unknown() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--replicate-unknown", "always",
+    ];
+    let parsed = Args::from_iter_safe(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            protocol: ProtocolArgs {
+                replicate_unknown: ReplicateUnknown::Always,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn pack_refs_threshold() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--pack-refs-threshold", "0",
+    ];
+    let parsed = Args::from_iter_safe(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            protocol: ProtocolArgs {
+                pack_refs_threshold: Some(0),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn health_listen() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--health-listen", "127.0.0.1:8080",
+    ];
+    let parsed = Args::from_iter_safe(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            health_listen: Some(SocketAddr::V4(SocketAddrV4::new(
+                Ipv4Addr::new(127, 0, 0, 1),
+                8080
+            ))),
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn tracing_otlp() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--tracing-otlp-endpoint", "http://localhost:4317",
+            "--tracing-sample-ratio", "0.25",
+    ];
+    let parsed = Args::from_iter_safe(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            tracing: TracingArgs {
+                otlp_endpoint: Some("http://localhost:4317".to_string()),
+                sample_ratio: SampleRatio::from_str("0.25").unwrap(),
+            },
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn tracing_sample_ratio_out_of_range() {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--tracing-sample-ratio", "1.5",
+    ];
+    assert!(Args::from_iter_safe(iter).is_err());
+    assert!(SampleRatio::from_str("NaN").is_err());
+}
+
 #[test]
 fn metrics_graphite() -> Result<()> {
     #[rustfmt::skip]
@@ -149,9 +400,101 @@ fn protocol_listen() -> Result<()> {
         parsed,
         Args {
             protocol: ProtocolArgs {
-                listen: ProtocolListen::Provided {
+                listen: vec![ProtocolListen::Provided {
                     addr: SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 12345))
-                },
+                }],
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn protocol_listen_multiple() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "127.0.0.1:12345",
+            "--protocol-listen", "[::1]:12345",
+            "--protocol-listen", "any",
+    ];
+    let parsed = Args::from_iter_safe(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            protocol: ProtocolArgs {
+                listen: vec![
+                    ProtocolListen::Provided {
+                        addr: "127.0.0.1:12345".parse()?
+                    },
+                    ProtocolListen::Provided {
+                        addr: "[::1]:12345".parse()?
+                    },
+                    ProtocolListen::Any,
+                ],
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn protocol_listen_required() {
+    let parsed = Args::from_iter_safe(vec!["linkd"]);
+    assert!(parsed.is_err());
+}
+
+#[test]
+fn advertise_addr() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "any",
+            "--advertise-addr", "198.51.100.7:12345",
+            "--advertise-addr", "[2001:db8::7]:12345",
+    ];
+    let parsed = Args::from_iter_safe(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            protocol: ProtocolArgs {
+                listen: vec![ProtocolListen::Any],
+                advertise_addrs: vec![
+                    "198.51.100.7:12345".parse()?,
+                    "[2001:db8::7]:12345".parse()?
+                ],
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn socks5_proxy() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--socks5-proxy", "127.0.0.1:1080",
+    ];
+    let parsed = Args::from_iter_safe(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            protocol: ProtocolArgs {
+                socks5_proxy: Some("127.0.0.1:1080".parse()?),
                 ..Default::default()
             },
             ..Default::default()
@@ -351,6 +694,29 @@ fn signer_ssh_agent() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn ssh_key_fingerprint() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "--ssh-key-fingerprint", "SHA256:ODVKsbsE/xy0l9E8QQaQKrBoS8NjSqMMwufvMfG7/Zw",
+    ];
+    let parsed = Args::from_iter_safe(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            ssh_key_fingerprint: Some(SshKey::Fingerprint(
+                "SHA256:ODVKsbsE/xy0l9E8QQaQKrBoS8NjSqMMwufvMfG7/Zw".to_string()
+            )),
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
 #[test]
 fn tmp_root() -> Result<()> {
     #[rustfmt::skip]
@@ -371,3 +737,290 @@ fn tmp_root() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn config_file() -> Result<()> {
+    let mut file = tempfile::NamedTempFile::new()?;
+    writeln!(
+        file,
+        r#"
+protocol-listen = ["127.0.0.1:12345", "[::1]:12345"]
+graft-policy = "tracked"
+pack-refs-threshold = 0
+tmp-root = true
+"#
+    )?;
+    let config = file.path().to_str().unwrap();
+
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--config", config,
+            "--graft-policy", "delegates",
+    ];
+    let parsed = Args::from_iter_safe(args::file::merge(iter)?)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            config: Some(PathBuf::from(config)),
+            protocol: ProtocolArgs {
+                listen: vec![
+                    ProtocolListen::Provided {
+                        addr: SocketAddr::from_str("127.0.0.1:12345")?,
+                    },
+                    ProtocolListen::Provided {
+                        addr: SocketAddr::from_str("[::1]:12345")?,
+                    },
+                ],
+                graft_policy: GraftPolicy::Delegates,
+                pack_refs_threshold: Some(0),
+                ..Default::default()
+            },
+            tmp_root: true,
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn config_file_unsupported_value() -> Result<()> {
+    let mut file = tempfile::NamedTempFile::new()?;
+    writeln!(file, "[protocol]\nlisten = \"localhost\"")?;
+
+    let iter = vec!["linkd", "--config", file.path().to_str().unwrap()];
+    assert_matches!(
+        args::file::merge(iter),
+        Err(args::file::Error::Value(key)) if key == "protocol"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn config_check() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--protocol-listen", "localhost",
+            "config", "check",
+    ];
+    let parsed = Args::from_iter_safe(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            command: Some(Command::Config(ConfigCommand::Check)),
+            ..Default::default()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn config_check_roundtrip() -> Result<()> {
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--bootstrap", "hynkyndc6w3p8urucakobzna7sxwgcqny7xxtw88dtx3pkf7m3nrzc@sprout.radicle.xyz:12345",
+            "--rad-home", "/tmp/linkd",
+            "--protocol-listen", "127.0.0.1:12345",
+            "--protocol-listen", "any",
+            "--protocol-network", "testnet",
+            "--graft-policy", "delegates",
+            "--graft-rate-limit", "10",
+            "--pack-refs-threshold", "0",
+            "--tracing-sample-ratio", "0.5",
+            "--trace-replication",
+            "config", "check",
+    ];
+    let parsed = Args::from_iter_safe(iter)?;
+
+    let table = args::file::to_table(&parsed);
+    assert_eq!(
+        table.get("graft-policy"),
+        Some(&toml::Value::String("delegates".to_owned()))
+    );
+    assert_eq!(
+        table.get("pack-refs-threshold"),
+        Some(&toml::Value::Integer(0))
+    );
+    assert_eq!(table.get("tmp-root"), Some(&toml::Value::Boolean(false)));
+    assert_eq!(table.get("socks5-proxy"), None);
+
+    let mut file = tempfile::NamedTempFile::new()?;
+    write!(file, "{}", toml::to_string(&table)?)?;
+    let config = file.path().to_str().unwrap();
+    let reparsed = Args::from_iter_safe(args::file::merge(vec![
+        "linkd", "--config", config, "config", "check",
+    ])?)?;
+
+    assert_eq!(
+        reparsed,
+        Args {
+            config: Some(PathBuf::from(config)),
+            ..parsed
+        }
+    );
+
+    Ok(())
+}
+
+// N.B. we fork these tests into subprocesses since they modify environment
+// variables, which would affect the other tests running.
+rusty_fork_test! {
+#[test]
+fn env_bootstraps() {
+    env::set_var(
+        "LINKD_BOOTSTRAP",
+        "hynkyndc6w3p8urucakobzna7sxwgcqny7xxtw88dtx3pkf7m3nrzc@sprout.radicle.xyz:12345,\
+         hybz9gfgtd9d4pd14a6r66j5hz6f77fed4jdu7pana4fxaxbt369kg@setzling.radicle.xyz:12345",
+    );
+    env::set_var("LINKD_PROTOCOL_LISTEN", "localhost");
+    let parsed = Args::from_iter_safe(vec!["linkd"]).unwrap();
+
+    assert_eq!(
+        parsed,
+        Args {
+            bootstraps: vec![
+                Bootstrap {
+                    addr: "sprout.radicle.xyz:12345".to_string(),
+                    peer_id: "hynkyndc6w3p8urucakobzna7sxwgcqny7xxtw88dtx3pkf7m3nrzc"
+                        .parse()
+                        .unwrap(),
+                },
+                Bootstrap {
+                    addr: "setzling.radicle.xyz:12345".to_string(),
+                    peer_id: "hybz9gfgtd9d4pd14a6r66j5hz6f77fed4jdu7pana4fxaxbt369kg"
+                        .parse()
+                        .unwrap(),
+                },
+            ],
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn env_protocol_listen() {
+    env::set_var("LINKD_PROTOCOL_LISTEN", "127.0.0.1:12345,[::1]:12345");
+    let parsed = Args::from_iter_safe(vec!["linkd"]).unwrap();
+
+    assert_eq!(
+        parsed,
+        Args {
+            protocol: ProtocolArgs {
+                listen: vec![
+                    ProtocolListen::Provided {
+                        addr: SocketAddr::from_str("127.0.0.1:12345").unwrap(),
+                    },
+                    ProtocolListen::Provided {
+                        addr: SocketAddr::from_str("[::1]:12345").unwrap(),
+                    },
+                ],
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn env_graft() {
+    env::set_var("LINKD_PROTOCOL_LISTEN", "localhost");
+    env::set_var("LINKD_GRAFT_POLICY", "tracked");
+    env::set_var("LINKD_GRAFT_RATE_LIMIT", "12");
+    env::set_var("LINKD_GRAFT_RATE_BURST", "3");
+    let parsed = Args::from_iter_safe(vec!["linkd"]).unwrap();
+
+    assert_eq!(
+        parsed,
+        Args {
+            protocol: ProtocolArgs {
+                graft_policy: GraftPolicy::Tracked,
+                graft_rate_limit: NonZeroU32::new(12),
+                graft_rate_burst: NonZeroU32::new(3),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn env_signer_key_file() {
+    env::set_var("LINKD_PROTOCOL_LISTEN", "localhost");
+    env::set_var("LINKD_SIGNER", "key");
+    env::set_var("LINKD_KEY_SOURCE", "file");
+    env::set_var("LINKD_KEY_FORMAT", "base64");
+    env::set_var("LINKD_KEY_FILE_PATH", "~/.config/radicle/secret.key");
+    let parsed = Args::from_iter_safe(vec!["linkd"]).unwrap();
+
+    assert_eq!(
+        parsed,
+        Args {
+            signer: Signer::Key,
+            key: KeyArgs {
+                file_path: Some(PathBuf::from("~/.config/radicle/secret.key")),
+                format: args::KeyFormat::Base64,
+                source: args::KeySource::File,
+            },
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn env_tmp_root() {
+    env::set_var("LINKD_TMP_ROOT", "true");
+    env::set_var("LINKD_PROTOCOL_LISTEN", "localhost");
+    let parsed = Args::from_iter_safe(args::file::merge(vec!["linkd"]).unwrap()).unwrap();
+
+    assert_eq!(
+        parsed,
+        Args {
+            tmp_root: true,
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn env_precedence() {
+    env::set_var("LINKD_GRAFT_POLICY", "tracked");
+    env::set_var("LINKD_REPLICATE_UNKNOWN", "never");
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    writeln!(
+        file,
+        "protocol-listen = \"localhost\"\n\
+         graft-policy = \"delegates\"\n\
+         replicate-unknown = \"always\""
+    )
+    .unwrap();
+    env::set_var("LINKD_CONFIG", file.path());
+
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--replicate-unknown", "from-tracked",
+    ];
+    let parsed = Args::from_iter_safe(args::file::merge(iter).unwrap()).unwrap();
+
+    assert_eq!(
+        parsed,
+        Args {
+            config: Some(file.path().to_path_buf()),
+            protocol: ProtocolArgs {
+                graft_policy: GraftPolicy::Tracked,
+                replicate_unknown: ReplicateUnknown::FromTracked,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    );
+}
+/* end rusty_fork! */
+}
@@ -0,0 +1,48 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use structopt::StructOpt as _;
+
+use librad::git::Urn;
+use node_lib::{
+    args::Args,
+    ci::{digest, Check},
+};
+
+#[test]
+fn parse_checks() -> anyhow::Result<()> {
+    let urn = Urn::new(git2::Oid::zero().into());
+    let check = format!("{}=cargo test --workspace", urn);
+
+    #[rustfmt::skip]
+    let iter = vec![
+        "linkd",
+            "--ci-check", check.as_str(),
+    ];
+    let parsed = Args::from_iter_safe(iter)?;
+
+    assert_eq!(
+        parsed,
+        Args {
+            ci_checks: vec![Check {
+                urn: urn.clone(),
+                command: "cargo test --workspace".to_owned(),
+            }],
+            ..Default::default()
+        }
+    );
+    assert!(format!("{}", urn).parse::<Check>().is_err());
+    assert!(format!("{}= ", urn).parse::<Check>().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn log_digest() {
+    assert_eq!(
+        digest(b""),
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+    );
+}
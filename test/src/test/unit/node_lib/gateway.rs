@@ -0,0 +1,98 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::{
+    git::{storage::Storage, util::quick_commit},
+    git_ext::tree,
+    paths::Paths,
+    reflike,
+    SecretKey,
+};
+use node_lib::gateway::{handle, Status};
+use serde_json::json;
+
+use crate::rad::identities::TestProject;
+
+#[test]
+fn serves_project() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        let TestProject { project, .. } = TestProject::create(&storage).unwrap();
+        let urn = project.urn();
+        let commit = quick_commit(
+            &storage,
+            &urn.clone().with_path(reflike!("refs/heads/next")),
+            vec![("README", tree::blob(b"Hello, World"))]
+                .into_iter()
+                .collect(),
+            "initial commit",
+        )
+        .unwrap();
+
+        let resp = handle(&storage, &format!("/v1/projects/{}", urn));
+        assert_eq!(Status::Ok, resp.status);
+        assert_eq!(json!("radicle-link"), resp.body["name"]);
+        assert_eq!(json!("next"), resp.body["defaultBranch"]);
+
+        let resp = handle(
+            &storage,
+            &format!("/v1/projects/{}/heads", urn.to_string().replace(':', "%3A")),
+        );
+        assert_eq!(Status::Ok, resp.status);
+        assert_eq!(
+            json!(commit.to_string()),
+            resp.body[storage.peer_id().to_string()]["next"]
+        );
+
+        let resp = handle(
+            &storage,
+            &format!("/v1/projects/{}/tree/{}/README", urn, commit),
+        );
+        assert_eq!(Status::Ok, resp.status);
+        assert_eq!(json!(false), resp.body["binary"]);
+        assert_eq!(json!("Hello, World"), resp.body["content"]);
+
+        let resp = handle(
+            &storage,
+            &format!("/v1/projects/{}/cobs/xyz.radicle.patch", urn),
+        );
+        assert_eq!(Status::Ok, resp.status);
+        assert_eq!(json!([]), resp.body);
+    }
+}
+
+#[test]
+fn rejects_unpublished_commits() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        let TestProject { project, .. } = TestProject::create(&storage).unwrap();
+
+        // The identity commit exists, but isn't reachable from any branch
+        let resp = handle(
+            &storage,
+            &format!("/v1/projects/{}/tree/{}", project.urn(), project.content_id),
+        );
+        assert_eq!(Status::NotFound, resp.status);
+    }
+}
+
+#[test]
+fn rejects_malformed_paths() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+
+        assert_eq!(
+            Status::BadRequest,
+            handle(&storage, "/v1/projects/not-a-urn").status
+        );
+        assert_eq!(Status::NotFound, handle(&storage, "/healthz").status);
+    }
+}
@@ -0,0 +1,108 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::{
+    git::{storage::Storage, util::quick_commit, Urn},
+    git_ext::tree,
+    paths::Paths,
+    reflike,
+    PeerId,
+    SecretKey,
+};
+use node_lib::git_http::{advertise, parse_target, Endpoint, Repo};
+
+use crate::rad::identities::TestProject;
+
+#[test]
+fn parse_targets() {
+    let urn = Urn::new(git2::Oid::zero().into());
+    let peer = PeerId::from(SecretKey::new());
+
+    assert_eq!(
+        Some((
+            Repo {
+                urn: urn.clone(),
+                peer: None
+            },
+            Endpoint::InfoRefs {
+                service: Some("git-upload-pack".to_owned())
+            }
+        )),
+        parse_target(&format!("/{}.git/info/refs?service=git-upload-pack", urn))
+    );
+    assert_eq!(
+        Some((
+            Repo {
+                urn: urn.clone(),
+                peer: Some(peer)
+            },
+            Endpoint::UploadPack
+        )),
+        parse_target(&format!("/{}/{}/git-upload-pack", urn, peer))
+    );
+    for escaped in &["%3A", "%3a"] {
+        assert_eq!(
+            Some((
+                Repo {
+                    urn: urn.clone(),
+                    peer: None
+                },
+                Endpoint::ReceivePack
+            )),
+            parse_target(&format!(
+                "/{}.git/git-receive-pack",
+                urn.to_string().replace(':', escaped)
+            ))
+        );
+    }
+    assert_eq!(None, parse_target(&format!("/{}.git/HEAD", urn)));
+    assert_eq!(None, parse_target("/not-a-urn.git/info/refs"));
+}
+
+#[test]
+fn advertises_delegate_view() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        let TestProject { project, .. } = TestProject::create(&storage).unwrap();
+        let urn = project.urn();
+        let commit = quick_commit(
+            &storage,
+            &urn.clone().with_path(reflike!("refs/heads/next")),
+            vec![("README", tree::blob(b"Hello, World"))]
+                .into_iter()
+                .collect(),
+            "initial commit",
+        )
+        .unwrap();
+
+        let adv = advertise(&storage, &Repo { urn, peer: None })
+            .unwrap()
+            .expect("project should be found");
+        let adv = String::from_utf8(adv).unwrap();
+        assert!(adv.starts_with("001e# service=git-upload-pack\n0000"));
+        assert!(adv.contains(&format!("{} HEAD\0", commit)));
+        assert!(adv.contains("symref=HEAD:refs/heads/next"));
+        assert!(adv.contains(&format!("{} refs/heads/next\n", commit)));
+        assert!(!adv.contains("refs/rad"));
+        assert!(adv.ends_with("0000"));
+    }
+}
+
+#[test]
+fn unknown_project() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        let repo = Repo {
+            urn: Urn::new(git2::Oid::zero().into()),
+            peer: None,
+        };
+
+        assert!(advertise(&storage, &repo).unwrap().is_none())
+    }
+}
@@ -0,0 +1,175 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    io::{self, Write as _},
+    sync::Arc,
+    time::Duration,
+};
+
+use flate2::{write::GzEncoder, Compression};
+
+use node_lib::http::{self, percent_decode, read_request, Limits, MAX_HEAD};
+use tokio::{
+    io::{AsyncReadExt as _, AsyncWriteExt as _},
+    net::{TcpListener, TcpStream},
+    sync::Notify,
+    time::timeout,
+};
+
+#[tokio::test]
+async fn reads_until_end_of_headers() {
+    let mut stream = (&b"GET /healthz HT"[..]).chain(&b"TP/1.1\r\nHost: localhost\r\n\r\n"[..]);
+    let req = read_request(&mut stream, 0).await.unwrap();
+
+    assert_eq!(req.method, "GET");
+    assert_eq!(req.target, "/healthz");
+    assert_eq!(req.header("host"), Some("localhost"));
+    assert!(req.body.is_empty());
+}
+
+#[tokio::test]
+async fn reads_content_length_body() {
+    let mut stream = (&b"POST /x HTTP/1.1\r\nContent-Length: 11\r\n\r\nhello"[..])
+        .chain(&b" world, and more"[..]);
+    let req = read_request(&mut stream, 1024).await.unwrap();
+
+    assert_eq!(req.body, b"hello world");
+}
+
+#[tokio::test]
+async fn reads_chunked_body() {
+    let mut stream = (&b"POST /x HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhel"[..])
+        .chain(&b"lo\r\n6;ext=1\r\n world\r\n0\r\n\r\n"[..]);
+    let req = read_request(&mut stream, 1024).await.unwrap();
+
+    assert_eq!(req.body, b"hello world");
+}
+
+#[tokio::test]
+async fn rejects_oversized_body() {
+    let mut stream = &b"POST /x HTTP/1.1\r\nContent-Length: 11\r\n\r\nhello world"[..];
+    let err = read_request(&mut stream, 10).await.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    let mut stream =
+        &b"POST /x HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nb\r\nhello world\r\n0\r\n\r\n"[..];
+    let err = read_request(&mut stream, 10).await.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[tokio::test]
+async fn rejects_overflowing_chunk_size() {
+    let mut stream = &b"POST /x HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n1\r\na\r\nffffffffffffffff\r\n\r\n"[..];
+    let err = read_request(&mut stream, 1024).await.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[tokio::test]
+async fn reads_gzip_body() {
+    let gzip = |body: &[u8]| {
+        let mut enc = GzEncoder::new(Vec::new(), Compression::default());
+        enc.write_all(body).unwrap();
+        enc.finish().unwrap()
+    };
+    let request = |body: Vec<u8>| {
+        let mut req = format!(
+            "POST /x HTTP/1.1\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\n\r\n",
+            body.len()
+        )
+        .into_bytes();
+        req.extend_from_slice(&body);
+        req
+    };
+
+    let req = request(gzip(b"hello world"));
+    let req = read_request(&mut req.as_slice(), 64).await.unwrap();
+    assert_eq!(req.body, b"hello world");
+
+    // A decoded body of exactly the maximum size is fine
+    let req = request(gzip(&[0; 64]));
+    let req = read_request(&mut req.as_slice(), 64).await.unwrap();
+    assert_eq!(req.body, [0; 64]);
+
+    // The compressed body fits, but the decoded one doesn't
+    let req = request(gzip(&[0; 1024]));
+    let err = read_request(&mut req.as_slice(), 512).await.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[tokio::test]
+async fn rejects_oversized_head() {
+    let mut head = b"GET / HTTP/1.1\r\n".to_vec();
+    while head.len() <= MAX_HEAD {
+        head.extend_from_slice(b"X-Padding: ...\r\n");
+    }
+    head.extend_from_slice(b"\r\n");
+    let err = read_request(&mut head.as_slice(), 0).await.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[tokio::test]
+async fn rejects_truncated_request() {
+    let mut stream = &b"GET / HTTP/1.1\r\nHost: localhost\r\n"[..];
+    let err = read_request(&mut stream, 0).await.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[tokio::test]
+async fn caps_concurrent_connections() -> anyhow::Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let gate = Arc::new(Notify::new());
+    let limits = Limits {
+        max_connections: 1,
+        ..Limits::default()
+    };
+    tokio::spawn(http::serve(listener, limits, {
+        let gate = Arc::clone(&gate);
+        move |_, mut stream| {
+            let gate = Arc::clone(&gate);
+            async move {
+                gate.notified().await;
+                http::respond_text(&mut stream, "200 OK", "ok\n").await?;
+                Ok::<_, anyhow::Error>(())
+            }
+        }
+    }));
+
+    let mut first = TcpStream::connect(addr).await?;
+    first.write_all(b"GET / HTTP/1.1\r\n\r\n").await?;
+    let mut second = TcpStream::connect(addr).await?;
+    second.write_all(b"GET / HTTP/1.1\r\n\r\n").await?;
+
+    // The second request is not served while the first is in flight
+    let mut buf = [0; 1];
+    assert!(
+        timeout(Duration::from_millis(200), second.read(&mut buf))
+            .await
+            .is_err(),
+        "second connection served concurrently"
+    );
+
+    gate.notify_one();
+    let mut response = String::new();
+    first.read_to_string(&mut response).await?;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+
+    gate.notify_one();
+    let mut response = String::new();
+    timeout(Duration::from_secs(5), second.read_to_string(&mut response)).await??;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+
+    Ok(())
+}
+
+#[test]
+fn decodes_percent_escapes() {
+    assert_eq!(percent_decode("rad%3Agit%3ahnrk"), "rad:git:hnrk");
+    assert_eq!(percent_decode("a%20b%2Fc"), "a b/c");
+    // Invalid or truncated escapes are kept as is
+    assert_eq!(percent_decode("100%"), "100%");
+    assert_eq!(percent_decode("%+1%zz%4"), "%+1%zz%4");
+}
@@ -0,0 +1,117 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use node_lib::supervisor::{Event, Policy, Supervisor};
+
+fn policy() -> Policy {
+    Policy {
+        max_restarts: 2,
+        window: Duration::from_secs(60),
+        backoff_base: Duration::from_millis(1),
+        backoff_max: Duration::from_millis(10),
+    }
+}
+
+/// A routine failing the first `failures` times it is run.
+fn flaky(
+    runs: Arc<AtomicUsize>,
+    failures: usize,
+) -> impl FnMut() -> futures::future::BoxFuture<'static, anyhow::Result<()>> {
+    move || {
+        let run = runs.fetch_add(1, Ordering::SeqCst);
+        Box::pin(async move {
+            if run < failures {
+                anyhow::bail!("failure {}", run)
+            }
+            Ok(())
+        })
+    }
+}
+
+#[tokio::test]
+async fn restarts_until_success() -> anyhow::Result<()> {
+    let supervisor = Supervisor::new(policy());
+    let mut events = supervisor.subscribe();
+    let runs = Arc::new(AtomicUsize::new(0));
+
+    supervisor.spawn("flaky", flaky(runs.clone(), 2)).await??;
+
+    assert_eq!(3, runs.load(Ordering::SeqCst));
+    for failures in 1..=2 {
+        assert_matches!(
+            events.recv().await?,
+            Event::Restarting { task: "flaky", failures: n, .. } if n == failures
+        );
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn gives_up_after_repeated_failures() -> anyhow::Result<()> {
+    let supervisor = Supervisor::new(policy());
+    let mut events = supervisor.subscribe();
+    let runs = Arc::new(AtomicUsize::new(0));
+
+    let res = supervisor
+        .spawn("broken", flaky(runs.clone(), usize::MAX))
+        .await?;
+
+    assert!(res.is_err());
+    assert_eq!(3, runs.load(Ordering::SeqCst));
+    assert_matches!(events.recv().await?, Event::Restarting { .. });
+    assert_matches!(events.recv().await?, Event::Restarting { .. });
+    assert_matches!(
+        events.recv().await?,
+        Event::GaveUp { task: "broken", reason } if reason == "failure 2"
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn restarts_after_panic() -> anyhow::Result<()> {
+    let supervisor = Supervisor::new(policy());
+    let mut events = supervisor.subscribe();
+    let runs = Arc::new(AtomicUsize::new(0));
+
+    let routine = {
+        let runs = runs.clone();
+        move || {
+            let run = runs.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if run == 0 {
+                    panic!("boom")
+                }
+                Ok::<_, anyhow::Error>(())
+            }
+        }
+    };
+    supervisor.spawn("panicky", routine).await??;
+
+    assert_eq!(2, runs.load(Ordering::SeqCst));
+    assert_matches!(
+        events.recv().await?,
+        Event::Restarting { reason, .. } if reason == "panicked: boom"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn backoff_is_bounded() {
+    let policy = Policy::default();
+    assert_eq!(Duration::from_secs(1), policy.backoff(1));
+    assert_eq!(Duration::from_secs(2), policy.backoff(2));
+    assert_eq!(Duration::from_secs(60), policy.backoff(100));
+}
@@ -0,0 +1,125 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::{
+    git::{storage::Storage, Urn},
+    paths::Paths,
+    SecretKey,
+};
+use node_lib::{
+    notify::Kind,
+    webhooks::{self, Webhook},
+};
+
+#[test]
+fn signature() {
+    // RFC 4231, test case 2
+    assert_eq!(
+        webhooks::sign(b"Jefe", b"what do ya want for nothing?"),
+        "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+}
+
+#[test]
+fn registry_roundtrip() {
+    let tmp = tempfile::tempdir().unwrap();
+    let paths = Paths::from_root(&tmp).unwrap();
+    Storage::open(&paths, SecretKey::new()).unwrap();
+    let urn = Urn::new(git2::Oid::zero().into());
+
+    let ci = Webhook {
+        name: "ci".to_owned(),
+        url: "https://ci.example.com/hook".to_owned(),
+        secret: "s3cr3t".to_owned(),
+        kinds: vec![Kind::ReplicationCompleted, Kind::CobChanged]
+            .into_iter()
+            .collect(),
+        urn: Some(urn),
+    };
+    let chat = Webhook {
+        name: "chat".to_owned(),
+        url: "https://chat.example.com/hook".to_owned(),
+        secret: "hunter2".to_owned(),
+        kinds: vec![Kind::IdentityUpdate].into_iter().collect(),
+        urn: None,
+    };
+    webhooks::add(&paths, &ci).unwrap();
+    webhooks::add(&paths, &chat).unwrap();
+    assert_eq!(
+        webhooks::list(&paths).unwrap(),
+        vec![chat.clone(), ci.clone()]
+    );
+
+    // Adding a webhook of the same name replaces it
+    let ci = Webhook {
+        kinds: vec![Kind::ReplicationCompleted].into_iter().collect(),
+        urn: None,
+        ..ci
+    };
+    webhooks::add(&paths, &ci).unwrap();
+    assert_eq!(
+        webhooks::list(&paths).unwrap(),
+        vec![chat.clone(), ci.clone()]
+    );
+    assert!(!format!("{:?}", ci).contains("s3cr3t"));
+
+    assert!(webhooks::remove(&paths, "chat").unwrap());
+    assert!(!webhooks::remove(&paths, "chat").unwrap());
+    assert_eq!(webhooks::list(&paths).unwrap(), vec![ci]);
+    assert!(webhooks::dead_letters(&paths).unwrap().is_empty());
+}
+
+#[test]
+fn invalid_webhooks_are_rejected() {
+    let tmp = tempfile::tempdir().unwrap();
+    let paths = Paths::from_root(&tmp).unwrap();
+    Storage::open(&paths, SecretKey::new()).unwrap();
+
+    let webhook = Webhook {
+        name: "ci.hooks".to_owned(),
+        url: "https://ci.example.com/hook".to_owned(),
+        secret: "s3cr3t".to_owned(),
+        kinds: vec![Kind::ReplicationCompleted].into_iter().collect(),
+        urn: None,
+    };
+    assert!(matches!(
+        webhooks::add(&paths, &webhook),
+        Err(webhooks::Error::InvalidName(_))
+    ));
+    assert!(matches!(
+        webhooks::add(
+            &paths,
+            &Webhook {
+                name: "ci".to_owned(),
+                kinds: vec![Kind::CobChanged].into_iter().collect(),
+                ..webhook
+            }
+        ),
+        Err(webhooks::Error::CobsUnrestricted(_))
+    ));
+}
+
+#[tokio::test]
+async fn redeliver_keeps_undeliverable() {
+    let tmp = tempfile::tempdir().unwrap();
+    let paths = Paths::from_root(&tmp).unwrap();
+    Storage::open(&paths, SecretKey::new()).unwrap();
+
+    let delivery = webhooks::Delivery {
+        id: "1-1".to_owned(),
+        webhook: "gone".to_owned(),
+        event: Kind::ReplicationCompleted.to_string(),
+        body: "{}".to_owned(),
+        attempts: webhooks::MAX_ATTEMPTS,
+        last_error: Some("boom".to_owned()),
+    };
+    let mut line = serde_json::to_string(&delivery).unwrap();
+    line.push('\n');
+    std::fs::write(paths.git_dir().join("webhooks-dead-letters.jsonl"), line).unwrap();
+
+    // The webhook is no longer registered
+    assert_eq!(webhooks::redeliver(&paths).await.unwrap(), (0, 1));
+    assert_eq!(webhooks::dead_letters(&paths).unwrap(), vec![delivery]);
+}
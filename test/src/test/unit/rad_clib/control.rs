@@ -0,0 +1,207 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use tokio::{
+    io::{AsyncBufReadExt as _, BufReader},
+    net::UnixListener,
+};
+
+use librad::{git::Urn, reflike, PeerId, SecretKey};
+use rad_clib::control::{
+    write_line,
+    Client,
+    Event,
+    Filter,
+    Kind,
+    Pushed,
+    Request,
+    Response,
+    Status,
+};
+
+#[test]
+fn request_encoding() {
+    let urn = Urn::new(git2::Oid::zero().into());
+    let peer = PeerId::from(SecretKey::from_seed([1; 32]));
+
+    assert_eq!(
+        serde_json::to_value(&Request::Status).unwrap(),
+        serde_json::json!({ "method": "status" })
+    );
+    // `addrs` may be omitted
+    let replicate = serde_json::json!({
+        "method": "replicate",
+        "urn": urn.to_string(),
+        "from": peer.to_string(),
+    });
+    assert_eq!(
+        serde_json::from_value::<Request>(replicate).unwrap(),
+        Request::Replicate {
+            urn: urn.clone(),
+            from: peer,
+            addrs: vec![]
+        }
+    );
+    let push = serde_json::json!({
+        "method": "push",
+        "urn": urn.to_string(),
+        "seed": peer.to_string(),
+    });
+    assert_eq!(
+        serde_json::from_value::<Request>(push).unwrap(),
+        Request::Push {
+            urn,
+            seed: peer,
+            addrs: vec![]
+        }
+    );
+    // The filter may be omitted
+    assert_eq!(
+        serde_json::from_value::<Request>(serde_json::json!({ "method": "subscribe" })).unwrap(),
+        Request::Subscribe {
+            filter: Filter::default()
+        }
+    );
+}
+
+#[test]
+fn pushed_synced() {
+    let tip = git2::Oid::hash_object(git2::ObjectType::Blob, b"tip")
+        .unwrap()
+        .into();
+    let pushed = |local, seed| Pushed {
+        updated: 0,
+        local,
+        seed,
+    };
+
+    assert!(pushed(Some(tip), Some(tip)).synced());
+    assert!(!pushed(Some(tip), None).synced());
+    assert!(!pushed(Some(tip), Some(git2::Oid::zero().into())).synced());
+    // Nothing to push is not the same as being in sync
+    assert!(!pushed(None, None).synced());
+}
+
+#[test]
+fn filter_events() {
+    let urn = Urn::new(git2::Oid::zero().into());
+    let other = Urn::new(
+        git2::Oid::hash_object(git2::ObjectType::Blob, b"other")
+            .unwrap()
+            .into(),
+    );
+    let peer = PeerId::from(SecretKey::from_seed([1; 32]));
+    let replicated = Event::ReplicationCompleted {
+        urn: urn.clone(),
+        provider: peer,
+    };
+    let joined = Event::PeerJoined { peer };
+    let lagged = Event::Lagged { skipped: 3 };
+
+    let everything = Filter::default();
+    assert!(everything.matches(&replicated));
+    assert!(everything.matches(&joined));
+
+    let by_urn = Filter {
+        urns: vec![urn.clone().with_path(reflike!("refs/heads/main"))],
+        ..Filter::default()
+    };
+    assert!(by_urn.matches(&replicated));
+    assert!(!by_urn.matches(&joined));
+    assert!(!by_urn.matches(&Event::Tracked { urn: other, peer }));
+
+    let by_peer = Filter {
+        peers: vec![PeerId::from(SecretKey::from_seed([2; 32]))],
+        ..Filter::default()
+    };
+    assert!(!by_peer.matches(&replicated));
+    assert!(!by_peer.matches(&joined));
+
+    let by_kind = Filter {
+        kinds: vec![Kind::PeerJoined].into_iter().collect(),
+        ..Filter::default()
+    };
+    assert!(!by_kind.matches(&replicated));
+    assert!(by_kind.matches(&joined));
+
+    // Lagging is always reported
+    for filter in &[by_urn, by_peer, by_kind] {
+        assert!(filter.matches(&lagged))
+    }
+}
+
+#[tokio::test]
+async fn client_roundtrip() -> anyhow::Result<()> {
+    let tmp = tempfile::tempdir()?;
+    let path = tmp.path().join("linkd.sock");
+    let listener = UnixListener::bind(&path)?;
+    let urn = Urn::new(git2::Oid::zero().into());
+    let peer = PeerId::from(SecretKey::from_seed([1; 32]));
+    let status = Status {
+        peer_id: peer,
+        connected_peers: vec![],
+        membership_active: 0,
+        membership_passive: 0,
+    };
+
+    let node = {
+        let status = status.clone();
+        let urn = urn.clone();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await?;
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            while let Some(line) = lines.next_line().await? {
+                match serde_json::from_str::<Request>(&line)? {
+                    Request::Status => {
+                        write_line(&mut writer, &Response::Status(status.clone())).await?
+                    },
+                    Request::Track { .. } => {
+                        write_line(
+                            &mut writer,
+                            &Response::Error {
+                                message: "no such urn".to_owned(),
+                            },
+                        )
+                        .await?
+                    },
+                    Request::Subscribe { .. } => {
+                        write_line(&mut writer, &Response::Subscribed).await?;
+                        write_line(
+                            &mut writer,
+                            &Event::ReplicationCompleted {
+                                urn: urn.clone(),
+                                provider: peer,
+                            },
+                        )
+                        .await?;
+                        break;
+                    },
+                    other => anyhow::bail!("unexpected request {:?}", other),
+                }
+            }
+            Ok::<_, anyhow::Error>(())
+        })
+    };
+
+    let mut client = Client::connect(&path).await?;
+    assert_eq!(client.status().await?, status);
+    assert_matches!(
+        client.track(urn.clone(), peer).await,
+        Err(rad_clib::control::Error::Node(msg)) if msg == "no such urn"
+    );
+    let mut events = client.subscribe(Filter::default()).await?;
+    assert_eq!(
+        events.next().await?,
+        Some(Event::ReplicationCompleted {
+            urn,
+            provider: peer
+        })
+    );
+    assert_eq!(events.next().await?, None);
+
+    node.await??;
+    Ok(())
+}
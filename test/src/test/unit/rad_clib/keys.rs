@@ -0,0 +1,41 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::PublicKey;
+use rad_clib::keys::{fingerprint, AgentKey, SshKey};
+
+// Generated by `ssh-keygen -t ed25519`, fingerprint as shown by `ssh-keygen
+// -l`.
+const KEY: [u8; 32] = [
+    0x45, 0xf8, 0xda, 0x34, 0x6f, 0xc4, 0x19, 0x73, 0xfd, 0x17, 0xd9, 0x36, 0x70, 0x7a, 0xa0, 0x18,
+    0x68, 0x23, 0xfd, 0xf2, 0x7d, 0xbf, 0x62, 0x9f, 0x87, 0x8d, 0x94, 0xdd, 0xe1, 0xd8, 0xfe, 0x53,
+];
+const FINGERPRINT: &str = "SHA256:ODVKsbsE/xy0l9E8QQaQKrBoS8NjSqMMwufvMfG7/Zw";
+
+#[test]
+fn openssh_fingerprint() {
+    let key = PublicKey::from_slice(&KEY).unwrap();
+    assert_eq!(FINGERPRINT, fingerprint(&key))
+}
+
+#[test]
+fn select_by_fingerprint_or_peer_id() {
+    let key = AgentKey::from(PublicKey::from_slice(&KEY).unwrap());
+    let other = AgentKey::from(librad::SecretKey::new().public());
+
+    let by_fingerprint = FINGERPRINT.parse::<SshKey>().unwrap();
+    assert!(by_fingerprint.matches(&key));
+    assert!(!by_fingerprint.matches(&other));
+
+    let by_peer_id = key.peer_id.to_string().parse::<SshKey>().unwrap();
+    assert_eq!(SshKey::PeerId(key.peer_id), by_peer_id);
+    assert!(by_peer_id.matches(&key));
+    assert!(!by_peer_id.matches(&other));
+}
+
+#[test]
+fn reject_garbage() {
+    assert!("ssh-ed25519 AAAA".parse::<SshKey>().is_err())
+}
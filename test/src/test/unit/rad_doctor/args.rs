@@ -0,0 +1,43 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use anyhow::Result;
+use structopt::StructOpt as _;
+
+use rad_doctor::cli::args::Args;
+
+#[test]
+fn defaults() -> Result<()> {
+    let parsed = Args::from_iter_safe(vec!["rad-doctor"])?;
+    assert_matches!(
+        parsed,
+        Args {
+            clock_skew: 300,
+            problems: false,
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn overrides() -> Result<()> {
+    let parsed = Args::from_iter_safe(vec!["rad-doctor", "--clock-skew", "60", "--problems"])?;
+    assert_matches!(
+        parsed,
+        Args {
+            clock_skew: 60,
+            problems: true,
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn invalid_clock_skew() {
+    assert!(Args::from_iter_safe(vec!["rad-doctor", "--clock-skew", "5m"]).is_err());
+    assert!(Args::from_iter_safe(vec!["rad-doctor", "--clock-skew", "-1"]).is_err());
+}
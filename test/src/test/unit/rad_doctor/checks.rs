@@ -0,0 +1,300 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::{
+    env,
+    fs,
+    os::unix::fs::PermissionsExt as _,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use rusty_fork::rusty_fork_test;
+use tokio::{net::UnixStream, runtime::Builder};
+
+use librad::{
+    git::{storage::Storage, types::Namespace},
+    profile::{Profile, ProfileId, RadHome, RAD_HOME},
+    PeerId,
+    SecretKey,
+};
+use rad_clib::keys::LIBRAD_KEY_FILE;
+use rad_doctor::checks::{self, Check, Options, Status};
+
+use crate::rad::{agent::Agent, identities::TestProject};
+
+const SKEW: Duration = Duration::from_secs(300);
+
+fn temp_profile(root: &tempfile::TempDir) -> Profile {
+    Profile::from_root(root.path(), Some(ProfileId::new())).unwrap()
+}
+
+fn agent_check(peer_id: &PeerId) -> Check {
+    Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(checks::agent::<UnixStream>(peer_id))
+}
+
+#[test]
+fn layout() {
+    let tmp = tempfile::tempdir().unwrap();
+    let profile = temp_profile(&tmp);
+    assert_eq!(checks::layout(&profile).status, Status::Ok);
+
+    fs::set_permissions(
+        profile.paths().git_includes_dir(),
+        fs::Permissions::from_mode(0o500),
+    )
+    .unwrap();
+    let check = checks::layout(&profile);
+    assert_eq!(check.status, Status::Fail);
+    assert!(check
+        .detail
+        .contains(&profile.paths().git_includes_dir().display().to_string()));
+
+    fs::set_permissions(
+        profile.paths().git_includes_dir(),
+        fs::Permissions::from_mode(0o700),
+    )
+    .unwrap();
+    fs::remove_dir(profile.paths().keys_dir()).unwrap();
+    let check = checks::layout(&profile);
+    assert_eq!(check.status, Status::Fail);
+    assert!(check
+        .detail
+        .contains(&profile.paths().keys_dir().display().to_string()));
+}
+
+#[test]
+fn key_file() {
+    let tmp = tempfile::tempdir().unwrap();
+    let profile = temp_profile(&tmp);
+    let path = profile.paths().keys_dir().join(LIBRAD_KEY_FILE);
+
+    let check = checks::key_file(&profile);
+    assert_eq!(check.status, Status::Warn);
+    assert!(check.detail.ends_with("does not exist"));
+
+    fs::write(&path, b"key").unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+    let check = checks::key_file(&profile);
+    assert_eq!(check.status, Status::Warn);
+    assert_eq!(check.fix, Some(format!("chmod 600 {}", path.display())));
+
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+    let check = checks::key_file(&profile);
+    assert_eq!(check.status, Status::Ok);
+    assert_eq!(check.fix, None);
+}
+
+#[test]
+fn config() {
+    let tmp = tempfile::tempdir().unwrap();
+    let profile = temp_profile(&tmp);
+    let storage = Storage::open(profile.paths(), SecretKey::new()).unwrap();
+    assert_eq!(checks::config(storage.read_only()).status, Status::Ok);
+
+    git2::Repository::open(storage.path())
+        .unwrap()
+        .config()
+        .unwrap()
+        .set_bool("core.logAllRefUpdates", false)
+        .unwrap();
+    let check = checks::config(storage.read_only());
+    assert_eq!(check.status, Status::Warn);
+    assert_eq!(
+        check.fix,
+        Some(format!(
+            "git --git-dir {} config core.logAllRefUpdates true",
+            storage.path().display()
+        ))
+    );
+}
+
+#[test]
+fn clock() {
+    let tmp = tempfile::tempdir().unwrap();
+    let profile = temp_profile(&tmp);
+    let storage = Storage::open(profile.paths(), SecretKey::new()).unwrap();
+    let TestProject { project, .. } = TestProject::create(&storage).unwrap();
+    let namespace = Namespace::from(&project.urn());
+
+    let check = checks::clock(storage.read_only(), SKEW);
+    assert_eq!(check.status, Status::Ok);
+    assert!(check
+        .detail
+        .ends_with("signed refs are not from the future"));
+
+    // Signed refs of another peer, dated an hour ahead
+    let repo = git2::Repository::open(storage.path()).unwrap();
+    let ahead = {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        let time = git2::Time::new(now.as_secs() as i64 + 3600, 0);
+        let author = git2::Signature::new("bob", "bob@example.com", &time).unwrap();
+        let tree = repo
+            .find_tree(repo.treebuilder(None).unwrap().write().unwrap())
+            .unwrap();
+        repo.commit(None, &author, &author, "from the future", &tree, &[])
+            .unwrap()
+    };
+    repo.reference(
+        &format!(
+            "refs/namespaces/{}/refs/remotes/{}/rad/signed_refs",
+            namespace,
+            PeerId::from(SecretKey::new())
+        ),
+        ahead,
+        false,
+        "from the future",
+    )
+    .unwrap();
+    let check = checks::clock(storage.read_only(), SKEW);
+    assert_eq!(check.status, Status::Warn);
+    assert!(check.detail.starts_with("1 signed refs of other peers"));
+
+    // A generous skew tolerates it
+    let check = checks::clock(storage.read_only(), Duration::from_secs(2 * 3600));
+    assert_eq!(check.status, Status::Ok);
+
+    // Our own signed refs from the future take precedence
+    repo.reference(
+        &format!("refs/namespaces/{}/refs/rad/signed_refs", namespace),
+        ahead,
+        true,
+        "from the future",
+    )
+    .unwrap();
+    let check = checks::clock(storage.read_only(), SKEW);
+    assert_eq!(check.status, Status::Warn);
+    assert!(check.detail.starts_with("1 of your signed refs"));
+}
+
+// N.B. we fork these tests into subprocesses since they modify environment
+// variables, which would affect the other tests running.
+rusty_fork_test! {
+#[test]
+fn profile() {
+    let tmp = tempfile::tempdir().unwrap();
+    env::set_var(RAD_HOME, tmp.path());
+    env::remove_var("RAD_PROFILE");
+
+    let check = checks::profile().unwrap_err();
+    assert_eq!(check.status, Status::Fail);
+    assert!(check.detail.starts_with("no such profile in"));
+
+    let home = RadHome::Root(tmp.path().to_path_buf());
+    let active = Profile::new(&home).unwrap();
+    Profile::set(&home, active.id().clone()).unwrap();
+    let (found, check) = checks::profile().unwrap();
+    assert_eq!(found.id(), active.id());
+    assert_eq!(check.status, Status::Ok);
+
+    // RAD_PROFILE takes precedence over the active profile
+    let other = Profile::new(&home).unwrap();
+    env::set_var("RAD_PROFILE", other.id().to_string());
+    let (found, _) = checks::profile().unwrap();
+    assert_eq!(found.id(), other.id());
+
+    env::set_var("RAD_PROFILE", ProfileId::new().to_string());
+    assert_eq!(checks::profile().unwrap_err().status, Status::Fail);
+
+    env::set_var("RAD_PROFILE", "../escape");
+    let check = checks::profile().unwrap_err();
+    assert_eq!(check.status, Status::Fail);
+    assert_eq!(check.fix.as_deref(), Some("check RAD_HOME and RAD_PROFILE"));
+}
+
+#[test]
+fn agent() {
+    let peer_id = PeerId::from(SecretKey::new());
+
+    env::remove_var("SSH_AUTH_SOCK");
+    let check = agent_check(&peer_id);
+    assert_eq!(check.status, Status::Warn);
+    assert_eq!(check.detail, "SSH_AUTH_SOCK is not set");
+
+    let tmp = tempfile::tempdir().unwrap();
+    env::set_var("SSH_AUTH_SOCK", tmp.path().join("missing.sock"));
+    assert_eq!(agent_check(&peer_id).status, Status::Fail);
+
+    let agent = Agent::spawn().unwrap();
+    env::set_var("SSH_AUTH_SOCK", agent.socket());
+    let check = agent_check(&peer_id);
+    assert_eq!(check.status, Status::Warn);
+    assert_eq!(check.detail, format!("key of peer {} is not loaded", peer_id));
+
+    agent.add(*peer_id.as_public_key());
+    let check = agent_check(&peer_id);
+    assert_eq!(check.status, Status::Ok);
+    assert_eq!(check.detail, format!("key of peer {} is loaded", peer_id));
+}
+
+#[test]
+fn git() {
+    let tmp = tempfile::tempdir().unwrap();
+    let helper = tmp.path().join("git-remote-rad");
+    fs::write(&helper, b"").unwrap();
+
+    let path = env::var_os("PATH").unwrap_or_default();
+    let path = env::join_paths(
+        Some(tmp.path().to_path_buf())
+            .into_iter()
+            .chain(env::split_paths(&path)),
+    )
+    .unwrap();
+    env::set_var("PATH", path);
+    let checks = checks::git();
+    assert_eq!(checks[1].name, "git-remote-rad");
+    assert_eq!(checks[1].status, Status::Ok);
+    assert_eq!(checks[1].detail, helper.display().to_string());
+
+    let empty = tempfile::tempdir().unwrap();
+    env::set_var("PATH", empty.path());
+    let checks = checks::git();
+    assert_eq!(checks[0].name, "git");
+    assert_eq!(checks[0].status, Status::Fail);
+    assert_eq!(checks[1].status, Status::Warn);
+}
+
+#[test]
+fn run() {
+    let tmp = tempfile::tempdir().unwrap();
+    env::set_var(RAD_HOME, tmp.path());
+    env::remove_var("RAD_PROFILE");
+    env::remove_var("SSH_AUTH_SOCK");
+
+    // Without a profile, only the checks independent of it are run
+    let rt = Builder::new_current_thread().enable_all().build().unwrap();
+    let names = |checks: Vec<Check>| checks.iter().map(|c| c.name).collect::<Vec<_>>();
+    assert_eq!(
+        names(rt.block_on(checks::run::<UnixStream>(Options { clock_skew: SKEW }))),
+        vec!["profile", "git", "git-remote-rad"]
+    );
+
+    // Without a key, neither in a file nor in the agent
+    let home = RadHome::Root(tmp.path().to_path_buf());
+    let profile = Profile::new(&home).unwrap();
+    Profile::set(&home, profile.id().clone()).unwrap();
+    Storage::open(profile.paths(), SecretKey::new()).unwrap();
+    let checks = rt.block_on(checks::run::<UnixStream>(Options { clock_skew: SKEW }));
+    assert_eq!(
+        names(checks.clone()),
+        vec![
+            "profile",
+            "layout",
+            "storage",
+            "keys",
+            "key file",
+            "ssh-agent",
+            "storage config",
+            "clock",
+            "git",
+            "git-remote-rad"
+        ]
+    );
+    assert_eq!(checks[3].status, Status::Fail);
+}
+}
@@ -0,0 +1,68 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use rad_doctor::{
+    checks::{Check, Status},
+    cli::main::{outcome, render},
+};
+
+fn check(name: &'static str, status: Status) -> Check {
+    Check {
+        name,
+        status,
+        detail: format!("{} detail", name),
+        fix: (status != Status::Ok).then(|| format!("fix {}", name)),
+    }
+}
+
+#[test]
+fn renders_all_checks() {
+    let checks = vec![
+        check("profile", Status::Ok),
+        check("ssh-agent", Status::Warn),
+        check("git", Status::Fail),
+    ];
+    assert_eq!(
+        render(&checks, false),
+        "[  ok] profile: profile detail\n\
+         [warn] ssh-agent: ssh-agent detail\n       \
+         fix: fix ssh-agent\n\
+         [FAIL] git: git detail\n       \
+         fix: fix git\n"
+    );
+}
+
+#[test]
+fn renders_only_problems() {
+    let checks = vec![
+        check("profile", Status::Ok),
+        check("ssh-agent", Status::Warn),
+        check("git", Status::Fail),
+    ];
+    let out = render(&checks, true);
+    assert!(!out.contains("profile"));
+    assert!(out.contains("[warn] ssh-agent"));
+    assert!(out.contains("[FAIL] git"));
+
+    assert_eq!(render(&[check("profile", Status::Ok)], true), "");
+}
+
+#[test]
+fn fails_only_on_failed_checks() {
+    assert!(outcome(&[]).is_ok());
+    assert!(outcome(&[
+        check("profile", Status::Ok),
+        check("ssh-agent", Status::Warn)
+    ])
+    .is_ok());
+
+    let err = outcome(&[
+        check("profile", Status::Ok),
+        check("ssh-agent", Status::Fail),
+        check("git", Status::Fail),
+    ])
+    .unwrap_err();
+    assert_eq!(err.to_string(), "2 of 3 checks failed");
+}
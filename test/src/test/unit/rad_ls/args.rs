@@ -0,0 +1,70 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use anyhow::Result;
+use structopt::StructOpt as _;
+
+use librad::{PeerId, SecretKey};
+use rad_clib::ser::Format;
+use rad_ls::{cli::args::Args, Kind};
+
+#[test]
+fn defaults() -> Result<()> {
+    let parsed = Args::from_iter_safe(vec!["rad-ls"])?;
+    assert_matches!(
+        parsed,
+        Args {
+            kind: None,
+            tracked_by: None,
+            verified_only: false,
+            format: None,
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn filters() -> Result<()> {
+    let peer = PeerId::from(SecretKey::new());
+    let parsed = Args::from_iter_safe(vec![
+        "rad-ls",
+        "--kind",
+        "project",
+        "--tracked-by",
+        &peer.to_string(),
+        "--verified-only",
+    ])?;
+    assert_matches!(
+        parsed,
+        Args {
+            kind: Some(Kind::Project),
+            tracked_by: Some(tracked_by),
+            verified_only: true,
+            ..
+        } if tracked_by == peer
+    );
+
+    let parsed = Args::from_iter_safe(vec!["rad-ls", "--kind", "person"])?;
+    assert_eq!(parsed.kind, Some(Kind::Person));
+
+    Ok(())
+}
+
+#[test]
+fn unknown_kind() {
+    assert!(Args::from_iter_safe(vec!["rad-ls", "--kind", "org"]).is_err())
+}
+
+#[test]
+fn formats() -> Result<()> {
+    let parsed = Args::from_iter_safe(vec!["rad-ls", "--format", "json"])?;
+    assert_matches!(parsed.format, Some(Format::Json));
+    let parsed = Args::from_iter_safe(vec!["rad-ls", "--format", "cbor"])?;
+    assert_matches!(parsed.format, Some(Format::Cbor));
+    assert!(Args::from_iter_safe(vec!["rad-ls", "--format", "yaml"]).is_err());
+
+    Ok(())
+}
@@ -0,0 +1,224 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::{
+    git::{storage::Storage, tracking},
+    paths::Paths,
+    PeerId,
+    SecretKey,
+};
+use rad_clib::ser::Format;
+use rad_ls::{cli::main::render, ls, Entry, Filter, Kind};
+use serde_json::json;
+
+use crate::rad::identities::TestProject;
+
+fn find(entries: &[Entry], kind: Kind) -> &Entry {
+    entries
+        .iter()
+        .find(|entry| entry.kind == kind)
+        .unwrap_or_else(|| panic!("no {} listed", kind))
+}
+
+#[test]
+fn lists_all() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        let TestProject { owner, project } = TestProject::create(&storage).unwrap();
+
+        let entries = ls(storage.read_only(), &Filter::default()).unwrap();
+        assert_eq!(entries.len(), 2);
+
+        let person = find(&entries, Kind::Person);
+        assert_eq!(person.urn, owner.urn());
+        assert_eq!(person.name, "alice");
+        assert_eq!(person.head, owner.content_id);
+        assert_eq!(person.default_branch, None);
+        assert!(person.verified);
+
+        let proj = find(&entries, Kind::Project);
+        assert_eq!(proj.urn, project.urn());
+        assert_eq!(proj.name, "radicle-link");
+        assert_eq!(proj.head, project.content_id);
+        assert_eq!(proj.default_branch.as_deref(), Some("next"));
+        assert!(proj.verified);
+    }
+}
+
+#[test]
+fn filters_by_kind() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        let TestProject { owner, project } = TestProject::create(&storage).unwrap();
+
+        let entries = ls(
+            storage.read_only(),
+            &Filter {
+                kind: Some(Kind::Project),
+                ..Filter::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            entries.iter().map(|e| e.urn.clone()).collect::<Vec<_>>(),
+            vec![project.urn()]
+        );
+
+        let entries = ls(
+            storage.read_only(),
+            &Filter {
+                kind: Some(Kind::Person),
+                ..Filter::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            entries.iter().map(|e| e.urn.clone()).collect::<Vec<_>>(),
+            vec![owner.urn()]
+        );
+    }
+}
+
+#[test]
+fn filters_by_tracked_peer() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        let TestProject { project, .. } = TestProject::create(&storage).unwrap();
+        let tracked = PeerId::from(SecretKey::new());
+        tracking::track(&storage, &project.urn(), tracked).unwrap();
+
+        let entries = ls(
+            storage.read_only(),
+            &Filter {
+                tracked_by: Some(tracked),
+                ..Filter::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            entries.iter().map(|e| e.urn.clone()).collect::<Vec<_>>(),
+            vec![project.urn()]
+        );
+
+        let entries = ls(
+            storage.read_only(),
+            &Filter {
+                tracked_by: Some(PeerId::from(SecretKey::new())),
+                ..Filter::default()
+            },
+        )
+        .unwrap();
+        assert!(entries.is_empty());
+    }
+}
+
+#[test]
+fn verified_only() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        TestProject::create(&storage).unwrap();
+
+        let entries = ls(
+            storage.read_only(),
+            &Filter {
+                verified_only: true,
+                ..Filter::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(entries.len(), 2);
+    }
+}
+
+#[test]
+fn renders_listing() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        let TestProject { project, .. } = TestProject::create(&storage).unwrap();
+        let mut entries = ls(
+            storage.read_only(),
+            &Filter {
+                kind: Some(Kind::Project),
+                ..Filter::default()
+            },
+        )
+        .unwrap();
+
+        let out = String::from_utf8(render(entries.clone(), None).unwrap()).unwrap();
+        assert_eq!(
+            out,
+            format!(
+                "{}\tproject\tradicle-link\t{}\n",
+                project.urn(),
+                project.content_id
+            )
+        );
+
+        entries[0].verified = false;
+        let out = String::from_utf8(render(entries, None).unwrap()).unwrap();
+        assert!(out.ends_with("\t(unverified)\n"));
+
+        let out = render(vec![], None).unwrap();
+        assert!(out.is_empty());
+    }
+}
+
+#[test]
+fn renders_json() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        let TestProject { project, .. } = TestProject::create(&storage).unwrap();
+        let entries = ls(
+            storage.read_only(),
+            &Filter {
+                kind: Some(Kind::Project),
+                ..Filter::default()
+            },
+        )
+        .unwrap();
+
+        let out = render(entries, Some(Format::Json)).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(
+            json,
+            json!([{
+                "urn": project.urn().to_string(),
+                "kind": "project",
+                "name": "radicle-link",
+                "head": project.content_id.to_string(),
+                "defaultBranch": "next",
+                "verified": true,
+            }])
+        );
+    }
+}
+
+#[test]
+fn renders_cbor() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        TestProject::create(&storage).unwrap();
+        let entries = ls(storage.read_only(), &Filter::default()).unwrap();
+
+        let out = render(entries, Some(Format::Cbor)).unwrap();
+        let mut decoder = minicbor::Decoder::new(&out);
+        assert_eq!(decoder.array().unwrap(), Some(2));
+        assert_matches!(decoder.map(), Ok(Some(_)));
+    }
+}
@@ -0,0 +1,135 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::env;
+
+use rusty_fork::rusty_fork_test;
+use tokio::{
+    net::UnixStream,
+    runtime::{Builder, Runtime},
+};
+
+use librad::{
+    crypto::keystore::{
+        crypto::{Pwhash, KDF_PARAMS_TEST},
+        pinentry::SecUtf8,
+    },
+    profile::{ProfileId, RAD_HOME},
+};
+use rad_profile::{cli::args::SshAdd, create, ssh_add, ssh_list, ssh_remove};
+
+use crate::rad::agent::{Agent, Key};
+
+fn crypto() -> Pwhash<SecUtf8> {
+    Pwhash::new(SecUtf8::from("asdf"), *KDF_PARAMS_TEST)
+}
+
+fn runtime() -> Runtime {
+    Builder::new_current_thread().enable_all().build().unwrap()
+}
+
+/// Set up a profile under a temporary RAD_HOME, and an agent for it to use.
+fn setup() -> (tempfile::TempDir, Agent) {
+    let tmp = tempfile::tempdir().unwrap();
+    env::set_var(RAD_HOME, tmp.path());
+    let agent = Agent::spawn().unwrap();
+    env::set_var("SSH_AUTH_SOCK", agent.socket());
+    (tmp, agent)
+}
+
+// N.B. we fork these tests into subprocesses since they set RAD_HOME and
+// SSH_AUTH_SOCK, which would affect the other tests running.
+rusty_fork_test! {
+#[test]
+fn ssh_add_confirms_by_default() {
+    let (_tmp, agent) = setup();
+    let (profile, peer_id) = create(crypto()).unwrap();
+
+    let args = SshAdd { id: None, time: None, confirm: false };
+    let (id, added) = runtime()
+        .block_on(ssh_add::<UnixStream, _, _>(args.id.clone(), crypto(), &args.constraints()))
+        .unwrap();
+    assert_eq!(&id, profile.id());
+    assert_eq!(added, peer_id);
+    assert_eq!(
+        agent.keys(),
+        vec![Key {
+            public: *peer_id.as_public_key(),
+            lifetime: None,
+            confirm: true,
+        }]
+    );
+}
+
+#[test]
+fn ssh_add_with_lifetime() {
+    let (_tmp, agent) = setup();
+    let (profile, peer_id) = create(crypto()).unwrap();
+
+    let args = SshAdd { id: Some(profile.id().clone()), time: Some(60), confirm: false };
+    runtime()
+        .block_on(ssh_add::<UnixStream, _, _>(args.id.clone(), crypto(), &args.constraints()))
+        .unwrap();
+    assert_eq!(
+        agent.keys(),
+        vec![Key {
+            public: *peer_id.as_public_key(),
+            lifetime: Some(60),
+            confirm: false,
+        }]
+    );
+
+    let args = SshAdd { id: None, time: Some(60), confirm: true };
+    runtime()
+        .block_on(ssh_add::<UnixStream, _, _>(args.id.clone(), crypto(), &args.constraints()))
+        .unwrap();
+    assert_eq!(
+        agent.keys(),
+        vec![Key {
+            public: *peer_id.as_public_key(),
+            lifetime: Some(60),
+            confirm: true,
+        }]
+    );
+}
+
+#[test]
+fn ssh_rm() {
+    let (_tmp, agent) = setup();
+    let (_, other) = create(crypto()).unwrap();
+    let (profile, peer_id) = create(crypto()).unwrap();
+    agent.add(*other.as_public_key());
+    agent.add(*peer_id.as_public_key());
+
+    // Removing the key requires neither the passphrase, nor the key to be
+    // loaded by us
+    let (id, removed) = runtime()
+        .block_on(ssh_remove::<UnixStream, _>(None::<ProfileId>))
+        .unwrap();
+    assert_eq!(&id, profile.id());
+    assert_eq!(removed, peer_id);
+    assert_eq!(agent.keys(), vec![Key::from(*other.as_public_key())]);
+}
+
+#[test]
+fn ssh_ls() {
+    let (_tmp, agent) = setup();
+    let (unloaded, _) = create(crypto()).unwrap();
+    let (loaded, peer_id) = create(crypto()).unwrap();
+    agent.add(*peer_id.as_public_key());
+
+    let statuses = runtime().block_on(ssh_list::<UnixStream>()).unwrap();
+    assert_eq!(statuses.len(), 2);
+    for status in statuses {
+        if &status.profile == loaded.id() {
+            assert_eq!(status.peer_id, peer_id);
+            assert!(status.loaded);
+        } else {
+            assert_eq!(&status.profile, unloaded.id());
+            assert!(!status.loaded);
+        }
+    }
+}
+}
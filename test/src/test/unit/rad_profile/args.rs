@@ -0,0 +1,134 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::path::PathBuf;
+
+use anyhow::Result;
+use structopt::StructOpt as _;
+use thrussh_agent::Constraint;
+
+use librad::profile::ProfileId;
+use rad_profile::cli::args::{Args, Command, ExportKey, ImportKey, SshAdd, SshLs, SshRm};
+
+#[test]
+fn export_key() -> Result<()> {
+    let parsed = Args::from_iter_safe(vec!["rad-profile", "export-key", "--output", "peer.key"])?;
+    assert_matches!(
+        parsed.command,
+        Command::ExportKey(ExportKey { id: None, output }) if output == PathBuf::from("peer.key")
+    );
+
+    let id = ProfileId::new();
+    let parsed = Args::from_iter_safe(vec![
+        "rad-profile",
+        "export-key",
+        "--id",
+        &id.to_string(),
+        "--output",
+        "peer.key",
+    ])?;
+    assert_matches!(
+        parsed.command,
+        Command::ExportKey(ExportKey { id: Some(given), .. }) if given == id
+    );
+
+    Ok(())
+}
+
+#[test]
+fn export_key_requires_output() {
+    assert!(Args::from_iter_safe(vec!["rad-profile", "export-key"]).is_err())
+}
+
+#[test]
+fn import_key() -> Result<()> {
+    let parsed = Args::from_iter_safe(vec!["rad-profile", "import-key", "--input", "peer.key"])?;
+    assert_matches!(
+        parsed.command,
+        Command::ImportKey(ImportKey { input }) if input == PathBuf::from("peer.key")
+    );
+
+    Ok(())
+}
+
+#[test]
+fn import_key_requires_input() {
+    assert!(Args::from_iter_safe(vec!["rad-profile", "import-key"]).is_err())
+}
+
+#[test]
+fn ssh_add() -> Result<()> {
+    let parsed = Args::from_iter_safe(vec!["rad-profile", "ssh-add"])?;
+    assert_matches!(
+        parsed.command,
+        Command::SshAdd(SshAdd {
+            id: None,
+            time: None,
+            confirm: false
+        })
+    );
+
+    let parsed =
+        Args::from_iter_safe(vec!["rad-profile", "add-to-agent", "-t", "60", "--confirm"])?;
+    assert_matches!(
+        parsed.command,
+        Command::SshAdd(SshAdd {
+            id: None,
+            time: Some(60),
+            confirm: true
+        })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn ssh_add_constraints() {
+    let add = |time, confirm| SshAdd {
+        id: None,
+        time,
+        confirm,
+    };
+
+    assert_matches!(add(None, false).constraints()[..], [Constraint::Confirm]);
+    assert_matches!(add(None, true).constraints()[..], [Constraint::Confirm]);
+    assert_matches!(
+        add(Some(60), false).constraints()[..],
+        [Constraint::KeyLifetime { seconds: 60 }]
+    );
+    assert_matches!(
+        add(Some(60), true).constraints()[..],
+        [Constraint::KeyLifetime { seconds: 60 }, Constraint::Confirm]
+    );
+}
+
+#[test]
+fn ssh_rm() -> Result<()> {
+    let parsed = Args::from_iter_safe(vec!["rad-profile", "ssh-rm"])?;
+    assert_matches!(parsed.command, Command::SshRm(SshRm { id: None }));
+
+    let id = ProfileId::new();
+    let parsed = Args::from_iter_safe(vec![
+        "rad-profile",
+        "rm-from-agent",
+        "--id",
+        &id.to_string(),
+    ])?;
+    assert_matches!(
+        parsed.command,
+        Command::SshRm(SshRm { id: Some(given) }) if given == id
+    );
+
+    Ok(())
+}
+
+#[test]
+fn ssh_ls() -> Result<()> {
+    let parsed = Args::from_iter_safe(vec!["rad-profile", "ssh-ls"])?;
+    assert_matches!(parsed.command, Command::SshLs(SshLs {}));
+    assert!(Args::from_iter_safe(vec!["rad-profile", "ssh-ls", "--id", "abc"]).is_err());
+
+    Ok(())
+}
@@ -0,0 +1,100 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use std::env;
+
+use rusty_fork::rusty_fork_test;
+
+use librad::{
+    crypto::{
+        keystore::{
+            crypto::{Pwhash, KDF_PARAMS_TEST},
+            pinentry::SecUtf8,
+        },
+        SecureBytes,
+    },
+    profile::{ProfileId, RAD_HOME},
+};
+use rad_profile::{create, export_key, get, import_key, peer_id, Error};
+
+fn crypto(passphrase: &str) -> Pwhash<SecUtf8> {
+    Pwhash::new(SecUtf8::from(passphrase), *KDF_PARAMS_TEST)
+}
+
+// N.B. we fork these tests into subprocesses since they set RAD_HOME, which
+// would affect the other tests running.
+rusty_fork_test! {
+#[test]
+fn export_import_roundtrip() {
+    let tmp = tempfile::tempdir().unwrap();
+    env::set_var(RAD_HOME, tmp.path());
+
+    let (created, created_peer) = create(crypto("asdf")).unwrap();
+    let (id, exported_peer, key) = export_key(created.id().clone(), crypto("asdf")).unwrap();
+    assert_eq!(&id, created.id());
+    assert_eq!(exported_peer, created_peer);
+
+    // The imported key may be protected by a different passphrase
+    let (imported, imported_peer) = import_key(crypto("qwer"), &key).unwrap();
+    assert_ne!(imported.id(), created.id());
+    assert_eq!(imported_peer, created_peer);
+
+    // The imported profile is the active one, and has its storage set up
+    assert_eq!(get(None).unwrap().map(|p| p.id().clone()), Some(imported.id().clone()));
+    assert_eq!(peer_id(None::<ProfileId>).unwrap(), created_peer);
+    let (_, reexported_peer, _) = export_key(None::<ProfileId>, crypto("qwer")).unwrap();
+    assert_eq!(reexported_peer, created_peer);
+}
+
+#[test]
+fn export_key_of_active_profile() {
+    let tmp = tempfile::tempdir().unwrap();
+    env::set_var(RAD_HOME, tmp.path());
+
+    let (created, created_peer) = create(crypto("asdf")).unwrap();
+    let (id, exported_peer, _) = export_key(None::<ProfileId>, crypto("asdf")).unwrap();
+    assert_eq!(&id, created.id());
+    assert_eq!(exported_peer, created_peer);
+}
+
+#[test]
+fn export_key_wrong_passphrase() {
+    let tmp = tempfile::tempdir().unwrap();
+    env::set_var(RAD_HOME, tmp.path());
+
+    create(crypto("asdf")).unwrap();
+    assert_matches!(
+        export_key(None::<ProfileId>, crypto("qwer")),
+        Err(Error::Keystore(_))
+    );
+}
+
+#[test]
+fn export_key_without_profile() {
+    let tmp = tempfile::tempdir().unwrap();
+    env::set_var(RAD_HOME, tmp.path());
+
+    assert_matches!(
+        export_key(None::<ProfileId>, crypto("asdf")),
+        Err(Error::NoActiveProfile)
+    );
+    assert_matches!(
+        export_key(ProfileId::new(), crypto("asdf")),
+        Err(Error::NoProfile(_))
+    );
+}
+
+#[test]
+fn import_key_rejects_malformed_key() {
+    let tmp = tempfile::tempdir().unwrap();
+    env::set_var(RAD_HOME, tmp.path());
+
+    assert_matches!(
+        import_key(crypto("asdf"), &SecureBytes::new(vec![0; 3])),
+        Err(Error::SecretKey(_))
+    );
+    assert_matches!(get(None), Ok(None));
+}
+}
@@ -0,0 +1,59 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use anyhow::Result;
+use structopt::StructOpt as _;
+
+use librad::{git::Urn, PeerId, SecretKey};
+use rad_storage::cli::args::{Args, Command, Inspect};
+
+#[test]
+fn inspect() -> Result<()> {
+    let urn = Urn::new(git2::Oid::zero().into());
+    let parsed = Args::from_iter_safe(vec!["rad-storage", "inspect", &urn.to_string()])?;
+    assert_matches!(
+        parsed.command,
+        Command::Inspect(Inspect {
+            urn: given,
+            peer: None,
+            refs: false,
+            sigrefs: false,
+            identity: false,
+            json: false,
+        }) if given == urn
+    );
+
+    let peer = PeerId::from(SecretKey::new());
+    let parsed = Args::from_iter_safe(vec![
+        "rad-storage",
+        "inspect",
+        &urn.to_string(),
+        "--peer",
+        &peer.to_string(),
+        "--refs",
+        "--sigrefs",
+        "--identity",
+        "--json",
+    ])?;
+    assert_matches!(
+        parsed.command,
+        Command::Inspect(Inspect {
+            peer: Some(given),
+            refs: true,
+            sigrefs: true,
+            identity: true,
+            json: true,
+            ..
+        }) if given == peer
+    );
+
+    Ok(())
+}
+
+#[test]
+fn inspect_requires_urn() {
+    assert!(Args::from_iter_safe(vec!["rad-storage", "inspect"]).is_err());
+    assert!(Args::from_iter_safe(vec!["rad-storage", "inspect", "--refs"]).is_err());
+}
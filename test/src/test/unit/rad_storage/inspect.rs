@@ -0,0 +1,208 @@
+// Copyright © 2021 The Radicle Link Contributors
+//
+// This file is part of radicle-link, distributed under the GPLv3 with Radicle
+// Linking Exception. For full terms see the included LICENSE file.
+
+use librad::{
+    git::{storage::Storage, types::Namespace, util::quick_commit, Urn},
+    git_ext::{tree, Oid},
+    paths::Paths,
+    reflike,
+    PeerId,
+    SecretKey,
+};
+use rad_storage::inspect::{inspect, Inspection, Options, Target};
+use serde_json::json;
+
+use crate::rad::identities::TestProject;
+
+const ALL: Options = Options {
+    peer: None,
+    refs: true,
+    sigrefs: true,
+    identity: true,
+};
+
+/// Create a project with a `next` branch, returning the commit it points to.
+fn setup(storage: &Storage) -> (Urn, git2::Oid) {
+    let TestProject { project, .. } = TestProject::create(storage).unwrap();
+    let urn = project.urn();
+    let commit = quick_commit(
+        storage,
+        &urn.clone().with_path(reflike!("refs/heads/next")),
+        vec![("README", tree::blob(b"Hello, World"))]
+            .into_iter()
+            .collect(),
+        "initial commit",
+    )
+    .unwrap();
+    (urn, commit)
+}
+
+#[test]
+fn inspects_everything() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        let (urn, commit) = setup(&storage);
+        let project = librad::git::identities::project::get(&storage, &urn)
+            .unwrap()
+            .unwrap();
+
+        let Inspection {
+            urn: inspected,
+            refs,
+            sigrefs,
+            identity,
+        } = inspect(storage.read_only(), &urn, ALL).unwrap();
+        assert_eq!(inspected, urn);
+
+        let refs = refs.unwrap();
+        assert_matches!(
+            refs.get("refs/heads/next"),
+            Some(Target::Direct(oid)) if *oid == Oid::from(commit)
+        );
+        assert_matches!(
+            refs.get("refs/rad/id"),
+            Some(Target::Direct(oid)) if *oid == project.content_id
+        );
+
+        let sigrefs = sigrefs.unwrap();
+        assert_eq!(sigrefs.len(), 1);
+        assert_eq!(&sigrefs[0].peer, storage.peer_id());
+        assert!(sigrefs[0].local);
+        assert!(sigrefs[0].signed > 0);
+        assert!(sigrefs[0].is_fresh());
+
+        let identity = identity.unwrap();
+        assert_eq!(identity.len(), 1);
+        assert_eq!(identity[0].commit, project.content_id);
+        assert_eq!(identity[0].revision, project.revision);
+        assert_eq!(identity[0].kind, "project");
+        assert_eq!(identity[0].name, "radicle-link");
+        assert_eq!(identity[0].signers, vec![*storage.peer_id()]);
+    }
+}
+
+#[test]
+fn inspects_only_what_is_asked_for() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        let (urn, _) = setup(&storage);
+
+        let inspection = inspect(
+            storage.read_only(),
+            &urn,
+            Options {
+                sigrefs: false,
+                identity: false,
+                ..ALL
+            },
+        )
+        .unwrap();
+        assert!(inspection.refs.is_some());
+        assert!(inspection.sigrefs.is_none());
+        assert!(inspection.identity.is_none());
+
+        let json = serde_json::to_value(&inspection).unwrap();
+        assert_eq!(json["urn"], json!(urn.to_string()));
+        assert!(json.get("refs").is_some());
+        assert!(json.get("sigrefs").is_none());
+        assert!(json.get("identity").is_none());
+
+        let text = inspection.to_string();
+        assert!(text.starts_with(&format!("{}\n", urn)));
+        assert!(text.contains("\nrefs:\n"));
+        assert!(!text.contains("\nsigrefs:\n"));
+        assert!(!text.contains("\nidentity:\n"));
+    }
+}
+
+#[test]
+fn reports_stale_sigrefs() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        let (urn, signed) = setup(&storage);
+
+        // Move `next` without updating the signed refs
+        let repo = git2::Repository::open(storage.path()).unwrap();
+        let next = format!("refs/namespaces/{}/refs/heads/next", Namespace::from(&urn));
+        let unsigned = {
+            let parent = repo.find_commit(signed).unwrap();
+            let author = git2::Signature::now("alice", "alice@example.com").unwrap();
+            repo.commit(
+                None,
+                &author,
+                &author,
+                "unsigned",
+                &parent.tree().unwrap(),
+                &[&parent],
+            )
+            .unwrap()
+        };
+        repo.reference(&next, unsigned, true, "unsigned").unwrap();
+
+        let inspection = inspect(storage.read_only(), &urn, ALL).unwrap();
+        let sigrefs = inspection.sigrefs.as_ref().unwrap();
+        assert!(!sigrefs[0].is_fresh());
+        assert_eq!(sigrefs[0].stale.len(), 1);
+        assert_eq!(sigrefs[0].stale[0].name, "refs/heads/next");
+        assert_eq!(sigrefs[0].stale[0].signed, Oid::from(signed));
+        assert_eq!(sigrefs[0].stale[0].actual, Some(Oid::from(unsigned)));
+        assert!(inspection.to_string().contains(&format!(
+            "    refs/heads/next: signed {}, actual {}\n",
+            signed, unsigned
+        )));
+
+        // A missing ref is stale, too
+        repo.find_reference(&next).unwrap().delete().unwrap();
+        let inspection = inspect(storage.read_only(), &urn, ALL).unwrap();
+        let sigrefs = inspection.sigrefs.as_ref().unwrap();
+        assert_eq!(sigrefs[0].stale[0].actual, None);
+        assert!(inspection.to_string().contains(&format!(
+            "    refs/heads/next: signed {}, missing\n",
+            signed
+        )));
+    }
+}
+
+#[test]
+fn filters_by_peer() {
+    let tmp = tempfile::tempdir().unwrap();
+    {
+        let paths = Paths::from_root(&tmp).unwrap();
+        let storage = Storage::open(&paths, SecretKey::new()).unwrap();
+        let (urn, _) = setup(&storage);
+
+        let inspection = inspect(
+            storage.read_only(),
+            &urn,
+            Options {
+                peer: Some(*storage.peer_id()),
+                ..ALL
+            },
+        )
+        .unwrap();
+        assert!(!inspection.refs.unwrap().is_empty());
+        assert_eq!(inspection.sigrefs.unwrap().len(), 1);
+        assert_eq!(inspection.identity.unwrap().len(), 1);
+
+        let inspection = inspect(
+            storage.read_only(),
+            &urn,
+            Options {
+                peer: Some(PeerId::from(SecretKey::new())),
+                ..ALL
+            },
+        )
+        .unwrap();
+        assert!(inspection.refs.unwrap().is_empty());
+        assert!(inspection.sigrefs.unwrap().is_empty());
+        assert!(inspection.identity.unwrap().is_empty());
+    }
+}